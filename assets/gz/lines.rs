@@ -0,0 +1 @@
+pub const LINES_GZ: &[u8] = include_bytes!("lines.gz");
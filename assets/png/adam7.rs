@@ -0,0 +1,8 @@
+// Adam7 interlaced grayscale images with empty passes and their non-interlaced twins,
+// written with filter type 0 by a small script
+pub const ADAM7_G02_5X3_PNG: &[u8] = include_bytes!("adam7_g02_5x3.png");
+pub const ADAM7_G02_5X3_REF_PNG: &[u8] = include_bytes!("adam7_g02_5x3_ref.png");
+pub const ADAM7_G04_1X1_PNG: &[u8] = include_bytes!("adam7_g04_1x1.png");
+pub const ADAM7_G04_1X1_REF_PNG: &[u8] = include_bytes!("adam7_g04_1x1_ref.png");
+pub const ADAM7_G04_13X11_PNG: &[u8] = include_bytes!("adam7_g04_13x11.png");
+pub const ADAM7_G04_13X11_REF_PNG: &[u8] = include_bytes!("adam7_g04_13x11_ref.png");
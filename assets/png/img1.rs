@@ -0,0 +1,4 @@
+// 556x20 indexed 1 bit, Adam7 interlaced
+pub const IMG1_PNG: &[u8] = include_bytes!("img1.png");
+// CRC-32 of the RGBA pixels as decoded by libpng, alpha set to 255
+pub const IMG1_RGBA_CRC: u32 = 0x9387_ae78;
@@ -0,0 +1,4 @@
+// libpng test image, 91x69 RGBA 8 bit, Adam7 interlaced
+pub const PNGTEST_PNG: &[u8] = include_bytes!("pngtest.png");
+// CRC-32 of the RGBA pixels as decoded by libpng
+pub const PNGTEST_RGBA_CRC: u32 = 0xfb29_6c7f;
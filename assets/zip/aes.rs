@@ -0,0 +1 @@
+pub const AES_ZIP: &[u8] = include_bytes!("aes.zip");
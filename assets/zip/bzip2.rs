@@ -0,0 +1 @@
+pub const BZIP2_ZIP: &[u8] = include_bytes!("bzip2.zip");
@@ -0,0 +1 @@
+pub const ENCRYPTED_ZIP: &[u8] = include_bytes!("encrypted.zip");
@@ -0,0 +1 @@
+pub const NESTED_ZIP: &[u8] = include_bytes!("nested.zip");
@@ -0,0 +1 @@
+pub const RECOMPRESS_ZIP: &[u8] = include_bytes!("recompress.zip");
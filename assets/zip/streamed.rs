@@ -0,0 +1 @@
+pub const STREAMED_ZIP: &[u8] = include_bytes!("streamed.zip");
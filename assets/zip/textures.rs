@@ -0,0 +1 @@
+pub const TEXTURES_ZIP: &[u8] = include_bytes!("textures.zip");
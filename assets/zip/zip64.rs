@@ -0,0 +1 @@
+pub const ZIP64_ZIP: &[u8] = include_bytes!("zip64.zip");
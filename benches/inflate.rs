@@ -0,0 +1,132 @@
+// Decoding throughput in MB/s of uncompressed output.
+// * text: the lines of assets/gz/lines.gz at levels 1, 6 and 9
+// * corpus: the file named by `MINIZ_BENCH_CORPUS`, e.g. a chunk of enwik8, at level 6
+// * png_idat: the concatenated IDAT stream of assets/png/pngtest.png
+// * zip: all entries of an archive of text and incompressible data
+// Run with `cargo bench --features metrics` to print the decoder counters of each input.
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use miniz::deflate::{deflate, CompressionLevel};
+use miniz::inflate::{inflate, inflate_zlib, InflateMetrics, InflateOptions};
+use miniz::zip_write::{FileOptions, ZipWriter};
+use miniz::{png_read, zip_read};
+
+const LINES_GZ: &[u8] = include_bytes!("../assets/gz/lines.gz");
+const PNGTEST: &[u8] = include_bytes!("../assets/png/pngtest.png");
+
+// ----------------------------------------------------------------------------
+fn compress(src: &[u8], level: CompressionLevel) -> Vec<u8> {
+    let mut dst = Vec::new();
+    deflate(&mut dst, src, level);
+    dst
+}
+
+// ----------------------------------------------------------------------------
+// xorshift, incompressible
+fn noise(len: usize) -> Vec<u8> {
+    let mut x = 0x2545_f491u32;
+    (0..len)
+        .map(|_| {
+            x ^= x << 13;
+            x ^= x >> 17;
+            x ^= x << 5;
+            x as u8
+        })
+        .collect()
+}
+
+// ----------------------------------------------------------------------------
+fn print_metrics(name: &str, packed: &[u8], len: usize) {
+    if cfg!(feature = "metrics") {
+        let metrics = core::cell::Cell::new(InflateMetrics::default());
+        let options = InflateOptions {
+            metrics: Some(&metrics),
+            ..Default::default()
+        };
+        let mut out = vec![0; len];
+        miniz::inflate::inflate_with_options(&mut out, packed, &options).unwrap();
+        println!("{name}: {:?}", metrics.get());
+    }
+}
+
+// ----------------------------------------------------------------------------
+fn bench_raw(c: &mut Criterion) {
+    let text = miniz::decompress_to_vec_gzip(LINES_GZ).unwrap();
+    let mut inputs = Vec::new();
+    for (name, level) in [
+        ("text/1", CompressionLevel::BestSpeed),
+        ("text/6", CompressionLevel::DefaultLevel),
+        ("text/9", CompressionLevel::BestCompression),
+    ] {
+        inputs.push((name.to_string(), compress(&text, level), text.len()));
+    }
+    if let Ok(path) = std::env::var("MINIZ_BENCH_CORPUS") {
+        let corpus = std::fs::read(path).unwrap();
+        let packed = compress(&corpus, CompressionLevel::DefaultLevel);
+        inputs.push(("corpus/6".to_string(), packed, corpus.len()));
+    }
+
+    let mut group = c.benchmark_group("inflate");
+    for (name, packed, len) in &inputs {
+        print_metrics(name, packed, *len);
+        let mut out = vec![0; *len];
+        group.throughput(Throughput::Bytes(*len as u64));
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter(|| inflate(&mut out, packed).unwrap())
+        });
+    }
+    group.finish();
+}
+
+// ----------------------------------------------------------------------------
+fn bench_png_idat(c: &mut Criterion) {
+    let idat_type = u32::from_be_bytes(*b"IDAT");
+    let idat: Vec<u8> = png_read::png_chunks(PNGTEST)
+        .unwrap()
+        .map(Result::unwrap)
+        .filter(|chunk| chunk.r#type == idat_type)
+        .flat_map(|chunk| chunk.data.iter().copied())
+        .collect();
+    let len = miniz::decompress_to_vec_zlib(&idat).unwrap().len();
+    let mut out = vec![0; len];
+
+    let mut group = c.benchmark_group("inflate");
+    group.throughput(Throughput::Bytes(len as u64));
+    group.bench_function("png_idat", |b| {
+        b.iter(|| inflate_zlib(&mut out, &idat).unwrap())
+    });
+    group.finish();
+}
+
+// ----------------------------------------------------------------------------
+fn bench_zip(c: &mut Criterion) {
+    let text = miniz::decompress_to_vec_gzip(LINES_GZ).unwrap();
+    let mut writer = ZipWriter::new();
+    let options = FileOptions::default();
+    writer.add_file("lines.txt", &text, &options).unwrap();
+    writer
+        .add_file("noise.bin", &noise(1 << 18), &options)
+        .unwrap();
+    for i in 0..16 {
+        let name = format!("small/{i}.txt");
+        writer
+            .add_file(&name, &text[i * 1000..i * 1000 + 4000], &options)
+            .unwrap();
+    }
+    let zip = writer.finish().unwrap();
+    let files = zip_read::zip_open(&zip).unwrap();
+    let len: usize = files.iter().map(|file| file.size).sum();
+
+    let mut group = c.benchmark_group("inflate");
+    group.throughput(Throughput::Bytes(len as u64));
+    group.bench_function("zip", |b| {
+        b.iter(|| {
+            for file in &files {
+                zip_read::zip_read(&zip, &files, &file.name).unwrap();
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_raw, bench_png_idat, bench_zip);
+criterion_main!(benches);
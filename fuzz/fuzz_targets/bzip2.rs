@@ -0,0 +1,14 @@
+// bzip2 decoding of arbitrary input.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use miniz::bzip2;
+
+fuzz_target!(|data: &[u8]| {
+    let mut out = vec![0; 1 << 20];
+    let len = bzip2::decompress(&mut out, data);
+    if let Ok(decoded) = bzip2::decompress_to_vec(data, 1 << 20) {
+        assert_eq!(len, Ok(decoded.len()));
+        assert_eq!(&out[..decoded.len()], decoded);
+    }
+});
@@ -0,0 +1,51 @@
+// Raw deflate, Deflate64, zlib and gzip decoding of arbitrary input, one-shot and resumable
+// decoding must agree.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use miniz::inflate::{self, InflateOptions, VecLimits};
+use miniz::stream::Inflater;
+
+const MAX_OUTPUT: usize = 1 << 20;
+
+fuzz_target!(|data: &[u8]| {
+    let limits = VecLimits {
+        size_hint: None,
+        max_output: MAX_OUTPUT,
+    };
+    let oneshot = inflate::inflate_to_vec(data, &limits);
+
+    let mut out = vec![0; MAX_OUTPUT];
+    let options = InflateOptions {
+        strict_end_of_input: true,
+        ..Default::default()
+    };
+    let _ = inflate::inflate_with_options(&mut out, data, &options);
+    let _ = inflate::inflate64(&mut out, data);
+    let _ = inflate::inflate_zlib(&mut out, data);
+    let _ = inflate::inflate_gzip(&mut out, data);
+
+    // the resumable decoder fed in two pieces
+    if let Ok(expected) = oneshot {
+        let split = data.first().map_or(0, |b| *b as usize).min(data.len());
+        let mut inflater = Inflater::new(false);
+        let mut decoded = vec![0; expected.len()];
+        let mut produced = 0;
+        let mut consumed = 0;
+        for end in [split, data.len()] {
+            if consumed > end {
+                continue;
+            }
+            let Ok((_, n_in, n_out)) =
+                inflater.decompress(&data[consumed..end], &mut decoded[produced..])
+            else {
+                return;
+            };
+            consumed += n_in;
+            produced += n_out;
+        }
+        if inflater.is_finished() {
+            assert_eq!(&decoded[..produced], &expected[..produced]);
+        }
+    }
+});
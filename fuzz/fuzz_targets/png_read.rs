@@ -0,0 +1,21 @@
+// PNG, APNG and metadata parsing of arbitrary input, images are limited to 64 MB.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use miniz::{apng, png_meta, png_read};
+
+const MAX_IMAGE: usize = 1 << 26;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = png_meta::png_metadata(data);
+    let Ok(ihdr) = png_read::png_info(data) else {
+        return;
+    };
+    if png_read::decoded_size(&ihdr).map_or(true, |size| size > MAX_IMAGE) {
+        return;
+    }
+    if let Ok((ihdr, _, pixels)) = png_read::png_read(data) {
+        assert_eq!(Ok(pixels.len()), png_read::decoded_size(&ihdr));
+    }
+    let _ = apng::apng_read(data);
+});
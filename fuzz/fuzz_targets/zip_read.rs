@@ -0,0 +1,18 @@
+// ZIP central directory parsing and extraction of arbitrary input, entries are limited to
+// 16 MB.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use miniz::zip_read;
+
+const MAX_ENTRY: usize = 1 << 24;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = zip_read::zip_entries(data);
+    let Ok(files) = zip_read::zip_open(data) else {
+        return;
+    };
+    for file in files.iter().filter(|file| file.size <= MAX_ENTRY) {
+        let _ = zip_read::zip_read(data, &files, &file.name);
+    }
+});
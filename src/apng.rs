@@ -0,0 +1,236 @@
+// Animated PNG (APNG) decoding.
+// * https://www.w3.org/TR/png-3/#apng-frame-control-chunk
+// * acTL announces the animation, each frame starts with an fcTL chunk and its image data is in
+//   IDAT for the first frame or in fdAT chunks, a sequence number orders fcTL and fdAT chunks
+// * an IDAT without preceding fcTL is a default image shown by decoders without APNG support
+// * frames are returned as decoded, disposing and blending them is up to the caller
+use crate::buffer::Heap;
+use crate::inflate::InflateOptions;
+use crate::png_read::{decode_idat, png_chunks, read_ihdr, read_plte};
+use crate::png_read::{Error, PNGChunkIHDR, Result};
+use alloc::vec::Vec;
+
+// ----------------------------------------------------------------------------
+const IHDR: u32 = u32::from_be_bytes(*b"IHDR");
+const PLTE: u32 = u32::from_be_bytes(*b"PLTE");
+const IDAT: u32 = u32::from_be_bytes(*b"IDAT");
+const IEND: u32 = u32::from_be_bytes(*b"IEND");
+const ACTL: u32 = u32::from_be_bytes(*b"acTL");
+const FCTL: u32 = u32::from_be_bytes(*b"fcTL");
+const FDAT: u32 = u32::from_be_bytes(*b"fdAT");
+
+// ----------------------------------------------------------------------------
+// Treatment of the frame area before rendering the next frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DisposeOp {
+    // left as is
+    None,
+    // cleared to fully transparent black
+    Background,
+    // restored to the contents before the frame was rendered
+    Previous,
+}
+
+// ----------------------------------------------------------------------------
+// How the frame is combined with the output buffer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlendOp {
+    // the frame replaces the area including alpha
+    Source,
+    // the frame is alpha-composited over the area
+    Over,
+}
+
+// ----------------------------------------------------------------------------
+// Contents of an fcTL chunk.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameControl {
+    pub width: usize,
+    pub height: usize,
+    pub x_offset: usize,
+    pub y_offset: usize,
+    // display time in seconds as fraction, a denominator of 0 means 1/100 s
+    pub delay_num: u16,
+    pub delay_den: u16,
+    pub dispose_op: DisposeOp,
+    pub blend_op: BlendOp,
+}
+
+// ----------------------------------------------------------------------------
+impl FrameControl {
+    pub fn delay_ms(&self) -> u32 {
+        let den = if self.delay_den == 0 {
+            100
+        } else {
+            self.delay_den
+        };
+        self.delay_num as u32 * 1000 / den as u32
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Frame with its scanlines decoded like `png_read` output, `width` by `height` pixels in the
+// color type and bit depth of the image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApngFrame {
+    pub control: FrameControl,
+    pub data: Vec<u8>,
+}
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone, PartialEq)]
+pub struct Apng {
+    pub ihdr: PNGChunkIHDR,
+    pub plte: Vec<u32>,
+    // number of times the animation is played, 0 loops forever
+    pub num_plays: u32,
+    // the IDAT image if it isn't the first frame
+    pub default_image: Option<Vec<u8>>,
+    pub frames: Vec<ApngFrame>,
+}
+
+// ----------------------------------------------------------------------------
+fn read_u32(data: &[u8], pos: usize) -> usize {
+    u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]) as usize
+}
+
+// ----------------------------------------------------------------------------
+fn read_fctl(data: &[u8], ihdr: &PNGChunkIHDR) -> Result<FrameControl> {
+    if data.len() != 26 {
+        return Err(Error::InvalidAnimation);
+    }
+
+    let control = FrameControl {
+        width: read_u32(data, 4),
+        height: read_u32(data, 8),
+        x_offset: read_u32(data, 12),
+        y_offset: read_u32(data, 16),
+        delay_num: u16::from_be_bytes([data[20], data[21]]),
+        delay_den: u16::from_be_bytes([data[22], data[23]]),
+        dispose_op: match data[24] {
+            0 => DisposeOp::None,
+            1 => DisposeOp::Background,
+            2 => DisposeOp::Previous,
+            _ => return Err(Error::InvalidAnimation),
+        },
+        blend_op: match data[25] {
+            0 => BlendOp::Source,
+            1 => BlendOp::Over,
+            _ => return Err(Error::InvalidAnimation),
+        },
+    };
+
+    let fits = |offset: usize, len: usize, max: usize| {
+        len > 0 && offset.checked_add(len).is_some_and(|end| end <= max)
+    };
+    if !fits(control.x_offset, control.width, ihdr.width)
+        || !fits(control.y_offset, control.height, ihdr.height)
+    {
+        return Err(Error::InvalidAnimation);
+    }
+    Ok(control)
+}
+
+// ----------------------------------------------------------------------------
+pub fn apng_read(png: &[u8]) -> Result<Apng> {
+    apng_read_with_options(png, &InflateOptions::default())
+}
+
+// ----------------------------------------------------------------------------
+// Decodes all frames of an animated PNG, a PNG without acTL chunk fails with
+// `Error::InvalidAnimation`. `options` apply to the decompression of every frame.
+pub fn apng_read_with_options(png: &[u8], options: &InflateOptions) -> Result<Apng> {
+    let (ihdr, _) = read_ihdr(png, options.verify_checksums)?;
+    if ihdr.bit_depth > 8 {
+        return Err(Error::UnsupportedFormat);
+    }
+
+    let mut plte = Vec::new();
+    let mut animation = None;
+    let mut idat = Vec::new();
+    // frame controls with the zlib data of their frame, `None` for the IDAT frame
+    let mut frames: Vec<(FrameControl, Option<Vec<u8>>)> = Vec::new();
+    let mut sequence = 0;
+    let mut seen_idat = false;
+
+    for chunk in png_chunks(png)? {
+        let chunk = chunk?;
+        let data = chunk.data;
+        let animation_chunk = matches!(chunk.r#type, ACTL | FCTL | FDAT);
+        let critical = !chunk.is_ancillary() || animation_chunk;
+        if options.verify_checksums && critical && !chunk.crc_ok() {
+            return Err(Error::ChecksumMismatch);
+        }
+
+        // fcTL and fdAT start with their sequence number
+        if animation_chunk && chunk.r#type != ACTL {
+            if data.len() < 4 || read_u32(data, 0) != sequence {
+                return Err(Error::InvalidAnimation);
+            }
+            sequence += 1;
+        }
+
+        match chunk.r#type {
+            IHDR => {}
+            PLTE => read_plte(data, &mut plte)?,
+            ACTL if data.len() == 8 && !seen_idat => {
+                animation = Some((read_u32(data, 0), read_u32(data, 4) as u32));
+            }
+            FCTL => {
+                let control = read_fctl(data, &ihdr)?;
+                // the frame data follows in IDAT before the first IDAT, else in fdAT chunks
+                let data = if seen_idat { Some(Vec::new()) } else { None };
+                frames.push((control, data));
+            }
+            IDAT => {
+                idat.extend_from_slice(data);
+                seen_idat = true;
+            }
+            FDAT => match frames.last_mut() {
+                Some((_, Some(frame))) => frame.extend_from_slice(&data[4..]),
+                _ => return Err(Error::InvalidAnimation),
+            },
+            IEND => break,
+            ACTL => return Err(Error::InvalidAnimation),
+            _ => {}
+        }
+    }
+
+    let (num_frames, num_plays) = animation.ok_or(Error::InvalidAnimation)?;
+    if frames.len() != num_frames || num_frames == 0 || idat.is_empty() {
+        return Err(Error::InvalidAnimation);
+    }
+
+    let decode = |ihdr: PNGChunkIHDR, data: &[u8]| -> Result<Vec<u8>> {
+        decode_idat(core::iter::once(data), &ihdr, &mut Heap, options)
+    };
+
+    let mut default_image = Some(decode(ihdr, &idat)?);
+    let mut decoded = Vec::with_capacity(frames.len());
+    for (control, data) in frames {
+        let data = match data {
+            Some(data) => decode(
+                PNGChunkIHDR {
+                    width: control.width,
+                    height: control.height,
+                    ..ihdr
+                },
+                &data,
+            )?,
+            // the IDAT frame covers the whole image
+            None if control.width == ihdr.width && control.height == ihdr.height => {
+                default_image.take().ok_or(Error::InvalidAnimation)?
+            }
+            None => return Err(Error::InvalidAnimation),
+        };
+        decoded.push(ApngFrame { control, data });
+    }
+
+    Ok(Apng {
+        ihdr,
+        plte,
+        num_plays,
+        default_image,
+        frames: decoded,
+    })
+}
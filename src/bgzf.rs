@@ -0,0 +1,119 @@
+// BGZF (blocked gzip) writing as used by bgzip, tabix and htslib.
+// * https://samtools.github.io/hts-specs/SAMv1.pdf (section 4.1)
+// * each block is a gzip member of at most 64 KB with its size in a "BC" extra subfield, the
+//   file ends with an empty block as EOF marker
+use crate::deflate::CompressionLevel;
+use crate::{checksum, deflate};
+use alloc::vec::Vec;
+
+// ----------------------------------------------------------------------------
+// uncompressed bytes per block, leaves room for the block framing within 64 KB
+pub const BGZF_BLOCK_SIZE: usize = 0xff00;
+
+// largest block BSIZE can describe
+const MAX_BLOCK_LEN: usize = 0x10000;
+
+// ----------------------------------------------------------------------------
+pub const BGZF_EOF: [u8; 28] = [
+    0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, 0x42, 0x43, 0x02, 0x00,
+    0x1b, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+// ----------------------------------------------------------------------------
+// Appends `src` compressed at `level` as a single block, `src` must not exceed
+// `BGZF_BLOCK_SIZE` bytes.
+fn write_block(dst: &mut Vec<u8>, src: &[u8], level: CompressionLevel) {
+    let start = dst.len();
+
+    // gzip header with FEXTRA, XLEN 6 and the BC subfield, BSIZE is patched below
+    dst.extend_from_slice(&[0x1f, 0x8b, 8, 0x04, 0, 0, 0, 0, 0, 0xff]);
+    dst.extend_from_slice(&[6, 0, b'B', b'C', 2, 0, 0, 0]);
+    let data = dst.len();
+    deflate::deflate(dst, src, level);
+
+    // incompressible data may not fit anymore, stored blocks always do
+    if dst.len() - start + 8 > MAX_BLOCK_LEN {
+        dst.truncate(data);
+        deflate::deflate_stored(dst, src);
+    }
+    dst.extend_from_slice(&checksum::crc32(0, src).to_le_bytes());
+    dst.extend_from_slice(&(src.len() as u32).to_le_bytes());
+
+    // BSIZE is the total block size minus 1
+    let bsize = (dst.len() - start - 1) as u16;
+    dst[start + 16..start + 18].copy_from_slice(&bsize.to_le_bytes());
+}
+
+// ----------------------------------------------------------------------------
+// Compresses `src` at `level` (0 to 9) into a complete BGZF file including the EOF marker.
+pub fn bgzf_compress(src: &[u8], level: u8) -> Vec<u8> {
+    let mut dst = Vec::new();
+    for block in src.chunks(BGZF_BLOCK_SIZE) {
+        write_block(&mut dst, block, level.into());
+    }
+    dst.extend_from_slice(&BGZF_EOF);
+    dst
+}
+
+// ----------------------------------------------------------------------------
+// Writes BGZF blocks to `W`, full blocks are written as the data arrives, `flush` ends the
+// current block early and `finish` appends the EOF marker.
+#[cfg(feature = "std")]
+pub struct BgzfWriter<W: std::io::Write> {
+    writer: W,
+    level: CompressionLevel,
+    block: Vec<u8>,
+    out: Vec<u8>,
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(feature = "std")]
+impl<W: std::io::Write> BgzfWriter<W> {
+    pub fn new(writer: W, level: u8) -> Self {
+        BgzfWriter {
+            writer,
+            level: level.into(),
+            block: Vec::with_capacity(BGZF_BLOCK_SIZE),
+            out: Vec::new(),
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    fn write_pending(&mut self) -> std::io::Result<()> {
+        if !self.block.is_empty() {
+            self.out.clear();
+            write_block(&mut self.out, &self.block, self.level);
+            self.writer.write_all(&self.out)?;
+            self.block.clear();
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.write_pending()?;
+        self.writer.write_all(&BGZF_EOF)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(feature = "std")]
+impl<W: std::io::Write> std::io::Write for BgzfWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let len = buf.len().min(BGZF_BLOCK_SIZE - self.block.len());
+        self.block.extend_from_slice(&buf[..len]);
+        if self.block.len() == BGZF_BLOCK_SIZE {
+            self.write_pending()?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.write_pending()?;
+        self.writer.flush()
+    }
+}
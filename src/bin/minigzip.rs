@@ -0,0 +1,55 @@
+// minigzip: compress and decompress gzip files, zlib's minigzip without stdin/stdout.
+// * usage: minigzip [-d] [-1..-9] <file>...
+// * `file` is compressed into `file.gz`, `-d` decompresses `file.gz` into `file`, the input is
+//   kept
+use miniz::file::{gunzip_file, gzip_file};
+use std::path::Path;
+
+// ----------------------------------------------------------------------------
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+// ----------------------------------------------------------------------------
+fn usage() -> Result<()> {
+    Err("usage: minigzip [-d] [-1..-9] <file>...".into())
+}
+
+// ----------------------------------------------------------------------------
+fn run() -> Result<()> {
+    let mut decompress = false;
+    let mut level = 6;
+    let mut paths = Vec::new();
+
+    for arg in std::env::args().skip(1) {
+        match arg.as_bytes() {
+            b"-d" => decompress = true,
+            [b'-', digit @ b'1'..=b'9'] => level = digit - b'0',
+            [b'-', ..] => return usage(),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        return usage();
+    }
+
+    for path in &paths {
+        if decompress {
+            let Some(out) = path.strip_suffix(".gz") else {
+                return Err(format!("{path}: unknown suffix").into());
+            };
+            gunzip_file(path, out).map_err(|err| format!("{path}: {err}"))?;
+        } else {
+            let out = format!("{path}.gz");
+            gzip_file(Path::new(path), &out, level).map_err(|err| format!("{path}: {err}"))?;
+        }
+    }
+
+    Ok(())
+}
+
+// ----------------------------------------------------------------------------
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("minigzip: {err}");
+        std::process::exit(1);
+    }
+}
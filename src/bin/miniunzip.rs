@@ -0,0 +1,78 @@
+// miniunzip: list and extract ZIP archives.
+// * usage: miniunzip [-l] <archive.zip> [-d <dir>]
+use miniz::zip_read::{zip_extract_all, zip_open};
+use std::path::{Component, Path, PathBuf};
+
+// ----------------------------------------------------------------------------
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+// ----------------------------------------------------------------------------
+fn usage() -> Result<()> {
+    Err("usage: miniunzip [-l] <archive.zip> [-d <dir>]".into())
+}
+
+// ----------------------------------------------------------------------------
+fn safe_path(dir: &Path, name: &str) -> Option<PathBuf> {
+    // never write outside of the target directory
+    let path = Path::new(name);
+    if path.components().all(|c| matches!(c, Component::Normal(_))) {
+        Some(dir.join(path))
+    } else {
+        None
+    }
+}
+
+// ----------------------------------------------------------------------------
+fn run() -> Result<()> {
+    let mut list = false;
+    let mut archive = None;
+    let mut dir = PathBuf::from(".");
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-l" => list = true,
+            "-d" => match args.next() {
+                Some(d) => dir = PathBuf::from(d),
+                None => return usage(),
+            },
+            _ if archive.is_none() => archive = Some(arg),
+            _ => return usage(),
+        }
+    }
+
+    let Some(archive) = archive else {
+        return usage();
+    };
+
+    let data = std::fs::read(&archive)?;
+    let files = zip_open(&data)?;
+
+    if list {
+        for file in &files {
+            println!("{:>10}  {}", file.size, file.name);
+        }
+        return Ok(());
+    }
+
+    zip_extract_all(&data, &files, &dir, |name| {
+        let Some(path) = safe_path(&dir, name) else {
+            eprintln!("skipping unsafe path: {name}");
+            return None;
+        };
+        if !name.ends_with('/') {
+            println!("  inflating: {}", path.display());
+        }
+        Some(PathBuf::from(name))
+    })?;
+
+    Ok(())
+}
+
+// ----------------------------------------------------------------------------
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("miniunzip: {err}");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,56 @@
+// pngdump: print chunk layout and header information of PNG files.
+// * usage: pngdump <file.png>...
+use miniz::png_read::{png_chunks, png_read};
+
+// ----------------------------------------------------------------------------
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+// ----------------------------------------------------------------------------
+fn dump(path: &str) -> Result<()> {
+    let png = std::fs::read(path)?;
+    println!("{path}:");
+
+    for chunk in png_chunks(&png)? {
+        let chunk = chunk?;
+        println!(
+            "  {} {:>8} bytes  crc {:08x}",
+            chunk.name(),
+            chunk.data.len(),
+            chunk.crc
+        );
+    }
+
+    match png_read(&png) {
+        Ok((ihdr, plte, _)) => {
+            println!("  size:        {} x {}", ihdr.width, ihdr.height);
+            println!("  bit depth:   {}", ihdr.bit_depth);
+            println!("  color type:  {:?}", ihdr.color_type);
+            println!("  interlace:   {}", ihdr.interlace);
+            println!("  palette:     {} entries", plte.len());
+        }
+        Err(err) => println!("  decode error: {err}"),
+    }
+
+    Ok(())
+}
+
+// ----------------------------------------------------------------------------
+fn main() {
+    let paths: Vec<String> = std::env::args().skip(1).collect();
+    if paths.is_empty() {
+        eprintln!("usage: pngdump <file.png>...");
+        std::process::exit(1);
+    }
+
+    let mut failed = false;
+    for path in &paths {
+        if let Err(err) = dump(path) {
+            eprintln!("pngdump: {path}: {err}");
+            failed = true;
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+}
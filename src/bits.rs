@@ -0,0 +1,265 @@
+// LSB-first bit reading and writing as used by deflate (RFC 1951, section 3.1.1).
+// * data elements other than Huffman codes are packed starting with their least significant bit,
+//   Huffman codes starting with their most significant bit
+use crate::huffman::{HuffmanDecoder, MAX_CODE_BITS};
+use crate::inflate::Error;
+use alloc::vec::Vec;
+
+// ----------------------------------------------------------------------------
+// Bit buffer of the reader: 64-bit targets refill up to 7 bytes at once, 32-bit targets (MCUs,
+// wasm32) stick to native 32-bit loads and shifts and refill up to 3 bytes.
+#[cfg(target_pointer_width = "64")]
+pub(crate) type BitBuf = u64;
+#[cfg(not(target_pointer_width = "64"))]
+pub(crate) type BitBuf = u32;
+
+pub(crate) const BITBUF_BYTES: usize = core::mem::size_of::<BitBuf>();
+
+// ----------------------------------------------------------------------------
+// LSB-first bit reader. Reading past the end of input yields zero bits; `Underflow` is returned
+// once no input bits are left at all, or in strict mode once any missing bit was consumed.
+// Segmented input is read slice by slice as if concatenated, the next slice is requested once the
+// current one is used up, `'r` is the borrow of their iterator.
+pub struct BitReader<'a, 'r> {
+    src: &'a [u8], // current slice
+    // slices following `src`
+    rest: Option<&'r mut dyn Iterator<Item = &'a [u8]>>,
+    base: usize,             // input bytes in the slices before `src`
+    pos: usize,              // next byte of `src` to load into `bits`
+    pub(crate) bits: BitBuf, // buffered bits, next bit in the LSB
+    count: u32,              // number of valid bits in `bits`
+    overrun: usize,          // bits consumed past the end of input
+    strict: bool,
+}
+
+// ----------------------------------------------------------------------------
+impl<'a, 'r> BitReader<'a, 'r> {
+    // In `strict` mode, consuming bits past the end of input fails with `Underflow`.
+    pub fn new(src: &'a [u8], strict: bool) -> Self {
+        BitReader {
+            src,
+            rest: None,
+            base: 0,
+            pos: 0,
+            bits: 0,
+            count: 0,
+            overrun: 0,
+            strict,
+        }
+    }
+
+    pub(crate) fn segmented(segments: &'r mut dyn Iterator<Item = &'a [u8]>, strict: bool) -> Self {
+        let mut br = BitReader::new(segments.next().unwrap_or_default(), strict);
+        br.rest = Some(segments);
+        br
+    }
+
+    // position of the next bit in the input
+    pub fn bit_pos(&self) -> usize {
+        (self.base + self.pos) * 8 - self.count as usize + self.overrun
+    }
+
+    // fails in strict mode if bits past the end of input were consumed
+    pub(crate) fn check_end(&self) -> Result<(), Error> {
+        if self.strict && (self.overrun > 0 || self.pos > self.src.len()) {
+            return Err(Error::Underflow);
+        }
+        Ok(())
+    }
+
+    // Moves on to the next slice of segmented input, false at the end of input.
+    fn next_segment(&mut self) -> bool {
+        match self.rest.as_mut().and_then(|rest| rest.next()) {
+            Some(next) => {
+                self.base += self.src.len();
+                self.src = next;
+                self.pos = 0;
+                true
+            }
+            None => {
+                self.rest = None;
+                false
+            }
+        }
+    }
+
+    pub(crate) fn refill(&mut self) {
+        if let Some(word) = self.src.get(self.pos..self.pos + BITBUF_BYTES) {
+            // Load a whole word and keep the complete bytes that fit. The bits of a partially
+            // fitting byte above `count` are the same ones the next refill will load.
+            let word = BitBuf::from_le_bytes(word.try_into().unwrap_or_default());
+            self.bits |= word << self.count;
+            let bytes = (BitBuf::BITS - 1 - self.count) / 8;
+            self.pos += bytes as usize;
+            self.count += bytes * 8;
+        } else {
+            // careful tail handling near the end of input or of the current slice
+            while self.count <= BitBuf::BITS - 8 {
+                if self.pos < self.src.len() {
+                    self.bits |= (self.src[self.pos] as BitBuf) << self.count;
+                    self.pos += 1;
+                    self.count += 8;
+                } else if !self.next_segment() {
+                    break;
+                }
+            }
+        }
+    }
+
+    // Makes at least `count` bits available unless the input ends, returns the buffered bits.
+    pub(crate) fn ensure(&mut self, count: u8) -> Result<BitBuf, Error> {
+        if self.count < count as u32 {
+            self.refill();
+            if self.count == 0 {
+                return Err(Error::Underflow);
+            }
+        }
+        Ok(self.bits)
+    }
+
+    // The next `count` bits, up to 16, without consuming them.
+    pub fn peek(&mut self, count: u8) -> Result<u16, Error> {
+        let bits = self.ensure(count)?;
+        Ok(bits as u16 & ((1u32 << count) - 1) as u16)
+    }
+
+    // Skips `count` bits, up to the ones returned by the last `peek`.
+    pub fn consume(&mut self, count: u8) {
+        let count = count as u32;
+        if count <= self.count {
+            self.bits >>= count;
+            self.count -= count;
+        } else {
+            self.overrun += (count - self.count) as usize;
+            self.bits = 0;
+            self.count = 0;
+        }
+    }
+
+    // number of bytes of the current slice not loaded into the bit buffer yet
+    pub(crate) fn remaining_bytes(&self) -> usize {
+        self.src.len().saturating_sub(self.pos)
+    }
+
+    // Takes `count` buffered bits, the caller guarantees that they are available.
+    pub(crate) fn take(&mut self, count: u8) -> u16 {
+        debug_assert!(count as u32 <= self.count);
+        let res = self.bits as u16 & ((1u32 << count) - 1) as u16;
+        self.bits >>= count;
+        self.count -= count as u32;
+        res
+    }
+
+    // Reads `count` bits, up to 16, the first one in the LSB.
+    pub fn read_bits(&mut self, count: u8) -> Result<u16, Error> {
+        let res = self.peek(count)?;
+        self.consume(count);
+        self.check_end()?;
+        Ok(res)
+    }
+
+    // Decodes the next symbol with `decoder`, a code without symbol fails with `InvalidSymbol`.
+    pub fn read_symbol(&mut self, decoder: &HuffmanDecoder) -> Result<u16, Error> {
+        let bits = self.ensure(MAX_CODE_BITS)?;
+        let (symbol, len) = decoder.decode(bits as u32).ok_or(Error::InvalidSymbol)?;
+        self.consume(len);
+        self.check_end()?;
+        Ok(symbol)
+    }
+
+    // Skips to the next byte boundary, whole bytes stay in the bit buffer.
+    pub fn align_to_byte(&mut self) {
+        self.consume((self.count % 8) as u8);
+    }
+
+    // Input bytes left after `align_to_byte`, `None` while more slices may follow.
+    pub fn bytes_left(&self) -> Option<usize> {
+        let left = (self.count / 8) as usize + self.src.len().saturating_sub(self.pos);
+        self.rest.is_none().then_some(left)
+    }
+
+    // Reads whole bytes after `align_to_byte`, the buffered ones first.
+    pub fn read_bytes(&mut self, dst: &mut [u8]) -> Result<(), Error> {
+        let mut dst = dst;
+        while self.count >= 8 && !dst.is_empty() {
+            dst[0] = self.take(8) as u8;
+            dst = &mut dst[1..];
+        }
+        if dst.is_empty() {
+            return Ok(());
+        }
+        // bits above `count` may hold part of the next byte, which is copied directly now
+        self.bits = 0;
+        while !dst.is_empty() {
+            let src = self.src.get(self.pos..).unwrap_or_default();
+            if src.is_empty() {
+                if !self.next_segment() {
+                    return Err(Error::Underflow);
+                }
+                continue;
+            }
+            let len = src.len().min(dst.len());
+            let (head, tail) = core::mem::take(&mut dst).split_at_mut(len);
+            head.copy_from_slice(&src[..len]);
+            self.pos += len;
+            dst = tail;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn set_byte_pos(&mut self, pos: usize) {
+        self.pos = pos;
+        self.bits = 0;
+        self.count = 0;
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Packs bits LSB first, Huffman codes are sent starting with their most significant bit.
+pub struct BitWriter<'a> {
+    pub(crate) dst: &'a mut Vec<u8>,
+    acc: u32,
+    pub(crate) bits: u32, // number of valid bits in `acc`
+}
+
+// ----------------------------------------------------------------------------
+impl<'a> BitWriter<'a> {
+    // Appends to `dst`.
+    pub fn new(dst: &'a mut Vec<u8>) -> Self {
+        BitWriter {
+            dst,
+            acc: 0,
+            bits: 0,
+        }
+    }
+
+    // Writes the `len` low bits of `value`, up to 24, the LSB first.
+    pub fn put(&mut self, value: u32, len: u32) {
+        self.acc |= value << self.bits;
+        self.bits += len;
+        while self.bits >= 8 {
+            self.dst.push(self.acc as u8);
+            self.acc >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    // Writes a Huffman code of `len` bits, its MSB first.
+    pub fn put_code(&mut self, code: u32, len: u32) {
+        self.put(code.reverse_bits() >> (32 - len), len);
+    }
+
+    // bits written so far, including those not in `dst` yet
+    pub fn bit_pos(&self) -> u64 {
+        8 * self.dst.len() as u64 + self.bits as u64
+    }
+
+    // Pads the last byte with zero bits.
+    pub fn flush(&mut self) {
+        if self.bits > 0 {
+            self.dst.push(self.acc as u8);
+            self.acc = 0;
+            self.bits = 0;
+        }
+    }
+}
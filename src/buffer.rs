@@ -0,0 +1,178 @@
+// Pluggable output buffers for the decoders.
+// * lets callers decode into arena or pool memory instead of fresh heap allocations
+// * the decoders' temporary buffers, e.g. the passes of interlaced PNGs, come from the provider
+//   as well, `Arena` hands them out of its memory, `Pool` recycles them for servers decoding many
+//   files in a row
+use alloc::{vec, vec::Vec};
+
+// ----------------------------------------------------------------------------
+// Temporary buffer of a decoder, from the global allocator or from the provider's memory.
+#[derive(Debug)]
+pub enum Scratch<B> {
+    Heap(Vec<u8>),
+    Provided(B),
+}
+
+// ----------------------------------------------------------------------------
+impl<B: AsRef<[u8]>> AsRef<[u8]> for Scratch<B> {
+    fn as_ref(&self) -> &[u8] {
+        match self {
+            Scratch::Heap(buffer) => buffer,
+            Scratch::Provided(buffer) => buffer.as_ref(),
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+impl<B: AsMut<[u8]>> AsMut<[u8]> for Scratch<B> {
+    fn as_mut(&mut self) -> &mut [u8] {
+        match self {
+            Scratch::Heap(buffer) => buffer,
+            Scratch::Provided(buffer) => buffer.as_mut(),
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+pub trait BufferProvider {
+    type Buffer: AsRef<[u8]> + AsMut<[u8]>;
+
+    // Returns a buffer of exactly `len` bytes with unspecified content, the decoder overwrites
+    // all of it. Returns None if the request can't be satisfied.
+    fn alloc(&mut self, len: usize) -> Option<Self::Buffer>;
+
+    // Returns a buffer of exactly `len` bytes for temporary data of the decoder, handed back
+    // with `release` once the decoder is done with it.
+    fn scratch(&mut self, len: usize) -> Option<Scratch<Self::Buffer>> {
+        Some(Scratch::Heap(vec![0u8; len]))
+    }
+
+    fn release(&mut self, _buffer: Scratch<Self::Buffer>) {}
+}
+
+// ----------------------------------------------------------------------------
+// Allocates every buffer from the global allocator.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Heap;
+
+// ----------------------------------------------------------------------------
+impl BufferProvider for Heap {
+    type Buffer = Vec<u8>;
+
+    fn alloc(&mut self, len: usize) -> Option<Vec<u8>> {
+        Some(vec![0u8; len])
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Hands out consecutive slices of a caller-owned memory region.
+#[derive(Debug)]
+pub struct Arena<'a> {
+    mem: &'a mut [u8],
+}
+
+// ----------------------------------------------------------------------------
+impl<'a> Arena<'a> {
+    pub fn new(mem: &'a mut [u8]) -> Self {
+        Arena { mem }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.mem.len()
+    }
+}
+
+// ----------------------------------------------------------------------------
+impl<'a> BufferProvider for Arena<'a> {
+    type Buffer = &'a mut [u8];
+
+    fn alloc(&mut self, len: usize) -> Option<&'a mut [u8]> {
+        if len > self.mem.len() {
+            return None;
+        }
+        let mem = core::mem::take(&mut self.mem);
+        let (head, tail) = mem.split_at_mut(len);
+        self.mem = tail;
+        Some(head)
+    }
+
+    // temporary buffers are taken from the arena as well, its memory is not reclaimed
+    fn scratch(&mut self, len: usize) -> Option<Scratch<&'a mut [u8]>> {
+        self.alloc(len).map(Scratch::Provided)
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Keeps up to `max_buffers` released buffers for reuse. Output buffers are returned to the pool
+// with `recycle` once the caller is done with them, temporary buffers of the decoders return
+// automatically.
+#[derive(Debug)]
+pub struct Pool {
+    free: Vec<Vec<u8>>,
+    max_buffers: usize,
+    reused: usize,
+}
+
+// ----------------------------------------------------------------------------
+impl Pool {
+    pub fn new(max_buffers: usize) -> Self {
+        Pool {
+            free: Vec::new(),
+            max_buffers,
+            reused: 0,
+        }
+    }
+
+    // number of buffers available for reuse
+    pub fn len(&self) -> usize {
+        self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.free.is_empty()
+    }
+
+    // number of allocations served from the pool
+    pub fn reused(&self) -> usize {
+        self.reused
+    }
+
+    pub fn recycle(&mut self, buffer: Vec<u8>) {
+        if self.free.len() < self.max_buffers && buffer.capacity() != 0 {
+            self.free.push(buffer);
+        }
+    }
+
+    // the smallest free buffer of at least `len` bytes capacity, otherwise the largest one
+    fn take(&mut self, len: usize) -> Option<Vec<u8>> {
+        let index = (0..self.free.len()).min_by_key(|&i| {
+            let capacity = self.free[i].capacity();
+            (capacity < len, capacity.abs_diff(len))
+        })?;
+        self.reused += 1;
+        let mut buffer = self.free.swap_remove(index);
+        buffer.clear();
+        Some(buffer)
+    }
+}
+
+// ----------------------------------------------------------------------------
+impl BufferProvider for Pool {
+    type Buffer = Vec<u8>;
+
+    fn alloc(&mut self, len: usize) -> Option<Vec<u8>> {
+        let mut buffer = self.take(len).unwrap_or_default();
+        buffer.resize(len, 0);
+        Some(buffer)
+    }
+
+    fn scratch(&mut self, len: usize) -> Option<Scratch<Vec<u8>>> {
+        self.alloc(len).map(Scratch::Heap)
+    }
+
+    fn release(&mut self, buffer: Scratch<Vec<u8>>) {
+        match buffer {
+            Scratch::Heap(buffer) | Scratch::Provided(buffer) => self.recycle(buffer),
+        }
+    }
+}
@@ -0,0 +1,522 @@
+// bzip2 decompression, as used by ZIP compression method 12.
+// * https://sourceware.org/bzip2/
+// * a stream is "BZh" and the block size level, then blocks of up to level * 100000 bytes and an
+//   end of stream marker with the combined CRC, concatenated streams are decoded one after the
+//   other
+// * each block undoes Huffman coding, the run-length coding of zeros (RUNA/RUNB), move-to-front,
+//   the Burrows-Wheeler transform and finally the initial run-length coding of the input
+use alloc::vec::Vec;
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Error {
+    Underflow,
+    Overflow,
+    // missing "BZh" signature or block size level
+    InvalidHeader,
+    // neither a block nor an end of stream marker
+    InvalidBlockSignature,
+    // malformed symbol map, Huffman tables, selectors or block data
+    InvalidData,
+    // block or stream CRC differs from the decoded data
+    ChecksumMismatch,
+    // blocks of bzip2 0.9.0 with randomised input are not supported
+    RandomizedBlock,
+    LimitExceeded,
+}
+
+// ----------------------------------------------------------------------------
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+// ----------------------------------------------------------------------------
+// Stable numeric codes (400-499) for FFI and logging, codes are never reused or renumbered.
+impl Error {
+    pub fn code(&self) -> u32 {
+        use Error::*;
+        match self {
+            Underflow => 400,
+            Overflow => 401,
+            InvalidHeader => 402,
+            InvalidBlockSignature => 403,
+            InvalidData => 404,
+            ChecksumMismatch => 405,
+            RandomizedBlock => 406,
+            LimitExceeded => 407,
+        }
+    }
+
+    pub fn from_code(code: u32) -> Option<Self> {
+        use Error::*;
+        Some(match code {
+            400 => Underflow,
+            401 => Overflow,
+            402 => InvalidHeader,
+            403 => InvalidBlockSignature,
+            404 => InvalidData,
+            405 => ChecksumMismatch,
+            406 => RandomizedBlock,
+            407 => LimitExceeded,
+            _ => return None,
+        })
+    }
+}
+
+// ----------------------------------------------------------------------------
+const BLOCK_MAGIC: u64 = 0x3141_5926_5359;
+const END_MAGIC: u64 = 0x1772_4538_5090;
+
+const MAX_GROUPS: usize = 6;
+const GROUP_SIZE: usize = 50;
+const MAX_ALPHA_SIZE: usize = 258;
+const MAX_CODE_LEN: usize = 20;
+// selectors beyond this count are read and ignored, as bzip2 1.0.8 does
+const MAX_SELECTORS: usize = 2 + (900000 / GROUP_SIZE);
+
+// ----------------------------------------------------------------------------
+// CRC-32 of bzip2: polynomial 0x04c11db7, MSB first.
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u32) << 24;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04c1_1db7
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+// ----------------------------------------------------------------------------
+// MSB-first bit reader, bzip2 packs all fields starting with their most significant bit.
+struct BitReader<'a> {
+    src: &'a [u8],
+    pos: usize,
+    bits: u64,
+    count: u32,
+}
+
+// ----------------------------------------------------------------------------
+impl<'a> BitReader<'a> {
+    fn new(src: &'a [u8]) -> Self {
+        BitReader {
+            src,
+            pos: 0,
+            bits: 0,
+            count: 0,
+        }
+    }
+
+    // Reads `count` bits, up to 32.
+    fn read(&mut self, count: u32) -> Result<u32, Error> {
+        while self.count < count {
+            let byte = *self.src.get(self.pos).ok_or(Error::Underflow)?;
+            self.bits = (self.bits << 8) | byte as u64;
+            self.pos += 1;
+            self.count += 8;
+        }
+        self.count -= count;
+        Ok(((self.bits >> self.count) & ((1 << count) - 1)) as u32)
+    }
+
+    fn read_bit(&mut self) -> Result<bool, Error> {
+        Ok(self.read(1)? != 0)
+    }
+
+    fn read_u48(&mut self) -> Result<u64, Error> {
+        Ok(((self.read(24)? as u64) << 24) | self.read(24)? as u64)
+    }
+
+    // Skips the padding bits of the last byte, returns the bytes consumed so far.
+    fn align_to_byte(&mut self) -> usize {
+        self.count -= self.count % 8;
+        self.pos - (self.count / 8) as usize
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Canonical Huffman decoding table of a coding group, codes are at most 20 bits long.
+struct HuffmanTable {
+    // largest code of each length, -1 outside of the used lengths
+    limit: [i32; MAX_CODE_LEN + 2],
+    // first code of each length minus the index of its first symbol in `perm`
+    base: [i32; MAX_CODE_LEN + 2],
+    // symbols sorted by code length
+    perm: [u16; MAX_ALPHA_SIZE],
+    min_len: u32,
+}
+
+// ----------------------------------------------------------------------------
+impl HuffmanTable {
+    fn new(lengths: &[u8]) -> Self {
+        let min_len = lengths.iter().copied().min().unwrap_or(1) as usize;
+        let max_len = lengths.iter().copied().max().unwrap_or(1) as usize;
+
+        let mut perm = [0; MAX_ALPHA_SIZE];
+        let mut pp = 0;
+        for len in min_len..=max_len {
+            for (symbol, _) in lengths
+                .iter()
+                .enumerate()
+                .filter(|(_, l)| **l as usize == len)
+            {
+                perm[pp] = symbol as u16;
+                pp += 1;
+            }
+        }
+
+        // number of codes shorter than each length
+        let mut count = [0; MAX_CODE_LEN + 2];
+        for len in lengths {
+            count[*len as usize + 1] += 1;
+        }
+        for i in 1..count.len() {
+            count[i] += count[i - 1];
+        }
+
+        let mut limit = [-1; MAX_CODE_LEN + 2];
+        let mut base = [0; MAX_CODE_LEN + 2];
+        let mut code = 0;
+        for len in min_len..=max_len {
+            code += count[len + 1] - count[len];
+            limit[len] = code - 1;
+            code <<= 1;
+        }
+        for len in min_len + 1..=max_len {
+            base[len] = ((limit[len - 1] + 1) << 1) - count[len];
+        }
+        base[min_len] = count[min_len];
+
+        HuffmanTable {
+            limit,
+            base,
+            perm,
+            min_len: min_len as u32,
+        }
+    }
+
+    fn decode(&self, br: &mut BitReader, alpha_size: usize) -> Result<u16, Error> {
+        let mut len = self.min_len as usize;
+        let mut code = br.read(self.min_len)? as i32;
+        while code > self.limit[len] {
+            len += 1;
+            if len > MAX_CODE_LEN {
+                return Err(Error::InvalidData);
+            }
+            code = (code << 1) | br.read(1)? as i32;
+        }
+        let idx = code - self.base[len];
+        match usize::try_from(idx) {
+            Ok(idx) if idx < alpha_size => Ok(self.perm[idx]),
+            _ => Err(Error::InvalidData),
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Destination of the decoded bytes.
+trait Sink {
+    fn put(&mut self, byte: u8, count: usize) -> Result<(), Error>;
+}
+
+// ----------------------------------------------------------------------------
+struct SliceSink<'a> {
+    dst: &'a mut [u8],
+    pos: usize,
+}
+
+// ----------------------------------------------------------------------------
+impl Sink for SliceSink<'_> {
+    fn put(&mut self, byte: u8, count: usize) -> Result<(), Error> {
+        let out = self
+            .dst
+            .get_mut(self.pos..self.pos + count)
+            .ok_or(Error::Overflow)?;
+        out.fill(byte);
+        self.pos += count;
+        Ok(())
+    }
+}
+
+// ----------------------------------------------------------------------------
+struct VecSink<'a> {
+    out: &'a mut Vec<u8>,
+    max_output: usize,
+}
+
+// ----------------------------------------------------------------------------
+impl Sink for VecSink<'_> {
+    fn put(&mut self, byte: u8, count: usize) -> Result<(), Error> {
+        if count > self.max_output - self.out.len() {
+            return Err(Error::LimitExceeded);
+        }
+        self.out.resize(self.out.len() + count, byte);
+        Ok(())
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Decodes the bzip2 streams of `src` into `dst`, returns the number of decoded bytes. Output
+// beyond `dst` fails with `Error::Overflow`.
+pub fn decompress(dst: &mut [u8], src: &[u8]) -> Result<usize, Error> {
+    let mut sink = SliceSink { dst, pos: 0 };
+    decode_streams(&mut sink, src)?;
+    Ok(sink.pos)
+}
+
+// ----------------------------------------------------------------------------
+// Decodes the bzip2 streams of `src` into a new vector, output beyond `max_output` bytes fails
+// with `Error::LimitExceeded`.
+pub fn decompress_to_vec(src: &[u8], max_output: usize) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::new();
+    let mut sink = VecSink {
+        out: &mut out,
+        max_output,
+    };
+    decode_streams(&mut sink, src)?;
+    Ok(out)
+}
+
+// ----------------------------------------------------------------------------
+// Decodes a stream and the streams concatenated to it, trailing data that doesn't start with a
+// stream header is ignored. Returns the bytes consumed.
+fn decode_streams(sink: &mut impl Sink, src: &[u8]) -> Result<usize, Error> {
+    let mut consumed = decode_stream(sink, src)?;
+    while src[consumed..].starts_with(b"BZh") {
+        consumed += decode_stream(sink, &src[consumed..])?;
+    }
+    Ok(consumed)
+}
+
+// ----------------------------------------------------------------------------
+fn decode_stream(sink: &mut impl Sink, src: &[u8]) -> Result<usize, Error> {
+    let level = match src {
+        [b'B', b'Z', b'h', level @ b'1'..=b'9', ..] => (level - b'0') as usize,
+        _ => return Err(Error::InvalidHeader),
+    };
+    let mut br = BitReader::new(&src[4..]);
+    let mut tt = Vec::new();
+    let mut combined_crc = 0u32;
+    loop {
+        match br.read_u48()? {
+            BLOCK_MAGIC => {
+                let block_crc = br.read(32)?;
+                decode_block(&mut br, &mut tt, level * 100000)?;
+                if decode_rle(sink, &tt)? != block_crc {
+                    return Err(Error::ChecksumMismatch);
+                }
+                combined_crc = combined_crc.rotate_left(1) ^ block_crc;
+            }
+            END_MAGIC => {
+                if br.read(32)? != combined_crc {
+                    return Err(Error::ChecksumMismatch);
+                }
+                return Ok(4 + br.align_to_byte());
+            }
+            _ => return Err(Error::InvalidBlockSignature),
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Decodes a block up to the inverse Burrows-Wheeler transform. Leaves the BWT vector in `tt`:
+// the byte of each position in the low 8 bits and the position that follows it above, followed
+// by the starting position.
+fn decode_block(br: &mut BitReader, tt: &mut Vec<u32>, max_len: usize) -> Result<(), Error> {
+    if br.read_bit()? {
+        return Err(Error::RandomizedBlock);
+    }
+    let orig_ptr = br.read(24)? as usize;
+
+    // bytes used in the block, a 16 bit map of 16 byte ranges each with a 16 bit map
+    let mut seq_to_unseq = [0u8; 256];
+    let mut in_use = 0;
+    let ranges = br.read(16)?;
+    for range in 0..16 {
+        if ranges & (0x8000 >> range) != 0 {
+            let used = br.read(16)?;
+            for byte in 0..16 {
+                if used & (0x8000 >> byte) != 0 {
+                    seq_to_unseq[in_use] = (range * 16 + byte) as u8;
+                    in_use += 1;
+                }
+            }
+        }
+    }
+    if in_use == 0 {
+        return Err(Error::InvalidData);
+    }
+    // RUNA, RUNB, the move-to-front indices 1 to in_use - 1 and the end of block
+    let alpha_size = in_use + 2;
+
+    let groups = br.read(3)? as usize;
+    let selector_count = br.read(15)? as usize;
+    if !(2..=MAX_GROUPS).contains(&groups) || selector_count == 0 {
+        return Err(Error::InvalidData);
+    }
+
+    // selectors are move-to-front coded in unary
+    let mut mtf_groups = [0u8, 1, 2, 3, 4, 5];
+    let mut selectors = Vec::with_capacity(selector_count.min(MAX_SELECTORS));
+    for _ in 0..selector_count {
+        let mut idx = 0;
+        while br.read_bit()? {
+            idx += 1;
+            if idx >= groups {
+                return Err(Error::InvalidData);
+            }
+        }
+        let group = mtf_groups[idx];
+        mtf_groups.copy_within(0..idx, 1);
+        mtf_groups[0] = group;
+        if selectors.len() < MAX_SELECTORS {
+            selectors.push(group);
+        }
+    }
+
+    // code lengths of each group, delta coded
+    let mut tables = Vec::with_capacity(groups);
+    for _ in 0..groups {
+        let mut lengths = [0u8; MAX_ALPHA_SIZE];
+        let mut len = br.read(5)? as i32;
+        for length in &mut lengths[..alpha_size] {
+            loop {
+                if !(1..=MAX_CODE_LEN as i32).contains(&len) {
+                    return Err(Error::InvalidData);
+                }
+                if !br.read_bit()? {
+                    break;
+                }
+                len += if br.read_bit()? { -1 } else { 1 };
+            }
+            *length = len as u8;
+        }
+        tables.push(HuffmanTable::new(&lengths[..alpha_size]));
+    }
+
+    // move-to-front indices with runs of index 0 coded as bijective base 2 numbers
+    let end_of_block = (in_use + 1) as u16;
+    let mut mtf: [u8; 256] = core::array::from_fn(|i| i as u8);
+    let mut counts = [0usize; 256];
+    let mut run = 0;
+    let mut run_weight = 1;
+    let mut selector = 0;
+    let mut group_left = 0;
+    let mut table = &tables[0];
+    tt.clear();
+    loop {
+        if group_left == 0 {
+            let group = *selectors.get(selector).ok_or(Error::InvalidData)?;
+            table = &tables[group as usize];
+            selector += 1;
+            group_left = GROUP_SIZE;
+        }
+        group_left -= 1;
+
+        let symbol = table.decode(br, alpha_size)?;
+        if symbol <= 1 {
+            // RUNA adds the weight, RUNB twice the weight
+            if run_weight > max_len {
+                return Err(Error::InvalidData);
+            }
+            run += (symbol as usize + 1) * run_weight;
+            run_weight <<= 1;
+            continue;
+        }
+
+        if run > 0 {
+            if run > max_len - tt.len() {
+                return Err(Error::InvalidData);
+            }
+            let byte = seq_to_unseq[mtf[0] as usize];
+            counts[byte as usize] += run;
+            tt.resize(tt.len() + run, byte as u32);
+            run = 0;
+            run_weight = 1;
+        }
+
+        if symbol == end_of_block {
+            break;
+        }
+
+        if tt.len() >= max_len {
+            return Err(Error::InvalidData);
+        }
+        let idx = (symbol - 1) as usize;
+        let value = mtf[idx];
+        mtf.copy_within(0..idx, 1);
+        mtf[0] = value;
+        let byte = seq_to_unseq[value as usize];
+        counts[byte as usize] += 1;
+        tt.push(byte as u32);
+    }
+
+    if orig_ptr >= tt.len() {
+        return Err(Error::InvalidData);
+    }
+
+    // inverse BWT: link each position to the next one in sorted order
+    let mut next = [0usize; 256];
+    let mut sum = 0;
+    for (next, count) in next.iter_mut().zip(counts) {
+        *next = sum;
+        sum += count;
+    }
+    for i in 0..tt.len() {
+        let byte = (tt[i] & 0xff) as usize;
+        tt[next[byte]] |= (i as u32) << 8;
+        next[byte] += 1;
+    }
+    let start = tt[orig_ptr] >> 8;
+    tt.push(start);
+    Ok(())
+}
+
+// ----------------------------------------------------------------------------
+// Follows the BWT vector and undoes the initial run-length coding: four equal bytes are followed
+// by the count of further repetitions. Returns the CRC of the decoded bytes.
+fn decode_rle(sink: &mut impl Sink, tt: &[u32]) -> Result<u32, Error> {
+    let (start, tt) = tt.split_last().ok_or(Error::InvalidData)?;
+    let mut pos = *start as usize;
+    let mut crc = 0xffff_ffffu32;
+    let mut last = 0;
+    let mut repeats = 0;
+    for _ in 0..tt.len() {
+        let entry = tt[pos];
+        pos = (entry >> 8) as usize;
+        let byte = entry as u8;
+        if repeats == 4 {
+            for _ in 0..byte {
+                crc = (crc << 8) ^ CRC_TABLE[((crc >> 24) as u8 ^ last) as usize];
+            }
+            sink.put(last, byte as usize)?;
+            repeats = 0;
+            continue;
+        }
+        if repeats > 0 && byte == last {
+            repeats += 1;
+        } else {
+            repeats = 1;
+            last = byte;
+        }
+        crc = (crc << 8) ^ CRC_TABLE[((crc >> 24) as u8 ^ byte) as usize];
+        sink.put(byte, 1)?;
+    }
+    Ok(!crc)
+}
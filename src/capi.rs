@@ -0,0 +1,422 @@
+// zlib- and miniz-compatible C API.
+// * https://www.zlib.net/manual.html#Utility
+// * exports compress2, compressBound, uncompress, uncompress2, crc32, adler32, crc32_combine and
+//   adler32_combine with zlib signatures
+// * exports the mz_ counterparts and tdefl_compress_mem_to_mem and tinfl_decompress_mem_to_mem
+//   with the signatures of miniz.h, miniz return codes equal zlib's
+// * on Z_BUF_ERROR, `dest` holds the start of the output and `*dest_len` its length
+use crate::deflate::{CompressionLevel, Strategy};
+use crate::{checksum, deflate, inflate};
+use std::ffi::{c_char, c_int, c_long, c_uint, c_ulong, c_void};
+
+// ----------------------------------------------------------------------------
+pub const Z_OK: c_int = 0;
+pub const Z_STREAM_ERROR: c_int = -2;
+pub const Z_DATA_ERROR: c_int = -3;
+pub const Z_MEM_ERROR: c_int = -4;
+pub const Z_BUF_ERROR: c_int = -5;
+
+pub const MZ_OK: c_int = Z_OK;
+pub const MZ_STREAM_END: c_int = 1;
+pub const MZ_NEED_DICT: c_int = 2;
+pub const MZ_ERRNO: c_int = -1;
+pub const MZ_STREAM_ERROR: c_int = Z_STREAM_ERROR;
+pub const MZ_DATA_ERROR: c_int = Z_DATA_ERROR;
+pub const MZ_MEM_ERROR: c_int = Z_MEM_ERROR;
+pub const MZ_BUF_ERROR: c_int = Z_BUF_ERROR;
+pub const MZ_VERSION_ERROR: c_int = -6;
+pub const MZ_PARAM_ERROR: c_int = -10000;
+
+pub const MZ_DEFAULT_COMPRESSION: c_int = -1;
+pub const MZ_UBER_COMPRESSION: c_int = 10;
+
+// tinfl_decompress_mem_to_mem flags, the Adler-32 of zlib streams is always checked
+pub const TINFL_FLAG_PARSE_ZLIB_HEADER: c_int = 1;
+pub const TINFL_FLAG_HAS_MORE_INPUT: c_int = 2;
+pub const TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF: c_int = 4;
+pub const TINFL_FLAG_COMPUTE_ADLER32: c_int = 8;
+pub const TINFL_DECOMPRESS_MEM_TO_MEM_FAILED: usize = usize::MAX;
+
+// tdefl_compress_mem_to_mem flags, the low 12 bits are the number of match probes
+pub const TDEFL_MAX_PROBES_MASK: c_int = 0xfff;
+pub const TDEFL_DEFAULT_MAX_PROBES: c_int = 128;
+pub const TDEFL_WRITE_ZLIB_HEADER: c_int = 0x01000;
+pub const TDEFL_COMPUTE_ADLER32: c_int = 0x02000;
+pub const TDEFL_GREEDY_PARSING_FLAG: c_int = 0x04000;
+pub const TDEFL_NONDETERMINISTIC_PARSING_FLAG: c_int = 0x08000;
+pub const TDEFL_RLE_MATCHES: c_int = 0x10000;
+pub const TDEFL_FILTER_MATCHES: c_int = 0x20000;
+pub const TDEFL_FORCE_ALL_STATIC_BLOCKS: c_int = 0x40000;
+pub const TDEFL_FORCE_ALL_RAW_BLOCKS: c_int = 0x80000;
+
+// probes of miniz levels 0 to 10
+const NUM_PROBES: [c_int; 11] = [0, 1, 6, 32, 16, 32, 128, 256, 512, 768, 1500];
+
+// ----------------------------------------------------------------------------
+unsafe fn slice<'a>(ptr: *const u8, len: usize) -> &'a [u8] {
+    if ptr.is_null() || len == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(ptr, len)
+    }
+}
+
+// ----------------------------------------------------------------------------
+unsafe fn slice_mut<'a>(ptr: *mut u8, len: usize) -> &'a mut [u8] {
+    if ptr.is_null() || len == 0 {
+        &mut []
+    } else {
+        std::slice::from_raw_parts_mut(ptr, len)
+    }
+}
+
+// ----------------------------------------------------------------------------
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "C" fn compressBound(source_len: c_ulong) -> c_ulong {
+    // saturates instead of wrapping for lengths no buffer can have
+    source_len.saturating_add((source_len >> 12) + (source_len >> 14) + (source_len >> 25) + 13)
+}
+
+// ----------------------------------------------------------------------------
+/// # Safety
+/// `dest` must be valid for `*dest_len` bytes of writes and `source` for `source_len` bytes of
+/// reads, as with zlib's `compress2`.
+#[no_mangle]
+pub unsafe extern "C" fn compress2(
+    dest: *mut u8,
+    dest_len: *mut c_ulong,
+    source: *const u8,
+    source_len: c_ulong,
+    level: c_int,
+) -> c_int {
+    if dest_len.is_null() || !(-1..=9).contains(&level) {
+        return Z_STREAM_ERROR;
+    }
+    compress_level(dest, dest_len, source, source_len, level)
+}
+
+// ----------------------------------------------------------------------------
+// `level` from -1 (default) to 10, 10 compresses like 9.
+unsafe fn compress_level(
+    dest: *mut u8,
+    dest_len: *mut c_ulong,
+    source: *const u8,
+    source_len: c_ulong,
+    level: c_int,
+) -> c_int {
+    let dst = slice_mut(dest, *dest_len as usize);
+    let src = slice(source, source_len as usize);
+
+    let level = match level {
+        -1 => CompressionLevel::DefaultLevel,
+        level => CompressionLevel::from(level as u8),
+    };
+    let mut packed = Vec::with_capacity(src.len() / 2 + 16);
+    deflate::deflate_zlib_level(&mut packed, src, level);
+
+    // like zlib, `dest` is filled with the start of the stream if it is too small
+    let len = packed.len().min(dst.len());
+    dst[..len].copy_from_slice(&packed[..len]);
+    *dest_len = len as c_ulong;
+    if len < packed.len() {
+        Z_BUF_ERROR
+    } else {
+        Z_OK
+    }
+}
+
+// ----------------------------------------------------------------------------
+/// # Safety
+/// `dest` must be valid for `*dest_len` bytes of writes and `source` for `source_len` bytes of
+/// reads, as with zlib's `uncompress`.
+#[no_mangle]
+pub unsafe extern "C" fn uncompress(
+    dest: *mut u8,
+    dest_len: *mut c_ulong,
+    source: *const u8,
+    source_len: c_ulong,
+) -> c_int {
+    let mut source_len = source_len;
+    uncompress2(dest, dest_len, source, &mut source_len)
+}
+
+// ----------------------------------------------------------------------------
+/// # Safety
+/// `dest` must be valid for `*dest_len` bytes of writes and `source` for `*source_len` bytes of
+/// reads, as with zlib's `uncompress2`.
+#[no_mangle]
+pub unsafe extern "C" fn uncompress2(
+    dest: *mut u8,
+    dest_len: *mut c_ulong,
+    source: *const u8,
+    source_len: *mut c_ulong,
+) -> c_int {
+    if dest_len.is_null() || source_len.is_null() {
+        return Z_STREAM_ERROR;
+    }
+
+    // data behind the Adler-32 trailer is ignored, `*source_len` returns the stream length
+    let dst = slice_mut(dest, *dest_len as usize);
+    let src = slice(source, *source_len as usize);
+    let options = inflate::InflateOptions::default();
+    match inflate::inflate_zlib_consumed(dst, src, &options) {
+        Ok((len, consumed)) => {
+            *dest_len = len as c_ulong;
+            *source_len = consumed as c_ulong;
+            Z_OK
+        }
+        Err(inflate::Error::Overflow) => {
+            *dest_len = inflate_prefix(dst, src) as c_ulong;
+            Z_BUF_ERROR
+        }
+        Err(_) => Z_DATA_ERROR,
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Fills `dst` with the start of the zlib stream `src` whose output does not fit, like zlib does
+// before it returns Z_BUF_ERROR. Returns the number of bytes written.
+fn inflate_prefix(dst: &mut [u8], src: &[u8]) -> usize {
+    let mut len = 0;
+    let data = src.get(2..).unwrap_or_default();
+    let full = inflate::inflate_to_sink(data, |block| {
+        let n = block.len().min(dst.len() - len);
+        dst[len..len + n].copy_from_slice(&block[..n]);
+        len += n;
+        if len == dst.len() {
+            Err(inflate::Error::Overflow)
+        } else {
+            Ok(())
+        }
+    });
+    // decoding stops with `Overflow` once `dst` is full
+    debug_assert!(full.is_err());
+    len
+}
+
+// ----------------------------------------------------------------------------
+/// # Safety
+/// `buf` must be null or valid for `len` bytes of reads.
+#[no_mangle]
+pub unsafe extern "C" fn crc32(crc: c_ulong, buf: *const u8, len: c_uint) -> c_ulong {
+    if buf.is_null() {
+        return 0;
+    }
+    checksum::crc32(crc as u32, slice(buf, len as usize)) as c_ulong
+}
+
+// ----------------------------------------------------------------------------
+/// # Safety
+/// `buf` must be null or valid for `len` bytes of reads.
+#[no_mangle]
+pub unsafe extern "C" fn adler32(adler: c_ulong, buf: *const u8, len: c_uint) -> c_ulong {
+    if buf.is_null() {
+        return 1;
+    }
+    checksum::adler32(adler as u32, slice(buf, len as usize)) as c_ulong
+}
+
+// ----------------------------------------------------------------------------
+#[no_mangle]
+pub extern "C" fn crc32_combine(crc1: c_ulong, crc2: c_ulong, len2: c_long) -> c_ulong {
+    checksum::crc32_combine(crc1 as u32, crc2 as u32, len2.max(0) as u64) as c_ulong
+}
+
+// ----------------------------------------------------------------------------
+#[no_mangle]
+pub extern "C" fn adler32_combine(adler1: c_ulong, adler2: c_ulong, len2: c_long) -> c_ulong {
+    // zlib's result for a negative length
+    if len2 < 0 {
+        return 0xffffffff;
+    }
+    checksum::adler32_combine(adler1 as u32, adler2 as u32, len2 as u64) as c_ulong
+}
+
+// ----------------------------------------------------------------------------
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "C" fn mz_compressBound(source_len: c_ulong) -> c_ulong {
+    compressBound(source_len)
+}
+
+// ----------------------------------------------------------------------------
+/// # Safety
+/// As with `compress2`.
+#[no_mangle]
+pub unsafe extern "C" fn mz_compress(
+    dest: *mut u8,
+    dest_len: *mut c_ulong,
+    source: *const u8,
+    source_len: c_ulong,
+) -> c_int {
+    mz_compress2(dest, dest_len, source, source_len, MZ_DEFAULT_COMPRESSION)
+}
+
+// ----------------------------------------------------------------------------
+/// # Safety
+/// As with `compress2`.
+#[no_mangle]
+pub unsafe extern "C" fn mz_compress2(
+    dest: *mut u8,
+    dest_len: *mut c_ulong,
+    source: *const u8,
+    source_len: c_ulong,
+    level: c_int,
+) -> c_int {
+    if dest_len.is_null() {
+        return MZ_STREAM_ERROR;
+    }
+    if !(MZ_DEFAULT_COMPRESSION..=MZ_UBER_COMPRESSION).contains(&level) {
+        return MZ_PARAM_ERROR;
+    }
+    compress_level(dest, dest_len, source, source_len, level)
+}
+
+// ----------------------------------------------------------------------------
+/// # Safety
+/// As with `uncompress`.
+#[no_mangle]
+pub unsafe extern "C" fn mz_uncompress(
+    dest: *mut u8,
+    dest_len: *mut c_ulong,
+    source: *const u8,
+    source_len: c_ulong,
+) -> c_int {
+    uncompress(dest, dest_len, source, source_len)
+}
+
+// ----------------------------------------------------------------------------
+/// # Safety
+/// As with `uncompress2`.
+#[no_mangle]
+pub unsafe extern "C" fn mz_uncompress2(
+    dest: *mut u8,
+    dest_len: *mut c_ulong,
+    source: *const u8,
+    source_len: *mut c_ulong,
+) -> c_int {
+    uncompress2(dest, dest_len, source, source_len)
+}
+
+// ----------------------------------------------------------------------------
+/// # Safety
+/// `buf` must be null or valid for `len` bytes of reads.
+#[no_mangle]
+pub unsafe extern "C" fn mz_crc32(crc: c_ulong, buf: *const u8, len: usize) -> c_ulong {
+    if buf.is_null() {
+        return 0;
+    }
+    checksum::crc32(crc as u32, slice(buf, len)) as c_ulong
+}
+
+// ----------------------------------------------------------------------------
+/// # Safety
+/// `buf` must be null or valid for `len` bytes of reads.
+#[no_mangle]
+pub unsafe extern "C" fn mz_adler32(adler: c_ulong, buf: *const u8, len: usize) -> c_ulong {
+    if buf.is_null() {
+        return 1;
+    }
+    checksum::adler32(adler as u32, slice(buf, len)) as c_ulong
+}
+
+// ----------------------------------------------------------------------------
+// Static description of a return code, null for unknown codes.
+#[no_mangle]
+pub extern "C" fn mz_error(err: c_int) -> *const c_char {
+    let msg: &'static std::ffi::CStr = match err {
+        MZ_OK => c"",
+        MZ_STREAM_END => c"stream end",
+        MZ_NEED_DICT => c"need dictionary",
+        MZ_ERRNO => c"file error",
+        MZ_STREAM_ERROR => c"stream error",
+        MZ_DATA_ERROR => c"data error",
+        MZ_MEM_ERROR => c"out of memory",
+        MZ_BUF_ERROR => c"buf error",
+        MZ_VERSION_ERROR => c"version error",
+        MZ_PARAM_ERROR => c"parameter error",
+        _ => return std::ptr::null(),
+    };
+    msg.as_ptr()
+}
+
+// ----------------------------------------------------------------------------
+/// # Safety
+/// `out_buf` must be valid for `out_buf_len` bytes of writes and `src_buf` for `src_buf_len`
+/// bytes of reads, as with miniz's `tinfl_decompress_mem_to_mem`.
+#[no_mangle]
+pub unsafe extern "C" fn tinfl_decompress_mem_to_mem(
+    out_buf: *mut c_void,
+    out_buf_len: usize,
+    src_buf: *const c_void,
+    src_buf_len: usize,
+    flags: c_int,
+) -> usize {
+    // data behind the stream is ignored, the whole stream has to fit into `out_buf`
+    let dst = slice_mut(out_buf.cast(), out_buf_len);
+    let src = slice(src_buf.cast(), src_buf_len);
+    let options = inflate::InflateOptions::default();
+    let res = if flags & TINFL_FLAG_PARSE_ZLIB_HEADER != 0 {
+        inflate::inflate_zlib_consumed(dst, src, &options)
+    } else {
+        inflate::inflate_consumed(dst, src, &options)
+    };
+    match res {
+        Ok((len, _)) => len,
+        Err(_) => TINFL_DECOMPRESS_MEM_TO_MEM_FAILED,
+    }
+}
+
+// ----------------------------------------------------------------------------
+/// # Safety
+/// `out_buf` must be valid for `out_buf_len` bytes of writes and `src_buf` for `src_buf_len`
+/// bytes of reads, as with miniz's `tdefl_compress_mem_to_mem`.
+#[no_mangle]
+pub unsafe extern "C" fn tdefl_compress_mem_to_mem(
+    out_buf: *mut c_void,
+    out_buf_len: usize,
+    src_buf: *const c_void,
+    src_buf_len: usize,
+    flags: c_int,
+) -> usize {
+    let dst = slice_mut(out_buf.cast(), out_buf_len);
+    let src = slice(src_buf.cast(), src_buf_len);
+
+    // the level whose probe count covers the requested one, the parsing flags have no
+    // counterpart and are ignored
+    let probes = flags & TDEFL_MAX_PROBES_MASK;
+    let level = NUM_PROBES.iter().position(|&n| n >= probes).unwrap_or(10);
+    let level = match flags & TDEFL_FORCE_ALL_RAW_BLOCKS {
+        0 => CompressionLevel::from(level as u8),
+        _ => CompressionLevel::NoCompression,
+    };
+    let strategy = if flags & TDEFL_FORCE_ALL_STATIC_BLOCKS != 0 {
+        Strategy::Fixed
+    } else if flags & TDEFL_RLE_MATCHES != 0 {
+        Strategy::Rle
+    } else if flags & TDEFL_FILTER_MATCHES != 0 {
+        Strategy::Filtered
+    } else {
+        Strategy::Default
+    };
+
+    let zlib = flags & TDEFL_WRITE_ZLIB_HEADER != 0;
+    let mut packed = Vec::with_capacity(src.len() / 2 + 16);
+    if zlib {
+        packed.extend_from_slice(&deflate::ZLIB_HEADER);
+    }
+    deflate::deflate_with_strategy(&mut packed, src, level, strategy);
+    if zlib {
+        packed.extend_from_slice(&checksum::adler32(1, src).to_be_bytes());
+    }
+
+    // 0 reports a too small `out_buf`
+    match dst.get_mut(..packed.len()) {
+        Some(dst) => {
+            dst.copy_from_slice(&packed);
+            packed.len()
+        }
+        None => 0,
+    }
+}
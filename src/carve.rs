@@ -0,0 +1,128 @@
+// Carving of deflate, zlib and gzip streams out of arbitrary binary data.
+// * every offset with a zlib or gzip header is a candidate, raw deflate data optionally at every
+//   offset
+// * a candidate is reported if it decodes completely and its checksum matches, the scan resumes
+//   after the end of the stream
+use crate::checksum;
+use crate::inflate::{self, InflateOptions};
+use alloc::{vec, vec::Vec};
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StreamKind {
+    Deflate,
+    Zlib,
+    Gzip,
+}
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone, PartialEq)]
+pub struct CarvedStream {
+    pub kind: StreamKind,
+    // byte range of the stream including header and trailer
+    pub offset: usize,
+    pub len: usize,
+    pub decompressed_len: usize,
+    // the first `CarveOptions::preview_len` decompressed bytes
+    pub preview: Vec<u8>,
+}
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone, Copy)]
+pub struct CarveOptions {
+    // also try raw deflate data at every offset, prone to short false positives
+    pub raw_deflate: bool,
+    // streams decompressing to fewer bytes are ignored
+    pub min_output: usize,
+    // streams decompressing to more bytes are ignored
+    pub max_output: usize,
+    pub preview_len: usize,
+}
+
+// ----------------------------------------------------------------------------
+impl Default for CarveOptions {
+    fn default() -> Self {
+        CarveOptions {
+            raw_deflate: false,
+            min_output: 1,
+            max_output: 1 << 24,
+            preview_len: 64,
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Decodes the stream of `kind` at the start of `src`, returns (stream length, output length).
+fn try_decode(kind: StreamKind, src: &[u8], out: &mut [u8]) -> Option<(usize, usize)> {
+    let options = InflateOptions {
+        strict_end_of_input: true,
+        ..Default::default()
+    };
+
+    match kind {
+        StreamKind::Deflate => {
+            let (len, consumed) = inflate::inflate_consumed(out, src, &options).ok()?;
+            Some((consumed, len))
+        }
+        StreamKind::Zlib => {
+            inflate::zlib_header(src).ok()?;
+            let (len, consumed) = inflate::inflate_consumed(out, &src[2..], &options).ok()?;
+            let end = 2 + consumed;
+            let adler = src.get(end..end + 4)?;
+            let ok = adler == checksum::adler32(1, &out[..len]).to_be_bytes();
+            ok.then_some((end + 4, len))
+        }
+        StreamKind::Gzip => {
+            let start = inflate::gzip_header(src).ok()?;
+            let (len, consumed) = inflate::inflate_consumed(out, &src[start..], &options).ok()?;
+            let end = start + consumed;
+            let trailer = src.get(end..end + 8)?;
+            let ok = trailer[..4] == checksum::crc32(0, &out[..len]).to_le_bytes()
+                && trailer[4..] == (len as u32).to_le_bytes();
+            ok.then_some((end + 8, len))
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Scans `data` for compressed streams, returned in order of their offsets.
+pub fn carve(data: &[u8], options: &CarveOptions) -> Vec<CarvedStream> {
+    let mut out = vec![0u8; options.max_output];
+    let mut streams = Vec::new();
+    let mut offset = 0;
+
+    while offset < data.len() {
+        let src = &data[offset..];
+        let candidates = [
+            (StreamKind::Gzip, src.starts_with(&[0x1f, 0x8b, 8])),
+            (StreamKind::Zlib, inflate::zlib_header(src).is_ok()),
+            (StreamKind::Deflate, options.raw_deflate),
+        ];
+
+        let found = candidates
+            .iter()
+            .filter(|(_, candidate)| *candidate)
+            .find_map(|&(kind, _)| {
+                let (len, decompressed_len) = try_decode(kind, src, &mut out)?;
+                (decompressed_len >= options.min_output).then_some((kind, len, decompressed_len))
+            });
+
+        match found {
+            Some((kind, len, decompressed_len)) => {
+                trace_event!(debug, offset, len, kind = ?kind, "carved stream");
+                let preview_len = decompressed_len.min(options.preview_len);
+                streams.push(CarvedStream {
+                    kind,
+                    offset,
+                    len,
+                    decompressed_len,
+                    preview: out[..preview_len].to_vec(),
+                });
+                offset += len.max(1);
+            }
+            None => offset += 1,
+        }
+    }
+
+    streams
+}
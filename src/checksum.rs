@@ -0,0 +1,322 @@
+// CRC-32, CRC-64 and Adler-32 checksum implementation.
+// * CRC-32 (ISO 3309, reflected polynomial 0xedb88320) as used by ZIP, PNG and gzip
+// * CRC-64 (ECMA-182, reflected polynomial 0xc96c5795d7870f42) as used by xz
+// * Adler-32 as used by zlib: https://datatracker.ietf.org/doc/html/rfc1950
+
+// ----------------------------------------------------------------------------
+const fn make_crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xedb88320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+}
+
+// ----------------------------------------------------------------------------
+const CRC32_TABLE: [u32; 256] = make_crc32_table();
+
+// ----------------------------------------------------------------------------
+// Continues the CRC-32 `crc` over `data`, start with 0 for a new checksum.
+pub fn crc32(crc: u32, data: &[u8]) -> u32 {
+    let mut c = !crc;
+    for b in data {
+        c = CRC32_TABLE[((c ^ *b as u32) & 0xff) as usize] ^ (c >> 8);
+    }
+    !c
+}
+
+// ----------------------------------------------------------------------------
+const fn make_crc64_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u64;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xc96c5795d7870f42 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+}
+
+// ----------------------------------------------------------------------------
+// Product of the polynomials `a` and `b` modulo the CRC-32 polynomial, bit 31 is x^0.
+const fn multmodp(a: u32, mut b: u32) -> u32 {
+    let mut p = 0;
+    let mut i = 32;
+    while i > 0 {
+        i -= 1;
+        if (a >> i) & 1 != 0 {
+            p ^= b;
+        }
+        b = if b & 1 != 0 {
+            (b >> 1) ^ 0xedb88320
+        } else {
+            b >> 1
+        };
+    }
+    p
+}
+
+// ----------------------------------------------------------------------------
+// x^(2^n) modulo the CRC-32 polynomial
+const fn make_x2n_table() -> [u32; 32] {
+    let mut table = [0u32; 32];
+    let mut p = 1 << 30;
+    let mut n = 0;
+    while n < 32 {
+        table[n] = p;
+        p = multmodp(p, p);
+        n += 1;
+    }
+    table
+}
+
+// ----------------------------------------------------------------------------
+const X2N_TABLE: [u32; 32] = make_x2n_table();
+
+// ----------------------------------------------------------------------------
+// CRC-32 of the concatenation of two pieces from their CRCs `crc1` and `crc2` and the length of
+// the second piece, in O(log len2) like zlib's crc32_combine.
+pub fn crc32_combine(crc1: u32, crc2: u32, len2: u64) -> u32 {
+    // crc1 shifted by 8 * len2 zero bits
+    let mut p = 1 << 31;
+    let mut n = len2;
+    let mut k = 3;
+    while n != 0 {
+        if n & 1 != 0 {
+            p = multmodp(X2N_TABLE[k & 31], p);
+        }
+        n >>= 1;
+        k += 1;
+    }
+    multmodp(p, crc1) ^ crc2
+}
+
+// ----------------------------------------------------------------------------
+const CRC64_TABLE: [u64; 256] = make_crc64_table();
+
+// ----------------------------------------------------------------------------
+// Continues the CRC-64 `crc` over `data`, start with 0 for a new checksum.
+pub fn crc64(crc: u64, data: &[u8]) -> u64 {
+    let mut c = !crc;
+    for b in data {
+        c = CRC64_TABLE[((c ^ *b as u64) & 0xff) as usize] ^ (c >> 8);
+    }
+    !c
+}
+
+// ----------------------------------------------------------------------------
+// largest prime below 65536
+const BASE: u32 = 65521;
+
+// ----------------------------------------------------------------------------
+// Continues the Adler-32 `adler` over `data`, start with 1 for a new checksum.
+pub fn adler32(adler: u32, data: &[u8]) -> u32 {
+    // largest n such that 255 * n * (n + 1) / 2 + (n + 1) * (BASE - 1) fits in 32 bits
+    const NMAX: usize = 5552;
+
+    let mut a = adler & 0xffff;
+    let mut b = adler >> 16;
+    for chunk in data.chunks(NMAX) {
+        for x in chunk {
+            a += *x as u32;
+            b += a;
+        }
+        a %= BASE;
+        b %= BASE;
+    }
+    (b << 16) | a
+}
+
+// ----------------------------------------------------------------------------
+// Adler-32 of the concatenation of two pieces from their checksums `adler1` and `adler2` and the
+// length of the second piece, like zlib's adler32_combine.
+pub fn adler32_combine(adler1: u32, adler2: u32, len2: u64) -> u32 {
+    let rem = (len2 % BASE as u64) as u32;
+    let a1 = adler1 & 0xffff;
+    let a = (a1 + (adler2 & 0xffff) + BASE - 1) % BASE;
+    // each byte of the second piece adds the sum a1 of the first to b once more
+    let b = (rem as u64 * a1 as u64 % BASE as u64) as u32;
+    let b = (b + (adler1 >> 16) % BASE + (adler2 >> 16) % BASE + BASE - rem) % BASE;
+    (b << 16) | a
+}
+
+// ----------------------------------------------------------------------------
+// Incrementally updated checksum.
+pub trait Checksum {
+    fn update(&mut self, data: &[u8]);
+    fn value(&self) -> u32;
+}
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Crc32(u32);
+
+// ----------------------------------------------------------------------------
+impl Crc32 {
+    pub fn new() -> Self {
+        Crc32(0)
+    }
+}
+
+// ----------------------------------------------------------------------------
+impl Checksum for Crc32 {
+    fn update(&mut self, data: &[u8]) {
+        self.0 = crc32(self.0, data);
+    }
+
+    fn value(&self) -> u32 {
+        self.0
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Incrementally updated CRC-64, separate from `Checksum` which reports 32 bit values.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Crc64(u64);
+
+// ----------------------------------------------------------------------------
+impl Crc64 {
+    pub fn new() -> Self {
+        Crc64(0)
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.0 = crc64(self.0, data);
+    }
+
+    pub fn value(&self) -> u64 {
+        self.0
+    }
+}
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone, Copy)]
+pub struct Adler32(u32);
+
+// ----------------------------------------------------------------------------
+impl Adler32 {
+    pub fn new() -> Self {
+        Adler32(1)
+    }
+}
+
+// ----------------------------------------------------------------------------
+impl Default for Adler32 {
+    fn default() -> Self {
+        Adler32::new()
+    }
+}
+
+// ----------------------------------------------------------------------------
+impl Checksum for Adler32 {
+    fn update(&mut self, data: &[u8]) {
+        self.0 = adler32(self.0, data);
+    }
+
+    fn value(&self) -> u32 {
+        self.0
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Reader that checksums all data read through it.
+#[cfg(feature = "std")]
+pub struct ChecksumReader<R, C> {
+    inner: R,
+    checksum: C,
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(feature = "std")]
+impl<R: std::io::Read, C: Checksum> ChecksumReader<R, C> {
+    pub fn new(inner: R, checksum: C) -> Self {
+        ChecksumReader { inner, checksum }
+    }
+
+    // checksum of the data read so far
+    pub fn checksum(&self) -> u32 {
+        self.checksum.value()
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(feature = "std")]
+impl<R: std::io::Read, C: Checksum> std::io::Read for ChecksumReader<R, C> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.checksum.update(&buf[..len]);
+        Ok(len)
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Writer that checksums all data written through it.
+#[cfg(feature = "std")]
+pub struct ChecksumWriter<W, C> {
+    inner: W,
+    checksum: C,
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(feature = "std")]
+impl<W: std::io::Write, C: Checksum> ChecksumWriter<W, C> {
+    pub fn new(inner: W, checksum: C) -> Self {
+        ChecksumWriter { inner, checksum }
+    }
+
+    // checksum of the data written so far
+    pub fn checksum(&self) -> u32 {
+        self.checksum.value()
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(feature = "std")]
+impl<W: std::io::Write, C: Checksum> std::io::Write for ChecksumWriter<W, C> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.checksum.update(&buf[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
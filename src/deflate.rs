@@ -0,0 +1,923 @@
+// RFC 1951 compression implementation.
+// * https://datatracker.ietf.org/doc/html/rfc1951
+// * https://datatracker.ietf.org/doc/html/rfc1950 (zlib wrapper)
+// * https://datatracker.ietf.org/doc/html/rfc1952 (gzip wrapper)
+// * LZ77 matches are found with hash chains over a 32 KB window, each block is written stored,
+//   with fixed or with dynamic Huffman codes, whichever is smallest
+use crate::bits::BitWriter;
+#[cfg(any(feature = "png", feature = "capi"))]
+use crate::checksum;
+use crate::inflate::{CODE_INFO, CODE_LEN_PERM, DIST_INFO, MAX_WINDOW_SIZE};
+use alloc::collections::BinaryHeap;
+use alloc::{vec, vec::Vec};
+use core::cmp::Reverse;
+
+// ----------------------------------------------------------------------------
+pub(crate) const MAX_STORED_LEN: usize = 0xffff;
+
+// ----------------------------------------------------------------------------
+// zlib header: 32k window, fastest compression
+pub(crate) const ZLIB_HEADER: [u8; 2] = [0x78, 0x01];
+
+// ----------------------------------------------------------------------------
+// gzip member header: deflate, no flags, no modification time, unknown OS
+pub(crate) const GZIP_HEADER: [u8; 10] = [0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+
+// ----------------------------------------------------------------------------
+// Appends a single stored block, `src` must not exceed `MAX_STORED_LEN` bytes.
+pub(crate) fn stored_block(dst: &mut Vec<u8>, src: &[u8], last: bool) {
+    let len = src.len() as u16;
+    dst.push(last as u8);
+    dst.extend_from_slice(&len.to_le_bytes());
+    dst.extend_from_slice(&(!len).to_le_bytes());
+    dst.extend_from_slice(src);
+}
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlockType {
+    Stored,
+    Fixed,
+    Dynamic,
+}
+
+// ----------------------------------------------------------------------------
+// Statistics of an emitted deflate block, bit counts include the block header.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlockStats {
+    pub block_type: BlockType,
+    pub input_len: usize,
+    pub literals: usize,
+    pub matches: usize,
+    // total length of all matches
+    pub match_bytes: usize,
+    // size predicted by the encoder when choosing the block type
+    pub estimated_bits: u64,
+    pub actual_bits: u64,
+}
+
+// ----------------------------------------------------------------------------
+impl BlockStats {
+    pub fn average_match_len(&self) -> f64 {
+        if self.matches == 0 {
+            0.0
+        } else {
+            self.match_bytes as f64 / self.matches as f64
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Statistics of a stored block of `len` bytes taking `bits`, its size is known in advance.
+fn stored_stats(len: usize, bits: u64) -> BlockStats {
+    BlockStats {
+        block_type: BlockType::Stored,
+        input_len: len,
+        literals: len,
+        matches: 0,
+        match_bytes: 0,
+        estimated_bits: bits,
+        actual_bits: bits,
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Like `stored_block`, the block is added to `stats`.
+fn stored_block_stats(
+    dst: &mut Vec<u8>,
+    src: &[u8],
+    last: bool,
+    stats: &mut Option<&mut Vec<BlockStats>>,
+) {
+    let start = dst.len();
+    stored_block(dst, src, last);
+    if let Some(stats) = stats {
+        stats.push(stored_stats(src.len(), 8 * (dst.len() - start) as u64));
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Appends `src` as a sequence of stored (uncompressed) blocks.
+pub(crate) fn deflate_stored(dst: &mut Vec<u8>, src: &[u8]) {
+    let mut chunks = src.chunks(MAX_STORED_LEN).peekable();
+    if chunks.peek().is_none() {
+        // an empty final block
+        stored_block(dst, &[], true);
+    }
+
+    while let Some(chunk) = chunks.next() {
+        stored_block(dst, chunk, chunks.peek().is_none());
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Appends `src` as RFC 1950 stream: header, deflate data and Adler-32 trailer.
+#[cfg(feature = "png")]
+pub(crate) fn deflate_zlib(dst: &mut Vec<u8>, src: &[u8]) {
+    dst.extend_from_slice(&ZLIB_HEADER);
+    deflate_stored(dst, src);
+    dst.extend_from_slice(&checksum::adler32(1, src).to_be_bytes());
+}
+
+// ----------------------------------------------------------------------------
+// Like `deflate_zlib` with `deflate_rle` for the deflate data.
+#[cfg(feature = "png")]
+pub(crate) fn deflate_zlib_rle(dst: &mut Vec<u8>, src: &[u8]) {
+    dst.extend_from_slice(&ZLIB_HEADER);
+    deflate_rle(dst, src);
+    dst.extend_from_slice(&checksum::adler32(1, src).to_be_bytes());
+}
+
+// ----------------------------------------------------------------------------
+// Like `deflate_zlib` with `deflate` at `level` for the deflate data.
+#[cfg(any(feature = "png", feature = "capi"))]
+pub(crate) fn deflate_zlib_level(dst: &mut Vec<u8>, src: &[u8], level: CompressionLevel) {
+    dst.extend_from_slice(&ZLIB_HEADER);
+    deflate(dst, src, level);
+    dst.extend_from_slice(&checksum::adler32(1, src).to_be_bytes());
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(feature = "png")]
+impl BitWriter<'_> {
+    // fixed Huffman code of a literal/length symbol, RFC 1951 section 3.2.6
+    fn put_fixed(&mut self, symbol: u32) {
+        match symbol {
+            0..=143 => self.put_code(0x30 + symbol, 8),
+            144..=255 => self.put_code(0x190 + symbol - 144, 9),
+            256..=279 => self.put_code(symbol - 256, 7),
+            _ => self.put_code(0xc0 + symbol - 280, 8),
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Appends `src` as a single fixed Huffman block of literals and runs of the previous byte
+// (matches at distance 1, zlib's Z_RLE strategy). Fast and effective on filtered image data.
+#[cfg(feature = "png")]
+pub(crate) fn deflate_rle(dst: &mut Vec<u8>, src: &[u8]) {
+    let mut bw = BitWriter::new(dst);
+    // BFINAL, fixed Huffman codes
+    bw.put(1, 1);
+    bw.put(1, 2);
+
+    let mut pos = 0;
+    while pos < src.len() {
+        let (run, _) = run_length(src, pos);
+        if run >= 3 {
+            let code = CODE_INFO
+                .iter()
+                .rposition(|&(_, base)| base as usize <= run);
+            let code = code.unwrap_or(0);
+            let (extra, base) = CODE_INFO[code];
+            bw.put_fixed(257 + code as u32);
+            bw.put((run - base as usize) as u32, extra as u32);
+            // distance code 0: distance 1
+            bw.put_code(0, 5);
+            pos += run;
+        } else {
+            bw.put_fixed(src[pos] as u32);
+            pos += 1;
+        }
+    }
+
+    bw.put_fixed(256);
+    bw.flush();
+}
+
+// ----------------------------------------------------------------------------
+// Returns (length, distance) of a run of the previous byte at `pos` as a match at distance 1,
+// length 0 if the run is too short.
+fn run_length(src: &[u8], pos: usize) -> (usize, usize) {
+    let Some(prev) = pos.checked_sub(1) else {
+        return (0, 0);
+    };
+    let run = src[pos..]
+        .iter()
+        .take(MAX_MATCH)
+        .take_while(|&&b| b == src[prev])
+        .count();
+    if run >= MIN_MATCH {
+        (run, 1)
+    } else {
+        (0, 0)
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Match search and block type choices, analogous to zlib's strategies.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Strategy {
+    #[default]
+    Default,
+    // matches shorter than 6 bytes are sent as literals, suits filtered image data whose small
+    // values are better left to the Huffman codes
+    Filtered,
+    // literals only, e.g. for already compressed data
+    HuffmanOnly,
+    // matches at distance 1 only, runs of the same byte
+    Rle,
+    // no dynamic Huffman blocks, saves the code tables on small inputs
+    Fixed,
+}
+
+// ----------------------------------------------------------------------------
+// Compression level of the deflate engine shared by all writers, the named levels are zlib's
+// levels 0, 1, 6 and 9.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum CompressionLevel {
+    // Stored blocks of up to 65535 bytes without any match search, wraps already compressed
+    // payloads at the cost of 5 bytes per block.
+    NoCompression,
+    BestSpeed,
+    #[default]
+    DefaultLevel,
+    BestCompression,
+    // zlib level from 0 to 9, levels above 9 are 9
+    Level(u8),
+}
+
+// ----------------------------------------------------------------------------
+impl CompressionLevel {
+    // zlib level from 0 (none) to 9 (best)
+    pub fn level(self) -> u8 {
+        match self {
+            CompressionLevel::NoCompression => 0,
+            CompressionLevel::BestSpeed => 1,
+            CompressionLevel::DefaultLevel => 6,
+            CompressionLevel::BestCompression => 9,
+            CompressionLevel::Level(level) => level.min(9),
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Maps zlib-style levels, 0 (none) to 9 (best).
+impl From<u8> for CompressionLevel {
+    fn from(level: u8) -> Self {
+        match level {
+            0 => CompressionLevel::NoCompression,
+            1 => CompressionLevel::BestSpeed,
+            6 => CompressionLevel::DefaultLevel,
+            9.. => CompressionLevel::BestCompression,
+            level => CompressionLevel::Level(level),
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+
+// length 3 matches further away cost more than their literals
+const MAX_MIN_MATCH_DISTANCE: usize = 4096;
+
+const HASH_BITS: u32 = 15;
+const WINDOW_MASK: usize = MAX_WINDOW_SIZE - 1;
+
+// a block ends before its input could exceed a single stored block
+const MAX_BLOCK_INPUT: usize = MAX_STORED_LEN - MAX_MATCH;
+const MAX_BLOCK_TOKENS: usize = 16384;
+
+const NUM_LITLEN_SYMBOLS: usize = 286;
+const NUM_DIST_SYMBOLS: usize = 30;
+const END_OF_BLOCK: usize = 256;
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone, Copy)]
+enum Token {
+    Literal(u8),
+    Match { len: u16, dist: u16 },
+}
+
+// ----------------------------------------------------------------------------
+// Returns (symbol index, extra bits, extra value) of `value` in a base table.
+fn base_code(table: &[(u8, u16)], value: usize) -> (usize, u32, u32) {
+    let idx = table
+        .iter()
+        .rposition(|&(_, base)| base as usize <= value)
+        .unwrap_or(0);
+    let (extra, base) = table[idx];
+    (idx, extra as u32, (value - base as usize) as u32)
+}
+
+// ----------------------------------------------------------------------------
+// Match search parameters of a compression level.
+#[derive(Debug, Clone, Copy)]
+struct MatchParams {
+    // chain entries visited per search
+    max_chain: usize,
+    // the search for a longer match than a deferred one of this length visits a quarter of the
+    // chain
+    good_len: usize,
+    // a match of this length ends the search
+    nice_len: usize,
+    // Lazy levels defer a match shorter than this by one byte if the next position has a longer
+    // one. Without lazy matching the positions inside longer matches are not added to the hash
+    // chains.
+    lazy: bool,
+    max_lazy: usize,
+    // shorter matches are dropped
+    min_len: usize,
+}
+
+// ----------------------------------------------------------------------------
+// zlib's configuration table, levels 1 to 3 without lazy matching
+fn match_params(level: u8) -> MatchParams {
+    let (good_len, max_lazy, nice_len, max_chain) = match level {
+        1 => (4, 4, 8, 4),
+        2 => (4, 5, 16, 8),
+        3 => (4, 6, 32, 32),
+        4 => (4, 4, 16, 16),
+        5 => (8, 16, 32, 32),
+        6 => (8, 16, 128, 128),
+        7 => (8, 32, 128, 256),
+        8 => (32, 128, MAX_MATCH, 1024),
+        _ => (32, MAX_MATCH, MAX_MATCH, 4096),
+    };
+    MatchParams {
+        max_chain,
+        good_len,
+        nice_len,
+        lazy: level >= 4,
+        max_lazy,
+        min_len: MIN_MATCH,
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Hash chains over the 3-byte prefixes, positions are stored plus one so that 0 ends a chain.
+struct MatchFinder {
+    params: MatchParams,
+    head: Vec<u32>,
+    prev: Vec<u32>,
+}
+
+// ----------------------------------------------------------------------------
+impl MatchFinder {
+    fn new(params: MatchParams) -> Self {
+        MatchFinder {
+            params,
+            head: vec![0; 1 << HASH_BITS],
+            prev: vec![0; MAX_WINDOW_SIZE],
+        }
+    }
+
+    fn hash(src: &[u8], pos: usize) -> usize {
+        let v = u32::from_le_bytes([src[pos], src[pos + 1], src[pos + 2], 0]);
+        (v.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+    }
+
+    fn insert(&mut self, src: &[u8], pos: usize) {
+        if pos + MIN_MATCH <= src.len() {
+            let h = Self::hash(src, pos);
+            self.prev[pos & WINDOW_MASK] = self.head[h];
+            self.head[h] = pos as u32 + 1;
+        }
+    }
+
+    // Returns (length, distance) of the longest match at `pos` within `max_chain` chain entries,
+    // length 0 if there is none.
+    fn find(&self, src: &[u8], pos: usize, max_chain: usize) -> (usize, usize) {
+        if pos + MIN_MATCH > src.len() {
+            return (0, 0);
+        }
+
+        let max_len = MAX_MATCH.min(src.len() - pos);
+        let nice_len = self.params.nice_len.min(max_len);
+        let mut best = (0, 0);
+        let mut candidate = self.head[Self::hash(src, pos)] as usize;
+
+        for _ in 0..max_chain {
+            let Some(start) = candidate.checked_sub(1) else {
+                break;
+            };
+            let dist = pos - start;
+            if dist > MAX_WINDOW_SIZE {
+                break;
+            }
+
+            // a longer match has to differ from the best one at its end
+            if src[start + best.0] == src[pos + best.0] {
+                let len = src[start..start + max_len]
+                    .iter()
+                    .zip(&src[pos..pos + max_len])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if len > best.0 {
+                    best = (len, dist);
+                    if len >= nice_len {
+                        break;
+                    }
+                }
+            }
+
+            candidate = self.prev[start & WINDOW_MASK] as usize;
+            if candidate > start {
+                break;
+            }
+        }
+
+        match best {
+            (len, _) if len < self.params.min_len => (0, 0),
+            (len, dist)
+                if len > MIN_MATCH || (len == MIN_MATCH && dist <= MAX_MIN_MATCH_DISTANCE) =>
+            {
+                best
+            }
+            _ => (0, 0),
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Code lengths of a Huffman code for `freq` limited to `max_len` bits. Unused symbols get
+// length 0, at least two symbols are assigned a code so that the code is complete.
+fn huffman_lengths(freq: &[u32], max_len: u8) -> Vec<u8> {
+    let mut freq = freq.to_vec();
+    let used = freq.iter().filter(|&&f| f > 0).count();
+    for f in freq
+        .iter_mut()
+        .filter(|f| **f == 0)
+        .take(2usize.saturating_sub(used))
+    {
+        *f = 1;
+    }
+
+    // halving the frequencies flattens the tree until it fits
+    loop {
+        let lengths = tree_lengths(&freq);
+        if lengths.iter().all(|&len| len <= max_len) {
+            return lengths;
+        }
+        for f in freq.iter_mut().filter(|f| **f > 0) {
+            *f = f.div_ceil(2);
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Depths of the leaves of an unrestricted Huffman tree.
+fn tree_lengths(freq: &[u32]) -> Vec<u8> {
+    let symbols: Vec<usize> = (0..freq.len()).filter(|&i| freq[i] > 0).collect();
+
+    // leaves are the first nodes, every merged node is appended behind its children
+    let mut parent = vec![0usize; symbols.len()];
+    let mut heap: BinaryHeap<_> = symbols
+        .iter()
+        .enumerate()
+        .map(|(node, &sym)| Reverse((freq[sym] as u64, node)))
+        .collect();
+    while let (Some(Reverse((f1, n1))), Some(Reverse((f2, n2)))) = (heap.pop(), heap.pop()) {
+        let node = parent.len();
+        parent.push(0);
+        parent[n1] = node;
+        parent[n2] = node;
+        heap.push(Reverse((f1 + f2, node)));
+    }
+
+    let mut depth = vec![0u8; parent.len()];
+    for node in (0..parent.len().saturating_sub(1)).rev() {
+        depth[node] = depth[parent[node]] + 1;
+    }
+
+    let mut lengths = vec![0; freq.len()];
+    for (node, &sym) in symbols.iter().enumerate() {
+        lengths[sym] = depth[node];
+    }
+    lengths
+}
+
+// ----------------------------------------------------------------------------
+// Canonical codes of the given code lengths, RFC 1951 section 3.2.2.
+fn canonical_codes(lengths: &[u8]) -> Vec<u16> {
+    let mut count = [0u16; 16];
+    for &len in lengths {
+        count[len as usize] += 1;
+    }
+    count[0] = 0;
+
+    let mut next = [0u16; 16];
+    let mut code = 0;
+    for bits in 1..16 {
+        code = (code + count[bits - 1]) << 1;
+        next[bits] = code;
+    }
+
+    lengths
+        .iter()
+        .map(|&len| {
+            let code = next[len as usize];
+            next[len as usize] += 1;
+            code
+        })
+        .collect()
+}
+
+// ----------------------------------------------------------------------------
+// Run-length encodes code lengths into (code length symbol, extra value) pairs.
+fn rle_lengths(lengths: &[u8]) -> Vec<(u8, u8)> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while pos < lengths.len() {
+        let len = lengths[pos];
+        let mut run = lengths[pos..].iter().take_while(|&&l| l == len).count();
+        pos += run;
+
+        if len == 0 {
+            while run >= 11 {
+                let n = run.min(138);
+                out.push((18, (n - 11) as u8));
+                run -= n;
+            }
+            if run >= 3 {
+                out.push((17, (run - 3) as u8));
+                run = 0;
+            }
+        } else {
+            out.push((len, 0));
+            run -= 1;
+            while run >= 3 {
+                let n = run.min(6);
+                out.push((16, (n - 3) as u8));
+                run -= n;
+            }
+        }
+        out.extend(core::iter::repeat_n((len, 0), run));
+    }
+    out
+}
+
+// ----------------------------------------------------------------------------
+fn fixed_lengths() -> (Vec<u8>, Vec<u8>) {
+    let ll = (0..288)
+        .map(|sym| match sym {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8,
+        })
+        .collect();
+    (ll, vec![5; NUM_DIST_SYMBOLS])
+}
+
+// ----------------------------------------------------------------------------
+// Dynamic block header: the trimmed code length counts and the encoded code lengths.
+struct DynamicHeader {
+    hlit: usize,
+    hdist: usize,
+    hclen: usize,
+    cl_lengths: Vec<u8>,
+    rle: Vec<(u8, u8)>,
+}
+
+// ----------------------------------------------------------------------------
+impl DynamicHeader {
+    fn new(ll_lengths: &[u8], d_lengths: &[u8]) -> Self {
+        let hlit = 257.max(ll_lengths.iter().rposition(|&l| l > 0).unwrap_or(0) + 1);
+        let hdist = 1.max(d_lengths.iter().rposition(|&l| l > 0).unwrap_or(0) + 1);
+
+        let mut lengths = ll_lengths[..hlit].to_vec();
+        lengths.extend_from_slice(&d_lengths[..hdist]);
+        let rle = rle_lengths(&lengths);
+
+        let mut cl_freq = [0u32; CODE_LEN_PERM.len()];
+        for &(sym, _) in &rle {
+            cl_freq[sym as usize] += 1;
+        }
+        let cl_lengths = huffman_lengths(&cl_freq, 7);
+        let hclen = 4.max(
+            CODE_LEN_PERM
+                .iter()
+                .rposition(|&sym| cl_lengths[sym as usize] > 0)
+                .unwrap_or(0)
+                + 1,
+        );
+
+        DynamicHeader {
+            hlit,
+            hdist,
+            hclen,
+            cl_lengths,
+            rle,
+        }
+    }
+
+    fn bits(&self) -> usize {
+        let codes: usize = self
+            .rle
+            .iter()
+            .map(|&(sym, _)| self.cl_lengths[sym as usize] as usize + rle_extra_bits(sym) as usize)
+            .sum();
+        5 + 5 + 4 + 3 * self.hclen + codes
+    }
+
+    fn write(&self, bw: &mut BitWriter) {
+        bw.put((self.hlit - 257) as u32, 5);
+        bw.put((self.hdist - 1) as u32, 5);
+        bw.put((self.hclen - 4) as u32, 4);
+        for &sym in &CODE_LEN_PERM[..self.hclen] {
+            bw.put(self.cl_lengths[sym as usize] as u32, 3);
+        }
+
+        let cl_codes = canonical_codes(&self.cl_lengths);
+        for &(sym, extra) in &self.rle {
+            let sym = sym as usize;
+            bw.put_code(cl_codes[sym] as u32, self.cl_lengths[sym] as u32);
+            bw.put(extra as u32, rle_extra_bits(sym as u8));
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+fn rle_extra_bits(sym: u8) -> u32 {
+    match sym {
+        16 => 2,
+        17 => 3,
+        18 => 7,
+        _ => 0,
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Bits of the block's symbols with the given code lengths, extra bits excluded.
+fn symbol_bits(ll_freq: &[u32], d_freq: &[u32], ll_lengths: &[u8], d_lengths: &[u8]) -> usize {
+    let ll: usize = ll_freq
+        .iter()
+        .zip(ll_lengths)
+        .map(|(&f, &l)| f as usize * l as usize)
+        .sum();
+    let d: usize = d_freq
+        .iter()
+        .zip(d_lengths)
+        .map(|(&f, &l)| f as usize * l as usize)
+        .sum();
+    ll + d
+}
+
+// ----------------------------------------------------------------------------
+// Writes the tokens of `raw` as a single block of the smallest type, dynamic Huffman blocks
+// unless `fixed_only`. The block is added to `stats`.
+fn write_block(
+    bw: &mut BitWriter,
+    raw: &[u8],
+    tokens: &[Token],
+    last: bool,
+    fixed_only: bool,
+    stats: &mut Option<&mut Vec<BlockStats>>,
+) {
+    let mut ll_freq = [0u32; NUM_LITLEN_SYMBOLS];
+    let mut d_freq = [0u32; NUM_DIST_SYMBOLS];
+    let mut extra_bits = 0;
+    let (mut matches, mut match_bytes) = (0, 0);
+    ll_freq[END_OF_BLOCK] = 1;
+    for token in tokens {
+        match *token {
+            Token::Literal(byte) => ll_freq[byte as usize] += 1,
+            Token::Match { len, dist } => {
+                let (len_idx, len_extra, _) = base_code(&CODE_INFO, len as usize);
+                let (dist_idx, dist_extra, _) = base_code(&DIST_INFO, dist as usize);
+                ll_freq[257 + len_idx] += 1;
+                d_freq[dist_idx] += 1;
+                extra_bits += (len_extra + dist_extra) as usize;
+                matches += 1;
+                match_bytes += len as usize;
+            }
+        }
+    }
+
+    let (fixed_ll, fixed_d) = fixed_lengths();
+    let fixed_bits = symbol_bits(&ll_freq, &d_freq, &fixed_ll, &fixed_d);
+
+    let dyn_ll = huffman_lengths(&ll_freq, 15);
+    let dyn_d = huffman_lengths(&d_freq, 15);
+    let header = DynamicHeader::new(&dyn_ll, &dyn_d);
+    let dyn_bits = if fixed_only {
+        usize::MAX
+    } else {
+        header.bits() + symbol_bits(&ll_freq, &d_freq, &dyn_ll, &dyn_d)
+    };
+
+    // stored blocks start at the next byte boundary after the 3 header bits
+    let stored_bits = (8 - (bw.bits as usize + 3) % 8) % 8 + 32 + 8 * raw.len();
+
+    let start = bw.bit_pos();
+    bw.put(last as u32, 1);
+    let (block_type, estimated_bits) = if stored_bits <= fixed_bits.min(dyn_bits) + extra_bits {
+        trace_event!(trace, len = raw.len(), "stored block");
+        bw.put(0, 2);
+        bw.flush();
+        let len = raw.len() as u16;
+        bw.dst.extend_from_slice(&len.to_le_bytes());
+        bw.dst.extend_from_slice(&(!len).to_le_bytes());
+        bw.dst.extend_from_slice(raw);
+        (BlockType::Stored, stored_bits)
+    } else if fixed_bits <= dyn_bits {
+        trace_event!(trace, len = raw.len(), "fixed Huffman block");
+        bw.put(1, 2);
+        write_tokens(bw, tokens, &fixed_ll, &fixed_d);
+        (BlockType::Fixed, fixed_bits + extra_bits)
+    } else {
+        trace_event!(trace, len = raw.len(), "dynamic Huffman block");
+        bw.put(2, 2);
+        header.write(bw);
+        write_tokens(bw, tokens, &dyn_ll, &dyn_d);
+        (BlockType::Dynamic, dyn_bits + extra_bits)
+    };
+
+    if let Some(stats) = stats {
+        // stored blocks carry the raw bytes, not the tokens
+        let (matches, match_bytes) = match block_type {
+            BlockType::Stored => (0, 0),
+            _ => (matches, match_bytes),
+        };
+        stats.push(BlockStats {
+            block_type,
+            input_len: raw.len(),
+            literals: raw.len() - match_bytes,
+            matches,
+            match_bytes,
+            estimated_bits: 3 + estimated_bits as u64,
+            actual_bits: bw.bit_pos() - start,
+        });
+    }
+}
+
+// ----------------------------------------------------------------------------
+fn write_tokens(bw: &mut BitWriter, tokens: &[Token], ll_lengths: &[u8], d_lengths: &[u8]) {
+    let ll_codes = canonical_codes(ll_lengths);
+    let d_codes = canonical_codes(d_lengths);
+    let put_symbol = |bw: &mut BitWriter, codes: &[u16], lengths: &[u8], sym: usize| {
+        bw.put_code(codes[sym] as u32, lengths[sym] as u32)
+    };
+
+    for token in tokens {
+        match *token {
+            Token::Literal(byte) => put_symbol(bw, &ll_codes, ll_lengths, byte as usize),
+            Token::Match { len, dist } => {
+                let (len_idx, len_extra, len_value) = base_code(&CODE_INFO, len as usize);
+                put_symbol(bw, &ll_codes, ll_lengths, 257 + len_idx);
+                bw.put(len_value, len_extra);
+
+                let (dist_idx, dist_extra, dist_value) = base_code(&DIST_INFO, dist as usize);
+                put_symbol(bw, &d_codes, d_lengths, dist_idx);
+                bw.put(dist_value, dist_extra);
+            }
+        }
+    }
+    put_symbol(bw, &ll_codes, ll_lengths, END_OF_BLOCK);
+}
+
+// ----------------------------------------------------------------------------
+// Appends `src` as raw deflate data compressed at `level`.
+pub fn deflate(dst: &mut Vec<u8>, src: &[u8], level: CompressionLevel) {
+    deflate_chunk(dst, src, level, Strategy::Default, true, None);
+}
+
+// ----------------------------------------------------------------------------
+// Like `deflate` with the match search and block types chosen by `strategy`. `NoCompression`
+// writes stored blocks regardless of the strategy.
+pub fn deflate_with_strategy(
+    dst: &mut Vec<u8>,
+    src: &[u8],
+    level: CompressionLevel,
+    strategy: Strategy,
+) {
+    deflate_chunk(dst, src, level, strategy, true, None);
+}
+
+// ----------------------------------------------------------------------------
+// Like `deflate`, a chunk that is not the `last` ends with an empty stored block instead of the
+// final block (a sync flush), so the next chunk can follow at a byte boundary. Matches never
+// refer to earlier chunks. Every emitted block, the flush marker included, is added to `stats`.
+pub(crate) fn deflate_chunk(
+    dst: &mut Vec<u8>,
+    src: &[u8],
+    level: CompressionLevel,
+    strategy: Strategy,
+    last: bool,
+    stats: Option<&mut Vec<BlockStats>>,
+) {
+    deflate_chunk_from(dst, src, 0, level, strategy, last, stats);
+}
+
+// ----------------------------------------------------------------------------
+// Like `deflate_chunk` for `src[start..]`, matches may refer to the up to 32 KB of `src` in
+// front of `start` that the decoder has seen already.
+pub(crate) fn deflate_chunk_from(
+    dst: &mut Vec<u8>,
+    src: &[u8],
+    start: usize,
+    level: CompressionLevel,
+    strategy: Strategy,
+    last: bool,
+    mut stats: Option<&mut Vec<BlockStats>>,
+) {
+    let mut params = match level.level() {
+        0 => {
+            let mut chunks = src[start..].chunks(MAX_STORED_LEN).peekable();
+            while let Some(chunk) = chunks.next() {
+                let end = last && chunks.peek().is_none();
+                stored_block_stats(dst, chunk, end, &mut stats);
+            }
+            // the flush marker, or an empty final block
+            if !last || src.len() == start {
+                stored_block_stats(dst, &[], last, &mut stats);
+            }
+            return;
+        }
+        level => match_params(level),
+    };
+    if strategy == Strategy::Filtered {
+        params.min_len = 6;
+    }
+    // the hash chains are only searched by the strategies with general matches
+    let search = !matches!(strategy, Strategy::HuffmanOnly | Strategy::Rle);
+    let fixed_only = strategy == Strategy::Fixed;
+
+    let mut bw = BitWriter::new(dst);
+    let mut finder = MatchFinder::new(params);
+    let mut tokens = Vec::with_capacity(MAX_BLOCK_TOKENS);
+    let mut block_start = start;
+    let mut pos = start;
+    let mut deferred = None;
+    if search {
+        for p in start.saturating_sub(MAX_WINDOW_SIZE)..start {
+            finder.insert(src, p);
+        }
+    }
+
+    while pos < src.len() {
+        if pos - block_start >= MAX_BLOCK_INPUT || tokens.len() >= MAX_BLOCK_TOKENS {
+            let raw = &src[block_start..pos];
+            write_block(&mut bw, raw, &tokens, false, fixed_only, &mut stats);
+            tokens.clear();
+            block_start = pos;
+        }
+
+        let (len, dist) = match strategy {
+            Strategy::HuffmanOnly => (0, 0),
+            Strategy::Rle => run_length(src, pos),
+            _ => deferred
+                .take()
+                .unwrap_or_else(|| finder.find(src, pos, params.max_chain)),
+        };
+        if search {
+            finder.insert(src, pos);
+        }
+        if len == 0 {
+            tokens.push(Token::Literal(src[pos]));
+            pos += 1;
+            continue;
+        }
+
+        if search && params.lazy && len < params.max_lazy {
+            let chain = if len >= params.good_len {
+                params.max_chain / 4
+            } else {
+                params.max_chain
+            };
+            let next = finder.find(src, pos + 1, chain);
+            if next.0 > len {
+                tokens.push(Token::Literal(src[pos]));
+                pos += 1;
+                deferred = Some(next);
+                continue;
+            }
+        }
+
+        tokens.push(Token::Match {
+            len: len as u16,
+            dist: dist as u16,
+        });
+        if search && (params.lazy || len <= params.max_lazy) {
+            for p in pos + 1..pos + len {
+                finder.insert(src, p);
+            }
+        } else if search {
+            // the last position keeps runs at distance 1
+            finder.insert(src, pos + len - 1);
+        }
+        pos += len;
+    }
+
+    write_block(
+        &mut bw,
+        &src[block_start..],
+        &tokens,
+        last,
+        fixed_only,
+        &mut stats,
+    );
+    if !last {
+        // empty stored block: BFINAL 0 and BTYPE 00, LEN and NLEN at the next byte boundary
+        let start = bw.bit_pos();
+        bw.put(0, 3);
+        bw.flush();
+        bw.dst.extend_from_slice(&[0, 0, 0xff, 0xff]);
+        if let Some(stats) = &mut stats {
+            stats.push(stored_stats(0, bw.bit_pos() - start));
+        }
+    }
+    bw.flush();
+}
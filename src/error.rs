@@ -0,0 +1,139 @@
+// Crate-level error type.
+// * wraps the per-module errors so callers combining modules need a single conversion
+// * PNG and ZIP errors keep the failure of their deflate stream, `source` chains down to it
+#[cfg(feature = "png")]
+use crate::png_read;
+#[cfg(feature = "zip")]
+use crate::zip_read;
+use crate::{bzip2, inflate};
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Error {
+    Inflate(inflate::Error),
+    #[cfg(feature = "png")]
+    Png(png_read::Error),
+    #[cfg(feature = "zip")]
+    Zip(zip_read::Error),
+    Bzip2(bzip2::Error),
+}
+
+// ----------------------------------------------------------------------------
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Error::Inflate(err) => write!(f, "inflate: {err}"),
+            #[cfg(feature = "png")]
+            Error::Png(err) => write!(f, "png: {err}"),
+            #[cfg(feature = "zip")]
+            Error::Zip(err) => write!(f, "zip: {err}"),
+            Error::Bzip2(err) => write!(f, "bzip2: {err}"),
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Stable numeric codes of the wrapped module errors: inflate 100-199, png 200-299, zip 300-399,
+// bzip2 400-499.
+impl Error {
+    pub fn code(&self) -> u32 {
+        match self {
+            Error::Inflate(err) => err.code(),
+            #[cfg(feature = "png")]
+            Error::Png(err) => err.code(),
+            #[cfg(feature = "zip")]
+            Error::Zip(err) => err.code(),
+            Error::Bzip2(err) => err.code(),
+        }
+    }
+
+    pub fn from_code(code: u32) -> Option<Self> {
+        match code {
+            100..=199 => inflate::Error::from_code(code).map(Error::Inflate),
+            #[cfg(feature = "png")]
+            200..=299 => png_read::Error::from_code(code).map(Error::Png),
+            #[cfg(feature = "zip")]
+            300..=399 => zip_read::Error::from_code(code).map(Error::Zip),
+            400..=499 => bzip2::Error::from_code(code).map(Error::Bzip2),
+            _ => None,
+        }
+    }
+
+    // The deflate failure behind the error, also when it was reported by the PNG or ZIP layer.
+    pub fn inflate_error(&self) -> Option<&inflate::Error> {
+        match self {
+            Error::Inflate(err) => Some(err),
+            #[cfg(feature = "png")]
+            Error::Png(png_read::Error::CompressionError(err)) => err.as_ref(),
+            #[cfg(feature = "zip")]
+            Error::Zip(zip_read::Error::CompressionError(err)) => err.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Inflate(err) => Some(err),
+            #[cfg(feature = "png")]
+            Error::Png(err) => Some(err),
+            #[cfg(feature = "zip")]
+            Error::Zip(err) => Some(err),
+            Error::Bzip2(err) => Some(err),
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+impl From<inflate::Error> for Error {
+    fn from(err: inflate::Error) -> Self {
+        Error::Inflate(err)
+    }
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(feature = "png")]
+impl From<png_read::Error> for Error {
+    fn from(err: png_read::Error) -> Self {
+        Error::Png(err)
+    }
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(feature = "zip")]
+impl From<zip_read::Error> for Error {
+    fn from(err: zip_read::Error) -> Self {
+        Error::Zip(err)
+    }
+}
+
+// ----------------------------------------------------------------------------
+impl From<bzip2::Error> for Error {
+    fn from(err: bzip2::Error) -> Self {
+        Error::Bzip2(err)
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Decoding errors of `io` adapters and sinks are `InvalidData` wrapping the crate error.
+#[cfg(feature = "std")]
+impl From<Error> for std::io::Error {
+    fn from(err: Error) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+    }
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(feature = "std")]
+impl From<inflate::Error> for std::io::Error {
+    fn from(err: inflate::Error) -> Self {
+        Error::Inflate(err).into()
+    }
+}
+
+// ----------------------------------------------------------------------------
+pub type Result<T> = core::result::Result<T, Error>;
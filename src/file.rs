@@ -0,0 +1,232 @@
+// File-to-file compression and decompression with bounded memory.
+// * input is read and output is written in chunks, decoding keeps the 32 KB window and the
+//   current deflate block in memory, independent of the file size
+// * decoding errors are returned as `io::ErrorKind::InvalidData` wrapping `crate::Error`
+use crate::checksum;
+use crate::gzip::{GzipHeader, GzipOptions};
+use crate::inflate::{self, Error};
+use crate::stream::{Compress, FlushCompress, Status};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+// ----------------------------------------------------------------------------
+const CHUNK_SIZE: usize = 1 << 16;
+const WINDOW_SIZE: usize = 1 << 15;
+
+// ----------------------------------------------------------------------------
+pub(crate) fn invalid_data(err: impl Into<crate::Error>) -> io::Error {
+    err.into().into()
+}
+
+// ----------------------------------------------------------------------------
+// Buffered input with a bit position into it, consumed bytes are dropped from the front.
+pub(crate) struct Input<R> {
+    reader: R,
+    pub(crate) data: Vec<u8>,
+    pub(crate) bit_pos: usize,
+    eof: bool,
+    // bytes dropped from the front so far
+    discarded: u64,
+}
+
+// ----------------------------------------------------------------------------
+impl<R: Read> Input<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Input {
+            reader,
+            data: Vec::new(),
+            bit_pos: 0,
+            eof: false,
+            discarded: 0,
+        }
+    }
+
+    pub(crate) fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    // offset of the next byte boundary from the start of the input
+    #[cfg(feature = "zip")]
+    pub(crate) fn position(&self) -> u64 {
+        self.discarded + self.bit_pos.div_ceil(8) as u64
+    }
+
+    // Reads at least as much as is buffered so retries on growing input stay linear, fails with
+    // `Underflow` at the end of the file.
+    pub(crate) fn fill(&mut self) -> io::Result<()> {
+        if self.eof {
+            return Err(invalid_data(Error::Underflow));
+        }
+        let want = self.data.len().max(CHUNK_SIZE);
+        let len = (&mut self.reader)
+            .take(want as u64)
+            .read_to_end(&mut self.data)?;
+        self.eof = len < want;
+        Ok(())
+    }
+
+    pub(crate) fn discard_bytes(&mut self) {
+        let bytes = self.bit_pos / 8;
+        self.data.drain(..bytes);
+        self.bit_pos -= bytes * 8;
+        self.discarded += bytes as u64;
+    }
+
+    // Takes `len` bytes after aligning to the next byte boundary.
+    pub(crate) fn take_aligned(&mut self, len: usize) -> io::Result<Vec<u8>> {
+        let start = self.bit_pos.div_ceil(8);
+        while self.data.len() < start + len {
+            self.fill()?;
+        }
+        let taken = self.data[start..start + len].to_vec();
+        self.bit_pos = (start + len) * 8;
+        self.discard_bytes();
+        Ok(taken)
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Decodes the raw deflate stream at the current input position, `output` is called with each
+// decoded chunk. Returns the number of decoded bytes.
+pub(crate) fn inflate_io<R: Read>(
+    input: &mut Input<R>,
+    mut output: impl FnMut(&[u8]) -> io::Result<()>,
+) -> io::Result<u64> {
+    let mut window = vec![0u8; WINDOW_SIZE + CHUNK_SIZE];
+    let mut dptr = 0;
+    let mut total = 0;
+    let mut state = inflate::DecoderState::default();
+    loop {
+        match inflate::inflate_block(
+            &mut window,
+            dptr,
+            &input.data,
+            input.bit_pos,
+            &mut state,
+            WINDOW_SIZE,
+            &mut inflate::InflateMetrics::default(),
+        ) {
+            Ok((last, bit_pos, end)) => {
+                output(&window[dptr..end])?;
+                total += (end - dptr) as u64;
+                input.bit_pos = bit_pos;
+                input.discard_bytes();
+
+                // keep the window for back-references of the next block
+                let keep = end.min(WINDOW_SIZE);
+                window.copy_within(end - keep..end, 0);
+                dptr = keep;
+                if last {
+                    return Ok(total);
+                }
+            }
+            Err(Error::Underflow) => input.fill()?,
+            Err(Error::Overflow) => window.resize(window.len() * 2, 0),
+            Err(err) => return Err(invalid_data(err)),
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+fn create(path: &Path) -> io::Result<io::BufWriter<std::fs::File>> {
+    Ok(io::BufWriter::new(std::fs::File::create(path)?))
+}
+
+// ----------------------------------------------------------------------------
+// Decompresses the raw deflate file `path_in` into `path_out`, returns the decompressed size.
+pub fn inflate_file<P: AsRef<Path>, Q: AsRef<Path>>(path_in: P, path_out: Q) -> io::Result<u64> {
+    let mut input = Input::new(std::fs::File::open(path_in)?);
+    let mut writer = create(path_out.as_ref())?;
+    let total = inflate_io(&mut input, |data| writer.write_all(data))?;
+    writer.flush()?;
+    Ok(total)
+}
+
+// ----------------------------------------------------------------------------
+// Decompresses the single-member gzip file `path_in` into `path_out` and verifies its CRC-32
+// and size, returns the decompressed size.
+pub fn gunzip_file<P: AsRef<Path>, Q: AsRef<Path>>(path_in: P, path_out: Q) -> io::Result<u64> {
+    let mut input = Input::new(std::fs::File::open(path_in)?);
+    let header_len = loop {
+        match inflate::gzip_header(&input.data) {
+            Ok(len) => break len,
+            Err(Error::Underflow) => input.fill()?,
+            Err(err) => return Err(invalid_data(err)),
+        }
+    };
+    input.bit_pos = header_len * 8;
+
+    let mut writer = create(path_out.as_ref())?;
+    let mut crc = 0;
+    let total = inflate_io(&mut input, |data| {
+        crc = checksum::crc32(crc, data);
+        writer.write_all(data)
+    })?;
+    writer.flush()?;
+
+    let trailer = input.take_aligned(8)?;
+    if trailer[..4] != crc.to_le_bytes() || trailer[4..] != (total as u32).to_le_bytes() {
+        return Err(invalid_data(Error::ChecksumMismatch));
+    }
+    Ok(total)
+}
+
+// ----------------------------------------------------------------------------
+// Compresses `path_in` into the gzip file `path_out`, returns the compressed size.
+pub fn gzip_file<P: AsRef<Path>, Q: AsRef<Path>>(
+    path_in: P,
+    path_out: Q,
+    level: u8,
+) -> io::Result<u64> {
+    gzip_file_with_header(path_in, path_out, level, &GzipHeader::default())
+}
+
+// ----------------------------------------------------------------------------
+// Like `gzip_file`, with file name, timestamp and other fields of the member header.
+pub fn gzip_file_with_header<P: AsRef<Path>, Q: AsRef<Path>>(
+    path_in: P,
+    path_out: Q,
+    level: u8,
+    header: &GzipHeader,
+) -> io::Result<u64> {
+    let header = *header;
+    let header = GzipOptions { level, header };
+    let header = header.header_bytes().map_err(invalid_data)?;
+    let mut reader = std::fs::File::open(path_in)?;
+    let mut writer = create(path_out.as_ref())?;
+    writer.write_all(&header)?;
+
+    let mut compress = Compress::new(level, false);
+    let mut chunk = vec![0u8; CHUNK_SIZE];
+    let mut out = vec![0u8; CHUNK_SIZE];
+    let mut crc = 0;
+    loop {
+        let len = reader.read(&mut chunk)?;
+        let input = &chunk[..len];
+        crc = checksum::crc32(crc, input);
+        let flush = if len == 0 {
+            FlushCompress::Finish
+        } else {
+            FlushCompress::None
+        };
+
+        // the compressor takes all input at once, drain its output
+        let mut input = input;
+        loop {
+            let before = compress.total_out();
+            let status = compress.run(input, &mut out, flush);
+            input = &[];
+            let written = (compress.total_out() - before) as usize;
+            writer.write_all(&out[..written])?;
+            if status == Status::StreamEnd {
+                writer.write_all(&crc.to_le_bytes())?;
+                writer.write_all(&(compress.total_in() as u32).to_le_bytes())?;
+                writer.flush()?;
+                return Ok(header.len() as u64 + compress.total_out() + 8);
+            }
+            if written < out.len() {
+                break;
+            }
+        }
+    }
+}
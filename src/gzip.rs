@@ -0,0 +1,184 @@
+// RFC 1952 gzip member reading and writing with optional header fields.
+// * https://datatracker.ietf.org/doc/html/rfc1952
+// * file name and comment are zero-terminated ISO 8859-1 strings, FEXTRA holds subfields of a
+//   2 byte ID and a 16 bit length, FHCRC protects the header with the low half of its CRC-32
+// * decoding skips all optional fields and verifies the header CRC, the CRC-32 and ISIZE
+use crate::inflate::Error;
+use crate::inflate::Wrapper;
+use crate::oneshot::inflate_growing;
+#[cfg(feature = "std")]
+use crate::oneshot::{deflate_parallel, BatchOptions};
+use crate::{checksum, deflate};
+use alloc::vec::Vec;
+
+// ----------------------------------------------------------------------------
+const FHCRC: u8 = 0x02;
+const FEXTRA: u8 = 0x04;
+const FNAME: u8 = 0x08;
+const FCOMMENT: u8 = 0x10;
+
+// ----------------------------------------------------------------------------
+// FEXTRA subfield, IDs with a second byte of 0 are reserved.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GzipExtra<'a> {
+    pub id: [u8; 2],
+    pub data: &'a [u8],
+}
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone, Copy)]
+pub struct GzipHeader<'a> {
+    // without the terminating zero, must not contain zero bytes
+    pub filename: Option<&'a [u8]>,
+    pub comment: Option<&'a [u8]>,
+    // seconds since 1970-01-01 00:00:00 UTC, 0 if not available
+    pub mtime: u32,
+    // 3 Unix, 11 NTFS, 255 unknown
+    pub os: u8,
+    pub extra: &'a [GzipExtra<'a>],
+    pub header_crc: bool,
+}
+
+// ----------------------------------------------------------------------------
+impl Default for GzipHeader<'_> {
+    fn default() -> Self {
+        GzipHeader {
+            filename: None,
+            comment: None,
+            mtime: 0,
+            os: 255,
+            extra: &[],
+            header_crc: false,
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+impl GzipHeader<'_> {
+    // Serializes the member header, fails with `InvalidHeader` for strings containing zero bytes
+    // and extra fields longer than 65535 bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        self.encode(0)
+    }
+
+    // `xfl` tells the level the data was compressed with, 2 for the best and 4 for the fastest
+    // compression, as set by gzip(1).
+    fn encode(&self, xfl: u8) -> Result<Vec<u8>, Error> {
+        let mut flags = 0;
+        let mut extra = Vec::new();
+        if !self.extra.is_empty() {
+            flags |= FEXTRA;
+            for field in self.extra {
+                let len = u16::try_from(field.data.len()).map_err(|_| Error::InvalidHeader)?;
+                extra.extend_from_slice(&field.id);
+                extra.extend_from_slice(&len.to_le_bytes());
+                extra.extend_from_slice(field.data);
+            }
+        }
+        let xlen = u16::try_from(extra.len()).map_err(|_| Error::InvalidHeader)?;
+
+        for (field, flag) in [(self.filename, FNAME), (self.comment, FCOMMENT)] {
+            if let Some(field) = field {
+                if field.contains(&0) {
+                    return Err(Error::InvalidHeader);
+                }
+                flags |= flag;
+            }
+        }
+        if self.header_crc {
+            flags |= FHCRC;
+        }
+
+        let mut header = Vec::with_capacity(12 + extra.len());
+        header.extend_from_slice(&[0x1f, 0x8b, 8, flags]);
+        header.extend_from_slice(&self.mtime.to_le_bytes());
+        header.extend_from_slice(&[xfl, self.os]);
+        if flags & FEXTRA != 0 {
+            header.extend_from_slice(&xlen.to_le_bytes());
+            header.extend_from_slice(&extra);
+        }
+        for field in [self.filename, self.comment].into_iter().flatten() {
+            header.extend_from_slice(field);
+            header.push(0);
+        }
+        if self.header_crc {
+            let crc = checksum::crc32(0, &header) as u16;
+            header.extend_from_slice(&crc.to_le_bytes());
+        }
+        Ok(header)
+    }
+}
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone, Copy)]
+pub struct GzipOptions<'a> {
+    // 0 (none) to 9 (best)
+    pub level: u8,
+    pub header: GzipHeader<'a>,
+}
+
+// ----------------------------------------------------------------------------
+impl Default for GzipOptions<'_> {
+    fn default() -> Self {
+        GzipOptions {
+            level: 6,
+            header: GzipHeader::default(),
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+impl GzipOptions<'_> {
+    pub(crate) fn header_bytes(&self) -> Result<Vec<u8>, Error> {
+        let xfl = match self.level {
+            1 => 4,
+            9.. => 2,
+            _ => 0,
+        };
+        self.header.encode(xfl)
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Compresses `src` into a single gzip member. With the file name, modification time and OS byte
+// 3 of the input file the output equals `gzip -k` on Unix: both compress short inputs alike and
+// set XFL from the level the same way, long inputs may be deflated differently.
+pub fn gzip_encode(src: &[u8], options: &GzipOptions) -> Result<Vec<u8>, Error> {
+    let mut dst = options.header_bytes()?;
+    deflate::deflate(&mut dst, src, options.level.into());
+    dst.extend_from_slice(&checksum::crc32(0, src).to_le_bytes());
+    dst.extend_from_slice(&(src.len() as u32).to_le_bytes());
+    Ok(dst)
+}
+
+// ----------------------------------------------------------------------------
+// Compresses `src` at `level`, 0 (none) to 9 (best), into a single gzip member with the given
+// header, see `gzip_encode`.
+pub fn gzip_compress(src: &[u8], level: u8, header: &GzipHeader) -> Result<Vec<u8>, Error> {
+    let header = *header;
+    gzip_encode(src, &GzipOptions { level, header })
+}
+
+// ----------------------------------------------------------------------------
+// Like `gzip_compress`, the data is deflated on multiple threads with `deflate_parallel`.
+#[cfg(feature = "std")]
+pub fn gzip_compress_parallel(
+    src: &[u8],
+    level: u8,
+    header: &GzipHeader,
+    options: &BatchOptions,
+) -> Result<Vec<u8>, Error> {
+    let header = *header;
+    let mut dst = GzipOptions { level, header }.header_bytes()?;
+    dst.extend_from_slice(&deflate_parallel(src, level, options));
+    dst.extend_from_slice(&checksum::crc32(0, src).to_le_bytes());
+    dst.extend_from_slice(&(src.len() as u32).to_le_bytes());
+    Ok(dst)
+}
+
+// ----------------------------------------------------------------------------
+// Decodes a single gzip member, a mismatching header CRC, CRC-32 or ISIZE fails with
+// `ChecksumMismatch`.
+pub fn gzip_decode(src: &[u8]) -> crate::Result<Vec<u8>> {
+    inflate_growing(src, Wrapper::Gzip, usize::MAX)
+}
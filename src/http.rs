@@ -0,0 +1,389 @@
+// HTTP Content-Encoding support, RFC 9110 section 8.4.1.
+// * https://datatracker.ietf.org/doc/html/rfc9110#section-8.4.1
+// * "gzip" is an RFC 1952 member, "deflate" an RFC 1950 zlib stream, "identity" is unchanged
+// * many servers send raw RFC 1951 data for "deflate", the decoder accepts both and tells them
+//   apart by the first 2 bytes
+use crate::stream::{Compress, FlushCompress, FlushDecompress, Inflater, Pending, Status};
+use crate::{checksum, deflate, inflate, Result};
+use alloc::vec::Vec;
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContentEncoding {
+    Identity,
+    Deflate,
+    Gzip,
+}
+
+// ----------------------------------------------------------------------------
+impl ContentEncoding {
+    // Maps a Content-Encoding token, case-insensitive, including the legacy "x-gzip" alias.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim();
+        if name.eq_ignore_ascii_case("identity") {
+            Some(ContentEncoding::Identity)
+        } else if name.eq_ignore_ascii_case("deflate") {
+            Some(ContentEncoding::Deflate)
+        } else if name.eq_ignore_ascii_case("gzip") || name.eq_ignore_ascii_case("x-gzip") {
+            Some(ContentEncoding::Gzip)
+        } else {
+            None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ContentEncoding::Identity => "identity",
+            ContentEncoding::Deflate => "deflate",
+            ContentEncoding::Gzip => "gzip",
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+fn status(finished: bool, output: &Pending, consumed: usize, written: usize) -> Status {
+    if finished && output.is_empty() {
+        Status::StreamEnd
+    } else if consumed == 0 && written == 0 {
+        Status::BufError
+    } else {
+        Status::Ok
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Encodes a response body, same call pattern as `stream::Compress`.
+#[derive(Debug)]
+pub struct Encoder {
+    encoding: ContentEncoding,
+    inner: Compress,
+    started: bool,
+    finished: bool,
+    crc: u32,
+    size: u32,
+    output: Pending,
+    total_in: u64,
+    total_out: u64,
+}
+
+// ----------------------------------------------------------------------------
+impl Encoder {
+    pub fn new(encoding: ContentEncoding, level: u8) -> Self {
+        Encoder {
+            encoding,
+            inner: Compress::new(level, encoding == ContentEncoding::Deflate),
+            started: false,
+            finished: false,
+            crc: 0,
+            size: 0,
+            output: Pending::default(),
+            total_in: 0,
+            total_out: 0,
+        }
+    }
+
+    pub fn encoding(&self) -> ContentEncoding {
+        self.encoding
+    }
+
+    pub fn total_in(&self) -> u64 {
+        self.total_in
+    }
+
+    pub fn total_out(&self) -> u64 {
+        self.total_out
+    }
+
+    pub fn run(&mut self, input: &[u8], output: &mut [u8], flush: FlushCompress) -> Status {
+        let consumed = if self.finished { 0 } else { input.len() };
+        if !self.finished {
+            self.encode(input, flush);
+            self.total_in += input.len() as u64;
+        }
+
+        let written = self.output.write_to(output);
+        self.total_out += written as u64;
+        status(self.finished, &self.output, consumed, written)
+    }
+
+    fn encode(&mut self, input: &[u8], flush: FlushCompress) {
+        if self.encoding == ContentEncoding::Identity {
+            self.output.data.extend_from_slice(input);
+            self.finished = flush == FlushCompress::Finish;
+            return;
+        }
+
+        if !self.started && self.encoding == ContentEncoding::Gzip {
+            self.output.data.extend_from_slice(&deflate::GZIP_HEADER);
+        }
+        self.started = true;
+        self.crc = checksum::crc32(self.crc, input);
+        self.size = self.size.wrapping_add(input.len() as u32);
+
+        // the compressor consumes all input at once, collect everything it produces
+        let mut buf = [0u8; 4096];
+        let mut input = input;
+        loop {
+            let total_out = self.inner.total_out();
+            let status = self.inner.run(input, &mut buf, flush);
+            let len = (self.inner.total_out() - total_out) as usize;
+            self.output.data.extend_from_slice(&buf[..len]);
+            input = &[];
+
+            if status == Status::StreamEnd {
+                if self.encoding == ContentEncoding::Gzip {
+                    self.output.data.extend_from_slice(&self.crc.to_le_bytes());
+                    self.output.data.extend_from_slice(&self.size.to_le_bytes());
+                }
+                self.finished = true;
+                break;
+            }
+            if len < buf.len() {
+                break;
+            }
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Decodes a response body, same call pattern as `stream::Decompress`. Compressed input is
+// decoded as it arrives, output beyond the `Limits` fails with `LimitExceeded`.
+#[derive(Debug)]
+pub struct Decoder {
+    encoding: ContentEncoding,
+    limits: Limits,
+    // start of the body until the gzip header is complete or the "deflate" format is known
+    head: Vec<u8>,
+    inflater: Option<Inflater>,
+    // CRC-32 of the gzip output so far and the trailer after the deflate data
+    crc: u32,
+    trailer: Vec<u8>,
+    finished: bool,
+    output: Pending,
+    total_in: u64,
+    total_out: u64,
+}
+
+// ----------------------------------------------------------------------------
+impl Decoder {
+    pub fn new(encoding: ContentEncoding) -> Self {
+        let limits = Limits {
+            max_output: usize::MAX,
+            max_ratio: None,
+        };
+        Self::with_limits(encoding, limits)
+    }
+
+    pub fn with_limits(encoding: ContentEncoding, limits: Limits) -> Self {
+        Decoder {
+            encoding,
+            limits,
+            head: Vec::new(),
+            inflater: None,
+            crc: 0,
+            trailer: Vec::new(),
+            finished: false,
+            output: Pending::default(),
+            total_in: 0,
+            total_out: 0,
+        }
+    }
+
+    pub fn encoding(&self) -> ContentEncoding {
+        self.encoding
+    }
+
+    pub fn total_in(&self) -> u64 {
+        self.total_in
+    }
+
+    pub fn total_out(&self) -> u64 {
+        self.total_out
+    }
+
+    // Identity data is passed through, compressed data is decoded up to the end of the input.
+    // Input behind the end of the compressed stream is not consumed.
+    pub fn run(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        flush: FlushDecompress,
+    ) -> Result<Status> {
+        let finish = flush == FlushDecompress::Finish;
+        let (consumed, written) = if self.encoding == ContentEncoding::Identity {
+            self.pass(input, output, finish)?
+        } else {
+            self.decode(input, output, finish)?
+        };
+        self.total_in += consumed as u64;
+        self.total_out += written as u64;
+        Ok(status(self.finished, &self.output, consumed, written))
+    }
+
+    fn pass(&mut self, input: &[u8], output: &mut [u8], finish: bool) -> Result<(usize, usize)> {
+        let consumed = if self.finished { 0 } else { input.len() };
+        if self.total_in + consumed as u64 > self.limits.max_output as u64 {
+            return Err(inflate::Error::LimitExceeded.into());
+        }
+        if !self.finished {
+            self.output.data.extend_from_slice(input);
+            self.finished = finish;
+        }
+        Ok((consumed, self.output.write_to(output)))
+    }
+
+    fn decode(&mut self, input: &[u8], output: &mut [u8], finish: bool) -> Result<(usize, usize)> {
+        if self.finished {
+            return Ok((0, 0));
+        }
+
+        let head;
+        let data = match self.inflater {
+            Some(_) => input,
+            None => {
+                self.head.extend_from_slice(input);
+                let Some((zlib, start)) = self.detect(finish)? else {
+                    return Ok((input.len(), 0));
+                };
+                self.inflater = Some(Inflater::new(zlib));
+                head = core::mem::take(&mut self.head);
+                &head[start..]
+            }
+        };
+        let Some(inflater) = self.inflater.as_mut() else {
+            return Ok((0, 0));
+        };
+
+        let (_, used, written) = inflater.decompress(data, output)?;
+        let mut rest = &data[used..];
+        let gzip = self.encoding == ContentEncoding::Gzip;
+        if gzip {
+            self.crc = checksum::crc32(self.crc, &output[..written]);
+            if inflater.is_finished() {
+                let len = rest.len().min(8 - self.trailer.len());
+                self.trailer.extend_from_slice(&rest[..len]);
+                rest = &rest[len..];
+            }
+        }
+        let consumed = input.len().saturating_sub(rest.len());
+
+        let decoded = inflater.total_decoded();
+        let max_input = self.total_in + consumed as u64;
+        let ratio_exceeded = self
+            .limits
+            .max_ratio
+            .is_some_and(|ratio| decoded > max_input.saturating_mul(ratio as u64));
+        if decoded > self.limits.max_output as u64 || ratio_exceeded {
+            return Err(inflate::Error::LimitExceeded.into());
+        }
+
+        let complete = inflater.is_finished() && (!gzip || self.trailer.len() == 8);
+        if complete && inflater.total_out() == decoded {
+            let size = (self.total_out + written as u64) as u32;
+            let trailer = &self.trailer;
+            if gzip
+                && (trailer[..4] != self.crc.to_le_bytes() || trailer[4..] != size.to_le_bytes())
+            {
+                return Err(inflate::Error::ChecksumMismatch.into());
+            }
+            self.finished = true;
+        } else if finish && !complete {
+            return Err(inflate::Error::Underflow.into());
+        }
+        Ok((consumed, written))
+    }
+
+    // The zlib header flag and the offset of the deflate data once the start of the body is
+    // known, "deflate" bodies are told from raw deflate data by their first 2 bytes.
+    fn detect(&self, finish: bool) -> Result<Option<(bool, usize)>> {
+        let header = match self.encoding {
+            ContentEncoding::Gzip => inflate::gzip_header(&self.head).map(|len| (false, len)),
+            _ if self.head.len() < 2 => Err(inflate::Error::Underflow),
+            _ => Ok((inflate::zlib_header(&self.head).is_ok(), 0)),
+        };
+        match header {
+            Ok(header) => Ok(Some(header)),
+            Err(inflate::Error::Underflow) if !finish => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(feature = "std")]
+const READ_CHUNK_SIZE: usize = 8192;
+
+// ----------------------------------------------------------------------------
+// Bounds for decoding untrusted bodies.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    // maximum number of decoded bytes
+    pub max_output: usize,
+    // maximum number of decoded bytes per encoded byte
+    pub max_ratio: Option<usize>,
+}
+
+// ----------------------------------------------------------------------------
+// Reads the decoded body of `reader`, fails with an `io::ErrorKind::InvalidData` error wrapping
+// `Error::Inflate(inflate::Error::LimitExceeded)` once a limit is exceeded. The encoded body is
+// read in chunks as output is requested, the ratio is checked against the bytes read so far.
+#[cfg(feature = "std")]
+pub struct LimitedReader<R> {
+    reader: R,
+    decoder: Decoder,
+    input: Vec<u8>,
+    pos: usize,
+    eof: bool,
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(feature = "std")]
+impl<R: std::io::Read> LimitedReader<R> {
+    pub fn new(reader: R, encoding: ContentEncoding, limits: Limits) -> Self {
+        LimitedReader {
+            reader,
+            decoder: Decoder::with_limits(encoding, limits),
+            input: Vec::new(),
+            pos: 0,
+            eof: false,
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(feature = "std")]
+impl<R: std::io::Read> std::io::Read for LimitedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let flush = if self.eof {
+                FlushDecompress::Finish
+            } else {
+                FlushDecompress::None
+            };
+            let total_in = self.decoder.total_in();
+            let total_out = self.decoder.total_out();
+            let status = self
+                .decoder
+                .run(&self.input[self.pos..], buf, flush)
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+            self.pos += (self.decoder.total_in() - total_in) as usize;
+            let written = (self.decoder.total_out() - total_out) as usize;
+            if written > 0 || status == Status::StreamEnd {
+                return Ok(written);
+            }
+            if self.eof {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+            if self.pos == self.input.len() {
+                self.input.resize(READ_CHUNK_SIZE, 0);
+                let len = self.reader.read(&mut self.input)?;
+                self.input.truncate(len);
+                self.pos = 0;
+                self.eof = len == 0;
+            }
+        }
+    }
+}
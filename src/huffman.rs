@@ -0,0 +1,253 @@
+// Canonical Huffman codes as used by deflate (RFC 1951, section 3.2.2).
+// * codes are assigned in order of increasing length, symbols of the same length in symbol order
+// * the decoding table is indexed with LSB-first bits: a first level of 9 bits, longer codes
+//   continue in second-level tables
+use crate::inflate::{Error, HuffmanPolicy, Tree};
+
+// ----------------------------------------------------------------------------
+pub(crate) fn reverse_bits(x: u16, count: usize) -> u16 {
+    let x = ((x & 0x5555) << 1) | ((x >> 1) & 0x5555);
+    let x = ((x & 0x3333) << 2) | ((x >> 2) & 0x3333);
+    let x = ((x & 0x0f0f) << 4) | ((x >> 4) & 0x0f0f);
+    let x = ((x & 0x00ff) << 8) | ((x >> 8) & 0x00ff);
+    x >> (16 - count)
+}
+
+// ----------------------------------------------------------------------------
+pub(crate) const TABLE_BITS: u8 = 9;
+// longest code, a decoder needs this many bits to look up any symbol
+pub const MAX_CODE_BITS: u8 = 15;
+// largest code set, the deflate literal/length alphabet
+pub const MAX_SYMBOLS: usize = 288;
+pub(crate) const NUM_DEFLATE_CODE_SYMBOLS: usize = MAX_SYMBOLS;
+pub(crate) const NUM_DISTANCE_SYMBOLS: usize = 32;
+
+// table entry for codes not assigned to any symbol in incomplete code sets
+pub(crate) const INVALID_SYMBOL: u16 = 0xffff;
+
+// ----------------------------------------------------------------------------
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct VarLenCode {
+    pub(crate) code: u16,
+    pub(crate) len: u8,
+}
+
+pub(crate) type LookupTable = [VarLenCode; 512 + 512];
+
+// initial contents of a lookup table, every code without symbol
+pub(crate) const EMPTY_TABLE: LookupTable = [VarLenCode {
+    code: INVALID_SYMBOL,
+    len: 1,
+}; 512 + 512];
+
+// ------------------------------------------------------------------------
+#[allow(clippy::comparison_chain, clippy::needless_range_loop)]
+pub(crate) fn generate_codes(
+    codes: &mut [u16],
+    lengths: &[u8],
+    tree: Tree,
+    policy: HuffmanPolicy,
+) -> core::result::Result<(), Error> {
+    const MAX_CODE_LENGTH: usize = 16;
+
+    // count number of instances of each code length
+    let mut code_len_count = [0; MAX_CODE_LENGTH];
+    for len in lengths.iter() {
+        code_len_count[*len as usize] += 1;
+    }
+
+    // monitor for over- or under-subscription by tracking available_codes
+    let mut available_codes: i32 = 1;
+    for i in 1..MAX_CODE_LENGTH {
+        available_codes = (available_codes << 1) - code_len_count[i] as i32;
+    }
+
+    // For a proper Huffman tree, the sum of all code lengths should match the total number of
+    // leaves (symbols) in the binary tree (available_codes == 0).
+    if available_codes < 0 {
+        return Err(Error::OverSubscribedTree(tree));
+    } else if available_codes > 0 {
+        // trivial under-subscriptions tolerated by zlib: no symbols at all, or a single symbol
+        // with a one-bit code (not for the code length tree)
+        let used = code_len_count[1..].iter().sum::<u16>();
+        let single = used == 1 && code_len_count[1] == 1 && tree != Tree::CodeLength;
+        if policy == HuffmanPolicy::Pedantic || (used != 0 && !single) {
+            return Err(Error::UnderSubscribedTree(tree));
+        }
+    }
+
+    // calculate next code for each code length
+    let mut next_code = [0u16; MAX_CODE_LENGTH];
+    for i in 1..MAX_CODE_LENGTH {
+        next_code[i] = next_code[i - 1].wrapping_add(code_len_count[i - 1]) << 1;
+    }
+
+    for (len, code) in lengths.iter().zip(codes.iter_mut()) {
+        if *len != 0 {
+            let len = *len as usize;
+            // Huffman bits are given in MSB first order but the bit reader reads LSB first
+            *code = reverse_bits(next_code[len], len);
+            next_code[len] = next_code[len].wrapping_add(1);
+        }
+    }
+
+    Ok(())
+}
+
+// ------------------------------------------------------------------------
+fn fill_table(table: &mut [VarLenCode], num: usize, offset: usize, len: u8, code: u16) {
+    for entry in table[offset..].iter_mut().step_by(1 << len).take(num) {
+        *entry = VarLenCode { code, len };
+    }
+}
+
+// ------------------------------------------------------------------------
+pub(crate) fn make_lookup_table(
+    lengths: &[u8],
+    tree: Tree,
+    policy: HuffmanPolicy,
+) -> core::result::Result<LookupTable, Error> {
+    let mut table = EMPTY_TABLE;
+    build_lookup_table(&mut table, lengths, tree, policy)?;
+    Ok(table)
+}
+
+// ------------------------------------------------------------------------
+// Like `make_lookup_table`, but overwrites `table` in place so decoders can reuse its memory.
+pub(crate) fn build_lookup_table(
+    table: &mut LookupTable,
+    lengths: &[u8],
+    tree: Tree,
+    policy: HuffmanPolicy,
+) -> core::result::Result<(), Error> {
+    const TABLE_SIZE: usize = 1 << TABLE_BITS; // size of the first table
+    const TABLE_MASK: u16 = (1 << TABLE_BITS) - 1;
+
+    let mut codes = [0u16; NUM_DEFLATE_CODE_SYMBOLS];
+    let codes = &mut codes[..lengths.len()];
+    generate_codes(codes, lengths, tree, policy)?;
+    *table = EMPTY_TABLE;
+
+    // compute maxlens: max total bit length of symbols sharing prefix in the first table
+    let mut maxlens = [0; TABLE_SIZE];
+    for (len, code) in lengths.iter().zip(codes.iter()) {
+        if *len <= TABLE_BITS {
+            // symbols that fit in first table don't increase secondary table size
+            continue;
+        }
+
+        // get the FIRSTBITS MSBs, the MSBs of the symbol are encoded first.
+        let index = (*code & TABLE_MASK) as usize;
+        maxlens[index] = maxlens[index].max(*len);
+    }
+
+    // fill in the first table for long symbols: max prefix size and pointer to secondary tables
+    let mut pointer = TABLE_SIZE;
+    for i in 0..TABLE_SIZE {
+        let l = maxlens[i];
+        if l <= TABLE_BITS {
+            continue;
+        }
+        table[i].len = l;
+        table[i].code = pointer as u16;
+
+        let scondary_table_size = 1 << (l - TABLE_BITS);
+        pointer += scondary_table_size;
+    }
+
+    // fill in the first table for short symbols, or secondary table for long symbols
+    for (i, (len, code)) in lengths.iter().zip(codes.iter()).enumerate() {
+        if *len == 0 {
+            continue;
+        }
+
+        if *len <= TABLE_BITS {
+            // short symbol, fully in first table, replicated num times if l < FIRSTBITS
+            let num = 1usize << (TABLE_BITS - *len);
+            fill_table(table, num, *code as usize, *len, i as u16);
+        } else {
+            // long symbol, shares prefix with other long symbols in first lookup table, needs second lookup
+            // the FIRSTBITS MSBs of the symbol are the first table index
+            let index = (*code & TABLE_MASK) as usize;
+            let maxlen = table[index].len;
+
+            // amount of entries of this symbol in secondary table
+            let num = 1usize << (maxlen - *len);
+            let start = table[index].code as usize;
+            let code = *code >> TABLE_BITS;
+            let len = *len - TABLE_BITS;
+            fill_table(&mut table[start..], num, code as usize, len, i as u16);
+        }
+    }
+
+    Ok(())
+}
+
+// ----------------------------------------------------------------------------
+// Table-driven decoder of a canonical Huffman code set, for deflate and other formats with the
+// same code assignment and LSB-first bit order.
+#[derive(Clone)]
+pub struct HuffmanDecoder {
+    table: LookupTable,
+}
+
+// ----------------------------------------------------------------------------
+impl HuffmanDecoder {
+    // Builds the decoder for the code lengths of the symbols 0..lengths.len(), 0 for unused
+    // symbols. Fails with `InvalidCodeLength` for more than `MAX_SYMBOLS` symbols or codes longer
+    // than `MAX_CODE_BITS`, with `OverSubscribedTree` or `UnderSubscribedTree` for code sets that
+    // are no proper prefix code. As zlib, an empty set and a single one-bit code are accepted.
+    pub fn from_lengths(lengths: &[u8]) -> Result<Self, Error> {
+        Self::with_policy(lengths, Tree::LiteralLength, HuffmanPolicy::ZlibCompatible)
+    }
+
+    // Like `from_lengths`, `tree` is reported with tree construction errors.
+    pub fn with_policy(lengths: &[u8], tree: Tree, policy: HuffmanPolicy) -> Result<Self, Error> {
+        if lengths.len() > MAX_SYMBOLS || lengths.iter().any(|len| *len > MAX_CODE_BITS) {
+            return Err(Error::InvalidCodeLength);
+        }
+        let table = make_lookup_table(lengths, tree, policy)?;
+        Ok(HuffmanDecoder { table })
+    }
+
+    // Decodes the symbol at the start of `bits`, the next bit of the stream in the LSB. Returns
+    // the symbol and its code length, or `None` for a code without symbol. At least
+    // `MAX_CODE_BITS` bits must be given, or all bits up to the end of the stream.
+    pub fn decode(&self, bits: u32) -> Option<(u16, u8)> {
+        let entry = self.table[(bits & ((1 << TABLE_BITS) - 1)) as usize];
+        let (symbol, len) = if entry.len <= TABLE_BITS {
+            (entry.code, entry.len)
+        } else {
+            let count = entry.len - TABLE_BITS;
+            let idx = ((bits >> TABLE_BITS) & ((1 << count) - 1)) as usize;
+            let entry = self.table[entry.code as usize + idx];
+            (entry.code, TABLE_BITS + entry.len)
+        };
+        (symbol != INVALID_SYMBOL).then_some((symbol, len))
+    }
+}
+
+// ----------------------------------------------------------------------------
+impl core::fmt::Debug for HuffmanDecoder {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("HuffmanDecoder").finish_non_exhaustive()
+    }
+}
+
+// ----------------------------------------------------------------------------
+// The canonical codes for `lengths` in MSB first (transmission) order, 0 for unused symbols.
+// Fails as `HuffmanDecoder::from_lengths`.
+pub fn canonical_codes(lengths: &[u8]) -> Result<alloc::vec::Vec<u16>, Error> {
+    if lengths.len() > MAX_SYMBOLS || lengths.iter().any(|len| *len > MAX_CODE_BITS) {
+        return Err(Error::InvalidCodeLength);
+    }
+    let mut codes = alloc::vec![0u16; lengths.len()];
+    let policy = HuffmanPolicy::ZlibCompatible;
+    generate_codes(&mut codes, lengths, Tree::LiteralLength, policy)?;
+    for (code, len) in codes.iter_mut().zip(lengths) {
+        if *len != 0 {
+            *code = reverse_bits(*code, *len as usize);
+        }
+    }
+    Ok(codes)
+}
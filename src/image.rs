@@ -0,0 +1,96 @@
+// `image` crate integration: PNG decoding as an `image::ImageDecoder`.
+// * 8 bit greyscale and truecolor images keep their layout, indexed and sub-byte images as well
+//   as images with a tRNS chunk are expanded to RGBA8
+// * 16 bit images are rejected by `new` like by the decoder
+use crate::png_read::{self, PNGChunkIHDR, PNGColorType, Transparency};
+use image::error::{DecodingError, ImageFormatHint};
+use image::{ColorType, ExtendedColorType, ImageDecoder, ImageError, ImageFormat, ImageResult};
+
+// ----------------------------------------------------------------------------
+impl From<png_read::Error> for ImageError {
+    fn from(err: png_read::Error) -> Self {
+        ImageError::Decoding(DecodingError::new(
+            ImageFormatHint::Exact(ImageFormat::Png),
+            err,
+        ))
+    }
+}
+
+// ----------------------------------------------------------------------------
+pub struct PngImageDecoder<'a> {
+    png: &'a [u8],
+    ihdr: PNGChunkIHDR,
+    trns: Option<Transparency>,
+}
+
+// ----------------------------------------------------------------------------
+impl<'a> PngImageDecoder<'a> {
+    // Parses the PNG header, pixel data is decoded by `read_image`.
+    pub fn new(png: &'a [u8]) -> png_read::Result<Self> {
+        let (ihdr, _) = png_read::read_ihdr(png, true)?;
+        if ihdr.bit_depth > 8 {
+            return Err(png_read::Error::UnsupportedFormat);
+        }
+        let trns = png_read::png_transparency(png)?;
+        Ok(PngImageDecoder { png, ihdr, trns })
+    }
+}
+
+// ----------------------------------------------------------------------------
+impl ImageDecoder for PngImageDecoder<'_> {
+    fn dimensions(&self) -> (u32, u32) {
+        (self.ihdr.width as u32, self.ihdr.height as u32)
+    }
+
+    fn color_type(&self) -> ColorType {
+        if self.trns.is_some() {
+            return ColorType::Rgba8;
+        }
+        match (self.ihdr.color_type, self.ihdr.bit_depth) {
+            (PNGColorType::Greyscale, 8) => ColorType::L8,
+            (PNGColorType::GreyscaleAplha, 8) => ColorType::La8,
+            (PNGColorType::TrueColor, 8) => ColorType::Rgb8,
+            _ => ColorType::Rgba8,
+        }
+    }
+
+    fn original_color_type(&self) -> ExtendedColorType {
+        match (self.ihdr.color_type, self.ihdr.bit_depth) {
+            (PNGColorType::Greyscale, 1) => ExtendedColorType::L1,
+            (PNGColorType::Greyscale, 2) => ExtendedColorType::L2,
+            (PNGColorType::Greyscale, 4) => ExtendedColorType::L4,
+            (PNGColorType::IndexedColor, 1) => ExtendedColorType::Rgb1,
+            (PNGColorType::IndexedColor, 2) => ExtendedColorType::Rgb2,
+            (PNGColorType::IndexedColor, 4) => ExtendedColorType::Rgb4,
+            (PNGColorType::IndexedColor, 8) => ExtendedColorType::Rgb8,
+            _ => self.color_type().into(),
+        }
+    }
+
+    fn read_image(self, buf: &mut [u8]) -> ImageResult<()> {
+        let (ihdr, plte, data) = png_read::png_read(self.png)?;
+        if self.color_type() == ColorType::Rgba8 {
+            buf.copy_from_slice(&png_read::to_rgba_with_transparency(
+                &ihdr,
+                &plte,
+                self.trns.as_ref(),
+                &data,
+            )?);
+            return Ok(());
+        }
+
+        // drop the leading filter byte of each scanline
+        let row_bytes = ihdr.width * self.color_type().bytes_per_pixel() as usize;
+        for (dst, src) in buf
+            .chunks_exact_mut(row_bytes)
+            .zip(data.chunks_exact(row_bytes + 1))
+        {
+            dst.copy_from_slice(&src[1..]);
+        }
+        Ok(())
+    }
+
+    fn read_image_boxed(self: Box<Self>, buf: &mut [u8]) -> ImageResult<()> {
+        (*self).read_image(buf)
+    }
+}
@@ -2,9 +2,20 @@
 // * https://datatracker.ietf.org/doc/html/rfc1951
 // * based on the excellent LodePNG implementation by Lode Vandevenne:
 //   https://lodev.org/lodepng/
+use crate::bits::{BitReader, BITBUF_BYTES};
+use crate::huffman::{
+    build_lookup_table, generate_codes, make_lookup_table, reverse_bits, LookupTable, EMPTY_TABLE,
+};
+use crate::huffman::{INVALID_SYMBOL, MAX_CODE_BITS, TABLE_BITS};
+use crate::huffman::{NUM_DEFLATE_CODE_SYMBOLS, NUM_DISTANCE_SYMBOLS};
+use crate::warning::Warning;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::sync::atomic::{AtomicBool, Ordering};
 
 // ----------------------------------------------------------------------------
 #[derive(Debug, PartialEq)]
+#[non_exhaustive]
 pub enum Error {
     Underflow,
     Overflow,
@@ -14,273 +25,621 @@ pub enum Error {
     InvalidBlockLength,
     InvalidCodeLength,
     InvalidCopyLength,
-    InvalidDistance,
+    // back-reference beyond the decoded data or the window
+    InvalidDistance { distance: usize, available: usize },
     InvalidLength,
     InvalidSymbol,
     InvalidData,
     InvalidFirstCopyCode,
     MissingEndMarker,
-    UnderSubscribedTree,
-    OverSubscribedTree,
+    UnderSubscribedTree(Tree),
+    OverSubscribedTree(Tree),
+    ChecksumMismatch,
+    Cancelled,
+    LimitExceeded,
+    // the zlib stream needs the preset dictionary with this Adler-32, none or another one was
+    // supplied
+    DictionaryRequired(u32),
+    // a saved `stream::Inflater` state that is truncated, corrupted or of another format version
+    InvalidState,
 }
 
 // ----------------------------------------------------------------------------
-impl std::fmt::Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let err = format!("{:?}", self);
-        f.write_str(&err)
+// Huffman tree of a deflate block, reported with tree construction errors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tree {
+    CodeLength,
+    LiteralLength,
+    Distance,
+}
+
+// ----------------------------------------------------------------------------
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{self:?}")
     }
 }
 
 // ----------------------------------------------------------------------------
+#[cfg(feature = "std")]
 impl std::error::Error for Error {}
 
 // ----------------------------------------------------------------------------
-fn show_bits(bp: &usize, src: &[u8], count: u8) -> std::result::Result<u16, Error> {
-    let bytepos = *bp >> 3;
-    let shift = *bp & 7;
-    let mask = (1 << count) - 1;
-
-    if bytepos + 2 < src.len() {
-        let bits32 = ((src[bytepos + 2] as u32) << 16)
-            | ((src[bytepos + 1] as u32) << 8)
-            | (src[bytepos] as u32);
-        Ok((bits32 >> shift) as u16 & mask)
-    } else if bytepos + 1 < src.len() {
-        let bits16 = ((src[bytepos + 1] as u16) << 8) | (src[bytepos] as u16);
-        Ok((bits16 >> shift) & mask)
-    } else if bytepos + 1 == src.len() {
-        let bits8 = src[bytepos] as u16;
-        Ok((bits8 >> shift) & mask)
-    } else {
-        Err(Error::Underflow)
-    }
+// Where decoding stopped, returned by `inflate_verbose` to locate damage in the input.
+#[derive(Debug, PartialEq)]
+pub struct ErrorContext {
+    pub error: Error,
+    // input bit offset at which the error was detected
+    pub bit_pos: usize,
+    // output bytes decoded before the error
+    pub out_pos: usize,
+    // zero-based index of the failing block and the bit offset of its header
+    pub block: usize,
+    pub block_bit_pos: usize,
 }
 
 // ----------------------------------------------------------------------------
-fn read_bits(src: &[u8], sptr: &mut usize, count: u8) -> std::result::Result<u16, Error> {
-    let res = show_bits(sptr, src, count)?;
-    *sptr += count as usize;
-    Ok(res)
+impl ErrorContext {
+    pub fn byte_pos(&self) -> usize {
+        self.bit_pos / 8
+    }
 }
 
 // ----------------------------------------------------------------------------
-fn reverse_bits(x: u16, count: usize) -> u16 {
-    let x = ((x & 0x5555) << 1) | ((x >> 1) & 0x5555);
-    let x = ((x & 0x3333) << 2) | ((x >> 2) & 0x3333);
-    let x = ((x & 0x0f0f) << 4) | ((x >> 4) & 0x0f0f);
-    let x = ((x & 0x00ff) << 8) | ((x >> 8) & 0x00ff);
-    x >> (16 - count)
+impl core::fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(
+            f,
+            "{} at input byte {} (bit {}) in block {} starting at bit {}, {} bytes decoded",
+            self.error,
+            self.byte_pos(),
+            self.bit_pos,
+            self.block,
+            self.block_bit_pos,
+            self.out_pos
+        )
+    }
 }
 
 // ----------------------------------------------------------------------------
-const TABLE_BITS: u8 = 9;
+#[cfg(feature = "std")]
+impl std::error::Error for ErrorContext {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
 
 // ----------------------------------------------------------------------------
-#[derive(Copy, Clone, Debug)]
-struct VarLenCode {
-    code: u16,
-    len: u8,
+// Stable numeric codes (100-199) for FFI and logging, codes are never reused or renumbered.
+impl Error {
+    pub fn code(&self) -> u32 {
+        use Error::*;
+        match self {
+            Underflow => 100,
+            Overflow => 101,
+            InvalidHeader => 102,
+            InvalidBitstream => 103,
+            InvalidBlockType => 104,
+            InvalidBlockLength => 105,
+            InvalidCodeLength => 106,
+            InvalidCopyLength => 107,
+            InvalidDistance { .. } => 108,
+            InvalidLength => 109,
+            InvalidSymbol => 110,
+            InvalidData => 111,
+            InvalidFirstCopyCode => 112,
+            MissingEndMarker => 113,
+            UnderSubscribedTree(Tree::CodeLength) => 114,
+            UnderSubscribedTree(Tree::LiteralLength) => 115,
+            UnderSubscribedTree(Tree::Distance) => 116,
+            OverSubscribedTree(Tree::CodeLength) => 117,
+            OverSubscribedTree(Tree::LiteralLength) => 118,
+            OverSubscribedTree(Tree::Distance) => 119,
+            ChecksumMismatch => 120,
+            Cancelled => 121,
+            LimitExceeded => 122,
+            DictionaryRequired(_) => 123,
+            InvalidState => 124,
+        }
+    }
+
+    pub fn from_code(code: u32) -> Option<Self> {
+        use Error::*;
+        Some(match code {
+            100 => Underflow,
+            101 => Overflow,
+            102 => InvalidHeader,
+            103 => InvalidBitstream,
+            104 => InvalidBlockType,
+            105 => InvalidBlockLength,
+            106 => InvalidCodeLength,
+            107 => InvalidCopyLength,
+            108 => InvalidDistance {
+                distance: 0,
+                available: 0,
+            },
+            109 => InvalidLength,
+            110 => InvalidSymbol,
+            111 => InvalidData,
+            112 => InvalidFirstCopyCode,
+            113 => MissingEndMarker,
+            114 => UnderSubscribedTree(Tree::CodeLength),
+            115 => UnderSubscribedTree(Tree::LiteralLength),
+            116 => UnderSubscribedTree(Tree::Distance),
+            117 => OverSubscribedTree(Tree::CodeLength),
+            118 => OverSubscribedTree(Tree::LiteralLength),
+            119 => OverSubscribedTree(Tree::Distance),
+            120 => ChecksumMismatch,
+            121 => Cancelled,
+            122 => LimitExceeded,
+            123 => DictionaryRequired(0),
+            124 => InvalidState,
+            _ => return None,
+        })
+    }
 }
 
-type LookupTable = [VarLenCode; 512 + 512];
+// ----------------------------------------------------------------------------
+// Largest back-reference distance a deflate stream can encode.
+pub(crate) const MAX_WINDOW_SIZE: usize = 32768;
 
-// ------------------------------------------------------------------------
-#[allow(clippy::comparison_chain)]
-fn generate_codes(codes: &mut [u16], lengths: &[u8]) -> std::result::Result<bool, Error> {
-    const MAX_CODE_LENGTH: usize = 16;
+// Window of Deflate64, a stream decoded with it uses the Deflate64 symbol tables.
+pub(crate) const DEFLATE64_WINDOW_SIZE: usize = 65536;
 
-    // count number of instances of each code length
-    let mut code_len_count = [0; MAX_CODE_LENGTH];
-    for len in lengths.iter() {
-        code_len_count[*len as usize] += 1;
-    }
+// ----------------------------------------------------------------------------
+// Handling of incomplete (under-subscribed) Huffman code sets.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum HuffmanPolicy {
+    // Reject every incomplete code set.
+    Pedantic,
+    // Accept what zlib accepts: an empty code set, or a single one-bit code in the
+    // literal/length and distance trees. Unused codes fail when they are read.
+    #[default]
+    ZlibCompatible,
+}
 
-    // monitor for over- or under-subscription by tracking available_codes
-    let mut available_codes: i32 = 1;
-    for i in 1..MAX_CODE_LENGTH {
-        available_codes = (available_codes << 1) - code_len_count[i] as i32;
-    }
+// ----------------------------------------------------------------------------
+// Rule sets for `InflateOptions::profile`. The defaults of `InflateOptions` lie in between:
+// checksums and the zlib window are verified, truncated input decodes as zeros, zlib's
+// incomplete code sets are accepted and container anomalies are only reported as warnings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DecodeProfile {
+    // Every rule of the specifications: checksums including those of ancillary PNG chunks, chunk
+    // order, complete Huffman codes, exact input and archive sizes.
+    Strict,
+    // What browsers and unzip tools accept: chunks out of order, back-references beyond the
+    // zlib window or before the start of the output, the latter decoding as zeros.
+    Lenient,
+}
 
-    if available_codes != 0 {
-        // For a proper Huffman tree, the sum of all code lengths should match the total number of
-        // leaves (symbols) in the binary tree (available_codes == 0).
-        return if available_codes == 0x8000 || available_codes == 0x4000 {
-            // trivial under-subscriptions: only a single symbol, or no symbols at all
-            Ok(false)
-        } else if available_codes < 0 {
-            Err(Error::OverSubscribedTree)
-        } else {
-            Err(Error::UnderSubscribedTree)
-        };
+// ----------------------------------------------------------------------------
+// Progress reporting: `callback(bytes_in, bytes_out)` is invoked each time another `interval`
+// output bytes have been produced, and once more when the stream is complete. `bytes_in` counts
+// the consumed bytes of the deflate data.
+#[derive(Clone, Copy)]
+pub struct Progress<'a> {
+    pub interval: usize,
+    pub callback: &'a dyn Fn(usize, usize),
+}
+
+// ----------------------------------------------------------------------------
+impl core::fmt::Debug for Progress<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Progress")
+            .field("interval", &self.interval)
+            .finish_non_exhaustive()
     }
+}
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone, Copy)]
+pub struct InflateOptions<'a> {
+    // Reject back-references reaching further than the window size declared in the zlib header
+    // (CINFO). Disable to accept streams from encoders that understate their window.
+    pub enforce_zlib_window: bool,
+    pub huffman_policy: HuffmanPolicy,
+    // Fail with `Error::Underflow` as soon as a read goes past the end of input instead of
+    // decoding the missing bits as zeros, which can turn truncated input into bogus symbols.
+    pub strict_end_of_input: bool,
+    // Decode back-references reaching before the start of the output, but within the window, as
+    // zeros like zlib built with INFLATE_ALLOW_INVALID_DISTANCE_TOOFAR_ARRR instead of failing
+    // with `Error::InvalidDistance`. Streaming decoders always fail.
+    pub allow_distance_too_far: bool,
+    // Fail on container anomalies otherwise only reported as warnings: CRC mismatches of
+    // ancillary PNG chunks, data after IEND, ZIP archives behind a prefix and inconsistent end of
+    // central directory records.
+    pub strict_containers: bool,
+    pub progress: Option<Progress<'a>>,
+    // Decoding stops with `Error::Cancelled` soon after this flag is set, e.g. from another
+    // thread.
+    pub cancel: Option<&'a AtomicBool>,
+    // Counters of successful decodes are added to this cell, they stay zero without the
+    // `metrics` feature.
+    pub metrics: Option<&'a core::cell::Cell<InflateMetrics>>,
+    // Non-fatal anomalies of the container formats are appended here.
+    pub warnings: Option<&'a RefCell<Vec<Warning>>>,
+    // Progressive display of interlaced PNG images.
+    #[cfg(feature = "png")]
+    pub adam7_preview: Option<crate::png_read::Adam7Preview<'a>>,
+    // Accept PNG chunks out of order or repeated, e.g. PLTE after IDAT, with a warning instead
+    // of `png_read::Error::MisplacedChunk` or `DuplicateChunk`. The last PLTE wins.
+    #[cfg(feature = "png")]
+    pub lenient_chunk_order: bool,
+    // Preset dictionary of zlib streams with the FDICT flag, at most the last 32 KB are used.
+    pub dictionary: Option<&'a [u8]>,
+    // Verify zlib and gzip trailers, CRCs of critical PNG chunks and CRC-32s of ZIP entries.
+    // Disable to skip the cost for trusted data.
+    pub verify_checksums: bool,
+    // Bounds for untrusted data, exceeding one fails with `Error::LimitExceeded`: decoded bytes
+    // (not counting a dictionary), deflate blocks and dynamic Huffman blocks, each of which
+    // rebuilds the lookup tables.
+    pub max_output: usize,
+    pub max_blocks: usize,
+    pub max_dynamic_tables: usize,
+}
 
-    // calculate next code for each code length
-    let mut next_code = [0u16; MAX_CODE_LENGTH];
-    for i in 1..MAX_CODE_LENGTH {
-        next_code[i] = next_code[i - 1].wrapping_add(code_len_count[i - 1]) << 1;
+// ----------------------------------------------------------------------------
+impl Default for InflateOptions<'_> {
+    fn default() -> Self {
+        InflateOptions {
+            enforce_zlib_window: true,
+            huffman_policy: HuffmanPolicy::default(),
+            strict_end_of_input: false,
+            allow_distance_too_far: false,
+            strict_containers: false,
+            progress: None,
+            cancel: None,
+            metrics: None,
+            warnings: None,
+            #[cfg(feature = "png")]
+            adam7_preview: None,
+            #[cfg(feature = "png")]
+            lenient_chunk_order: false,
+            dictionary: None,
+            verify_checksums: true,
+            max_output: usize::MAX,
+            max_blocks: usize::MAX,
+            max_dynamic_tables: usize::MAX,
+        }
     }
+}
 
-    for (len, code) in lengths.iter().zip(codes.iter_mut()) {
-        if *len != 0 {
-            let len = *len as usize;
-            // Huffman bits are given in MSB first order but the bit reader reads LSB first
-            *code = reverse_bits(next_code[len], len);
-            next_code[len] = next_code[len].wrapping_add(1);
+// ----------------------------------------------------------------------------
+impl InflateOptions<'_> {
+    // Options enforcing or relaxing the rules as `profile` describes, the others at their
+    // defaults.
+    pub fn profile(profile: DecodeProfile) -> Self {
+        let strict = profile == DecodeProfile::Strict;
+        InflateOptions {
+            enforce_zlib_window: strict,
+            huffman_policy: match profile {
+                DecodeProfile::Strict => HuffmanPolicy::Pedantic,
+                DecodeProfile::Lenient => HuffmanPolicy::ZlibCompatible,
+            },
+            strict_end_of_input: strict,
+            allow_distance_too_far: !strict,
+            strict_containers: strict,
+            #[cfg(feature = "png")]
+            lenient_chunk_order: !strict,
+            ..Default::default()
         }
     }
+}
 
-    Ok(true)
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct InflateMetrics {
+    pub stored_blocks: u64,
+    pub fixed_blocks: u64,
+    pub dynamic_blocks: u64,
+    // Huffman lookup tables built, including the code length tables of dynamic blocks
+    pub table_rebuilds: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    // literal/length symbols decoded by the fast loop and by the bounds-checked loop
+    pub fast_path_iterations: u64,
+    pub slow_path_iterations: u64,
+    // literals and matches of the Huffman blocks, stored bytes count towards neither
+    pub literals: u64,
+    pub matches: u64,
 }
 
-// ------------------------------------------------------------------------
-fn fill_table(table: &mut [VarLenCode], num: usize, offset: usize, len: u8, code: u16) {
-    for entry in table[offset..].iter_mut().step_by(1 << len).take(num) {
-        *entry = VarLenCode { code, len };
+// ----------------------------------------------------------------------------
+impl core::ops::AddAssign for InflateMetrics {
+    fn add_assign(&mut self, rhs: Self) {
+        self.stored_blocks += rhs.stored_blocks;
+        self.fixed_blocks += rhs.fixed_blocks;
+        self.dynamic_blocks += rhs.dynamic_blocks;
+        self.table_rebuilds += rhs.table_rebuilds;
+        self.bytes_in += rhs.bytes_in;
+        self.bytes_out += rhs.bytes_out;
+        self.fast_path_iterations += rhs.fast_path_iterations;
+        self.slow_path_iterations += rhs.slow_path_iterations;
+        self.literals += rhs.literals;
+        self.matches += rhs.matches;
     }
 }
 
-// ------------------------------------------------------------------------
-fn make_lookup_table(lengths: &[u8]) -> std::result::Result<LookupTable, Error> {
-    const TABLE_SIZE: usize = 1 << TABLE_BITS; // size of the first table
-    const TABLE_MASK: u16 = (1 << TABLE_BITS) - 1;
-    let mut table = [VarLenCode { code: 0, len: 1 }; 1024];
+// ----------------------------------------------------------------------------
+// Cancellation is polled at the start of every block and whenever this many more output bytes
+// have been produced.
+const CANCEL_CHECK_INTERVAL: usize = 16384;
 
-    let mut codes = vec![0; lengths.len()];
-    if !generate_codes(&mut codes, lengths)? {
-        // no codes generated for trivial cases
-        return Ok(table);
-    }
+// ----------------------------------------------------------------------------
+struct Monitor<'a> {
+    progress: Option<Progress<'a>>,
+    zeros_too_far: bool,
+    cancel: Option<&'a AtomicBool>,
+    next_report: usize,
+    next: usize,
+    #[cfg(feature = "metrics")]
+    metrics: InflateMetrics,
+}
 
-    // compute maxlens: max total bit length of symbols sharing prefix in the first table
-    let mut maxlens = [0; TABLE_SIZE];
-    for (len, code) in lengths.iter().zip(codes.iter_mut()) {
-        if *len <= TABLE_BITS {
-            // symbols that fit in first table don't increase secondary table size
-            continue;
-        }
+// ----------------------------------------------------------------------------
+impl<'a> Monitor<'a> {
+    fn new(options: &InflateOptions<'a>) -> Self {
+        let progress = options.progress;
+        let mut monitor = Monitor {
+            progress,
+            zeros_too_far: options.allow_distance_too_far,
+            cancel: options.cancel,
+            next_report: progress.map_or(usize::MAX, |p| p.interval.max(1)),
+            next: 0,
+            #[cfg(feature = "metrics")]
+            metrics: InflateMetrics::default(),
+        };
+        monitor.schedule(0);
+        monitor
+    }
 
-        // get the FIRSTBITS MSBs, the MSBs of the symbol are encoded first.
-        let index = (*code & TABLE_MASK) as usize;
-        maxlens[index] = maxlens[index].max(*len);
+    fn schedule(&mut self, dptr: usize) {
+        self.next = match self.cancel {
+            Some(_) => self
+                .next_report
+                .min(dptr.saturating_add(CANCEL_CHECK_INTERVAL)),
+            None => self.next_report,
+        };
     }
 
-    // fill in the first table for long symbols: max prefix size and pointer to secondary tables
-    let mut pointer = TABLE_SIZE;
-    for i in 0..TABLE_SIZE {
-        let l = maxlens[i];
-        if l <= TABLE_BITS {
-            continue;
+    fn update(&mut self, sptr: usize, dptr: usize) -> core::result::Result<(), Error> {
+        if dptr >= self.next {
+            self.check(sptr, dptr)?;
         }
-        table[i].len = l;
-        table[i].code = pointer as u16;
-
-        let scondary_table_size = 1 << (l - TABLE_BITS);
-        pointer += scondary_table_size;
+        Ok(())
     }
 
-    // fill in the first table for short symbols, or secondary table for long symbols
-    for (i, (len, code)) in lengths.iter().zip(codes.iter_mut()).enumerate() {
-        if *len == 0 {
-            continue;
+    fn check(&mut self, sptr: usize, dptr: usize) -> core::result::Result<(), Error> {
+        if self
+            .cancel
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+        {
+            return Err(Error::Cancelled);
         }
-
-        if *len <= TABLE_BITS {
-            // short symbol, fully in first table, replicated num times if l < FIRSTBITS
-            let num = 1usize << (TABLE_BITS - *len);
-            fill_table(&mut table, num, *code as usize, *len, i as u16);
-        } else {
-            // long symbol, shares prefix with other long symbols in first lookup table, needs second lookup
-            // the FIRSTBITS MSBs of the symbol are the first table index
-            let index = (*code & TABLE_MASK) as usize;
-            let maxlen = table[index].len;
-
-            // amount of entries of this symbol in secondary table
-            let num = 1usize << (maxlen - *len);
-            let start = table[index].code as usize;
-            let code = *code >> TABLE_BITS;
-            let len = *len - TABLE_BITS;
-            fill_table(&mut table[start..], num, code as usize, len, i as u16);
+        if dptr >= self.next_report {
+            self.report(sptr, dptr);
         }
+        self.schedule(dptr);
+        Ok(())
     }
 
-    Ok(table)
+    fn report(&mut self, sptr: usize, dptr: usize) {
+        if let Some(progress) = self.progress {
+            (progress.callback)(sptr.div_ceil(8), dptr);
+            self.next_report = dptr.saturating_add(progress.interval.max(1));
+        }
+    }
 }
 
 // ----------------------------------------------------------------------------
-fn read_symbol(
-    src: &[u8],
-    sptr: &mut usize,
-    lookup_table: &LookupTable,
-) -> std::result::Result<u16, Error> {
-    let idx = show_bits(sptr, src, TABLE_BITS)? as usize;
+fn read_symbol(br: &mut BitReader, lookup_table: &LookupTable) -> core::result::Result<u16, Error> {
+    // a single refill covers the longest code, both table lookups use the same bits
+    let bits = br.ensure(MAX_CODE_BITS)?;
+    let idx = (bits & ((1 << TABLE_BITS) - 1)) as usize;
     let code_0 = &lookup_table[idx];
 
-    if code_0.len <= TABLE_BITS {
+    let symbol = if code_0.len <= TABLE_BITS {
         // short symbol, fully in first table
-        *sptr += code_0.len as usize;
-        Ok(code_0.code)
+        br.consume(code_0.len);
+        code_0.code
     } else {
         // long symbol, needs second lookup, code_0.code points to start of second table
-        *sptr += TABLE_BITS as usize;
         let count = code_0.len - TABLE_BITS;
-
-        let idx = show_bits(sptr, src, count)? as usize;
+        let idx = ((bits >> TABLE_BITS) & ((1 << count) - 1)) as usize;
         let code_1 = &lookup_table[code_0.code as usize + idx];
 
-        *sptr += code_1.len as usize;
-        Ok(code_1.code)
+        br.consume(TABLE_BITS + code_1.len);
+        code_1.code
+    };
+    br.check_end()?;
+    Ok(symbol)
+}
+
+// ----------------------------------------------------------------------------
+// Decodes a symbol from the buffered bits, the caller guarantees that 15 bits are available.
+fn take_symbol(br: &mut BitReader, lookup_table: &LookupTable) -> u16 {
+    let code_0 = lookup_table[(br.bits & ((1 << TABLE_BITS) - 1)) as usize];
+    if code_0.len <= TABLE_BITS {
+        br.take(code_0.len);
+        code_0.code
+    } else {
+        let count = code_0.len - TABLE_BITS;
+        let idx = ((br.bits >> TABLE_BITS) & ((1 << count) - 1)) as usize;
+        let code_1 = lookup_table[code_0.code as usize + idx];
+        br.take(TABLE_BITS + code_1.len);
+        code_1.code
     }
 }
 
 // ----------------------------------------------------------------------------
-fn generate_fixed_luts() -> std::result::Result<(LookupTable, LookupTable), Error> {
-    const NUM_DEFLATE_CODE_SYMBOLS: usize = 288;
+fn generate_fixed_luts() -> core::result::Result<(LookupTable, LookupTable), Error> {
     let mut len_ll = [8; NUM_DEFLATE_CODE_SYMBOLS];
     len_ll[144..256].fill(9);
     len_ll[256..280].fill(7);
-    let lut_ll = make_lookup_table(&len_ll)?;
+    let lut_ll = make_lookup_table(&len_ll, Tree::LiteralLength, HuffmanPolicy::Pedantic)?;
 
-    const NUM_DISTANCE_SYMBOLS: usize = 32;
     let len_d = [5; NUM_DISTANCE_SYMBOLS];
-    let lut_d = make_lookup_table(&len_d)?;
+    let lut_d = make_lookup_table(&len_d, Tree::Distance, HuffmanPolicy::Pedantic)?;
 
     Ok((lut_ll, lut_d))
 }
 
 // ----------------------------------------------------------------------------
-fn read_encoded_luts(
-    src: &[u8],
-    sptr: &mut usize,
-) -> std::result::Result<(LookupTable, LookupTable), Error> {
-    let ll_len = (read_bits(src, sptr, 5)? + 257) as usize;
-    let dt_len = (read_bits(src, sptr, 5)? + 1) as usize;
-    let cl_len = (read_bits(src, sptr, 4)? + 4) as usize;
+// Huffman tables of a stream, kept across its blocks: the fixed tables are built on the first
+// fixed block and reused for later ones, each dynamic block header is decoded into the same
+// code length and literal/length and distance tables. Kept inline rather than boxed so one-shot
+// decoding never allocates.
+#[derive(Debug, Clone)]
+pub(crate) struct DecoderState {
+    fixed: Option<(LookupTable, LookupTable)>,
+    dynamic: (LookupTable, LookupTable),
+    code_length: LookupTable,
+    // code lengths the dynamic tables were built from, literal/length codes first, so a saved
+    // `stream::Inflater` can rebuild them
+    lengths: [u8; NUM_DEFLATE_CODE_SYMBOLS + NUM_DISTANCE_SYMBOLS],
+    ll_len: usize,
+    dt_len: usize,
+}
+
+// ----------------------------------------------------------------------------
+impl Default for DecoderState {
+    fn default() -> Self {
+        DecoderState {
+            fixed: None,
+            dynamic: (EMPTY_TABLE, EMPTY_TABLE),
+            code_length: EMPTY_TABLE,
+            lengths: [0; NUM_DEFLATE_CODE_SYMBOLS + NUM_DISTANCE_SYMBOLS],
+            ll_len: 0,
+            dt_len: 0,
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+impl DecoderState {
+    fn fixed(&mut self) -> core::result::Result<&(LookupTable, LookupTable), Error> {
+        if self.fixed.is_none() {
+            self.fixed = Some(generate_fixed_luts()?);
+        }
+        Ok(self.fixed.as_ref().unwrap())
+    }
+
+    fn is_fixed_built(&self) -> bool {
+        self.fixed.is_some()
+    }
+
+    // Reads a dynamic block header and rebuilds the dynamic tables from it.
+    fn read_dynamic(
+        &mut self,
+        br: &mut BitReader,
+        policy: HuffmanPolicy,
+        window_size: usize,
+    ) -> core::result::Result<&(LookupTable, LookupTable), Error> {
+        let max_dt_len = symbol_info(window_size).1.len();
+        let lengths = read_code_lengths(br, policy, max_dt_len, &mut self.code_length)?;
+        let (lut_ll, lut_d) = &mut self.dynamic;
+        build_lookup_table(
+            lut_ll,
+            lengths.literal_length(),
+            Tree::LiteralLength,
+            policy,
+        )?;
+        build_lookup_table(lut_d, lengths.distance(), Tree::Distance, policy)?;
+        self.lengths = lengths.bitlen;
+        (self.ll_len, self.dt_len) = (lengths.ll_len, lengths.dt_len);
+        Ok(&self.dynamic)
+    }
+
+    // The literal/length and distance code lengths of the current dynamic tables.
+    pub(crate) fn dynamic_lengths(&self) -> (&[u8], &[u8]) {
+        let (ll, rest) = self.lengths.split_at(self.ll_len);
+        (ll, &rest[..self.dt_len])
+    }
+
+    // Rebuilds the dynamic tables of a deflate stream from lengths of `dynamic_lengths`.
+    pub(crate) fn set_dynamic_lengths(
+        &mut self,
+        ll: &[u8],
+        dt: &[u8],
+    ) -> core::result::Result<(), Error> {
+        let max_dt_len = symbol_info(MAX_WINDOW_SIZE).1.len();
+        if !(257..=286).contains(&ll.len())
+            || !(1..=max_dt_len).contains(&dt.len())
+            || ll.iter().chain(dt).any(|&len| len > 15)
+            || ll[256] == 0
+        {
+            return Err(Error::InvalidCodeLength);
+        }
+        let policy = HuffmanPolicy::default();
+        let (lut_ll, lut_d) = &mut self.dynamic;
+        build_lookup_table(lut_ll, ll, Tree::LiteralLength, policy)?;
+        build_lookup_table(lut_d, dt, Tree::Distance, policy)?;
+        self.lengths[..ll.len()].copy_from_slice(ll);
+        self.lengths[ll.len()..ll.len() + dt.len()].copy_from_slice(dt);
+        (self.ll_len, self.dt_len) = (ll.len(), dt.len());
+        Ok(())
+    }
+}
+
+// ----------------------------------------------------------------------------
+const NUM_CODE_LENGTH_CODES: usize = 19;
+
+// ----------------------------------------------------------------------------
+// transmission order of the code length code lengths
+pub(crate) const CODE_LEN_PERM: [u8; NUM_CODE_LENGTH_CODES] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+// code lengths of a dynamic block header
+struct CodeLengths {
+    cl: [u8; NUM_CODE_LENGTH_CODES],
+    bitlen: [u8; NUM_DEFLATE_CODE_SYMBOLS + NUM_DISTANCE_SYMBOLS],
+    ll_len: usize,
+    dt_len: usize,
+}
+
+// ----------------------------------------------------------------------------
+impl CodeLengths {
+    fn literal_length(&self) -> &[u8] {
+        &self.bitlen[..self.ll_len]
+    }
+
+    fn distance(&self) -> &[u8] {
+        &self.bitlen[self.ll_len..self.ll_len + self.dt_len]
+    }
+}
 
-    if ll_len > 286 || dt_len > 30 {
+// ----------------------------------------------------------------------------
+// `max_dt_len` is the size of the distance alphabet, 30 codes for deflate and 32 for Deflate64,
+// the code length code is built in `vlc_cl`.
+fn read_code_lengths(
+    br: &mut BitReader,
+    policy: HuffmanPolicy,
+    max_dt_len: usize,
+    vlc_cl: &mut LookupTable,
+) -> core::result::Result<CodeLengths, Error> {
+    let ll_len = (br.read_bits(5)? + 257) as usize;
+    let dt_len = (br.read_bits(5)? + 1) as usize;
+    let cl_len = (br.read_bits(4)? + 4) as usize;
+
+    if ll_len > 286 || dt_len > max_dt_len {
         return Err(Error::InvalidCodeLength);
     }
 
-    const NUM_CODE_LENGTH_CODES: usize = 19;
     let mut len_cl = [0; NUM_CODE_LENGTH_CODES];
 
-    const CODE_LEN_PERM: [u8; NUM_CODE_LENGTH_CODES] = [
-        16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
-    ];
     for cl in &CODE_LEN_PERM[..cl_len] {
-        len_cl[*cl as usize] = read_bits(src, sptr, 3)? as u8;
+        len_cl[*cl as usize] = br.read_bits(3)? as u8;
     }
 
-    let vlc_cl = make_lookup_table(&len_cl)?;
+    build_lookup_table(vlc_cl, &len_cl, Tree::CodeLength, policy)?;
 
     let count = ll_len + dt_len;
-    const NUM_DEFLATE_CODE_SYMBOLS: usize = 288;
-    const NUM_DISTANCE_SYMBOLS: usize = 32;
     let mut bitlen = [0; NUM_DEFLATE_CODE_SYMBOLS + NUM_DISTANCE_SYMBOLS];
 
     let mut ptr = 0;
     while ptr < count {
-        let code = read_symbol(src, sptr, &vlc_cl)?;
+        let code = read_symbol(br, vlc_cl)?;
         match code {
             0..=15 => {
                 bitlen[ptr] = code as u8;
@@ -290,7 +649,7 @@ fn read_encoded_luts(
                 if ptr == 0 {
                     return Err(Error::InvalidFirstCopyCode);
                 }
-                let len = 3 + read_bits(src, sptr, 2)? as usize;
+                let len = 3 + br.read_bits(2)? as usize;
                 if ptr + len > count {
                     return Err(Error::InvalidCopyLength);
                 }
@@ -300,9 +659,9 @@ fn read_encoded_luts(
             }
             17 | 18 => {
                 let len = if code == 17 {
-                    3 + read_bits(src, sptr, 3)?
+                    3 + br.read_bits(3)?
                 } else {
-                    11 + read_bits(src, sptr, 7)?
+                    11 + br.read_bits(7)?
                 } as usize;
                 if ptr + len > count {
                     return Err(Error::InvalidCopyLength);
@@ -321,15 +680,289 @@ fn read_encoded_luts(
         return Err(Error::InvalidData);
     }
 
-    let lut_ll = make_lookup_table(&bitlen[0..ll_len])?;
-    let lut_d = make_lookup_table(&bitlen[ll_len..ll_len + dt_len])?;
+    Ok(CodeLengths {
+        cl: len_cl,
+        bitlen,
+        ll_len,
+        dt_len,
+    })
+}
+
+// ----------------------------------------------------------------------------
+// A symbol of a Huffman code set with its code bits in MSB first (transmission) order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HuffmanCode {
+    pub symbol: u16,
+    pub code: u16,
+    pub len: u8,
+}
 
-    Ok((lut_ll, lut_d))
+// ----------------------------------------------------------------------------
+// The codes of a Huffman tree and the occupancy of the lookup table built for it, `Display`
+// renders a symbol listing followed by the table statistics.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HuffmanDump {
+    pub tree: Tree,
+    // symbols with a non-zero code length, in symbol order
+    pub codes: Vec<HuffmanCode>,
+    pub max_len: u8,
+    // first-level table entries resolving to a symbol or pointing to a second-level table
+    pub primary_size: usize,
+    pub primary_used: usize,
+    pub secondary_tables: usize,
+    pub secondary_size: usize,
+    pub secondary_used: usize,
+}
+
+// ----------------------------------------------------------------------------
+impl HuffmanDump {
+    // True if the codes fill the code space, incomplete sets decode some bit patterns to nothing.
+    pub fn is_complete(&self) -> bool {
+        self.primary_used == self.primary_size && self.secondary_used == self.secondary_size
+    }
+}
+
+// ----------------------------------------------------------------------------
+impl core::fmt::Display for HuffmanDump {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        writeln!(
+            f,
+            "{:?} tree: {} codes, max length {}",
+            self.tree,
+            self.codes.len(),
+            self.max_len
+        )?;
+        for code in &self.codes {
+            let len = code.len as usize;
+            writeln!(f, "  {:3} -> {:0len$b} ({len})", code.symbol, code.code)?;
+        }
+        writeln!(
+            f,
+            "primary table: {}/{} entries used",
+            self.primary_used, self.primary_size
+        )?;
+        write!(
+            f,
+            "secondary tables: {} ({}/{} entries used)",
+            self.secondary_tables, self.secondary_used, self.secondary_size
+        )
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Builds the codes and lookup table for `lengths` exactly as the decoder does, so the same
+// over- and under-subscription errors are reported.
+pub fn huffman_dump(
+    lengths: &[u8],
+    tree: Tree,
+    policy: HuffmanPolicy,
+) -> core::result::Result<HuffmanDump, Error> {
+    if lengths.len() > NUM_DEFLATE_CODE_SYMBOLS || lengths.iter().any(|len| *len > 15) {
+        return Err(Error::InvalidCodeLength);
+    }
+    let table = make_lookup_table(lengths, tree, policy)?;
+
+    let mut codes = [0u16; NUM_DEFLATE_CODE_SYMBOLS];
+    let codes = &mut codes[..lengths.len()];
+    generate_codes(codes, lengths, tree, policy)?;
+
+    const TABLE_SIZE: usize = 1 << TABLE_BITS;
+    let primary = &table[..TABLE_SIZE];
+    let mut dump = HuffmanDump {
+        tree,
+        codes: Vec::new(),
+        max_len: lengths.iter().copied().max().unwrap_or(0),
+        primary_size: TABLE_SIZE,
+        primary_used: primary.iter().filter(|e| e.code != INVALID_SYMBOL).count(),
+        secondary_tables: 0,
+        secondary_size: 0,
+        secondary_used: 0,
+    };
+
+    for entry in primary.iter().filter(|e| e.len > TABLE_BITS) {
+        let size = 1 << (entry.len - TABLE_BITS);
+        let start = entry.code as usize;
+        dump.secondary_tables += 1;
+        dump.secondary_size += size;
+        dump.secondary_used += table[start..start + size]
+            .iter()
+            .filter(|e| e.code != INVALID_SYMBOL)
+            .count();
+    }
+
+    for (symbol, (len, code)) in lengths.iter().zip(codes.iter()).enumerate() {
+        if *len != 0 {
+            dump.codes.push(HuffmanCode {
+                symbol: symbol as u16,
+                code: reverse_bits(*code, *len as usize),
+                len: *len,
+            });
+        }
+    }
+
+    Ok(dump)
+}
+
+// ----------------------------------------------------------------------------
+// Dumps the Huffman trees of the first block of the raw deflate stream `src`: none for a stored
+// block, literal/length and distance for a fixed block, code length, literal/length and distance
+// for a dynamic block.
+pub fn block_huffman_dumps(
+    src: &[u8],
+    policy: HuffmanPolicy,
+) -> core::result::Result<Vec<HuffmanDump>, Error> {
+    let mut br = BitReader::new(src, false);
+    let _b_final = br.read_bits(1)?;
+    match br.read_bits(2)? {
+        0 => Ok(Vec::new()),
+        1 => {
+            let mut len_ll = [8; NUM_DEFLATE_CODE_SYMBOLS];
+            len_ll[144..256].fill(9);
+            len_ll[256..280].fill(7);
+            let len_d = [5; NUM_DISTANCE_SYMBOLS];
+            Ok(Vec::from([
+                huffman_dump(&len_ll, Tree::LiteralLength, HuffmanPolicy::Pedantic)?,
+                huffman_dump(&len_d, Tree::Distance, HuffmanPolicy::Pedantic)?,
+            ]))
+        }
+        2 => {
+            let mut vlc_cl = EMPTY_TABLE;
+            let lengths = read_code_lengths(&mut br, policy, DIST_INFO.len(), &mut vlc_cl)?;
+            Ok(Vec::from([
+                huffman_dump(&lengths.cl, Tree::CodeLength, policy)?,
+                huffman_dump(lengths.literal_length(), Tree::LiteralLength, policy)?,
+                huffman_dump(lengths.distance(), Tree::Distance, policy)?,
+            ]))
+        }
+        _ => Err(Error::InvalidBlockType),
+    }
+}
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlockType {
+    Stored,
+    Fixed,
+    Dynamic,
+}
+
+// ----------------------------------------------------------------------------
+// Position and size of a deflate block. The bit offsets count from the start of the stream,
+// the data of a stored block are the `size` bytes before `end_bit / 8`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlockInfo {
+    pub block_type: BlockType,
+    pub last: bool,
+    // bit offset of the block header and of the first bit after the block
+    pub start_bit: usize,
+    pub end_bit: usize,
+    // output offset of the first decoded byte and the number of decoded bytes
+    pub out_pos: u64,
+    pub size: usize,
+}
+
+// ----------------------------------------------------------------------------
+// Iterator over the blocks of a raw deflate stream, see `deflate_block_iter`.
+pub struct DeflateBlocks<'a> {
+    src: &'a [u8],
+    bit_pos: usize,
+    out_pos: u64,
+    // the preceding 32 KiB of output for back-references followed by the current block
+    window: Vec<u8>,
+    block: core::ops::Range<usize>,
+    state: DecoderState,
+    done: bool,
+}
+
+// ----------------------------------------------------------------------------
+// Walks the raw deflate stream `src` block by block without writing output anywhere, each block
+// is decoded into an internal window to find its end and size. Iteration ends after the final
+// block or the first error, input ending within a block fails with `Error::Underflow`.
+pub fn deflate_block_iter(src: &[u8]) -> DeflateBlocks<'_> {
+    DeflateBlocks {
+        src,
+        bit_pos: 0,
+        out_pos: 0,
+        window: Vec::new(),
+        block: 0..0,
+        state: DecoderState::default(),
+        done: false,
+    }
+}
+
+// ----------------------------------------------------------------------------
+impl DeflateBlocks<'_> {
+    // Output of the block returned last, for tools that keep the data while walking the blocks.
+    pub fn data(&self) -> &[u8] {
+        &self.window[self.block.clone()]
+    }
+
+    fn next_block(&mut self) -> core::result::Result<BlockInfo, Error> {
+        // the block header, BFINAL and BTYPE, may span two bytes
+        let byte = self.bit_pos / 8;
+        let lo = *self.src.get(byte).ok_or(Error::Underflow)? as u16;
+        let hi = self.src.get(byte + 1).copied().unwrap_or(0) as u16;
+        let block_type = match ((lo | hi << 8) >> (self.bit_pos % 8) >> 1) & 3 {
+            0 => BlockType::Stored,
+            1 => BlockType::Fixed,
+            2 => BlockType::Dynamic,
+            _ => return Err(Error::InvalidBlockType),
+        };
+
+        // keep the last window of output for back-references
+        let end = self.block.end;
+        let keep = end.min(MAX_WINDOW_SIZE);
+        self.window.copy_within(end - keep..end, 0);
+        if self.window.len() < 2 * MAX_WINDOW_SIZE {
+            self.window.resize(2 * MAX_WINDOW_SIZE, 0);
+        }
+        self.block = keep..keep;
+
+        let metrics = &mut InflateMetrics::default();
+        let (src, bit_pos, state) = (self.src, self.bit_pos, &mut self.state);
+        let (last, end_bit, end) = loop {
+            let window = &mut self.window;
+            match inflate_block(window, keep, src, bit_pos, state, MAX_WINDOW_SIZE, metrics) {
+                Err(Error::Overflow) => {
+                    let len = window.len().checked_mul(2).ok_or(Error::Overflow)?;
+                    window.resize(len, 0);
+                }
+                result => break result?,
+            }
+        };
+
+        let info = BlockInfo {
+            block_type,
+            last,
+            start_bit: self.bit_pos,
+            end_bit,
+            out_pos: self.out_pos,
+            size: end - keep,
+        };
+        self.block = keep..end;
+        self.bit_pos = end_bit;
+        self.out_pos += info.size as u64;
+        Ok(info)
+    }
+}
+
+// ----------------------------------------------------------------------------
+impl Iterator for DeflateBlocks<'_> {
+    type Item = core::result::Result<BlockInfo, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self.next_block();
+        self.done = !matches!(result, Ok(BlockInfo { last: false, .. }));
+        Some(result)
+    }
 }
 
 // ----------------------------------------------------------------------------
 #[rustfmt::skip]
-const DIST_INFO: [(u8, u16); 30] = [
+pub(crate) const DIST_INFO: [(u8, u16); 30] = [
     ( 0,    1), ( 0,    2), ( 0,    3), ( 0,    4), ( 1,    5), ( 1,    7), ( 2,    9), ( 2,   13),
     ( 3,   17), ( 3,   25), ( 4,   33), ( 4,   49), ( 5,   65), ( 5,   97), ( 6,  129), ( 6,  193),
     ( 7,  257), ( 7,  385), ( 8,  513), ( 8,  769), ( 9, 1025), ( 9, 1537), (10, 2049), (10, 3073),
@@ -338,7 +971,7 @@ const DIST_INFO: [(u8, u16); 30] = [
 
 // ----------------------------------------------------------------------------
 #[rustfmt::skip]
-const CODE_INFO: [(u8, u16); 29] = [
+pub(crate) const CODE_INFO: [(u8, u16); 29] = [
     ( 0,    3), ( 0,    4), ( 0,    5), ( 0,    6), ( 0,    7), ( 0,    8), ( 0,   9), ( 0,   10),
     ( 1,   11), ( 1,   13), ( 1,   15), ( 1,   17), ( 2,   19), ( 2,   23), ( 2,  27), ( 2,   31),
     ( 3,   35), ( 3,   43), ( 3,   51), ( 3,   59), ( 4,   67), ( 4,   83), ( 4,  99), ( 4,  115),
@@ -346,63 +979,157 @@ const CODE_INFO: [(u8, u16); 29] = [
 ];
 
 // ----------------------------------------------------------------------------
-fn inflate_huffman_block(
+// Deflate64 (PKWARE "enhanced deflate"): distance codes 30 and 31 reach up to 65536 bytes back,
+// length code 285 takes 16 extra bits for lengths of 3 to 65538 bytes.
+const DEFLATE64_DIST_INFO: [(u8, u16); 32] = {
+    let mut info = [(14, 32769); 32];
+    let mut i = 0;
+    while i < DIST_INFO.len() {
+        info[i] = DIST_INFO[i];
+        i += 1;
+    }
+    info[31] = (14, 49153);
+    info
+};
+
+const DEFLATE64_CODE_INFO: [(u8, u16); 29] = {
+    let mut info = CODE_INFO;
+    info[28] = (16, 3);
+    info
+};
+
+// ----------------------------------------------------------------------------
+// (extra bits, base value) of the length or distance symbols
+type SymbolInfo = &'static [(u8, u16)];
+
+// (length, distance) symbol tables of a stream decoded with `window_size`.
+fn symbol_info(window_size: usize) -> (SymbolInfo, SymbolInfo) {
+    if window_size == DEFLATE64_WINDOW_SIZE {
+        (&DEFLATE64_CODE_INFO, &DEFLATE64_DIST_INFO)
+    } else {
+        (&CODE_INFO, &DIST_INFO)
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Copies `length` bytes from `distance` bytes back to `dst[*dptr..]`. With `zeros_too_far`,
+// distances beyond the start of the output within the window read zeros.
+fn copy_match(
     dst: &mut [u8],
     dptr: &mut usize,
-    src: &[u8],
-    sptr: &mut usize,
-    trees: (LookupTable, LookupTable),
-) -> std::result::Result<(), Error> {
-    loop {
-        let code_ll = read_symbol(src, sptr, &trees.0)?;
+    distance: usize,
+    length: usize,
+    window_size: usize,
+    zeros_too_far: bool,
+) -> core::result::Result<(), Error> {
+    let start = *dptr;
+    if zeros_too_far && distance > start && distance <= window_size {
+        let zeros = (distance - start).min(length);
+        dst.get_mut(start..start + zeros)
+            .ok_or(Error::Overflow)?
+            .fill(0);
+        *dptr += zeros;
+        if zeros == length {
+            return Ok(());
+        }
+        return copy_match(dst, dptr, distance, length - zeros, window_size, false);
+    }
+    if distance > start || distance > window_size {
+        return Err(Error::InvalidDistance {
+            distance,
+            available: start.min(window_size),
+        });
+    }
+
+    if length > dst.len() - start {
+        return Err(Error::Overflow);
+    }
+
+    if distance == 1 {
+        let value = dst[start - 1];
+        dst[start..start + length].fill(value);
+        *dptr += length;
+        return Ok(());
+    }
+
+    let loops = length / distance;
+    let remain = length % distance;
+
+    for _ in 0..loops {
+        dst.copy_within(start - distance..start, *dptr);
+        *dptr += distance;
+    }
+
+    dst.copy_within(start - distance..start - distance + remain, *dptr);
+    *dptr += remain;
+    Ok(())
+}
+
+// ----------------------------------------------------------------------------
+// Output headroom of the fast loop: the longest match (258 bytes) rounded up to 8-byte chunks.
+const FAST_OUTPUT_MARGIN: usize = 264;
+
+// ----------------------------------------------------------------------------
+// Decodes symbols while the input holds three full bit buffer refills and the output has room for
+// the longest match. Every refill makes at least 24 bits available, enough for a symbol and its
+// extra bits, so no symbol runs into the end of input and literals need no bounds checks. Matches
+// at least 8 bytes back are copied in 8-byte chunks that may write up to 7 bytes past the match
+// into the headroom. Returns true at the end of the block, false once the slow loop has to take
+// over.
+fn inflate_huffman_fast(
+    dst: &mut [u8],
+    dptr: &mut usize,
+    br: &mut BitReader,
+    trees: &(LookupTable, LookupTable),
+    window_size: usize,
+    monitor: &mut Monitor,
+) -> core::result::Result<bool, Error> {
+    let (code_info, dist_info) = symbol_info(window_size);
+    while br.remaining_bytes() >= 3 * BITBUF_BYTES && dst.len() - *dptr >= FAST_OUTPUT_MARGIN {
+        monitor.update(br.bit_pos(), *dptr)?;
+        metric!(monitor.metrics.fast_path_iterations += 1);
+        br.refill();
+        let code_ll = take_symbol(br, &trees.0);
         match code_ll {
             0..=255 => {
+                metric!(monitor.metrics.literals += 1);
                 dst[*dptr] = code_ll as u8;
                 *dptr += 1;
             }
             256 => {
-                return Ok(());
+                return Ok(true);
             }
             257..=285 => {
-                let idx = (code_ll - 257) as usize;
-                let info_ll = CODE_INFO.get(idx).ok_or(Error::InvalidLength)?;
-
-                let start = *dptr;
-                let length = info_ll.1 as usize + read_bits(src, sptr, info_ll.0.into())? as usize;
-
-                let code_d = read_symbol(src, sptr, &trees.1)?;
-                if code_d == 0 {
-                    // distance is 1
-                    let value = *dst.get(start - 1).ok_or(Error::InvalidDistance)?;
-                    dst.get_mut(start..start + length)
-                        .ok_or(Error::InvalidLength)?
-                        .fill(value);
+                metric!(monitor.metrics.matches += 1);
+                let info_ll = code_info[(code_ll - 257) as usize];
+                if info_ll.0 > 8 {
+                    // the 16 extra bits of the Deflate64 length code 285
+                    br.refill();
+                }
+                let length = info_ll.1 as usize + br.take(info_ll.0) as usize;
+
+                br.refill();
+                let code_d = take_symbol(br, &trees.1);
+                // distance codes 30 and 31 only occur in Deflate64 streams
+                let info_d = dist_info.get(code_d as usize).ok_or(Error::InvalidSymbol)?;
+
+                br.refill();
+                let distance = info_d.1 as usize + br.take(info_d.0) as usize;
+                if distance >= 8 && distance <= (*dptr).min(window_size) && length <= 258 {
+                    let start = *dptr;
+                    for i in (start..start + length).step_by(8) {
+                        dst.copy_within(i - distance..i - distance + 8, i);
+                    }
                     *dptr += length;
                 } else {
-                    let idx = code_d as usize;
-                    let info_d = DIST_INFO.get(idx).ok_or(Error::InvalidDistance)?;
-
-                    let distance =
-                        info_d.1 as usize + read_bits(src, sptr, info_d.0.into())? as usize;
-
-                    if distance > start {
-                        return Err(Error::InvalidDistance);
-                    }
-
-                    if length > dst.len() - start {
-                        return Err(Error::InvalidLength);
-                    }
-
-                    let loops = length / distance;
-                    let remain = length % distance;
-
-                    for _ in 0..loops {
-                        dst.copy_within(start - distance..start, *dptr);
-                        *dptr += distance;
-                    }
-
-                    dst.copy_within(start - distance..start - distance + remain, *dptr);
-                    *dptr += remain;
+                    copy_match(
+                        dst,
+                        dptr,
+                        distance,
+                        length,
+                        window_size,
+                        monitor.zeros_too_far,
+                    )?;
                 }
             }
             _ => {
@@ -410,73 +1137,1123 @@ fn inflate_huffman_block(
             }
         }
     }
+    Ok(false)
 }
 
 // ----------------------------------------------------------------------------
-fn inflate_no_compression(
+fn inflate_huffman_block(
     dst: &mut [u8],
     dptr: &mut usize,
-    src: &[u8],
-    sptr: &mut usize,
-) -> std::result::Result<(), Error> {
-    // align on byte boundary
-    *sptr = (*sptr + 7) & (!7);
-
-    let bytepos = *sptr >> 3;
+    br: &mut BitReader,
+    trees: &(LookupTable, LookupTable),
+    window_size: usize,
+    monitor: &mut Monitor,
+) -> core::result::Result<(), Error> {
+    if inflate_huffman_fast(dst, dptr, br, trees, window_size, monitor)? {
+        return Ok(());
+    }
+    while !inflate_symbol(dst, dptr, br, trees, window_size, monitor)? {}
+    Ok(())
+}
 
-    if bytepos + 4 > src.len() {
-        return Err(Error::Underflow);
+// ----------------------------------------------------------------------------
+// Decodes a single literal or match with bounds checks, returns true at the end of the block.
+// Output is written only once the symbol and its extra bits have been read completely.
+fn inflate_symbol(
+    dst: &mut [u8],
+    dptr: &mut usize,
+    br: &mut BitReader,
+    trees: &(LookupTable, LookupTable),
+    window_size: usize,
+    monitor: &mut Monitor,
+) -> core::result::Result<bool, Error> {
+    let (code_info, dist_info) = symbol_info(window_size);
+    monitor.update(br.bit_pos(), *dptr)?;
+    metric!(monitor.metrics.slow_path_iterations += 1);
+    let code_ll = read_symbol(br, &trees.0)?;
+    match code_ll {
+        0..=255 => {
+            metric!(monitor.metrics.literals += 1);
+            *dst.get_mut(*dptr).ok_or(Error::Overflow)? = code_ll as u8;
+            *dptr += 1;
+            Ok(false)
+        }
+        256 => Ok(true),
+        257..=285 => {
+            metric!(monitor.metrics.matches += 1);
+            let idx = (code_ll - 257) as usize;
+            let info_ll = code_info.get(idx).ok_or(Error::InvalidLength)?;
+            let length = info_ll.1 as usize + br.read_bits(info_ll.0)? as usize;
+
+            let code_d = read_symbol(br, &trees.1)?;
+            // distance codes 30 and 31 only occur in Deflate64 streams
+            let info_d = dist_info.get(code_d as usize).ok_or(Error::InvalidSymbol)?;
+            let distance = info_d.1 as usize + br.read_bits(info_d.0)? as usize;
+            copy_match(
+                dst,
+                dptr,
+                distance,
+                length,
+                window_size,
+                monitor.zeros_too_far,
+            )?;
+            Ok(false)
+        }
+        _ => Err(Error::InvalidSymbol),
     }
+}
+
+// ----------------------------------------------------------------------------
+fn inflate_no_compression(
+    dst: &mut [u8],
+    dptr: &mut usize,
+    br: &mut BitReader,
+) -> core::result::Result<(), Error> {
+    // align on byte boundary
+    br.align_to_byte();
+    let mut header = [0; 4];
+    br.read_bytes(&mut header)?;
 
-    let len = src[bytepos] as usize + ((src[bytepos + 1] as usize) << 8);
-    let nlen = src[bytepos + 2] as usize + ((src[bytepos + 3] as usize) << 8);
+    let len = u16::from_le_bytes([header[0], header[1]]) as usize;
+    let nlen = u16::from_le_bytes([header[2], header[3]]) as usize;
 
     if len + nlen != 65535 {
         // error: NLEN is not one's complement of LEN
         return Err(Error::InvalidBlockLength);
     }
 
-    if bytepos + 4 + len > src.len() {
+    if br.bytes_left().is_some_and(|left| left < len) {
         // error, bit pointer will jump past memory
         return Err(Error::Underflow);
     }
 
     // read the literal data: len bytes are now stored in the out buffer
-    dst[*dptr..*dptr + len].copy_from_slice(&src[bytepos + 4..bytepos + 4 + len]);
+    let out = dst.get_mut(*dptr..*dptr + len).ok_or(Error::Overflow)?;
+    br.read_bytes(out)?;
     *dptr += len;
-    *sptr += (4 + len) * 8;
 
     Ok(())
 }
 
 // ----------------------------------------------------------------------------
-pub fn inflate(dst: &mut [u8], src: &[u8]) -> std::result::Result<usize, Error> {
-    let mut sptr = 0;
-    let mut dptr = 0;
-    loop {
-        let b_final = read_bits(src, &mut sptr, 1)?;
-        let b_type = read_bits(src, &mut sptr, 2)?;
+// RFC 1950 stream header: checks CMF/FLG and returns the declared window size and the DICTID
+// of a preset dictionary.
+fn zlib_dict_header(src: &[u8]) -> core::result::Result<(usize, Option<u32>), Error> {
+    if src.len() < 2 {
+        return Err(Error::Underflow);
+    }
 
-        match b_type {
-            0 => {
-                inflate_no_compression(dst, &mut dptr, src, &mut sptr)?;
-            }
-            1 => {
-                let trees = generate_fixed_luts()?;
-                inflate_huffman_block(dst, &mut dptr, src, &mut sptr, trees)?;
-            }
-            2 => {
-                let trees = read_encoded_luts(src, &mut sptr)?;
-                inflate_huffman_block(dst, &mut dptr, src, &mut sptr, trees)?;
-            }
-            _ => {
-                return Err(Error::InvalidBlockType);
-            }
-        }
+    // fcheck is set correctly, compression method is inflate, sliding window is at most 32k
+    let check = ((src[0] as usize) * 256 + (src[1] as usize)) % 31;
+    let cm = src[0] & 15;
+    let cinfo = (src[0] >> 4) & 15;
+    let fdict = (src[1] >> 5) & 1;
 
-        if b_final != 0 {
-            break;
-        }
+    if check != 0 || cm != 8 || cinfo > 7 {
+        return Err(Error::InvalidHeader);
     }
-    Ok(dptr)
+
+    let dict_id = if fdict != 0 {
+        let id = src.get(2..6).ok_or(Error::Underflow)?;
+        Some(u32::from_be_bytes([id[0], id[1], id[2], id[3]]))
+    } else {
+        None
+    };
+    Ok((1 << (cinfo + 8), dict_id))
+}
+
+// ----------------------------------------------------------------------------
+// RFC 1950 stream header without a preset dictionary, returns the declared window size.
+pub(crate) fn zlib_header(src: &[u8]) -> core::result::Result<usize, Error> {
+    match zlib_dict_header(src) {
+        Ok((window_size, None)) => Ok(window_size),
+        Ok((_, Some(_))) => Err(Error::InvalidHeader),
+        Err(err) => Err(err),
+    }
+}
+
+// ----------------------------------------------------------------------------
+// RFC 1950 stream: header, deflate data and Adler-32 trailer in the last 4 bytes of `src`.
+// Streams with a preset dictionary fail with `Error::DictionaryRequired` unless
+// `InflateOptions::dictionary` supplies it.
+pub fn inflate_zlib(dst: &mut [u8], src: &[u8]) -> core::result::Result<usize, Error> {
+    inflate_zlib_with_options(dst, src, &InflateOptions::default())
+}
+
+// ----------------------------------------------------------------------------
+pub fn inflate_zlib_with_options(
+    dst: &mut [u8],
+    src: &[u8],
+    options: &InflateOptions,
+) -> core::result::Result<usize, Error> {
+    zlib_stream(dst, src, options, true).map(|(len, _)| len)
+}
+
+// ----------------------------------------------------------------------------
+// RFC 1950 stream at the start of `src` with the Adler-32 trailer right after the deflate data.
+// Returns (output length, bytes of `src` the stream occupies), data behind the stream is ignored.
+pub fn inflate_zlib_consumed(
+    dst: &mut [u8],
+    src: &[u8],
+    options: &InflateOptions,
+) -> core::result::Result<(usize, usize), Error> {
+    zlib_stream(dst, src, options, false)
+}
+
+// ----------------------------------------------------------------------------
+fn zlib_stream(
+    dst: &mut [u8],
+    src: &[u8],
+    options: &InflateOptions,
+    trailer_at_end: bool,
+) -> core::result::Result<(usize, usize), Error> {
+    let (window_size, dict_id) = zlib_dict_header(src)?;
+    let header_len = if dict_id.is_some() { 6 } else { 2 };
+    if src.len() < header_len + 4 {
+        return Err(Error::Underflow);
+    }
+
+    let window_size = if options.enforce_zlib_window {
+        window_size
+    } else {
+        MAX_WINDOW_SIZE
+    };
+
+    let data = if trailer_at_end {
+        &src[header_len..src.len() - 4]
+    } else {
+        &src[header_len..]
+    };
+    let (len, consumed) = match dict_id {
+        None => inflate_stream(dst, 0, data, window_size, options)?,
+        Some(id) => {
+            let dict = match options.dictionary {
+                Some(dict) if crate::checksum::adler32(1, dict) == id => dict,
+                _ => return Err(Error::DictionaryRequired(id)),
+            };
+
+            // the dictionary precedes the output as if it had been decoded before
+            let dict = &dict[dict.len().saturating_sub(MAX_WINDOW_SIZE)..];
+            let mut buf = Vec::with_capacity(dict.len() + dst.len());
+            buf.extend_from_slice(dict);
+            buf.resize(dict.len() + dst.len(), 0);
+            let (end, consumed) = inflate_stream(&mut buf, dict.len(), data, window_size, options)?;
+            let len = end - dict.len();
+            dst[..len].copy_from_slice(&buf[dict.len()..end]);
+            (len, consumed)
+        }
+    };
+
+    let trailer_pos = if trailer_at_end {
+        src.len() - 4
+    } else {
+        header_len + consumed
+    };
+    let trailer = src
+        .get(trailer_pos..trailer_pos + 4)
+        .ok_or(Error::Underflow)?;
+    let adler = u32::from_be_bytes(trailer.try_into().map_err(|_| Error::Underflow)?);
+    if options.verify_checksums && adler != crate::checksum::adler32(1, &dst[..len]) {
+        return Err(Error::ChecksumMismatch);
+    }
+
+    Ok((len, trailer_pos + 4))
+}
+
+// ----------------------------------------------------------------------------
+// RFC 1950 stream split into consecutive slices, e.g. the data of the IDAT chunks of a PNG image,
+// decoded without joining the slices first. Streams with a preset dictionary fail with
+// `Error::InvalidHeader`.
+pub fn inflate_zlib_segments(
+    dst: &mut [u8],
+    segments: &mut dyn Iterator<Item = &[u8]>,
+    options: &InflateOptions,
+) -> core::result::Result<usize, Error> {
+    trace_span!("inflate", dst_len = dst.len());
+    let mut br = BitReader::segmented(segments, options.strict_end_of_input);
+    let mut header = [0; 2];
+    br.read_bytes(&mut header)?;
+    if header[1] & 0x20 != 0 {
+        return Err(Error::InvalidHeader);
+    }
+    let (window_size, _) = zlib_dict_header(&header)?;
+    let window_size = if options.enforce_zlib_window {
+        window_size
+    } else {
+        MAX_WINDOW_SIZE
+    };
+
+    let (len, _) = inflate_reader(dst, 0, &mut br, window_size, options)?;
+
+    br.align_to_byte();
+    let mut trailer = [0; 4];
+    br.read_bytes(&mut trailer)?;
+    let adler = u32::from_be_bytes(trailer);
+    if options.verify_checksums && adler != crate::checksum::adler32(1, &dst[..len]) {
+        return Err(Error::ChecksumMismatch);
+    }
+
+    Ok(len)
+}
+
+// ----------------------------------------------------------------------------
+// RFC 1952 member header: checks ID1/ID2/CM, skips the optional fields and returns the offset
+// of the deflate data.
+pub(crate) fn gzip_header(src: &[u8]) -> core::result::Result<usize, Error> {
+    const FHCRC: u8 = 0x02;
+    const FEXTRA: u8 = 0x04;
+    const FNAME: u8 = 0x08;
+    const FCOMMENT: u8 = 0x10;
+
+    let header = src.get(..10).ok_or(Error::Underflow)?;
+    let flags = header[3];
+    if header[..3] != [0x1f, 0x8b, 8] || flags & 0xe0 != 0 {
+        return Err(Error::InvalidHeader);
+    }
+
+    let mut pos = 10;
+    if flags & FEXTRA != 0 {
+        let xlen = src.get(pos..pos + 2).ok_or(Error::Underflow)?;
+        pos += 2 + u16::from_le_bytes([xlen[0], xlen[1]]) as usize;
+    }
+
+    // file name and comment are zero-terminated
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            let field = src.get(pos..).ok_or(Error::Underflow)?;
+            pos += field.iter().position(|&b| b == 0).ok_or(Error::Underflow)? + 1;
+        }
+    }
+
+    if flags & FHCRC != 0 {
+        // low half of the CRC-32 of all header bytes before it
+        let crc = src.get(pos..pos + 2).ok_or(Error::Underflow)?;
+        if u16::from_le_bytes([crc[0], crc[1]]) != crate::checksum::crc32(0, &src[..pos]) as u16 {
+            return Err(Error::ChecksumMismatch);
+        }
+        pos += 2;
+    }
+
+    if pos > src.len() {
+        return Err(Error::Underflow);
+    }
+
+    Ok(pos)
+}
+
+// ----------------------------------------------------------------------------
+// RFC 1952 member: header, deflate data and CRC-32/ISIZE trailer in the last 8 bytes of `src`.
+pub fn inflate_gzip(dst: &mut [u8], src: &[u8]) -> core::result::Result<usize, Error> {
+    inflate_gzip_with_options(dst, src, &InflateOptions::default())
+}
+
+// ----------------------------------------------------------------------------
+pub fn inflate_gzip_with_options(
+    dst: &mut [u8],
+    src: &[u8],
+    options: &InflateOptions,
+) -> core::result::Result<usize, Error> {
+    gzip_member(dst, src, options, true).map(|(len, _)| len)
+}
+
+// ----------------------------------------------------------------------------
+// RFC 1952 member at the start of `src`. Returns (output length, bytes of `src` the member
+// occupies), data behind the member is ignored.
+pub fn inflate_gzip_consumed(
+    dst: &mut [u8],
+    src: &[u8],
+    options: &InflateOptions,
+) -> core::result::Result<(usize, usize), Error> {
+    gzip_member(dst, src, options, false)
+}
+
+// ----------------------------------------------------------------------------
+fn gzip_member(
+    dst: &mut [u8],
+    src: &[u8],
+    options: &InflateOptions,
+    trailer_at_end: bool,
+) -> core::result::Result<(usize, usize), Error> {
+    let header_len = gzip_header(src)?;
+    if src.len() < header_len + 8 {
+        return Err(Error::Underflow);
+    }
+
+    let data = if trailer_at_end {
+        &src[header_len..src.len() - 8]
+    } else {
+        &src[header_len..]
+    };
+    let (len, consumed) = inflate_stream(dst, 0, data, MAX_WINDOW_SIZE, options)?;
+
+    let trailer_pos = if trailer_at_end {
+        src.len() - 8
+    } else {
+        header_len + consumed
+    };
+    let trailer = src
+        .get(trailer_pos..trailer_pos + 8)
+        .ok_or(Error::Underflow)?;
+    let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let isize = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+    let crc_ok = !options.verify_checksums || crc == crate::checksum::crc32(0, &dst[..len]);
+    if !crc_ok || isize != len as u32 {
+        return Err(Error::ChecksumMismatch);
+    }
+
+    Ok((len, trailer_pos + 8))
+}
+
+// ----------------------------------------------------------------------------
+pub fn inflate(dst: &mut [u8], src: &[u8]) -> core::result::Result<usize, Error> {
+    inflate_with_options(dst, src, &InflateOptions::default())
+}
+
+// ----------------------------------------------------------------------------
+// Decodes a raw deflate stream of at most `N` bytes into an array, without heap allocations,
+// e.g. small assets on targets without an allocator. Returns the array and the decoded length.
+pub fn inflate_fixed<const N: usize>(src: &[u8]) -> core::result::Result<([u8; N], usize), Error> {
+    let mut dst = [0u8; N];
+    let len = inflate(&mut dst, src)?;
+    Ok((dst, len))
+}
+
+// ----------------------------------------------------------------------------
+// Size of the output of the raw deflate stream `src`, for callers without a size field that
+// want to allocate once before `inflate`. Nothing is written: literals and match lengths are
+// counted and distances checked against the count, invalid streams fail as with `inflate`.
+pub fn inflate_scan_size(src: &[u8]) -> core::result::Result<usize, Error> {
+    let options = InflateOptions::default();
+    let mut br = BitReader::new(src, options.strict_end_of_input);
+    let mut state = DecoderState::default();
+    let mut size = 0;
+    loop {
+        let b_final = br.read_bits(1)?;
+        match br.read_bits(2)? {
+            0 => scan_no_compression(&mut br, &mut size)?,
+            1 => scan_huffman_block(&mut br, state.fixed()?, &mut size)?,
+            2 => {
+                let trees = state.read_dynamic(&mut br, options.huffman_policy, MAX_WINDOW_SIZE)?;
+                scan_huffman_block(&mut br, trees, &mut size)?;
+            }
+            _ => return Err(Error::InvalidBlockType),
+        }
+        if b_final != 0 {
+            return Ok(size);
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Like `inflate_no_compression`, the data bytes are skipped and only counted.
+fn scan_no_compression(br: &mut BitReader, size: &mut usize) -> core::result::Result<(), Error> {
+    br.align_to_byte();
+    let mut header = [0; 4];
+    br.read_bytes(&mut header)?;
+
+    let len = u16::from_le_bytes([header[0], header[1]]) as usize;
+    let nlen = u16::from_le_bytes([header[2], header[3]]) as usize;
+    if len + nlen != 65535 {
+        return Err(Error::InvalidBlockLength);
+    }
+    if br.bytes_left().is_some_and(|left| left < len) {
+        return Err(Error::Underflow);
+    }
+
+    br.set_byte_pos(br.bit_pos() / 8 + len);
+    *size = size.checked_add(len).ok_or(Error::Overflow)?;
+    Ok(())
+}
+
+// ----------------------------------------------------------------------------
+// Like `inflate_huffman_block`, adding the decoded length to `size` instead of writing output.
+fn scan_huffman_block(
+    br: &mut BitReader,
+    trees: &(LookupTable, LookupTable),
+    size: &mut usize,
+) -> core::result::Result<(), Error> {
+    loop {
+        let code_ll = read_symbol(br, &trees.0)?;
+        let length = match code_ll {
+            0..=255 => 1,
+            256 => return Ok(()),
+            257..=285 => {
+                let idx = (code_ll - 257) as usize;
+                let info_ll = CODE_INFO.get(idx).ok_or(Error::InvalidLength)?;
+                let length = info_ll.1 as usize + br.read_bits(info_ll.0)? as usize;
+
+                let code_d = read_symbol(br, &trees.1)?;
+                let info_d = DIST_INFO.get(code_d as usize).ok_or(Error::InvalidSymbol)?;
+                let distance = info_d.1 as usize + br.read_bits(info_d.0)? as usize;
+                if distance > *size {
+                    return Err(Error::InvalidDistance {
+                        distance,
+                        available: (*size).min(MAX_WINDOW_SIZE),
+                    });
+                }
+                length
+            }
+            _ => return Err(Error::InvalidSymbol),
+        };
+        *size = size.checked_add(length).ok_or(Error::Overflow)?;
+    }
+}
+
+// ----------------------------------------------------------------------------
+pub fn inflate_with_options(
+    dst: &mut [u8],
+    src: &[u8],
+    options: &InflateOptions,
+) -> core::result::Result<usize, Error> {
+    inflate_window(dst, src, MAX_WINDOW_SIZE, options)
+}
+
+// ----------------------------------------------------------------------------
+// Decodes a raw Deflate64 stream (ZIP compression method 9), deflate with a 64 KB window and
+// longer matches.
+pub fn inflate64(dst: &mut [u8], src: &[u8]) -> core::result::Result<usize, Error> {
+    inflate64_with_options(dst, src, &InflateOptions::default())
+}
+
+// ----------------------------------------------------------------------------
+pub fn inflate64_with_options(
+    dst: &mut [u8],
+    src: &[u8],
+    options: &InflateOptions,
+) -> core::result::Result<usize, Error> {
+    inflate_window(dst, src, DEFLATE64_WINDOW_SIZE, options)
+}
+
+// ----------------------------------------------------------------------------
+// Output bounds of `inflate_to_vec`.
+#[derive(Debug, Clone, Copy)]
+pub struct VecLimits {
+    // expected output size, e.g. recorded by a container format, avoids regrowing the output
+    pub size_hint: Option<usize>,
+    // output beyond this many bytes fails with `Error::LimitExceeded`
+    pub max_output: usize,
+}
+
+// ----------------------------------------------------------------------------
+impl Default for VecLimits {
+    fn default() -> Self {
+        VecLimits {
+            size_hint: None,
+            max_output: usize::MAX,
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Decodes a raw deflate stream into a buffer that grows until the whole output fits.
+pub fn inflate_to_vec(src: &[u8], limits: &VecLimits) -> core::result::Result<Vec<u8>, Error> {
+    let len = limits
+        .size_hint
+        .unwrap_or_else(|| src.len().saturating_mul(4).max(1024));
+    let mut out = Vec::new();
+    decode_growing(&mut out, src, Wrapper::Raw, len, limits.max_output)?;
+    Ok(out)
+}
+
+// ----------------------------------------------------------------------------
+// Container around the deflate data decoded by `decode_growing`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Wrapper {
+    Raw,
+    // RFC 1950, Adler-32 trailer in the last 4 bytes
+    Zlib,
+    // RFC 1952, CRC-32 and ISIZE trailer in the last 8 bytes
+    Gzip,
+}
+
+// ----------------------------------------------------------------------------
+// Decodes into `out` resized to `len` bytes, output beyond `limit` bytes fails with
+// `Error::LimitExceeded`. Blocks are decoded one after the other, a block that doesn't fit
+// doubles the buffer and only that block is decoded again.
+pub(crate) fn decode_growing(
+    out: &mut Vec<u8>,
+    src: &[u8],
+    wrapper: Wrapper,
+    len: usize,
+    limit: usize,
+) -> core::result::Result<(), Error> {
+    let (header_len, trailer_len, window_size) = match wrapper {
+        Wrapper::Raw => (0, 0, MAX_WINDOW_SIZE),
+        Wrapper::Zlib => match zlib_dict_header(src)? {
+            (window_size, None) => (2, 4, window_size),
+            (_, Some(id)) => return Err(Error::DictionaryRequired(id)),
+        },
+        Wrapper::Gzip => (gzip_header(src)?, 8, MAX_WINDOW_SIZE),
+    };
+    let data = src
+        .get(header_len..src.len().saturating_sub(trailer_len))
+        .filter(|_| src.len() >= header_len + trailer_len)
+        .ok_or(Error::Underflow)?;
+
+    out.clear();
+    out.resize(len.min(limit), 0);
+    let mut state = DecoderState::default();
+    let (mut dptr, mut bit_pos) = (0, 0);
+    loop {
+        let metrics = &mut InflateMetrics::default();
+        match inflate_block(out, dptr, data, bit_pos, &mut state, window_size, metrics) {
+            Ok((last, next, end)) => {
+                (dptr, bit_pos) = (end, next);
+                if last {
+                    break;
+                }
+            }
+            Err(Error::Overflow) if out.len() >= limit => return Err(Error::LimitExceeded),
+            Err(Error::Overflow) => {
+                let len = out.len().checked_mul(2).ok_or(Error::Overflow)?;
+                out.resize(len.max(1024).min(limit), 0);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    out.truncate(dptr);
+
+    let trailer = &src[src.len() - trailer_len..];
+    let valid = match wrapper {
+        Wrapper::Raw => true,
+        Wrapper::Zlib => *trailer == crate::checksum::adler32(1, out).to_be_bytes(),
+        Wrapper::Gzip => {
+            trailer[..4] == crate::checksum::crc32(0, out).to_le_bytes()
+                && trailer[4..] == (out.len() as u32).to_le_bytes()
+        }
+    };
+    if !valid {
+        return Err(Error::ChecksumMismatch);
+    }
+    Ok(())
+}
+
+// ----------------------------------------------------------------------------
+// Like `inflate_with_options`, also returns the number of bytes of `src` the stream occupies,
+// rounded up to whole bytes. Data behind the final block is ignored.
+pub fn inflate_consumed(
+    dst: &mut [u8],
+    src: &[u8],
+    options: &InflateOptions,
+) -> core::result::Result<(usize, usize), Error> {
+    inflate_stream(dst, 0, src, MAX_WINDOW_SIZE, options)
+}
+
+// ----------------------------------------------------------------------------
+// Outcome of `inflate_partial`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PartialStatus {
+    // the final block was decoded
+    Done,
+    // the input ended before the final block, everything up to there was decoded
+    NeedsMoreInput,
+}
+
+// ----------------------------------------------------------------------------
+// Like `inflate_with_options`, input ending before the final block is a successful partial
+// decode instead of `Error::Underflow`, e.g. a WebSocket permessage-deflate message (RFC 7692)
+// whose trailing 00 00 ff ff was stripped. Returns the status and the number of decoded bytes.
+// `options.dictionary` supplies the earlier messages of a connection with context takeover.
+pub fn inflate_partial(
+    dst: &mut [u8],
+    src: &[u8],
+    options: &InflateOptions,
+) -> core::result::Result<(PartialStatus, usize), Error> {
+    let dict = options.dictionary.unwrap_or_default();
+    let dict = &dict[dict.len().saturating_sub(MAX_WINDOW_SIZE)..];
+    if dict.is_empty() {
+        return partial_stream(dst, 0, src, options);
+    }
+
+    // the dictionary precedes the output as if it had been decoded before
+    let mut buf = Vec::with_capacity(dict.len() + dst.len());
+    buf.extend_from_slice(dict);
+    buf.resize(dict.len() + dst.len(), 0);
+    let (status, end) = partial_stream(&mut buf, dict.len(), src, options)?;
+    let len = end - dict.len();
+    dst[..len].copy_from_slice(&buf[dict.len()..end]);
+    Ok((status, len))
+}
+
+// ----------------------------------------------------------------------------
+// Decodes into `dst[start..]` like `inflate_stream`, returns the status and the output position.
+fn partial_stream(
+    dst: &mut [u8],
+    start: usize,
+    src: &[u8],
+    options: &InflateOptions,
+) -> core::result::Result<(PartialStatus, usize), Error> {
+    let mut pos = Position {
+        out: start,
+        ..Default::default()
+    };
+    let mut br = BitReader::new(src, true);
+    match inflate_reader_at(dst, &mut pos, &mut br, MAX_WINDOW_SIZE, options) {
+        Ok((end, _)) => Ok((PartialStatus::Done, end)),
+        Err(Error::Underflow) => Ok((PartialStatus::NeedsMoreInput, pos.out)),
+        Err(err) => Err(err),
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Decodes a raw deflate stream without an output buffer of its full size: the output of each
+// block is passed to `sink` once the block is decoded, only the 32 KB window and the current
+// block are kept. Returns (bytes consumed, bytes decoded), an error of `sink` ends decoding.
+pub fn inflate_to_sink<E: From<Error>>(
+    src: &[u8],
+    mut sink: impl FnMut(&[u8]) -> core::result::Result<(), E>,
+) -> core::result::Result<(usize, u64), E> {
+    let mut window = alloc::vec![0u8; 4 * MAX_WINDOW_SIZE];
+    let mut state = DecoderState::default();
+    let mut metrics = InflateMetrics::default();
+    let (mut dptr, mut bit_pos, mut total) = (0, 0, 0);
+    loop {
+        let block = inflate_block(
+            &mut window,
+            dptr,
+            src,
+            bit_pos,
+            &mut state,
+            MAX_WINDOW_SIZE,
+            &mut metrics,
+        );
+        match block {
+            Ok((last, next, end)) => {
+                sink(&window[dptr..end])?;
+                total += (end - dptr) as u64;
+                bit_pos = next;
+                if last {
+                    return Ok((bit_pos.div_ceil(8), total));
+                }
+
+                // keep the window for back-references of the next block
+                let keep = end.min(MAX_WINDOW_SIZE);
+                window.copy_within(end - keep..end, 0);
+                dptr = keep;
+            }
+            // a block larger than the buffer is decoded again into a larger one
+            Err(Error::Overflow) => window.resize(2 * window.len(), 0),
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Like `inflate_with_options`, errors report the input and output positions reached.
+pub fn inflate_verbose(
+    dst: &mut [u8],
+    src: &[u8],
+    options: &InflateOptions,
+) -> core::result::Result<usize, ErrorContext> {
+    let mut br = BitReader::new(src, options.strict_end_of_input);
+    let mut pos = Position::default();
+    match inflate_reader_at(dst, &mut pos, &mut br, MAX_WINDOW_SIZE, options) {
+        Ok((len, _)) => Ok(len),
+        Err(error) => Err(ErrorContext {
+            error,
+            bit_pos: br.bit_pos(),
+            out_pos: pos.out,
+            block: pos.blocks.saturating_sub(1),
+            block_bit_pos: pos.block_bit_pos,
+        }),
+    }
+}
+
+// ----------------------------------------------------------------------------
+fn inflate_window(
+    dst: &mut [u8],
+    src: &[u8],
+    window_size: usize,
+    options: &InflateOptions,
+) -> core::result::Result<usize, Error> {
+    inflate_stream(dst, 0, src, window_size, options).map(|(len, _)| len)
+}
+
+// ----------------------------------------------------------------------------
+// Decodes the single block starting at bit `bit_pos` of `src` into `dst[dptr..]`, `dst[..dptr]`
+// holds the preceding output for back-references. Returns (final block, bit position after the
+// block, output position after the block). Input ending within the block fails with `Underflow`,
+// so incremental callers can retry with more input, output beyond `dst` fails with `Overflow`.
+// `state` keeps the Huffman tables for the following blocks of the stream, the counters of a
+// successfully decoded block are added to `metrics`.
+pub(crate) fn inflate_block(
+    dst: &mut [u8],
+    dptr: usize,
+    src: &[u8],
+    bit_pos: usize,
+    state: &mut DecoderState,
+    window_size: usize,
+    metrics: &mut InflateMetrics,
+) -> core::result::Result<(bool, usize, usize), Error> {
+    let mut br = BitReader::new(src, true);
+    br.set_byte_pos(bit_pos / 8);
+    let skip = (bit_pos % 8) as u8;
+    if skip != 0 {
+        br.read_bits(skip)?;
+    }
+
+    let options = InflateOptions::default();
+    let mut monitor = Monitor::new(&options);
+    let start = dptr;
+    let mut dptr = dptr;
+    let b_final = br.read_bits(1)?;
+    match br.read_bits(2)? {
+        0 => {
+            inflate_no_compression(dst, &mut dptr, &mut br)?;
+            metric!(monitor.metrics.stored_blocks += 1);
+        }
+        1 => {
+            metric!(monitor.metrics.fixed_blocks += 1);
+            if !state.is_fixed_built() {
+                metric!(monitor.metrics.table_rebuilds += 2);
+            }
+            let trees = state.fixed()?;
+            inflate_huffman_block(dst, &mut dptr, &mut br, trees, window_size, &mut monitor)?;
+        }
+        2 => {
+            metric!(monitor.metrics.dynamic_blocks += 1);
+            metric!(monitor.metrics.table_rebuilds += 3);
+            let trees = state.read_dynamic(&mut br, options.huffman_policy, window_size)?;
+            inflate_huffman_block(dst, &mut dptr, &mut br, trees, window_size, &mut monitor)?;
+        }
+        _ => return Err(Error::InvalidBlockType),
+    }
+    metric!({
+        monitor.metrics.bytes_in = (br.bit_pos().div_ceil(8) - bit_pos.div_ceil(8)) as u64;
+        monitor.metrics.bytes_out = (dptr - start) as u64;
+        *metrics += monitor.metrics;
+    });
+    #[cfg(not(feature = "metrics"))]
+    let _ = (metrics, start);
+    Ok((b_final != 0, br.bit_pos(), dptr))
+}
+
+// ----------------------------------------------------------------------------
+// Position of an incremental decoder within a deflate block, see `inflate_block_part`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) enum BlockPart {
+    // the next bits are a block header
+    #[default]
+    Header,
+    // `remaining` bytes of a stored block are still to be copied
+    Stored {
+        remaining: usize,
+        last: bool,
+    },
+    // Huffman coded symbols follow, decoded with the fixed or the current dynamic tables
+    Huffman {
+        fixed: bool,
+        last: bool,
+    },
+}
+
+// bits of the longest literal/length code and distance code with their extra bits, rounded up
+const MAX_SYMBOL_BITS: usize = 64;
+
+// ----------------------------------------------------------------------------
+// Like `inflate_block` with a `MAX_WINDOW_SIZE` window, but decodes only as far as `src` and
+// `dst` allow: decoding stops after the last complete symbol and `part` tells where the next
+// call continues, so an incremental caller never decodes data twice. Only a block header is
+// read again from its start if the input ends within it. Returns (final block once the block is
+// complete, bit position reached, output position reached), `Underflow` or `Overflow` if no
+// progress was possible. Unless `finish` marks the input as complete, an error within the last
+// bits of the input counts as missing input, the following bits may make the symbol valid.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn inflate_block_part(
+    dst: &mut [u8],
+    dptr: usize,
+    src: &[u8],
+    bit_pos: usize,
+    state: &mut DecoderState,
+    part: &mut BlockPart,
+    finish: bool,
+    metrics: &mut InflateMetrics,
+) -> core::result::Result<(Option<bool>, usize, usize), Error> {
+    let mut br = BitReader::new(src, true);
+    br.set_byte_pos(bit_pos / 8);
+    let skip = (bit_pos % 8) as u8;
+    if skip != 0 {
+        br.read_bits(skip)?;
+    }
+    let near_end = |br: &BitReader| !finish && br.bit_pos() + MAX_SYMBOL_BITS > 8 * src.len();
+
+    let options = InflateOptions::default();
+    let mut monitor = Monitor::new(&options);
+    if *part == BlockPart::Header {
+        *part = match read_block_header(&mut br, state, &mut monitor) {
+            Ok(next) => next,
+            Err(Error::Underflow) => return Err(Error::Underflow),
+            Err(_) if near_end(&br) => return Err(Error::Underflow),
+            Err(err) => return Err(err),
+        };
+    }
+
+    let start = dptr;
+    let mut dptr = dptr;
+    let mut end_bits = None;
+    let mut full = false;
+    let complete = match *part {
+        BlockPart::Header => false,
+        BlockPart::Stored { remaining, last } => {
+            let len = remaining
+                .min(br.bytes_left().unwrap_or(0))
+                .min(dst.len() - dptr);
+            br.read_bytes(&mut dst[dptr..dptr + len])?;
+            dptr += len;
+            full = dptr == dst.len();
+            *part = BlockPart::Stored {
+                remaining: remaining - len,
+                last,
+            };
+            remaining == len
+        }
+        BlockPart::Huffman { fixed, .. } => {
+            let trees = if fixed {
+                state.fixed()?
+            } else {
+                &state.dynamic
+            };
+            let window_size = MAX_WINDOW_SIZE;
+            let mut complete =
+                inflate_huffman_fast(dst, &mut dptr, &mut br, trees, window_size, &mut monitor)?;
+            while !complete {
+                let resume = (br.bit_pos(), dptr);
+                match inflate_symbol(dst, &mut dptr, &mut br, trees, window_size, &mut monitor) {
+                    Ok(end) => complete = end,
+                    Err(err @ (Error::Underflow | Error::Overflow)) => {
+                        // the symbol is read again by the next call
+                        full = err == Error::Overflow;
+                        (end_bits, dptr) = (Some(resume.0), resume.1);
+                        break;
+                    }
+                    Err(_) if near_end(&br) => {
+                        (end_bits, dptr) = (Some(resume.0), resume.1);
+                        break;
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+            complete
+        }
+    };
+
+    let end_bits = end_bits.unwrap_or(br.bit_pos());
+    if end_bits == bit_pos && dptr == start {
+        return Err(if full {
+            Error::Overflow
+        } else {
+            Error::Underflow
+        });
+    }
+    metric!({
+        monitor.metrics.bytes_in = (end_bits.div_ceil(8) - bit_pos.div_ceil(8)) as u64;
+        monitor.metrics.bytes_out = (dptr - start) as u64;
+        *metrics += monitor.metrics;
+    });
+    #[cfg(not(feature = "metrics"))]
+    let _ = metrics;
+
+    let last = match *part {
+        BlockPart::Stored { last, .. } | BlockPart::Huffman { last, .. } => last,
+        BlockPart::Header => false,
+    };
+    if complete {
+        *part = BlockPart::Header;
+    }
+    Ok((complete.then_some(last), end_bits, dptr))
+}
+
+// ----------------------------------------------------------------------------
+// Reads a block header up to the first symbol or stored byte, builds the tables it needs.
+fn read_block_header(
+    br: &mut BitReader,
+    state: &mut DecoderState,
+    monitor: &mut Monitor,
+) -> core::result::Result<BlockPart, Error> {
+    #[cfg(not(feature = "metrics"))]
+    let _ = monitor;
+    let last = br.read_bits(1)? != 0;
+    match br.read_bits(2)? {
+        0 => {
+            br.align_to_byte();
+            let mut header = [0; 4];
+            br.read_bytes(&mut header)?;
+            let len = u16::from_le_bytes([header[0], header[1]]);
+            let nlen = u16::from_le_bytes([header[2], header[3]]);
+            if len != !nlen {
+                return Err(Error::InvalidBlockLength);
+            }
+            metric!(monitor.metrics.stored_blocks += 1);
+            Ok(BlockPart::Stored {
+                remaining: len as usize,
+                last,
+            })
+        }
+        1 => {
+            metric!(monitor.metrics.fixed_blocks += 1);
+            if !state.is_fixed_built() {
+                metric!(monitor.metrics.table_rebuilds += 2);
+            }
+            state.fixed()?;
+            Ok(BlockPart::Huffman { fixed: true, last })
+        }
+        2 => {
+            metric!(monitor.metrics.dynamic_blocks += 1);
+            metric!(monitor.metrics.table_rebuilds += 3);
+            state.read_dynamic(br, HuffmanPolicy::default(), MAX_WINDOW_SIZE)?;
+            Ok(BlockPart::Huffman { fixed: false, last })
+        }
+        _ => Err(Error::InvalidBlockType),
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Decodes a raw deflate stream into `dst[start..]`, `dst[..start]` is a preset dictionary.
+// Returns (output position after the stream, bytes consumed).
+fn inflate_stream(
+    dst: &mut [u8],
+    start: usize,
+    src: &[u8],
+    window_size: usize,
+    options: &InflateOptions,
+) -> core::result::Result<(usize, usize), Error> {
+    trace_span!("inflate", src_len = src.len(), dst_len = dst.len());
+    let mut br = BitReader::new(src, options.strict_end_of_input);
+    inflate_reader(dst, start, &mut br, window_size, options)
+}
+
+// ----------------------------------------------------------------------------
+// Progress of `inflate_blocks`, kept up to date for error reports.
+#[derive(Default)]
+struct Position {
+    // output position
+    out: usize,
+    // blocks started
+    blocks: usize,
+    // bit offset of the current block header
+    block_bit_pos: usize,
+}
+
+// ----------------------------------------------------------------------------
+fn inflate_reader(
+    dst: &mut [u8],
+    start: usize,
+    br: &mut BitReader,
+    window_size: usize,
+    options: &InflateOptions,
+) -> core::result::Result<(usize, usize), Error> {
+    let mut pos = Position {
+        out: start,
+        ..Default::default()
+    };
+    inflate_reader_at(dst, &mut pos, br, window_size, options)
+}
+
+// ----------------------------------------------------------------------------
+fn inflate_reader_at(
+    dst: &mut [u8],
+    pos: &mut Position,
+    br: &mut BitReader,
+    window_size: usize,
+    options: &InflateOptions,
+) -> core::result::Result<(usize, usize), Error> {
+    // output beyond `max_output` overflows the shortened buffer
+    let end = pos.out.saturating_add(options.max_output).min(dst.len());
+    let limited = end < dst.len();
+    let result = inflate_blocks(&mut dst[..end], pos, br, window_size, options);
+    match result {
+        Err(Error::Overflow) if limited => Err(Error::LimitExceeded),
+        result => result,
+    }
+}
+
+// ----------------------------------------------------------------------------
+fn inflate_blocks(
+    dst: &mut [u8],
+    pos: &mut Position,
+    br: &mut BitReader,
+    window_size: usize,
+    options: &InflateOptions,
+) -> core::result::Result<(usize, usize), Error> {
+    #[cfg(feature = "metrics")]
+    let start = pos.out;
+    let Position {
+        out: dptr,
+        blocks,
+        block_bit_pos,
+    } = pos;
+    let mut monitor = Monitor::new(options);
+    let mut state = DecoderState::default();
+    let mut dynamic_tables = 0;
+    loop {
+        monitor.check(br.bit_pos(), *dptr)?;
+        *blocks += 1;
+        *block_bit_pos = br.bit_pos();
+        if *blocks > options.max_blocks {
+            return Err(Error::LimitExceeded);
+        }
+        let b_final = br.read_bits(1)?;
+        let b_type = br.read_bits(2)?;
+        trace_event!(
+            trace,
+            b_final,
+            b_type,
+            bit_pos = br.bit_pos(),
+            out_pos = *dptr,
+            "deflate block"
+        );
+
+        match b_type {
+            0 => {
+                metric!(monitor.metrics.stored_blocks += 1);
+                inflate_no_compression(dst, dptr, br)?;
+            }
+            1 => {
+                metric!(monitor.metrics.fixed_blocks += 1);
+                if !state.is_fixed_built() {
+                    metric!(monitor.metrics.table_rebuilds += 2);
+                }
+                let trees = state.fixed()?;
+                inflate_huffman_block(dst, dptr, br, trees, window_size, &mut monitor)?;
+            }
+            2 => {
+                dynamic_tables += 1;
+                if dynamic_tables > options.max_dynamic_tables {
+                    return Err(Error::LimitExceeded);
+                }
+                metric!(monitor.metrics.dynamic_blocks += 1);
+                metric!(monitor.metrics.table_rebuilds += 3);
+                let trees = state.read_dynamic(br, options.huffman_policy, window_size)?;
+                inflate_huffman_block(dst, dptr, br, trees, window_size, &mut monitor)?;
+            }
+            _ => {
+                return Err(Error::InvalidBlockType);
+            }
+        }
+
+        if b_final != 0 {
+            break;
+        }
+    }
+
+    let sptr = br.bit_pos();
+    let dptr = *dptr;
+    monitor.report(sptr, dptr);
+    metric!({
+        let mut metrics = monitor.metrics;
+        metrics.bytes_in = sptr.div_ceil(8) as u64;
+        metrics.bytes_out = (dptr - start) as u64;
+        if let Some(cell) = options.metrics {
+            let mut total = cell.get();
+            total += metrics;
+            cell.set(total);
+        }
+    });
+    trace_event!(
+        debug,
+        consumed = sptr.div_ceil(8),
+        produced = dptr,
+        "inflate done"
+    );
+    Ok((dptr, sptr.div_ceil(8)))
 }
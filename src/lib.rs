@@ -1,3 +1,84 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[macro_use]
+mod trace;
+
+#[cfg(feature = "png")]
+pub mod apng;
+pub mod bgzf;
+pub mod bits;
+pub mod buffer;
+pub mod bzip2;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod carve;
+pub mod checksum;
+pub mod deflate;
+pub mod error;
+#[cfg(feature = "std")]
+pub mod file;
+pub mod gzip;
+pub mod http;
+pub mod huffman;
+#[cfg(feature = "image")]
+pub mod image;
 pub mod inflate;
+mod oneshot;
+#[cfg(feature = "png")]
+pub mod png_meta;
+#[cfg(feature = "png")]
 pub mod png_read;
+#[cfg(feature = "png")]
+mod png_simd;
+#[cfg(feature = "png")]
+pub mod png_stream;
+#[cfg(feature = "png")]
+pub mod png_write;
+#[cfg(feature = "png")]
+pub mod pnm;
+pub mod prelude;
+#[cfg(feature = "std")]
+pub mod read;
+pub mod stream;
+pub mod warning;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "std")]
+pub mod write;
+#[cfg(feature = "aes")]
+mod zip_aes;
+#[cfg(feature = "zip")]
+mod zip_crypto;
+#[cfg(feature = "zip")]
+pub mod zip_diff;
+#[cfg(feature = "zip")]
 pub mod zip_read;
+#[cfg(all(feature = "std", feature = "zip"))]
+pub mod zip_stream;
+#[cfg(feature = "zip")]
+pub mod zip_write;
+
+pub use error::{Error, Result};
+#[cfg(all(feature = "std", feature = "png"))]
+pub use oneshot::png_decode_batch;
+#[cfg(feature = "png")]
+pub use oneshot::read_png_rgba;
+#[cfg(feature = "zip")]
+pub use oneshot::unzip_file;
+#[cfg(all(feature = "std", feature = "png", feature = "zip"))]
+pub use oneshot::zip_decode_png_batch;
+#[cfg(all(feature = "std", feature = "zip"))]
+pub use oneshot::zip_read_many;
+#[cfg(all(feature = "png", feature = "zip"))]
+pub use oneshot::zip_read_png;
+pub use oneshot::{
+    compress, compress_to_vec, decompress_to_vec, decompress_to_vec_gzip,
+    decompress_to_vec_gzip_members, decompress_to_vec_gzip_members_with_limit,
+    decompress_to_vec_gzip_with_limit, decompress_to_vec_members,
+    decompress_to_vec_members_with_limit, decompress_to_vec_with_limit, decompress_to_vec_zlib,
+    decompress_to_vec_zlib_with_limit, uncompress, Member, DEFAULT_MAX_OUTPUT,
+};
+#[cfg(feature = "std")]
+pub use oneshot::{decompress_batch, deflate_parallel, BatchOptions, PARALLEL_BLOCK_SIZE};
@@ -0,0 +1,416 @@
+// One-shot convenience functions for the common tasks.
+#[cfg(all(feature = "png", feature = "zip"))]
+use crate::buffer::Heap;
+#[cfg(feature = "std")]
+use crate::deflate::Strategy;
+use crate::inflate::{InflateOptions, Wrapper};
+#[cfg(feature = "png")]
+use crate::png_read;
+#[cfg(feature = "zip")]
+use crate::zip_read;
+use crate::{checksum, deflate, inflate, Result};
+use alloc::vec::Vec;
+use core::ops::Range;
+
+// ----------------------------------------------------------------------------
+// decode into a buffer that grows until the whole stream fits, output beyond `limit` bytes
+// fails with `inflate::Error::LimitExceeded`
+pub(crate) fn inflate_growing(src: &[u8], wrapper: Wrapper, limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    inflate_growing_into(&mut out, src, wrapper, limit)?;
+    Ok(out)
+}
+
+// ----------------------------------------------------------------------------
+// Like `inflate_growing`, decodes into `out` and keeps its allocation.
+fn inflate_growing_into(
+    out: &mut Vec<u8>,
+    src: &[u8],
+    wrapper: Wrapper,
+    limit: usize,
+) -> Result<()> {
+    let len = src.len().saturating_mul(4).max(1024).max(out.capacity());
+    Ok(inflate::decode_growing(out, src, wrapper, len, limit)?)
+}
+
+// ----------------------------------------------------------------------------
+// Output limit of `decompress_to_vec`, its zlib and gzip variants and the multi-member
+// decoders, a decompression bomb fails with `LimitExceeded` instead of exhausting the memory.
+pub const DEFAULT_MAX_OUTPUT: usize = 1 << 30;
+
+// ----------------------------------------------------------------------------
+// Decompresses a raw deflate stream of up to `DEFAULT_MAX_OUTPUT` bytes.
+pub fn decompress_to_vec(src: &[u8]) -> Result<Vec<u8>> {
+    decompress_to_vec_with_limit(src, DEFAULT_MAX_OUTPUT)
+}
+
+// ----------------------------------------------------------------------------
+// Decompresses a raw deflate stream, output beyond `limit` bytes fails with `LimitExceeded`.
+pub fn decompress_to_vec_with_limit(src: &[u8], limit: usize) -> Result<Vec<u8>> {
+    inflate_growing(src, Wrapper::Raw, limit)
+}
+
+// ----------------------------------------------------------------------------
+// Decompresses a zlib stream and verifies its Adler-32 checksum.
+pub fn decompress_to_vec_zlib(src: &[u8]) -> Result<Vec<u8>> {
+    decompress_to_vec_zlib_with_limit(src, DEFAULT_MAX_OUTPUT)
+}
+
+// ----------------------------------------------------------------------------
+pub fn decompress_to_vec_zlib_with_limit(src: &[u8], limit: usize) -> Result<Vec<u8>> {
+    inflate_growing(src, Wrapper::Zlib, limit)
+}
+
+// ----------------------------------------------------------------------------
+// Decompresses a single-member gzip stream and verifies its CRC-32 and size.
+pub fn decompress_to_vec_gzip(src: &[u8]) -> Result<Vec<u8>> {
+    decompress_to_vec_gzip_with_limit(src, DEFAULT_MAX_OUTPUT)
+}
+
+// ----------------------------------------------------------------------------
+pub fn decompress_to_vec_gzip_with_limit(src: &[u8], limit: usize) -> Result<Vec<u8>> {
+    inflate_growing(src, Wrapper::Gzip, limit)
+}
+
+// ----------------------------------------------------------------------------
+// One stream of concatenated streams: its bytes in the input and its data in the output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Member {
+    pub input: Range<usize>,
+    pub output: Range<usize>,
+}
+
+// ----------------------------------------------------------------------------
+// decoder returning (output length, input length) of the stream at the start of the input
+type DecodeConsumed =
+    fn(&mut [u8], &[u8], &InflateOptions) -> core::result::Result<(usize, usize), inflate::Error>;
+
+// ----------------------------------------------------------------------------
+// Decodes streams with `decode` until the input is exhausted, appending their output. The output
+// of the previous stream is the size guess for the next one. Output beyond `limit` bytes in total
+// fails with `inflate::Error::LimitExceeded`.
+fn inflate_members(
+    src: &[u8],
+    decode: DecodeConsumed,
+    limit: usize,
+) -> Result<(Vec<u8>, Vec<Member>)> {
+    let options = InflateOptions::default();
+    let mut out = Vec::new();
+    let mut members = Vec::new();
+    let mut pos = 0;
+    let mut guess = src.len().saturating_mul(4);
+    loop {
+        let start = out.len();
+        let remaining = limit - start;
+        out.resize(start + guess.max(1024).min(remaining), 0);
+        let (len, consumed) = loop {
+            match decode(&mut out[start..], &src[pos..], &options) {
+                Ok(result) => break result,
+                Err(inflate::Error::Overflow) if out.len() - start >= remaining => {
+                    return Err(inflate::Error::LimitExceeded.into());
+                }
+                Err(inflate::Error::Overflow) => {
+                    let len = (out.len() - start).checked_mul(2);
+                    let len = len.ok_or(inflate::Error::Overflow)?.min(remaining);
+                    out.resize(start + len, 0);
+                }
+                Err(err) => return Err(err.into()),
+            }
+        };
+
+        out.truncate(start + len);
+        members.push(Member {
+            input: pos..pos + consumed,
+            output: start..start + len,
+        });
+        pos += consumed;
+        guess = len;
+        if pos == src.len() {
+            return Ok((out, members));
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Decompresses concatenated raw deflate streams, each one starting at the byte boundary after
+// the final block of the previous one, up to `DEFAULT_MAX_OUTPUT` bytes in total.
+pub fn decompress_to_vec_members(src: &[u8]) -> Result<(Vec<u8>, Vec<Member>)> {
+    decompress_to_vec_members_with_limit(src, DEFAULT_MAX_OUTPUT)
+}
+
+// ----------------------------------------------------------------------------
+pub fn decompress_to_vec_members_with_limit(
+    src: &[u8],
+    limit: usize,
+) -> Result<(Vec<u8>, Vec<Member>)> {
+    inflate_members(src, inflate::inflate_consumed, limit)
+}
+
+// ----------------------------------------------------------------------------
+// Decompresses a multi-member gzip stream as written by pigz, bgzip or `cat a.gz b.gz`, the
+// CRC-32 and size of every member are verified. Output beyond `DEFAULT_MAX_OUTPUT` bytes in
+// total fails with `LimitExceeded`.
+pub fn decompress_to_vec_gzip_members(src: &[u8]) -> Result<(Vec<u8>, Vec<Member>)> {
+    decompress_to_vec_gzip_members_with_limit(src, DEFAULT_MAX_OUTPUT)
+}
+
+// ----------------------------------------------------------------------------
+pub fn decompress_to_vec_gzip_members_with_limit(
+    src: &[u8],
+    limit: usize,
+) -> Result<(Vec<u8>, Vec<Member>)> {
+    inflate_members(src, inflate::inflate_gzip_consumed, limit)
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BatchOptions {
+    // number of worker threads, 0 uses the available parallelism
+    pub threads: usize,
+    // inputs are zlib streams instead of raw deflate data (`decompress_batch`)
+    pub zlib: bool,
+}
+
+// ----------------------------------------------------------------------------
+// Applies `f` to all items on a pool of scoped threads, results are in input order. Each worker
+// passes its own state, created by `init`, to every call.
+#[cfg(feature = "std")]
+fn run_batch<I, S, T, F>(items: I, threads: usize, init: impl Fn() -> S + Sync, f: F) -> Vec<T>
+where
+    I: ExactSizeIterator + Send,
+    I::Item: Send,
+    T: Send,
+    F: Fn(&mut S, I::Item) -> T + Sync,
+{
+    let threads = match threads {
+        0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    };
+    let threads = threads.min(items.len()).max(1);
+
+    // workers pick the next unprocessed item until all are taken
+    let mut results: Vec<Option<T>> = (0..items.len()).map(|_| None).collect();
+    let items = std::sync::Mutex::new(items.enumerate());
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut state = init();
+                    let mut done = Vec::new();
+                    loop {
+                        let next = items.lock().map(|mut items| items.next());
+                        let Ok(Some((index, item))) = next else {
+                            return done;
+                        };
+                        done.push((index, f(&mut state, item)));
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            match worker.join() {
+                Ok(done) => {
+                    for (index, result) in done {
+                        results[index] = Some(result);
+                    }
+                }
+                Err(panic) => std::panic::resume_unwind(panic),
+            }
+        }
+    });
+
+    results.into_iter().flatten().collect()
+}
+
+// ----------------------------------------------------------------------------
+// Decompresses independent buffers on a pool of scoped threads, results are in input order.
+#[cfg(feature = "std")]
+pub fn decompress_batch(srcs: &[&[u8]], options: &BatchOptions) -> Vec<Result<Vec<u8>>> {
+    let decode = if options.zlib {
+        decompress_to_vec_zlib
+    } else {
+        decompress_to_vec
+    };
+    run_batch(srcs.iter(), options.threads, || (), |_, src| decode(src))
+}
+
+// ----------------------------------------------------------------------------
+// Extracts the entries `names` of a ZIP archive on a pool of scoped threads, the data is in the
+// order of `names`. Fails with the error of the first entry that cannot be extracted.
+#[cfg(all(feature = "std", feature = "zip"))]
+pub fn zip_read_many(
+    data: &[u8],
+    files: &[zip_read::File],
+    names: &[&str],
+    options: &BatchOptions,
+) -> Result<Vec<Vec<u8>>> {
+    let results = run_batch(
+        names.iter(),
+        options.threads,
+        || (),
+        |_, name| zip_read::zip_read(data, files, name),
+    );
+    Ok(results
+        .into_iter()
+        .collect::<core::result::Result<_, _>>()?)
+}
+
+// ----------------------------------------------------------------------------
+// Input bytes per block of `deflate_parallel`, as pigz.
+#[cfg(feature = "std")]
+pub const PARALLEL_BLOCK_SIZE: usize = 1 << 17;
+
+// ----------------------------------------------------------------------------
+// Compresses `src` at `level` into a raw deflate stream on a pool of scoped threads. Every block
+// of `PARALLEL_BLOCK_SIZE` bytes is compressed on its own and ends with a sync flush, the output
+// is the concatenation of the blocks as written by pigz.
+#[cfg(feature = "std")]
+pub fn deflate_parallel(src: &[u8], level: u8, options: &BatchOptions) -> Vec<u8> {
+    let count = src.len().div_ceil(PARALLEL_BLOCK_SIZE).max(1);
+    let blocks = (0..count).map(|i| {
+        let start = i * PARALLEL_BLOCK_SIZE;
+        (
+            &src[start..src.len().min(start + PARALLEL_BLOCK_SIZE)],
+            i + 1 == count,
+        )
+    });
+    let compressed = run_batch(
+        blocks,
+        options.threads,
+        || (),
+        |_, (block, last)| {
+            let mut dst = Vec::with_capacity(block.len() / 2 + 16);
+            deflate::deflate_chunk(&mut dst, block, level.into(), Strategy::Default, last, None);
+            dst
+        },
+    );
+    compressed.concat()
+}
+
+// ----------------------------------------------------------------------------
+// Memory a batch worker reuses for all its images: the scratch buffers of `png_read_into` and
+// the scanlines before the conversion to RGBA8.
+#[cfg(all(feature = "std", feature = "png"))]
+#[derive(Default)]
+struct PngWorker {
+    scratch: png_read::PngScratch,
+    lines: Vec<u8>,
+}
+
+// ----------------------------------------------------------------------------
+// Decodes a PNG as RGBA8 into `dst`, returns (width, height).
+#[cfg(all(feature = "std", feature = "png"))]
+fn decode_png_into(png: &[u8], dst: &mut [u8], worker: &mut PngWorker) -> Result<(usize, usize)> {
+    let ihdr = png_read::png_info(png)?;
+    worker.lines.resize(png_read::decoded_size(&ihdr)?, 0);
+    png_read::png_read_into(png, &mut worker.lines, &mut worker.scratch)?;
+    let trns = png_read::png_transparency(png)?;
+    let sbit = png_read::png_significant_bits(png)?;
+    let plte = worker.scratch.palette();
+    png_read::to_rgba_into(&ihdr, plte, trns.as_ref(), sbit, &worker.lines, dst)?;
+    Ok((ihdr.width, ihdr.height))
+}
+
+// ----------------------------------------------------------------------------
+// Decodes PNG images concurrently as RGBA8 into the corresponding `dsts` buffers, which must
+// hold at least `width * height * 4` bytes each. Returns (width, height) per image, or
+// `png_read::Error::BufferError` if there are not as many buffers as images.
+#[cfg(all(feature = "std", feature = "png"))]
+pub fn png_decode_batch(
+    pngs: &[&[u8]],
+    dsts: &mut [&mut [u8]],
+    options: &BatchOptions,
+) -> Result<Vec<Result<(usize, usize)>>> {
+    if pngs.len() != dsts.len() {
+        return Err(png_read::Error::BufferError.into());
+    }
+    let items = pngs.iter().zip(dsts.iter_mut());
+    Ok(run_batch(
+        items,
+        options.threads,
+        PngWorker::default,
+        |worker, (png, dst)| decode_png_into(png, dst, worker),
+    ))
+}
+
+// ----------------------------------------------------------------------------
+// Like `png_decode_batch` for the PNG entries `names` of a ZIP archive.
+#[cfg(all(feature = "std", feature = "png", feature = "zip"))]
+pub fn zip_decode_png_batch(
+    data: &[u8],
+    files: &[zip_read::File],
+    names: &[&str],
+    dsts: &mut [&mut [u8]],
+    options: &BatchOptions,
+) -> Result<Vec<Result<(usize, usize)>>> {
+    if names.len() != dsts.len() {
+        return Err(png_read::Error::BufferError.into());
+    }
+    let items = names.iter().zip(dsts.iter_mut());
+    Ok(run_batch(
+        items,
+        options.threads,
+        PngWorker::default,
+        |worker, (name, dst)| {
+            let png = zip_read::entry_data(data, files, name, &InflateOptions::default())?;
+            decode_png_into(&png, dst, worker)
+        },
+    ))
+}
+
+// ----------------------------------------------------------------------------
+// Compresses `src` into a zlib stream, `level` ranges from 0 (none) to 9 (best).
+pub fn compress_to_vec(src: &[u8], level: u8) -> Vec<u8> {
+    let mut dst = Vec::with_capacity(src.len() / 2 + 16);
+    dst.extend_from_slice(&deflate::ZLIB_HEADER);
+    deflate::deflate(&mut dst, src, level.into());
+    dst.extend_from_slice(&checksum::adler32(1, src).to_be_bytes());
+    dst
+}
+
+// ----------------------------------------------------------------------------
+// zlib's `compress2`: `data` as a zlib stream with Adler-32 trailer, `level` from 0 to 9.
+pub fn compress(data: &[u8], level: u8) -> Vec<u8> {
+    compress_to_vec(data, level)
+}
+
+// ----------------------------------------------------------------------------
+// zlib's `uncompress`: decodes the zlib stream `data` into a buffer of `expected_len` bytes, the
+// size of the original data kept by the caller, and verifies the Adler-32 checksum. As zlib's
+// `destLen` it is an upper bound, longer output fails with `inflate::Error::Overflow` and
+// shorter output is returned as it is.
+pub fn uncompress(data: &[u8], expected_len: usize) -> Result<Vec<u8>> {
+    let mut out = alloc::vec![0; expected_len];
+    let len = inflate::inflate_zlib(&mut out, data)?;
+    out.truncate(len);
+    Ok(out)
+}
+
+// ----------------------------------------------------------------------------
+// Decodes a PNG file into (width, height, RGBA8 pixels).
+#[cfg(feature = "png")]
+pub fn read_png_rgba(png: &[u8]) -> Result<(usize, usize, Vec<u8>)> {
+    Ok(png_read::png_decode_rgba(png)?)
+}
+
+// ----------------------------------------------------------------------------
+// Extracts the entry `name` from the ZIP archive `data`.
+#[cfg(feature = "zip")]
+pub fn unzip_file(data: &[u8], name: &str) -> Result<Vec<u8>> {
+    let files = zip_read::zip_open(data)?;
+    Ok(zip_read::zip_read(data, &files, name)?)
+}
+
+// ----------------------------------------------------------------------------
+// Decodes the PNG entry `name` of a ZIP archive, stored entries are decoded in place.
+#[cfg(all(feature = "png", feature = "zip"))]
+pub fn zip_read_png(
+    data: &[u8],
+    files: &[zip_read::File],
+    name: &str,
+    options: &InflateOptions,
+) -> Result<(png_read::PNGChunkIHDR, Vec<u32>, Vec<u8>)> {
+    let png = zip_read::entry_data(data, files, name, options)?;
+    Ok(png_read::png_read_with_options(&png, &mut Heap, options)?)
+}
@@ -0,0 +1,388 @@
+// PNG ancillary chunks with image metadata.
+// * https://www.w3.org/TR/png-3/#11Ancillary-chunks
+// * tEXt holds Latin-1 text, zTXt zlib compressed Latin-1 text, iTXt UTF-8 text that may be
+//   compressed, with a language tag and a translated keyword
+// * ancillary chunks with a bad CRC or invalid contents are skipped with a warning, the first
+//   instance of single-instance chunks is used
+// * compressed text and ICC profiles are decoded up to a size limit, so a small chunk can't
+//   expand to gigabytes, `InflateOptions::max_output` lowers the limits further
+// * gAMA, sRGB and iCCP describe the color space, sRGB and iCCP take precedence over gAMA
+use crate::inflate::{self, InflateOptions, Wrapper};
+use crate::oneshot::inflate_growing;
+use crate::png_read::{png_chunks, png_decode_rgba, read_ihdr, PNGChunk, PNGColorType, Result};
+use crate::warning::{warn, Warning};
+use alloc::{string::String, vec::Vec};
+
+// ----------------------------------------------------------------------------
+const IEND: u32 = u32::from_be_bytes(*b"IEND");
+const TEXT: u32 = u32::from_be_bytes(*b"tEXt");
+const ZTXT: u32 = u32::from_be_bytes(*b"zTXt");
+const ITXT: u32 = u32::from_be_bytes(*b"iTXt");
+const GAMA: u32 = u32::from_be_bytes(*b"gAMA");
+const SRGB: u32 = u32::from_be_bytes(*b"sRGB");
+const PHYS: u32 = u32::from_be_bytes(*b"pHYs");
+const BKGD: u32 = u32::from_be_bytes(*b"bKGD");
+const ICCP: u32 = u32::from_be_bytes(*b"iCCP");
+const TIME: u32 = u32::from_be_bytes(*b"tIME");
+
+// ----------------------------------------------------------------------------
+// decompressed zTXt and iTXt text beyond this size is skipped
+pub const MAX_TEXT_LEN: usize = 1 << 20;
+// decompressed ICC profiles beyond this size are skipped
+pub const MAX_PROFILE_LEN: usize = 1 << 24;
+
+// ----------------------------------------------------------------------------
+// gAMA value of the sRGB transfer function, 1/2.2 times 100000
+pub const SRGB_GAMMA: u32 = 45455;
+
+// ----------------------------------------------------------------------------
+// Text chunk, `language` and `translated_keyword` are empty for tEXt and zTXt.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PngText {
+    pub keyword: String,
+    pub text: String,
+    pub language: String,
+    pub translated_keyword: String,
+    // the text was stored zlib compressed
+    pub compressed: bool,
+}
+
+// ----------------------------------------------------------------------------
+// Contents of the pHYs chunk.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhysicalSize {
+    // pixels per unit in x and y direction
+    pub x: u32,
+    pub y: u32,
+    // the unit is the meter, otherwise only the pixel aspect ratio is known
+    pub meter: bool,
+}
+
+// ----------------------------------------------------------------------------
+impl PhysicalSize {
+    // Pixels per inch in x and y direction rounded to integers, `None` without a unit.
+    pub fn dpi(&self) -> Option<(u32, u32)> {
+        let dpi = |ppm: u32| ((ppm as u64 * 254 + 5000) / 10000) as u32;
+        self.meter.then(|| (dpi(self.x), dpi(self.y)))
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Contents of the tIME chunk, the time of the last modification in UTC.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PngTime {
+    pub year: u16,
+    // 1 to 12
+    pub month: u8,
+    // 1 to 31
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    // up to 60 for leap seconds
+    pub second: u8,
+}
+
+// ----------------------------------------------------------------------------
+// Contents of the bKGD chunk, samples are in the image bit depth.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Background {
+    Palette(u8),
+    Grey(u16),
+    Rgb(u16, u16, u16),
+}
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PngMetadata {
+    // text chunks in file order
+    pub texts: Vec<PngText>,
+    // image gamma times 100000
+    pub gamma: Option<u32>,
+    // sRGB rendering intent, 0 (perceptual) to 3 (absolute colorimetric)
+    pub srgb: Option<u8>,
+    pub physical_size: Option<PhysicalSize>,
+    pub background: Option<Background>,
+    pub icc_profile: Option<IccProfile>,
+    pub modified: Option<PngTime>,
+}
+
+// ----------------------------------------------------------------------------
+impl PngMetadata {
+    // Text of the first text chunk with `keyword`, e.g. "Title" or "Author".
+    pub fn text(&self, keyword: &str) -> Option<&str> {
+        let text = self.texts.iter().find(|text| text.keyword == keyword)?;
+        Some(&text.text)
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Contents of the iCCP chunk, the profile is decompressed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IccProfile {
+    pub name: String,
+    pub profile: Vec<u8>,
+}
+
+// ----------------------------------------------------------------------------
+// RGBA8 pixels of a PNG with its metadata.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RgbaImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+    pub metadata: PngMetadata,
+}
+
+// ----------------------------------------------------------------------------
+fn latin1(data: &[u8]) -> String {
+    data.iter().map(|&b| b as char).collect()
+}
+
+// ----------------------------------------------------------------------------
+// Splits at the first zero byte, `None` if there is none.
+fn split_zero(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let pos = data.iter().position(|&b| b == 0)?;
+    Some((&data[..pos], &data[pos + 1..]))
+}
+
+// ----------------------------------------------------------------------------
+// keyword of 1 to 79 bytes followed by a zero byte
+fn keyword(data: &[u8]) -> Option<(String, &[u8])> {
+    let (keyword, rest) = split_zero(data)?;
+    (1..=79)
+        .contains(&keyword.len())
+        .then(|| (latin1(keyword), rest))
+}
+
+// ----------------------------------------------------------------------------
+// Why an ancillary chunk was skipped.
+enum Skip {
+    Invalid,
+    // the decompressed contents exceed the limit
+    TooLarge,
+}
+
+// ----------------------------------------------------------------------------
+// Decodes the zlib stream of a compressed chunk, the output buffer grows to at most `limit`
+// bytes.
+fn decompress(data: &[u8], limit: usize) -> core::result::Result<Vec<u8>, Skip> {
+    inflate_growing(data, Wrapper::Zlib, limit).map_err(|err| match err {
+        crate::Error::Inflate(inflate::Error::LimitExceeded) => Skip::TooLarge,
+        _ => Skip::Invalid,
+    })
+}
+
+// ----------------------------------------------------------------------------
+fn parse_icc_profile(data: &[u8], limit: usize) -> core::result::Result<IccProfile, Skip> {
+    let (name, rest) = keyword(data).ok_or(Skip::Invalid)?;
+    match rest.split_first() {
+        Some((0, data)) => Ok(IccProfile {
+            name,
+            profile: decompress(data, limit)?,
+        }),
+        _ => Err(Skip::Invalid),
+    }
+}
+
+// ----------------------------------------------------------------------------
+fn parse_text(chunk: &PNGChunk, limit: usize) -> core::result::Result<PngText, Skip> {
+    let (keyword, rest) = keyword(chunk.data).ok_or(Skip::Invalid)?;
+    match chunk.r#type {
+        TEXT => Ok(PngText {
+            keyword,
+            text: latin1(rest),
+            ..Default::default()
+        }),
+        ZTXT => match rest.split_first() {
+            Some((0, data)) => Ok(PngText {
+                keyword,
+                text: latin1(&decompress(data, limit)?),
+                compressed: true,
+                ..Default::default()
+            }),
+            _ => Err(Skip::Invalid),
+        },
+        _ => {
+            let (&[flag, method], rest) = rest.split_first_chunk().ok_or(Skip::Invalid)?;
+            let (language, rest) = split_zero(rest).ok_or(Skip::Invalid)?;
+            let (translated_keyword, text) = split_zero(rest).ok_or(Skip::Invalid)?;
+            let text = match (flag, method) {
+                (0, _) => text.to_vec(),
+                (1, 0) => decompress(text, limit)?,
+                _ => return Err(Skip::Invalid),
+            };
+            let utf8 = |bytes: Vec<u8>| String::from_utf8(bytes).map_err(|_| Skip::Invalid);
+            Ok(PngText {
+                keyword,
+                text: utf8(text)?,
+                language: utf8(language.to_vec())?,
+                translated_keyword: utf8(translated_keyword.to_vec())?,
+                compressed: flag == 1,
+            })
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+fn parse_time(data: &[u8]) -> Option<PngTime> {
+    let data: [u8; 7] = data.try_into().ok()?;
+    let time = PngTime {
+        year: u16::from_be_bytes([data[0], data[1]]),
+        month: data[2],
+        day: data[3],
+        hour: data[4],
+        minute: data[5],
+        second: data[6],
+    };
+    let valid = (1..=12).contains(&time.month)
+        && (1..=31).contains(&time.day)
+        && time.hour <= 23
+        && time.minute <= 59
+        && time.second <= 60;
+    valid.then_some(time)
+}
+
+// ----------------------------------------------------------------------------
+fn parse_background(data: &[u8], color_type: PNGColorType) -> Option<Background> {
+    let sample = |i: usize| u16::from_be_bytes([data[2 * i], data[2 * i + 1]]);
+    match (color_type, data.len()) {
+        (PNGColorType::IndexedColor, 1) => Some(Background::Palette(data[0])),
+        (PNGColorType::Greyscale | PNGColorType::GreyscaleAplha, 2) => {
+            Some(Background::Grey(sample(0)))
+        }
+        (PNGColorType::TrueColor | PNGColorType::TrueColorAlpha, 6) => {
+            Some(Background::Rgb(sample(0), sample(1), sample(2)))
+        }
+        _ => None,
+    }
+}
+
+// ----------------------------------------------------------------------------
+pub fn png_metadata(png: &[u8]) -> Result<PngMetadata> {
+    png_metadata_with_options(png, &InflateOptions::default())
+}
+
+// ----------------------------------------------------------------------------
+// Collects the metadata of all chunks up to IEND. CRCs are checked unless
+// `InflateOptions::verify_checksums` is off, skipped chunks are reported to
+// `InflateOptions::warnings`.
+pub fn png_metadata_with_options(png: &[u8], options: &InflateOptions) -> Result<PngMetadata> {
+    let (ihdr, _) = read_ihdr(png, options.verify_checksums)?;
+    let text_limit = options.max_output.min(MAX_TEXT_LEN);
+    let profile_limit = options.max_output.min(MAX_PROFILE_LEN);
+    let mut meta = PngMetadata::default();
+    for chunk in png_chunks(png)? {
+        let chunk = chunk?;
+        let data = chunk.data;
+        if chunk.r#type == IEND {
+            break;
+        }
+        if !chunk.is_ancillary() {
+            continue;
+        }
+        if options.verify_checksums && !chunk.crc_ok() {
+            warn(
+                options.warnings,
+                Warning::PngChunkCrc {
+                    chunk: chunk.r#type,
+                },
+            );
+            continue;
+        }
+
+        let valid = match chunk.r#type {
+            TEXT | ZTXT | ITXT => parse_text(&chunk, text_limit).map(|text| meta.texts.push(text)),
+            ICCP => parse_icc_profile(data, profile_limit).map(|profile| {
+                meta.icc_profile.get_or_insert(profile);
+            }),
+            GAMA => <[u8; 4]>::try_from(data)
+                .map(|gamma| {
+                    meta.gamma.get_or_insert(u32::from_be_bytes(gamma));
+                })
+                .map_err(|_| Skip::Invalid),
+            SRGB => (data.len() == 1 && data[0] <= 3)
+                .then(|| {
+                    meta.srgb.get_or_insert(data[0]);
+                })
+                .ok_or(Skip::Invalid),
+            PHYS => (data.len() == 9 && data[8] <= 1)
+                .then(|| {
+                    meta.physical_size.get_or_insert(PhysicalSize {
+                        x: u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+                        y: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+                        meter: data[8] == 1,
+                    });
+                })
+                .ok_or(Skip::Invalid),
+            BKGD => parse_background(data, ihdr.color_type)
+                .map(|background| {
+                    meta.background.get_or_insert(background);
+                })
+                .ok_or(Skip::Invalid),
+            TIME => parse_time(data)
+                .map(|time| {
+                    meta.modified.get_or_insert(time);
+                })
+                .ok_or(Skip::Invalid),
+            _ => Ok(()),
+        };
+        let chunk = chunk.r#type;
+        match valid {
+            Ok(()) => {}
+            Err(Skip::Invalid) => warn(options.warnings, Warning::PngChunkInvalid { chunk }),
+            Err(Skip::TooLarge) => warn(options.warnings, Warning::PngChunkTooLarge { chunk }),
+        }
+    }
+    Ok(meta)
+}
+
+// ----------------------------------------------------------------------------
+// Like `png_decode_rgba`, with the metadata needed to interpret the colors.
+pub fn png_decode_rgba_with_metadata(png: &[u8]) -> Result<RgbaImage> {
+    let (width, height, pixels) = png_decode_rgba(png)?;
+    let metadata = png_metadata(png)?;
+    Ok(RgbaImage {
+        width,
+        height,
+        pixels,
+        metadata,
+    })
+}
+
+// ----------------------------------------------------------------------------
+// Like `png_decode_rgba_with_metadata`, the color samples of images with a gAMA chunk, but neither
+// sRGB nor iCCP, are converted to sRGB. The metadata still describes the file.
+#[cfg(feature = "std")]
+pub fn png_decode_rgba_srgb(png: &[u8]) -> Result<RgbaImage> {
+    let mut image = png_decode_rgba_with_metadata(png)?;
+    let meta = &image.metadata;
+    if let (Some(gamma), None, None) = (meta.gamma, meta.srgb, &meta.icc_profile) {
+        gamma_to_srgb(&mut image.pixels, gamma);
+    }
+    Ok(image)
+}
+
+// ----------------------------------------------------------------------------
+// Converts the color samples of RGBA8 pixels encoded with the gAMA value `gamma` to the sRGB
+// transfer function, alpha stays linear. A gamma of 1.0 (100000) brightens linear data.
+#[cfg(feature = "std")]
+pub fn gamma_to_srgb(rgba: &mut [u8], gamma: u32) {
+    if gamma == 0 || gamma.abs_diff(SRGB_GAMMA) <= 100 {
+        return;
+    }
+    let decode = 100000.0 / gamma as f64;
+    let lut: [u8; 256] = core::array::from_fn(|i| {
+        let linear = (i as f64 / 255.0).powf(decode);
+        let srgb = if linear <= 0.0031308 {
+            12.92 * linear
+        } else {
+            1.055 * linear.powf(1.0 / 2.4) - 0.055
+        };
+        (srgb * 255.0).round() as u8
+    });
+    for pixel in rgba.chunks_exact_mut(4) {
+        for sample in &mut pixel[..3] {
+            *sample = lut[*sample as usize];
+        }
+    }
+}
@@ -1,53 +1,140 @@
 // PNG decompression implementation.
 // * https://www.w3.org/TR/png-3/
-use crate::inflate;
-use std::mem;
+use crate::buffer::{BufferProvider, Heap, Pool, Scratch};
+use crate::checksum;
+use crate::inflate::{self, InflateOptions};
+use crate::png_simd;
+use crate::warning::{warn, Warning};
+use alloc::{string::String, vec, vec::Vec};
 
 // ----------------------------------------------------------------------------
 #[derive(Debug, PartialEq)]
+#[non_exhaustive]
 pub enum Error {
     InvalidPng,
     InvalidSignature,
     InvalidFormat,
-    InvalidColorFormat,
+    // IHDR color type
+    InvalidColorFormat(u8),
     InvalidPalette,
-    InvalidFilterType,
+    // scanline filter type
+    InvalidFilterType(u8),
     UnsupportedFormat,
-    CompressionError,
+    // failure of the deflate stream, `Cancelled` and `ChecksumMismatch` have their own variants,
+    // no cause when rebuilt from the error code
+    CompressionError(Option<inflate::Error>),
     BufferError,
     BufferUnderrun,
     InvalidIDAT,
     MissingIHDR,
     MissingIEND,
+    Cancelled,
+    // CRC of a critical chunk or checksum of the IDAT stream
+    ChecksumMismatch,
+    // APNG control chunks missing, out of sequence or inconsistent with the image
+    InvalidAnimation,
+    // chunk type at a position the specification doesn't allow, e.g. PLTE after IDAT or IDAT
+    // chunks that are not consecutive
+    MisplacedChunk(u32),
+    // second instance of a chunk type that may appear only once
+    DuplicateChunk(u32),
+    // the IDAT stream decodes to more bytes than the scanlines of the image
+    ExcessImageData,
 }
 
 // ----------------------------------------------------------------------------
-impl std::fmt::Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let err = format!("{:?}", self);
-        f.write_str(&err)
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{self:?}")
     }
 }
 
 // ----------------------------------------------------------------------------
-impl std::error::Error for Error {}
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::CompressionError(Some(err)) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Stable numeric codes (200-299) for FFI and logging, codes are never reused or renumbered.
+impl Error {
+    pub fn code(&self) -> u32 {
+        use Error::*;
+        match self {
+            InvalidPng => 200,
+            InvalidSignature => 201,
+            InvalidFormat => 202,
+            InvalidColorFormat(_) => 203,
+            InvalidPalette => 204,
+            InvalidFilterType(_) => 205,
+            UnsupportedFormat => 206,
+            CompressionError(_) => 207,
+            BufferError => 208,
+            BufferUnderrun => 209,
+            InvalidIDAT => 210,
+            MissingIHDR => 211,
+            MissingIEND => 212,
+            Cancelled => 213,
+            ChecksumMismatch => 214,
+            InvalidAnimation => 215,
+            MisplacedChunk(_) => 216,
+            DuplicateChunk(_) => 217,
+            ExcessImageData => 218,
+        }
+    }
+
+    pub fn from_code(code: u32) -> Option<Self> {
+        use Error::*;
+        Some(match code {
+            200 => InvalidPng,
+            201 => InvalidSignature,
+            202 => InvalidFormat,
+            203 => InvalidColorFormat(0),
+            204 => InvalidPalette,
+            205 => InvalidFilterType(0),
+            206 => UnsupportedFormat,
+            207 => CompressionError(None),
+            208 => BufferError,
+            209 => BufferUnderrun,
+            210 => InvalidIDAT,
+            211 => MissingIHDR,
+            212 => MissingIEND,
+            213 => Cancelled,
+            214 => ChecksumMismatch,
+            215 => InvalidAnimation,
+            216 => MisplacedChunk(0),
+            217 => DuplicateChunk(0),
+            218 => ExcessImageData,
+            _ => return None,
+        })
+    }
+}
 
 // ----------------------------------------------------------------------------
 impl From<inflate::Error> for Error {
-    fn from(_: inflate::Error) -> Self {
-        Error::CompressionError
+    fn from(err: inflate::Error) -> Self {
+        match err {
+            inflate::Error::Cancelled => Error::Cancelled,
+            inflate::Error::ChecksumMismatch => Error::ChecksumMismatch,
+            err => Error::CompressionError(Some(err)),
+        }
     }
 }
 
 // ----------------------------------------------------------------------------
-impl From<std::array::TryFromSliceError> for Error {
-    fn from(_: std::array::TryFromSliceError) -> Self {
+impl From<core::array::TryFromSliceError> for Error {
+    fn from(_: core::array::TryFromSliceError) -> Self {
         Error::BufferError
     }
 }
 
 // ----------------------------------------------------------------------------
-pub type Result<T> = std::result::Result<T, Error>;
+pub type Result<T> = core::result::Result<T, Error>;
 
 // ----------------------------------------------------------------------------
 macro_rules! fourcc {
@@ -61,16 +148,11 @@ const IHDR: u32 = fourcc!('I', 'H', 'D', 'R');
 const IDAT: u32 = fourcc!('I', 'D', 'A', 'T');
 const IEND: u32 = fourcc!('I', 'E', 'N', 'D');
 const PLTE: u32 = fourcc!('P', 'L', 'T', 'E');
+const TRNS: u32 = fourcc!('t', 'R', 'N', 'S');
+const SBIT: u32 = fourcc!('s', 'B', 'I', 'T');
 
 // ----------------------------------------------------------------------------
-#[derive(Debug)]
-struct PNGChunkHead {
-    length: u32,
-    r#type: u32,
-}
-
-// ----------------------------------------------------------------------------
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum PNGColorType {
     Greyscale = 0,
     TrueColor = 2,
@@ -91,14 +173,14 @@ impl TryFrom<u8> for PNGColorType {
             3 => Ok(IndexedColor),
             4 => Ok(GreyscaleAplha),
             6 => Ok(TrueColorAlpha),
-            _ => Err(Error::InvalidColorFormat),
+            _ => Err(Error::InvalidColorFormat(value)),
         }
     }
 }
 
 // ----------------------------------------------------------------------------
 impl PNGColorType {
-    fn channels(&self) -> usize {
+    pub(crate) fn channels(&self) -> usize {
         use PNGColorType::*;
         match self {
             Greyscale | IndexedColor => 1,
@@ -107,10 +189,20 @@ impl PNGColorType {
             TrueColorAlpha => 4,
         }
     }
+
+    // Bit depths the specification allows for the color type (table 11.1).
+    pub fn bit_depths(&self) -> &'static [usize] {
+        use PNGColorType::*;
+        match self {
+            Greyscale => &[1, 2, 4, 8, 16],
+            IndexedColor => &[1, 2, 4, 8],
+            TrueColor | GreyscaleAplha | TrueColorAlpha => &[8, 16],
+        }
+    }
 }
 
 // ----------------------------------------------------------------------------
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct PNGChunkIHDR {
     pub width: usize,
     pub height: usize,
@@ -121,9 +213,29 @@ pub struct PNGChunkIHDR {
     pub interlace: u8,
 }
 
+// ----------------------------------------------------------------------------
+impl PNGChunkIHDR {
+    // Fails with `InvalidFormat` for headers the specification does not allow: an empty image,
+    // dimensions beyond 2^31 - 1, a bit depth not allowed for the color type or unknown
+    // compression, filter or interlace methods.
+    pub fn validate(&self) -> Result<()> {
+        const MAX_DIMENSION: usize = (1 << 31) - 1;
+        if !(1..=MAX_DIMENSION).contains(&self.width)
+            || !(1..=MAX_DIMENSION).contains(&self.height)
+            || !self.color_type.bit_depths().contains(&self.bit_depth)
+            || self.compression != 0
+            || self.filter != 0
+            || self.interlace > 1
+        {
+            return Err(Error::InvalidFormat);
+        }
+        Ok(())
+    }
+}
+
 // ----------------------------------------------------------------------------
 #[derive(Debug, Copy, Clone, PartialEq)]
-enum PNGFilterType {
+pub(crate) enum PNGFilterType {
     None = 0,
     Sub = 1,
     Up = 2,
@@ -142,13 +254,13 @@ impl TryFrom<u8> for PNGFilterType {
             2 => Ok(PNGFilterType::Up),
             3 => Ok(PNGFilterType::Average),
             4 => Ok(PNGFilterType::Paeth),
-            _ => Err(Error::InvalidFilterType),
+            _ => Err(Error::InvalidFilterType(value)),
         }
     }
 }
 
 // ----------------------------------------------------------------------------
-const fn paeth(a: u8, b: u8, c: u8) -> u8 {
+pub(crate) const fn paeth(a: u8, b: u8, c: u8) -> u8 {
     let pa = u8::abs_diff(b, c) as u32;
     let pb = u8::abs_diff(a, c) as u32;
     let pc = u32::abs_diff(a as u32 + b as u32, 2 * c as u32);
@@ -168,13 +280,16 @@ fn unfilter_scanline_0<const N: usize>(recon: &mut [u8], filter_type: PNGFilterT
         PNGFilterType::None | PNGFilterType::Up => (),
         PNGFilterType::Sub | PNGFilterType::Paeth => {
             // paeth(recon[i-1], 0, 0) is always recon[i-1]
+            if png_simd::unfilter(&mut recon[..cx], &[], PNGFilterType::Sub, N) {
+                return;
+            }
             for i in N..cx {
                 recon[i] = recon[i].wrapping_add(recon[i - N]);
             }
         }
         PNGFilterType::Average => {
             for i in N..cx {
-                recon[i] = recon[i - N] / 2;
+                recon[i] = recon[i].wrapping_add(recon[i - N] / 2);
             }
         }
     }
@@ -187,6 +302,9 @@ fn unfilter_scanline_n<const N: usize>(
     filter_type: PNGFilterType,
     cx: usize,
 ) {
+    if png_simd::unfilter(&mut recon[..cx], &precon[..cx], filter_type, N) {
+        return;
+    }
     match filter_type {
         PNGFilterType::None => (),
         PNGFilterType::Sub => {
@@ -223,165 +341,1263 @@ fn unfilter_scanline_n<const N: usize>(
 }
 
 // ----------------------------------------------------------------------------
-fn unfilter<const N: usize>(data: &mut [u8], line_bytes: usize, cy: usize) -> Result<()> {
-    let mut data = data;
+// unfilters the second of the two scanlines in `lines`
+fn unfilter_next<const N: usize>(lines: &mut [u8], line_bytes: usize) -> Result<()> {
+    let (prev, line) = lines.split_at_mut(line_bytes);
+    let filter_type = line[0].try_into()?;
+    unfilter_scanline_n::<N>(&mut line[1..], &prev[1..], filter_type, line_bytes - 1);
+    Ok(())
+}
 
+// ----------------------------------------------------------------------------
+fn unfilter<const N: usize>(data: &mut [u8], line_bytes: usize, cy: usize) -> Result<()> {
     let filter_type = data[0].try_into()?;
     unfilter_scanline_0::<N>(&mut data[1..], filter_type, line_bytes - 1);
 
-    for _ in 1..cy {
-        let (prev, line) = data.split_at_mut(line_bytes);
-        let filter_type = line[0].try_into()?;
-        unfilter_scanline_n::<N>(&mut line[1..], &prev[1..], filter_type, line_bytes - 1);
-
-        data = line;
+    for y in 1..cy {
+        unfilter_next::<N>(&mut data[(y - 1) * line_bytes..], line_bytes)?;
     }
 
     Ok(())
 }
 
 // ----------------------------------------------------------------------------
-fn decode_idat(
-    idat: Vec<u8>,
-    plte: Vec<u32>,
-    ihdr: PNGChunkIHDR,
-) -> Result<(PNGChunkIHDR, Vec<u32>, Vec<u8>)> {
-    // Check if fcheck is set correctly, compression method is inflate, sliding window is less than 32k,
-    // and no dictonary is used as per PNG spec
-    let check = ((idat[0] as usize) * 256 + (idat[1] as usize)) % 31;
-    let cm = idat[0] & 15;
-    let cinfo = (idat[0] >> 4) & 15;
-    let fdict = (idat[1] >> 5) & 1;
+pub(crate) fn unfilter_image(
+    data: &mut [u8],
+    color_type: PNGColorType,
+    bpl: usize,
+    cy: usize,
+) -> Result<()> {
+    match color_type {
+        PNGColorType::Greyscale | PNGColorType::IndexedColor => unfilter::<1>(data, bpl, cy),
+        PNGColorType::TrueColor => unfilter::<3>(data, bpl, cy),
+        PNGColorType::GreyscaleAplha => unfilter::<2>(data, bpl, cy),
+        PNGColorType::TrueColorAlpha => unfilter::<4>(data, bpl, cy),
+    }
+}
 
-    if check != 0 || cm != 8 || cinfo > 7 || fdict != 0 {
-        return Err(Error::InvalidIDAT);
+// ----------------------------------------------------------------------------
+// Unfilters the second scanline of `lines` against the already unfiltered first one.
+pub(crate) fn unfilter_scanline(
+    lines: &mut [u8],
+    color_type: PNGColorType,
+    bpl: usize,
+) -> Result<()> {
+    match color_type {
+        PNGColorType::Greyscale | PNGColorType::IndexedColor => unfilter_next::<1>(lines, bpl),
+        PNGColorType::TrueColor => unfilter_next::<3>(lines, bpl),
+        PNGColorType::GreyscaleAplha => unfilter_next::<2>(lines, bpl),
+        PNGColorType::TrueColorAlpha => unfilter_next::<4>(lines, bpl),
     }
+}
 
+// ----------------------------------------------------------------------------
+// bytes per scanline of `width` pixels including the filter byte
+pub(crate) fn line_bytes(ihdr: &PNGChunkIHDR, width: usize) -> Result<usize> {
     let bpp = ihdr.color_type.channels() * ihdr.bit_depth;
-    let bpl = ihdr.width.checked_mul(bpp).ok_or(Error::InvalidPng)?;
-    let bpl = bpl.div_ceil(8) + 1;
-    let size = ihdr.height.checked_mul(bpl).ok_or(Error::InvalidPng)?;
+    let bits = width.checked_mul(bpp).ok_or(Error::InvalidPng)?;
+    Ok(bits.div_ceil(8) + 1)
+}
+
+// ----------------------------------------------------------------------------
+// Adam7 passes: (x offset, y offset, x step, y step)
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+// ----------------------------------------------------------------------------
+// A completed pass of an interlaced image, reported to `Adam7Preview::callback`.
+#[derive(Debug)]
+pub struct Adam7Pass<'a> {
+    // 1 to 7
+    pub pass: usize,
+    // the pixels of this pass as a `width` x `height` image in the layout of `png_read`
+    pub width: usize,
+    pub height: usize,
+    pub data: &'a [u8],
+    // the full image with all passes so far, pixels of later passes are zero
+    pub image: &'a [u8],
+}
 
-    let mut data = vec![0u8; size];
+// ----------------------------------------------------------------------------
+// Progressive display of interlaced images: `callback` is invoked after each non-empty pass.
+#[derive(Clone, Copy)]
+pub struct Adam7Preview<'a> {
+    pub callback: &'a dyn Fn(&Adam7Pass),
+}
 
-    if inflate::inflate(&mut data, &idat[2..])? != size {
-        return Err(Error::InvalidPng);
+// ----------------------------------------------------------------------------
+impl core::fmt::Debug for Adam7Preview<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Adam7Preview").finish_non_exhaustive()
     }
+}
 
-    match ihdr.color_type {
-        PNGColorType::Greyscale | PNGColorType::IndexedColor => {
-            unfilter::<1>(&mut data, bpl, ihdr.height)?;
+// ----------------------------------------------------------------------------
+// Copies the pixels of an unfiltered pass image into their positions in the full image.
+fn scatter_pass(
+    image: &mut [u8],
+    ihdr: &PNGChunkIHDR,
+    bpl: usize,
+    pass: &[u8],
+    pass_bpl: usize,
+    (x0, y0, dx, dy): (usize, usize, usize, usize),
+) {
+    let bits = ihdr.color_type.channels() * ihdr.bit_depth;
+    for (py, row) in pass.chunks_exact(pass_bpl).enumerate() {
+        let row = &row[1..];
+        let dst = &mut image[(y0 + py * dy) * bpl + 1..][..bpl - 1];
+        let pass_width = row.len() * 8 / bits;
+        for px in 0..pass_width.min((ihdr.width - x0).div_ceil(dx)) {
+            let x = x0 + px * dx;
+            if bits >= 8 {
+                let n = bits / 8;
+                dst[x * n..(x + 1) * n].copy_from_slice(&row[px * n..(px + 1) * n]);
+            } else {
+                let value = sample(row, px, bits);
+                let bit = x * bits;
+                dst[bit >> 3] |= value << (8 - bits - (bit & 7));
+            }
         }
-        PNGColorType::TrueColor => {
-            unfilter::<3>(&mut data, bpl, ihdr.height)?;
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Decodes the passes of an interlaced image from `raw` into the non-interlaced `image`.
+pub(crate) fn deinterlace(
+    image: &mut [u8],
+    raw: &mut [u8],
+    ihdr: &PNGChunkIHDR,
+    bpl: usize,
+    options: &InflateOptions,
+) -> Result<()> {
+    image.fill(0);
+    let mut raw = raw;
+    for (i, &(x0, y0, dx, dy)) in ADAM7.iter().enumerate() {
+        let width = ihdr.width.saturating_sub(x0).div_ceil(dx);
+        let height = ihdr.height.saturating_sub(y0).div_ceil(dy);
+        if width == 0 || height == 0 {
+            // empty passes have no scanlines, not even filter bytes
+            continue;
         }
-        PNGColorType::GreyscaleAplha => {
-            unfilter::<2>(&mut data, bpl, ihdr.height)?;
+
+        let pass_bpl = line_bytes(ihdr, width)?;
+        let (pass, rest) = raw.split_at_mut(pass_bpl * height);
+        unfilter_image(pass, ihdr.color_type, pass_bpl, height)?;
+        for row in pass.chunks_exact_mut(pass_bpl) {
+            row[0] = 0;
         }
-        PNGColorType::TrueColorAlpha => {
-            unfilter::<4>(&mut data, bpl, ihdr.height)?;
+        scatter_pass(image, ihdr, bpl, pass, pass_bpl, (x0, y0, dx, dy));
+        trace_event!(trace, pass = i + 1, width, height, "png adam7 pass");
+
+        if let Some(preview) = options.adam7_preview {
+            (preview.callback)(&Adam7Pass {
+                pass: i + 1,
+                width,
+                height,
+                data: pass,
+                image,
+            });
         }
+        raw = rest;
     }
+    Ok(())
+}
 
-    Ok((ihdr, plte, data))
+// ----------------------------------------------------------------------------
+// size of the decompressed data of all Adam7 passes
+pub(crate) fn interlaced_size(ihdr: &PNGChunkIHDR) -> Result<usize> {
+    let mut size = 0usize;
+    for &(x0, y0, dx, dy) in &ADAM7 {
+        let width = ihdr.width.saturating_sub(x0).div_ceil(dx);
+        let height = ihdr.height.saturating_sub(y0).div_ceil(dy);
+        if width != 0 && height != 0 {
+            let pass = line_bytes(ihdr, width)?
+                .checked_mul(height)
+                .ok_or(Error::InvalidPng)?;
+            size = size.checked_add(pass).ok_or(Error::InvalidPng)?;
+        }
+    }
+    Ok(size)
 }
 
 // ----------------------------------------------------------------------------
-pub fn png_read(png: &[u8]) -> Result<(PNGChunkIHDR, Vec<u32>, Vec<u8>)> {
-    const SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
-    if png.len() < 8 || !png.starts_with(&SIGNATURE) {
-        return Err(Error::InvalidSignature);
+// Decodes the image data split over the payloads of the IDAT chunks in `idat`.
+pub(crate) fn decode_idat<'a, P: BufferProvider>(
+    idat: impl Iterator<Item = &'a [u8]> + Clone,
+    ihdr: &PNGChunkIHDR,
+    provider: &mut P,
+    options: &InflateOptions,
+) -> Result<P::Buffer> {
+    // zlib header with at most 32k window and no preset dictionary as per PNG spec
+    let mut header = [0u8; 2];
+    let len = idat
+        .clone()
+        .flatten()
+        .zip(&mut header)
+        .map(|(b, h)| *h = *b)
+        .count();
+    inflate::zlib_header(&header[..len]).map_err(|_| Error::InvalidIDAT)?;
+    let inflate = |out: &mut [u8]| {
+        let result = inflate::inflate_zlib_segments(out, &mut idat.clone(), options);
+        result.map_err(|err| match err {
+            // the stream goes on after the last scanline
+            inflate::Error::Overflow => Error::ExcessImageData,
+            err => err.into(),
+        })
+    };
+
+    let bpl = line_bytes(ihdr, ihdr.width)?;
+    let size = decoded_size(ihdr)?;
+
+    let mut buffer = provider.alloc(size).ok_or(Error::BufferError)?;
+    let data = buffer.as_mut();
+
+    if ihdr.interlace == 0 {
+        if inflate(data)? != size {
+            return Err(Error::InvalidPng);
+        }
+        unfilter_image(data, ihdr.color_type, bpl, ihdr.height)?;
+    } else {
+        // the passes are decoded into a separate buffer and spread out into the image
+        let raw_size = interlaced_size(ihdr)?;
+        let mut raw = provider.scratch(raw_size).ok_or(Error::BufferError)?;
+        let decoded = match inflate(raw.as_mut()) {
+            Ok(len) if len == raw_size => deinterlace(data, raw.as_mut(), ihdr, bpl, options),
+            Ok(_) => Err(Error::InvalidPng),
+            Err(err) => Err(err),
+        };
+        provider.release(raw);
+        decoded?;
+    }
+
+    Ok(buffer)
+}
+
+// ----------------------------------------------------------------------------
+#[derive(Debug)]
+pub struct PNGChunk<'a> {
+    pub r#type: u32,
+    pub data: &'a [u8],
+    pub crc: u32,
+}
+
+// ----------------------------------------------------------------------------
+impl PNGChunk<'_> {
+    // ancillary chunks (lowercase first letter) are not needed to display the image
+    pub fn is_ancillary(&self) -> bool {
+        self.r#type & 0x2000_0000 != 0
+    }
+
+    // CRC over chunk type and data
+    pub fn crc_ok(&self) -> bool {
+        let crc = checksum::crc32(0, &self.r#type.to_be_bytes());
+        checksum::crc32(crc, self.data) == self.crc
+    }
+
+    pub fn name(&self) -> String {
+        String::from_utf8_lossy(&self.r#type.to_be_bytes()).into_owned()
+    }
+}
+
+// ----------------------------------------------------------------------------
+fn read_chunk(png: &[u8]) -> Result<(PNGChunk<'_>, &[u8])> {
+    if png.len() < 8 {
+        return Err(Error::BufferUnderrun);
     }
 
-    let mut png = &png[8..png.len()];
+    let length = u32::from_be_bytes(png[0..4].try_into()?) as usize;
+    let r#type = u32::from_be_bytes(png[4..8].try_into()?);
 
-    if png.len() < mem::size_of::<PNGChunkHead>() {
+    // chunk head, data and CRC
+    let end = length.checked_add(12).ok_or(Error::BufferUnderrun)?;
+    if png.len() < end {
         return Err(Error::BufferUnderrun);
     }
 
-    let head = PNGChunkHead {
-        length: u32::from_be_bytes(png[0..4].try_into()?),
-        r#type: u32::from_be_bytes(png[4..8].try_into()?),
+    let chunk = PNGChunk {
+        r#type,
+        data: &png[8..end - 4],
+        crc: u32::from_be_bytes(png[end - 4..end].try_into()?),
     };
+    Ok((chunk, &png[end..]))
+}
+
+// ----------------------------------------------------------------------------
+#[derive(Clone)]
+pub struct PNGChunks<'a> {
+    png: &'a [u8],
+}
+
+// ----------------------------------------------------------------------------
+impl<'a> Iterator for PNGChunks<'a> {
+    type Item = Result<PNGChunk<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.png.is_empty() {
+            return None;
+        }
+
+        match read_chunk(self.png) {
+            Ok((chunk, rest)) => {
+                self.png = rest;
+                Some(Ok(chunk))
+            }
+            Err(err) => {
+                self.png = &[];
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Payloads of the IDAT chunks in `png`, chunks already read by `read_png`.
+fn idat_chunks(png: &[u8]) -> impl Iterator<Item = &[u8]> + Clone {
+    PNGChunks { png }
+        .map_while(|chunk| chunk.ok())
+        .filter(|chunk| chunk.r#type == IDAT)
+        .map(|chunk| chunk.data)
+}
+
+// ----------------------------------------------------------------------------
+const SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+// ----------------------------------------------------------------------------
+pub fn png_chunks(png: &[u8]) -> Result<PNGChunks<'_>> {
+    if png.len() < 8 || !png.starts_with(&SIGNATURE) {
+        return Err(Error::InvalidSignature);
+    }
+    Ok(PNGChunks { png: &png[8..] })
+}
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Placement {
+    BeforePlte,
+    AfterPlte,
+    BeforeIdat,
+    Anywhere,
+}
+
+// ----------------------------------------------------------------------------
+// Chunks that appear at most once and where they go, section 5.6 of the specification. The
+// chunks after PLTE precede IDAT as well.
+const SINGLE_CHUNKS: [([u8; 4], Placement); 17] = [
+    (*b"IHDR", Placement::Anywhere),
+    (*b"PLTE", Placement::BeforeIdat),
+    (*b"cHRM", Placement::BeforePlte),
+    (*b"gAMA", Placement::BeforePlte),
+    (*b"iCCP", Placement::BeforePlte),
+    (*b"sBIT", Placement::BeforePlte),
+    (*b"sRGB", Placement::BeforePlte),
+    (*b"cICP", Placement::BeforePlte),
+    (*b"mDCV", Placement::BeforePlte),
+    (*b"cLLI", Placement::BeforePlte),
+    (*b"bKGD", Placement::AfterPlte),
+    (*b"hIST", Placement::AfterPlte),
+    (*b"tRNS", Placement::AfterPlte),
+    (*b"pHYs", Placement::BeforeIdat),
+    (*b"acTL", Placement::BeforeIdat),
+    (*b"eXIf", Placement::Anywhere),
+    (*b"tIME", Placement::Anywhere),
+];
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum IdatRun {
+    Before,
+    Inside,
+    After,
+}
+
+// ----------------------------------------------------------------------------
+// Checks the chunk types following IHDR in file order against the ordering rules: PLTE before
+// IDAT, consecutive IDATs and single instances of IHDR, PLTE and the ancillary chunks above.
+// IEND ends the chunk sequence, data after it is not checked.
+#[derive(Debug, Clone)]
+pub(crate) struct ChunkOrder {
+    // bits of the `SINGLE_CHUNKS` seen so far
+    seen: u32,
+    idat: IdatRun,
+}
+
+// ----------------------------------------------------------------------------
+impl ChunkOrder {
+    pub(crate) fn new() -> Self {
+        ChunkOrder {
+            seen: 1,
+            idat: IdatRun::Before,
+        }
+    }
+
+    fn seen(&self, chunk: &[u8; 4]) -> bool {
+        let index = SINGLE_CHUNKS.iter().position(|(name, _)| name == chunk);
+        index.is_some_and(|index| self.seen & (1 << index) != 0)
+    }
+
+    // The chunk is recorded even if it breaks a rule, lenient readers go on with the next one.
+    pub(crate) fn check(&mut self, chunk: u32) -> Result<()> {
+        if chunk == IDAT {
+            let misplaced = self.idat == IdatRun::After;
+            self.idat = IdatRun::Inside;
+            return if misplaced {
+                Err(Error::MisplacedChunk(chunk))
+            } else {
+                Ok(())
+            };
+        }
+        if self.idat == IdatRun::Inside {
+            self.idat = IdatRun::After;
+        }
+
+        let name = chunk.to_be_bytes();
+        let Some(index) = SINGLE_CHUNKS.iter().position(|(single, _)| *single == name) else {
+            return Ok(());
+        };
+        let before_idat = self.idat == IdatRun::Before;
+        let misplaced = match SINGLE_CHUNKS[index].1 {
+            Placement::BeforePlte => !before_idat || self.seen(b"PLTE"),
+            Placement::AfterPlte | Placement::BeforeIdat => !before_idat,
+            Placement::Anywhere => false,
+        };
+        // the chunks that follow PLTE must not precede it
+        let mut after_plte = SINGLE_CHUNKS
+            .iter()
+            .filter(|(_, p)| *p == Placement::AfterPlte);
+        let misplaced = misplaced || (chunk == PLTE && after_plte.any(|(name, _)| self.seen(name)));
+
+        let duplicate = self.seen & (1 << index) != 0;
+        self.seen |= 1 << index;
+        if duplicate {
+            Err(Error::DuplicateChunk(chunk))
+        } else if misplaced {
+            Err(Error::MisplacedChunk(chunk))
+        } else {
+            Ok(())
+        }
+    }
+}
 
-    png = &png[8..png.len()];
+// ----------------------------------------------------------------------------
+pub(crate) fn read_ihdr(png: &[u8], verify_crc: bool) -> Result<(PNGChunkIHDR, &[u8])> {
+    let chunks = png_chunks(png)?;
+    let (chunk, png) = read_chunk(chunks.png)?;
 
-    if head.r#type != IHDR {
+    if chunk.r#type != IHDR {
         return Err(Error::MissingIHDR);
     }
+    if verify_crc && !chunk.crc_ok() {
+        return Err(Error::ChecksumMismatch);
+    }
+    Ok((parse_ihdr(chunk.data)?, png))
+}
 
+// ----------------------------------------------------------------------------
+pub(crate) fn parse_ihdr(data: &[u8]) -> Result<PNGChunkIHDR> {
     const IHDR_LEN: usize = 13;
-    if head.length as usize != IHDR_LEN || png.len() < IHDR_LEN {
+    if data.len() != IHDR_LEN {
         return Err(Error::BufferUnderrun);
     }
 
     let ihdr = PNGChunkIHDR {
-        width: u32::from_be_bytes(png[0..4].try_into()?) as usize,
-        height: u32::from_be_bytes(png[4..8].try_into()?) as usize,
-        bit_depth: png[8] as usize,
-        color_type: png[9].try_into()?,
-        compression: png[10],
-        filter: png[11],
-        interlace: png[12],
+        width: u32::from_be_bytes(data[0..4].try_into()?) as usize,
+        height: u32::from_be_bytes(data[4..8].try_into()?) as usize,
+        bit_depth: data[8] as usize,
+        color_type: data[9].try_into()?,
+        compression: data[10],
+        filter: data[11],
+        interlace: data[12],
     };
 
-    png = &png[IHDR_LEN + 4..png.len()];
+    ihdr.validate()?;
+    Ok(ihdr)
+}
 
-    if ihdr.width == 0
-        || ihdr.height == 0
-        || ihdr.bit_depth == 0
-        || ihdr.compression != 0
-        || ihdr.filter != 0
-        || ihdr.interlace > 1
-    {
-        return Err(Error::InvalidFormat);
+// ----------------------------------------------------------------------------
+// palette entries as `b << 16 | g << 8 | r`
+pub(crate) fn read_plte(data: &[u8], plte: &mut Vec<u32>) -> Result<()> {
+    if !data.len().is_multiple_of(3) || data.len() > 256 * 3 {
+        return Err(Error::InvalidPalette);
     }
+    let entries = data.chunks_exact(3).map(|rgb| {
+        let r = rgb[2] as u32;
+        let g = rgb[1] as u32;
+        let b = rgb[0] as u32;
+        (r << 16) | (g << 8) | b
+    });
+    plte.clear();
+    plte.extend(entries);
+    Ok(())
+}
 
-    if ihdr.interlace != 0 || ihdr.bit_depth > 8 {
-        // Adam7 interlace is not supported
-        return Err(Error::UnsupportedFormat);
+// ----------------------------------------------------------------------------
+// Size of the decoded image: `height` scanlines with a leading filter byte each.
+pub fn decoded_size(ihdr: &PNGChunkIHDR) -> Result<usize> {
+    let bpl = line_bytes(ihdr, ihdr.width)?;
+    ihdr.height.checked_mul(bpl).ok_or(Error::InvalidPng)
+}
+
+// ----------------------------------------------------------------------------
+// Temporary memory the decoder takes from its `BufferProvider` besides the image: the Adam7
+// passes of interlaced images.
+pub fn scratch_size(ihdr: &PNGChunkIHDR) -> Result<usize> {
+    if ihdr.interlace == 0 {
+        Ok(0)
+    } else {
+        interlaced_size(ihdr)
     }
+}
+
+// ----------------------------------------------------------------------------
+// Reads the image header only, e.g. to size the output buffer of `png_read_into`.
+pub fn png_info(png: &[u8]) -> Result<PNGChunkIHDR> {
+    Ok(read_ihdr(png, true)?.0)
+}
+
+// ----------------------------------------------------------------------------
+pub fn png_read(png: &[u8]) -> Result<(PNGChunkIHDR, Vec<u32>, Vec<u8>)> {
+    png_read_with(png, &mut Heap)
+}
 
-    let mut idat = Vec::with_capacity(png.len());
+// ----------------------------------------------------------------------------
+// Like `png_read`, but the image buffer is obtained from `provider`.
+pub fn png_read_with<P: BufferProvider>(
+    png: &[u8],
+    provider: &mut P,
+) -> Result<(PNGChunkIHDR, Vec<u32>, P::Buffer)> {
+    png_read_with_options(png, provider, &InflateOptions::default())
+}
+
+// ----------------------------------------------------------------------------
+// Like `png_read_with`, with `options` applied to the decompression of the IDAT stream.
+pub fn png_read_with_options<P: BufferProvider>(
+    png: &[u8],
+    provider: &mut P,
+    options: &InflateOptions,
+) -> Result<(PNGChunkIHDR, Vec<u32>, P::Buffer)> {
     let mut plte = Vec::new();
+    let (ihdr, buffer) = read_png(png, provider, options, &mut plte)?;
+    Ok((ihdr, plte, buffer))
+}
 
-    while !png.is_empty() {
-        let head = PNGChunkHead {
-            length: u32::from_be_bytes(png[0..4].try_into()?),
-            r#type: u32::from_be_bytes(png[4..8].try_into()?),
+// ----------------------------------------------------------------------------
+// Scratch memory of `png_read_into`: the undistributed passes of interlaced images and the
+// palette. Decoding images no larger than the previous ones doesn't
+// allocate.
+#[derive(Debug)]
+pub struct PngScratch {
+    pool: Pool,
+    plte: Vec<u32>,
+}
+
+// ----------------------------------------------------------------------------
+impl Default for PngScratch {
+    fn default() -> Self {
+        PngScratch {
+            pool: Pool::new(1),
+            plte: Vec::with_capacity(256),
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+impl PngScratch {
+    // palette of the last image decoded with `png_read_into`
+    pub fn palette(&self) -> &[u32] {
+        &self.plte
+    }
+
+    // number of scratch buffers served without allocating
+    pub fn reused(&self) -> usize {
+        self.pool.reused()
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Hands out the caller's output buffer and the scratch buffers of a `PngScratch`.
+struct IntoBuffer<'a, 'b> {
+    out: Option<&'a mut [u8]>,
+    pool: &'b mut Pool,
+}
+
+// ----------------------------------------------------------------------------
+impl<'a> BufferProvider for IntoBuffer<'a, '_> {
+    type Buffer = &'a mut [u8];
+
+    fn alloc(&mut self, len: usize) -> Option<&'a mut [u8]> {
+        let out = self.out.take()?;
+        out.get_mut(..len)
+    }
+
+    fn scratch(&mut self, len: usize) -> Option<Scratch<&'a mut [u8]>> {
+        self.pool.alloc(len).map(Scratch::Heap)
+    }
+
+    fn release(&mut self, buffer: Scratch<&'a mut [u8]>) {
+        if let Scratch::Heap(buffer) = buffer {
+            self.pool.recycle(buffer);
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Decodes into `out` of at least `decoded_size` bytes, returns the decoded size. An output
+// buffer that is too small fails with `Error::BufferError`.
+pub fn png_read_into(png: &[u8], out: &mut [u8], scratch: &mut PngScratch) -> Result<usize> {
+    let mut provider = IntoBuffer {
+        out: Some(out),
+        pool: &mut scratch.pool,
+    };
+    let options = InflateOptions::default();
+    let (_, buffer) = read_png(png, &mut provider, &options, &mut scratch.plte)?;
+    Ok(buffer.len())
+}
+
+// ----------------------------------------------------------------------------
+// Calls `on_row` with the row index and the unfiltered samples of each scanline, without the
+// filter byte, instead of keeping the whole image. Besides the decompressor state this takes two
+// scanlines for non-interlaced images, all Adam7 passes for interlaced ones. Returns the header
+// and the palette.
+pub fn png_read_rows<F: FnMut(usize, &[u8])>(
+    png: &[u8],
+    on_row: F,
+) -> Result<(PNGChunkIHDR, Vec<u32>)> {
+    let mut decoder = crate::png_stream::PngDecoder::new();
+    decoder.feed(png, on_row)?;
+    if !decoder.is_finished() {
+        return Err(Error::MissingIEND);
+    }
+    let ihdr = decoder.header().copied().ok_or(Error::MissingIHDR)?;
+    Ok((ihdr, decoder.palette().to_vec()))
+}
+
+// ----------------------------------------------------------------------------
+// Appends `len` bits of `row` starting at bit `start`, moved to the first bit of a new byte.
+fn crop_bits(row: &[u8], start: usize, len: usize, out: &mut Vec<u8>) {
+    if start.is_multiple_of(8) && len.is_multiple_of(8) {
+        out.extend_from_slice(&row[start / 8..(start + len) / 8]);
+        return;
+    }
+    let first = out.len();
+    out.resize(first + len.div_ceil(8), 0);
+    for i in 0..len {
+        let bit = start + i;
+        let value = (row[bit >> 3] >> (7 - (bit & 7))) & 1;
+        out[first + (i >> 3)] |= value << (7 - (i & 7));
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Decodes the `width` x `height` pixels at (`x`, `y`), e.g. a tile of a large image. Scanlines
+// are still decompressed and unfiltered from the top, but rows outside the region are not kept
+// and decoding stops after its last row, the CRCs of the chunks after it are not checked.
+// Returns the header of the region, non-interlaced, the palette and the region's scanlines in
+// the layout of `png_read`, with filter bytes of 0. A region beyond the image fails with
+// `Error::BufferError`.
+pub fn png_read_region(
+    png: &[u8],
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+) -> Result<(PNGChunkIHDR, Vec<u32>, Vec<u8>)> {
+    let ihdr = png_info(png)?;
+    let fits = |start: usize, len: usize, size: usize| {
+        len > 0 && start.checked_add(len).is_some_and(|end| end <= size)
+    };
+    if !fits(x, width, ihdr.width) || !fits(y, height, ihdr.height) {
+        return Err(Error::BufferError);
+    }
+
+    let region = PNGChunkIHDR {
+        width,
+        height,
+        interlace: 0,
+        ..ihdr
+    };
+    let bits = ihdr.color_type.channels() * ihdr.bit_depth;
+    let mut data = Vec::with_capacity(decoded_size(&region)?);
+    let mut decoder = crate::png_stream::PngDecoder::new();
+    let rows = y..y + height;
+
+    // fed in pieces to stop once the region is complete
+    for part in png.chunks(16 * 1024) {
+        decoder.feed(part, |index, row| {
+            if rows.contains(&index) {
+                data.push(0);
+                crop_bits(row, x * bits, width * bits, &mut data);
+            }
+        })?;
+        if decoder.rows_decoded() >= rows.end {
+            return Ok((region, decoder.palette().to_vec(), data));
+        }
+    }
+    Err(Error::MissingIEND)
+}
+
+// ----------------------------------------------------------------------------
+// scanlines per message from the decompressing thread, about 64 KB
+#[cfg(feature = "threads")]
+const PARALLEL_BATCH: usize = 64 * 1024;
+
+// ----------------------------------------------------------------------------
+// Like `png_read`, with the decompression on a second thread: the scanlines are passed back in
+// batches through a bounded channel and unfiltered while the next ones are decompressed.
+// Interlaced images are decoded by `png_read`, their passes only complete at the end of the
+// stream.
+#[cfg(feature = "threads")]
+pub fn png_read_parallel(png: &[u8]) -> Result<(PNGChunkIHDR, Vec<u32>, Vec<u8>)> {
+    let options = InflateOptions::default();
+    let mut plte = Vec::new();
+    let (ihdr, idat) = read_chunks(png, &options, &mut plte)?;
+    if ihdr.interlace != 0 {
+        let data = decode_idat(idat_chunks(idat), &ihdr, &mut Heap, &options)?;
+        return Ok((ihdr, plte, data));
+    }
+
+    let bpl = line_bytes(&ihdr, ihdr.width)?;
+    let size = decoded_size(&ihdr)?;
+    let batch = (PARALLEL_BATCH / bpl).max(1) * bpl;
+    let mut data = vec![0; size];
+
+    let (sender, receiver) = std::sync::mpsc::sync_channel(4);
+    std::thread::scope(|scope| {
+        let inflater = scope.spawn(move || inflate_rows(idat, size, batch, sender));
+        let mut filled = 0;
+        let unfiltered = receiver.iter().try_for_each(|rows: Vec<u8>| {
+            let first = filled / bpl;
+            data[filled..filled + rows.len()].copy_from_slice(&rows);
+            filled += rows.len();
+            unfilter_rows(&mut data[..filled], ihdr.color_type, bpl, first)
+        });
+        // stops the inflater at its next batch if unfiltering failed
+        drop(receiver);
+        let inflated = match inflater.join() {
+            Ok(result) => result,
+            Err(panic) => std::panic::resume_unwind(panic),
         };
+        unfiltered.and(inflated)
+    })?;
+    Ok((ihdr, plte, data))
+}
 
-        png = &png[8..png.len()];
+// ----------------------------------------------------------------------------
+// Decompresses the IDAT payloads in `idat` and sends the `size` bytes of scanlines in pieces of
+// `batch` bytes.
+#[cfg(feature = "threads")]
+fn inflate_rows(
+    idat: &[u8],
+    size: usize,
+    batch: usize,
+    sender: std::sync::mpsc::SyncSender<Vec<u8>>,
+) -> Result<()> {
+    let mut chunks = idat_chunks(idat);
+    let mut header = [0u8; 2];
+    let len = chunks
+        .clone()
+        .flatten()
+        .zip(&mut header)
+        .map(|(b, h)| *h = *b)
+        .count();
+    inflate::zlib_header(&header[..len]).map_err(|_| Error::InvalidIDAT)?;
+
+    let mut inflater = crate::stream::Inflater::new(true);
+    let mut input: &[u8] = &[];
+    let mut rows = vec![0; batch.min(size)];
+    let (mut filled, mut total) = (0, 0);
+    loop {
+        // any data after the last scanline is an error
+        let mut excess = [0u8; 1];
+        let output = if total == size {
+            &mut excess[..]
+        } else {
+            &mut rows[filled..]
+        };
+        let (_, consumed, produced) =
+            inflater
+                .decompress(input, output)
+                .map_err(|err| match err {
+                    crate::Error::Inflate(err) => err.into(),
+                    crate::Error::Png(err) => err,
+                    _ => Error::CompressionError(None),
+                })?;
+        if total == size && produced > 0 {
+            return Err(Error::ExcessImageData);
+        }
+        input = &input[consumed..];
+        filled += produced;
+        total += produced;
+        if filled > 0 && filled == rows.len() {
+            let next = vec![0; batch.min(size - total)];
+            if sender.send(core::mem::replace(&mut rows, next)).is_err() {
+                // the receiving side failed and reports its error
+                return Ok(());
+            }
+            filled = 0;
+        }
+        if consumed == 0 && produced == 0 {
+            match chunks.next() {
+                Some(next) if input.is_empty() => input = next,
+                _ => break,
+            }
+        }
+    }
 
-        match head.r#type {
+    if filled > 0 {
+        rows.truncate(filled);
+        let _ = sender.send(rows);
+    }
+    if !inflater.is_finished() {
+        return Err(Error::CompressionError(Some(inflate::Error::Underflow)));
+    }
+    if total != size {
+        return Err(Error::InvalidPng);
+    }
+    Ok(())
+}
+
+// ----------------------------------------------------------------------------
+// Unfilters the complete scanlines in `data` from row `first` on, the rows before are done.
+#[cfg(feature = "threads")]
+fn unfilter_rows(
+    data: &mut [u8],
+    color_type: PNGColorType,
+    bpl: usize,
+    first: usize,
+) -> Result<()> {
+    for y in first..data.len() / bpl {
+        if y == 0 {
+            unfilter_image(&mut data[..bpl], color_type, bpl, 1)?;
+        } else {
+            unfilter_scanline(&mut data[(y - 1) * bpl..(y + 1) * bpl], color_type, bpl)?;
+        }
+    }
+    Ok(())
+}
+
+// ----------------------------------------------------------------------------
+fn read_png<P: BufferProvider>(
+    png: &[u8],
+    provider: &mut P,
+    options: &InflateOptions,
+    plte: &mut Vec<u32>,
+) -> Result<(PNGChunkIHDR, P::Buffer)> {
+    trace_span!("png_read", len = png.len());
+    let (ihdr, idat) = read_chunks(png, options, plte)?;
+    let buffer = decode_idat(idat_chunks(idat), &ihdr, provider, options)?;
+    Ok((ihdr, buffer))
+}
+
+// ----------------------------------------------------------------------------
+// Reads the chunks up to IEND, returns the header and the chunks from the first IDAT on, to be
+// passed to `idat_chunks`. The palette is read into `plte`.
+fn read_chunks<'a>(
+    png: &'a [u8],
+    options: &InflateOptions,
+    plte: &mut Vec<u32>,
+) -> Result<(PNGChunkIHDR, &'a [u8])> {
+    let (ihdr, mut png) = read_ihdr(png, options.verify_checksums)?;
+    trace_event!(
+        debug,
+        width = ihdr.width,
+        height = ihdr.height,
+        bit_depth = ihdr.bit_depth,
+        color_type = ?ihdr.color_type,
+        interlace = ihdr.interlace,
+        "png header"
+    );
+
+    if ihdr.bit_depth > 8 {
+        return Err(Error::UnsupportedFormat);
+    }
+
+    // the IDAT chunks are collected on the way to IEND without copying their positions
+    let mut idat: Option<&[u8]> = None;
+    let mut order = ChunkOrder::new();
+    plte.clear();
+
+    while !png.is_empty() {
+        let (chunk, rest) = read_chunk(png)?;
+        trace_event!(trace, chunk = %chunk.name(), len = chunk.data.len(), "png chunk");
+        let checked = options.strict_containers || !chunk.is_ancillary();
+        if options.verify_checksums && checked && !chunk.crc_ok() {
+            return Err(Error::ChecksumMismatch);
+        }
+        if let Err(err) = order.check(chunk.r#type) {
+            if !options.lenient_chunk_order {
+                return Err(err);
+            }
+            trace_event!(warn, chunk = %chunk.name(), "png chunk out of order");
+            let chunk = chunk.r#type;
+            warn(options.warnings, Warning::PngChunkMisplaced { chunk });
+        }
+
+        match chunk.r#type {
             IDAT => {
-                idat.extend_from_slice(&png[0..head.length as usize]);
+                idat.get_or_insert(png);
             }
             IEND => {
-                return decode_idat(idat, plte, ihdr);
+                if !rest.is_empty() {
+                    if options.strict_containers {
+                        return Err(Error::InvalidPng);
+                    }
+                    trace_event!(warn, trailing = rest.len(), "data after IEND ignored");
+                    let len = rest.len();
+                    warn(options.warnings, Warning::PngDataAfterIend { len });
+                }
+                let idat = idat.map_or(&[][..], |idat| &idat[..idat.len() - png.len()]);
+                return Ok((ihdr, idat));
             }
             PLTE => {
-                if !head.length.is_multiple_of(3) || head.length > 256 * 3 {
-                    return Err(Error::InvalidPalette);
-                }
-                for i in (0..head.length as usize).step_by(3) {
-                    let r = png[i + 2] as u32;
-                    let g = png[i + 1] as u32;
-                    let b = png[i] as u32;
-                    plte.push((r << 16) | (g << 8) | b);
-                }
+                read_plte(chunk.data, plte)?;
             }
             _ => {
                 // Skip other chunks
+                trace_event!(debug, chunk = %chunk.name(), "png chunk skipped");
+                let chunk_type = chunk.r#type;
+                if options.warnings.is_some() && chunk.is_ancillary() && !chunk.crc_ok() {
+                    warn(options.warnings, Warning::PngChunkCrc { chunk: chunk_type });
+                } else {
+                    warn(
+                        options.warnings,
+                        Warning::PngChunkSkipped { chunk: chunk_type },
+                    );
+                }
             }
         }
 
-        png = &png[head.length as usize + 4..png.len()];
+        png = rest;
     }
 
     Err(Error::MissingIEND)
 }
 
+// ----------------------------------------------------------------------------
+fn sample(row: &[u8], index: usize, bit_depth: usize) -> u8 {
+    if bit_depth == 8 {
+        return row[index];
+    }
+    let bit = index * bit_depth;
+    let shift = 8 - bit_depth - (bit & 7);
+    (row[bit >> 3] >> shift) & ((1 << bit_depth) - 1)
+}
+
+// ----------------------------------------------------------------------------
+// Contents of the tRNS chunk.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Transparency {
+    // alpha of the first palette entries, the remaining entries are opaque
+    Palette(Vec<u8>),
+    // greyscale sample value at the image bit depth that is fully transparent
+    Grey(u16),
+    // truecolor sample values that are fully transparent
+    Rgb(u16, u16, u16),
+}
+
+// ----------------------------------------------------------------------------
+// Reads the tRNS chunk before the image data, `None` if there is none or the color type has an
+// alpha channel already.
+pub fn png_transparency(png: &[u8]) -> Result<Option<Transparency>> {
+    let (ihdr, _) = read_ihdr(png, false)?;
+    for chunk in png_chunks(png)? {
+        let chunk = chunk?;
+        match chunk.r#type {
+            IDAT | IEND => break,
+            TRNS => {
+                let data = chunk.data;
+                let sample = |i: usize| u16::from_be_bytes([data[2 * i], data[2 * i + 1]]);
+                return match (ihdr.color_type, data.len()) {
+                    (PNGColorType::IndexedColor, len) if len <= 256 => {
+                        Ok(Some(Transparency::Palette(data.to_vec())))
+                    }
+                    (PNGColorType::Greyscale, 2) => Ok(Some(Transparency::Grey(sample(0)))),
+                    (PNGColorType::TrueColor, 6) => {
+                        Ok(Some(Transparency::Rgb(sample(0), sample(1), sample(2))))
+                    }
+                    (PNGColorType::GreyscaleAplha | PNGColorType::TrueColorAlpha, _) => Ok(None),
+                    _ => Err(Error::InvalidFormat),
+                };
+            }
+            _ => {}
+        }
+    }
+    Ok(None)
+}
+
+// ----------------------------------------------------------------------------
+// Reads the sBIT chunk if present: the number of significant bits of the (red, green, blue,
+// alpha) samples, greyscale images repeat the grey value for red, green and blue. Channels the
+// color type lacks hold the sample depth, 8 for indexed images whose sBIT describes the palette.
+pub fn png_significant_bits(png: &[u8]) -> Result<Option<[u8; 4]>> {
+    let (ihdr, _) = read_ihdr(png, false)?;
+    let depth = match ihdr.color_type {
+        PNGColorType::IndexedColor => 8,
+        _ => ihdr.bit_depth as u8,
+    };
+    for chunk in png_chunks(png)? {
+        let chunk = chunk?;
+        match chunk.r#type {
+            IDAT | IEND => break,
+            SBIT => {
+                let data = chunk.data;
+                if data.iter().any(|&bits| bits == 0 || bits > depth) {
+                    return Err(Error::InvalidFormat);
+                }
+                return match (ihdr.color_type, data) {
+                    (PNGColorType::Greyscale, &[g]) => Ok(Some([g, g, g, depth])),
+                    (PNGColorType::GreyscaleAplha, &[g, a]) => Ok(Some([g, g, g, a])),
+                    (PNGColorType::TrueColor | PNGColorType::IndexedColor, &[r, g, b]) => {
+                        Ok(Some([r, g, b, depth]))
+                    }
+                    (PNGColorType::TrueColorAlpha, &[r, g, b, a]) => Ok(Some([r, g, b, a])),
+                    _ => Err(Error::InvalidFormat),
+                };
+            }
+            _ => {}
+        }
+    }
+    Ok(None)
+}
+
+// ----------------------------------------------------------------------------
+// Decodes a PNG file of any color type at 1, 2, 4 or 8 bits into (width, height, RGBA8 pixels),
+// palettes and tRNS transparency are applied and samples scaled to the full range from their
+// significant bits given by sBIT, or from the bit depth without it.
+pub fn png_decode_rgba(png: &[u8]) -> Result<(usize, usize, Vec<u8>)> {
+    let (ihdr, plte, data) = png_read(png)?;
+    let trns = png_transparency(png)?;
+    let sbit = png_significant_bits(png)?;
+    let rgba = to_rgba_with_significant_bits(&ihdr, &plte, trns.as_ref(), sbit, &data)?;
+    Ok((ihdr.width, ihdr.height, rgba))
+}
+
+// ----------------------------------------------------------------------------
+// Maps the indices of an indexed image, packed at 1, 2, 4 or 8 bits, through the palette. The
+// result is RGB8 (`TrueColor`) or RGBA8 (`TrueColorAlpha`) if `trns` holds palette alpha values.
+// Indices beyond the palette fail with `Error::InvalidPalette`.
+pub fn expand_palette(
+    ihdr: &PNGChunkIHDR,
+    plte: &[u32],
+    trns: Option<&Transparency>,
+    data: &[u8],
+) -> Result<(PNGColorType, Vec<u8>)> {
+    if ihdr.color_type != PNGColorType::IndexedColor || ![1, 2, 4, 8].contains(&ihdr.bit_depth) {
+        return Err(Error::InvalidFormat);
+    }
+
+    let alpha = match trns {
+        Some(Transparency::Palette(alpha)) => Some(alpha.as_slice()),
+        _ => None,
+    };
+    let (color_type, channels) = match alpha {
+        Some(_) => (PNGColorType::TrueColorAlpha, 4),
+        None => (PNGColorType::TrueColor, 3),
+    };
+
+    let bpl = line_bytes(ihdr, ihdr.width)?;
+    if data.len() / bpl < ihdr.height {
+        return Err(Error::BufferUnderrun);
+    }
+    let pixels = ihdr.width.checked_mul(ihdr.height);
+    let size = pixels.and_then(|pixels| pixels.checked_mul(channels));
+    let mut out = Vec::with_capacity(size.ok_or(Error::InvalidPng)?);
+
+    for row in data.chunks_exact(bpl).take(ihdr.height) {
+        for x in 0..ihdr.width {
+            let index = sample(&row[1..], x, ihdr.bit_depth) as usize;
+            let entry = plte.get(index).ok_or(Error::InvalidPalette)?;
+            out.extend_from_slice(&entry.to_le_bytes()[..3]);
+            if let Some(alpha) = alpha {
+                out.push(alpha.get(index).copied().unwrap_or(255));
+            }
+        }
+    }
+
+    Ok((color_type, out))
+}
+
+// ----------------------------------------------------------------------------
+// Expands unfiltered scanlines (with leading filter bytes) into packed RGBA8 pixels.
+pub fn to_rgba(ihdr: &PNGChunkIHDR, plte: &[u32], data: &[u8]) -> Result<Vec<u8>> {
+    to_rgba_with_transparency(ihdr, plte, None, data)
+}
+
+// ----------------------------------------------------------------------------
+// Like `to_rgba`, pixels matching the tRNS color key or palette alpha get that alpha.
+pub fn to_rgba_with_transparency(
+    ihdr: &PNGChunkIHDR,
+    plte: &[u32],
+    trns: Option<&Transparency>,
+    data: &[u8],
+) -> Result<Vec<u8>> {
+    to_rgba_with_significant_bits(ihdr, plte, trns, None, data)
+}
+
+// ----------------------------------------------------------------------------
+// Like `to_rgba_with_transparency`, samples (palette entries for indexed images) keep only the
+// significant bits from `png_significant_bits` and are rescaled from there, e.g. 5 bit values
+// stored at 8 bits map 0x80 to 132 rather than 128.
+pub fn to_rgba_with_significant_bits(
+    ihdr: &PNGChunkIHDR,
+    plte: &[u32],
+    trns: Option<&Transparency>,
+    sbit: Option<[u8; 4]>,
+    data: &[u8],
+) -> Result<Vec<u8>> {
+    let size = ihdr
+        .width
+        .checked_mul(ihdr.height)
+        .and_then(|pixels| pixels.checked_mul(4))
+        .ok_or(Error::InvalidPng)?;
+    let mut rgba = vec![0u8; size];
+    to_rgba_into(ihdr, plte, trns, sbit, data, &mut rgba)?;
+    Ok(rgba)
+}
+
+// ----------------------------------------------------------------------------
+// Like `to_rgba_with_significant_bits` into the first `width * height * 4` bytes of `out`.
+pub(crate) fn to_rgba_into(
+    ihdr: &PNGChunkIHDR,
+    plte: &[u32],
+    trns: Option<&Transparency>,
+    sbit: Option<[u8; 4]>,
+    data: &[u8],
+    out: &mut [u8],
+) -> Result<()> {
+    let channels = ihdr.color_type.channels();
+    let bit_depth = ihdr.bit_depth;
+    if ![1, 2, 4, 8].contains(&bit_depth) {
+        return Err(Error::UnsupportedFormat);
+    }
+
+    let bpl = ihdr
+        .width
+        .checked_mul(channels * bit_depth)
+        .ok_or(Error::InvalidPng)?;
+    let bpl = bpl.div_ceil(8) + 1;
+    let pixels = ihdr
+        .width
+        .checked_mul(ihdr.height)
+        .ok_or(Error::InvalidPng)?;
+    let size = pixels.checked_mul(4).ok_or(Error::InvalidPng)?;
+    let out = out.get_mut(..size).ok_or(Error::BufferError)?;
+
+    if data.len() / bpl < ihdr.height {
+        return Err(Error::BufferUnderrun);
+    }
+
+    // scale the significant bits of each channel to the full 8 bit range, rounding to nearest
+    let depth = match ihdr.color_type {
+        PNGColorType::IndexedColor => 8,
+        _ => bit_depth as u32,
+    };
+    let bits = sbit.map_or([depth; 4], |sbit| {
+        sbit.map(|bits| (bits as u32).clamp(1, depth))
+    });
+    let scale = |v: u8, c: usize| {
+        let max = (1u32 << bits[c]) - 1;
+        (((v as u32 >> (depth - bits[c])) * 255 + max / 2) / max) as u8
+    };
+
+    let mut rgba = out.chunks_exact_mut(4);
+    for row in data.chunks_exact(bpl).take(ihdr.height) {
+        let row = &row[1..];
+        for x in 0..ihdr.width {
+            let px = |c: usize| sample(row, x * channels + c, bit_depth);
+            let pixel = match ihdr.color_type {
+                PNGColorType::Greyscale => {
+                    let g = scale(px(0), 0);
+                    let key = matches!(trns, Some(Transparency::Grey(v)) if *v == px(0) as u16);
+                    [g, g, g, if key { 0 } else { 255 }]
+                }
+                PNGColorType::GreyscaleAplha => {
+                    let g = scale(px(0), 0);
+                    [g, g, g, scale(px(1), 3)]
+                }
+                PNGColorType::TrueColor => {
+                    let rgb = (px(0) as u16, px(1) as u16, px(2) as u16);
+                    let key =
+                        matches!(trns, Some(Transparency::Rgb(r, g, b)) if (*r, *g, *b) == rgb);
+                    let alpha = if key { 0 } else { 255 };
+                    [scale(px(0), 0), scale(px(1), 1), scale(px(2), 2), alpha]
+                }
+                PNGColorType::TrueColorAlpha => [
+                    scale(px(0), 0),
+                    scale(px(1), 1),
+                    scale(px(2), 2),
+                    scale(px(3), 3),
+                ],
+                PNGColorType::IndexedColor => {
+                    let index = px(0) as usize;
+                    let entry = plte.get(index).ok_or(Error::InvalidPalette)?;
+                    let [r, g, b, _] = entry.to_le_bytes();
+                    let alpha = match trns {
+                        Some(Transparency::Palette(alpha)) => alpha.get(index).copied(),
+                        _ => None,
+                    };
+                    [scale(r, 0), scale(g, 1), scale(b, 2), alpha.unwrap_or(255)]
+                }
+            };
+            if let Some(dst) = rgba.next() {
+                dst.copy_from_slice(&pixel);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// ----------------------------------------------------------------------------
+#[derive(Debug)]
+pub struct PNGPlanes {
+    pub width: usize,
+    pub height: usize,
+    pub stride: usize,
+    // R, G, B and A planes, each `stride * height` bytes
+    pub planes: [Vec<u8>; 4],
+}
+
+// ----------------------------------------------------------------------------
+// Like `to_rgba`, but de-interleaves the channels into separate planes with rows `stride` bytes
+// apart. Padding bytes at the end of each row are zero.
+pub fn to_planar(
+    ihdr: &PNGChunkIHDR,
+    plte: &[u32],
+    data: &[u8],
+    stride: usize,
+) -> Result<PNGPlanes> {
+    if stride < ihdr.width {
+        return Err(Error::BufferError);
+    }
+
+    let rgba = to_rgba(ihdr, plte, data)?;
+    let size = stride.checked_mul(ihdr.height).ok_or(Error::InvalidPng)?;
+    let mut planes: [Vec<u8>; 4] = core::array::from_fn(|_| vec![0u8; size]);
+
+    for (y, row) in rgba.chunks_exact(ihdr.width * 4).enumerate() {
+        let ofs = y * stride;
+        for (x, px) in row.chunks_exact(4).enumerate() {
+            for (plane, value) in planes.iter_mut().zip(px) {
+                plane[ofs + x] = *value;
+            }
+        }
+    }
+
+    Ok(PNGPlanes {
+        width: ihdr.width,
+        height: ihdr.height,
+        stride,
+        planes,
+    })
+}
+
 // ----------------------------------------------------------------------------
 #[test]
 fn test_paeth() {
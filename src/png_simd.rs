@@ -0,0 +1,140 @@
+// Vectorized PNG unfiltering.
+// * SSE2 is part of the x86_64 baseline, no runtime detection is needed, other targets use the
+//   scalar loops of `png_read`
+// * Up adds 16 bytes per step, Sub, Average and Paeth depend on the pixel to the left and
+//   process one pixel of 3 or 4 bytes per step, as libpng's `filter_sse2_intrinsics.c`
+use crate::png_read::PNGFilterType;
+
+// ----------------------------------------------------------------------------
+// Unfilters `recon` against the previous scanline `precon`, returns false if there is no
+// vectorized version for the filter type and pixel size.
+#[cfg(target_arch = "x86_64")]
+pub(crate) fn unfilter(
+    recon: &mut [u8],
+    precon: &[u8],
+    filter_type: PNGFilterType,
+    bpp: usize,
+) -> bool {
+    // SAFETY: SSE2 is available on every x86_64 CPU
+    unsafe {
+        match (filter_type, bpp) {
+            (PNGFilterType::Up, _) => sse2::up(recon, precon),
+            (PNGFilterType::Sub, 3) => sse2::sub::<3>(recon),
+            (PNGFilterType::Sub, 4) => sse2::sub::<4>(recon),
+            (PNGFilterType::Average, 3) => sse2::average::<3>(recon, precon),
+            (PNGFilterType::Average, 4) => sse2::average::<4>(recon, precon),
+            (PNGFilterType::Paeth, 3) => sse2::paeth::<3>(recon, precon),
+            (PNGFilterType::Paeth, 4) => sse2::paeth::<4>(recon, precon),
+            _ => return false,
+        }
+    }
+    true
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(not(target_arch = "x86_64"))]
+pub(crate) fn unfilter(
+    _recon: &mut [u8],
+    _precon: &[u8],
+    _filter_type: PNGFilterType,
+    _bpp: usize,
+) -> bool {
+    false
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(target_arch = "x86_64")]
+mod sse2 {
+    use core::arch::x86_64::*;
+
+    // the `BPP` bytes of a pixel in the lowest lanes
+    #[target_feature(enable = "sse2")]
+    fn load<const BPP: usize>(src: &[u8]) -> __m128i {
+        let mut bytes = [0u8; 4];
+        bytes[..BPP].copy_from_slice(&src[..BPP]);
+        _mm_cvtsi32_si128(i32::from_le_bytes(bytes))
+    }
+
+    #[target_feature(enable = "sse2")]
+    fn store<const BPP: usize>(dst: &mut [u8], value: __m128i) {
+        let bytes = _mm_cvtsi128_si32(value).to_le_bytes();
+        dst[..BPP].copy_from_slice(&bytes[..BPP]);
+    }
+
+    #[target_feature(enable = "sse2")]
+    fn select(mask: __m128i, a: __m128i, b: __m128i) -> __m128i {
+        _mm_or_si128(_mm_and_si128(mask, a), _mm_andnot_si128(mask, b))
+    }
+
+    #[target_feature(enable = "sse2")]
+    fn abs16(x: __m128i) -> __m128i {
+        _mm_max_epi16(x, _mm_sub_epi16(_mm_setzero_si128(), x))
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub(super) fn up(recon: &mut [u8], precon: &[u8]) {
+        let len = recon.len().min(precon.len());
+        let blocks = recon[..len].chunks_exact_mut(16);
+        for (r, p) in blocks.zip(precon.chunks_exact(16)) {
+            // SAFETY: both chunks are 16 bytes long, unaligned loads and stores are allowed
+            unsafe {
+                let a = _mm_loadu_si128(r.as_ptr().cast());
+                let b = _mm_loadu_si128(p.as_ptr().cast());
+                _mm_storeu_si128(r.as_mut_ptr().cast(), _mm_add_epi8(a, b));
+            }
+        }
+        for i in len - len % 16..len {
+            recon[i] = recon[i].wrapping_add(precon[i]);
+        }
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub(super) fn sub<const BPP: usize>(recon: &mut [u8]) {
+        let mut a = _mm_setzero_si128();
+        for px in recon.chunks_exact_mut(BPP) {
+            a = _mm_add_epi8(load::<BPP>(px), a);
+            store::<BPP>(px, a);
+        }
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub(super) fn average<const BPP: usize>(recon: &mut [u8], precon: &[u8]) {
+        let one = _mm_set1_epi8(1);
+        let mut a = _mm_setzero_si128();
+        for (px, up) in recon.chunks_exact_mut(BPP).zip(precon.chunks_exact(BPP)) {
+            let b = load::<BPP>(up);
+            // `_mm_avg_epu8` rounds up, odd sums are corrected to round down
+            let odd = _mm_and_si128(_mm_xor_si128(a, b), one);
+            let avg = _mm_sub_epi8(_mm_avg_epu8(a, b), odd);
+            a = _mm_add_epi8(load::<BPP>(px), avg);
+            store::<BPP>(px, a);
+        }
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub(super) fn paeth<const BPP: usize>(recon: &mut [u8], precon: &[u8]) {
+        // left (a), up (b) and upper left (c) neighbours as 16 bit lanes
+        let zero = _mm_setzero_si128();
+        let (mut a, mut c) = (zero, zero);
+        for (px, up) in recon.chunks_exact_mut(BPP).zip(precon.chunks_exact(BPP)) {
+            let b = _mm_unpacklo_epi8(load::<BPP>(up), zero);
+            let d = _mm_unpacklo_epi8(load::<BPP>(px), zero);
+
+            // distances of the prediction a + b - c to a, b and c
+            let pa = _mm_sub_epi16(b, c);
+            let pb = _mm_sub_epi16(a, c);
+            let pc = _mm_add_epi16(pa, pb);
+            let (pa, pb, pc) = (abs16(pa), abs16(pb), abs16(pc));
+
+            // ties prefer a, then b
+            let smallest = _mm_min_epi16(pc, _mm_min_epi16(pa, pb));
+            let b_or_c = select(_mm_cmpeq_epi16(smallest, pb), b, c);
+            let nearest = select(_mm_cmpeq_epi16(smallest, pa), a, b_or_c);
+
+            // bytewise addition wraps without carrying into the zero high bytes
+            let d = _mm_add_epi8(d, nearest);
+            store::<BPP>(px, _mm_packus_epi16(d, d));
+            (a, c) = (d, b);
+        }
+    }
+}
@@ -0,0 +1,344 @@
+// Push-based PNG decoding for data arriving in pieces, e.g. progressive display over a network.
+// * `feed` takes the PNG bytes in arbitrary pieces and reports every scanline as soon as it is
+//   decompressed and unfiltered
+// * IDAT and skipped chunks are streamed, only IHDR, PLTE and IEND are buffered, the CRCs of
+//   critical chunks are checked unless disabled
+// * decompression works per deflate block (see `stream::Inflater`), a scanline is reported once
+//   the block it ends in is complete
+// * interlaced images are reported once all Adam7 passes are complete
+use crate::checksum;
+use crate::inflate::{self, InflateOptions};
+use crate::png_read::{self, ChunkOrder, Error, PNGChunkIHDR, Result};
+use crate::png_read::{deinterlace, interlaced_size, line_bytes, parse_ihdr, read_plte};
+use crate::png_read::{unfilter_image, unfilter_scanline};
+use crate::stream::Inflater;
+use alloc::{vec, vec::Vec};
+
+// ----------------------------------------------------------------------------
+const SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+const IHDR: u32 = u32::from_be_bytes(*b"IHDR");
+const PLTE: u32 = u32::from_be_bytes(*b"PLTE");
+const IDAT: u32 = u32::from_be_bytes(*b"IDAT");
+const IEND: u32 = u32::from_be_bytes(*b"IEND");
+
+// ----------------------------------------------------------------------------
+// chunks are at most 2^31 - 1 bytes
+const MAX_CHUNK_LEN: usize = 0x7fff_ffff;
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Signature,
+    // length and type of the next chunk
+    ChunkHead,
+    // chunk buffered with its CRC
+    Buffered {
+        chunk: u32,
+        len: usize,
+    },
+    // chunk data passed through, IDAT data to the decompressor, others are dropped
+    Streamed {
+        chunk: u32,
+        remaining: usize,
+        crc: u32,
+    },
+    // CRC of a streamed chunk
+    Crc {
+        chunk: u32,
+        crc: u32,
+    },
+    Done,
+}
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone)]
+pub struct PngDecoder {
+    verify_checksums: bool,
+    lenient_chunk_order: bool,
+    order: ChunkOrder,
+    state: State,
+    // incomplete signature, chunk head or buffered chunk
+    pending: Vec<u8>,
+    ihdr: Option<PNGChunkIHDR>,
+    plte: Vec<u32>,
+    inflater: Inflater,
+    // previous and current scanline, or the passes of an interlaced image
+    rows: Vec<u8>,
+    // decompressed bytes of the current scanline or of all passes
+    filled: usize,
+    // number of scanlines reported
+    row: usize,
+}
+
+// ----------------------------------------------------------------------------
+impl Default for PngDecoder {
+    fn default() -> Self {
+        PngDecoder::new()
+    }
+}
+
+// ----------------------------------------------------------------------------
+impl PngDecoder {
+    pub fn new() -> Self {
+        PngDecoder {
+            verify_checksums: true,
+            lenient_chunk_order: false,
+            order: ChunkOrder::new(),
+            state: State::Signature,
+            pending: Vec::new(),
+            ihdr: None,
+            plte: Vec::new(),
+            inflater: Inflater::new(true),
+            rows: Vec::new(),
+            filled: 0,
+            row: 0,
+        }
+    }
+
+    // Checks the CRCs of critical chunks, on by default.
+    pub fn set_verify_checksums(&mut self, enabled: bool) {
+        self.verify_checksums = enabled;
+    }
+
+    // Accepts chunks out of order or repeated, off by default. Repeated IHDR chunks are
+    // skipped, a PLTE after IDAT replaces the palette for the remaining scanlines.
+    pub fn set_lenient_chunk_order(&mut self, enabled: bool) {
+        self.lenient_chunk_order = enabled;
+    }
+
+    // The image header once IHDR has been fed.
+    pub fn header(&self) -> Option<&PNGChunkIHDR> {
+        self.ihdr.as_ref()
+    }
+
+    // palette entries as `b << 16 | g << 8 | r`
+    pub fn palette(&self) -> &[u32] {
+        &self.plte
+    }
+
+    // number of scanlines reported so far
+    pub fn rows_decoded(&self) -> usize {
+        self.row
+    }
+
+    // IEND has been reached with all scanlines decoded.
+    pub fn is_finished(&self) -> bool {
+        self.state == State::Done
+    }
+
+    // Decodes `data` and calls `on_row` with the row index and the unfiltered samples of each
+    // completed scanline, without the filter byte. Returns the number of bytes consumed, data
+    // after IEND is not consumed.
+    pub fn feed<F: FnMut(usize, &[u8])>(&mut self, data: &[u8], mut on_row: F) -> Result<usize> {
+        let mut input = data;
+        while !input.is_empty() && self.state != State::Done {
+            if let State::Streamed {
+                chunk,
+                remaining,
+                crc,
+            } = self.state
+            {
+                let (part, rest) = input.split_at(remaining.min(input.len()));
+                if chunk == IDAT {
+                    self.inflate(part, &mut on_row)?;
+                }
+                input = rest;
+                let crc = checksum::crc32(crc, part);
+                let remaining = remaining - part.len();
+                self.state = if remaining == 0 {
+                    State::Crc { chunk, crc }
+                } else {
+                    State::Streamed {
+                        chunk,
+                        remaining,
+                        crc,
+                    }
+                };
+                continue;
+            }
+
+            let wanted = match self.state {
+                State::Signature | State::ChunkHead => 8,
+                State::Buffered { len, .. } => len + 4,
+                _ => 4,
+            };
+            let (part, rest) = input.split_at((wanted - self.pending.len()).min(input.len()));
+            self.pending.extend_from_slice(part);
+            input = rest;
+            if self.pending.len() == wanted {
+                self.next_state()?;
+                self.pending.clear();
+            }
+        }
+        Ok(data.len() - input.len())
+    }
+
+    // Processes the complete signature, chunk head, buffered chunk or CRC in `pending`.
+    fn next_state(&mut self) -> Result<()> {
+        let data = &self.pending[..];
+        self.state = match self.state {
+            State::Signature if data == SIGNATURE => State::ChunkHead,
+            State::Signature => return Err(Error::InvalidSignature),
+            State::ChunkHead => {
+                let len = u32::from_be_bytes(data[0..4].try_into()?) as usize;
+                let chunk = u32::from_be_bytes(data[4..8].try_into()?);
+                if len > MAX_CHUNK_LEN {
+                    return Err(Error::InvalidPng);
+                }
+                if self.ihdr.is_none() && chunk != IHDR {
+                    return Err(Error::MissingIHDR);
+                }
+                let first = self.ihdr.is_none();
+                if !first {
+                    if let Err(err) = self.order.check(chunk) {
+                        if !self.lenient_chunk_order {
+                            return Err(err);
+                        }
+                    }
+                }
+                match chunk {
+                    IHDR if first => State::Buffered { chunk, len },
+                    PLTE | IEND => State::Buffered { chunk, len },
+                    _ => State::Streamed {
+                        chunk,
+                        remaining: len,
+                        crc: checksum::crc32(0, &data[4..8]),
+                    },
+                }
+            }
+            State::Buffered { chunk, len } => {
+                let (body, crc) = data.split_at(len);
+                let expected = checksum::crc32(checksum::crc32(0, &chunk.to_be_bytes()), body);
+                if self.verify_checksums && crc != expected.to_be_bytes() {
+                    return Err(Error::ChecksumMismatch);
+                }
+                match chunk {
+                    IHDR => {
+                        let ihdr = parse_ihdr(body)?;
+                        self.start(&ihdr)?;
+                        self.ihdr = Some(ihdr);
+                        State::ChunkHead
+                    }
+                    PLTE => {
+                        read_plte(body, &mut self.plte)?;
+                        State::ChunkHead
+                    }
+                    _ => {
+                        self.finish()?;
+                        State::Done
+                    }
+                }
+            }
+            State::Crc { chunk, crc } => {
+                let critical = chunk & 0x2000_0000 == 0;
+                if self.verify_checksums && critical && data != crc.to_be_bytes() {
+                    return Err(Error::ChecksumMismatch);
+                }
+                State::ChunkHead
+            }
+            State::Streamed { .. } | State::Done => self.state,
+        };
+        Ok(())
+    }
+
+    // Allocates the scanline buffers for the image described by `ihdr`.
+    fn start(&mut self, ihdr: &PNGChunkIHDR) -> Result<()> {
+        if ihdr.bit_depth > 8 {
+            return Err(Error::UnsupportedFormat);
+        }
+        let len = if ihdr.interlace == 0 {
+            2 * line_bytes(ihdr, ihdr.width)?
+        } else {
+            interlaced_size(ihdr)?
+        };
+        self.rows = vec![0; len];
+        Ok(())
+    }
+
+    // bytes decompressed into `rows` before scanlines are reported
+    fn unit_len(&self, ihdr: &PNGChunkIHDR) -> usize {
+        if ihdr.interlace == 0 {
+            self.rows.len() / 2
+        } else {
+            self.rows.len()
+        }
+    }
+
+    // Decompresses IDAT data and reports the completed scanlines.
+    fn inflate<F: FnMut(usize, &[u8])>(&mut self, data: &[u8], on_row: &mut F) -> Result<()> {
+        let ihdr = self.ihdr.ok_or(Error::MissingIHDR)?;
+        let unit = self.unit_len(&ihdr);
+        let mut input = data;
+        loop {
+            let complete = self.row == ihdr.height;
+            // any data after the last scanline is an error
+            let mut excess = [0u8; 1];
+            let output = if complete {
+                &mut excess[..]
+            } else {
+                let start = self.rows.len() - unit + self.filled;
+                &mut self.rows[start..]
+            };
+            let (_, consumed, produced) =
+                self.inflater
+                    .decompress(input, output)
+                    .map_err(|err| match err {
+                        crate::Error::Inflate(err) => err.into(),
+                        crate::Error::Png(err) => err,
+                        _ => Error::CompressionError(None),
+                    })?;
+            if complete && produced > 0 {
+                return Err(Error::ExcessImageData);
+            }
+            input = &input[consumed..];
+            self.filled += produced;
+            if self.filled == unit {
+                self.report(&ihdr, on_row)?;
+            }
+            if consumed == 0 && produced == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    // Unfilters the completed scanline or deinterlaces the completed passes.
+    fn report<F: FnMut(usize, &[u8])>(
+        &mut self,
+        ihdr: &PNGChunkIHDR,
+        on_row: &mut F,
+    ) -> Result<()> {
+        self.filled = 0;
+        let bpl = line_bytes(ihdr, ihdr.width)?;
+        if ihdr.interlace == 0 {
+            if self.row == 0 {
+                unfilter_image(&mut self.rows[bpl..], ihdr.color_type, bpl, 1)?;
+            } else {
+                unfilter_scanline(&mut self.rows, ihdr.color_type, bpl)?;
+            }
+            on_row(self.row, &self.rows[bpl + 1..]);
+            self.rows.copy_within(bpl.., 0);
+            self.row += 1;
+        } else {
+            let mut image = vec![0; png_read::decoded_size(ihdr)?];
+            let options = InflateOptions::default();
+            deinterlace(&mut image, &mut self.rows, ihdr, bpl, &options)?;
+            for (y, row) in image.chunks_exact(bpl).enumerate() {
+                on_row(y, &row[1..]);
+            }
+            self.row = ihdr.height;
+        }
+        Ok(())
+    }
+
+    // IEND requires the complete image and zlib stream.
+    fn finish(&self) -> Result<()> {
+        let height = self.ihdr.map_or(0, |ihdr| ihdr.height);
+        if self.row != height {
+            return Err(Error::InvalidPng);
+        }
+        if !self.inflater.is_finished() {
+            return Err(Error::CompressionError(Some(inflate::Error::Underflow)));
+        }
+        Ok(())
+    }
+}
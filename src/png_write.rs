@@ -0,0 +1,569 @@
+// PNG compression implementation.
+// * https://www.w3.org/TR/png-3/
+// * scanlines are written into a single IDAT chunk, unfiltered and stored by default, with the
+//   Up filter and run-length deflate by the `EncodeOptions::fast` preset or with a per-scanline
+//   filter choice and full deflate by `EncodeOptions::small`
+// * `png_repair` re-emits damaged files with recomputed CRCs, recovered chunk lengths and critical
+//   chunks in the required order
+use crate::deflate::CompressionLevel;
+use crate::png_read::{paeth, Error, PNGChunkIHDR, PNGColorType, Result};
+use crate::{checksum, deflate};
+use alloc::{vec, vec::Vec};
+
+// ----------------------------------------------------------------------------
+const SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+// ----------------------------------------------------------------------------
+// Color space information, a PNG carries either an sRGB or an iCCP chunk.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ColorProfile<'a> {
+    #[default]
+    None,
+    // sRGB with the rendering intent 0 (perceptual) to 3 (absolute colorimetric)
+    Srgb(u8),
+    // ICC profile with a name of 1 to 79 printable Latin-1 characters
+    Icc {
+        name: &'a str,
+        profile: &'a [u8],
+    },
+}
+
+// ----------------------------------------------------------------------------
+// Color key written as tRNS chunk: pixels of exactly this value are fully transparent. The
+// samples are in the image bit depth and the variant must match the color type.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorKey {
+    Grey(u16),
+    Rgb(u16, u16, u16),
+}
+
+// ----------------------------------------------------------------------------
+// Position of an extra chunk, PLTE and IDAT are always written even if they are omitted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChunkPlacement {
+    BeforePlte,
+    BeforeIdat,
+    AfterIdat,
+}
+
+// ----------------------------------------------------------------------------
+// Application-specific chunk written unchanged, e.g. editor state in a private chunk. The type
+// must be ancillary with the reserved bit clear so that other decoders can skip it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExtraChunk<'a> {
+    pub chunk_type: [u8; 4],
+    pub data: &'a [u8],
+    pub placement: ChunkPlacement,
+}
+
+// ----------------------------------------------------------------------------
+// Filter type of the scanlines, the first one is unfiltered unless Sub or Adaptive is chosen.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum RowFilter {
+    #[default]
+    None,
+    // difference to the previous pixel, suits horizontal gradients
+    Sub,
+    // difference to the previous scanline, turns repeated rows into runs of zeros
+    Up,
+    // per scanline the filter with the smallest sum of absolute differences, including Paeth
+    Adaptive,
+}
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum IdatCompression {
+    #[default]
+    Stored,
+    // fixed Huffman codes with runs of repeated bytes, a single pass over the data
+    Rle,
+    // LZ77 with Huffman codes chosen per block
+    Deflate(CompressionLevel),
+}
+
+// ----------------------------------------------------------------------------
+// Trade-off between encoding speed and size, like the compression levels of libpng.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum EncodePreset {
+    // no filtering and the fastest deflate level
+    Fast,
+    // adaptive filtering and the default deflate level
+    #[default]
+    Default,
+    // adaptive filtering and the best deflate level
+    Best,
+}
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EncodeOptions<'a> {
+    pub color_profile: ColorProfile<'a>,
+    pub color_key: Option<ColorKey>,
+    // written in the given order at their placement
+    pub extra_chunks: &'a [ExtraChunk<'a>],
+    pub filter: RowFilter,
+    pub compression: IdatCompression,
+}
+
+// ----------------------------------------------------------------------------
+impl EncodeOptions<'_> {
+    // Filter selection and deflate level of `preset`, the other options at their defaults.
+    pub fn preset(preset: EncodePreset) -> Self {
+        let (filter, level) = match preset {
+            EncodePreset::Fast => (RowFilter::None, CompressionLevel::BestSpeed),
+            EncodePreset::Default => (RowFilter::Adaptive, CompressionLevel::DefaultLevel),
+            EncodePreset::Best => (RowFilter::Adaptive, CompressionLevel::BestCompression),
+        };
+        EncodeOptions {
+            filter,
+            compression: IdatCompression::Deflate(level),
+            ..Default::default()
+        }
+    }
+
+    // Low-latency preset for screen and frame capture: Up filter and run-length deflate.
+    pub fn fast() -> Self {
+        EncodeOptions {
+            filter: RowFilter::Up,
+            compression: IdatCompression::Rle,
+            ..Default::default()
+        }
+    }
+
+    // Preset for the smallest files: adaptive filtering and the best deflate level.
+    pub fn small() -> Self {
+        EncodeOptions {
+            filter: RowFilter::Adaptive,
+            compression: IdatCompression::Deflate(CompressionLevel::BestCompression),
+            ..Default::default()
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Appends the filter type byte and the filtered `row`, `prev` is the previous unfiltered row
+// or all zeros for the first, `bpp` the distance to the corresponding byte of the previous pixel.
+fn filter_row(dst: &mut Vec<u8>, filter: u8, row: &[u8], prev: &[u8], bpp: usize) {
+    dst.push(filter);
+    for (i, (&x, &b)) in row.iter().zip(prev).enumerate() {
+        let a = if i >= bpp { row[i - bpp] } else { 0 };
+        let c = if i >= bpp { prev[i - bpp] } else { 0 };
+        dst.push(match filter {
+            1 => x.wrapping_sub(a),
+            2 => x.wrapping_sub(b),
+            3 => x.wrapping_sub(((a as u32 + b as u32) / 2) as u8),
+            4 => x.wrapping_sub(paeth(a, b, c)),
+            _ => x,
+        });
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Filter type of the scanline with the smallest sum of filtered bytes taken as signed values.
+fn adaptive_filter(row: &[u8], prev: &[u8], bpp: usize, buf: &mut Vec<u8>) -> u8 {
+    let mut best = (u64::MAX, 0);
+    for filter in 0..5 {
+        buf.clear();
+        filter_row(buf, filter, row, prev, bpp);
+        let cost = buf[1..]
+            .iter()
+            .map(|&x| (x as i8).unsigned_abs() as u64)
+            .sum();
+        if cost < best.0 {
+            best = (cost, filter);
+        }
+    }
+    best.1
+}
+
+// ----------------------------------------------------------------------------
+fn write_chunk(png: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(chunk_type);
+    png.extend_from_slice(data);
+    let crc = checksum::crc32(checksum::crc32(0, chunk_type), data);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+// ----------------------------------------------------------------------------
+fn write_extra_chunks(png: &mut Vec<u8>, chunks: &[ExtraChunk], placement: ChunkPlacement) {
+    for chunk in chunks.iter().filter(|c| c.placement == placement) {
+        write_chunk(png, &chunk.chunk_type, chunk.data);
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Letters only, lowercase first letter (ancillary) and uppercase third letter (reserved bit).
+fn valid_extra_chunk(chunk: &ExtraChunk) -> bool {
+    let [a, b, c, d] = chunk.chunk_type;
+    [a, b, c, d].iter().all(u8::is_ascii_alphabetic)
+        && a.is_ascii_lowercase()
+        && c.is_ascii_uppercase()
+        && chunk.data.len() <= i32::MAX as usize
+}
+
+// ----------------------------------------------------------------------------
+// Keywords are printable Latin-1 without leading, trailing or consecutive spaces.
+fn valid_keyword(name: &str) -> bool {
+    let latin1 = |c: char| matches!(c as u32, 32..=126 | 161..=255);
+    (1..=79).contains(&name.chars().count())
+        && name.chars().all(latin1)
+        && !name.starts_with(' ')
+        && !name.ends_with(' ')
+        && !name.contains("  ")
+}
+
+// ----------------------------------------------------------------------------
+fn write_color_profile(png: &mut Vec<u8>, profile: &ColorProfile) -> Result<()> {
+    match *profile {
+        ColorProfile::None => {}
+        ColorProfile::Srgb(intent) => {
+            if intent > 3 {
+                return Err(Error::InvalidFormat);
+            }
+            write_chunk(png, b"sRGB", &[intent]);
+        }
+        ColorProfile::Icc { name, profile } => {
+            if !valid_keyword(name) {
+                return Err(Error::InvalidFormat);
+            }
+            // name, null separator, compression method 0 and the zlib compressed profile
+            let mut data: Vec<u8> = name.chars().map(|c| c as u8).collect();
+            data.extend_from_slice(&[0, 0]);
+            deflate::deflate_zlib(&mut data, profile);
+            write_chunk(png, b"iCCP", &data);
+        }
+    }
+    Ok(())
+}
+
+// ----------------------------------------------------------------------------
+fn write_color_key(png: &mut Vec<u8>, ihdr: &PNGChunkIHDR, key: ColorKey) -> Result<()> {
+    let samples: &[u16] = match (ihdr.color_type, &key) {
+        (PNGColorType::Greyscale, ColorKey::Grey(grey)) => &[*grey],
+        (PNGColorType::TrueColor, ColorKey::Rgb(r, g, b)) => &[*r, *g, *b],
+        _ => return Err(Error::InvalidFormat),
+    };
+
+    let max = (1u32 << ihdr.bit_depth) - 1;
+    let mut data = Vec::with_capacity(6);
+    for &sample in samples {
+        if sample as u32 > max {
+            return Err(Error::InvalidFormat);
+        }
+        data.extend_from_slice(&sample.to_be_bytes());
+    }
+    write_chunk(png, b"tRNS", &data);
+    Ok(())
+}
+
+// ----------------------------------------------------------------------------
+// Encodes `data`, the packed scanlines without filter bytes, as a PNG file. `plte` holds the
+// palette of indexed images in the layout returned by `png_read`.
+pub fn png_write(
+    ihdr: &PNGChunkIHDR,
+    plte: &[u32],
+    data: &[u8],
+    options: &EncodeOptions,
+) -> Result<Vec<u8>> {
+    use PNGColorType::*;
+    ihdr.validate()?;
+    if ihdr.interlace != 0 {
+        return Err(Error::UnsupportedFormat);
+    }
+    if ihdr.color_type == IndexedColor && !(1..=256).contains(&plte.len()) {
+        return Err(Error::InvalidPalette);
+    }
+    if !options.extra_chunks.iter().all(valid_extra_chunk) {
+        return Err(Error::InvalidFormat);
+    }
+
+    let row_bits = ihdr.width * ihdr.color_type.channels() * ihdr.bit_depth;
+    let row_bytes = row_bits.div_ceil(8);
+    if data.len() != row_bytes * ihdr.height {
+        return Err(Error::BufferError);
+    }
+
+    let mut png = Vec::from(SIGNATURE);
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(ihdr.width as u32).to_be_bytes());
+    header.extend_from_slice(&(ihdr.height as u32).to_be_bytes());
+    header.extend_from_slice(&[ihdr.bit_depth as u8, ihdr.color_type as u8, 0, 0, 0]);
+    write_chunk(&mut png, b"IHDR", &header);
+
+    write_color_profile(&mut png, &options.color_profile)?;
+    write_extra_chunks(&mut png, options.extra_chunks, ChunkPlacement::BeforePlte);
+
+    if ihdr.color_type == IndexedColor {
+        let mut palette = Vec::with_capacity(plte.len() * 3);
+        for rgb in plte {
+            palette.extend_from_slice(&rgb.to_le_bytes()[..3]);
+        }
+        write_chunk(&mut png, b"PLTE", &palette);
+    }
+
+    if let Some(key) = options.color_key {
+        write_color_key(&mut png, ihdr, key)?;
+    }
+    write_extra_chunks(&mut png, options.extra_chunks, ChunkPlacement::BeforeIdat);
+
+    // filter type in front of every scanline, sub-byte pixels are filtered bytewise
+    let bpp = (ihdr.color_type.channels() * ihdr.bit_depth).div_ceil(8);
+    let zeros = vec![0u8; row_bytes];
+    let mut scanlines = Vec::with_capacity(data.len() + ihdr.height);
+    let mut buf = Vec::new();
+    let mut prev: &[u8] = &zeros;
+    for (y, row) in data.chunks_exact(row_bytes).enumerate() {
+        let filter = match options.filter {
+            RowFilter::None => 0,
+            RowFilter::Sub => 1,
+            RowFilter::Up if y == 0 => 0,
+            RowFilter::Up => 2,
+            RowFilter::Adaptive => adaptive_filter(row, prev, bpp, &mut buf),
+        };
+        filter_row(&mut scanlines, filter, row, prev, bpp);
+        prev = row;
+    }
+
+    // stored data grows by 5 bytes per 64 KB, fixed Huffman literals by at most 1/8
+    let mut idat = Vec::with_capacity(scanlines.len() + scanlines.len() / 8 + 16);
+    match options.compression {
+        IdatCompression::Stored => deflate::deflate_zlib(&mut idat, &scanlines),
+        IdatCompression::Rle => deflate::deflate_zlib_rle(&mut idat, &scanlines),
+        IdatCompression::Deflate(level) => {
+            deflate::deflate_zlib_level(&mut idat, &scanlines, level)
+        }
+    }
+    png.reserve(idat.len() + 24);
+    write_chunk(&mut png, b"IDAT", &idat);
+    write_extra_chunks(&mut png, options.extra_chunks, ChunkPlacement::AfterIdat);
+
+    write_chunk(&mut png, b"IEND", &[]);
+    Ok(png)
+}
+
+// ----------------------------------------------------------------------------
+// Smallest bit depth able to index a palette of `len` entries.
+pub fn indexed_bit_depth(len: usize) -> usize {
+    match len {
+        0..=2 => 1,
+        3..=4 => 2,
+        5..=16 => 4,
+        _ => 8,
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Packs one palette index per byte into scanlines of `bit_depth` bits per pixel, leftmost pixel
+// in the most significant bits and the last byte of a row padded with zero bits.
+pub fn pack_indices(indices: &[u8], width: usize, bit_depth: usize) -> Result<Vec<u8>> {
+    if ![1, 2, 4, 8].contains(&bit_depth) || width == 0 {
+        return Err(Error::InvalidFormat);
+    }
+    if !indices.len().is_multiple_of(width) {
+        return Err(Error::BufferError);
+    }
+    if bit_depth < 8 && indices.iter().any(|&index| index >> bit_depth != 0) {
+        return Err(Error::InvalidPalette);
+    }
+
+    let row_bytes = (width * bit_depth).div_ceil(8);
+    let mut data = Vec::with_capacity(row_bytes * indices.len() / width);
+    let per_byte = 8 / bit_depth;
+    for row in indices.chunks_exact(width) {
+        for pixels in row.chunks(per_byte) {
+            let byte = pixels.iter().enumerate().fold(0u8, |byte, (i, &index)| {
+                byte | index << (8 - bit_depth * (i + 1))
+            });
+            data.push(byte);
+        }
+    }
+    Ok(data)
+}
+
+// ----------------------------------------------------------------------------
+// Encodes an indexed image given as one palette index per byte, packed at the smallest bit depth
+// the palette allows.
+pub fn png_write_indexed(
+    width: usize,
+    height: usize,
+    plte: &[u32],
+    indices: &[u8],
+    options: &EncodeOptions,
+) -> Result<Vec<u8>> {
+    if indices.len() != width * height {
+        return Err(Error::BufferError);
+    }
+    if indices.iter().any(|&index| index as usize >= plte.len()) {
+        return Err(Error::InvalidPalette);
+    }
+
+    let ihdr = PNGChunkIHDR {
+        width,
+        height,
+        bit_depth: indexed_bit_depth(plte.len()),
+        color_type: PNGColorType::IndexedColor,
+        compression: 0,
+        filter: 0,
+        interlace: 0,
+    };
+    let data = pack_indices(indices, width, ihdr.bit_depth)?;
+    png_write(&ihdr, plte, &data, options)
+}
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone, Copy)]
+pub struct RepairOptions {
+    pub fix_crcs: bool,
+    // recover the length of chunks whose length field points to no valid chunk boundary
+    pub fix_lengths: bool,
+    pub reorder: bool,
+}
+
+// ----------------------------------------------------------------------------
+impl Default for RepairOptions {
+    fn default() -> Self {
+        RepairOptions {
+            fix_crcs: true,
+            fix_lengths: true,
+            reorder: true,
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RepairReport {
+    pub crcs_fixed: usize,
+    pub lengths_fixed: usize,
+    pub reordered: bool,
+    pub iend_added: bool,
+}
+
+// ----------------------------------------------------------------------------
+struct RawChunk<'a> {
+    chunk_type: [u8; 4],
+    data: &'a [u8],
+    crc: [u8; 4],
+}
+
+// ----------------------------------------------------------------------------
+fn is_chunk_type(chunk_type: &[u8]) -> bool {
+    chunk_type.len() == 4 && chunk_type.iter().all(u8::is_ascii_alphabetic)
+}
+
+// ----------------------------------------------------------------------------
+// Data length of the chunk at `pos`. A length field that does not lead to the next chunk or
+// the end of file is replaced by the length at which the stored CRC matches.
+fn recover_len(png: &[u8], pos: usize) -> Option<usize> {
+    let declared = u32::from_be_bytes(png[pos..pos + 4].try_into().ok()?) as usize;
+    let data = pos + 8;
+    let end = data.checked_add(declared)?.checked_add(4)?;
+    if end == png.len() || png.get(end + 4..end + 8).is_some_and(is_chunk_type) {
+        return Some(declared);
+    }
+
+    let mut crc = checksum::crc32(0, &png[pos + 4..data]);
+    for len in 0..=png.len().checked_sub(data + 4)? {
+        if png[data + len..data + len + 4] == crc.to_be_bytes() {
+            return Some(len);
+        }
+        crc = checksum::crc32(crc, &png[data + len..data + len + 1]);
+    }
+    None
+}
+
+// ----------------------------------------------------------------------------
+// Position class of a chunk, chunks are stable-sorted by it.
+fn chunk_rank(chunk_type: &[u8; 4], before_idat: bool) -> u8 {
+    match chunk_type {
+        b"IHDR" => 0,
+        b"cHRM" | b"gAMA" | b"iCCP" | b"sBIT" | b"sRGB" | b"cICP" | b"mDCV" | b"cLLI" => 1,
+        b"PLTE" => 2,
+        b"tRNS" | b"bKGD" | b"hIST" => 3,
+        b"IDAT" => 4,
+        b"IEND" => 6,
+        _ if before_idat => 3,
+        _ => 5,
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Re-emits the chunks of `png`, repairing what `options` selects. Data after IEND is dropped
+// and a missing IEND is added.
+pub fn png_repair(png: &[u8], options: &RepairOptions) -> Result<(Vec<u8>, RepairReport)> {
+    if !png.starts_with(&SIGNATURE) {
+        return Err(Error::InvalidSignature);
+    }
+
+    let mut report = RepairReport::default();
+    let mut chunks = Vec::new();
+    let mut pos = SIGNATURE.len();
+    while pos + 12 <= png.len() && is_chunk_type(&png[pos + 4..pos + 8]) {
+        let declared = u32::from_be_bytes(png[pos..pos + 4].try_into()?) as usize;
+        let len = if options.fix_lengths {
+            recover_len(png, pos).ok_or(Error::BufferUnderrun)?
+        } else {
+            declared
+        };
+        report.lengths_fixed += (len != declared) as usize;
+
+        let end = len.checked_add(pos + 12).ok_or(Error::BufferUnderrun)?;
+        let chunk = png.get(pos..end).ok_or(Error::BufferUnderrun)?;
+        chunks.push(RawChunk {
+            chunk_type: chunk[4..8].try_into()?,
+            data: &chunk[8..8 + len],
+            crc: chunk[8 + len..].try_into()?,
+        });
+        pos = end;
+
+        if &chunk[4..8] == b"IEND" {
+            break;
+        }
+    }
+
+    if chunks
+        .last()
+        .is_none_or(|chunk| &chunk.chunk_type != b"IEND")
+    {
+        report.iend_added = true;
+        let crc = checksum::crc32(0, b"IEND").to_be_bytes();
+        let chunk_type = *b"IEND";
+        chunks.push(RawChunk {
+            chunk_type,
+            data: &[],
+            crc,
+        });
+    }
+
+    if options.reorder {
+        let mut before_idat = true;
+        let mut ranked: Vec<_> = chunks
+            .into_iter()
+            .map(|chunk| {
+                before_idat &= &chunk.chunk_type != b"IDAT";
+                (chunk_rank(&chunk.chunk_type, before_idat), chunk)
+            })
+            .collect();
+        report.reordered = ranked.windows(2).any(|pair| pair[0].0 > pair[1].0);
+        ranked.sort_by_key(|(rank, _)| *rank);
+        chunks = ranked.into_iter().map(|(_, chunk)| chunk).collect();
+    }
+
+    let mut out = Vec::from(SIGNATURE);
+    for chunk in &chunks {
+        out.extend_from_slice(&(chunk.data.len() as u32).to_be_bytes());
+        out.extend_from_slice(&chunk.chunk_type);
+        out.extend_from_slice(chunk.data);
+        let crc = checksum::crc32(checksum::crc32(0, &chunk.chunk_type), chunk.data).to_be_bytes();
+        if options.fix_crcs && crc != chunk.crc {
+            report.crcs_fixed += 1;
+            out.extend_from_slice(&crc);
+        } else {
+            out.extend_from_slice(&chunk.crc);
+        }
+    }
+
+    Ok((out, report))
+}
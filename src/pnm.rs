@@ -0,0 +1,64 @@
+// Netpbm export of decoded images, binary variants with 8 bit samples.
+// * https://netpbm.sourceforge.net/doc/pgm.html
+// * https://netpbm.sourceforge.net/doc/ppm.html
+// * https://netpbm.sourceforge.net/doc/pam.html
+use crate::png_read::{Error, Result};
+use alloc::{format, vec::Vec};
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PnmFormat {
+    // greyscale (P5), the alpha channel is dropped
+    Pgm,
+    // RGB (P6), the alpha channel is dropped
+    Ppm,
+    // RGB_ALPHA (P7)
+    Pam,
+}
+
+// ----------------------------------------------------------------------------
+// ITU-R BT.601 luma of an RGB pixel
+fn luma(px: &[u8]) -> u8 {
+    ((px[0] as u32 * 299 + px[1] as u32 * 587 + px[2] as u32 * 114 + 500) / 1000) as u8
+}
+
+// ----------------------------------------------------------------------------
+// Converts packed RGBA8 pixels as returned by `png_read::to_rgba` or `read_png_rgba` into a
+// complete PGM, PPM or PAM file.
+pub fn rgba_to_pnm(width: usize, height: usize, rgba: &[u8], format: PnmFormat) -> Result<Vec<u8>> {
+    let size = width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(4))
+        .ok_or(Error::InvalidPng)?;
+    let rgba = rgba.get(..size).ok_or(Error::BufferUnderrun)?;
+
+    let (header, channels) = match format {
+        PnmFormat::Pgm => (format!("P5\n{width} {height}\n255\n"), 1),
+        PnmFormat::Ppm => (format!("P6\n{width} {height}\n255\n"), 3),
+        PnmFormat::Pam => (
+            format!(
+                "P7\nWIDTH {width}\nHEIGHT {height}\nDEPTH 4\nMAXVAL 255\n\
+                 TUPLTYPE RGB_ALPHA\nENDHDR\n"
+            ),
+            4,
+        ),
+    };
+
+    let mut out = Vec::with_capacity(header.len() + size / 4 * channels);
+    out.extend_from_slice(header.as_bytes());
+    for px in rgba.chunks_exact(4) {
+        match format {
+            PnmFormat::Pgm => out.push(luma(px)),
+            PnmFormat::Ppm => out.extend_from_slice(&px[..3]),
+            PnmFormat::Pam => out.extend_from_slice(px),
+        }
+    }
+    Ok(out)
+}
+
+// ----------------------------------------------------------------------------
+// Decodes a PNG file straight into a PGM, PPM or PAM file.
+pub fn png_to_pnm(png: &[u8], format: PnmFormat) -> crate::Result<Vec<u8>> {
+    let (width, height, rgba) = crate::read_png_rgba(png)?;
+    Ok(rgba_to_pnm(width, height, &rgba, format)?)
+}
@@ -0,0 +1,26 @@
+// Commonly used items, `use miniz::prelude::*;` brings them into scope.
+pub use crate::inflate::inflate;
+#[cfg(all(feature = "std", feature = "png"))]
+pub use crate::png_decode_batch;
+#[cfg(feature = "png")]
+pub use crate::png_read::{png_read, PNGChunkIHDR, PNGColorType};
+#[cfg(feature = "png")]
+pub use crate::read_png_rgba;
+#[cfg(feature = "zip")]
+pub use crate::unzip_file;
+#[cfg(all(feature = "std", feature = "png", feature = "zip"))]
+pub use crate::zip_decode_png_batch;
+#[cfg(feature = "zip")]
+pub use crate::zip_read::{zip_open, zip_read};
+#[cfg(all(feature = "std", feature = "zip"))]
+pub use crate::zip_read_many;
+#[cfg(all(feature = "png", feature = "zip"))]
+pub use crate::zip_read_png;
+pub use crate::{
+    compress, compress_to_vec, decompress_to_vec, decompress_to_vec_gzip,
+    decompress_to_vec_gzip_members, decompress_to_vec_members, decompress_to_vec_zlib, uncompress,
+    Member,
+};
+#[cfg(feature = "std")]
+pub use crate::{decompress_batch, deflate_parallel, BatchOptions};
+pub use crate::{Error, Result};
@@ -0,0 +1,223 @@
+// `std::io::Read` adapters that decompress on demand, counterparts of `flate2::read` decoders.
+// * deflate blocks are decoded one at a time, memory is the 32 KB window plus the current block
+//   independent of the stream size
+// * `GzipReader` reads a single member, zlib and gzip trailers are verified at the end of the
+//   stream
+// * the inner reader is read ahead in chunks, its position after the stream is unspecified
+// * decoding errors are returned as `io::ErrorKind::InvalidData` wrapping `crate::Error`
+use crate::checksum;
+use crate::file::{invalid_data, Input};
+use crate::inflate::{self, DecoderState, Error, InflateMetrics};
+use std::io::{self, Read};
+
+// ----------------------------------------------------------------------------
+const WINDOW_SIZE: usize = 1 << 15;
+
+// ----------------------------------------------------------------------------
+// Decodes the raw deflate stream at the input position block by block.
+struct Blocks<R> {
+    input: Input<R>,
+    window: Vec<u8>,
+    // decoded bytes not returned yet
+    pos: usize,
+    end: usize,
+    last: bool,
+    state: DecoderState,
+}
+
+// ----------------------------------------------------------------------------
+impl<R: Read> Blocks<R> {
+    fn new(input: Input<R>) -> Self {
+        Blocks {
+            input,
+            window: vec![0u8; 2 * WINDOW_SIZE],
+            pos: 0,
+            end: 0,
+            last: false,
+            state: DecoderState::default(),
+        }
+    }
+
+    // Returns 0 at the end of the stream.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.end && !self.last {
+            self.next_block()?;
+        }
+        let len = buf.len().min(self.end - self.pos);
+        buf[..len].copy_from_slice(&self.window[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+
+    fn next_block(&mut self) -> io::Result<()> {
+        // keep the window for back-references of the next block
+        let keep = self.end.min(WINDOW_SIZE);
+        self.window.copy_within(self.end - keep..self.end, 0);
+        (self.pos, self.end) = (keep, keep);
+
+        let input = &mut self.input;
+        loop {
+            match inflate::inflate_block(
+                &mut self.window,
+                keep,
+                &input.data,
+                input.bit_pos,
+                &mut self.state,
+                inflate::MAX_WINDOW_SIZE,
+                &mut InflateMetrics::default(),
+            ) {
+                Ok((last, bit_pos, end)) => {
+                    input.bit_pos = bit_pos;
+                    input.discard_bytes();
+                    (self.end, self.last) = (end, last);
+                    return Ok(());
+                }
+                Err(Error::Underflow) => input.fill()?,
+                Err(Error::Overflow) => self.window.resize(self.window.len() * 2, 0),
+                Err(err) => return Err(invalid_data(err)),
+            }
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Raw RFC 1951 deflate stream.
+pub struct InflateReader<R> {
+    blocks: Blocks<R>,
+}
+
+// ----------------------------------------------------------------------------
+impl<R: Read> InflateReader<R> {
+    pub fn new(reader: R) -> Self {
+        InflateReader {
+            blocks: Blocks::new(Input::new(reader)),
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        self.blocks.input.get_ref()
+    }
+}
+
+// ----------------------------------------------------------------------------
+impl<R: Read> Read for InflateReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.blocks.read(buf)
+    }
+}
+
+// ----------------------------------------------------------------------------
+// RFC 1950 zlib stream, streams with a preset dictionary are rejected.
+pub struct ZlibReader<R> {
+    blocks: Blocks<R>,
+    header: bool,
+    adler: u32,
+    done: bool,
+}
+
+// ----------------------------------------------------------------------------
+impl<R: Read> ZlibReader<R> {
+    pub fn new(reader: R) -> Self {
+        ZlibReader {
+            blocks: Blocks::new(Input::new(reader)),
+            header: false,
+            adler: 1,
+            done: false,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        self.blocks.input.get_ref()
+    }
+}
+
+// ----------------------------------------------------------------------------
+impl<R: Read> Read for ZlibReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+        if !self.header {
+            let header = self.blocks.input.take_aligned(2)?;
+            inflate::zlib_header(&header).map_err(invalid_data)?;
+            self.header = true;
+        }
+
+        let len = self.blocks.read(buf)?;
+        self.adler = checksum::adler32(self.adler, &buf[..len]);
+        if len == 0 {
+            let trailer = self.blocks.input.take_aligned(4)?;
+            if trailer != self.adler.to_be_bytes() {
+                return Err(invalid_data(Error::ChecksumMismatch));
+            }
+            self.done = true;
+        }
+        Ok(len)
+    }
+}
+
+// ----------------------------------------------------------------------------
+// RFC 1952 gzip member, the header CRC is verified if present.
+pub struct GzipReader<R> {
+    blocks: Blocks<R>,
+    header: bool,
+    crc: u32,
+    size: u32,
+    done: bool,
+}
+
+// ----------------------------------------------------------------------------
+impl<R: Read> GzipReader<R> {
+    pub fn new(reader: R) -> Self {
+        GzipReader {
+            blocks: Blocks::new(Input::new(reader)),
+            header: false,
+            crc: 0,
+            size: 0,
+            done: false,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        self.blocks.input.get_ref()
+    }
+
+    fn read_header(&mut self) -> io::Result<()> {
+        let input = &mut self.blocks.input;
+        let header_len = loop {
+            match inflate::gzip_header(&input.data) {
+                Ok(len) => break len,
+                Err(Error::Underflow) => input.fill()?,
+                Err(err) => return Err(invalid_data(err)),
+            }
+        };
+        input.bit_pos = header_len * 8;
+        input.discard_bytes();
+        self.header = true;
+        Ok(())
+    }
+}
+
+// ----------------------------------------------------------------------------
+impl<R: Read> Read for GzipReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+        if !self.header {
+            self.read_header()?;
+        }
+
+        let len = self.blocks.read(buf)?;
+        self.crc = checksum::crc32(self.crc, &buf[..len]);
+        self.size = self.size.wrapping_add(len as u32);
+        if len == 0 {
+            let trailer = self.blocks.input.take_aligned(8)?;
+            if trailer[..4] != self.crc.to_le_bytes() || trailer[4..] != self.size.to_le_bytes() {
+                return Err(invalid_data(Error::ChecksumMismatch));
+            }
+            self.done = true;
+        }
+        Ok(len)
+    }
+}
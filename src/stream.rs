@@ -0,0 +1,866 @@
+// Streaming compression and decompression with flate2 backend semantics.
+// * `run(input, output, flush)` consumes input and produces output incrementally, `total_in` and
+//   `total_out` count the bytes processed since creation or the last `reset`
+// * compression buffers input in chunks of 128 KB compressed by `deflate`, matches reach into
+//   the last 32 KB of earlier chunks unless a full flush came in between
+// * `FlushCompress::Sync` ends the output at a byte boundary with an empty stored block, so the
+//   peer can decode everything so far (zlib's Z_SYNC_FLUSH, as WebSocket permessage-deflate
+//   needs), `Full` additionally drops the window (Z_FULL_FLUSH)
+// * decompression decodes the input as it arrives, `FlushDecompress::Block` returns the data up
+//   to the end of the next complete block only, the position after each block is reported as
+//   `BlockBoundary`
+// * `Inflater` decodes from the first call on and reports consumed and produced bytes, only the
+//   window and the input of a symbol cut off by the end of the input are buffered
+// * `FlushCompress::Full` and the optional restart interval mark positions where decompression
+//   can start without any preceding data, their offsets are reported as `RestartPoint`s
+// * `reset` keeps the allocated buffers, a single object can be reused for many small messages
+// * optional per-block `BlockStats` report what the encoder produced for tuning
+// * `Decompress` can be cloned mid-stream (zlib's `inflateCopy`) to try alternative
+//   continuations from a snapshot
+// * `run_vectored` takes segmented input and fills a list of output buffers in order, for ring
+//   buffers and scattered network memory
+// * `Inflater::save_state` serializes the decoder between calls, `Inflater::from_state` resumes
+//   it later, e.g. in another process once more of a download has arrived
+pub use crate::deflate::{BlockStats, BlockType};
+use crate::deflate::{CompressionLevel, Strategy};
+use crate::inflate::{BlockPart, InflateMetrics};
+use crate::{checksum, deflate, inflate, Result};
+use alloc::vec::Vec;
+
+// ----------------------------------------------------------------------------
+// input compressed at once unless a flush comes first
+const CHUNK_SIZE: usize = 1 << 17;
+
+// longest deflate match, the decoding window is moved back before less space is left
+const MAX_MATCH_LEN: usize = 258;
+
+// "MZIS" and the format version of a saved `Inflater` state
+const STATE_MAGIC: [u8; 4] = *b"MZIS";
+const STATE_VERSION: u8 = 2;
+
+// flags of a saved `Inflater` state
+const STATE_ZLIB: u8 = 0x01;
+const STATE_FINISHED: u8 = 0x02;
+const STATE_STARTED: u8 = 0x04;
+const STATE_LAST: u8 = 0x08;
+const STATE_BOUNDARY: u8 = 0x10;
+const STATE_BOUNDARY_LAST: u8 = 0x20;
+
+// position within the current block of a saved `Inflater` state, the low bit marks the final block
+const PART_HEADER: u8 = 0x00;
+const PART_STORED: u8 = 0x02;
+const PART_FIXED: u8 = 0x04;
+const PART_DYNAMIC: u8 = 0x06;
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    // progress was made, more input or output space may be needed
+    Ok,
+    // no progress was possible
+    BufError,
+    // the end of the stream was reached and all output has been returned
+    StreamEnd,
+}
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlushCompress {
+    None,
+    // output all pending data and align to a byte boundary
+    Sync,
+    Partial,
+    Full,
+    // output all pending data and terminate the stream
+    Finish,
+}
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlushDecompress {
+    None,
+    Sync,
+    // all input has been provided
+    Finish,
+    // decode up to the end of the next deflate block if the buffered input contains all of it,
+    // the stream is decoded block by block from then on (zlib's Z_BLOCK)
+    Block,
+}
+
+// ----------------------------------------------------------------------------
+// End of a decoded deflate block.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlockBoundary {
+    // bits of the stream before the next block, including the zlib header
+    pub bit_pos: u64,
+    // output bytes up to the end of the block
+    pub total_out: u64,
+    // the block was the final one of the stream
+    pub last: bool,
+}
+
+// ----------------------------------------------------------------------------
+// Offsets of a full flush point: decoding the raw deflate data from `total_out` on yields the
+// input from `total_in` on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RestartPoint {
+    pub total_in: u64,
+    pub total_out: u64,
+}
+
+// ----------------------------------------------------------------------------
+// Encoded data waiting for output space.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Pending {
+    pub(crate) data: Vec<u8>,
+    pos: usize,
+}
+
+// ----------------------------------------------------------------------------
+impl Pending {
+    pub(crate) fn write_to(&mut self, output: &mut [u8]) -> usize {
+        let len = output.len().min(self.data.len() - self.pos);
+        output[..len].copy_from_slice(&self.data[self.pos..self.pos + len]);
+        self.pos += len;
+        if self.pos == self.data.len() {
+            self.data.clear();
+            self.pos = 0;
+        }
+        len
+    }
+
+    // fills the buffers in order with up to `max` bytes, returns the number of bytes written
+    pub(crate) fn write_to_vectored(&mut self, outputs: &mut [&mut [u8]], max: usize) -> usize {
+        let mut written = 0;
+        for output in outputs.iter_mut() {
+            if self.is_empty() || written == max {
+                break;
+            }
+            let len = output.len().min(max - written);
+            written += self.write_to(&mut output[..len]);
+        }
+        written
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pos == self.data.len()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.data.clear();
+        self.pos = 0;
+    }
+}
+
+// ----------------------------------------------------------------------------
+#[derive(Debug)]
+pub struct Compress {
+    level: CompressionLevel,
+    zlib_header: bool,
+    started: bool,
+    flushed: bool,
+    finished: bool,
+    adler: u32,
+    // the window of earlier data in front of the input not compressed yet
+    input: Vec<u8>,
+    history: usize,
+    output: Pending,
+    total_in: u64,
+    total_out: u64,
+    restart_interval: usize,
+    since_restart: usize,
+    restart_points: Vec<RestartPoint>,
+    block_stats: Option<Vec<BlockStats>>,
+}
+
+// ----------------------------------------------------------------------------
+impl Compress {
+    // `level` ranges from 0 (none) to 9 (best), `zlib_header` selects a zlib instead of a raw
+    // deflate stream.
+    pub fn new(level: u8, zlib_header: bool) -> Self {
+        Compress {
+            level: level.into(),
+            zlib_header,
+            started: false,
+            flushed: false,
+            finished: false,
+            adler: 1,
+            input: Vec::new(),
+            history: 0,
+            output: Pending::default(),
+            total_in: 0,
+            total_out: 0,
+            restart_interval: 0,
+            since_restart: 0,
+            restart_points: Vec::new(),
+            block_stats: None,
+        }
+    }
+
+    pub fn total_in(&self) -> u64 {
+        self.total_in
+    }
+
+    pub fn total_out(&self) -> u64 {
+        self.total_out
+    }
+
+    // Inserts a full flush after every `interval` input bytes, 0 disables it. Lowering the
+    // interval below the input since the last restart point flushes on the next call.
+    pub fn set_restart_interval(&mut self, interval: usize) {
+        self.restart_interval = interval;
+    }
+
+    // Full flush points so far, in stream order.
+    pub fn restart_points(&self) -> &[RestartPoint] {
+        &self.restart_points
+    }
+
+    // Collects `BlockStats` for every block emitted from now on.
+    pub fn set_block_stats(&mut self, enabled: bool) {
+        self.block_stats = enabled.then(Vec::new);
+    }
+
+    // Statistics of the blocks emitted so far, empty unless enabled with `set_block_stats`.
+    pub fn block_stats(&self) -> &[BlockStats] {
+        self.block_stats.as_deref().unwrap_or_default()
+    }
+
+    // Clears the stream state, the restart interval and whether block statistics are collected
+    // are kept, so are the allocated buffers.
+    pub fn reset(&mut self) {
+        self.started = false;
+        self.flushed = false;
+        self.finished = false;
+        self.adler = 1;
+        self.input.clear();
+        self.history = 0;
+        self.output.clear();
+        self.total_in = 0;
+        self.total_out = 0;
+        self.since_restart = 0;
+        self.restart_points.clear();
+        if let Some(stats) = &mut self.block_stats {
+            stats.clear();
+        }
+    }
+
+    pub fn run(&mut self, input: &[u8], output: &mut [u8], flush: FlushCompress) -> Status {
+        self.run_vectored(&[input], &mut [output], flush)
+    }
+
+    // Same as `run` with the concatenation of `inputs` as input, `flush` applies after the last
+    // slice.
+    pub fn run_vectored(
+        &mut self,
+        inputs: &[&[u8]],
+        outputs: &mut [&mut [u8]],
+        flush: FlushCompress,
+    ) -> Status {
+        let consumed: usize = if self.finished {
+            0
+        } else {
+            inputs.iter().map(|input| input.len()).sum()
+        };
+        if !self.finished {
+            match inputs.split_last() {
+                Some((last, head)) => {
+                    for input in head {
+                        self.encode(input, FlushCompress::None);
+                    }
+                    self.encode(last, flush);
+                }
+                None => self.encode(&[], flush),
+            }
+        }
+
+        let written = self.output.write_to_vectored(outputs, usize::MAX);
+        self.total_out += written as u64;
+
+        if self.finished && self.output.is_empty() {
+            Status::StreamEnd
+        } else if consumed == 0 && written == 0 {
+            Status::BufError
+        } else {
+            Status::Ok
+        }
+    }
+
+    fn encode(&mut self, input: &[u8], flush: FlushCompress) {
+        if !self.started && self.zlib_header {
+            self.output.data.extend_from_slice(&deflate::ZLIB_HEADER);
+        }
+        self.started = true;
+        self.adler = checksum::adler32(self.adler, input);
+
+        let mut input = input;
+        while self.restart_interval != 0
+            && self.since_restart + input.len() >= self.restart_interval
+        {
+            // the interval may have been lowered below the input since the last restart point
+            let len = self.restart_interval.saturating_sub(self.since_restart);
+            let (head, tail) = input.split_at(len);
+            self.buffer(head);
+            self.full_flush();
+            input = tail;
+        }
+        self.buffer(input);
+
+        match flush {
+            FlushCompress::None => {}
+            // repeated flushes without new input only drain the output
+            FlushCompress::Sync | FlushCompress::Partial | FlushCompress::Full if self.flushed => {}
+            FlushCompress::Sync | FlushCompress::Partial => self.sync_flush(),
+            FlushCompress::Full => self.full_flush(),
+            FlushCompress::Finish => {
+                self.compress(self.pending(), true);
+                if self.zlib_header {
+                    let adler = self.adler.to_be_bytes();
+                    self.output.data.extend_from_slice(&adler);
+                }
+                self.finished = true;
+            }
+        }
+    }
+
+    // compresses full chunks, keeps the remainder for the next call
+    fn buffer(&mut self, input: &[u8]) {
+        self.input.extend_from_slice(input);
+        self.total_in += input.len() as u64;
+        self.since_restart += input.len();
+        self.flushed &= input.is_empty();
+
+        while self.pending() >= CHUNK_SIZE {
+            self.compress(CHUNK_SIZE, false);
+        }
+    }
+
+    // buffered input bytes not compressed yet
+    fn pending(&self) -> usize {
+        self.input.len() - self.history
+    }
+
+    // compresses the first `len` pending input bytes, ended by a sync flush unless `last`
+    fn compress(&mut self, len: usize, last: bool) {
+        // without input, a stored block is smaller than a Huffman block with only its end code
+        let level = match len {
+            0 => CompressionLevel::NoCompression,
+            _ => self.level,
+        };
+        let end = self.history + len;
+        let input = &self.input[..end];
+        let stats = self.block_stats.as_mut();
+        let out = &mut self.output.data;
+        let (history, strategy) = (self.history, Strategy::Default);
+        deflate::deflate_chunk_from(out, input, history, level, strategy, last, stats);
+
+        // the window for the next chunk
+        let window = end.min(inflate::MAX_WINDOW_SIZE);
+        self.input.drain(..end - window);
+        self.history = window;
+    }
+
+    fn sync_flush(&mut self) {
+        self.compress(self.pending(), false);
+        self.flushed = true;
+    }
+
+    // no later match refers to data in front of a full flush
+    fn full_flush(&mut self) {
+        self.sync_flush();
+        self.input.drain(..self.history);
+        self.history = 0;
+        self.since_restart = 0;
+        self.restart_points.push(RestartPoint {
+            total_in: self.total_in,
+            total_out: self.total_out + self.output.len() as u64,
+        });
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Block-by-block decoding state, `input` holds the buffered stream from byte `discarded` on.
+#[derive(Debug, Clone, Default)]
+struct BlockState {
+    window: Vec<u8>,
+    dptr: usize,
+    bit_pos: usize,
+    discarded: u64,
+    started: bool,
+    last: bool,
+    adler: u32,
+    decoded: u64,
+    boundary: Option<BlockBoundary>,
+    // input bytes behind the end of the stream, not counted as consumed
+    trailing: usize,
+    // position within the current block
+    part: inflate::BlockPart,
+    tables: inflate::DecoderState,
+    metrics: InflateMetrics,
+}
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone)]
+pub struct Decompress {
+    zlib_header: bool,
+    finished: bool,
+    input: Vec<u8>,
+    output: Pending,
+    total_in: u64,
+    total_out: u64,
+    blocks: Option<BlockState>,
+}
+
+// ----------------------------------------------------------------------------
+impl Decompress {
+    // `zlib_header` expects a zlib instead of a raw deflate stream.
+    pub fn new(zlib_header: bool) -> Self {
+        Decompress {
+            zlib_header,
+            finished: false,
+            input: Vec::new(),
+            output: Pending::default(),
+            total_in: 0,
+            total_out: 0,
+            blocks: None,
+        }
+    }
+
+    pub fn total_in(&self) -> u64 {
+        self.total_in
+    }
+
+    pub fn total_out(&self) -> u64 {
+        self.total_out
+    }
+
+    // The end of the last complete block.
+    pub fn block_boundary(&self) -> Option<BlockBoundary> {
+        self.blocks.as_ref().and_then(|blocks| blocks.boundary)
+    }
+
+    // Counters of the blocks decoded so far, zero without the `metrics` feature.
+    pub fn metrics(&self) -> InflateMetrics {
+        self.blocks
+            .as_ref()
+            .map_or(InflateMetrics::default(), |blocks| blocks.metrics)
+    }
+
+    // Clears the stream state and keeps the allocated buffers.
+    pub fn reset(&mut self, zlib_header: bool) {
+        self.zlib_header = zlib_header;
+        self.finished = false;
+        self.input.clear();
+        self.output.clear();
+        self.total_in = 0;
+        self.total_out = 0;
+        self.blocks = None;
+    }
+
+    // Decodes the input up to its last complete symbol and returns as much of the decoded data as
+    // fits, the rest with the next calls. `FlushDecompress::Finish` fails with `Underflow` if the
+    // stream is incomplete.
+    pub fn run(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        flush: FlushDecompress,
+    ) -> Result<Status> {
+        self.run_vectored(&[input], &mut [output], flush)
+    }
+
+    // Same as `run` with the concatenation of `inputs` as input.
+    pub fn run_vectored(
+        &mut self,
+        inputs: &[&[u8]],
+        outputs: &mut [&mut [u8]],
+        flush: FlushDecompress,
+    ) -> Result<Status> {
+        let mut consumed: usize = if self.finished {
+            0
+        } else {
+            inputs.iter().map(|input| input.len()).sum()
+        };
+        if !self.finished {
+            for input in inputs {
+                self.input.extend_from_slice(input);
+            }
+
+            match flush {
+                FlushDecompress::Block => self.decode_blocks(true, false)?,
+                FlushDecompress::Finish => self.decode_blocks(false, true)?,
+                FlushDecompress::None | FlushDecompress::Sync => {
+                    self.decode_blocks(false, false)?
+                }
+            }
+            consumed -= self.blocks.as_ref().map_or(0, |blocks| blocks.trailing);
+        }
+
+        self.total_in += consumed as u64;
+        // with `Block` the decoded part of an incomplete block is held back
+        let max = match (flush, self.block_boundary()) {
+            (FlushDecompress::Block, None) => 0,
+            (FlushDecompress::Block, Some(boundary)) => {
+                boundary.total_out.saturating_sub(self.total_out) as usize
+            }
+            _ => usize::MAX,
+        };
+        let written = self.output.write_to_vectored(outputs, max);
+        self.total_out += written as u64;
+
+        Ok(if self.finished && self.output.is_empty() {
+            Status::StreamEnd
+        } else if consumed == 0 && written == 0 {
+            Status::BufError
+        } else {
+            Status::Ok
+        })
+    }
+
+    // Decodes complete blocks from the buffered input, a single one with `one`. Missing input
+    // fails only with `finish`, otherwise decoding continues on the next call.
+    fn decode_blocks(&mut self, one: bool, finish: bool) -> Result<()> {
+        let state = self.blocks.get_or_insert_with(BlockState::default);
+        if !state.started {
+            if self.zlib_header {
+                match inflate::zlib_header(&self.input) {
+                    Err(inflate::Error::Underflow) if !finish => return Ok(()),
+                    result => result?,
+                };
+                state.bit_pos = 16;
+                state.adler = 1;
+            }
+            state.window.resize(2 * inflate::MAX_WINDOW_SIZE, 0);
+            state.started = true;
+        }
+
+        while !state.last {
+            let step = inflate::inflate_block_part(
+                &mut state.window,
+                state.dptr,
+                &self.input,
+                state.bit_pos,
+                &mut state.tables,
+                &mut state.part,
+                finish,
+                &mut state.metrics,
+            );
+            match step {
+                Ok((complete, bit_pos, end)) => {
+                    let data = &state.window[state.dptr..end];
+                    if self.zlib_header {
+                        state.adler = checksum::adler32(state.adler, data);
+                    }
+                    self.output.data.extend_from_slice(data);
+                    state.decoded += data.len() as u64;
+                    state.bit_pos = bit_pos;
+                    state.dptr = end;
+
+                    // keep the window for back-references once the longest match may not fit
+                    if state.window.len() - end < MAX_MATCH_LEN {
+                        let keep = end.min(inflate::MAX_WINDOW_SIZE);
+                        state.window.copy_within(end - keep..end, 0);
+                        state.dptr = keep;
+                    }
+                    if let Some(last) = complete {
+                        state.last = last;
+                        state.boundary = Some(BlockBoundary {
+                            bit_pos: 8 * state.discarded + bit_pos as u64,
+                            total_out: state.decoded,
+                            last,
+                        });
+                        if one {
+                            break;
+                        }
+                    }
+                }
+                Err(inflate::Error::Underflow) if !finish => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        // drop consumed input
+        let bytes = state.bit_pos / 8;
+        self.input.drain(..bytes);
+        state.bit_pos -= 8 * bytes;
+        state.discarded += bytes as u64;
+
+        if state.last {
+            let start = state.bit_pos.div_ceil(8);
+            let mut end = start;
+            if self.zlib_header {
+                end += 4;
+                match self.input.get(start..end) {
+                    Some(trailer) if *trailer == state.adler.to_be_bytes() => {}
+                    Some(_) => return Err(inflate::Error::ChecksumMismatch.into()),
+                    None if finish => return Err(inflate::Error::Underflow.into()),
+                    None => return Ok(()),
+                }
+            }
+            state.trailing = self.input.len().saturating_sub(end);
+            self.input.clear();
+            self.finished = true;
+        }
+        Ok(())
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Resumable decoder for data arriving in pieces from a socket or a read loop. Input is decoded
+// up to its last complete symbol as soon as it arrives, input behind the end of the stream is not
+// consumed. The next call continues within the block, only a block header cut off by the end of
+// the input is read again.
+#[derive(Debug, Clone)]
+pub struct Inflater {
+    inner: Decompress,
+}
+
+// ----------------------------------------------------------------------------
+impl Inflater {
+    // `zlib_header` expects a zlib instead of a raw deflate stream.
+    pub fn new(zlib_header: bool) -> Self {
+        let mut inner = Decompress::new(zlib_header);
+        inner.blocks = Some(BlockState::default());
+        Inflater { inner }
+    }
+
+    pub fn total_in(&self) -> u64 {
+        self.inner.total_in
+    }
+
+    pub fn total_out(&self) -> u64 {
+        self.inner.total_out
+    }
+
+    // The end of the stream has been reached, decoded data may still be waiting for output.
+    pub fn is_finished(&self) -> bool {
+        self.inner.finished
+    }
+
+    // Bytes decoded so far, including the ones still waiting for output.
+    pub(crate) fn total_decoded(&self) -> u64 {
+        self.inner.total_out + self.inner.output.len() as u64
+    }
+
+    // Counters of the blocks decoded so far, zero without the `metrics` feature.
+    pub fn metrics(&self) -> InflateMetrics {
+        self.inner.metrics()
+    }
+
+    // Clears the stream state and keeps the allocated buffers.
+    pub fn reset(&mut self, zlib_header: bool) {
+        self.inner.reset(zlib_header);
+        self.inner.blocks = Some(BlockState::default());
+    }
+
+    // Returns the status with the number of bytes consumed from `input` and written to
+    // `output`. Decoded data that does not fit is returned by the next calls.
+    pub fn decompress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> Result<(Status, usize, usize)> {
+        let (total_in, total_out) = (self.inner.total_in, self.inner.total_out);
+        let status = self.inner.run(input, output, FlushDecompress::None)?;
+        let consumed = (self.inner.total_in - total_in) as usize;
+        let produced = (self.inner.total_out - total_out) as usize;
+        Ok((status, consumed, produced))
+    }
+
+    // Serializes the decoder: the last 32 KB of output for back-references, the input not
+    // decoded yet, decoded data not returned yet, positions and checksums, and the position within
+    // the current block with the code lengths of a dynamic block to rebuild its tables from. The
+    // `metrics` counters are not part of the state.
+    pub fn save_state(&self) -> Vec<u8> {
+        let inner = &self.inner;
+        let blocks = inner
+            .blocks
+            .as_ref()
+            .expect("an Inflater decodes block by block");
+        let boundary = blocks.boundary.unwrap_or(BlockBoundary {
+            bit_pos: 0,
+            total_out: 0,
+            last: false,
+        });
+        let flags = [
+            (inner.zlib_header, STATE_ZLIB),
+            (inner.finished, STATE_FINISHED),
+            (blocks.started, STATE_STARTED),
+            (blocks.last, STATE_LAST),
+            (blocks.boundary.is_some(), STATE_BOUNDARY),
+            (boundary.last, STATE_BOUNDARY_LAST),
+        ]
+        .iter()
+        .fold(
+            0,
+            |flags, &(set, flag)| if set { flags | flag } else { flags },
+        );
+
+        let pending = &inner.output.data[inner.output.pos..];
+        let window =
+            &blocks.window[blocks.dptr.saturating_sub(inflate::MAX_WINDOW_SIZE)..blocks.dptr];
+        let (part, remaining) = match blocks.part {
+            BlockPart::Header => (PART_HEADER, 0),
+            BlockPart::Stored { remaining, last } => (PART_STORED | last as u8, remaining),
+            BlockPart::Huffman { fixed: true, last } => (PART_FIXED | last as u8, 0),
+            BlockPart::Huffman { fixed: false, last } => (PART_DYNAMIC | last as u8, 0),
+        };
+        let (lengths_ll, lengths_d) = match blocks.part {
+            BlockPart::Huffman { fixed: false, .. } => blocks.tables.dynamic_lengths(),
+            _ => (&[][..], &[][..]),
+        };
+        let lengths = lengths_ll.len() + lengths_d.len();
+        let mut state =
+            Vec::with_capacity(128 + window.len() + inner.input.len() + pending.len() + lengths);
+        state.extend_from_slice(&STATE_MAGIC);
+        state.extend_from_slice(&[STATE_VERSION, flags]);
+        for value in [
+            inner.total_in,
+            inner.total_out,
+            blocks.discarded,
+            blocks.decoded,
+            boundary.bit_pos,
+            boundary.total_out,
+            blocks.bit_pos as u64,
+            blocks.trailing as u64,
+            remaining as u64,
+        ] {
+            state.extend_from_slice(&value.to_le_bytes());
+        }
+        state.extend_from_slice(&blocks.adler.to_le_bytes());
+        state.push(part);
+        for data in [window, &inner.input, pending, lengths_ll, lengths_d] {
+            state.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            state.extend_from_slice(data);
+        }
+        let crc = checksum::crc32(0, &state);
+        state.extend_from_slice(&crc.to_le_bytes());
+        state
+    }
+
+    // Restores a decoder saved with `save_state`, which fails with
+    // `inflate::Error::InvalidState` if `state` is truncated, corrupted or of another version.
+    pub fn from_state(state: &[u8]) -> Result<Self> {
+        Self::parse_state(state).ok_or(inflate::Error::InvalidState.into())
+    }
+
+    fn parse_state(state: &[u8]) -> Option<Self> {
+        let (state, crc) = state.split_last_chunk::<4>()?;
+        if checksum::crc32(0, state) != u32::from_le_bytes(*crc) {
+            return None;
+        }
+
+        let mut reader = StateReader(state);
+        if reader.bytes(4)? != STATE_MAGIC || reader.u8()? != STATE_VERSION {
+            return None;
+        }
+        let flags = reader.u8()?;
+        let total_in = reader.u64()?;
+        let total_out = reader.u64()?;
+        let discarded = reader.u64()?;
+        let decoded = reader.u64()?;
+        let boundary_bit_pos = reader.u64()?;
+        let boundary_total_out = reader.u64()?;
+        let bit_pos = usize::try_from(reader.u64()?).ok()?;
+        let trailing = usize::try_from(reader.u64()?).ok()?;
+        let remaining = usize::try_from(reader.u64()?).ok()?;
+        let adler = reader.u32()?;
+        let part = reader.u8()?;
+        let window = reader.vec()?;
+        let input = reader.vec()?;
+        let pending = reader.vec()?;
+        let lengths_ll = reader.vec()?;
+        let lengths_d = reader.vec()?;
+
+        let last = part & 1 != 0;
+        let part = match part & !1 {
+            PART_HEADER if !last && remaining == 0 => BlockPart::Header,
+            PART_STORED if remaining <= 0xffff => BlockPart::Stored { remaining, last },
+            PART_FIXED if remaining == 0 => BlockPart::Huffman { fixed: true, last },
+            PART_DYNAMIC if remaining == 0 => BlockPart::Huffman { fixed: false, last },
+            _ => return None,
+        };
+        let dynamic = part == BlockPart::Huffman { fixed: false, last };
+
+        let started = flags & STATE_STARTED != 0;
+        let consistent = reader.0.is_empty()
+            && flags & !0x3f == 0
+            && window.len() <= inflate::MAX_WINDOW_SIZE
+            && (started || window.is_empty() && part == BlockPart::Header)
+            && (flags & STATE_FINISHED != 0 || bit_pos <= 8 * input.len())
+            && (dynamic || lengths_ll.is_empty() && lengths_d.is_empty());
+        if !consistent {
+            return None;
+        }
+
+        let mut inflater = Inflater::new(flags & STATE_ZLIB != 0);
+        let inner = &mut inflater.inner;
+        inner.finished = flags & STATE_FINISHED != 0;
+        inner.input.extend_from_slice(input);
+        inner.output.data.extend_from_slice(pending);
+        inner.total_in = total_in;
+        inner.total_out = total_out;
+
+        let blocks = inner.blocks.as_mut()?;
+        if started {
+            blocks.window.resize(2 * inflate::MAX_WINDOW_SIZE, 0);
+            blocks.window[..window.len()].copy_from_slice(window);
+        }
+        if dynamic {
+            blocks
+                .tables
+                .set_dynamic_lengths(lengths_ll, lengths_d)
+                .ok()?;
+        }
+        blocks.part = part;
+        blocks.dptr = window.len();
+        blocks.bit_pos = bit_pos;
+        blocks.discarded = discarded;
+        blocks.started = started;
+        blocks.last = flags & STATE_LAST != 0;
+        blocks.adler = adler;
+        blocks.decoded = decoded;
+        blocks.boundary = (flags & STATE_BOUNDARY != 0).then_some(BlockBoundary {
+            bit_pos: boundary_bit_pos,
+            total_out: boundary_total_out,
+            last: flags & STATE_BOUNDARY_LAST != 0,
+        });
+        blocks.trailing = trailing;
+        Some(inflater)
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Little-endian fields of a saved `Inflater` state, `None` once the data runs out.
+struct StateReader<'a>(&'a [u8]);
+
+// ----------------------------------------------------------------------------
+impl<'a> StateReader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let (head, tail) = self.0.split_at_checked(len)?;
+        self.0 = tail;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.bytes(4)?.try_into().ok()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.bytes(8)?.try_into().ok()?))
+    }
+
+    // length-prefixed bytes
+    fn vec(&mut self) -> Option<&'a [u8]> {
+        let len = usize::try_from(self.u64()?).ok()?;
+        self.bytes(len)
+    }
+}
@@ -0,0 +1,32 @@
+// Optional tracing and metrics instrumentation.
+// * the macros forward to the `tracing` crate with the `tracing` feature and expand to nothing
+//   otherwise, so instrumentation has no cost in default builds
+// * `metric!` statements only exist with the `metrics` feature
+
+// ----------------------------------------------------------------------------
+macro_rules! trace_event {
+    ($level:ident, $($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        tracing::$level!($($arg)+);
+    };
+}
+
+// ----------------------------------------------------------------------------
+// Enters a span that lasts until the end of the enclosing block.
+macro_rules! trace_span {
+    ($($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!($($arg)+).entered();
+    };
+}
+
+// ----------------------------------------------------------------------------
+// Updates a counter, e.g. `metric!(metrics.stored_blocks += 1)`.
+macro_rules! metric {
+    ($($arg:tt)+) => {
+        #[cfg(feature = "metrics")]
+        {
+            $($arg)+;
+        }
+    };
+}
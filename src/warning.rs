@@ -0,0 +1,49 @@
+// Non-fatal anomalies found while decoding.
+// * lenient decoding continues past these, strict callers can collect them via
+//   `InflateOptions::warnings` and reject the input
+// * `InflateOptions::strict_containers` turns the PNG CRC, data after IEND and ZIP layout
+//   anomalies into errors
+use alloc::string::String;
+#[cfg(any(feature = "png", feature = "zip"))]
+use alloc::vec::Vec;
+#[cfg(any(feature = "png", feature = "zip"))]
+use core::cell::RefCell;
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Warning {
+    // CRC mismatch in an ancillary PNG chunk, the chunk was ignored
+    PngChunkCrc { chunk: u32 },
+    // PNG chunk that is not interpreted by the decoder
+    PngChunkSkipped { chunk: u32 },
+    // ancillary PNG chunk with invalid contents, the chunk was ignored
+    PngChunkInvalid { chunk: u32 },
+    // compressed ancillary PNG chunk decoding to more than the size limit, the chunk was ignored
+    PngChunkTooLarge { chunk: u32 },
+    // PNG chunk out of order or repeated, accepted by `InflateOptions::lenient_chunk_order`
+    PngChunkMisplaced { chunk: u32 },
+    // bytes following the IEND chunk
+    PngDataAfterIend { len: usize },
+    // absolute path or `..` component in a ZIP entry name
+    ZipSuspiciousPath { name: String },
+    // bytes in front of a ZIP archive whose offsets don't count them, e.g. a self-extractor stub
+    ZipPrefix { len: usize },
+    // end of central directory record not consistent with the archive layout
+    ZipSuspiciousEocd { offset: usize },
+}
+
+// ----------------------------------------------------------------------------
+impl core::fmt::Display for Warning {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(any(feature = "png", feature = "zip"))]
+pub(crate) fn warn(sink: Option<&RefCell<Vec<Warning>>>, warning: Warning) {
+    if let Some(sink) = sink {
+        sink.borrow_mut().push(warning);
+    }
+}
@@ -0,0 +1,92 @@
+// wasm-bindgen bindings for inflating and PNG decoding in the browser.
+// * inflate_to_vec(bytes, max_output) returns the decoded raw deflate stream
+// * decode_png(bytes) returns width, height and RGBA8 pixels ready for `new ImageData(...)`, with
+//   the `png` feature
+// * `--no-default-features --features wasm` leaves out the PNG and ZIP parsers, see the `wasm`
+//   profile in Cargo.toml for a size-optimized build
+use crate::inflate;
+#[cfg(feature = "png")]
+use crate::png_read;
+use wasm_bindgen::prelude::*;
+#[cfg(feature = "png")]
+use wasm_bindgen::Clamped;
+
+// ----------------------------------------------------------------------------
+// wasm32 linear memory is limited to 4 GiB, keep decoded images well below that
+#[cfg(feature = "png")]
+const MAX_IMAGE_BYTES: usize = 256 << 20;
+
+// ----------------------------------------------------------------------------
+// Decodes a raw deflate stream, output beyond `max_output` bytes fails.
+#[wasm_bindgen]
+pub fn inflate_to_vec(bytes: &[u8], max_output: usize) -> Result<Vec<u8>, JsError> {
+    let limits = inflate::VecLimits {
+        max_output,
+        ..Default::default()
+    };
+    inflate::inflate_to_vec(bytes, &limits).map_err(|err| JsError::new(&err.to_string()))
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(feature = "png")]
+#[wasm_bindgen]
+pub struct DecodedPng {
+    width: u32,
+    height: u32,
+    rgba: Vec<u8>,
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(feature = "png")]
+#[wasm_bindgen]
+impl DecodedPng {
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    // Copies the pixels on every access, `into_rgba` hands them over without a copy.
+    #[wasm_bindgen(getter)]
+    pub fn rgba(&self) -> Clamped<Vec<u8>> {
+        Clamped(self.rgba.clone())
+    }
+
+    // Moves the pixels out, the object can't be used afterwards.
+    #[wasm_bindgen(js_name = intoRgba)]
+    pub fn into_rgba(self) -> Clamped<Vec<u8>> {
+        Clamped(self.rgba)
+    }
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(feature = "png")]
+fn decode(png: &[u8]) -> png_read::Result<DecodedPng> {
+    // reject oversized images before any pixel buffer is allocated
+    let (ihdr, _) = png_read::read_ihdr(png, true)?;
+    let bytes = ihdr
+        .width
+        .checked_mul(ihdr.height)
+        .and_then(|n| n.checked_mul(4));
+    if bytes.is_none_or(|n| n > MAX_IMAGE_BYTES) {
+        return Err(png_read::Error::UnsupportedFormat);
+    }
+
+    let (width, height, rgba) = png_read::png_decode_rgba(png)?;
+    Ok(DecodedPng {
+        width: width as u32,
+        height: height as u32,
+        rgba,
+    })
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(feature = "png")]
+#[wasm_bindgen]
+pub fn decode_png(bytes: &[u8]) -> Result<DecodedPng, JsError> {
+    decode(bytes).map_err(|err| JsError::new(&err.to_string()))
+}
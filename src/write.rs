@@ -0,0 +1,199 @@
+// `std::io::Write` adapters that compress the data written to them, counterparts of
+// `flate2::write` encoders.
+// * input is buffered and compressed in chunks of 128 KB, matches don't reach into earlier chunks
+// * `flush` compresses the buffered input and ends it with a sync flush (an empty stored block),
+//   so all data written so far can be decoded, `finish` ends the stream
+use crate::checksum;
+use crate::deflate::{self, CompressionLevel, Strategy};
+use std::io::{self, Write};
+
+// ----------------------------------------------------------------------------
+const CHUNK_SIZE: usize = 1 << 17;
+
+// ----------------------------------------------------------------------------
+// Buffers input and writes it compressed to `writer`, `out` starts with the stream header.
+struct Encoder<W> {
+    writer: W,
+    level: CompressionLevel,
+    strategy: Strategy,
+    input: Vec<u8>,
+    out: Vec<u8>,
+}
+
+// ----------------------------------------------------------------------------
+impl<W: Write> Encoder<W> {
+    fn new(writer: W, level: u8, header: &[u8]) -> Self {
+        Encoder {
+            writer,
+            level: level.into(),
+            strategy: Strategy::Default,
+            input: Vec::with_capacity(CHUNK_SIZE),
+            out: header.to_vec(),
+        }
+    }
+
+    // Takes as much of `buf` as fits into the current chunk.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(CHUNK_SIZE - self.input.len());
+        self.input.extend_from_slice(&buf[..len]);
+        if self.input.len() == CHUNK_SIZE {
+            self.compress(false)?;
+        }
+        Ok(len)
+    }
+
+    fn compress(&mut self, last: bool) -> io::Result<()> {
+        let (level, strategy) = (self.level, self.strategy);
+        deflate::deflate_chunk(&mut self.out, &self.input, level, strategy, last, None);
+        self.input.clear();
+        self.writer.write_all(&self.out)?;
+        self.out.clear();
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.input.is_empty() {
+            self.compress(false)?;
+        }
+        self.writer.flush()
+    }
+
+    // Ends the stream, `trailer` follows the final block.
+    fn finish(mut self, trailer: &[u8]) -> io::Result<W> {
+        self.compress(true)?;
+        self.writer.write_all(trailer)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Raw RFC 1951 deflate stream, `level` ranges from 0 (none) to 9 (best).
+pub struct DeflateWriter<W> {
+    inner: Encoder<W>,
+}
+
+// ----------------------------------------------------------------------------
+impl<W: Write> DeflateWriter<W> {
+    pub fn new(writer: W, level: u8) -> Self {
+        DeflateWriter {
+            inner: Encoder::new(writer, level, &[]),
+        }
+    }
+
+    // Match search and block types of the data written from now on, see `deflate::Strategy`.
+    pub fn set_strategy(&mut self, strategy: Strategy) {
+        self.inner.strategy = strategy;
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner.writer
+    }
+
+    pub fn finish(self) -> io::Result<W> {
+        self.inner.finish(&[])
+    }
+}
+
+// ----------------------------------------------------------------------------
+impl<W: Write> Write for DeflateWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// ----------------------------------------------------------------------------
+// RFC 1950 zlib stream.
+pub struct ZlibWriter<W> {
+    inner: Encoder<W>,
+    adler: u32,
+}
+
+// ----------------------------------------------------------------------------
+impl<W: Write> ZlibWriter<W> {
+    pub fn new(writer: W, level: u8) -> Self {
+        ZlibWriter {
+            inner: Encoder::new(writer, level, &deflate::ZLIB_HEADER),
+            adler: 1,
+        }
+    }
+
+    // See `DeflateWriter::set_strategy`.
+    pub fn set_strategy(&mut self, strategy: Strategy) {
+        self.inner.strategy = strategy;
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner.writer
+    }
+
+    pub fn finish(self) -> io::Result<W> {
+        self.inner.finish(&self.adler.to_be_bytes())
+    }
+}
+
+// ----------------------------------------------------------------------------
+impl<W: Write> Write for ZlibWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.adler = checksum::adler32(self.adler, &buf[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// ----------------------------------------------------------------------------
+// RFC 1952 gzip member with a minimal header.
+pub struct GzipWriter<W> {
+    inner: Encoder<W>,
+    crc: u32,
+    size: u32,
+}
+
+// ----------------------------------------------------------------------------
+impl<W: Write> GzipWriter<W> {
+    pub fn new(writer: W, level: u8) -> Self {
+        GzipWriter {
+            inner: Encoder::new(writer, level, &deflate::GZIP_HEADER),
+            crc: 0,
+            size: 0,
+        }
+    }
+
+    // See `DeflateWriter::set_strategy`.
+    pub fn set_strategy(&mut self, strategy: Strategy) {
+        self.inner.strategy = strategy;
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner.writer
+    }
+
+    pub fn finish(self) -> io::Result<W> {
+        let mut trailer = [0u8; 8];
+        trailer[..4].copy_from_slice(&self.crc.to_le_bytes());
+        trailer[4..].copy_from_slice(&self.size.to_le_bytes());
+        self.inner.finish(&trailer)
+    }
+}
+
+// ----------------------------------------------------------------------------
+impl<W: Write> Write for GzipWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.crc = checksum::crc32(self.crc, &buf[..len]);
+        self.size = self.size.wrapping_add(len as u32);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,352 @@
+// WinZip AES encryption (AE-1 and AE-2), as written by WinZip and 7-Zip.
+// * entries have compression method 99, the 0x9901 extra field holds the AE version, the key
+//   strength and the actual compression method
+// * the entry data is a salt, a 2 byte password verification value, the AES-CTR encrypted data
+//   and a 10 byte HMAC-SHA1 authentication code of the encrypted data
+// * the keys are derived from the password and salt with PBKDF2-HMAC-SHA1 and 1000 iterations
+// * AE-2 entries leave the CRC-32 zero, the authentication code protects the data instead
+// * AES, SHA-1 and HMAC are implemented here, decryption needs no further dependencies
+// * with the `zeroize` feature the derived keys and round keys are wiped after use
+use crate::zip_read::{Error, Result};
+
+// ----------------------------------------------------------------------------
+const MAC_SIZE: usize = 10;
+const VERIFIER_SIZE: usize = 2;
+const ITERATIONS: usize = 1000;
+
+// ----------------------------------------------------------------------------
+// AES key size in bytes for the strength byte of the extra field, the salt is half as long.
+fn key_size(strength: u8) -> Option<usize> {
+    match strength {
+        1 => Some(16),
+        2 => Some(24),
+        3 => Some(32),
+        _ => None,
+    }
+}
+
+// ----------------------------------------------------------------------------
+#[derive(Clone)]
+struct Sha1 {
+    state: [u32; 5],
+    block: [u8; 64],
+    len: u64,
+}
+
+// ----------------------------------------------------------------------------
+impl Sha1 {
+    fn new() -> Self {
+        Sha1 {
+            state: [
+                0x6745_2301,
+                0xefcd_ab89,
+                0x98ba_dcfe,
+                0x1032_5476,
+                0xc3d2_e1f0,
+            ],
+            block: [0; 64],
+            len: 0,
+        }
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let used = (self.len % 64) as usize;
+            let n = data.len().min(64 - used);
+            self.block[used..used + n].copy_from_slice(&data[..n]);
+            self.len += n as u64;
+            data = &data[n..];
+            if used + n == 64 {
+                let block = self.block;
+                self.compress(&block);
+            }
+        }
+    }
+
+    fn finish(&mut self) -> [u8; 20] {
+        let bits = self.len * 8;
+        self.update(&[0x80]);
+        while self.len % 64 != 56 {
+            self.update(&[0]);
+        }
+        self.update(&bits.to_be_bytes());
+
+        let mut digest = [0; 20];
+        for (dst, word) in digest.chunks_exact_mut(4).zip(self.state) {
+            dst.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+
+    fn compress(&mut self, block: &[u8; 64]) {
+        let mut w = [0u32; 80];
+        for (w, bytes) in w.iter_mut().zip(block.chunks_exact(4)) {
+            *w = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = self.state;
+        for (i, w) in w.iter().enumerate() {
+            let (f, k) = match i / 20 {
+                0 => ((b & c) | (!b & d), 0x5a82_7999),
+                1 => (b ^ c ^ d, 0x6ed9_eba1),
+                2 => ((b & c) | (b & d) | (c & d), 0x8f1b_bcdc),
+                _ => (b ^ c ^ d, 0xca62_c1d6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*w);
+            (e, d, c, b, a) = (d, c, b.rotate_left(30), a, temp);
+        }
+        for (state, value) in self.state.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(feature = "zeroize")]
+impl Drop for Sha1 {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.state);
+        zeroize::Zeroize::zeroize(&mut self.block);
+    }
+}
+
+// ----------------------------------------------------------------------------
+// HMAC-SHA1 (RFC 2104), keyed once and cloned for each message.
+#[derive(Clone)]
+struct Hmac {
+    inner: Sha1,
+    outer: Sha1,
+}
+
+// ----------------------------------------------------------------------------
+impl Hmac {
+    fn new(key: &[u8]) -> Self {
+        let mut padded = [0u8; 64];
+        if key.len() > 64 {
+            let mut hash = Sha1::new();
+            hash.update(key);
+            padded[..20].copy_from_slice(&hash.finish());
+        } else {
+            padded[..key.len()].copy_from_slice(key);
+        }
+
+        let mut inner = Sha1::new();
+        let mut outer = Sha1::new();
+        inner.update(&padded.map(|b| b ^ 0x36));
+        outer.update(&padded.map(|b| b ^ 0x5c));
+        #[cfg(feature = "zeroize")]
+        zeroize::Zeroize::zeroize(&mut padded);
+        Hmac { inner, outer }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    fn finish(&mut self) -> [u8; 20] {
+        let hash = self.inner.finish();
+        self.outer.update(&hash);
+        self.outer.finish()
+    }
+}
+
+// ----------------------------------------------------------------------------
+// PBKDF2 (RFC 8018) with HMAC-SHA1, fills `out` with key material.
+fn pbkdf2(password: &[u8], salt: &[u8], iterations: usize, out: &mut [u8]) {
+    let prf = Hmac::new(password);
+    for (i, chunk) in out.chunks_mut(20).enumerate() {
+        let mut mac = prf.clone();
+        mac.update(salt);
+        mac.update(&(i as u32 + 1).to_be_bytes());
+        let mut u = mac.finish();
+        let mut t = u;
+        for _ in 1..iterations {
+            let mut mac = prf.clone();
+            mac.update(&u);
+            u = mac.finish();
+            t.iter_mut().zip(u).for_each(|(t, u)| *t ^= u);
+        }
+        chunk.copy_from_slice(&t[..chunk.len()]);
+    }
+}
+
+// ----------------------------------------------------------------------------
+const fn xtime(x: u8) -> u8 {
+    (x << 1) ^ if x & 0x80 != 0 { 0x1b } else { 0 }
+}
+
+// ----------------------------------------------------------------------------
+// The AES S-box, the multiplicative inverse in GF(2^8) followed by the affine transformation.
+const SBOX: [u8; 256] = {
+    let mut sbox = [0u8; 256];
+    // p runs through all non-zero elements as powers of 3, q through their inverses
+    let (mut p, mut q) = (1u8, 1u8);
+    loop {
+        p ^= xtime(p);
+        q ^= q << 1;
+        q ^= q << 2;
+        q ^= q << 4;
+        if q & 0x80 != 0 {
+            q ^= 0x09;
+        }
+        let affine = q ^ q.rotate_left(1) ^ q.rotate_left(2) ^ q.rotate_left(3) ^ q.rotate_left(4);
+        sbox[p as usize] = affine ^ 0x63;
+        if p == 1 {
+            break;
+        }
+    }
+    sbox[0] = 0x63;
+    sbox
+};
+
+// ----------------------------------------------------------------------------
+// AES-128, -192 or -256 (FIPS 197), encryption only as CTR mode needs no decryption.
+struct Aes {
+    round_keys: [[u8; 16]; 15],
+    rounds: usize,
+}
+
+// ----------------------------------------------------------------------------
+impl Aes {
+    // `key` is 16, 24 or 32 bytes long.
+    fn new(key: &[u8]) -> Self {
+        let nk = key.len() / 4;
+        let rounds = nk + 6;
+        let mut words = [[0u8; 4]; 60];
+        for (word, bytes) in words.iter_mut().zip(key.chunks_exact(4)) {
+            word.copy_from_slice(bytes);
+        }
+        let mut rcon = 1;
+        for i in nk..4 * (rounds + 1) {
+            let mut temp = words[i - 1];
+            if i % nk == 0 {
+                temp.rotate_left(1);
+                temp = temp.map(|b| SBOX[b as usize]);
+                temp[0] ^= rcon;
+                rcon = xtime(rcon);
+            } else if nk > 6 && i % nk == 4 {
+                temp = temp.map(|b| SBOX[b as usize]);
+            }
+            for (j, byte) in temp.iter().enumerate() {
+                words[i][j] = words[i - nk][j] ^ byte;
+            }
+        }
+
+        let mut round_keys = [[0u8; 16]; 15];
+        for (round_key, words) in round_keys.iter_mut().zip(words.chunks_exact(4)) {
+            for (dst, word) in round_key.chunks_exact_mut(4).zip(words) {
+                dst.copy_from_slice(word);
+            }
+        }
+        #[cfg(feature = "zeroize")]
+        zeroize::Zeroize::zeroize(&mut words);
+        Aes { round_keys, rounds }
+    }
+
+    // The state is stored column by column, byte `4 * c + r` is row `r` of column `c`.
+    fn encrypt_block(&self, block: &mut [u8; 16]) {
+        let add_round_key = |block: &mut [u8; 16], round: usize| {
+            for (b, k) in block.iter_mut().zip(&self.round_keys[round]) {
+                *b ^= k;
+            }
+        };
+        let sub_shift = |block: &mut [u8; 16]| {
+            let state = *block;
+            for (i, b) in block.iter_mut().enumerate() {
+                let (c, r) = (i / 4, i % 4);
+                *b = SBOX[state[4 * ((c + r) % 4) + r] as usize];
+            }
+        };
+
+        add_round_key(block, 0);
+        for round in 1..self.rounds {
+            sub_shift(block);
+            for column in block.chunks_exact_mut(4) {
+                let [a0, a1, a2, a3] = [column[0], column[1], column[2], column[3]];
+                let t = a0 ^ a1 ^ a2 ^ a3;
+                column[0] ^= t ^ xtime(a0 ^ a1);
+                column[1] ^= t ^ xtime(a1 ^ a2);
+                column[2] ^= t ^ xtime(a2 ^ a3);
+                column[3] ^= t ^ xtime(a3 ^ a0);
+            }
+            add_round_key(block, round);
+        }
+        sub_shift(block);
+        add_round_key(block, self.rounds);
+    }
+
+    // WinZip's CTR mode: a little-endian 128 bit counter starting at 1 is the input block.
+    fn ctr(&self, data: &mut [u8]) {
+        for (counter, chunk) in (1u128..).zip(data.chunks_mut(16)) {
+            let mut stream = counter.to_le_bytes();
+            self.encrypt_block(&mut stream);
+            chunk.iter_mut().zip(stream).for_each(|(b, s)| *b ^= s);
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(feature = "zeroize")]
+impl Drop for Aes {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.round_keys);
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Checks the password verification value and the authentication code of the entry data in
+// `data` and decrypts it in place, returns the decrypted data. A wrong password fails with
+// `Error::WrongPassword`, data not matching its authentication code with
+// `Error::ChecksumMismatch`.
+pub(crate) fn decrypt_entry<'a>(
+    data: &'a mut [u8],
+    password: &[u8],
+    strength: u8,
+) -> Result<&'a [u8]> {
+    let key_size = key_size(strength).ok_or(Error::InvalidZip)?;
+    let salt_size = key_size / 2;
+    if data.len() < salt_size + VERIFIER_SIZE + MAC_SIZE {
+        return Err(Error::InvalidZip);
+    }
+
+    // AES key, HMAC key and password verification value
+    let mut keys = [0u8; 2 * 32 + VERIFIER_SIZE];
+    let keys = &mut keys[..2 * key_size + VERIFIER_SIZE];
+    pbkdf2(password, &data[..salt_size], ITERATIONS, keys);
+    let result = decrypt_with_keys(data, keys, salt_size);
+    #[cfg(feature = "zeroize")]
+    zeroize::Zeroize::zeroize(keys);
+    result
+}
+
+// ----------------------------------------------------------------------------
+fn decrypt_with_keys<'a>(data: &'a mut [u8], keys: &[u8], salt_size: usize) -> Result<&'a [u8]> {
+    let key_size = (keys.len() - VERIFIER_SIZE) / 2;
+    let (header, rest) = data.split_at_mut(salt_size + VERIFIER_SIZE);
+    if header[salt_size..] != keys[2 * key_size..] {
+        return Err(Error::WrongPassword);
+    }
+
+    let (content, code) = rest.split_at_mut(rest.len() - MAC_SIZE);
+    let mut hmac = Hmac::new(&keys[key_size..2 * key_size]);
+    hmac.update(content);
+    let mac = hmac.finish();
+    let diff = mac
+        .iter()
+        .zip(code.iter())
+        .fold(0, |diff, (a, b)| diff | (a ^ b));
+    if diff != 0 {
+        return Err(Error::ChecksumMismatch);
+    }
+
+    Aes::new(&keys[..key_size]).ctr(content);
+    Ok(content)
+}
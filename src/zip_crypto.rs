@@ -0,0 +1,84 @@
+// Traditional PKWARE encryption (ZipCrypto).
+// * APPNOTE section 6.1, a stream cipher keyed by the password with a 12 byte header in front of
+//   the entry data
+// * weak by today's standards, supported for reading legacy archives only
+// * with the `zeroize` feature the keys are wiped when the cipher is dropped, the password is
+//   never copied
+use crate::checksum;
+
+// ----------------------------------------------------------------------------
+const HEADER_SIZE: usize = 12;
+
+// ----------------------------------------------------------------------------
+// one step of the CRC-32 register without the pre- and post-conditioning
+fn crc32_byte(crc: u32, byte: u8) -> u32 {
+    !checksum::crc32(!crc, &[byte])
+}
+
+// ----------------------------------------------------------------------------
+pub(crate) struct ZipCrypto {
+    keys: [u32; 3],
+}
+
+// ----------------------------------------------------------------------------
+impl ZipCrypto {
+    pub(crate) fn new(password: &[u8]) -> Self {
+        let mut crypto = ZipCrypto {
+            keys: [0x1234_5678, 0x2345_6789, 0x3456_7890],
+        };
+        password.iter().for_each(|&b| crypto.update(b));
+        crypto
+    }
+
+    fn update(&mut self, byte: u8) {
+        let [key0, key1, key2] = &mut self.keys;
+        *key0 = crc32_byte(*key0, byte);
+        *key1 = key1
+            .wrapping_add(*key0 & 0xff)
+            .wrapping_mul(134_775_813)
+            .wrapping_add(1);
+        *key2 = crc32_byte(*key2, (*key1 >> 24) as u8);
+    }
+
+    fn key_byte(&self) -> u8 {
+        let temp = self.keys[2] | 2;
+        (temp.wrapping_mul(temp ^ 1) >> 8) as u8
+    }
+
+    pub(crate) fn decrypt(&mut self, data: &mut [u8]) {
+        for b in data {
+            *b ^= self.key_byte();
+            self.update(*b);
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(feature = "zeroize")]
+impl Drop for ZipCrypto {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.keys);
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Decrypts the encryption header and the entry data in `data`, returns the entry data. The last
+// header byte has to match `check`, the high byte of the CRC-32 or, for entries with a data
+// descriptor, of the modification time. `None` for a wrong password.
+pub(crate) fn decrypt_entry<'a>(
+    data: &'a mut [u8],
+    password: &[u8],
+    check: u8,
+) -> Option<&'a [u8]> {
+    if data.len() < HEADER_SIZE {
+        return None;
+    }
+    let mut crypto = ZipCrypto::new(password);
+    let (header, content) = data.split_at_mut(HEADER_SIZE);
+    crypto.decrypt(header);
+    if header[HEADER_SIZE - 1] != check {
+        return None;
+    }
+    crypto.decrypt(content);
+    Some(content)
+}
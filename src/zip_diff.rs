@@ -0,0 +1,77 @@
+// Comparison of two ZIP archives by entry name.
+// * only the central directories are read, no entry is decompressed
+// * content changes are detected by CRC-32 and size, compressed sizes and offsets are ignored as
+//   they follow from the archive layout and compressor
+use crate::zip_read::{self, EntryInfo, Result};
+use alloc::{collections::BTreeMap, vec::Vec};
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModifiedEntry {
+    pub old: EntryInfo,
+    pub new: EntryInfo,
+}
+
+// ----------------------------------------------------------------------------
+impl ModifiedEntry {
+    pub fn name(&self) -> &str {
+        &self.old.name
+    }
+
+    pub fn content_changed(&self) -> bool {
+        self.old.crc32 != self.new.crc32 || self.old.size != self.new.size
+    }
+
+    // compression method, flags, timestamp, attributes or comment differ
+    pub fn metadata_changed(&self) -> bool {
+        let (old, new) = (&self.old, &self.new);
+        old.method != new.method
+            || old.flags != new.flags
+            || old.dos_time != new.dos_time
+            || old.external_attributes != new.external_attributes
+            || old.comment != new.comment
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Entries of the second archive compared to the first, each list is sorted by name.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ZipDiff {
+    pub added: Vec<EntryInfo>,
+    pub removed: Vec<EntryInfo>,
+    pub modified: Vec<ModifiedEntry>,
+}
+
+// ----------------------------------------------------------------------------
+impl ZipDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Compares the archives `a` and `b`, of entries with duplicate names the last one is used.
+pub fn zip_diff(a: &[u8], b: &[u8]) -> Result<ZipDiff> {
+    let by_name = |data| -> Result<BTreeMap<_, _>> {
+        let entries = zip_read::zip_entries(data)?;
+        Ok(entries.into_iter().map(|e| (e.name.clone(), e)).collect())
+    };
+    let old = by_name(a)?;
+    let mut new = by_name(b)?;
+
+    let mut diff = ZipDiff::default();
+    for (name, old) in old {
+        match new.remove(&name) {
+            None => diff.removed.push(old),
+            Some(new) => {
+                let entry = ModifiedEntry { old, new };
+                if entry.content_changed() || entry.metadata_changed() {
+                    diff.modified.push(entry);
+                }
+            }
+        }
+    }
+    diff.added.extend(new.into_values());
+
+    Ok(diff)
+}
@@ -1,74 +1,539 @@
 // ZIP parsing implementation.
 // * https://pkwaredownloads.blob.core.windows.net/pkware-general/Documentation/APPNOTE-6.3.9.TXT
-use crate::inflate;
+use crate::buffer::{BufferProvider, Heap};
+use crate::bzip2;
+use crate::checksum;
+use crate::inflate::{self, InflateOptions};
+#[cfg(feature = "std")]
+use crate::stream::Inflater;
+use crate::warning::{warn, Warning};
+#[cfg(feature = "aes")]
+use crate::zip_aes;
+use crate::zip_crypto;
+use alloc::{borrow::Cow, collections::BTreeMap, string::String, vec, vec::Vec};
+use core::ops::Bound;
 
 // ----------------------------------------------------------------------------
 #[derive(Debug, PartialEq)]
+#[non_exhaustive]
 pub enum Error {
     InvalidZip,
     NoCentralDirectory,
     InvalidSignature,
-    InvalidCompressionMethod,
+    InvalidCompressionMethod(u16),
     FileNotFound,
-    CompressionError,
+    // failure of the deflate stream, `Cancelled` and `ChecksumMismatch` have their own variants,
+    // no cause when rebuilt from the error code
+    CompressionError(Option<inflate::Error>),
     BufferError,
+    Cancelled,
+    DuplicateEntry,
+    // CRC-32 of the extracted data differs from the local header
+    ChecksumMismatch,
+    // encrypted entry read without a password or with a wrong one
+    WrongPassword,
+    // encrypted entry read through an interface without decryption support
+    Encrypted,
+    // archive split across several disks or volumes (spanned or split archive)
+    MultiDiskUnsupported,
+    // failure of the bzip2 data of a method 12 entry, no cause when rebuilt from the error code
+    Bzip2Error(Option<bzip2::Error>),
 }
 
 // ----------------------------------------------------------------------------
-impl std::fmt::Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let err = format!("{:?}", self);
-        f.write_str(&err)
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{self:?}")
     }
 }
 
 // ----------------------------------------------------------------------------
-impl std::error::Error for Error {}
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::CompressionError(Some(err)) => Some(err),
+            Error::Bzip2Error(Some(err)) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Stable numeric codes (300-399) for FFI and logging, codes are never reused or renumbered.
+impl Error {
+    pub fn code(&self) -> u32 {
+        use Error::*;
+        match self {
+            InvalidZip => 300,
+            NoCentralDirectory => 301,
+            InvalidSignature => 302,
+            InvalidCompressionMethod(_) => 303,
+            FileNotFound => 304,
+            CompressionError(_) => 305,
+            BufferError => 306,
+            Cancelled => 307,
+            DuplicateEntry => 308,
+            ChecksumMismatch => 309,
+            WrongPassword => 310,
+            Encrypted => 311,
+            MultiDiskUnsupported => 312,
+            Bzip2Error(_) => 313,
+        }
+    }
+
+    pub fn from_code(code: u32) -> Option<Self> {
+        use Error::*;
+        Some(match code {
+            300 => InvalidZip,
+            301 => NoCentralDirectory,
+            302 => InvalidSignature,
+            303 => InvalidCompressionMethod(0),
+            304 => FileNotFound,
+            305 => CompressionError(None),
+            306 => BufferError,
+            307 => Cancelled,
+            308 => DuplicateEntry,
+            309 => ChecksumMismatch,
+            310 => WrongPassword,
+            311 => Encrypted,
+            312 => MultiDiskUnsupported,
+            313 => Bzip2Error(None),
+            _ => return None,
+        })
+    }
+}
 
 // ----------------------------------------------------------------------------
 impl From<inflate::Error> for Error {
-    fn from(_: inflate::Error) -> Self {
-        Error::CompressionError
+    fn from(err: inflate::Error) -> Self {
+        match err {
+            inflate::Error::Cancelled => Error::Cancelled,
+            inflate::Error::ChecksumMismatch => Error::ChecksumMismatch,
+            err => Error::CompressionError(Some(err)),
+        }
     }
 }
 
 // ----------------------------------------------------------------------------
-impl From<std::array::TryFromSliceError> for Error {
-    fn from(_: std::array::TryFromSliceError) -> Self {
+impl From<bzip2::Error> for Error {
+    fn from(err: bzip2::Error) -> Self {
+        match err {
+            bzip2::Error::ChecksumMismatch => Error::ChecksumMismatch,
+            err => Error::Bzip2Error(Some(err)),
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+impl From<core::array::TryFromSliceError> for Error {
+    fn from(_: core::array::TryFromSliceError) -> Self {
         Error::BufferError
     }
 }
 
 // ----------------------------------------------------------------------------
-pub type Result<T> = std::result::Result<T, Error>;
+pub type Result<T> = core::result::Result<T, Error>;
 
 // ----------------------------------------------------------------------------
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct File {
     pub name: String,
     pub offset: usize,
+    // central directory values, the sizes take precedence over the local header
+    pub crc32: u32,
+    pub compressed_size: usize,
+    pub size: usize,
+    pub external_attributes: u32,
+}
+
+// ----------------------------------------------------------------------------
+impl File {
+    // Directories end with '/' or carry the MS-DOS directory attribute.
+    pub fn is_dir(&self) -> bool {
+        self.name.ends_with('/') || self.external_attributes & 0x10 != 0
+    }
+}
+
+// ----------------------------------------------------------------------------
+// size of the end of central directory record without the comment
+const EOCD_SIZE: usize = 22;
+const MAX_COMMENT_LEN: usize = 0x10000;
+
+// ----------------------------------------------------------------------------
+fn read_u64(data: &[u8]) -> Result<usize> {
+    let value = u64::from_le_bytes(data.try_into()?);
+    usize::try_from(value).map_err(|_| Error::BufferError)
+}
+
+// ----------------------------------------------------------------------------
+// Data of the first extra field record with `header_id` in `extra`.
+fn extra_field(mut extra: &[u8], id: u16) -> Option<&[u8]> {
+    while extra.len() >= 4 {
+        let header_id = u16::from_le_bytes([extra[0], extra[1]]);
+        let len = u16::from_le_bytes([extra[2], extra[3]]) as usize;
+        let data = extra.get(4..4 + len)?;
+        if header_id == id {
+            return Some(data);
+        }
+        extra = &extra[4 + len..];
+    }
+    None
+}
+
+// ----------------------------------------------------------------------------
+// code page 437 characters of the bytes 0x80 to 0xff, the lower half equals ASCII
+const CP437_HIGH: &str = concat!(
+    "ÇüéâäàåçêëèïîìÄÅ",
+    "ÉæÆôöòûùÿÖÜ¢£¥₧ƒ",
+    "áíóúñÑªº¿⌐¬½¼¡«»",
+    "░▒▓│┤╡╢╖╕╣║╗╝╜╛┐",
+    "└┴┬├─┼╞╟╚╔╩╦╠═╬╧",
+    "╨╤╥╙╘╒╓╫╪┘┌█▄▌▐▀",
+    "αßΓπΣσµτΦΘΩδ∞φε∩",
+    "≡±≥≤⌠⌡÷≈°∙·√ⁿ²■\u{a0}",
+);
+
+// ----------------------------------------------------------------------------
+fn decode_cp437(raw: &[u8]) -> String {
+    raw.iter()
+        .map(|&b| match b {
+            0..=0x7f => b as char,
+            _ => CP437_HIGH.chars().nth(b as usize - 0x80).unwrap_or('?'),
+        })
+        .collect()
+}
+
+// ----------------------------------------------------------------------------
+// Text of the Info-ZIP Unicode Path (0x7075) or Comment (0x6375) extra field `id`. The field is
+// ignored if it was made for different header bytes than `raw`, e.g. after a rename by a tool
+// unaware of the field.
+fn unicode_extra_field(extra: &[u8], id: u16, raw: &[u8]) -> Option<String> {
+    let data = extra_field(extra, id)?;
+    let (&version, data) = data.split_first()?;
+    let crc = u32::from_le_bytes(data.get(..4)?.try_into().ok()?);
+    if version != 1 || crc != checksum::crc32(0, raw) {
+        return None;
+    }
+    core::str::from_utf8(&data[4..]).ok().map(String::from)
+}
+
+// ----------------------------------------------------------------------------
+// Decodes a name or comment: UTF-8 if general purpose bit 11 is set, otherwise the Info-ZIP
+// Unicode extra field `id` if present, or code page 437, the original encoding of ZIP.
+fn decode_text(raw: &[u8], flags: u16, extra: &[u8], id: u16) -> String {
+    if flags & 0x0800 != 0 {
+        return String::from_utf8_lossy(raw).into_owned();
+    }
+    unicode_extra_field(extra, id, raw).unwrap_or_else(|| decode_cp437(raw))
 }
 
 // ----------------------------------------------------------------------------
-fn read_eocd(data: &[u8]) -> Result<(usize, usize, u16)> {
-    const MAX_COMMENT_LEN: usize = 0x10000;
-    const EOCD_SIZE: usize = 22;
-    let start = data.len().saturating_sub(EOCD_SIZE + MAX_COMMENT_LEN);
-    let end = data.len().saturating_sub(EOCD_SIZE - 4);
+// Entry name of a central directory record or local header with its general purpose `flags`
+// and `extra` field.
+pub(crate) fn decode_name(raw: &[u8], flags: u16, extra: &[u8]) -> String {
+    decode_text(raw, flags, extra, 0x7075)
+}
+
+// ----------------------------------------------------------------------------
+// Modification time of the extended timestamp extra field (header ID 0x5455, "UT") in seconds
+// since 1970-01-01 UTC.
+pub(crate) fn extended_mtime(extra: &[u8]) -> Option<i64> {
+    let data = extra_field(extra, 0x5455)?;
+    if data.first()? & 0x01 == 0 {
+        return None;
+    }
+    let mtime = data.get(1..5)?;
+    Some(i32::from_le_bytes(mtime.try_into().ok()?) as i64)
+}
+
+// ----------------------------------------------------------------------------
+// Modification time of the NTFS extra field (header ID 0x000a) in 100 ns intervals since
+// 1601-01-01 UTC. After 4 reserved bytes the field holds attributes with the layout of extra
+// fields, attribute 1 the modification, access and creation times.
+pub(crate) fn ntfs_mtime(extra: &[u8]) -> Option<u64> {
+    let attributes = extra_field(extra, 0x000a)?.get(4..)?;
+    let times = extra_field(attributes, 0x0001)?;
+    Some(u64::from_le_bytes(times.get(..8)?.try_into().ok()?))
+}
+
+// ----------------------------------------------------------------------------
+// Replaces the `fields` set to 0xffffffff with the 64 bit values of the ZIP64 extra field, which
+// holds values only for these fields and in the given order.
+fn read_zip64_fields(extra: &[u8], fields: &mut [&mut usize]) -> Result<()> {
+    let mut values = extra_field(extra, 0x0001)
+        .unwrap_or_default()
+        .chunks_exact(8);
+    for field in fields {
+        if **field == u32::MAX as usize {
+            **field = read_u64(values.next().ok_or(Error::InvalidZip)?)?;
+        }
+    }
+    Ok(())
+}
+
+// ----------------------------------------------------------------------------
+// Offset of the ZIP64 end of central directory record, `None` if there is no locator in front of
+// the end of central directory record `eocd`.
+fn zip64_locator(data: &[u8], eocd: usize) -> Result<Option<usize>> {
+    let Some(locator) = eocd.checked_sub(20).map(|ofs| &data[ofs..eocd]) else {
+        return Ok(None);
+    };
+    if !locator.starts_with(&[0x50, 0x4b, 0x06, 0x07]) {
+        return Ok(None);
+    }
+    // the disk with the ZIP64 record and the total number of disks
+    let disk = u32::from_le_bytes(locator[4..8].try_into()?);
+    let disks = u32::from_le_bytes(locator[16..20].try_into()?);
+    if disk != 0 || disks > 1 {
+        return Err(Error::MultiDiskUnsupported);
+    }
+    Ok(Some(read_u64(&locator[8..16])?))
+}
+
+// ----------------------------------------------------------------------------
+// Reads (cd size, cd offset, entries) from the ZIP64 end of central directory record.
+fn read_zip64_eocd(record: &[u8]) -> Result<(usize, usize, usize)> {
+    let record = record.get(..56).ok_or(Error::BufferError)?;
+    if !record.starts_with(&[0x50, 0x4b, 0x06, 0x06]) {
+        return Err(Error::InvalidSignature);
+    }
+    // the number of this disk and of the disk where the central directory starts
+    if record[16..24] != [0; 8] {
+        return Err(Error::MultiDiskUnsupported);
+    }
+    let total_entries = read_u64(&record[32..40])?;
+    let cd_size = read_u64(&record[40..48])?;
+    let cd_offset = read_u64(&record[48..56])?;
+    Ok((cd_size, cd_offset, total_entries))
+}
+
+// ----------------------------------------------------------------------------
+// Locates the end of central directory record, returns (cd size, cd offset, entries, offset,
+// prefix). Archives with a ZIP64 end of central directory record take the values from that
+// record. The prefix is the length of data in front of the archive, e.g. a self-extractor stub,
+// whose offsets count from its own start: the returned cd offset includes it, the offsets of
+// the entries in the central directory don't.
+pub(crate) fn read_eocd(
+    data: &[u8],
+    options: &InflateOptions,
+) -> Result<(usize, usize, usize, usize, usize)> {
+    let record = |ofs: usize| data.get(ofs..).ok_or(Error::BufferError);
+    find_eocd(data, 0, options, record)
+}
+
+// ----------------------------------------------------------------------------
+// Like `read_eocd` on the last bytes `tail` of an archive starting at `base`, offsets are
+// relative to the start of the archive. `record` returns the bytes at the offset of a ZIP64 end
+// of central directory record.
+fn find_eocd<F, B>(
+    tail: &[u8],
+    base: usize,
+    options: &InflateOptions,
+    mut record: F,
+) -> Result<(usize, usize, usize, usize, usize)>
+where
+    F: FnMut(usize) -> Result<B>,
+    B: AsRef<[u8]>,
+{
+    let start = tail.len().saturating_sub(EOCD_SIZE + MAX_COMMENT_LEN);
+    let end = tail.len().saturating_sub(EOCD_SIZE - 4);
     for i in (start..end).rev() {
-        if data[i..i + 4] == [0x50, 0x4b, 0x05, 0x06] {
-            let data = &data[i..i + 20];
-            let cd_size = u32::from_le_bytes(data[12..16].try_into()?) as usize;
-            let cd_offset = u32::from_le_bytes(data[16..20].try_into()?) as usize;
-            let total_entries = u16::from_le_bytes(data[10..12].try_into()?);
-            return Ok((cd_size, cd_offset, total_entries));
+        if tail[i..i + 4] == [0x50, 0x4b, 0x05, 0x06] {
+            let Some(eocd) = tail.get(i..i + EOCD_SIZE) else {
+                continue;
+            };
+            let mut cd_size = u32::from_le_bytes(eocd[12..16].try_into()?) as usize;
+            let mut cd_offset = u32::from_le_bytes(eocd[16..20].try_into()?) as usize;
+            let mut total_entries = u16::from_le_bytes(eocd[10..12].try_into()?) as usize;
+            let comment_len = u16::from_le_bytes(eocd[20..22].try_into()?) as usize;
+
+            let offset = base + i;
+            let mut cd_end = offset;
+            match zip64_locator(tail, i)? {
+                Some(mut zip64) => {
+                    // after a prefix the record is further back than its offset, usually just
+                    // in front of the locator
+                    let signature = [0x50, 0x4b, 0x06, 0x06];
+                    let moved = offset.checked_sub(20 + 56).filter(|&ofs| ofs > zip64);
+                    if let Some(moved) = moved {
+                        let found = record(zip64).is_ok_and(|r| r.as_ref().starts_with(&signature));
+                        if !found {
+                            zip64 = moved;
+                        }
+                    }
+                    let zip64_eocd = read_zip64_eocd(record(zip64)?.as_ref())?;
+                    (cd_size, cd_offset, total_entries) = zip64_eocd;
+                    cd_end = zip64;
+                }
+                // the number of this disk and of the disk where the central directory starts
+                None if eocd[4..8] != [0; 4] => return Err(Error::MultiDiskUnsupported),
+                None => {}
+            }
+
+            // a central directory ending in front of the record is taken as a sign of a prefix,
+            // like Info-ZIP and libzip do
+            let prefix = cd_offset
+                .checked_add(cd_size)
+                .and_then(|cd_end_declared| cd_end.checked_sub(cd_end_declared))
+                .unwrap_or(0);
+            if prefix > 0 {
+                if options.strict_containers {
+                    return Err(Error::InvalidZip);
+                }
+                trace_event!(debug, prefix, "zip archive after a prefix");
+                warn(options.warnings, Warning::ZipPrefix { len: prefix });
+            }
+
+            // the comment should end the file and the central directory should end at the record
+            if i + EOCD_SIZE + comment_len != tail.len()
+                || cd_offset.checked_add(cd_size + prefix) != Some(cd_end)
+            {
+                if options.strict_containers {
+                    return Err(Error::InvalidZip);
+                }
+                trace_event!(warn, offset, "zip end of central directory inconsistent");
+                warn(options.warnings, Warning::ZipSuspiciousEocd { offset });
+            }
+            return Ok((cd_size, cd_offset + prefix, total_entries, offset, prefix));
         }
     }
     Err(Error::NoCentralDirectory)
 }
 
 // ----------------------------------------------------------------------------
-fn read_cd(data: &[u8], total_entries: u16) -> Result<Vec<File>> {
+// MS-DOS date and time in 2 second steps, in local time of the archiver without a time zone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DosDateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+// ----------------------------------------------------------------------------
+impl DosDateTime {
+    // Splits the MS-DOS date in the upper and the time in the lower 16 bits of `dos_time`.
+    pub fn from_dos(dos_time: u32) -> Self {
+        let (date, time) = (dos_time >> 16, dos_time & 0xffff);
+        DosDateTime {
+            year: 1980 + (date >> 9) as u16,
+            month: (date >> 5 & 0x0f) as u8,
+            day: (date & 0x1f) as u8,
+            hour: (time >> 11) as u8,
+            minute: (time >> 5 & 0x3f) as u8,
+            second: (time & 0x1f) as u8 * 2,
+        }
+    }
+
+    // Seconds since 1970-01-01 taking the time as UTC, `None` for invalid dates.
+    pub fn unix_time(&self) -> Option<i64> {
+        let (year, month, day) = (self.year as i64, self.month as i64, self.day as i64);
+        if !(1..=12).contains(&month) || day == 0 {
+            return None;
+        }
+
+        // days since 1970-01-01 of the civil date, March based years put leap days last
+        let (y, m) = if month <= 2 {
+            (year - 1, month + 9)
+        } else {
+            (year, month - 3)
+        };
+        let days = 365 * y + y / 4 - y / 100 + y / 400 + (153 * m + 2) / 5 + day - 719469;
+        let seconds = self.hour as i64 * 3600 + self.minute as i64 * 60 + self.second as i64;
+        Some(days * 86400 + seconds)
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Central directory record of an entry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntryInfo {
+    pub name: String,
+    pub offset: usize,
+    pub crc32: u32,
+    pub compressed_size: usize,
+    pub size: usize,
+    pub method: u16,
+    pub flags: u16,
+    // MS-DOS date in the upper and time in the lower 16 bits
+    pub dos_time: u32,
+    pub external_attributes: u32,
+    pub comment: String,
+    // host system in the upper byte (0 MS-DOS, 3 Unix), specification version in the lower
+    pub version_made_by: u16,
+    // seconds since 1970-01-01 UTC from the extended timestamp extra field
+    pub mtime: Option<i64>,
+    // 100 ns intervals since 1601-01-01 UTC from the NTFS extra field
+    pub ntfs_mtime: Option<u64>,
+}
+
+// ----------------------------------------------------------------------------
+impl EntryInfo {
+    // Directories end with '/' or carry the MS-DOS directory attribute.
+    pub fn is_dir(&self) -> bool {
+        self.name.ends_with('/') || self.external_attributes & 0x10 != 0
+    }
+
+    // Unix mode including the file type bits, `None` for archives not made on Unix.
+    pub fn unix_mode(&self) -> Option<u32> {
+        let mode = self.external_attributes >> 16;
+        (self.version_made_by >> 8 == 3 && mode != 0).then_some(mode)
+    }
+
+    // `dos_time` split into its fields.
+    pub fn dos_datetime(&self) -> DosDateTime {
+        DosDateTime::from_dos(self.dos_time)
+    }
+
+    // Modification time from the NTFS extra field with 100 ns precision, the extended timestamp
+    // extra field or else the MS-DOS time taken as UTC. `None` for invalid MS-DOS dates and
+    // times beyond the range of `SystemTime`.
+    #[cfg(feature = "std")]
+    pub fn modified(&self) -> Option<std::time::SystemTime> {
+        // seconds from 1601-01-01 to 1970-01-01
+        const NTFS_EPOCH: i64 = 11_644_473_600;
+        let (secs, nanos) = match (self.ntfs_mtime, self.mtime) {
+            (Some(ticks), _) => (
+                (ticks / 10_000_000) as i64 - NTFS_EPOCH,
+                (ticks % 10_000_000) as u32 * 100,
+            ),
+            (None, Some(mtime)) => (mtime, 0),
+            (None, None) => (self.dos_datetime().unix_time()?, 0),
+        };
+        let offset = std::time::Duration::from_secs(secs.unsigned_abs());
+        let time = match secs >= 0 {
+            true => std::time::UNIX_EPOCH.checked_add(offset),
+            false => std::time::UNIX_EPOCH.checked_sub(offset),
+        };
+        time?.checked_add(std::time::Duration::from_nanos(nanos as u64))
+    }
+}
+
+// ----------------------------------------------------------------------------
+impl From<EntryInfo> for File {
+    fn from(entry: EntryInfo) -> Self {
+        File {
+            name: entry.name,
+            offset: entry.offset,
+            crc32: entry.crc32,
+            compressed_size: entry.compressed_size,
+            size: entry.size,
+            external_attributes: entry.external_attributes,
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Entry offsets are moved by `prefix`, see `read_eocd`.
+fn read_cd(
+    data: &[u8],
+    total_entries: usize,
+    prefix: usize,
+    options: &InflateOptions,
+) -> Result<Vec<EntryInfo>> {
     let mut data = data;
     let mut entries = Vec::new();
 
@@ -77,64 +542,1420 @@ fn read_cd(data: &[u8], total_entries: u16) -> Result<Vec<File>> {
             return Err(Error::InvalidSignature);
         }
 
-        let name_len = u16::from_le_bytes(data[28..30].try_into()?) as usize;
-        let extra_len = u16::from_le_bytes(data[30..32].try_into()?) as usize;
-        let comment_len = u16::from_le_bytes(data[32..34].try_into()?) as usize;
-        let offset = u32::from_le_bytes(data[42..46].try_into()?) as usize;
-        let name = String::from_utf8_lossy(&data[46..46 + name_len]).into_owned();
+        let hdr = data.get(..46).ok_or(Error::BufferError)?;
+        let version_made_by = u16::from_le_bytes(hdr[4..6].try_into()?);
+        let flags = u16::from_le_bytes(hdr[8..10].try_into()?);
+        let method = u16::from_le_bytes(hdr[10..12].try_into()?);
+        let time = u16::from_le_bytes(hdr[12..14].try_into()?);
+        let date = u16::from_le_bytes(hdr[14..16].try_into()?);
+        let crc32 = u32::from_le_bytes(hdr[16..20].try_into()?);
+        let mut compressed_size = u32::from_le_bytes(hdr[20..24].try_into()?) as usize;
+        let mut size = u32::from_le_bytes(hdr[24..28].try_into()?) as usize;
+        let name_len = u16::from_le_bytes(hdr[28..30].try_into()?) as usize;
+        let extra_len = u16::from_le_bytes(hdr[30..32].try_into()?) as usize;
+        let comment_len = u16::from_le_bytes(hdr[32..34].try_into()?) as usize;
+        // 0xffff defers the disk number to the ZIP64 extra field
+        let disk = u16::from_le_bytes(hdr[34..36].try_into()?);
+        if disk != 0 && disk != 0xffff {
+            return Err(Error::MultiDiskUnsupported);
+        }
+        let external_attributes = u32::from_le_bytes(hdr[38..42].try_into()?);
+        let mut offset = u32::from_le_bytes(hdr[42..46].try_into()?) as usize;
+
+        let extra = data
+            .get(46 + name_len..46 + name_len + extra_len)
+            .ok_or(Error::BufferError)?;
+        read_zip64_fields(extra, &mut [&mut size, &mut compressed_size, &mut offset])?;
+        let offset = offset.checked_add(prefix).ok_or(Error::InvalidZip)?;
+        let name = data.get(46..46 + name_len).ok_or(Error::BufferError)?;
+        let name = decode_name(name, flags, extra);
+
+        let comment_start = 46 + name_len + extra_len;
+        let comment = data
+            .get(comment_start..comment_start + comment_len)
+            .ok_or(Error::BufferError)?;
+        let comment = decode_text(comment, flags, extra, 0x6375);
+
+        if name.starts_with('/') || name.split(['/', '\\']).any(|part| part == "..") {
+            trace_event!(warn, name = %name, "zip entry with suspicious path");
+            let name = name.clone();
+            warn(options.warnings, Warning::ZipSuspiciousPath { name });
+        }
 
-        entries.push(File { name, offset });
+        entries.push(EntryInfo {
+            name,
+            offset,
+            crc32,
+            compressed_size,
+            size,
+            method,
+            flags,
+            dos_time: (date as u32) << 16 | time as u32,
+            external_attributes,
+            comment,
+            version_made_by,
+            mtime: extended_mtime(extra),
+            ntfs_mtime: ntfs_mtime(extra),
+        });
 
-        data = &data[46 + name_len + extra_len + comment_len..];
+        data = &data[comment_start + comment_len..];
     }
 
     Ok(entries)
 }
 
 // ----------------------------------------------------------------------------
-fn extract_file(data: &[u8], file: &File) -> Result<Vec<u8>> {
-    println!("{file:?}",);
+struct LocalEntry<'a> {
+    compression_method: u16,
+    compressed: &'a [u8],
+    uncompressed_size: usize,
+    crc32: u32,
+    flags: u16,
+    // expected last byte of the decrypted ZipCrypto header
+    check: u8,
+    // WinZip AES encryption of compression method 99
+    aes: Option<AesField>,
+}
+
+// ----------------------------------------------------------------------------
+// The 0x9901 extra field of an AES encrypted entry.
+#[derive(Debug, Clone, Copy)]
+struct AesField {
+    // 1 for AE-1, 2 for AE-2 without CRC-32
+    version: u16,
+    // 1, 2 or 3 for 128, 192 or 256 bit keys
+    #[cfg_attr(not(feature = "aes"), allow(dead_code))]
+    strength: u8,
+}
+
+// ----------------------------------------------------------------------------
+// Reads the AES extra field, returns it with the actual compression method of the entry.
+fn aes_field(extra: &[u8]) -> Result<(AesField, u16)> {
+    match extra_field(extra, 0x9901) {
+        Some(&[v0, v1, b'A', b'E', strength, m0, m1]) => {
+            let version = u16::from_le_bytes([v0, v1]);
+            if !(1..=2).contains(&version) || !(1..=3).contains(&strength) {
+                return Err(Error::InvalidZip);
+            }
+            let method = u16::from_le_bytes([m0, m1]);
+            Ok((AesField { version, strength }, method))
+        }
+        _ => Err(Error::InvalidZip),
+    }
+}
+
+// ----------------------------------------------------------------------------
+impl LocalEntry<'_> {
+    fn is_encrypted(&self) -> bool {
+        self.flags & 0x01 != 0
+    }
+
+    fn verify(&self, data: &[u8], options: &InflateOptions) -> Result<()> {
+        // AE-2 entries are protected by their authentication code only
+        if matches!(self.aes, Some(AesField { version: 2, .. })) {
+            return Ok(());
+        }
+        if options.verify_checksums && self.crc32 != checksum::crc32(0, data) {
+            return Err(Error::ChecksumMismatch);
+        }
+        Ok(())
+    }
+}
+
+// ----------------------------------------------------------------------------
+fn read_local_entry<'a>(data: &'a [u8], file: &File) -> Result<LocalEntry<'a>> {
     let ofs = file.offset;
-    let hdr = &data[ofs..ofs + 30];
+    let hdr = data
+        .get(ofs..ofs.saturating_add(30))
+        .ok_or(Error::BufferError)?;
 
-    if !data.starts_with(&[0x50, 0x4b, 0x03, 0x04]) {
+    if !hdr.starts_with(&[0x50, 0x4b, 0x03, 0x04]) {
         return Err(Error::InvalidSignature);
     }
 
-    let compression_method = u16::from_le_bytes(hdr[8..10].try_into()?);
-    let compressed_size = u32::from_le_bytes(hdr[18..22].try_into()?) as usize;
-    let uncompressed_size = u32::from_le_bytes(hdr[22..26].try_into()?) as usize;
+    let flags = u16::from_le_bytes(hdr[6..8].try_into()?);
+    let mut compression_method = u16::from_le_bytes(hdr[8..10].try_into()?);
+    let time = u16::from_le_bytes(hdr[10..12].try_into()?);
+    let mut crc32 = u32::from_le_bytes(hdr[14..18].try_into()?);
     let name_len = u16::from_le_bytes(hdr[26..28].try_into()?) as usize;
     let extra_len = u16::from_le_bytes(hdr[28..30].try_into()?) as usize;
 
-    let ofs = ofs + 30 + name_len + extra_len;
-    let compressed = &data[ofs..ofs + compressed_size];
+    let extra_start = ofs + 30 + name_len;
+    let extra = data
+        .get(extra_start..extra_start + extra_len)
+        .ok_or(Error::BufferError)?;
+
+    // AES encrypted entries give their actual compression method in the extra field
+    let mut aes = None;
+    if compression_method == 99 && flags & 0x01 != 0 {
+        let (field, method) = aes_field(extra)?;
+        aes = Some(field);
+        compression_method = method;
+    }
+
+    // The sizes are taken from the central directory, streaming writers leave them zero in the
+    // local header.
+    let ofs = extra_start + extra_len;
+    let end = ofs.saturating_add(file.compressed_size);
+    let compressed = data.get(ofs..end).ok_or(Error::BufferError)?;
+
+    // deflate expands a byte to at most 1032 bytes, larger sizes would only waste an allocation
+    if compression_method == 8 && file.size / 1032 > compressed.len() {
+        return Err(Error::InvalidZip);
+    }
+
+    // the data descriptor following the data repeats the central directory values
+    if flags & 0x08 != 0 {
+        let zip64 = extra_field(extra, 0x0001).is_some();
+        let descriptor = data.get(end..).ok_or(Error::BufferError)?;
+        let descriptor = read_data_descriptor(descriptor, zip64)?;
+        if descriptor.0 != file.crc32 {
+            return Err(Error::ChecksumMismatch);
+        }
+        if (descriptor.1, descriptor.2) != (file.compressed_size, file.size) {
+            return Err(Error::InvalidZip);
+        }
+        crc32 = descriptor.0;
+    }
+
+    // the CRC-32 is not known in advance when writing a data descriptor
+    let check = if flags & 0x08 != 0 {
+        (time >> 8) as u8
+    } else {
+        (crc32 >> 24) as u8
+    };
+
+    Ok(LocalEntry {
+        compression_method,
+        compressed,
+        uncompressed_size: file.size,
+        crc32,
+        flags,
+        check,
+        aes,
+    })
+}
+
+// ----------------------------------------------------------------------------
+// Reads (CRC-32, compressed size, size) from the data descriptor at the start of `data`. The
+// signature is optional, the sizes take 8 bytes each in ZIP64 entries.
+fn read_data_descriptor(data: &[u8], zip64: bool) -> Result<(u32, usize, usize)> {
+    let data = data.strip_prefix(&[0x50, 0x4b, 0x07, 0x08]).unwrap_or(data);
+    let crc32 = u32::from_le_bytes(data.get(0..4).ok_or(Error::BufferError)?.try_into()?);
+    let (compressed_size, size) = if zip64 {
+        let sizes = data.get(4..20).ok_or(Error::BufferError)?;
+        (read_u64(&sizes[0..8])?, read_u64(&sizes[8..16])?)
+    } else {
+        let sizes = data.get(4..12).ok_or(Error::BufferError)?;
+        (
+            u32::from_le_bytes(sizes[0..4].try_into()?) as usize,
+            u32::from_le_bytes(sizes[4..8].try_into()?) as usize,
+        )
+    };
+    Ok((crc32, compressed_size, size))
+}
 
-    match compression_method {
-        0 => Ok(compressed.into()),
-        8 => {
-            let mut uncompressed = vec![0u8; uncompressed_size];
-            if inflate::inflate(&mut uncompressed, compressed)? != uncompressed_size {
+// ----------------------------------------------------------------------------
+fn extract_file<P: BufferProvider>(
+    data: &[u8],
+    file: &File,
+    provider: &mut P,
+    options: &InflateOptions,
+    password: Option<&[u8]>,
+) -> Result<P::Buffer> {
+    trace_span!("zip_extract", name = %file.name);
+    let entry = read_local_entry(data, file)?;
+    if !entry.is_encrypted() {
+        return decode_entry(&entry, entry.compressed, provider, options);
+    }
+
+    // the entry is decrypted into a scratch buffer first
+    let password = password.ok_or(Error::WrongPassword)?;
+    let mut decrypted = provider
+        .scratch(entry.compressed.len())
+        .ok_or(Error::BufferError)?;
+    decrypted.as_mut().copy_from_slice(entry.compressed);
+    let decoded = match decrypt_entry(&entry, decrypted.as_mut(), password) {
+        Ok(compressed) => decode_entry(&entry, compressed, provider, options),
+        Err(err) => Err(err),
+    };
+    // the plaintext must not linger in a recycled buffer
+    #[cfg(feature = "zeroize")]
+    zeroize::Zeroize::zeroize(decrypted.as_mut());
+    provider.release(decrypted);
+    decoded
+}
+
+// ----------------------------------------------------------------------------
+// Decrypts the entry data in `data` in place, returns the compressed data. AES encrypted entries
+// need the `aes` feature and fail with `Error::Encrypted` without it.
+fn decrypt_entry<'a>(entry: &LocalEntry, data: &'a mut [u8], password: &[u8]) -> Result<&'a [u8]> {
+    match entry.aes {
+        #[cfg(feature = "aes")]
+        Some(aes) => zip_aes::decrypt_entry(data, password, aes.strength),
+        #[cfg(not(feature = "aes"))]
+        Some(_) => Err(Error::Encrypted),
+        None => zip_crypto::decrypt_entry(data, password, entry.check).ok_or(Error::WrongPassword),
+    }
+}
+
+// ----------------------------------------------------------------------------
+fn decode_entry<P: BufferProvider>(
+    entry: &LocalEntry,
+    compressed: &[u8],
+    provider: &mut P,
+    options: &InflateOptions,
+) -> Result<P::Buffer> {
+    trace_event!(
+        debug,
+        method = entry.compression_method,
+        compressed = compressed.len(),
+        uncompressed = entry.uncompressed_size,
+        "zip entry"
+    );
+
+    match entry.compression_method {
+        0 => {
+            entry.verify(compressed, options)?;
+            let mut stored = provider.alloc(compressed.len()).ok_or(Error::BufferError)?;
+            stored.as_mut().copy_from_slice(compressed);
+            Ok(stored)
+        }
+        // deflate, Deflate64 and bzip2
+        method @ (8 | 9 | 12) => {
+            let size = entry.uncompressed_size;
+            let mut uncompressed = provider.alloc(size).ok_or(Error::BufferError)?;
+            let out = uncompressed.as_mut();
+            let len = match method {
+                8 => inflate::inflate_with_options(out, compressed, options)?,
+                9 => inflate::inflate64_with_options(out, compressed, options)?,
+                _ => bzip2::decompress(out, compressed)?,
+            };
+            if len != size {
                 return Err(Error::InvalidZip);
             }
+            entry.verify(uncompressed.as_ref(), options)?;
             Ok(uncompressed)
         }
-        _ => Err(Error::InvalidCompressionMethod),
+        method => Err(Error::InvalidCompressionMethod(method)),
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Contents of `file`, stored entries are borrowed from the archive without a copy, e.g. from a
+// memory-mapped asset bundle, after their CRC-32 is checked. Compressed entries are decoded into
+// a new buffer.
+pub fn zip_entry_slice<'a>(data: &'a [u8], file: &File) -> Result<Cow<'a, [u8]>> {
+    entry_slice(data, file, &InflateOptions::default())
+}
+
+// ----------------------------------------------------------------------------
+// Contents of the entry `name`, see `zip_entry_slice`.
+pub(crate) fn entry_data<'a>(
+    data: &'a [u8],
+    files: &[File],
+    name: &str,
+    options: &InflateOptions,
+) -> Result<Cow<'a, [u8]>> {
+    let file = files
+        .iter()
+        .find(|file| file.name == name)
+        .ok_or(Error::FileNotFound)?;
+    entry_slice(data, file, options)
+}
+
+// ----------------------------------------------------------------------------
+fn entry_slice<'a>(data: &'a [u8], file: &File, options: &InflateOptions) -> Result<Cow<'a, [u8]>> {
+    let entry = read_local_entry(data, file)?;
+    match entry.compression_method {
+        0 if !entry.is_encrypted() => {
+            entry.verify(entry.compressed, options)?;
+            Ok(Cow::Borrowed(entry.compressed))
+        }
+        _ => Ok(Cow::Owned(extract_file(
+            data, file, &mut Heap, options, None,
+        )?)),
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Field of a local file header that contradicts the central directory record, with the local
+// value. The CRC-32 and sizes of entries with a data descriptor are taken from the descriptor.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mismatch {
+    Name(String),
+    Method(u16),
+    Crc32(u32),
+    CompressedSize(usize),
+    Size(usize),
+}
+
+// ----------------------------------------------------------------------------
+// Outcome of `zip_validate` for an entry of the central directory.
+#[derive(Debug, PartialEq)]
+pub struct EntryReport {
+    pub entry: EntryInfo,
+    pub mismatches: Vec<Mismatch>,
+    // reading the local header and extracting the data with CRC-32 verification, encrypted
+    // entries are not decrypted and fail with `Error::Encrypted`
+    pub status: Result<()>,
+}
+
+// ----------------------------------------------------------------------------
+impl EntryReport {
+    pub fn is_ok(&self) -> bool {
+        self.mismatches.is_empty() && self.status.is_ok()
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Local header values compared by `zip_validate`.
+struct LocalHeader {
+    name: String,
+    method: u16,
+    flags: u16,
+    crc32: u32,
+    compressed_size: usize,
+    size: usize,
+    zip64: bool,
+    // offset of the entry data
+    start: usize,
+}
+
+// ----------------------------------------------------------------------------
+// Reads the local header at `ofs`. The values of an entry with a data descriptor are taken from
+// the descriptor `compressed_size` bytes after the data, or left as they are without a size.
+fn read_local_header(
+    data: &[u8],
+    ofs: usize,
+    cd_compressed_size: Option<usize>,
+) -> Result<LocalHeader> {
+    let hdr = data
+        .get(ofs..ofs.saturating_add(30))
+        .ok_or(Error::BufferError)?;
+    if !hdr.starts_with(&[0x50, 0x4b, 0x03, 0x04]) {
+        return Err(Error::InvalidSignature);
+    }
+
+    let flags = u16::from_le_bytes(hdr[6..8].try_into()?);
+    let method = u16::from_le_bytes(hdr[8..10].try_into()?);
+    let mut crc32 = u32::from_le_bytes(hdr[14..18].try_into()?);
+    let mut compressed_size = u32::from_le_bytes(hdr[18..22].try_into()?) as usize;
+    let mut size = u32::from_le_bytes(hdr[22..26].try_into()?) as usize;
+    let name_len = u16::from_le_bytes(hdr[26..28].try_into()?) as usize;
+    let extra_len = u16::from_le_bytes(hdr[28..30].try_into()?) as usize;
+
+    let name = data
+        .get(ofs + 30..ofs + 30 + name_len)
+        .ok_or(Error::BufferError)?;
+    let extra_start = ofs + 30 + name_len;
+    let extra = data
+        .get(extra_start..extra_start + extra_len)
+        .ok_or(Error::BufferError)?;
+
+    let start = extra_start + extra_len;
+    let zip64 = extra_field(extra, 0x0001).is_some();
+    if flags & 0x08 == 0 {
+        // the ZIP64 extra field of a local header holds both sizes, in this order
+        read_zip64_fields(extra, &mut [&mut size, &mut compressed_size])?;
+    } else if let Some(len) = cd_compressed_size {
+        // the descriptor follows the data
+        let descriptor = data
+            .get(start.saturating_add(len)..)
+            .ok_or(Error::BufferError)?;
+        (crc32, compressed_size, size) = read_data_descriptor(descriptor, zip64)?;
+    }
+
+    Ok(LocalHeader {
+        name: decode_name(name, flags, extra),
+        method,
+        flags,
+        crc32,
+        compressed_size,
+        size,
+        zip64,
+        start,
+    })
+}
+
+// ----------------------------------------------------------------------------
+fn validate_entry(data: &[u8], entry: EntryInfo) -> EntryReport {
+    let mut mismatches = Vec::new();
+    let compressed_size = Some(entry.compressed_size);
+    let status = read_local_header(data, entry.offset, compressed_size).and_then(|local| {
+        let encrypted = (local.flags | entry.flags) & 0x01 != 0;
+        if local.name != entry.name {
+            mismatches.push(Mismatch::Name(local.name));
+        }
+        if local.method != entry.method {
+            mismatches.push(Mismatch::Method(local.method));
+        }
+        if local.crc32 != entry.crc32 {
+            mismatches.push(Mismatch::Crc32(local.crc32));
+        }
+        if local.compressed_size != entry.compressed_size {
+            mismatches.push(Mismatch::CompressedSize(local.compressed_size));
+        }
+        if local.size != entry.size {
+            mismatches.push(Mismatch::Size(local.size));
+        }
+
+        if encrypted {
+            return Err(Error::Encrypted);
+        }
+        let file = File::from(entry.clone());
+        extract_file(data, &file, &mut Heap, &InflateOptions::default(), None).map(|_| ())
+    });
+    EntryReport {
+        entry,
+        mismatches,
+        status,
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Cross-checks the local header of every entry against its central directory record and
+// extracts each entry to verify its CRC-32. Listings show the central directory while some
+// extractors follow the local headers, an archive crafted to show one name and extract another
+// has mismatches. Fails only if the central directory cannot be read.
+pub fn zip_validate(data: &[u8]) -> Result<Vec<EntryReport>> {
+    let entries = zip_entries(data)?;
+    Ok(entries
+        .into_iter()
+        .map(|entry| validate_entry(data, entry))
+        .collect())
+}
+
+// ----------------------------------------------------------------------------
+// Length of the deflate stream at the start of `data`.
+fn deflate_len(data: &[u8]) -> Option<usize> {
+    let mut inflater = crate::stream::Inflater::new(false);
+    let mut out = vec![0; 32 * 1024];
+    let mut input = data;
+    while !inflater.is_finished() {
+        let (_, consumed, produced) = inflater.decompress(input, &mut out).ok()?;
+        if consumed == 0 && produced == 0 {
+            return None;
+        }
+        input = &input[consumed..];
+    }
+    Some(inflater.total_in() as usize)
+}
+
+// ----------------------------------------------------------------------------
+// Locates the data descriptor behind the entry data at the start of `data`, returns (CRC-32,
+// compressed size, size, end of the descriptor). A signature whose compressed size matches its
+// position marks the descriptor, deflate data also ends by itself.
+fn find_data_descriptor(
+    data: &[u8],
+    method: u16,
+    zip64: bool,
+) -> Option<(u32, usize, usize, usize)> {
+    const SIGNATURE: [u8; 4] = [0x50, 0x4b, 0x07, 0x08];
+    let values_len = if zip64 { 20 } else { 12 };
+    let at = |pos: usize| {
+        let descriptor = data.get(pos..)?;
+        let (crc32, compressed_size, size) = read_data_descriptor(descriptor, zip64).ok()?;
+        let signature = if descriptor.starts_with(&SIGNATURE) {
+            4
+        } else {
+            0
+        };
+        let end = pos + signature + values_len;
+        (compressed_size == pos && end <= data.len()).then_some((crc32, pos, size, end))
+    };
+    let signed = data.windows(4).enumerate().filter(|(_, w)| *w == SIGNATURE);
+    let found = signed.map(|(pos, _)| pos).find_map(at);
+    match found {
+        None if method == 8 => at(deflate_len(data)?),
+        found => found,
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Entry of the local header at `ofs` and the offset following its data, `None` if the data is
+// cut off or cannot be delimited.
+fn recover_entry(data: &[u8], ofs: usize) -> Option<(File, usize)> {
+    let local = read_local_header(data, ofs, None).ok()?;
+    let (crc32, compressed_size, size, end) = if local.flags & 0x08 == 0 {
+        let end = local.start.checked_add(local.compressed_size)?;
+        (local.crc32, local.compressed_size, local.size, end)
+    } else {
+        let (crc32, compressed_size, size, end) =
+            find_data_descriptor(&data[local.start..], local.method, local.zip64)?;
+        (crc32, compressed_size, size, local.start + end)
+    };
+    if end > data.len() {
+        return None;
+    }
+    let file = File {
+        name: local.name,
+        offset: ofs,
+        crc32,
+        compressed_size,
+        size,
+        external_attributes: 0,
+    };
+    Some((file, end))
+}
+
+// ----------------------------------------------------------------------------
+// Like `zip_open`, but if the end of central directory record or the central directory is
+// missing or damaged, e.g. in a partial download, the entries are reconstructed from the local
+// file headers found in `data`. Entries whose data is cut off are left out, the others can be
+// read with `zip_read`. Fails with the error of `zip_open` if no entry is found.
+pub fn zip_open_lossy(data: &[u8]) -> Result<Vec<File>> {
+    const SIGNATURE: [u8; 4] = [0x50, 0x4b, 0x03, 0x04];
+    let err = match zip_open(data) {
+        Ok(files) => return Ok(files),
+        Err(err) => err,
+    };
+
+    let mut files = Vec::new();
+    let mut pos = 0;
+    while let Some(found) = data[pos..].windows(4).position(|w| w == SIGNATURE) {
+        let ofs = pos + found;
+        pos = match recover_entry(data, ofs) {
+            Some((file, end)) => {
+                files.push(file);
+                end
+            }
+            None => ofs + 4,
+        };
+    }
+    if files.is_empty() {
+        Err(err)
+    } else {
+        Ok(files)
+    }
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(feature = "std")]
+enum EntrySource<'a> {
+    Stored(&'a [u8]),
+    Deflated {
+        compressed: &'a [u8],
+        size: u64,
+        inflater: Box<Inflater>,
+        // compressed bytes passed to the inflater and the decoded position
+        in_pos: usize,
+        out_pos: u64,
+    },
+}
+
+// ----------------------------------------------------------------------------
+// Read + Seek access to the uncompressed content of a single entry. Stored entries are read in
+// place, deflated entries are decoded as they are read with only the window and the current
+// block in memory. Seeking forward decodes and discards, seeking backward decodes again from the
+// start of the entry. Content read from the start through to the end is checked against the
+// CRC-32 of the entry, a mismatch fails the read at the end with `Error::ChecksumMismatch`.
+#[cfg(feature = "std")]
+pub struct ZipEntryReader<'a> {
+    source: EntrySource<'a>,
+    pos: u64,
+    // expected CRC-32, and the CRC-32 of the content read in order up to `crc_pos`
+    crc32: u32,
+    crc: u32,
+    crc_pos: u64,
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(feature = "std")]
+impl ZipEntryReader<'_> {
+    // compressed input passed to the decoder per step, bounds the decoded data buffered at once
+    const INPUT_STEP: usize = 4096;
+
+    pub fn len(&self) -> u64 {
+        match &self.source {
+            EntrySource::Stored(data) => data.len() as u64,
+            EntrySource::Deflated { size, .. } => *size,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Extends the running CRC-32 with `data` read at `pos`, checks it once the end is reached.
+    fn update_crc(&mut self, pos: u64, data: &[u8]) -> std::io::Result<()> {
+        if pos != self.crc_pos {
+            return Ok(());
+        }
+        if data.is_empty() {
+            if self.crc_pos == self.len() && self.crc != self.crc32 {
+                return Err(crate::file::invalid_data(Error::ChecksumMismatch));
+            }
+            return Ok(());
+        }
+        self.crc = checksum::crc32(self.crc, data);
+        self.crc_pos += data.len() as u64;
+        Ok(())
+    }
+
+    // Decodes the next bytes of a deflated entry into `out`, 0 at the end of the entry.
+    fn decode(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+        use crate::file::invalid_data;
+        use crate::stream::Status;
+
+        let EntrySource::Deflated {
+            compressed,
+            size,
+            inflater,
+            in_pos,
+            out_pos,
+        } = &mut self.source
+        else {
+            return Ok(0);
+        };
+
+        let len = out
+            .len()
+            .min((*size - *out_pos).try_into().unwrap_or(usize::MAX));
+        let out = &mut out[..len];
+        if out.is_empty() {
+            return Ok(0);
+        }
+
+        // more input only once the decoded data is used up
+        let mut input: &[u8] = &[];
+        loop {
+            let (status, _, produced) = inflater.decompress(input, out).map_err(invalid_data)?;
+            if produced != 0 {
+                *out_pos += produced as u64;
+                return Ok(produced);
+            }
+            if status == Status::StreamEnd || *in_pos == compressed.len() {
+                // the stream ended before the size recorded in the archive
+                return Err(invalid_data(Error::InvalidZip));
+            }
+            let end = compressed.len().min(*in_pos + Self::INPUT_STEP);
+            input = &compressed[*in_pos..end];
+            *in_pos = end;
+        }
     }
 }
 
+// ----------------------------------------------------------------------------
+#[cfg(feature = "std")]
+impl std::io::Read for ZipEntryReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let pos = self.pos;
+        if let EntrySource::Stored(data) = self.source {
+            let start = pos.min(data.len() as u64) as usize;
+            let len = buf.len().min(data.len() - start);
+            buf[..len].copy_from_slice(&data[start..start + len]);
+            self.update_crc(pos, &buf[..len])?;
+            self.pos += len as u64;
+            return Ok(len);
+        }
+
+        let EntrySource::Deflated {
+            inflater,
+            in_pos,
+            out_pos,
+            ..
+        } = &mut self.source
+        else {
+            return Ok(0);
+        };
+        if pos < *out_pos {
+            inflater.reset(false);
+            (*in_pos, *out_pos) = (0, 0);
+        }
+
+        let mut skip = [0u8; 4096];
+        while let EntrySource::Deflated { out_pos, .. } = self.source {
+            if out_pos >= pos {
+                break;
+            }
+            let len = skip
+                .len()
+                .min((pos - out_pos).try_into().unwrap_or(usize::MAX));
+            if self.decode(&mut skip[..len])? == 0 {
+                // seeking behind the end reads nothing
+                return Ok(0);
+            }
+        }
+
+        let len = self.decode(buf)?;
+        self.update_crc(pos, &buf[..len])?;
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(feature = "std")]
+impl std::io::Seek for ZipEntryReader<'_> {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        let (base, offset) = match pos {
+            std::io::SeekFrom::Start(pos) => (pos, 0),
+            std::io::SeekFrom::End(offset) => (self.len(), offset),
+            std::io::SeekFrom::Current(offset) => (self.pos, offset),
+        };
+
+        self.pos = base.checked_add_signed(offset).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "seek before start")
+        })?;
+        Ok(self.pos)
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Encrypted entries fail with `Error::Encrypted`.
+#[cfg(feature = "std")]
+pub fn zip_entry_reader<'a>(
+    data: &'a [u8],
+    files: &[File],
+    name: &str,
+) -> Result<ZipEntryReader<'a>> {
+    let file = files
+        .iter()
+        .find(|file| file.name == name)
+        .ok_or(Error::FileNotFound)?;
+    let entry = read_local_entry(data, file)?;
+    if entry.is_encrypted() {
+        return Err(Error::Encrypted);
+    }
+
+    let source = match entry.compression_method {
+        0 => EntrySource::Stored(entry.compressed),
+        8 => EntrySource::Deflated {
+            compressed: entry.compressed,
+            size: entry.uncompressed_size as u64,
+            inflater: Box::new(Inflater::new(false)),
+            in_pos: 0,
+            out_pos: 0,
+        },
+        method => return Err(Error::InvalidCompressionMethod(method)),
+    };
+
+    Ok(ZipEntryReader {
+        source,
+        pos: 0,
+        crc32: entry.crc32,
+        crc: 0,
+        crc_pos: 0,
+    })
+}
+
 // ----------------------------------------------------------------------------
 pub fn zip_read(data: &[u8], files: &[File], name: &str) -> Result<Vec<u8>> {
+    zip_read_with(data, files, name, &mut Heap)
+}
+
+// ----------------------------------------------------------------------------
+// Like `zip_read`, but the output buffer is obtained from `provider`.
+pub fn zip_read_with<P: BufferProvider>(
+    data: &[u8],
+    files: &[File],
+    name: &str,
+    provider: &mut P,
+) -> Result<P::Buffer> {
+    zip_read_with_options(data, files, name, provider, &InflateOptions::default())
+}
+
+// ----------------------------------------------------------------------------
+// Like `zip_read_with`, with `options` applied to the decompression of deflated entries.
+pub fn zip_read_with_options<P: BufferProvider>(
+    data: &[u8],
+    files: &[File],
+    name: &str,
+    provider: &mut P,
+    options: &InflateOptions,
+) -> Result<P::Buffer> {
     for file in files {
         if file.name == name {
-            return extract_file(data, file);
+            return extract_file(data, file, provider, options, None);
         }
     }
     Err(Error::FileNotFound)
 }
 
+// ----------------------------------------------------------------------------
+// Like `zip_read`, for entries encrypted with the traditional PKWARE encryption (ZipCrypto) or,
+// with the `aes` feature, WinZip AES. A wrong password fails with `Error::WrongPassword`, AES
+// data not matching its authentication code with `Error::ChecksumMismatch`. Unencrypted entries
+// are read as they are.
+pub fn zip_read_encrypted(
+    data: &[u8],
+    files: &[File],
+    name: &str,
+    password: &[u8],
+) -> Result<Vec<u8>> {
+    let file = files
+        .iter()
+        .find(|file| file.name == name)
+        .ok_or(Error::FileNotFound)?;
+    let options = InflateOptions::default();
+    extract_file(data, file, &mut Heap, &options, Some(password))
+}
+
+// ----------------------------------------------------------------------------
+// Writes the contents of the entry `name` to `writer`, deflated entries are decoded block by
+// block with memory independent of the entry size. Returns the number of written bytes, ZIP
+// errors are returned as `io::ErrorKind::InvalidData`.
+#[cfg(feature = "std")]
+pub fn zip_read_to<W: std::io::Write>(
+    data: &[u8],
+    files: &[File],
+    name: &str,
+    writer: &mut W,
+) -> std::io::Result<u64> {
+    use crate::file::{inflate_io, invalid_data, Input};
+
+    let file = files
+        .iter()
+        .find(|file| file.name == name)
+        .ok_or_else(|| invalid_data(Error::FileNotFound))?;
+    let entry = read_local_entry(data, file).map_err(invalid_data)?;
+    if entry.is_encrypted() {
+        return Err(invalid_data(Error::WrongPassword));
+    }
+
+    let mut crc = 0;
+    let total = match entry.compression_method {
+        0 => {
+            crc = checksum::crc32(0, entry.compressed);
+            writer.write_all(entry.compressed)?;
+            entry.compressed.len() as u64
+        }
+        8 => inflate_io(&mut Input::new(entry.compressed), |chunk| {
+            crc = checksum::crc32(crc, chunk);
+            writer.write_all(chunk)
+        })?,
+        method => return Err(invalid_data(Error::InvalidCompressionMethod(method))),
+    };
+
+    if total != entry.uncompressed_size as u64 {
+        return Err(invalid_data(Error::InvalidZip));
+    }
+    if crc != entry.crc32 {
+        return Err(invalid_data(Error::ChecksumMismatch));
+    }
+    Ok(total)
+}
+
 // ----------------------------------------------------------------------------
 pub fn zip_open(data: &[u8]) -> Result<Vec<File>> {
-    let (cd_size, cd_offset, total_entries) = read_eocd(data)?;
-    read_cd(&data[cd_offset..cd_offset + cd_size], total_entries)
+    zip_open_with_options(data, &InflateOptions::default())
+}
+
+// ----------------------------------------------------------------------------
+// Like `zip_open`, anomalies in the archive structure are reported to `options.warnings`.
+pub fn zip_open_with_options(data: &[u8], options: &InflateOptions) -> Result<Vec<File>> {
+    let entries = zip_entries_with_options(data, options)?;
+    Ok(entries.into_iter().map(File::from).collect())
+}
+
+// ----------------------------------------------------------------------------
+// Comment of the end of central directory record at `eocd_offset`, cut off at the end of `data`.
+pub(crate) fn archive_comment(data: &[u8], eocd_offset: usize) -> Result<&[u8]> {
+    let len = data.get(eocd_offset + 20..eocd_offset + 22);
+    let len = u16::from_le_bytes(len.ok_or(Error::BufferError)?.try_into()?) as usize;
+    let start = data.len().min(eocd_offset + EOCD_SIZE);
+    let end = data.len().min(start + len);
+    Ok(&data[start..end])
+}
+
+// ----------------------------------------------------------------------------
+// Comment of the archive as raw bytes, ZIP records no encoding for it. The comments of the
+// entries are in `EntryInfo::comment`.
+pub fn zip_comment(data: &[u8]) -> Result<&[u8]> {
+    let (_, _, _, eocd_offset, _) = read_eocd(data, &InflateOptions::default())?;
+    archive_comment(data, eocd_offset)
+}
+
+// ----------------------------------------------------------------------------
+// Like `zip_open`, with the full central directory record of each entry.
+pub fn zip_entries(data: &[u8]) -> Result<Vec<EntryInfo>> {
+    zip_entries_with_options(data, &InflateOptions::default())
+}
+
+// ----------------------------------------------------------------------------
+pub fn zip_entries_with_options(data: &[u8], options: &InflateOptions) -> Result<Vec<EntryInfo>> {
+    trace_span!("zip_open", len = data.len());
+    let (cd_size, cd_offset, total_entries, _, prefix) = read_eocd(data, options)?;
+    trace_event!(
+        debug,
+        cd_offset,
+        cd_size,
+        total_entries,
+        "zip central directory"
+    );
+    let cd = data
+        .get(cd_offset..cd_offset.saturating_add(cd_size))
+        .ok_or(Error::BufferError)?;
+    read_cd(cd, total_entries, prefix, options)
+}
+
+// ----------------------------------------------------------------------------
+// Restores the modification time and permission bits of an extracted file or directory. The time
+// goes first, the permissions may take away the right to change it.
+#[cfg(feature = "std")]
+fn restore_metadata(path: &std::path::Path, entry: &EntryInfo) -> std::io::Result<()> {
+    if let Some(time) = entry.modified() {
+        set_modified(path, time)?;
+    }
+
+    #[cfg(unix)]
+    if let Some(mode) = entry.unix_mode() {
+        use std::os::unix::fs::PermissionsExt;
+        // permission bits only, no setuid, setgid or sticky bits
+        let permissions = std::fs::Permissions::from_mode(mode & 0o777);
+        std::fs::set_permissions(path, permissions)?;
+    }
+    Ok(())
+}
+
+// ----------------------------------------------------------------------------
+// The owner may set the times through a read-only handle, which opens directories as well.
+#[cfg(all(feature = "std", unix))]
+fn set_modified(path: &std::path::Path, time: std::time::SystemTime) -> std::io::Result<()> {
+    std::fs::File::open(path)?.set_modified(time)
+}
+
+// ----------------------------------------------------------------------------
+// Directories can't be opened for writing, they keep the time of their extraction.
+#[cfg(all(feature = "std", not(unix)))]
+fn set_modified(path: &std::path::Path, time: std::time::SystemTime) -> std::io::Result<()> {
+    if path.is_dir() {
+        return Ok(());
+    }
+    std::fs::File::options()
+        .write(true)
+        .open(path)?
+        .set_modified(time)
+}
+
+// ----------------------------------------------------------------------------
+// Extracts the entries of the archive below `dir`. `map` turns each entry name into a path
+// relative to `dir` or returns `None` to skip the entry, e.g. to flatten folders, strip prefixes
+// or rename entries. Mapped paths leaving `dir` are skipped, names ending in '/' create
+// directories. Files and directories get the modification time of the entry and, on Unix, the
+// permission bits recorded by Unix archivers, directories once all entries are extracted.
+// Returns the number of extracted files, ZIP errors are returned as `io::ErrorKind::InvalidData`.
+#[cfg(feature = "std")]
+pub fn zip_extract_all<F>(
+    data: &[u8],
+    files: &[File],
+    dir: &std::path::Path,
+    mut map: F,
+) -> std::io::Result<usize>
+where
+    F: FnMut(&str) -> Option<std::path::PathBuf>,
+{
+    use std::path::Component;
+
+    let invalid_data = |err| std::io::Error::new(std::io::ErrorKind::InvalidData, err);
+    let entries = zip_entries(data).map_err(invalid_data)?;
+    let entries: BTreeMap<usize, &EntryInfo> = entries.iter().map(|e| (e.offset, e)).collect();
+
+    let mut count = 0;
+    let mut dirs = Vec::new();
+    for file in files {
+        let Some(path) = map(&file.name) else {
+            continue;
+        };
+        if !path.components().all(|c| matches!(c, Component::Normal(_))) {
+            trace_event!(warn, name = %file.name, "zip entry mapped outside of target");
+            continue;
+        }
+
+        let path = dir.join(path);
+        if file.name.ends_with('/') {
+            std::fs::create_dir_all(&path)?;
+            if let Some(entry) = entries.get(&file.offset) {
+                dirs.push((path, *entry));
+            }
+            continue;
+        }
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = extract_file(data, file, &mut Heap, &InflateOptions::default(), None)
+            .map_err(invalid_data)?;
+        std::fs::write(&path, content)?;
+        if let Some(entry) = entries.get(&file.offset) {
+            restore_metadata(&path, entry)?;
+        }
+        count += 1;
+    }
+
+    // extracting into a directory changes its time, and its permissions may forbid it, nested
+    // directories usually follow their parents
+    for (path, entry) in dirs.iter().rev() {
+        restore_metadata(path, entry)?;
+    }
+    Ok(count)
+}
+
+// ----------------------------------------------------------------------------
+// Matches `name` against `pattern`, '*' stands for any run and '?' for a single character
+// except '/'.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<char>, Vec<char>) =
+        (pattern.chars().collect(), name.chars().collect());
+    let (mut p, mut n) = (0, 0);
+    // position of the last '*' and the name position it was tried at
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if (c == '?' && name[n] != '/') || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                // let the last '*' take one more character
+                Some((sp, sn)) if name[sn] != '/' => {
+                    star = Some((sp, sn + 1));
+                    p = sp + 1;
+                    n = sn + 1;
+                }
+                _ => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+// ----------------------------------------------------------------------------
+// Position of each entry name in the central directory, the first of duplicate names wins.
+fn name_index(files: &[File]) -> BTreeMap<String, usize> {
+    let mut index = BTreeMap::new();
+    for (i, file) in files.iter().enumerate() {
+        index.entry(file.name.clone()).or_insert(i);
+    }
+    index
+}
+
+// ----------------------------------------------------------------------------
+// Archive bytes together with their parsed central directory, the bytes are either owned or
+// borrowed from a buffer or an enclosing archive. Entries are indexed by name.
+#[derive(Debug)]
+pub struct ZipArchive<'a> {
+    data: Cow<'a, [u8]>,
+    files: Vec<File>,
+    index: BTreeMap<String, usize>,
+}
+
+// ----------------------------------------------------------------------------
+impl<'a> ZipArchive<'a> {
+    pub fn from_vec(data: Vec<u8>) -> Result<ZipArchive<'static>> {
+        let files = zip_open(&data)?;
+        Ok(ZipArchive {
+            data: Cow::Owned(data),
+            index: name_index(&files),
+            files,
+        })
+    }
+
+    pub fn from_slice(data: &'a [u8]) -> Result<Self> {
+        let files = zip_open(data)?;
+        Ok(ZipArchive {
+            data: Cow::Borrowed(data),
+            index: name_index(&files),
+            files,
+        })
+    }
+
+    // Reads only the central directory of the archive in `reader`, entries are read on demand,
+    // see `ZipReader`.
+    #[cfg(feature = "std")]
+    pub fn from_reader<R: std::io::Read + std::io::Seek>(
+        reader: R,
+    ) -> std::io::Result<ZipReader<R>> {
+        ZipReader::new(reader)
+    }
+
+    // Comment of the archive, see `zip_comment`.
+    pub fn comment(&self) -> &[u8] {
+        zip_comment(&self.data).unwrap_or_default()
+    }
+
+    // Starts adding, replacing or removing entries of this archive, see `ZipAppender`.
+    pub fn append(&self) -> Result<crate::zip_write::ZipAppender<'_>> {
+        crate::zip_write::ZipAppender::new(&self.data)
+    }
+
+    // Reads the archive at `path`, ZIP errors are returned as `io::ErrorKind::InvalidData`.
+    #[cfg(feature = "std")]
+    pub fn open_path<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<ZipArchive<'static>> {
+        let data = std::fs::read(path)?;
+        ZipArchive::from_vec(data)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn files(&self) -> &[File] {
+        &self.files
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn iter(&self) -> core::slice::Iter<'_, File> {
+        self.files.iter()
+    }
+
+    // Entry `name` looked up in the index.
+    pub fn file(&self, name: &str) -> Option<&File> {
+        self.index.get(name).map(|&i| &self.files[i])
+    }
+
+    // Entries whose names start with `prefix`, e.g. "dir/" for the contents of a folder, in
+    // name order.
+    pub fn entries_with_prefix<'b>(&'b self, prefix: &'b str) -> impl Iterator<Item = &'b File> {
+        self.index
+            .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .take_while(move |(name, _)| name.starts_with(prefix))
+            .map(|(_, &i)| &self.files[i])
+    }
+
+    // Entries whose names match `pattern` in name order, '*' stands for any run and '?' for a
+    // single character except '/', e.g. "textures/*.png".
+    pub fn entries_matching<'b>(&'b self, pattern: &'b str) -> impl Iterator<Item = &'b File> {
+        let literal = pattern.find(['*', '?']).unwrap_or(pattern.len());
+        self.entries_with_prefix(&pattern[..literal])
+            .filter(move |file| glob_match(pattern, &file.name))
+    }
+
+    pub fn by_name(&self, name: &str) -> Result<Vec<u8>> {
+        let file = self.file(name).ok_or(Error::FileNotFound)?;
+        let options = InflateOptions::default();
+        extract_file(&self.data, file, &mut Heap, &options, None)
+    }
+
+    // Reads the encrypted entry `name`, see `zip_read_encrypted`.
+    pub fn by_name_encrypted(&self, name: &str, password: &[u8]) -> Result<Vec<u8>> {
+        let file = self.file(name).ok_or(Error::FileNotFound)?;
+        let options = InflateOptions::default();
+        extract_file(&self.data, file, &mut Heap, &options, Some(password))
+    }
+
+    // Opens the entry `name` as an archive of its own, a stored entry is used in place without
+    // a copy, a deflated one is decompressed into memory first.
+    pub fn open_nested(&self, name: &str) -> Result<ZipArchive<'_>> {
+        match entry_data(&self.data, &self.files, name, &InflateOptions::default())? {
+            Cow::Borrowed(data) => ZipArchive::from_slice(data),
+            Cow::Owned(data) => ZipArchive::from_vec(data),
+        }
+    }
+
+    // Entry `name` borrowed from the archive if it is stored, see `zip_entry_slice`.
+    pub fn entry_slice(&self, name: &str) -> Result<Cow<'_, [u8]>> {
+        entry_data(&self.data, &self.files, name, &InflateOptions::default())
+    }
+
+    pub fn by_index(&self, index: usize) -> Result<Vec<u8>> {
+        let file = self.files.get(index).ok_or(Error::FileNotFound)?;
+        let options = InflateOptions::default();
+        extract_file(&self.data, file, &mut Heap, &options, None)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn entry_reader(&self, name: &str) -> Result<ZipEntryReader<'_>> {
+        zip_entry_reader(&self.data, &self.files, name)
+    }
+
+    // Extracts the entries below `dir`, see `zip_extract_all`.
+    #[cfg(feature = "std")]
+    pub fn extract_all<F>(&self, dir: &std::path::Path, map: F) -> std::io::Result<usize>
+    where
+        F: FnMut(&str) -> Option<std::path::PathBuf>,
+    {
+        zip_extract_all(&self.data, &self.files, dir, map)
+    }
+
+    // Checks local headers against the central directory and the CRC-32 of every entry, see
+    // `zip_validate`.
+    pub fn validate(&self) -> Result<Vec<EntryReport>> {
+        zip_validate(&self.data)
+    }
+
+    // Decodes the PNG entry `name`, see `zip_read_png`.
+    #[cfg(feature = "png")]
+    pub fn read_png(
+        &self,
+        name: &str,
+        options: &InflateOptions,
+    ) -> crate::Result<(crate::png_read::PNGChunkIHDR, Vec<u32>, Vec<u8>)> {
+        crate::zip_read_png(&self.data, &self.files, name, options)
+    }
+}
+
+// ----------------------------------------------------------------------------
+impl<'b> IntoIterator for &'b ZipArchive<'_> {
+    type Item = &'b File;
+    type IntoIter = core::slice::Iter<'b, File>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.files.iter()
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Archive read through `Read + Seek`, e.g. a file too large for memory. Only the end of the
+// archive and the central directory are read up front, extracting an entry seeks to it and reads
+// just its compressed bytes. ZIP errors are returned as `io::ErrorKind::InvalidData`.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct ZipReader<R> {
+    reader: R,
+    files: Vec<File>,
+    index: BTreeMap<String, usize>,
+    comment: Vec<u8>,
+}
+
+// ----------------------------------------------------------------------------
+// Reads up to `len` bytes at `offset`, fewer only at the end of the stream.
+#[cfg(feature = "std")]
+fn read_at<R: std::io::Read + std::io::Seek>(
+    reader: &mut R,
+    offset: u64,
+    len: usize,
+    buf: &mut Vec<u8>,
+) -> std::io::Result<usize> {
+    use std::io::Read;
+    reader.seek(std::io::SeekFrom::Start(offset))?;
+    // grown as data arrives, a forged size cannot force a large allocation
+    reader.by_ref().take(len as u64).read_to_end(buf)
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(feature = "std")]
+impl<R: std::io::Read + std::io::Seek> ZipReader<R> {
+    pub fn new(reader: R) -> std::io::Result<Self> {
+        Self::with_options(reader, &InflateOptions::default())
+    }
+
+    // Like `new`, anomalies in the archive structure are reported to `options.warnings`.
+    pub fn with_options(mut reader: R, options: &InflateOptions) -> std::io::Result<Self> {
+        use crate::file::invalid_data;
+
+        let len = reader.seek(std::io::SeekFrom::End(0))?;
+        let len = usize::try_from(len).map_err(|_| invalid_data(Error::BufferError))?;
+
+        // the record, the longest comment and a ZIP64 locator in front of them
+        let base = len.saturating_sub(EOCD_SIZE + MAX_COMMENT_LEN + 20);
+        let mut tail = Vec::new();
+        read_at(&mut reader, base as u64, len - base, &mut tail)?;
+
+        let mut io_error = None;
+        let eocd = find_eocd(&tail, base, options, |ofs| {
+            let mut record = Vec::new();
+            match read_at(&mut reader, ofs as u64, 56, &mut record) {
+                Ok(_) => Ok(record),
+                Err(err) => {
+                    io_error = Some(err);
+                    Err(Error::BufferError)
+                }
+            }
+        });
+        if let Some(err) = io_error {
+            return Err(err);
+        }
+        let (cd_size, cd_offset, total_entries, eocd_offset, prefix) =
+            eocd.map_err(invalid_data)?;
+        let comment = archive_comment(&tail, eocd_offset - base).map_err(invalid_data)?;
+        let comment = comment.to_vec();
+
+        let mut cd = Vec::new();
+        if read_at(&mut reader, cd_offset as u64, cd_size, &mut cd)? != cd_size {
+            return Err(invalid_data(Error::BufferError));
+        }
+        let entries = read_cd(&cd, total_entries, prefix, options).map_err(invalid_data)?;
+        let files: Vec<File> = entries.into_iter().map(File::from).collect();
+        Ok(ZipReader {
+            reader,
+            index: name_index(&files),
+            files,
+            comment,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn files(&self) -> &[File] {
+        &self.files
+    }
+
+    // Entry `name` looked up in the index.
+    pub fn file(&self, name: &str) -> Option<&File> {
+        self.index.get(name).map(|&i| &self.files[i])
+    }
+
+    // Comment of the archive, see `zip_comment`.
+    pub fn comment(&self) -> &[u8] {
+        &self.comment
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    pub fn by_name(&mut self, name: &str) -> std::io::Result<Vec<u8>> {
+        let index = self.index.get(name).copied();
+        self.extract(index, None)
+    }
+
+    pub fn by_index(&mut self, index: usize) -> std::io::Result<Vec<u8>> {
+        self.extract(Some(index), None)
+    }
+
+    // Reads the encrypted entry `name`, see `zip_read_encrypted`.
+    pub fn by_name_encrypted(&mut self, name: &str, password: &[u8]) -> std::io::Result<Vec<u8>> {
+        let index = self.index.get(name).copied();
+        self.extract(index, Some(password))
+    }
+
+    // Reads the local header, the compressed data and a data descriptor of the entry into a
+    // buffer of their own and extracts the entry from it.
+    fn extract(
+        &mut self,
+        index: Option<usize>,
+        password: Option<&[u8]>,
+    ) -> std::io::Result<Vec<u8>> {
+        use crate::file::invalid_data;
+
+        let file = index.and_then(|i| self.files.get(i));
+        let file = file.ok_or_else(|| invalid_data(Error::FileNotFound))?;
+        let mut local = Vec::new();
+        read_at(&mut self.reader, file.offset as u64, 30, &mut local)?;
+        let hdr = local
+            .get(26..30)
+            .ok_or_else(|| invalid_data(Error::BufferError))?;
+        let name_len = u16::from_le_bytes([hdr[0], hdr[1]]) as usize;
+        let extra_len = u16::from_le_bytes([hdr[2], hdr[3]]) as usize;
+
+        // the data descriptor takes at most 24 bytes with the signature and ZIP64 sizes
+        let len = name_len + extra_len + file.compressed_size.saturating_add(24);
+        read_at(&mut self.reader, file.offset as u64 + 30, len, &mut local)?;
+        let file = File {
+            offset: 0,
+            ..file.clone()
+        };
+        let options = InflateOptions::default();
+        extract_file(&local, &file, &mut Heap, &options, password).map_err(invalid_data)
+    }
 }
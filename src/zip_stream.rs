@@ -0,0 +1,137 @@
+// Sequential ZIP reading from forward-only streams such as stdin or HTTP bodies.
+// * https://pkware.cachefly.net/webdocs/casestudies/APPNOTE.TXT (sections 4.3.7 and 4.3.9)
+// * entries are parsed from their local headers in file order, the central directory is not
+//   read, reading stops at its first record
+// * deflated entries with a data descriptor (flag bit 3) end with their deflate stream, stored
+//   entries need the sizes in the local header
+use crate::checksum;
+use crate::file::{inflate_io, invalid_data, Input};
+use crate::inflate;
+use crate::zip_read::{decode_name, extended_mtime, ntfs_mtime, EntryInfo, Error};
+use std::io::{self, Read};
+
+// ----------------------------------------------------------------------------
+const FLAG_DATA_DESCRIPTOR: u16 = 0x0008;
+
+// ----------------------------------------------------------------------------
+fn u16_at(data: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes([data[pos], data[pos + 1]])
+}
+
+// ----------------------------------------------------------------------------
+fn u32_at(data: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]])
+}
+
+// ----------------------------------------------------------------------------
+// Yields the entries of an archive together with their contents as they arrive. The returned
+// `EntryInfo` is built from the local header and the data descriptor, `external_attributes`,
+// `comment` and `version_made_by` are only stored in the central directory and left empty. Errors are returned as
+// `io::ErrorKind::InvalidData` wrapping `crate::Error`.
+pub struct ZipStreamReader<R> {
+    input: Input<R>,
+    done: bool,
+}
+
+// ----------------------------------------------------------------------------
+impl<R: Read> ZipStreamReader<R> {
+    pub fn new(reader: R) -> Self {
+        ZipStreamReader {
+            input: Input::new(reader),
+            done: false,
+        }
+    }
+
+    pub fn next_entry(&mut self) -> io::Result<Option<(EntryInfo, Vec<u8>)>> {
+        if self.done {
+            return Ok(None);
+        }
+
+        let offset = self.input.position() as usize;
+        let signature = self.input.take_aligned(4)?;
+        match signature[..] {
+            [0x50, 0x4b, 0x03, 0x04] => {}
+            // central directory or end of an empty archive
+            [0x50, 0x4b, 0x01, 0x02] | [0x50, 0x4b, 0x05, 0x06] => {
+                self.done = true;
+                return Ok(None);
+            }
+            _ => return Err(invalid_data(Error::InvalidSignature)),
+        }
+
+        let header = self.input.take_aligned(26)?;
+        let flags = u16_at(&header, 2);
+        let method = u16_at(&header, 4);
+        let name_len = u16_at(&header, 22) as usize;
+        let extra_len = u16_at(&header, 24) as usize;
+        let name = self.input.take_aligned(name_len)?;
+        let extra = self.input.take_aligned(extra_len)?;
+
+        let mut entry = EntryInfo {
+            name: decode_name(&name, flags, &extra),
+            offset,
+            crc32: u32_at(&header, 10),
+            compressed_size: u32_at(&header, 14) as usize,
+            size: u32_at(&header, 18) as usize,
+            method,
+            flags,
+            dos_time: (u16_at(&header, 8) as u32) << 16 | u16_at(&header, 6) as u32,
+            external_attributes: 0,
+            comment: String::new(),
+            version_made_by: 0,
+            mtime: extended_mtime(&extra),
+            ntfs_mtime: ntfs_mtime(&extra),
+        };
+
+        let data = match method {
+            // the end of stored data can't be found without the sizes
+            0 if flags & FLAG_DATA_DESCRIPTOR != 0 => {
+                return Err(invalid_data(Error::InvalidZip));
+            }
+            0 => self.input.take_aligned(entry.compressed_size)?,
+            8 => {
+                let mut data = Vec::new();
+                inflate_io(&mut self.input, |chunk| {
+                    data.extend_from_slice(chunk);
+                    Ok(())
+                })?;
+                data
+            }
+            method => return Err(invalid_data(Error::InvalidCompressionMethod(method))),
+        };
+
+        if flags & FLAG_DATA_DESCRIPTOR != 0 {
+            // CRC-32, compressed and uncompressed size, optionally preceded by a signature
+            let mut descriptor = self.input.take_aligned(12)?;
+            if descriptor.starts_with(&[0x50, 0x4b, 0x07, 0x08]) {
+                descriptor.drain(..4);
+                descriptor.extend_from_slice(&self.input.take_aligned(4)?);
+            }
+            entry.crc32 = u32_at(&descriptor, 0);
+            entry.compressed_size = u32_at(&descriptor, 4) as usize;
+            entry.size = u32_at(&descriptor, 8) as usize;
+        }
+
+        if data.len() != entry.size {
+            return Err(invalid_data(Error::InvalidZip));
+        }
+        if checksum::crc32(0, &data) != entry.crc32 {
+            return Err(invalid_data(inflate::Error::ChecksumMismatch));
+        }
+
+        Ok(Some((entry, data)))
+    }
+}
+
+// ----------------------------------------------------------------------------
+impl<R: Read> Iterator for ZipStreamReader<R> {
+    type Item = io::Result<(EntryInfo, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.next_entry().transpose();
+        if matches!(entry, Some(Err(_))) {
+            self.done = true;
+        }
+        entry
+    }
+}
@@ -0,0 +1,740 @@
+// ZIP writing implementation.
+// * https://pkwaredownloads.blob.core.windows.net/pkware-general/Documentation/APPNOTE-6.3.9.TXT
+use crate::inflate::{self, InflateOptions};
+use crate::zip_read::{self, Error, Result};
+use crate::{checksum, deflate};
+use alloc::{collections::BTreeMap, vec, vec::Vec};
+
+// ----------------------------------------------------------------------------
+const LOCAL_HEADER_SIGNATURE: [u8; 4] = [0x50, 0x4b, 0x03, 0x04];
+const CD_HEADER_SIGNATURE: [u8; 4] = [0x50, 0x4b, 0x01, 0x02];
+const DATA_DESCRIPTOR_SIGNATURE: [u8; 4] = [0x50, 0x4b, 0x07, 0x08];
+const LOCAL_HEADER_SIZE: usize = 30;
+const CD_HEADER_SIZE: usize = 46;
+
+// ----------------------------------------------------------------------------
+// general purpose bit flags
+const FLAG_ENCRYPTED: u16 = 0x0001;
+const FLAG_DATA_DESCRIPTOR: u16 = 0x0008;
+
+// ----------------------------------------------------------------------------
+fn read_u16(data: &[u8], ofs: usize) -> Result<u16> {
+    let bytes = data.get(ofs..ofs + 2).ok_or(Error::BufferError)?;
+    Ok(u16::from_le_bytes(bytes.try_into()?))
+}
+
+// ----------------------------------------------------------------------------
+fn read_u32(data: &[u8], ofs: usize) -> Result<u32> {
+    let bytes = data.get(ofs..ofs + 4).ok_or(Error::BufferError)?;
+    Ok(u32::from_le_bytes(bytes.try_into()?))
+}
+
+// ----------------------------------------------------------------------------
+fn write_u16(data: &mut [u8], ofs: usize, value: u16) {
+    data[ofs..ofs + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+// ----------------------------------------------------------------------------
+fn write_u32(data: &mut [u8], ofs: usize, value: u32) {
+    data[ofs..ofs + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+// ----------------------------------------------------------------------------
+// Decodes and compresses deflated entry data again, `None` if the result is not smaller.
+fn recompress(compressed: &[u8], size: usize, crc: u32, level: u8) -> Result<Option<Vec<u8>>> {
+    let mut data = vec![0u8; size];
+    if inflate::inflate(&mut data, compressed)? != size || checksum::crc32(0, &data) != crc {
+        return Err(Error::InvalidZip);
+    }
+
+    let mut out = Vec::new();
+    deflate::deflate(&mut out, &data, level.into());
+    Ok((out.len() < compressed.len()).then_some(out))
+}
+
+// ----------------------------------------------------------------------------
+// An entry of an archive in its raw form.
+struct RawEntry<'a> {
+    // central directory record including name, extra field and comment
+    record: &'a [u8],
+    // local header, entry data and data descriptor
+    local: &'a [u8],
+    header_len: usize,
+    data_len: usize,
+}
+
+// ----------------------------------------------------------------------------
+impl<'a> RawEntry<'a> {
+    fn name(&self) -> &'a [u8] {
+        let name_len = u16::from_le_bytes([self.record[28], self.record[29]]) as usize;
+        &self.record[CD_HEADER_SIZE..CD_HEADER_SIZE + name_len]
+    }
+
+    fn data(&self) -> &'a [u8] {
+        &self.local[self.header_len..self.header_len + self.data_len]
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Splits `src` into its entries, returns them with the offset of the end of central directory
+// record.
+fn raw_entries(src: &[u8]) -> Result<(Vec<RawEntry<'_>>, usize)> {
+    let (cd_size, cd_offset, total_entries, eocd_offset, prefix) =
+        zip_read::read_eocd(src, &InflateOptions::default())?;
+    let mut records = src
+        .get(cd_offset..cd_offset + cd_size)
+        .ok_or(Error::BufferError)?;
+
+    let mut entries = Vec::with_capacity(total_entries.min(cd_size / CD_HEADER_SIZE));
+    for _ in 0..total_entries {
+        if !records.starts_with(&CD_HEADER_SIGNATURE) {
+            return Err(Error::InvalidSignature);
+        }
+
+        let name_len = read_u16(records, 28)? as usize;
+        let extra_len = read_u16(records, 30)? as usize;
+        let comment_len = read_u16(records, 32)? as usize;
+        let record_len = CD_HEADER_SIZE + name_len + extra_len + comment_len;
+        let record = records.get(..record_len).ok_or(Error::BufferError)?;
+        records = &records[record_len..];
+
+        // sizes are taken from the central directory, the local header may defer them to a
+        // data descriptor
+        let flags = read_u16(record, 8)?;
+        let data_len = read_u32(record, 20)? as usize;
+        let offset = read_u32(record, 42)? as usize + prefix;
+        let local = src.get(offset..).ok_or(Error::BufferError)?;
+        if !local.starts_with(&LOCAL_HEADER_SIGNATURE) {
+            return Err(Error::InvalidSignature);
+        }
+        let header_len =
+            LOCAL_HEADER_SIZE + read_u16(local, 26)? as usize + read_u16(local, 28)? as usize;
+        let data_end = header_len + data_len;
+
+        let descriptor_len = if flags & FLAG_DATA_DESCRIPTOR == 0 {
+            0
+        } else if local.get(data_end..data_end + 4) == Some(&DATA_DESCRIPTOR_SIGNATURE) {
+            16
+        } else {
+            12
+        };
+        let local = local
+            .get(..data_end + descriptor_len)
+            .ok_or(Error::BufferError)?;
+
+        entries.push(RawEntry {
+            record,
+            local,
+            header_len,
+            data_len,
+        });
+    }
+
+    Ok((entries, eocd_offset))
+}
+
+// ----------------------------------------------------------------------------
+// Appends the central directory `cd` and an end of central directory record with `comment`.
+fn write_cd(out: &mut Vec<u8>, cd: &[u8], entries: usize, comment: &[u8]) -> Result<()> {
+    let entries = u16::try_from(entries).map_err(|_| Error::InvalidZip)?;
+    let comment_len = u16::try_from(comment.len()).map_err(|_| Error::InvalidZip)?;
+    let cd_offset = u32::try_from(out.len()).map_err(|_| Error::InvalidZip)?;
+    out.extend_from_slice(cd);
+    out.extend_from_slice(&[0x50, 0x4b, 0x05, 0x06, 0, 0, 0, 0]);
+    out.extend_from_slice(&entries.to_le_bytes());
+    out.extend_from_slice(&entries.to_le_bytes());
+    out.extend_from_slice(&(cd.len() as u32).to_le_bytes());
+    out.extend_from_slice(&cd_offset.to_le_bytes());
+    out.extend_from_slice(&comment_len.to_le_bytes());
+    out.extend_from_slice(comment);
+    Ok(())
+}
+
+// ----------------------------------------------------------------------------
+// Rewrites the archive `src` with its deflated entries compressed again, entries only change
+// if they get smaller. Names, timestamps, attributes, extra fields and comments are copied
+// unchanged, data preceding the first entry is dropped.
+pub fn zip_recompress(src: &[u8], level: u8) -> Result<Vec<u8>> {
+    let (entries, eocd_offset) = raw_entries(src)?;
+
+    let mut out = Vec::with_capacity(src.len());
+    let mut cd = Vec::new();
+    for entry in &entries {
+        let mut record = entry.record.to_vec();
+        let flags = read_u16(&record, 8)?;
+        let method = read_u16(&record, 10)?;
+        let crc = read_u32(&record, 16)?;
+        let size = read_u32(&record, 24)? as usize;
+
+        let recompressed = match method {
+            8 if flags & FLAG_ENCRYPTED == 0 => recompress(entry.data(), size, crc, level)?,
+            _ => None,
+        };
+
+        write_u32(&mut record, 42, out.len() as u32);
+        match recompressed {
+            Some(data) => {
+                // the sizes move into the local header, a data descriptor is dropped
+                let flags = flags & !FLAG_DATA_DESCRIPTOR;
+                let mut header = entry.local[..entry.header_len].to_vec();
+                write_u16(&mut header, 6, flags);
+                write_u32(&mut header, 14, crc);
+                write_u32(&mut header, 18, data.len() as u32);
+                write_u32(&mut header, 22, size as u32);
+                write_u16(&mut record, 8, flags);
+                write_u32(&mut record, 20, data.len() as u32);
+                out.extend_from_slice(&header);
+                out.extend_from_slice(&data);
+            }
+            None => out.extend_from_slice(entry.local),
+        }
+        cd.extend_from_slice(&record);
+    }
+
+    // the end of central directory record keeps its comment
+    let comment = zip_read::archive_comment(src, eocd_offset)?;
+    write_cd(&mut out, &cd, entries.len(), comment)?;
+    Ok(out)
+}
+
+// ----------------------------------------------------------------------------
+// Handling of entries with the same name in several archives passed to `zip_merge`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DuplicatePolicy {
+    // the entry of the first archive containing the name is kept
+    KeepFirst,
+    // the entry of the last archive containing the name replaces the earlier ones in place
+    KeepLast,
+    // the merge fails with `Error::DuplicateEntry`
+    Reject,
+}
+
+// ----------------------------------------------------------------------------
+// Merges the archives `sources` into a single archive. Entries are copied raw without
+// recompression in the order of the archives, the archive comments are dropped.
+pub fn zip_merge(sources: &[&[u8]], policy: DuplicatePolicy) -> Result<Vec<u8>> {
+    let mut merged: Vec<RawEntry> = Vec::new();
+    let mut index = BTreeMap::new();
+    for src in sources {
+        let (entries, _) = raw_entries(src)?;
+        for entry in entries {
+            match index.get(entry.name()) {
+                None => {
+                    index.insert(entry.name(), merged.len());
+                    merged.push(entry);
+                }
+                Some(&i) => match policy {
+                    DuplicatePolicy::KeepFirst => {}
+                    DuplicatePolicy::KeepLast => merged[i] = entry,
+                    DuplicatePolicy::Reject => return Err(Error::DuplicateEntry),
+                },
+            }
+        }
+    }
+
+    let mut out = Vec::with_capacity(sources.iter().map(|src| src.len()).sum());
+    let mut cd = Vec::new();
+    for entry in &merged {
+        let offset = u32::try_from(out.len()).map_err(|_| Error::InvalidZip)?;
+        let mut record = entry.record.to_vec();
+        write_u32(&mut record, 42, offset);
+        out.extend_from_slice(entry.local);
+        cd.extend_from_slice(&record);
+    }
+
+    write_cd(&mut out, &cd, merged.len(), &[])?;
+    Ok(out)
+}
+
+// ----------------------------------------------------------------------------
+// Entry of the archive written by `ZipAppender`.
+enum AppendedEntry<'a> {
+    // copied from the existing archive
+    Raw(RawEntry<'a>),
+    // added, with its local header and data and its central directory record
+    New { local: Vec<u8>, record: Vec<u8> },
+}
+
+// ----------------------------------------------------------------------------
+// Adds, replaces and removes entries of an existing archive. The local records of untouched
+// entries are copied without recompression, only the central directory is written anew. A
+// replaced entry keeps its position in the central directory, new entries follow the existing
+// ones. The archive comment is kept, data preceding the first entry is dropped.
+pub struct ZipAppender<'a> {
+    src: &'a [u8],
+    entries: Vec<Option<AppendedEntry<'a>>>,
+    index: BTreeMap<Vec<u8>, usize>,
+    comment: &'a [u8],
+}
+
+// ----------------------------------------------------------------------------
+impl<'a> ZipAppender<'a> {
+    pub fn new(src: &'a [u8]) -> Result<Self> {
+        let (raw, eocd_offset) = raw_entries(src)?;
+        let mut index = BTreeMap::new();
+        let mut entries = Vec::with_capacity(raw.len());
+        for entry in raw {
+            index.entry(entry.name().to_vec()).or_insert(entries.len());
+            entries.push(Some(AppendedEntry::Raw(entry)));
+        }
+        Ok(ZipAppender {
+            src,
+            entries,
+            index,
+            comment: zip_read::archive_comment(src, eocd_offset)?,
+        })
+    }
+
+    // Number of entries the archive will have.
+    pub fn len(&self) -> usize {
+        self.entries.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Adds the file `name`, an existing entry of that name is replaced.
+    pub fn add_file(&mut self, name: &str, data: &[u8], options: &FileOptions) -> Result<()> {
+        let mut writer = ZipWriter::new();
+        writer.add_file(name, data, options)?;
+        self.insert(name, writer);
+        Ok(())
+    }
+
+    // Adds a directory entry like `ZipWriter::add_directory`, replacing one of the same name.
+    pub fn add_directory(&mut self, name: &str, options: &FileOptions) -> Result<()> {
+        let mut writer = ZipWriter::new();
+        writer.add_directory(name, options)?;
+        let mut name = alloc::string::String::from(name);
+        if !name.ends_with('/') {
+            name.push('/');
+        }
+        self.insert(&name, writer);
+        Ok(())
+    }
+
+    // Removes the entry `name`, false if there is none.
+    pub fn remove(&mut self, name: &str) -> bool {
+        match self.index.remove(name.as_bytes()) {
+            Some(i) => self.entries[i].take().is_some(),
+            None => false,
+        }
+    }
+
+    // the single entry of `writer` takes the place of an entry of the same name
+    fn insert(&mut self, name: &str, writer: ZipWriter) {
+        let entry = AppendedEntry::New {
+            local: writer.out,
+            record: writer.cd,
+        };
+        match self.index.get(name.as_bytes()) {
+            Some(&i) => self.entries[i] = Some(entry),
+            None => {
+                self.index
+                    .insert(name.as_bytes().to_vec(), self.entries.len());
+                self.entries.push(Some(entry));
+            }
+        }
+    }
+
+    // Replaces the archive comment, at most 65535 bytes.
+    pub fn set_comment(&mut self, comment: &'a [u8]) {
+        self.comment = comment;
+    }
+
+    pub fn finish(self) -> Result<Vec<u8>> {
+        let mut out = Vec::with_capacity(self.src.len());
+        let mut cd = Vec::new();
+        let mut count = 0;
+        for entry in self.entries.iter().flatten() {
+            let offset = u32::try_from(out.len()).map_err(|_| Error::InvalidZip)?;
+            let (local, record) = match entry {
+                AppendedEntry::Raw(entry) => (entry.local, entry.record),
+                AppendedEntry::New { local, record } => (&local[..], &record[..]),
+            };
+            let mut record = record.to_vec();
+            write_u32(&mut record, 42, offset);
+            out.extend_from_slice(local);
+            cd.extend_from_slice(&record);
+            count += 1;
+        }
+        write_cd(&mut out, &cd, count, self.comment)?;
+        Ok(out)
+    }
+}
+
+// ----------------------------------------------------------------------------
+// MS-DOS date and time of 1980-01-01 00:00:00, the earliest representable timestamp
+pub const DOS_EPOCH: u32 = 0x0021_0000;
+
+// ----------------------------------------------------------------------------
+// general purpose bit flag: names are UTF-8
+const FLAG_UTF8: u16 = 0x0800;
+
+// ----------------------------------------------------------------------------
+// Extra field record (APPNOTE 4.5.1): header ID and data, the size is filled in on write.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExtraField<'a> {
+    pub header_id: u16,
+    pub data: &'a [u8],
+}
+
+// ----------------------------------------------------------------------------
+#[derive(Debug, Clone, Copy)]
+pub struct FileOptions<'a> {
+    // 0 stores the data, higher levels deflate it if that makes it smaller
+    pub level: u8,
+    // MS-DOS date in the upper and time in the lower 16 bits, see `zip_read::EntryInfo`
+    pub dos_time: u32,
+    // Unix permission bits, recorded with a Unix "version made by"
+    pub unix_mode: Option<u32>,
+    // extra fields of the local header and of the central directory record, the ZIP64 header
+    // ID 0x0001 is reserved for the writer
+    pub local_extra: &'a [ExtraField<'a>],
+    pub central_extra: &'a [ExtraField<'a>],
+    // comment of the entry in the central directory, UTF-8 like the name
+    pub comment: &'a str,
+}
+
+// ----------------------------------------------------------------------------
+impl Default for FileOptions<'_> {
+    fn default() -> Self {
+        FileOptions {
+            level: 6,
+            dos_time: DOS_EPOCH,
+            unix_mode: None,
+            local_extra: &[],
+            central_extra: &[],
+            comment: "",
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Serializes extra field records, the total must fit the 16 bit extra field length.
+fn encode_extra(fields: &[ExtraField]) -> Result<Vec<u8>> {
+    let mut extra = Vec::new();
+    for field in fields {
+        let len = u16::try_from(field.data.len()).map_err(|_| Error::InvalidZip)?;
+        if field.header_id == 0x0001 {
+            return Err(Error::InvalidZip);
+        }
+        extra.extend_from_slice(&field.header_id.to_le_bytes());
+        extra.extend_from_slice(&len.to_le_bytes());
+        extra.extend_from_slice(field.data);
+    }
+    u16::try_from(extra.len()).map_err(|_| Error::InvalidZip)?;
+    Ok(extra)
+}
+
+// ----------------------------------------------------------------------------
+// Builds an archive in memory, entries are written in the order they are added.
+#[derive(Debug, Default)]
+pub struct ZipWriter {
+    out: Vec<u8>,
+    cd: Vec<u8>,
+    entries: usize,
+    comment: Vec<u8>,
+}
+
+// ----------------------------------------------------------------------------
+impl ZipWriter {
+    pub fn new() -> Self {
+        ZipWriter::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries == 0
+    }
+
+    // Sets the archive comment, at most 65535 bytes, e.g. build metadata.
+    pub fn set_comment(&mut self, comment: &[u8]) -> Result<()> {
+        u16::try_from(comment.len()).map_err(|_| Error::InvalidZip)?;
+        self.comment = comment.to_vec();
+        Ok(())
+    }
+
+    pub fn add_file(&mut self, name: &str, data: &[u8], options: &FileOptions) -> Result<()> {
+        let mut deflated = Vec::new();
+        if options.level != 0 {
+            deflate::deflate(&mut deflated, data, options.level.into());
+        }
+        self.add_compressed(name, data, &deflated, options)
+    }
+
+    // Like `add_file`, the data is deflated on multiple threads with `deflate_parallel`.
+    #[cfg(feature = "std")]
+    pub fn add_file_parallel(
+        &mut self,
+        name: &str,
+        data: &[u8],
+        options: &FileOptions,
+        batch: &crate::BatchOptions,
+    ) -> Result<()> {
+        let deflated = match options.level {
+            0 => Vec::new(),
+            level => crate::oneshot::deflate_parallel(data, level, batch),
+        };
+        self.add_compressed(name, data, &deflated, options)
+    }
+
+    // stores `data` unless its deflated version is smaller
+    fn add_compressed(
+        &mut self,
+        name: &str,
+        data: &[u8],
+        deflated: &[u8],
+        options: &FileOptions,
+    ) -> Result<()> {
+        let (method, stored) = if options.level != 0 && deflated.len() < data.len() {
+            (8, deflated)
+        } else {
+            (0, data)
+        };
+        let mode = options.unix_mode.map(|mode| mode | 0o100000);
+        self.add_entry(name, method, data, stored, options, mode, 0)
+    }
+
+    // Adds a directory entry, a trailing '/' is appended to `name` if missing.
+    pub fn add_directory(&mut self, name: &str, options: &FileOptions) -> Result<()> {
+        let mut name = alloc::string::String::from(name);
+        if !name.ends_with('/') {
+            name.push('/');
+        }
+        let mode = options.unix_mode.map(|mode| mode | 0o040000);
+        // MS-DOS directory attribute
+        self.add_entry(&name, 0, &[], &[], options, mode, 0x10)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn add_entry(
+        &mut self,
+        name: &str,
+        method: u16,
+        data: &[u8],
+        stored: &[u8],
+        options: &FileOptions,
+        unix_mode: Option<u32>,
+        dos_attributes: u32,
+    ) -> Result<()> {
+        let offset = u32::try_from(self.out.len()).map_err(|_| Error::InvalidZip)?;
+        let size = u32::try_from(data.len()).map_err(|_| Error::InvalidZip)?;
+        let name_len = u16::try_from(name.len()).map_err(|_| Error::InvalidZip)?;
+        let crc = checksum::crc32(0, data);
+        let local_extra = encode_extra(options.local_extra)?;
+        let central_extra = encode_extra(options.central_extra)?;
+        let comment_len = u16::try_from(options.comment.len()).map_err(|_| Error::InvalidZip)?;
+
+        // fields shared by the local header and the central directory record, from "version
+        // needed to extract" to the file name length
+        let mut common = Vec::with_capacity(24);
+        common.extend_from_slice(&20u16.to_le_bytes());
+        common.extend_from_slice(&FLAG_UTF8.to_le_bytes());
+        common.extend_from_slice(&method.to_le_bytes());
+        common.extend_from_slice(&options.dos_time.to_le_bytes());
+        common.extend_from_slice(&crc.to_le_bytes());
+        common.extend_from_slice(&(stored.len() as u32).to_le_bytes());
+        common.extend_from_slice(&size.to_le_bytes());
+        common.extend_from_slice(&name_len.to_le_bytes());
+
+        self.out.extend_from_slice(&LOCAL_HEADER_SIGNATURE);
+        self.out.extend_from_slice(&common);
+        self.out
+            .extend_from_slice(&(local_extra.len() as u16).to_le_bytes());
+        self.out.extend_from_slice(name.as_bytes());
+        self.out.extend_from_slice(&local_extra);
+        self.out.extend_from_slice(stored);
+
+        // version made by: 3 (Unix) or 0 (MS-DOS) in the upper byte, spec version 2.0
+        let (made_by, attributes) = match unix_mode {
+            Some(mode) => (0x0314u16, (mode << 16) | dos_attributes),
+            None => (0x0014u16, dos_attributes),
+        };
+        self.cd.extend_from_slice(&CD_HEADER_SIGNATURE);
+        self.cd.extend_from_slice(&made_by.to_le_bytes());
+        self.cd.extend_from_slice(&common);
+        self.cd
+            .extend_from_slice(&(central_extra.len() as u16).to_le_bytes());
+        self.cd.extend_from_slice(&comment_len.to_le_bytes());
+        // disk number, internal attributes
+        self.cd.extend_from_slice(&[0; 4]);
+        self.cd.extend_from_slice(&attributes.to_le_bytes());
+        self.cd.extend_from_slice(&offset.to_le_bytes());
+        self.cd.extend_from_slice(name.as_bytes());
+        self.cd.extend_from_slice(&central_extra);
+        self.cd.extend_from_slice(options.comment.as_bytes());
+
+        self.entries += 1;
+        Ok(())
+    }
+
+    // Adds the files and directories below `path` with their relative paths as names, see
+    // `DirOptions`. Symbolic links are skipped, returns the number of added files.
+    #[cfg(feature = "std")]
+    pub fn add_dir_recursive<P: AsRef<std::path::Path>>(
+        &mut self,
+        path: P,
+        options: &DirOptions,
+    ) -> std::io::Result<usize> {
+        self.add_dir_entries(path.as_ref(), "", options)
+    }
+
+    #[cfg(feature = "std")]
+    fn add_dir_entries(
+        &mut self,
+        dir: &std::path::Path,
+        prefix: &str,
+        options: &DirOptions,
+    ) -> std::io::Result<usize> {
+        let invalid = |err| std::io::Error::new(std::io::ErrorKind::InvalidData, err);
+
+        let mut entries = std::fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+
+        let mut count = 0;
+        for entry in entries {
+            let file_name = entry.file_name();
+            let file_name = file_name
+                .to_str()
+                .ok_or_else(|| invalid(Error::InvalidZip))?;
+            let name = alloc::format!("{prefix}{file_name}");
+            let matches = |patterns: &[&str]| patterns.iter().any(|p| glob_match(p, &name));
+
+            let metadata = entry.metadata()?;
+            let file_options = FileOptions {
+                level: options.level,
+                dos_time: metadata.modified().map_or(DOS_EPOCH, dos_time),
+                unix_mode: unix_mode(&metadata),
+                ..Default::default()
+            };
+
+            if metadata.is_dir() && !matches(options.exclude) {
+                self.add_directory(&name, &file_options).map_err(invalid)?;
+                let prefix = alloc::format!("{name}/");
+                count += self.add_dir_entries(&entry.path(), &prefix, options)?;
+            } else if metadata.is_file()
+                && !matches(options.exclude)
+                && (options.include.is_empty() || matches(options.include))
+            {
+                let data = std::fs::read(entry.path())?;
+                self.add_file(&name, &data, &file_options)
+                    .map_err(invalid)?;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    pub fn finish(self) -> Result<Vec<u8>> {
+        let mut out = self.out;
+        write_cd(&mut out, &self.cd, self.entries, &self.comment)?;
+        Ok(out)
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Options of `ZipWriter::add_dir_recursive`. Patterns are matched against the relative path with
+// '/' separators, `*` matches any run of characters including '/' and `?` a single character.
+// Include patterns select files, an empty list selects all. Exclude patterns skip files and
+// whole directories.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy)]
+pub struct DirOptions<'a> {
+    pub level: u8,
+    pub include: &'a [&'a str],
+    pub exclude: &'a [&'a str],
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(feature = "std")]
+impl Default for DirOptions<'_> {
+    fn default() -> Self {
+        DirOptions {
+            level: 6,
+            include: &[],
+            exclude: &[],
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(feature = "std")]
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name) = (pattern.as_bytes(), name.as_bytes());
+    let (mut p, mut n) = (0, 0);
+    // position after the last '*' and the name position it was tried at
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                // let the last '*' consume one more character
+                Some((sp, sn)) => {
+                    star = Some((sp, sn + 1));
+                    p = sp;
+                    n = sn + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+// ----------------------------------------------------------------------------
+// Converts to an MS-DOS timestamp in UTC, clamped to the representable range 1980-2107.
+#[cfg(feature = "std")]
+fn dos_time(time: std::time::SystemTime) -> u32 {
+    let Ok(since_epoch) = time.duration_since(std::time::UNIX_EPOCH) else {
+        return DOS_EPOCH;
+    };
+    let secs = since_epoch.as_secs();
+    let (days, secs) = (secs / 86400, secs % 86400);
+
+    // civil date from days since 1970-01-01, proleptic Gregorian calendar
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as u64;
+
+    if year < 1980 {
+        return DOS_EPOCH;
+    }
+    if year > 2107 {
+        // 2107-12-31 23:59:58
+        return 0xff9f_bf7d;
+    }
+    let date = ((year - 1980) << 9) | (month << 5) | day;
+    let time = ((secs / 3600) << 11) | ((secs / 60 % 60) << 5) | (secs % 60 / 2);
+    ((date << 16) | time) as u32
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(all(feature = "std", unix))]
+fn unix_mode(metadata: &std::fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode() & 0o7777)
+}
+
+// ----------------------------------------------------------------------------
+#[cfg(all(feature = "std", not(unix)))]
+fn unix_mode(_metadata: &std::fs::Metadata) -> Option<u32> {
+    None
+}
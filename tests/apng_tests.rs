@@ -0,0 +1,153 @@
+#![cfg(feature = "png")]
+
+// ----------------------------------------------------------------------------
+mod test {
+    use miniz::apng::*;
+    use miniz::png_read::{png_read, Error, PNGChunkIHDR, PNGColorType};
+    use miniz::png_write::*;
+
+    const IHDR: PNGChunkIHDR = PNGChunkIHDR {
+        width: 4,
+        height: 2,
+        bit_depth: 8,
+        color_type: PNGColorType::Greyscale,
+        compression: 0,
+        filter: 0,
+        interlace: 0,
+    };
+
+    fn actl(num_frames: u32, num_plays: u32) -> Vec<u8> {
+        [num_frames.to_be_bytes(), num_plays.to_be_bytes()].concat()
+    }
+
+    fn fctl(seq: u32, size: (u32, u32), offset: (u32, u32), dispose: u8, blend: u8) -> Vec<u8> {
+        let mut data = Vec::new();
+        for value in [seq, size.0, size.1, offset.0, offset.1] {
+            data.extend_from_slice(&value.to_be_bytes());
+        }
+        data.extend_from_slice(&[0, 5, 0, 100, dispose, blend]);
+        data
+    }
+
+    // fdAT with unfiltered greyscale rows
+    fn fdat(seq: u32, rows: &[&[u8]]) -> Vec<u8> {
+        let raw: Vec<u8> = rows.iter().flat_map(|row| [&[0], *row].concat()).collect();
+        [&seq.to_be_bytes()[..], &miniz::compress_to_vec(&raw, 6)].concat()
+    }
+
+    fn chunk(
+        chunk_type: &[u8; 4],
+        data: &[u8],
+        placement: ChunkPlacement,
+    ) -> (Vec<u8>, [u8; 4], ChunkPlacement) {
+        (data.to_vec(), *chunk_type, placement)
+    }
+
+    fn write(chunks: &[(Vec<u8>, [u8; 4], ChunkPlacement)]) -> Vec<u8> {
+        let extra: Vec<ExtraChunk> = chunks
+            .iter()
+            .map(|(data, chunk_type, placement)| ExtraChunk {
+                chunk_type: *chunk_type,
+                data,
+                placement: *placement,
+            })
+            .collect();
+        let options = EncodeOptions {
+            extra_chunks: &extra,
+            ..Default::default()
+        };
+        png_write(&IHDR, &[], &[1, 2, 3, 4, 5, 6, 7, 8], &options).unwrap()
+    }
+
+    #[test]
+    fn test_apng_read() {
+        use ChunkPlacement::*;
+
+        // the IDAT image is the first frame, the second one updates the lower right corner
+        let png = write(&[
+            chunk(b"acTL", &actl(2, 0), BeforeIdat),
+            chunk(b"fcTL", &fctl(0, (4, 2), (0, 0), 0, 0), BeforeIdat),
+            chunk(b"fcTL", &fctl(1, (2, 1), (2, 1), 1, 1), AfterIdat),
+            chunk(b"fdAT", &fdat(2, &[&[9, 10]]), AfterIdat),
+        ]);
+        let apng = apng_read(&png).unwrap();
+        assert_eq!(apng.ihdr, IHDR);
+        assert_eq!(apng.num_plays, 0);
+        assert_eq!(apng.default_image, None);
+        assert_eq!(apng.frames.len(), 2);
+        assert_eq!(apng.frames[0].data, png_read(&png).unwrap().2);
+        assert_eq!(apng.frames[0].control.delay_ms(), 50);
+
+        let frame = &apng.frames[1];
+        let control = FrameControl {
+            width: 2,
+            height: 1,
+            x_offset: 2,
+            y_offset: 1,
+            delay_num: 5,
+            delay_den: 100,
+            dispose_op: DisposeOp::Background,
+            blend_op: BlendOp::Over,
+        };
+        assert_eq!(frame.control, control);
+        assert_eq!(frame.data, [0, 9, 10]);
+
+        // a default image that is not part of the animation, frame data split over two fdATs
+        let png = write(&[
+            chunk(b"acTL", &actl(1, 3), BeforeIdat),
+            chunk(b"fcTL", &fctl(0, (1, 2), (3, 0), 2, 0), AfterIdat),
+            chunk(b"fdAT", &fdat(1, &[&[11], &[12]])[..6], AfterIdat),
+            chunk(
+                b"fdAT",
+                &[&2u32.to_be_bytes(), &fdat(1, &[&[11], &[12]])[6..]].concat(),
+                AfterIdat,
+            ),
+        ]);
+        let apng = apng_read(&png).unwrap();
+        assert_eq!(apng.num_plays, 3);
+        assert_eq!(apng.default_image, Some(png_read(&png).unwrap().2));
+        assert_eq!(apng.frames[0].control.dispose_op, DisposeOp::Previous);
+        assert_eq!(apng.frames[0].data, [0, 11, 0, 12]);
+    }
+
+    #[test]
+    fn test_invalid_animation() {
+        use ChunkPlacement::*;
+
+        let invalid = [
+            // no acTL
+            vec![],
+            // frame count mismatch
+            vec![
+                chunk(b"acTL", &actl(2, 0), BeforeIdat),
+                chunk(b"fcTL", &fctl(0, (4, 2), (0, 0), 0, 0), BeforeIdat),
+            ],
+            // sequence number gap
+            vec![
+                chunk(b"acTL", &actl(2, 0), BeforeIdat),
+                chunk(b"fcTL", &fctl(0, (4, 2), (0, 0), 0, 0), BeforeIdat),
+                chunk(b"fcTL", &fctl(2, (2, 1), (2, 1), 0, 0), AfterIdat),
+                chunk(b"fdAT", &fdat(3, &[&[9, 10]]), AfterIdat),
+            ],
+            // frame outside of the image
+            vec![
+                chunk(b"acTL", &actl(1, 0), BeforeIdat),
+                chunk(b"fcTL", &fctl(0, (2, 1), (3, 1), 0, 0), AfterIdat),
+                chunk(b"fdAT", &fdat(1, &[&[9, 10]]), AfterIdat),
+            ],
+            // the IDAT frame must cover the image
+            vec![
+                chunk(b"acTL", &actl(1, 0), BeforeIdat),
+                chunk(b"fcTL", &fctl(0, (2, 2), (0, 0), 0, 0), BeforeIdat),
+            ],
+            // invalid dispose op
+            vec![
+                chunk(b"acTL", &actl(1, 0), BeforeIdat),
+                chunk(b"fcTL", &fctl(0, (4, 2), (0, 0), 3, 0), BeforeIdat),
+            ],
+        ];
+        for chunks in invalid {
+            assert_eq!(apng_read(&write(&chunks)), Err(Error::InvalidAnimation));
+        }
+    }
+}
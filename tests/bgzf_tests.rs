@@ -0,0 +1,89 @@
+// ----------------------------------------------------------------------------
+mod test {
+    use miniz::bgzf::{bgzf_compress, BGZF_EOF};
+    use miniz::decompress_to_vec_gzip;
+
+    // splits a BGZF file into its blocks using the BSIZE field
+    fn blocks(mut data: &[u8]) -> Vec<&[u8]> {
+        let mut blocks = Vec::new();
+        while !data.is_empty() {
+            assert_eq!(&data[12..16], b"BC\x02\x00");
+            let bsize = u16::from_le_bytes([data[16], data[17]]) as usize;
+            let (block, rest) = data.split_at(bsize + 1);
+            blocks.push(block);
+            data = rest;
+        }
+        blocks
+    }
+
+    fn decode(data: &[u8]) -> Vec<u8> {
+        let blocks = blocks(data);
+        assert_eq!(*blocks.last().unwrap(), &BGZF_EOF);
+        blocks
+            .iter()
+            .flat_map(|block| decompress_to_vec_gzip(block).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_compress() {
+        let data: Vec<u8> = (0..150_000u32).map(|i| (i % 251) as u8).collect();
+        let bgzf = bgzf_compress(&data, 6);
+        assert_eq!(blocks(&bgzf).len(), 4);
+        assert!(blocks(&bgzf).iter().all(|block| block.len() <= 0x10000));
+        assert_eq!(decode(&bgzf), data);
+
+        assert_eq!(bgzf_compress(&[], 6), BGZF_EOF);
+        assert_eq!(decompress_to_vec_gzip(&BGZF_EOF), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_compress_levels() {
+        let data: Vec<u8> = (0..150_000u32).map(|i| (i % 251) as u8).collect();
+        let stored = bgzf_compress(&data, 0);
+        assert!(stored.len() > data.len());
+        let packed = bgzf_compress(&data, 6);
+        assert!(packed.len() < data.len() / 10, "{} bytes", packed.len());
+        assert_eq!(decode(&packed), data);
+
+        // incompressible blocks stay within the 64 KB of BSIZE
+        let mut x = 0x2219u32;
+        let noise: Vec<u8> = (0..150_000)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 17;
+                x ^= x << 5;
+                x as u8
+            })
+            .collect();
+        let bgzf = bgzf_compress(&noise, 9);
+        assert!(blocks(&bgzf).iter().all(|block| block.len() <= 0x10000));
+        assert_eq!(decode(&bgzf), noise);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_writer() {
+        use miniz::bgzf::{BgzfWriter, BGZF_BLOCK_SIZE};
+        use std::io::Write;
+
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i % 13) as u8).collect();
+        let mut writer = BgzfWriter::new(Vec::new(), 6);
+        writer.write_all(&data[..10]).unwrap();
+        writer.flush().unwrap();
+        assert_eq!(blocks(writer.get_ref()).len(), 1, "flush ends a block");
+        writer.write_all(&data[10..]).unwrap();
+        let bgzf = writer.finish().unwrap();
+
+        // 10 bytes, one full block and the rest plus the EOF marker
+        let sizes: Vec<usize> = blocks(&bgzf)
+            .iter()
+            .map(|block| decompress_to_vec_gzip(block).unwrap().len())
+            .collect();
+        assert_eq!(
+            sizes,
+            [10, BGZF_BLOCK_SIZE, 100_000 - 10 - BGZF_BLOCK_SIZE, 0]
+        );
+        assert_eq!(decode(&bgzf), data);
+    }
+}
@@ -0,0 +1,96 @@
+// ----------------------------------------------------------------------------
+mod test {
+    use miniz::bits::{BitReader, BitWriter};
+    use miniz::huffman::HuffmanDecoder;
+    use miniz::inflate::Error;
+
+    #[test]
+    fn test_reader() {
+        let src = [0b1010_1101, 0x34, 0x12, 0xff];
+        let mut br = BitReader::new(&src, false);
+        assert_eq!(br.peek(3), Ok(0b101));
+        assert_eq!(br.peek(3), Ok(0b101), "peek does not consume");
+        br.consume(3);
+        assert_eq!(br.bit_pos(), 3);
+        assert_eq!(br.read_bits(5), Ok(0b10101));
+
+        // values spanning bytes, LSB first
+        assert_eq!(br.read_bits(16), Ok(0x1234));
+        assert_eq!(br.bytes_left(), Some(1));
+
+        // a partially read byte is skipped by aligning
+        assert_eq!(br.read_bits(1), Ok(1));
+        br.align_to_byte();
+        assert_eq!(br.bit_pos(), 32);
+        assert_eq!(br.bytes_left(), Some(0));
+        assert_eq!(br.read_bits(1), Err(Error::Underflow));
+    }
+
+    #[test]
+    fn test_reader_bytes() {
+        let src: Vec<u8> = (0..40).collect();
+        let mut br = BitReader::new(&src, false);
+        assert_eq!(br.read_bits(12), Ok(0x100));
+        br.align_to_byte();
+        assert_eq!(br.bytes_left(), Some(38));
+
+        // buffered bytes first, then the input
+        let mut bytes = [0u8; 30];
+        br.read_bytes(&mut bytes).unwrap();
+        assert!(bytes.iter().copied().eq(2..32));
+        assert_eq!(br.read_bits(8), Ok(32));
+        assert_eq!(br.bytes_left(), Some(7));
+        assert_eq!(br.read_bytes(&mut bytes), Err(Error::Underflow));
+    }
+
+    #[test]
+    fn test_reader_end() {
+        // missing bits read as zeros, strict readers fail instead
+        let mut br = BitReader::new(&[0xff], false);
+        assert_eq!(br.read_bits(12), Ok(0xff));
+        let mut br = BitReader::new(&[0xff], true);
+        assert_eq!(br.read_bits(12), Err(Error::Underflow));
+        let mut br = BitReader::new(&[], false);
+        assert_eq!(br.peek(1), Err(Error::Underflow));
+    }
+
+    #[test]
+    fn test_writer() {
+        let mut dst = vec![0xaa];
+        let mut bw = BitWriter::new(&mut dst);
+        bw.put(0b101, 3);
+        bw.put(0b10101, 5);
+        bw.put(0x1234, 16);
+        assert_eq!(bw.bit_pos(), 32);
+        bw.put_code(0b110, 3);
+        assert_eq!(bw.bit_pos(), 35);
+        bw.flush();
+        bw.flush();
+        assert_eq!(dst, [0xaa, 0b1010_1101, 0x34, 0x12, 0b011]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        // Huffman codes written MSB first are decoded by the table
+        let lengths = [3, 3, 3, 3, 3, 2, 4, 4];
+        let codes = miniz::huffman::canonical_codes(&lengths).unwrap();
+        let decoder = HuffmanDecoder::from_lengths(&lengths).unwrap();
+        let message = [5, 0, 7, 6, 1, 5, 4, 2, 3];
+
+        let mut dst = Vec::new();
+        let mut bw = BitWriter::new(&mut dst);
+        for (i, symbol) in message.iter().enumerate() {
+            bw.put_code(codes[*symbol] as u32, lengths[*symbol] as u32);
+            bw.put(i as u32, 4);
+        }
+        bw.flush();
+
+        let mut br = BitReader::new(&dst, true);
+        for (i, symbol) in message.iter().enumerate() {
+            assert_eq!(br.read_symbol(&decoder), Ok(*symbol as u16));
+            assert_eq!(br.read_bits(4), Ok(i as u16));
+        }
+        br.align_to_byte();
+        assert_eq!(br.bytes_left(), Some(0));
+    }
+}
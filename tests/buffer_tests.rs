@@ -0,0 +1,103 @@
+#![cfg(all(feature = "png", feature = "zip"))]
+
+// ----------------------------------------------------------------------------
+mod test {
+    use miniz::buffer::{Arena, Pool};
+    use miniz::png_read::{
+        decoded_size, png_info, png_read, png_read_with, scratch_size, Error as PngError,
+    };
+    use miniz::zip_read::{zip_open, zip_read_with, Error as ZipError};
+
+    include!("../assets/zip/deflated.rs");
+
+    #[test]
+    fn test_png_arena() {
+        let png = include_bytes!("../assets/png/basn2c08.png");
+        let (_, _, expected) = png_read(png).unwrap();
+
+        let mut mem = vec![0u8; 4096];
+        let mut arena = Arena::new(&mut mem);
+        let (ihdr, _, data) = png_read_with(png, &mut arena).unwrap();
+        assert_eq!(ihdr.width, 32);
+        assert_eq!(data, &expected[..]);
+        assert_eq!(arena.remaining(), 4096 - expected.len());
+
+        let mut mem = vec![0u8; 64];
+        let mut arena = Arena::new(&mut mem);
+        assert_eq!(
+            png_read_with(png, &mut arena).err(),
+            Some(PngError::BufferError)
+        );
+    }
+
+    #[test]
+    fn test_png_arena_exact() {
+        // the passes of the interlaced image come from the arena as well
+        for png in [
+            &include_bytes!("../assets/png/basn2c08.png")[..],
+            &include_bytes!("../assets/png/pngtest.png")[..],
+        ] {
+            let (_, _, expected) = png_read(png).unwrap();
+            let ihdr = png_info(png).unwrap();
+            let len = decoded_size(&ihdr).unwrap() + scratch_size(&ihdr).unwrap();
+
+            let mut mem = vec![0u8; len];
+            let mut arena = Arena::new(&mut mem);
+            let (_, _, data) = png_read_with(png, &mut arena).unwrap();
+            assert_eq!(data, &expected[..]);
+            assert_eq!(arena.remaining(), 0);
+
+            let mut mem = vec![0u8; len - 1];
+            let mut arena = Arena::new(&mut mem);
+            assert_eq!(
+                png_read_with(png, &mut arena).err(),
+                Some(PngError::BufferError)
+            );
+        }
+    }
+
+    #[test]
+    fn test_zip_arena() {
+        let files = zip_open(DEFLATED_ZIP).unwrap();
+        let mut mem = vec![0u8; FIRST.len() + SECOND.len()];
+        let mut arena = Arena::new(&mut mem);
+        let first = zip_read_with(DEFLATED_ZIP, &files, "first", &mut arena).unwrap();
+        let second = zip_read_with(DEFLATED_ZIP, &files, "second", &mut arena).unwrap();
+        assert_eq!(first, &FIRST[..]);
+        assert_eq!(second, &SECOND[..]);
+        assert_eq!(
+            zip_read_with(DEFLATED_ZIP, &files, "first", &mut arena).err(),
+            Some(ZipError::BufferError)
+        );
+    }
+
+    #[test]
+    fn test_pool() {
+        let png = include_bytes!("../assets/png/pngtest.png");
+        let (_, _, expected) = png_read(png).unwrap();
+
+        // the interlace buffer returns to the pool, the image is recycled by the caller
+        let mut pool = Pool::new(4);
+        let (_, _, data) = png_read_with(png, &mut pool).unwrap();
+        assert_eq!(data, expected);
+        assert_eq!((pool.len(), pool.reused()), (1, 0));
+        pool.recycle(data);
+
+        for _ in 0..3 {
+            let (_, _, data) = png_read_with(png, &mut pool).unwrap();
+            assert_eq!(data, expected);
+            pool.recycle(data);
+        }
+        assert_eq!(pool.len(), 2);
+        assert_eq!(pool.reused(), 3 * 2, "no new allocations");
+
+        let files = zip_open(DEFLATED_ZIP).unwrap();
+        let first = zip_read_with(DEFLATED_ZIP, &files, "first", &mut pool).unwrap();
+        assert_eq!(first, FIRST);
+        assert_eq!(pool.len(), 1);
+
+        let mut pool = Pool::new(0);
+        pool.recycle(first);
+        assert!(pool.is_empty());
+    }
+}
@@ -0,0 +1,72 @@
+// ----------------------------------------------------------------------------
+mod test {
+    use miniz::bzip2::{decompress, decompress_to_vec, Error};
+
+    // bz2.compress(b"hello hello hello\n")
+    const HELLO: [u8; 47] = [
+        0x42, 0x5a, 0x68, 0x39, 0x31, 0x41, 0x59, 0x26, 0x53, 0x59, 0xe5, 0xb5, 0xf3, 0x09, 0x00,
+        0x00, 0x04, 0x51, 0x00, 0x00, 0x10, 0x40, 0x00, 0x02, 0x44, 0xa0, 0x00, 0x21, 0xb5, 0x18,
+        0x0c, 0x02, 0x90, 0x69, 0xc2, 0xa3, 0x0b, 0xb9, 0x22, 0x9c, 0x28, 0x48, 0x72, 0xda, 0xf9,
+        0x84, 0x80,
+    ];
+
+    // bz2.compress(b"")
+    const EMPTY: [u8; 14] = [
+        0x42, 0x5a, 0x68, 0x39, 0x17, 0x72, 0x45, 0x38, 0x50, 0x90, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn test_decompress() {
+        let mut out = [0u8; 32];
+        assert_eq!(decompress(&mut out, &HELLO), Ok(18));
+        assert_eq!(&out[..18], b"hello hello hello\n");
+        assert_eq!(decompress(&mut out, &EMPTY), Ok(0));
+
+        // concatenated streams, trailing data is ignored
+        let mut concat = HELLO.to_vec();
+        concat.extend_from_slice(&EMPTY);
+        concat.extend_from_slice(&HELLO);
+        concat.extend_from_slice(b"trailer");
+        let out = decompress_to_vec(&concat, usize::MAX).unwrap();
+        assert_eq!(out, b"hello hello hello\n".repeat(2));
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut out = [0u8; 32];
+        assert_eq!(decompress(&mut out[..17], &HELLO), Err(Error::Overflow));
+        assert_eq!(decompress_to_vec(&HELLO, 17), Err(Error::LimitExceeded));
+        assert_eq!(decompress(&mut out, &HELLO[..40]), Err(Error::Underflow));
+        assert_eq!(decompress(&mut out, b"BZh0"), Err(Error::InvalidHeader));
+        assert_eq!(decompress(&mut out, &HELLO[1..]), Err(Error::InvalidHeader));
+
+        let mut broken = HELLO;
+        broken[4] ^= 1;
+        assert_eq!(
+            decompress(&mut out, &broken),
+            Err(Error::InvalidBlockSignature)
+        );
+
+        // block CRC and combined stream CRC
+        let mut broken = HELLO;
+        broken[10] ^= 1;
+        assert_eq!(decompress(&mut out, &broken), Err(Error::ChecksumMismatch));
+        let mut broken = EMPTY;
+        broken[13] ^= 1;
+        assert_eq!(decompress(&mut out, &broken), Err(Error::ChecksumMismatch));
+
+        // randomised bit
+        let mut broken = HELLO;
+        broken[14] |= 0x80;
+        assert_eq!(decompress(&mut out, &broken), Err(Error::RandomizedBlock));
+
+        // corrupt block data never panics
+        for i in 14..HELLO.len() - 10 {
+            for bit in 0..8 {
+                let mut broken = HELLO;
+                broken[i] ^= 1 << bit;
+                let _ = decompress(&mut out, &broken);
+            }
+        }
+    }
+}
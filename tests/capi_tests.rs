@@ -0,0 +1,325 @@
+#![cfg(feature = "capi")]
+
+// ----------------------------------------------------------------------------
+mod test {
+    use miniz::capi::*;
+    use std::ffi::{c_ulong, c_void, CStr};
+
+    // xorshift, incompressible
+    fn noise(len: usize) -> Vec<u8> {
+        let mut x = 0x2318u32;
+        (0..len)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 17;
+                x ^= x << 5;
+                x as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let src = b"hello hello hello hello";
+        let mut packed = [0u8; 64];
+        let mut packed_len = packed.len() as c_ulong;
+        let res = unsafe {
+            compress2(
+                packed.as_mut_ptr(),
+                &mut packed_len,
+                src.as_ptr(),
+                src.len() as c_ulong,
+                6,
+            )
+        };
+        assert_eq!(res, Z_OK);
+        assert!(packed_len <= compressBound(src.len() as c_ulong));
+
+        let mut out = [0u8; 64];
+        let mut out_len = out.len() as c_ulong;
+        let res =
+            unsafe { uncompress(out.as_mut_ptr(), &mut out_len, packed.as_ptr(), packed_len) };
+        assert_eq!(res, Z_OK);
+        assert_eq!(&out[..out_len as usize], src);
+
+        let mut out_len = 4;
+        let res =
+            unsafe { uncompress(out.as_mut_ptr(), &mut out_len, packed.as_ptr(), packed_len) };
+        assert_eq!(res, Z_BUF_ERROR);
+        assert_eq!(&out[..out_len as usize], b"hell");
+
+        // the output written before the buffer ran out
+        let mut short = [0u8; 8];
+        let mut short_len = short.len() as c_ulong;
+        let res = unsafe {
+            compress2(
+                short.as_mut_ptr(),
+                &mut short_len,
+                src.as_ptr(),
+                src.len() as c_ulong,
+                6,
+            )
+        };
+        assert_eq!(res, Z_BUF_ERROR);
+        assert_eq!(short_len, 8);
+        assert_eq!(short, packed[..8]);
+        assert_eq!(compressBound(c_ulong::MAX), c_ulong::MAX);
+
+        packed[packed_len as usize - 1] ^= 1;
+        let mut out_len = out.len() as c_ulong;
+        let res =
+            unsafe { uncompress(out.as_mut_ptr(), &mut out_len, packed.as_ptr(), packed_len) };
+        assert_eq!(res, Z_DATA_ERROR);
+    }
+
+    #[test]
+    fn test_checksums() {
+        let data = b"123456789";
+        assert_eq!(unsafe { crc32(0, std::ptr::null(), 0) }, 0);
+        assert_eq!(unsafe { crc32(0, data.as_ptr(), 9) }, 0xcbf43926);
+        assert_eq!(unsafe { adler32(0, std::ptr::null(), 0) }, 1);
+        assert_eq!(unsafe { adler32(1, data.as_ptr(), 9) }, 0x091e01de);
+
+        let (crc1, crc2) = unsafe { (crc32(0, data.as_ptr(), 4), crc32(0, data[4..].as_ptr(), 5)) };
+        assert_eq!(crc32_combine(crc1, crc2, 5), 0xcbf43926);
+        let adler1 = unsafe { adler32(1, data.as_ptr(), 4) };
+        let adler2 = unsafe { adler32(1, data[4..].as_ptr(), 5) };
+        assert_eq!(adler32_combine(adler1, adler2, 5), 0x091e01de);
+        assert_eq!(adler32_combine(adler1, adler2, -1), 0xffffffff);
+    }
+
+    fn compressed_len(src: &[u8], level: i32) -> usize {
+        let mut packed = vec![0u8; src.len() + 64];
+        let mut packed_len = packed.len() as c_ulong;
+        let res = unsafe {
+            compress2(
+                packed.as_mut_ptr(),
+                &mut packed_len,
+                src.as_ptr(),
+                src.len() as c_ulong,
+                level,
+            )
+        };
+        assert_eq!(res, Z_OK);
+        packed_len as usize
+    }
+
+    #[test]
+    fn test_compress_levels() {
+        let text = b"hello hello hello hello ".repeat(100);
+        let noise = noise(100_000);
+        for src in [&text[..], &noise[..]] {
+            let bound = compressBound(src.len() as c_ulong);
+            let mut sizes = Vec::new();
+            for level in -1..=9 {
+                let mut packed = vec![0u8; bound as usize];
+                let mut packed_len = bound;
+                let res = unsafe {
+                    compress2(
+                        packed.as_mut_ptr(),
+                        &mut packed_len,
+                        src.as_ptr(),
+                        src.len() as c_ulong,
+                        level,
+                    )
+                };
+                assert_eq!(res, Z_OK, "{level}");
+                sizes.push(packed_len);
+
+                let mut out = vec![0u8; src.len()];
+                let mut out_len = out.len() as c_ulong;
+                let res = unsafe {
+                    uncompress(out.as_mut_ptr(), &mut out_len, packed.as_ptr(), packed_len)
+                };
+                assert_eq!(res, Z_OK, "{level}");
+                assert_eq!(&out[..out_len as usize], src, "{level}");
+            }
+            // level 0 stores
+            assert!(sizes[1] > src.len() as c_ulong);
+        }
+        assert!(compressed_len(&text, 6) < text.len() / 10);
+    }
+
+    #[test]
+    fn test_uncompress2() {
+        let src = b"hello hello hello hello";
+        let mut packed = [0u8; 64];
+        let mut packed_len = packed.len() as c_ulong;
+        let res = unsafe {
+            compress2(
+                packed.as_mut_ptr(),
+                &mut packed_len,
+                src.as_ptr(),
+                src.len() as c_ulong,
+                6,
+            )
+        };
+        assert_eq!(res, Z_OK);
+
+        // trailing data is not part of the stream
+        let stream_len = packed_len;
+        let mut source_len = stream_len + 8;
+        let mut out = [0u8; 64];
+        let mut out_len = out.len() as c_ulong;
+        let res = unsafe {
+            uncompress2(
+                out.as_mut_ptr(),
+                &mut out_len,
+                packed.as_ptr(),
+                &mut source_len,
+            )
+        };
+        assert_eq!(res, Z_OK);
+        assert_eq!(source_len, stream_len);
+        assert_eq!(&out[..out_len as usize], src);
+
+        let mut source_len = stream_len - 1;
+        let res = unsafe {
+            mz_uncompress2(
+                out.as_mut_ptr(),
+                &mut out_len,
+                packed.as_ptr(),
+                &mut source_len,
+            )
+        };
+        assert_eq!(res, MZ_DATA_ERROR);
+    }
+
+    #[test]
+    fn test_mz_round_trip() {
+        let src = b"miniz miniz miniz miniz miniz".repeat(50);
+        let bound = mz_compressBound(src.len() as c_ulong);
+        let mut packed = vec![0u8; bound as usize];
+        let mut packed_len = bound;
+        let res = unsafe {
+            mz_compress(
+                packed.as_mut_ptr(),
+                &mut packed_len,
+                src.as_ptr(),
+                src.len() as c_ulong,
+            )
+        };
+        assert_eq!(res, MZ_OK);
+        assert!(packed_len < 100);
+
+        let mut out = vec![0u8; src.len()];
+        let mut out_len = out.len() as c_ulong;
+        let res =
+            unsafe { mz_uncompress(out.as_mut_ptr(), &mut out_len, packed.as_ptr(), packed_len) };
+        assert_eq!(res, MZ_OK);
+        assert_eq!(out, src);
+
+        let mut out_len = 10;
+        let res =
+            unsafe { mz_uncompress(out.as_mut_ptr(), &mut out_len, packed.as_ptr(), packed_len) };
+        assert_eq!(res, MZ_BUF_ERROR);
+
+        // MZ_UBER_COMPRESSION is accepted, levels beyond are not
+        let mut packed_len = bound;
+        let res = unsafe {
+            mz_compress2(
+                packed.as_mut_ptr(),
+                &mut packed_len,
+                src.as_ptr(),
+                src.len() as c_ulong,
+                MZ_UBER_COMPRESSION,
+            )
+        };
+        assert_eq!(res, MZ_OK);
+        let mut packed_len = bound;
+        let res = unsafe {
+            mz_compress2(
+                packed.as_mut_ptr(),
+                &mut packed_len,
+                src.as_ptr(),
+                src.len() as c_ulong,
+                11,
+            )
+        };
+        assert_eq!(res, MZ_PARAM_ERROR);
+    }
+
+    #[test]
+    fn test_mem_to_mem() {
+        let src = b"tinfl tdefl tinfl tdefl tinfl tdefl".repeat(40);
+        for flags in [
+            TDEFL_DEFAULT_MAX_PROBES,
+            TDEFL_DEFAULT_MAX_PROBES | TDEFL_WRITE_ZLIB_HEADER,
+            1 | TDEFL_RLE_MATCHES,
+            TDEFL_FORCE_ALL_STATIC_BLOCKS | TDEFL_WRITE_ZLIB_HEADER,
+            TDEFL_FORCE_ALL_RAW_BLOCKS,
+            0,
+            TDEFL_MAX_PROBES_MASK,
+        ] {
+            let mut packed = vec![0u8; src.len() + 64];
+            let packed_len = unsafe {
+                tdefl_compress_mem_to_mem(
+                    packed.as_mut_ptr().cast::<c_void>(),
+                    packed.len(),
+                    src.as_ptr().cast::<c_void>(),
+                    src.len(),
+                    flags,
+                )
+            };
+            assert_ne!(packed_len, 0, "{flags:x}");
+
+            let zlib = flags & TDEFL_WRITE_ZLIB_HEADER != 0;
+            let tinfl_flags = if zlib {
+                TINFL_FLAG_PARSE_ZLIB_HEADER
+            } else {
+                0
+            };
+            let mut out = vec![0u8; src.len()];
+            let len = unsafe {
+                tinfl_decompress_mem_to_mem(
+                    out.as_mut_ptr().cast::<c_void>(),
+                    out.len(),
+                    packed.as_ptr().cast::<c_void>(),
+                    packed_len,
+                    tinfl_flags,
+                )
+            };
+            assert_eq!(len, src.len(), "{flags:x}");
+            assert_eq!(out, src, "{flags:x}");
+
+            // too small output buffers fail
+            let len = unsafe {
+                tinfl_decompress_mem_to_mem(
+                    out.as_mut_ptr().cast::<c_void>(),
+                    out.len() - 1,
+                    packed.as_ptr().cast::<c_void>(),
+                    packed_len,
+                    tinfl_flags,
+                )
+            };
+            assert_eq!(len, TINFL_DECOMPRESS_MEM_TO_MEM_FAILED, "{flags:x}");
+        }
+
+        let mut packed = [0u8; 16];
+        let packed_len = unsafe {
+            tdefl_compress_mem_to_mem(
+                packed.as_mut_ptr().cast::<c_void>(),
+                packed.len(),
+                src.as_ptr().cast::<c_void>(),
+                src.len(),
+                TDEFL_FORCE_ALL_RAW_BLOCKS,
+            )
+        };
+        assert_eq!(packed_len, 0);
+    }
+
+    #[test]
+    fn test_mz_checksums_and_errors() {
+        let data = b"123456789";
+        assert_eq!(unsafe { mz_crc32(0, std::ptr::null(), 0) }, 0);
+        assert_eq!(unsafe { mz_crc32(0, data.as_ptr(), 9) }, 0xcbf43926);
+        assert_eq!(unsafe { mz_adler32(0, std::ptr::null(), 0) }, 1);
+        assert_eq!(unsafe { mz_adler32(1, data.as_ptr(), 9) }, 0x091e01de);
+
+        let msg = unsafe { CStr::from_ptr(mz_error(MZ_DATA_ERROR)) };
+        assert_eq!(msg.to_str(), Ok("data error"));
+        let msg = unsafe { CStr::from_ptr(mz_error(MZ_PARAM_ERROR)) };
+        assert_eq!(msg.to_str(), Ok("parameter error"));
+        assert!(mz_error(42).is_null());
+    }
+}
@@ -0,0 +1,79 @@
+// ----------------------------------------------------------------------------
+mod test {
+    use miniz::carve::{carve, CarveOptions, StreamKind};
+    use miniz::http::{ContentEncoding, Encoder};
+    use miniz::stream::{FlushCompress, Status};
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = Encoder::new(ContentEncoding::Gzip, 6);
+        let mut out = vec![0u8; data.len() + 100];
+        let status = encoder.run(data, &mut out, FlushCompress::Finish);
+        assert_eq!(status, Status::StreamEnd);
+        out.truncate(encoder.total_out() as usize);
+        out
+    }
+
+    #[test]
+    fn test_carve() {
+        let text = b"The quick brown fox jumps over the lazy dog.".repeat(3);
+        let zlib = miniz::compress_to_vec(&text, 6);
+        let gzip = gzip(b"gzip member");
+
+        let mut blob = b"header junk \x01\x02\x03".to_vec();
+        let zlib_offset = blob.len();
+        blob.extend_from_slice(&zlib);
+        blob.extend_from_slice(b"more junk");
+        let gzip_offset = blob.len();
+        blob.extend_from_slice(&gzip);
+        blob.extend_from_slice(b"tail");
+
+        let options = CarveOptions {
+            preview_len: 9,
+            ..Default::default()
+        };
+        let streams = carve(&blob, &options);
+        assert_eq!(streams.len(), 2);
+        assert_eq!(streams[0].kind, StreamKind::Zlib);
+        assert_eq!(
+            (streams[0].offset, streams[0].len),
+            (zlib_offset, zlib.len())
+        );
+        assert_eq!(streams[0].decompressed_len, text.len());
+        assert_eq!(streams[0].preview, b"The quick");
+        assert_eq!(streams[1].kind, StreamKind::Gzip);
+        assert_eq!(
+            (streams[1].offset, streams[1].len),
+            (gzip_offset, gzip.len())
+        );
+        assert_eq!(streams[1].preview, b"gzip memb");
+
+        // a damaged checksum is not reported
+        let mut damaged = blob.clone();
+        damaged[zlib_offset + zlib.len() - 1] ^= 1;
+        assert_eq!(carve(&damaged, &options).len(), 1);
+    }
+
+    #[test]
+    fn test_carve_raw() {
+        // "hello hello hello\n" as raw deflate data
+        let raw = [
+            0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x90, 0x5c, 0x00,
+        ];
+        let mut blob = vec![0u8; 20];
+        blob.extend_from_slice(&raw);
+        blob.extend_from_slice(&[0u8; 20]);
+
+        let options = CarveOptions {
+            raw_deflate: true,
+            min_output: 10,
+            ..Default::default()
+        };
+        let streams = carve(&blob, &options);
+        assert_eq!(streams.len(), 1);
+        assert_eq!(streams[0].kind, StreamKind::Deflate);
+        assert_eq!((streams[0].offset, streams[0].len), (20, raw.len()));
+        assert_eq!(streams[0].preview, b"hello hello hello\n");
+
+        assert!(carve(&blob, &CarveOptions::default()).is_empty());
+    }
+}
@@ -0,0 +1,100 @@
+// ----------------------------------------------------------------------------
+mod test {
+    use miniz::checksum::{
+        adler32, adler32_combine, crc32, crc32_combine, crc64, Adler32, Checksum, Crc32, Crc64,
+    };
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(0, b""), 0);
+        assert_eq!(crc32(0, b"123456789"), 0xcbf43926);
+        assert_eq!(crc32(crc32(0, b"1234"), b"56789"), 0xcbf43926);
+    }
+
+    #[test]
+    fn test_crc64() {
+        assert_eq!(crc64(0, b""), 0);
+        assert_eq!(crc64(0, b"123456789"), 0x995dc9bbdf1939fa);
+        assert_eq!(crc64(crc64(0, b"1234"), b"56789"), 0x995dc9bbdf1939fa);
+
+        let mut crc = Crc64::new();
+        crc.update(b"12345");
+        crc.update(b"6789");
+        assert_eq!(crc.value(), 0x995dc9bbdf1939fa);
+    }
+
+    #[test]
+    fn test_adler32() {
+        assert_eq!(adler32(1, b""), 1);
+        assert_eq!(adler32(1, b"Wikipedia"), 0x11e60398);
+        assert_eq!(adler32(adler32(1, b"Wiki"), b"pedia"), 0x11e60398);
+
+        // long runs exercise the deferred modulo
+        let data = vec![0xffu8; 100_000];
+        let mut a = 1u32;
+        let mut b = 0u32;
+        for x in &data {
+            a = (a + *x as u32) % 65521;
+            b = (b + a) % 65521;
+        }
+        assert_eq!(adler32(1, &data), (b << 16) | a);
+    }
+
+    #[test]
+    fn test_combine() {
+        let data: Vec<u8> = (0..200_000u32)
+            .map(|i| (i.wrapping_mul(i) >> 7) as u8)
+            .collect();
+        for split in [0, 1, 9, 5552, 65521, 65522, 100_000, 200_000] {
+            let (first, second) = data.split_at(split);
+            let len2 = second.len() as u64;
+            assert_eq!(
+                crc32_combine(crc32(0, first), crc32(0, second), len2),
+                crc32(0, &data),
+                "{split}"
+            );
+            assert_eq!(
+                adler32_combine(adler32(1, first), adler32(1, second), len2),
+                adler32(1, &data),
+                "{split}"
+            );
+        }
+        assert_eq!(crc32_combine(0xcbf43926, 0, 0), 0xcbf43926);
+        assert_eq!(adler32_combine(0x11e60398, 1, 0), 0x11e60398);
+    }
+
+    #[test]
+    fn test_hashers() {
+        let mut crc = Crc32::new();
+        crc.update(b"1234");
+        crc.update(b"56789");
+        assert_eq!(crc.value(), 0xcbf43926);
+
+        let mut adler = Adler32::default();
+        assert_eq!(adler.value(), 1);
+        adler.update(b"Wikipedia");
+        assert_eq!(adler.value(), 0x11e60398);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_tee() {
+        use miniz::checksum::{ChecksumReader, ChecksumWriter};
+        use std::io::{Read, Write};
+
+        let mut reader = ChecksumReader::new(&b"123456789"[..], Crc32::new());
+        let mut buf = [0u8; 4];
+        assert_eq!(reader.read(&mut buf).unwrap(), 4);
+        assert_eq!(reader.checksum(), crc32(0, b"1234"));
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(reader.checksum(), 0xcbf43926);
+
+        let mut writer = ChecksumWriter::new(Vec::new(), Adler32::new());
+        writer.write_all(b"Wiki").unwrap();
+        writer.write_all(b"pedia").unwrap();
+        writer.flush().unwrap();
+        assert_eq!(writer.checksum(), 0x11e60398);
+        assert_eq!(writer.into_inner(), b"Wikipedia");
+    }
+}
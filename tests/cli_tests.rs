@@ -0,0 +1,82 @@
+#![cfg(feature = "cli")]
+
+// ----------------------------------------------------------------------------
+mod test {
+    use std::process::Command;
+
+    #[test]
+    fn test_miniunzip() {
+        let out = Command::new(env!("CARGO_BIN_EXE_miniunzip"))
+            .args(["-l", "assets/zip/folder.zip"])
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        let listing = String::from_utf8(out.stdout).unwrap();
+        assert!(listing.contains("testdir/test2"));
+        let files = miniz::zip_read::zip_open(include_bytes!("../assets/zip/folder.zip")).unwrap();
+        let test2 = files.iter().find(|f| f.name == "testdir/test2").unwrap();
+        let line = format!("{:>10}  testdir/test2", test2.size);
+        assert!(listing.lines().any(|l| l == line), "uncompressed size");
+
+        let dir = std::env::temp_dir().join("miniz-miniunzip-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        let out = Command::new(env!("CARGO_BIN_EXE_miniunzip"))
+            .args(["assets/zip/folder.zip", "-d"])
+            .arg(&dir)
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        let test2 = std::fs::read(dir.join("testdir/test2")).unwrap();
+        let test = std::fs::read(dir.join("test")).unwrap();
+        assert_eq!(test, test2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_minigzip() {
+        let dir = std::env::temp_dir().join("miniz-minigzip-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("data.txt");
+        let data = "minigzip round trip\n".repeat(1000);
+        std::fs::write(&path, &data).unwrap();
+
+        let out = Command::new(env!("CARGO_BIN_EXE_minigzip"))
+            .arg("-9")
+            .arg(&path)
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        let gz = std::fs::read(dir.join("data.txt.gz")).unwrap();
+        assert_eq!(miniz::decompress_to_vec_gzip(&gz).unwrap(), data.as_bytes());
+
+        std::fs::remove_file(&path).unwrap();
+        let out = Command::new(env!("CARGO_BIN_EXE_minigzip"))
+            .arg("-d")
+            .arg(dir.join("data.txt.gz"))
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), data);
+
+        let out = Command::new(env!("CARGO_BIN_EXE_minigzip"))
+            .args(["-d", "missing"])
+            .output()
+            .unwrap();
+        assert!(!out.status.success());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_pngdump() {
+        let out = Command::new(env!("CARGO_BIN_EXE_pngdump"))
+            .arg("assets/png/basn2c08.png")
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        let dump = String::from_utf8(out.stdout).unwrap();
+        assert!(dump.contains("IHDR"));
+        assert!(dump.contains("IEND"));
+        assert!(dump.contains("32 x 32"));
+    }
+}
@@ -0,0 +1,146 @@
+// Differential tests against corpora with known results.
+// * zlib's infcover.c: https://github.com/madler/zlib/blob/master/test/infcover.c
+// * PngSuite: http://www.schaik.com/pngsuite/
+
+// ----------------------------------------------------------------------------
+mod test {
+    use miniz::checksum::adler32;
+    use miniz::inflate::{
+        inflate_gzip_with_options, inflate_with_options, inflate_zlib_with_options, Error,
+        InflateOptions,
+    };
+
+    #[derive(Debug, Clone, Copy)]
+    enum Wrapper {
+        Raw,
+        Zlib,
+        Gzip,
+    }
+    use Wrapper::*;
+
+    // result of zlib 1.2.13 for the complete input
+    #[derive(Debug, Clone, Copy)]
+    enum Expected {
+        // stream end with the output length and its Adler-32
+        Decoded(usize, u32),
+        // no error, but the stream doesn't end
+        Incomplete,
+        Invalid,
+    }
+    use Expected::*;
+
+    // the inflate vectors of infcover.c, hex bytes as written there
+    #[rustfmt::skip]
+    const INFCOVER: [(&str, Wrapper, &str, Expected); 46] = [
+        ("force window allocation", Raw, "63 0", Incomplete),
+        ("force window replacement", Raw, "63 18 5", Incomplete),
+        ("force split window update", Raw, "63 18 68 30 d0 0 0", Decoded(257, 0x01010001)),
+        ("use fixed blocks", Raw, "3 0", Decoded(0, 0x00000001)),
+        ("bad gzip method", Gzip, "1f 8b 0 0", Invalid),
+        ("bad gzip flags", Gzip, "1f 8b 8 80", Invalid),
+        ("bad zlib method", Zlib, "77 85", Invalid),
+        ("set window size from header", Zlib, "8 99", Incomplete),
+        ("bad zlib window size", Zlib, "78 9c", Incomplete),
+        ("check adler32", Zlib, "78 9c 63 0 0 0 1 0 1", Decoded(1, 0x00010001)),
+        ("bad header crc", Gzip, "1f 8b 8 1e 0 0 0 0 0 0 1 0 0 0 0 0 0", Invalid),
+        ("check gzip length", Gzip, "1f 8b 8 2 0 0 0 0 0 0 1d 26 3 0 0 0 0 0 0 0 0 0", Decoded(0, 0x00000001)),
+        ("bad zlib header check", Zlib, "78 90", Invalid),
+        ("need dictionary", Zlib, "8 b8 0 0 0 1", Invalid),
+        ("compute adler32", Zlib, "78 9c 63 0", Incomplete),
+        ("inflate_fast TYPE return", Raw, "2 8 20 80 0 3 0", Decoded(0, 0x00000001)),
+        ("window wrap", Raw, "63 18 5 40 c 0", Decoded(262, 0x01060001)),
+        ("fast length extra bits", Raw, "e5 e0 81 ad 6d cb b2 2c c9 01 1e 59 63 ae 7d ee fb 4d fd b5 35 41 68 ff 7f 0f 0 0 0", Invalid),
+        ("fast distance extra bits", Raw, "25 fd 81 b5 6d 59 b6 6a 49 ea af 35 6 34 eb 8c b9 f6 b9 1e ef 67 49 50 fe ff ff 3f 0 0", Invalid),
+        ("fast invalid distance code", Raw, "3 7e 0 0 0 0 0", Invalid),
+        ("fast invalid literal/length code", Raw, "1b 7 0 0 0 0 0", Invalid),
+        ("fast 2nd level codes and too far back", Raw, "d c7 1 ae eb 38 c 4 41 a0 87 72 de df fb 1f b8 36 b1 38 5d ff ff 0", Invalid),
+        ("very common case", Raw, "63 18 5 8c 10 8 0 0 0 0", Decoded(266, 0x011a0005)),
+        ("contiguous and wrap around window", Raw, "63 60 60 18 c9 0 8 18 18 18 26 c0 28 0 29 0 0 0", Decoded(270, 0x073e0121)),
+        ("copy direct from output", Raw, "63 0 3 0 0 0 0 0", Decoded(6, 0x00060001)),
+        ("invalid stored block lengths", Raw, "0 0 0 0 0", Invalid),
+        ("fixed", Raw, "3 0", Decoded(0, 0x00000001)),
+        ("invalid block type", Raw, "6", Invalid),
+        ("stored", Raw, "1 1 0 fe ff 0", Decoded(1, 0x00010001)),
+        ("too many length or distance symbols", Raw, "fc 0 0", Invalid),
+        ("invalid code lengths set", Raw, "4 0 fe ff", Invalid),
+        ("invalid bit length repeat", Raw, "4 0 24 49 0", Invalid),
+        ("invalid bit length repeat", Raw, "4 0 24 e9 ff ff", Invalid),
+        ("invalid code -- missing end-of-block", Raw, "4 0 24 e9 ff 6d", Invalid),
+        ("invalid literal/lengths set", Raw, "4 80 49 92 24 49 92 24 71 ff ff 93 11 0", Invalid),
+        ("invalid distances set", Raw, "4 80 49 92 24 49 92 24 f b4 ff ff c3 84", Invalid),
+        ("invalid literal/length code", Raw, "4 c0 81 8 0 0 0 0 20 7f eb b 0 0", Invalid),
+        ("invalid distance code", Raw, "2 7e ff ff", Invalid),
+        ("invalid distance too far back", Raw, "c c0 81 0 0 0 0 0 90 ff 6b 4 0", Invalid),
+        ("incorrect data check", Gzip, "1f 8b 8 0 0 0 0 0 0 0 3 0 0 0 0 1", Invalid),
+        ("incorrect length check", Gzip, "1f 8b 8 0 0 0 0 0 0 0 3 0 0 0 0 0 0 0 0 1", Invalid),
+        ("pull 17", Raw, "5 c0 21 d 0 0 0 80 b0 fe 6d 2f 91 6c", Decoded(0, 0x00000001)),
+        ("long code", Raw, "5 e0 81 91 24 cb b2 2c 49 e2 f 2e 8b 9a 47 56 9f fb fe ec d2 ff 1f", Decoded(0, 0x00000001)),
+        ("length extra", Raw, "ed c0 1 1 0 0 0 40 20 ff 57 1b 42 2c 4f", Decoded(516, 0x02040001)),
+        ("long distance and extra", Raw, "ed cf c1 b1 2c 47 10 c4 30 fa 6f 35 1d 1 82 59 3d fb be 2e 2a fc f c", Decoded(518, 0x02060001)),
+        ("window end", Raw, "ed c0 81 0 0 0 0 80 a0 fd a9 17 a9 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 6", Decoded(33025, 0x81010001)),
+    ];
+
+    fn decode(wrapper: Wrapper, src: &[u8]) -> Result<Vec<u8>, Error> {
+        let options = InflateOptions {
+            strict_end_of_input: true,
+            ..Default::default()
+        };
+        let mut out = vec![0; 1 << 16];
+        let len = match wrapper {
+            Raw => inflate_with_options(&mut out, src, &options)?,
+            Zlib => inflate_zlib_with_options(&mut out, src, &options)?,
+            Gzip => inflate_gzip_with_options(&mut out, src, &options)?,
+        };
+        out.truncate(len);
+        Ok(out)
+    }
+
+    #[test]
+    fn test_infcover() {
+        for (name, wrapper, hex, expected) in INFCOVER {
+            let src: Vec<u8> = hex
+                .split_whitespace()
+                .map(|byte| u8::from_str_radix(byte, 16).unwrap())
+                .collect();
+            let result = decode(wrapper, &src);
+            match expected {
+                Decoded(len, adler) => {
+                    let out = result.unwrap_or_else(|err| panic!("{name}: {err:?}"));
+                    assert_eq!((out.len(), adler32(1, &out)), (len, adler), "{name}");
+                }
+                Incomplete => assert_eq!(result, Err(Error::Underflow), "{name}"),
+                Invalid => assert!(result.is_err(), "{name}: {result:?}"),
+            }
+        }
+    }
+
+    // Decodes every PngSuite image in the directory named by `MINIZ_PNGSUITE`, the corrupted
+    // images starting with 'x' must fail and all others decode to their IHDR size.
+    #[test]
+    #[cfg(feature = "png")]
+    fn test_pngsuite() {
+        use miniz::png_read::{decoded_size, png_read};
+
+        let Ok(dir) = std::env::var("MINIZ_PNGSUITE") else {
+            return;
+        };
+        let mut count = 0;
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|ext| ext != "png") {
+                continue;
+            }
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            let png = std::fs::read(&path).unwrap();
+            match png_read(&png) {
+                Ok((ihdr, _, pixels)) => {
+                    assert!(!name.starts_with('x'), "{name}: corrupt image decoded");
+                    assert_eq!(pixels.len(), decoded_size(&ihdr).unwrap(), "{name}");
+                }
+                Err(err) => assert!(name.starts_with('x'), "{name}: {err:?}"),
+            }
+            count += 1;
+        }
+        assert!(count > 0, "no PNG files found");
+    }
+}
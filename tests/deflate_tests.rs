@@ -0,0 +1,185 @@
+// ----------------------------------------------------------------------------
+mod test {
+    use miniz::deflate::{deflate, deflate_with_strategy, CompressionLevel, Strategy};
+    use miniz::{decompress_to_vec, decompress_to_vec_gzip};
+
+    include!("../assets/gz/lines.rs");
+
+    const LEVELS: [CompressionLevel; 4] = [
+        CompressionLevel::NoCompression,
+        CompressionLevel::BestSpeed,
+        CompressionLevel::DefaultLevel,
+        CompressionLevel::BestCompression,
+    ];
+
+    fn compress(src: &[u8], level: CompressionLevel) -> Vec<u8> {
+        let mut dst = Vec::new();
+        deflate(&mut dst, src, level);
+        dst
+    }
+
+    // xorshift, incompressible
+    fn noise(len: usize) -> Vec<u8> {
+        let mut x = 0x2545_f491u32;
+        (0..len)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 17;
+                x ^= x << 5;
+                x as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let text = decompress_to_vec_gzip(LINES_GZ).unwrap();
+        let mut far = noise(20000);
+        far.extend_from_within(..);
+        far.extend_from_within(..30000);
+
+        let inputs = [
+            Vec::new(),
+            b"a".to_vec(),
+            b"abcabcabcabcabc".to_vec(),
+            vec![0; 300_000],
+            noise(100_000),
+            far,
+            text,
+        ];
+        for src in &inputs {
+            for level in LEVELS {
+                let packed = compress(src, level);
+                assert_eq!(decompress_to_vec(&packed).as_ref(), Ok(src), "{level:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_ratio() {
+        let text = decompress_to_vec_gzip(LINES_GZ).unwrap();
+        let sizes: Vec<usize> = LEVELS.iter().map(|&l| compress(&text, l).len()).collect();
+        assert!(sizes[0] > text.len());
+        assert!(sizes[1] * 2 < text.len());
+        assert!(sizes[2] <= sizes[1]);
+        assert!(sizes[3] <= sizes[2]);
+
+        // runs collapse to maximum length matches
+        assert!(compress(&[7; 300_000], CompressionLevel::BestSpeed).len() < 1000);
+
+        // incompressible data falls back to stored blocks
+        let random = noise(100_000);
+        let packed = compress(&random, CompressionLevel::BestCompression);
+        assert!(packed.len() < random.len() + random.len() / 1000);
+    }
+
+    #[test]
+    fn test_stored_passthrough() {
+        // full blocks of 65535 bytes and a final partial block, each with a 5 byte header
+        let src = noise(3 * 65535 + 100);
+        let packed = compress(&src, CompressionLevel::NoCompression);
+        assert_eq!(packed.len(), src.len() + 4 * 5);
+        for (i, block) in packed.chunks(65535 + 5).enumerate() {
+            let len = (block.len() - 5) as u16;
+            assert_eq!(block[0], (i == 3) as u8, "BFINAL and BTYPE 00");
+            assert_eq!(
+                block[1..5],
+                [len.to_le_bytes(), (!len).to_le_bytes()].concat()
+            );
+            assert_eq!(block[5..], src[i * 65535..i * 65535 + len as usize]);
+        }
+
+        // the strategy doesn't matter without compression
+        let mut packed = Vec::new();
+        deflate_with_strategy(&mut packed, &src, CompressionLevel::from(0), Strategy::Rle);
+        assert_eq!(packed, compress(&src, CompressionLevel::NoCompression));
+        assert_eq!(
+            compress(&[], CompressionLevel::NoCompression),
+            [1, 0, 0, 0xff, 0xff]
+        );
+    }
+
+    #[test]
+    fn test_strategies() {
+        let text = decompress_to_vec_gzip(LINES_GZ).unwrap();
+        let strategies = [
+            Strategy::Default,
+            Strategy::Filtered,
+            Strategy::HuffmanOnly,
+            Strategy::Rle,
+            Strategy::Fixed,
+        ];
+        let pack = |src: &[u8], level, strategy| {
+            let mut dst = Vec::new();
+            deflate_with_strategy(&mut dst, src, level, strategy);
+            dst
+        };
+
+        for src in [&text, &vec![9; 100_000], &noise(10_000), &Vec::new()] {
+            for level in LEVELS {
+                for strategy in strategies {
+                    let packed = pack(src, level, strategy);
+                    assert_eq!(
+                        decompress_to_vec(&packed).as_ref(),
+                        Ok(src),
+                        "{level:?} {strategy:?}"
+                    );
+                }
+            }
+        }
+
+        // the default strategy finds the most matches, literals alone still save on text
+        let level = CompressionLevel::DefaultLevel;
+        let sizes: Vec<usize> = strategies
+            .iter()
+            .map(|&strategy| pack(&text, level, strategy).len())
+            .collect();
+        assert_eq!(sizes[0], compress(&text, level).len());
+        assert!(sizes[0] < sizes[1] && sizes[1] < sizes[2]);
+        assert!(sizes[2] < text.len());
+
+        // runs are matches at distance 1
+        assert!(pack(&[7; 100_000], level, Strategy::Rle).len() < 1000);
+        assert!(pack(&[7; 100_000], level, Strategy::HuffmanOnly).len() > 10_000);
+
+        // BTYPE of the first block: fixed instead of dynamic Huffman codes
+        let block_type = |packed: &[u8]| (packed[0] >> 1) & 3;
+        assert_eq!(block_type(&pack(&text, level, Strategy::Default)), 2);
+        assert_eq!(block_type(&pack(&text, level, Strategy::Fixed)), 1);
+    }
+
+    #[test]
+    fn test_zlib_levels() {
+        let text = decompress_to_vec_gzip(LINES_GZ).unwrap();
+        let sizes: Vec<usize> = (1..=9u8)
+            .map(|level| {
+                let packed = compress(&text, level.into());
+                assert_eq!(
+                    decompress_to_vec(&packed),
+                    Ok(text.clone()),
+                    "level {level}"
+                );
+                packed.len()
+            })
+            .collect();
+
+        // longer chains and lazy matching pay off, with small steps in between
+        assert!(sizes[8] <= sizes[5] && sizes[5] <= sizes[3] && sizes[3] < sizes[0]);
+        assert!(
+            sizes.windows(2).all(|w| w[1] <= w[0] + w[0] / 50),
+            "{sizes:?}"
+        );
+    }
+
+    #[test]
+    fn test_level_from_u8() {
+        assert_eq!(CompressionLevel::from(0), CompressionLevel::NoCompression);
+        assert_eq!(CompressionLevel::from(1), CompressionLevel::BestSpeed);
+        assert_eq!(CompressionLevel::from(6), CompressionLevel::DefaultLevel);
+        assert_eq!(CompressionLevel::from(9), CompressionLevel::BestCompression);
+        assert_eq!(CompressionLevel::from(4), CompressionLevel::Level(4));
+        assert_eq!(CompressionLevel::from(12).level(), 9);
+        assert_eq!(CompressionLevel::Level(1).level(), 1);
+        assert_eq!(CompressionLevel::default(), CompressionLevel::DefaultLevel);
+    }
+}
@@ -0,0 +1,145 @@
+#![cfg(all(feature = "png", feature = "zip"))]
+
+// ----------------------------------------------------------------------------
+mod test {
+    use miniz::{inflate, png_read, zip_read, Error};
+
+    fn read_first(data: &[u8]) -> miniz::Result<Vec<u8>> {
+        let files = zip_read::zip_open(data)?;
+        let name = files
+            .first()
+            .ok_or(zip_read::Error::FileNotFound)?
+            .name
+            .clone();
+        Ok(zip_read::zip_read(data, &files, &name)?)
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(
+            Error::from(inflate::Error::Underflow),
+            Error::Inflate(inflate::Error::Underflow)
+        );
+        assert_eq!(
+            Error::from(png_read::Error::MissingIEND),
+            Error::Png(png_read::Error::MissingIEND)
+        );
+        assert_eq!(
+            read_first(&[0u8; 16]),
+            Err(Error::Zip(zip_read::Error::NoCentralDirectory))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_source() {
+        use std::error::Error as _;
+
+        let err = Error::from(png_read::Error::InvalidSignature);
+        assert_eq!(err.to_string(), "png: InvalidSignature");
+        let source = err.source().unwrap();
+        assert_eq!(source.to_string(), "InvalidSignature");
+    }
+
+    #[test]
+    fn test_codes() {
+        // codes are part of the API and must not change
+        assert_eq!(Error::from(inflate::Error::Underflow).code(), 100);
+        assert_eq!(
+            inflate::Error::UnderSubscribedTree(inflate::Tree::Distance).code(),
+            116
+        );
+        assert_eq!(png_read::Error::InvalidPng.code(), 200);
+        assert_eq!(zip_read::Error::FileNotFound.code(), 304);
+
+        let mut count = 0;
+        for code in 0..1000 {
+            if let Some(err) = Error::from_code(code) {
+                assert_eq!(err.code(), code);
+                count += 1;
+            }
+        }
+        assert_eq!(count, 25 + 19 + 14 + 8, "all variants");
+        assert_eq!(Error::from_code(99), None);
+    }
+
+    #[test]
+    fn test_payloads() {
+        assert_eq!(zip_read::Error::InvalidCompressionMethod(12).code(), 303);
+        assert_eq!(
+            Error::from_code(108),
+            Some(Error::Inflate(inflate::Error::InvalidDistance {
+                distance: 0,
+                available: 0
+            }))
+        );
+
+        // a cause cannot be rebuilt from the code alone
+        assert_eq!(
+            Error::from_code(207),
+            Some(Error::Png(png_read::Error::CompressionError(None)))
+        );
+        assert_eq!(Error::from_code(305).unwrap().inflate_error(), None);
+
+        let err = Error::from(png_read::Error::InvalidColorFormat(5));
+        assert_eq!(err.to_string(), "png: InvalidColorFormat(5)");
+        let err = inflate::Error::InvalidDistance {
+            distance: 300,
+            available: 256,
+        };
+        assert_eq!(
+            err.to_string(),
+            "InvalidDistance { distance: 300, available: 256 }"
+        );
+    }
+
+    #[test]
+    fn test_inflate_through_layers() {
+        use miniz::zip_write::{FileOptions, ZipWriter};
+
+        // a deflated entry whose first block has the reserved block type 3
+        let mut writer = ZipWriter::new();
+        let text = b"inflate errors through the zip layer ".repeat(10);
+        writer
+            .add_file("a", &text, &FileOptions::default())
+            .unwrap();
+        let mut zip = writer.finish().unwrap();
+        zip[31] = 0x07;
+        let err = read_first(&zip).unwrap_err();
+        assert_eq!(
+            err,
+            Error::Zip(zip_read::Error::CompressionError(Some(
+                inflate::Error::InvalidBlockType
+            )))
+        );
+        assert_eq!(err.inflate_error(), Some(&inflate::Error::InvalidBlockType));
+
+        let mut png = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+        let ihdr = [0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0];
+        for (ty, data) in [
+            (b"IHDR", &ihdr[..]),
+            (b"IDAT", &[0x78, 0x01, 0x07]),
+            (b"IEND", &[]),
+        ] {
+            png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            let start = png.len();
+            png.extend_from_slice(ty);
+            png.extend_from_slice(data);
+            let crc = miniz::checksum::crc32(0, &png[start..]);
+            png.extend_from_slice(&crc.to_be_bytes());
+        }
+        let err = Error::from(png_read::png_read(&png).unwrap_err());
+        assert_eq!(err.inflate_error(), Some(&inflate::Error::InvalidBlockType));
+        assert_eq!(
+            Error::from(png_read::Error::MissingIEND).inflate_error(),
+            None
+        );
+
+        #[cfg(feature = "std")]
+        {
+            use std::error::Error as _;
+            let inner = err.source().and_then(|err| err.source()).unwrap();
+            assert_eq!(inner.to_string(), "InvalidBlockType");
+        }
+    }
+}
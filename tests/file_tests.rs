@@ -0,0 +1,94 @@
+#![cfg(feature = "std")]
+
+// ----------------------------------------------------------------------------
+mod test {
+    use miniz::file::{gunzip_file, gzip_file, gzip_file_with_header, inflate_file};
+    use std::path::PathBuf;
+
+    include!("../assets/gz/lines.rs");
+
+    // content of LINES_GZ, compressed with gzip -9 -n
+    fn lines() -> Vec<u8> {
+        (0..15000u32)
+            .flat_map(|i| format!("line {i}: {}\n", i * i % 9973).into_bytes())
+            .collect()
+    }
+
+    fn temp(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("miniz_test_file_{name}"))
+    }
+
+    #[test]
+    fn test_gunzip_file() {
+        let (gz, out) = (temp("gunzip.gz"), temp("gunzip.txt"));
+        std::fs::write(&gz, LINES_GZ).unwrap();
+        let expected = lines();
+        assert_eq!(gunzip_file(&gz, &out).unwrap(), expected.len() as u64);
+        assert_eq!(std::fs::read(&out).unwrap(), expected);
+
+        // raw deflate data between the 10 byte header and the trailer
+        let raw = temp("inflate.deflate");
+        std::fs::write(&raw, &LINES_GZ[10..LINES_GZ.len() - 8]).unwrap();
+        assert_eq!(inflate_file(&raw, &out).unwrap(), expected.len() as u64);
+        assert_eq!(std::fs::read(&out).unwrap(), expected);
+
+        // truncated and corrupted files
+        std::fs::write(&gz, &LINES_GZ[..LINES_GZ.len() / 2]).unwrap();
+        let err = gunzip_file(&gz, &out).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        let mut corrupt = LINES_GZ.to_vec();
+        let crc = corrupt.len() - 8;
+        corrupt[crc] ^= 1;
+        std::fs::write(&gz, corrupt).unwrap();
+        let err = gunzip_file(&gz, &out).unwrap_err();
+        assert_eq!(
+            err.into_inner().unwrap().to_string(),
+            "inflate: ChecksumMismatch"
+        );
+
+        for path in [gz, out, raw] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_gzip_file() {
+        let (txt, gz, out) = (temp("gzip.txt"), temp("gzip.gz"), temp("gzip.out"));
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 253) as u8).collect();
+        std::fs::write(&txt, &data).unwrap();
+
+        let len = gzip_file(&txt, &gz, 6).unwrap();
+        let compressed = std::fs::read(&gz).unwrap();
+        assert_eq!(compressed.len() as u64, len);
+        assert!(len < data.len() as u64 / 10, "compressed to {len} bytes");
+        assert_eq!(miniz::decompress_to_vec_gzip(&compressed).unwrap(), data);
+
+        // level 0 stores the data
+        let stored = gzip_file(&txt, &gz, 0).unwrap();
+        assert!(stored > data.len() as u64);
+        assert_eq!(gunzip_file(&gz, &out).unwrap(), data.len() as u64);
+        assert_eq!(gunzip_file(&gz, &out).unwrap(), data.len() as u64);
+        assert_eq!(std::fs::read(&out).unwrap(), data);
+
+        let header = miniz::gzip::GzipHeader {
+            filename: Some(b"gzip.txt"),
+            mtime: 1_700_000_000,
+            ..Default::default()
+        };
+        let len = gzip_file_with_header(&txt, &gz, 6, &header).unwrap();
+        let compressed = std::fs::read(&gz).unwrap();
+        assert_eq!(compressed.len() as u64, len);
+        assert_eq!(compressed[4..8], 1_700_000_000u32.to_le_bytes());
+        assert_eq!(&compressed[10..19], b"gzip.txt\0");
+        assert_eq!(gunzip_file(&gz, &out).unwrap(), data.len() as u64);
+
+        // empty input
+        std::fs::write(&txt, []).unwrap();
+        gzip_file(&txt, &gz, 6).unwrap();
+        assert_eq!(gunzip_file(&gz, &out).unwrap(), 0);
+
+        for path in [txt, gz, out] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,140 @@
+// ----------------------------------------------------------------------------
+mod test {
+    use miniz::checksum::crc32;
+    use miniz::decompress_to_vec_gzip;
+    use miniz::gzip::{
+        gzip_compress, gzip_decode, gzip_encode, GzipExtra, GzipHeader, GzipOptions,
+    };
+    use miniz::inflate::Error;
+
+    include!("../assets/gz/lines.rs");
+
+    #[test]
+    fn test_default_header() {
+        let gz = gzip_compress(b"hello", 6, &GzipHeader::default()).unwrap();
+        assert_eq!(gz[..10], [0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff]);
+        assert_eq!(decompress_to_vec_gzip(&gz).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_levels() {
+        let lines = decompress_to_vec_gzip(LINES_GZ).unwrap();
+        let stored = gzip_compress(&lines, 0, &GzipHeader::default()).unwrap();
+        let deflated = gzip_compress(&lines, 6, &GzipHeader::default()).unwrap();
+        // header, one stored block header per 65535 bytes and the trailer
+        let blocks = lines.len().div_ceil(65535);
+        assert_eq!(stored.len(), 10 + lines.len() + 5 * blocks + 8);
+        assert!(deflated.len() * 2 < lines.len());
+        for gz in [stored, deflated] {
+            assert_eq!(decompress_to_vec_gzip(&gz).unwrap(), lines);
+        }
+    }
+
+    #[test]
+    fn test_header_fields() {
+        let extra = [GzipExtra {
+            id: *b"AP",
+            data: b"xy",
+        }];
+        let header = GzipHeader {
+            filename: Some(b"hello.txt"),
+            comment: Some(b"greeting"),
+            mtime: 0x6000_0000,
+            os: 3,
+            extra: &extra,
+            header_crc: true,
+        };
+        let bytes = header.to_bytes().unwrap();
+
+        let mut expected = vec![0x1f, 0x8b, 8, 0x1e, 0, 0, 0, 0x60, 0, 3];
+        expected.extend_from_slice(&[6, 0, b'A', b'P', 2, 0, b'x', b'y']);
+        expected.extend_from_slice(b"hello.txt\0greeting\0");
+        let crc = crc32(0, &expected) as u16;
+        expected.extend_from_slice(&crc.to_le_bytes());
+        assert_eq!(bytes, expected);
+
+        let gz = gzip_compress(b"hello", 6, &header).unwrap();
+        assert!(gz.starts_with(&bytes));
+        assert_eq!(decompress_to_vec_gzip(&gz).unwrap(), b"hello");
+
+        let header = GzipHeader {
+            filename: Some(b"a\0b"),
+            ..Default::default()
+        };
+        assert_eq!(header.to_bytes(), Err(Error::InvalidHeader));
+    }
+
+    #[test]
+    fn test_encode() {
+        // `gzip -k h.txt` of "hello\n" modified at 2020-09-13 12:26:40 UTC
+        const HELLO_GZ: [u8; 32] = [
+            0x1f, 0x8b, 0x08, 0x08, 0x00, 0x10, 0x5e, 0x5f, 0x00, 0x03, 0x68, 0x2e, 0x74, 0x78,
+            0x74, 0x00, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0xe7, 0x02, 0x00, 0x20, 0x30, 0x3a, 0x36,
+            0x06, 0x00, 0x00, 0x00,
+        ];
+        let mut options = GzipOptions {
+            level: 6,
+            header: GzipHeader {
+                filename: Some(b"h.txt"),
+                mtime: 1_600_000_000,
+                os: 3,
+                ..Default::default()
+            },
+        };
+        assert_eq!(gzip_encode(b"hello\n", &options).unwrap(), HELLO_GZ);
+
+        // XFL of `gzip -9` and `gzip -1`
+        for (level, xfl) in [(9, 2), (1, 4), (0, 0)] {
+            options.level = level;
+            let gz = gzip_encode(b"hello\n", &options).unwrap();
+            assert_eq!(gz[8], xfl, "level {level}");
+            assert_eq!(gzip_decode(&gz).unwrap(), b"hello\n");
+        }
+
+        options.header.header_crc = true;
+        let gz = gzip_encode(b"hello\n", &options).unwrap();
+        assert_eq!(gz[3], 0x0a);
+        let crc = crc32(0, &gz[..16]) as u16;
+        assert_eq!(gz[16..18], crc.to_le_bytes());
+        assert_eq!(gzip_decode(&gz).unwrap(), b"hello\n");
+
+        let gz = gzip_encode(b"", &GzipOptions::default()).unwrap();
+        assert_eq!(gzip_decode(&gz).unwrap(), b"");
+    }
+
+    #[test]
+    fn test_decode() {
+        let lines = gzip_decode(LINES_GZ).unwrap();
+        assert_eq!(lines, decompress_to_vec_gzip(LINES_GZ).unwrap());
+
+        let extra = [GzipExtra {
+            id: *b"AP",
+            data: b"xy",
+        }];
+        let header = GzipHeader {
+            filename: Some(b"lines.txt"),
+            comment: Some(b"test data"),
+            extra: &extra,
+            header_crc: true,
+            ..Default::default()
+        };
+        let gz = gzip_compress(&lines, 9, &header).unwrap();
+        assert_eq!(gzip_decode(&gz).unwrap(), lines);
+
+        let header_len = header.to_bytes().unwrap().len();
+        let corrupt = |pos: usize| {
+            let mut gz = gz.clone();
+            gz[pos] ^= 1;
+            gzip_decode(&gz).err()
+        };
+        let mismatch = Some(miniz::Error::Inflate(Error::ChecksumMismatch));
+        assert_eq!(corrupt(header_len - 1), mismatch, "header CRC");
+        assert_eq!(corrupt(header_len - 3), mismatch, "comment");
+        assert_eq!(corrupt(gz.len() - 5), mismatch, "CRC-32");
+        assert_eq!(corrupt(gz.len() - 1), mismatch, "ISIZE");
+        assert_eq!(
+            gzip_decode(&gz[..header_len - 1]).err(),
+            Some(miniz::Error::Inflate(Error::Underflow))
+        );
+    }
+}
@@ -0,0 +1,257 @@
+// ----------------------------------------------------------------------------
+mod test {
+    use miniz::http::{ContentEncoding, Decoder, Encoder};
+    use miniz::stream::{FlushCompress, FlushDecompress, Status};
+    use miniz::{decompress_to_vec_gzip, inflate, Error};
+
+    // "hello hello hello\n" as written by gzip with the file name "hello.txt"
+    const HELLO_GZIP: [u8; 39] = [
+        0x1f, 0x8b, 0x08, 0x08, 0x00, 0x00, 0x00, 0x00, 0x02, 0xff, 0x68, 0x65, 0x6c, 0x6c, 0x6f,
+        0x2e, 0x74, 0x78, 0x74, 0x00, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x90, 0x5c,
+        0x00, 0x3b, 0x7c, 0x8a, 0xdf, 0x12, 0x00, 0x00, 0x00,
+    ];
+
+    // the same payload as raw deflate data
+    const HELLO_RAW: [u8; 11] = [
+        0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x90, 0x5c, 0x00,
+    ];
+
+    fn encode_all(encoder: &mut Encoder, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut buf = [0u8; 100];
+        let mut input = data;
+        loop {
+            let before = encoder.total_out() as usize;
+            let status = encoder.run(input, &mut buf, FlushCompress::Finish);
+            out.extend_from_slice(&buf[..encoder.total_out() as usize - before]);
+            input = &[];
+            if status == Status::StreamEnd {
+                return out;
+            }
+        }
+    }
+
+    fn decode_all(decoder: &mut Decoder, data: &[u8]) -> miniz::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        let mut buf = [0u8; 100];
+        for chunk in data.chunks(7) {
+            let before = decoder.total_out() as usize;
+            decoder.run(chunk, &mut buf, FlushDecompress::None)?;
+            out.extend_from_slice(&buf[..decoder.total_out() as usize - before]);
+        }
+        loop {
+            let before = decoder.total_out() as usize;
+            let status = decoder.run(&[], &mut buf, FlushDecompress::Finish)?;
+            out.extend_from_slice(&buf[..decoder.total_out() as usize - before]);
+            if status == Status::StreamEnd {
+                return Ok(out);
+            }
+        }
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(
+            ContentEncoding::from_name("gzip"),
+            Some(ContentEncoding::Gzip)
+        );
+        assert_eq!(
+            ContentEncoding::from_name(" X-GZip "),
+            Some(ContentEncoding::Gzip)
+        );
+        assert_eq!(
+            ContentEncoding::from_name("Deflate"),
+            Some(ContentEncoding::Deflate)
+        );
+        assert_eq!(
+            ContentEncoding::from_name("identity"),
+            Some(ContentEncoding::Identity)
+        );
+        assert_eq!(ContentEncoding::from_name("br"), None);
+        assert_eq!(ContentEncoding::Gzip.name(), "gzip");
+    }
+
+    #[test]
+    fn test_decode() {
+        let mut decoder = Decoder::new(ContentEncoding::Gzip);
+        assert_eq!(
+            decode_all(&mut decoder, &HELLO_GZIP),
+            Ok(b"hello hello hello\n".to_vec())
+        );
+        assert_eq!(decoder.total_in(), HELLO_GZIP.len() as u64);
+
+        // servers declaring "deflate" often send raw deflate data
+        let mut decoder = Decoder::new(ContentEncoding::Deflate);
+        assert_eq!(
+            decode_all(&mut decoder, &HELLO_RAW),
+            Ok(b"hello hello hello\n".to_vec())
+        );
+
+        let mut decoder = Decoder::new(ContentEncoding::Identity);
+        assert_eq!(decode_all(&mut decoder, &HELLO_RAW), Ok(HELLO_RAW.to_vec()));
+
+        let mut corrupt = HELLO_GZIP;
+        corrupt[32] ^= 1;
+        assert_eq!(
+            decompress_to_vec_gzip(&corrupt),
+            Err(Error::Inflate(inflate::Error::ChecksumMismatch))
+        );
+        assert_eq!(
+            decompress_to_vec_gzip(&HELLO_GZIP[..15]),
+            Err(Error::Inflate(inflate::Error::Underflow))
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let data: Vec<u8> = (0..70000u32).map(|i| (i % 251) as u8).collect();
+        for encoding in [
+            ContentEncoding::Identity,
+            ContentEncoding::Deflate,
+            ContentEncoding::Gzip,
+        ] {
+            let mut encoder = Encoder::new(encoding, 6);
+            let encoded = encode_all(&mut encoder, &data);
+            assert_eq!(encoder.total_in(), data.len() as u64);
+            if encoding != ContentEncoding::Identity {
+                assert!(encoded.len() < data.len() / 10, "{encoding:?} compresses");
+            }
+
+            let mut decoder = Decoder::new(encoding);
+            assert_eq!(decode_all(&mut decoder, &encoded), Ok(data.clone()));
+        }
+    }
+
+    #[test]
+    fn test_decode_incremental() {
+        use miniz::http::Limits;
+
+        let data: Vec<u8> = (0..70000u32).map(|i| (i % 251) as u8).collect();
+        for encoding in [ContentEncoding::Deflate, ContentEncoding::Gzip] {
+            let encoded = encode_all(&mut Encoder::new(encoding, 6), &data);
+
+            // output arrives before the end of the body, headers may be split across calls
+            let mut decoder = Decoder::new(encoding);
+            let mut out = vec![0u8; data.len()];
+            for byte in encoded[..encoded.len() - 1].chunks(1) {
+                let produced = decoder.total_out() as usize;
+                decoder
+                    .run(byte, &mut out[produced..], FlushDecompress::None)
+                    .unwrap();
+            }
+            assert!(decoder.total_out() > data.len() as u64 / 2, "{encoding:?}");
+
+            // data behind the body is not consumed
+            let produced = decoder.total_out() as usize;
+            let last = [encoded[encoded.len() - 1], b'x'];
+            let status = decoder.run(&last, &mut out[produced..], FlushDecompress::Finish);
+            assert_eq!(status, Ok(Status::StreamEnd), "{encoding:?}");
+            assert_eq!(decoder.total_in(), encoded.len() as u64);
+            assert_eq!(out, data);
+
+            // limits are checked while decoding
+            let limits = Limits {
+                max_output: 1000,
+                max_ratio: None,
+            };
+            let mut decoder = Decoder::with_limits(encoding, limits);
+            let mut buf = vec![0u8; data.len()];
+            assert_eq!(
+                decoder.run(&encoded, &mut buf, FlushDecompress::None),
+                Err(Error::Inflate(inflate::Error::LimitExceeded))
+            );
+            let limits = Limits {
+                max_output: usize::MAX,
+                max_ratio: Some(10),
+            };
+            let mut decoder = Decoder::with_limits(encoding, limits);
+            assert_eq!(
+                decoder.run(&encoded, &mut buf, FlushDecompress::Finish),
+                Err(Error::Inflate(inflate::Error::LimitExceeded))
+            );
+        }
+
+        // the stream ends within the body
+        let mut decoder = Decoder::new(ContentEncoding::Gzip);
+        let mut buf = [0u8; 100];
+        assert_eq!(
+            decoder.run(&HELLO_GZIP[..30], &mut buf, FlushDecompress::Finish),
+            Err(Error::Inflate(inflate::Error::Underflow))
+        );
+        let mut corrupt = HELLO_GZIP;
+        corrupt[32] ^= 1;
+        let mut decoder = Decoder::new(ContentEncoding::Gzip);
+        assert_eq!(
+            decode_all(&mut decoder, &corrupt),
+            Err(Error::Inflate(inflate::Error::ChecksumMismatch))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_limited_reader() {
+        use miniz::http::{LimitedReader, Limits};
+        use std::io::Read;
+
+        let read = |encoding, data: &[u8], max_output, max_ratio| {
+            let limits = Limits {
+                max_output,
+                max_ratio,
+            };
+            let mut out = Vec::new();
+            LimitedReader::new(data, encoding, limits)
+                .read_to_end(&mut out)
+                .map(|_| out)
+                .map_err(|err| {
+                    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+                    err.into_inner().unwrap().downcast::<Error>().unwrap()
+                })
+        };
+
+        let hello = b"hello hello hello\n".to_vec();
+        let gzip = ContentEncoding::Gzip;
+        assert_eq!(read(gzip, &HELLO_GZIP, 18, None), Ok(hello.clone()));
+        assert_eq!(
+            read(gzip, &HELLO_GZIP, 17, None),
+            Err(Box::new(Error::Inflate(inflate::Error::LimitExceeded)))
+        );
+
+        // 11 encoded bytes may expand to at most 11 bytes with a ratio of 1
+        let deflate = ContentEncoding::Deflate;
+        assert_eq!(read(deflate, &HELLO_RAW, 100, Some(2)), Ok(hello));
+        assert_eq!(
+            read(deflate, &HELLO_RAW, 100, Some(1)),
+            Err(Box::new(Error::Inflate(inflate::Error::LimitExceeded)))
+        );
+
+        // 114 bytes expanding to 100000 zeros
+        let mut bomb = vec![
+            0xed, 0xc1, 0x31, 0x01, 0x00, 0x00, 0x00, 0xc2, 0xa0, 0xf5, 0x4f, 0x6d, 0x0d, 0x0f,
+            0xa0,
+        ];
+        bomb.resize(111, 0);
+        bomb.extend_from_slice(&[0x80, 0x57, 0x03]);
+        assert_eq!(read(deflate, &bomb, 100000, None), Ok(vec![0; 100000]));
+        assert!(read(deflate, &bomb, 1 << 16, None).is_err());
+        assert!(read(deflate, &bomb, 1 << 20, Some(100)).is_err());
+
+        // the body is read as output is requested, not up front
+        struct Failing;
+        impl Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::Other.into())
+            }
+        }
+        let limits = Limits {
+            max_output: 1 << 20,
+            max_ratio: Some(100),
+        };
+        let mut reader = LimitedReader::new(bomb.chain(Failing), deflate, limits);
+        let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        let mut reader = LimitedReader::new(HELLO_GZIP.chain(Failing), gzip, limits);
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out).unwrap();
+        assert_eq!(out, b"hello hello hello\n");
+    }
+}
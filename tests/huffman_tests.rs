@@ -0,0 +1,67 @@
+// ----------------------------------------------------------------------------
+mod test {
+    use miniz::huffman::{canonical_codes, HuffmanDecoder, MAX_CODE_BITS};
+    use miniz::inflate::{Error, Tree};
+
+    // reverses the MSB first `code` of `len` bits into stream order
+    fn stream_bits(code: u16, len: u8) -> u32 {
+        (code.reverse_bits() >> (16 - len)) as u32
+    }
+
+    #[test]
+    fn test_canonical_codes() {
+        // example of RFC 1951, section 3.2.2: ABCDEFGH
+        let lengths = [3, 3, 3, 3, 3, 2, 4, 4];
+        let codes = canonical_codes(&lengths).unwrap();
+        assert_eq!(
+            codes,
+            [0b010, 0b011, 0b100, 0b101, 0b110, 0b00, 0b1110, 0b1111]
+        );
+
+        let decoder = HuffmanDecoder::from_lengths(&lengths).unwrap();
+        for (symbol, (code, len)) in codes.iter().zip(lengths).enumerate() {
+            // bits following the code do not matter
+            let bits = stream_bits(*code, len) | 0x5a00;
+            assert_eq!(decoder.decode(bits), Some((symbol as u16, len)));
+        }
+    }
+
+    #[test]
+    fn test_long_codes() {
+        // 1, 2, .. 14, 15, 15 bits, the longest ones go through the second-level tables
+        let lengths: Vec<u8> = (1..=15).chain([15]).collect();
+        let decoder = HuffmanDecoder::from_lengths(&lengths).unwrap();
+        let codes = canonical_codes(&lengths).unwrap();
+        assert_eq!(codes[15], 0x7fff);
+        for (symbol, (code, len)) in codes.iter().zip(&lengths).enumerate() {
+            let bits = stream_bits(*code, *len);
+            assert_eq!(decoder.decode(bits), Some((symbol as u16, *len)));
+        }
+    }
+
+    #[test]
+    fn test_invalid_lengths() {
+        assert_eq!(
+            HuffmanDecoder::from_lengths(&[1, 1, 1]).err(),
+            Some(Error::OverSubscribedTree(Tree::LiteralLength))
+        );
+        assert_eq!(
+            HuffmanDecoder::from_lengths(&[1, 2]).err(),
+            Some(Error::UnderSubscribedTree(Tree::LiteralLength))
+        );
+        assert_eq!(
+            HuffmanDecoder::from_lengths(&[MAX_CODE_BITS + 1, 1]).err(),
+            Some(Error::InvalidCodeLength)
+        );
+        assert_eq!(
+            canonical_codes(&[1; 289]).err(),
+            Some(Error::InvalidCodeLength)
+        );
+
+        // a single one-bit code is accepted, its unused code decodes to nothing
+        let decoder = HuffmanDecoder::from_lengths(&[0, 1]).unwrap();
+        assert_eq!(decoder.decode(0), Some((1, 1)));
+        assert_eq!(decoder.decode(1), None);
+        assert!(HuffmanDecoder::from_lengths(&[]).is_ok());
+    }
+}
@@ -0,0 +1,50 @@
+#![cfg(feature = "image")]
+
+// ----------------------------------------------------------------------------
+mod test {
+    use image::{ColorType, DynamicImage, ExtendedColorType, ImageDecoder};
+    use miniz::image::PngImageDecoder;
+    use miniz::png_read;
+
+    include!("../assets/png/basn2c08.rs");
+
+    const BASN0G01_PNG: &[u8] = include_bytes!("../assets/png/basn0g01.png");
+    const RGB16_PNG: &[u8] = include_bytes!("../assets/png/rgb16_4x3.png");
+
+    #[test]
+    fn test_truecolor() {
+        let decoder = PngImageDecoder::new(BASN2C08_PNG).unwrap();
+        assert_eq!(decoder.dimensions(), (32, 32));
+        assert_eq!(decoder.color_type(), ColorType::Rgb8);
+
+        let mut buf = vec![0u8; decoder.total_bytes() as usize];
+        decoder.read_image(&mut buf).unwrap();
+        for (row, expected) in buf.chunks_exact(96).zip(BASN2C08_IMG.chunks_exact(97)) {
+            assert_eq!(row, &expected[1..]);
+        }
+    }
+
+    #[test]
+    fn test_expanded() {
+        let decoder = PngImageDecoder::new(BASN0G01_PNG).unwrap();
+        assert_eq!(decoder.color_type(), ColorType::Rgba8);
+        assert_eq!(decoder.original_color_type(), ExtendedColorType::L1);
+
+        let image = DynamicImage::from_decoder(decoder).unwrap();
+        assert_eq!(image.width(), 32);
+        assert_eq!(image.as_bytes().len(), 32 * 32 * 4);
+    }
+
+    #[test]
+    fn test_16bit() {
+        assert_eq!(
+            PngImageDecoder::new(RGB16_PNG).err(),
+            Some(png_read::Error::UnsupportedFormat)
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(PngImageDecoder::new(&BASN0G01_PNG[..20]).is_err());
+    }
+}
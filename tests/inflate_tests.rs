@@ -1,7 +1,87 @@
 // ----------------------------------------------------------------------------
 mod test {
 
-    use miniz::inflate::{inflate, Error};
+    use miniz::checksum::adler32;
+    use miniz::inflate::{
+        block_huffman_dumps, huffman_dump, inflate, inflate_to_sink, inflate_to_vec,
+        inflate_verbose, inflate_with_options, inflate_zlib, inflate_zlib_with_options,
+        DecodeProfile, Error, HuffmanCode, HuffmanPolicy, InflateOptions, Progress, Tree,
+        VecLimits,
+    };
+
+    // counts the heap allocations of the current thread
+    struct CountingAlloc;
+
+    std::thread_local! {
+        static ALLOCATIONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+
+    unsafe impl std::alloc::GlobalAlloc for CountingAlloc {
+        unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+            ALLOCATIONS.with(|count| count.set(count.get() + 1));
+            unsafe { std::alloc::System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+            unsafe { std::alloc::System.dealloc(ptr, layout) }
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAlloc = CountingAlloc;
+
+    // LSB-first bit writer for hand-made fixed Huffman streams
+    struct Bits(Vec<u8>, usize);
+
+    impl Bits {
+        fn put(&mut self, value: u32, count: usize) {
+            for i in 0..count {
+                if self.1.is_multiple_of(8) {
+                    self.0.push(0);
+                }
+                *self.0.last_mut().unwrap() |= (((value >> i) & 1) as u8) << (self.1 % 8);
+                self.1 += 1;
+            }
+        }
+
+        // Huffman codes are stored MSB first
+        fn put_code(&mut self, code: u32, len: usize) {
+            for i in (0..len).rev() {
+                self.put((code >> i) & 1, 1);
+            }
+        }
+
+        fn put_literal(&mut self, value: u8) {
+            match value {
+                0..=143 => self.put_code(0x30 + value as u32, 8),
+                _ => self.put_code(0x190 + (value as u32 - 144), 9),
+            }
+        }
+    }
+
+    // zlib stream with the given CINFO: 300 literals followed by a copy of 3 bytes at distance 300
+    fn far_reference_stream(cinfo: u8) -> (Vec<u8>, Vec<u8>) {
+        let mut out: Vec<u8> = (0..300).map(|i| i as u8).collect();
+        out.extend_from_within(0..3);
+
+        let mut bits = Bits(Vec::new(), 0);
+        bits.put(1, 1); // BFINAL
+        bits.put(1, 2); // fixed Huffman
+        for b in &out[..300] {
+            bits.put_literal(*b);
+        }
+        bits.put_code(1, 7); // length code 257: 3 bytes
+        bits.put_code(16, 5); // distance code 16: 257 + 7 extra bits
+        bits.put(300 - 257, 7);
+        bits.put_code(0, 7); // end of block
+
+        let cmf = (cinfo << 4) | 8;
+        let flg = 31 - ((cmf as u16 * 256) % 31) as u8;
+        let mut zlib = vec![cmf, flg];
+        zlib.extend_from_slice(&bits.0);
+        zlib.extend_from_slice(&adler32(1, &out).to_be_bytes());
+        (zlib, out)
+    }
 
     #[test]
     fn test_coverage() {
@@ -42,7 +122,7 @@ mod test {
         ];
         assert_eq!(
             inflate(&mut out, &inp),
-            Err(Error::OverSubscribedTree),
+            Err(Error::OverSubscribedTree(Tree::LiteralLength)),
             "oversubscribed 2nd tree"
         );
 
@@ -77,7 +157,10 @@ mod test {
 
         // invalid code lengths set
         let inp = [0x04, 0x00, 0xfe, 0xff];
-        assert_eq!(inflate(&mut out, &inp), Err(Error::UnderSubscribedTree));
+        assert_eq!(
+            inflate(&mut out, &inp),
+            Err(Error::UnderSubscribedTree(Tree::CodeLength))
+        );
 
         // invalid bit length repeat
         let inp = [0x04, 0x00, 0x24, 0x49, 0x00];
@@ -105,4 +188,1046 @@ mod test {
             b"------------------------\n--- THIS IS THIS TEST --\n------------------------\n"
         );
     }
+
+    #[test]
+    fn test_zlib_window() {
+        let mut out = [0u8; 512];
+
+        let (zlib, expected) = far_reference_stream(7);
+        assert_eq!(
+            inflate_zlib(&mut out, &zlib),
+            Ok(expected.len()),
+            "32k window"
+        );
+        assert_eq!(&out[..expected.len()], &expected[..]);
+
+        let (zlib, expected) = far_reference_stream(0);
+        assert_eq!(
+            inflate_zlib(&mut out, &zlib),
+            Err(Error::InvalidDistance {
+                distance: 300,
+                available: 256
+            }),
+            "distance beyond 256 byte window"
+        );
+
+        let lenient = InflateOptions {
+            enforce_zlib_window: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            inflate_zlib_with_options(&mut out, &zlib, &lenient),
+            Ok(expected.len()),
+            "lenient window"
+        );
+    }
+
+    #[test]
+    fn test_zlib_dictionary() {
+        // python: zlib.compressobj(9, zdict=DICT), the data only refers to the dictionary
+        const DICT: &[u8] = b"the quick brown fox jumps over the lazy dog";
+        const ZLIB: [u8; 18] = [
+            0x78, 0xf9, 0x61, 0x3c, 0x0f, 0xfa, 0x43, 0x66, 0xa3, 0xab, 0x41, 0x33, 0x02, 0x00,
+            0x5d, 0x66, 0x0f, 0xfa,
+        ];
+        let expected = b"the lazy dog jumps over the quick brown fox";
+        let mut out = [0u8; 64];
+
+        let required = Err(Error::DictionaryRequired(adler32(1, DICT)));
+        assert_eq!(inflate_zlib(&mut out, &ZLIB), required);
+        let wrong = InflateOptions {
+            dictionary: Some(b"the quick brown fox"),
+            ..Default::default()
+        };
+        assert_eq!(inflate_zlib_with_options(&mut out, &ZLIB, &wrong), required);
+
+        let options = InflateOptions {
+            dictionary: Some(DICT),
+            ..Default::default()
+        };
+        assert_eq!(
+            inflate_zlib_with_options(&mut out, &ZLIB, &options),
+            Ok(expected.len())
+        );
+        assert_eq!(&out[..expected.len()], expected);
+
+        let mut corrupt = ZLIB;
+        corrupt[17] ^= 1;
+        assert_eq!(
+            inflate_zlib_with_options(&mut out, &corrupt, &options),
+            Err(Error::ChecksumMismatch)
+        );
+        assert_eq!(
+            inflate_zlib_with_options(&mut out, &ZLIB[..5], &options),
+            Err(Error::Underflow)
+        );
+    }
+
+    #[test]
+    fn test_huffman_policy() {
+        let mut out = [0u8; 1024];
+        let pedantic = InflateOptions {
+            huffman_policy: HuffmanPolicy::Pedantic,
+            ..Default::default()
+        };
+
+        // dynamic block with a single one-bit code in the distance tree
+        let inp = [
+            0xed, 0xc0, 0x1, 0x1, 0x0, 0x0, 0x0, 0x40, 0xa0, 0xfb, 0x66, 0x1b, 0x42, 0x2c, 0x4f,
+        ];
+        assert_eq!(inflate(&mut out, &inp), Ok(516), "zlib compatible");
+        assert_eq!(
+            inflate_with_options(&mut out, &inp, &pedantic),
+            Err(Error::UnderSubscribedTree(Tree::Distance)),
+            "pedantic"
+        );
+    }
+
+    #[test]
+    fn test_huffman_dump() {
+        let dump = huffman_dump(&[2, 1, 3, 3], Tree::Distance, HuffmanPolicy::Pedantic).unwrap();
+        assert_eq!(
+            dump.codes[..2],
+            [
+                HuffmanCode {
+                    symbol: 0,
+                    code: 0b10,
+                    len: 2
+                },
+                HuffmanCode {
+                    symbol: 1,
+                    code: 0b0,
+                    len: 1
+                },
+            ]
+        );
+        assert!(dump.is_complete());
+        assert_eq!(
+            dump.to_string(),
+            "Distance tree: 4 codes, max length 3\n\
+             \x20   0 -> 10 (2)\n\
+             \x20   1 -> 0 (1)\n\
+             \x20   2 -> 110 (3)\n\
+             \x20   3 -> 111 (3)\n\
+             primary table: 512/512 entries used\n\
+             secondary tables: 0 (0/0 entries used)"
+        );
+
+        // codes longer than the first table need a second-level table
+        let lengths = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 10];
+        let dump = huffman_dump(&lengths, Tree::LiteralLength, HuffmanPolicy::Pedantic).unwrap();
+        assert_eq!(dump.codes[10].code, 0b1111111111);
+        assert_eq!(
+            (
+                dump.secondary_tables,
+                dump.secondary_used,
+                dump.secondary_size
+            ),
+            (1, 2, 2)
+        );
+
+        assert_eq!(
+            huffman_dump(&[1, 1, 1], Tree::CodeLength, HuffmanPolicy::ZlibCompatible),
+            Err(Error::OverSubscribedTree(Tree::CodeLength))
+        );
+    }
+
+    #[test]
+    fn test_block_huffman_dumps() {
+        // dynamic block with a single one-bit code in the distance tree
+        let inp = [
+            0xed, 0xc0, 0x1, 0x1, 0x0, 0x0, 0x0, 0x40, 0xa0, 0xfb, 0x66, 0x1b, 0x42, 0x2c, 0x4f,
+        ];
+        let dumps = block_huffman_dumps(&inp, HuffmanPolicy::ZlibCompatible).unwrap();
+        let trees = dumps.iter().map(|d| d.tree).collect::<Vec<_>>();
+        assert_eq!(
+            trees,
+            [Tree::CodeLength, Tree::LiteralLength, Tree::Distance]
+        );
+        assert_eq!(dumps[2].codes.len(), 1);
+        assert!(!dumps[2].is_complete(), "single distance code");
+        assert_eq!(
+            block_huffman_dumps(&inp, HuffmanPolicy::Pedantic),
+            Err(Error::UnderSubscribedTree(Tree::Distance))
+        );
+
+        // fixed block
+        let dumps = block_huffman_dumps(&[0x03, 0x00], HuffmanPolicy::Pedantic).unwrap();
+        assert_eq!(dumps[0].codes.len(), 288);
+        assert_eq!(
+            dumps[0].codes[256],
+            HuffmanCode {
+                symbol: 256,
+                code: 0,
+                len: 7
+            }
+        );
+        assert_eq!(dumps[1].max_len, 5);
+
+        // stored block
+        let dumps = block_huffman_dumps(
+            &[0x01, 0x00, 0x00, 0xff, 0xff],
+            HuffmanPolicy::ZlibCompatible,
+        );
+        assert_eq!(dumps, Ok(Vec::new()));
+    }
+
+    #[test]
+    fn test_progress() {
+        let mut out = [0u8; 33025];
+        let inp = [
+            0xed, 0xc0, 0x81, 0x0, 0x0, 0x0, 0x0, 0x80, 0xa0, 0xfd, 0xa9, 0x17, 0xa9, 0x0, 0x0,
+            0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+            0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x6,
+        ];
+
+        let reports = std::cell::RefCell::new(Vec::new());
+        let callback = |bytes_in, bytes_out| reports.borrow_mut().push((bytes_in, bytes_out));
+        let options = InflateOptions {
+            progress: Some(Progress {
+                interval: 8192,
+                callback: &callback,
+            }),
+            ..Default::default()
+        };
+        assert_eq!(inflate_with_options(&mut out, &inp, &options), Ok(33025));
+
+        let reports = reports.into_inner();
+        assert_eq!(reports.len(), 5, "4 intervals and completion");
+        assert!(reports[..4].windows(2).all(|w| w[0].1 + 8192 <= w[1].1));
+        assert_eq!(reports.last(), Some(&(inp.len(), 33025)));
+    }
+
+    #[test]
+    fn test_cancel() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let mut out = [0u8; 33025];
+        let inp = [
+            0xed, 0xc0, 0x81, 0x0, 0x0, 0x0, 0x0, 0x80, 0xa0, 0xfd, 0xa9, 0x17, 0xa9, 0x0, 0x0,
+            0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+            0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x6,
+        ];
+
+        let cancel = AtomicBool::new(false);
+        let options = InflateOptions {
+            cancel: Some(&cancel),
+            ..Default::default()
+        };
+        assert_eq!(inflate_with_options(&mut out, &inp, &options), Ok(33025));
+
+        cancel.store(true, Ordering::Relaxed);
+        assert_eq!(
+            inflate_with_options(&mut out, &inp, &options),
+            Err(Error::Cancelled),
+            "cancelled up front"
+        );
+
+        // cancel from within the decode loop
+        let cancel = AtomicBool::new(false);
+        let callback = |_, _| cancel.store(true, Ordering::Relaxed);
+        let options = InflateOptions {
+            progress: Some(Progress {
+                interval: 1024,
+                callback: &callback,
+            }),
+            cancel: Some(&cancel),
+            ..Default::default()
+        };
+        assert_eq!(
+            inflate_with_options(&mut out, &inp, &options),
+            Err(Error::Cancelled),
+            "cancelled while decoding"
+        );
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_metrics() {
+        use miniz::inflate::InflateMetrics;
+
+        let mut out = [0u8; 4096];
+        let metrics = std::cell::Cell::new(InflateMetrics::default());
+        let options = InflateOptions {
+            metrics: Some(&metrics),
+            ..Default::default()
+        };
+
+        let inp = [0x01, 0x01, 0x00, 0xfe, 0xff, 0x66];
+        assert_eq!(inflate_with_options(&mut out, &inp, &options), Ok(1));
+        let inp = [0x2b, 0x1f, 0x05, 0x40, 0x0c, 0x00];
+        assert_eq!(inflate_with_options(&mut out, &inp, &options), Ok(262));
+
+        let metrics = metrics.get();
+        assert_eq!(metrics.stored_blocks, 1);
+        assert_eq!(metrics.fixed_blocks, 1);
+        assert_eq!(metrics.dynamic_blocks, 0);
+        assert_eq!(metrics.table_rebuilds, 2);
+        assert_eq!(metrics.bytes_in, 12);
+        assert_eq!(metrics.bytes_out, 263);
+        assert_eq!(
+            metrics.slow_path_iterations, 4,
+            "literal, 2 matches, end of block"
+        );
+        assert_eq!((metrics.literals, metrics.matches), (1, 2));
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_fixed_tables_reused() {
+        use miniz::inflate::InflateMetrics;
+
+        // three fixed blocks, each followed by an empty fixed block of a partial flush
+        let inp = [
+            0xca, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0x00, 0x08, 0xa0, 0xb4, 0xcc, 0x8a, 0xd4, 0x14,
+            0x05, 0x80, 0x00, 0x4a, 0xca, 0xc9, 0x4f, 0xce, 0x2e, 0x06, 0x08, 0x30, 0x00,
+        ];
+        let mut out = [0u8; 64];
+        let metrics = std::cell::Cell::new(InflateMetrics::default());
+        let options = InflateOptions {
+            metrics: Some(&metrics),
+            ..Default::default()
+        };
+        assert_eq!(inflate_with_options(&mut out, &inp, &options), Ok(18));
+        assert_eq!(&out[..18], b"hello fixed blocks");
+
+        let metrics = metrics.get();
+        assert!(metrics.fixed_blocks >= 6, "{metrics:?}");
+        assert_eq!(metrics.table_rebuilds, 2, "fixed tables built once");
+    }
+
+    #[test]
+    fn test_stored_after_huffman() {
+        // empty fixed block followed by a stored block: the stored header starts on the byte
+        // boundary after bits that were already buffered by the bit reader
+        let mut out = [0u8; 16];
+        let inp = [0x02, 0x04, 0x03, 0x00, 0xfc, 0xff, b'a', b'b', b'c'];
+        assert_eq!(inflate(&mut out, &inp), Ok(3));
+        assert_eq!(&out[..3], b"abc");
+
+        // truncated stored data
+        assert_eq!(inflate(&mut out, &inp[..8]), Err(Error::Underflow));
+    }
+
+    #[test]
+    fn test_strict_end_of_input() {
+        let mut out = [0u8; 16];
+        let strict = InflateOptions {
+            strict_end_of_input: true,
+            ..Default::default()
+        };
+
+        // fixed block with "a", the last 2 bits of the end-of-block code are cut off and read
+        // as zeros unless the reader is strict
+        let inp = [0x4b, 0x04, 0x00];
+        assert_eq!(inflate_with_options(&mut out, &inp, &strict), Ok(1));
+        assert_eq!(inflate(&mut out, &inp[..2]), Ok(1));
+        assert_eq!(
+            inflate_with_options(&mut out, &inp[..2], &strict),
+            Err(Error::Underflow)
+        );
+        assert_eq!(
+            inflate_with_options(&mut out, &inp[..1], &strict),
+            Err(Error::Underflow)
+        );
+    }
+
+    #[test]
+    fn test_profiles() {
+        let mut out = [0u8; 16];
+        let strict = InflateOptions::profile(DecodeProfile::Strict);
+        let lenient = InflateOptions::profile(DecodeProfile::Lenient);
+
+        // "a" and a copy of 3 bytes at distance 2, one byte before the start of the output
+        let mut bits = Bits(Vec::new(), 0);
+        bits.put(1, 1);
+        bits.put(1, 2);
+        bits.put_literal(b'a');
+        bits.put_code(1, 7);
+        bits.put_code(1, 5);
+        bits.put_code(0, 7);
+        let too_far = Err(Error::InvalidDistance {
+            distance: 2,
+            available: 1,
+        });
+        assert_eq!(inflate(&mut out, &bits.0), too_far);
+        assert_eq!(inflate_with_options(&mut out, &bits.0, &strict), too_far);
+        assert_eq!(inflate_with_options(&mut out, &bits.0, &lenient), Ok(4));
+        assert_eq!(out[..4], *b"a\0a\0");
+
+        // copies entirely before the start, and beyond the declared zlib window
+        let mut bits = Bits(Vec::new(), 0);
+        bits.put(1, 1);
+        bits.put(1, 2);
+        bits.put_literal(b'a');
+        bits.put_code(1, 7);
+        bits.put_code(2, 5); // distance code 2: 3 bytes
+        bits.put_code(0, 7);
+        assert_eq!(inflate_with_options(&mut out, &bits.0, &lenient), Ok(4));
+        assert_eq!(out[..4], *b"a\0\0a");
+        let (zlib, expected) = far_reference_stream(0);
+        let mut out = [0u8; 512];
+        let len = inflate_zlib_with_options(&mut out, &zlib, &lenient);
+        assert_eq!(len, Ok(expected.len()));
+        assert!(inflate_zlib_with_options(&mut out, &zlib, &strict).is_err());
+
+        // truncated input fails only when strict
+        let inp = [0x4b, 0x04];
+        assert_eq!(inflate_with_options(&mut out, &inp, &lenient), Ok(1));
+        let truncated = inflate_with_options(&mut out, &inp, &strict);
+        assert_eq!(truncated, Err(Error::Underflow));
+        assert_eq!(strict.huffman_policy, HuffmanPolicy::Pedantic);
+    }
+
+    #[test]
+    fn test_long_codes_at_end_of_input() {
+        // geometric byte distribution for code lengths up to the 15-bit maximum
+        let mut x = 0x9e37_79b9_7f4a_7c15u64;
+        let data: Vec<u8> = (0..20_000)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                x.trailing_zeros() as u8
+            })
+            .collect();
+        let zlib = miniz::compress_to_vec(&data, 9);
+        let raw = &zlib[2..zlib.len() - 4];
+        assert!(raw.len() < data.len() / 2, "Huffman coded");
+        let strict = InflateOptions {
+            strict_end_of_input: true,
+            ..Default::default()
+        };
+
+        let mut out = vec![0u8; data.len()];
+        let len = inflate_with_options(&mut out, raw, &strict);
+        assert_eq!(len, Ok(data.len()));
+        assert_eq!(out, data);
+
+        // every cut within the last 16 bytes runs into the end of input
+        for end in raw.len() - 16..raw.len() {
+            assert!(inflate_with_options(&mut out, &raw[..end], &strict).is_err());
+        }
+    }
+
+    #[test]
+    fn test_fast_loop() {
+        // periodic data for every short distance and text-like data for long distances
+        let mut data = Vec::new();
+        for period in 1..=20 {
+            let pattern: Vec<u8> = (0..period).map(|i| (i * 37 + period) as u8).collect();
+            data.extend(pattern.iter().cycle().take(1000 + period));
+        }
+        data.extend(
+            (0..50_000u64).map(|i| b"lorem ipsum dolor sit amet "[((i * i) >> 9) as usize % 27]),
+        );
+        let zlib = miniz::compress_to_vec(&data, 6);
+        let raw = &zlib[2..zlib.len() - 4];
+
+        // without spare room the last matches are decoded by the slow loop
+        for spare in [0, 1, 7, 8, 300] {
+            let mut out = vec![0xa5u8; data.len() + spare];
+            assert_eq!(inflate(&mut out, raw), Ok(data.len()));
+            assert_eq!(out[..data.len()], data[..]);
+        }
+
+        // a match reaching before the start of output, followed by enough input for the fast loop
+        let mut bits = Bits(Vec::new(), 0);
+        bits.put(1, 1); // BFINAL
+        bits.put(1, 2); // fixed Huffman
+        bits.put_literal(b'a');
+        bits.put_code(1, 7); // length code 257: 3 bytes
+        bits.put_code(4, 5); // distance code 4: 5 + 1 extra bit
+        bits.put(0, 1);
+        for _ in 0..100 {
+            bits.put_literal(b'b');
+        }
+        bits.put_code(0, 7); // end of block
+        let mut out = [0u8; 1024];
+        assert_eq!(
+            inflate(&mut out, &bits.0),
+            Err(Error::InvalidDistance {
+                distance: 5,
+                available: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_sink() {
+        // far more output than the window, with matches across block boundaries
+        let data: Vec<u8> = (0..1_000_000u64).map(|i| ((i * i) >> 11) as u8).collect();
+        let zlib = miniz::compress_to_vec(&data, 6);
+        let raw = &zlib[2..zlib.len() - 4];
+
+        let mut out = Vec::new();
+        let mut calls = 0;
+        let sink = |chunk: &[u8]| -> Result<(), Error> {
+            out.extend_from_slice(chunk);
+            calls += 1;
+            Ok(())
+        };
+        assert_eq!(
+            inflate_to_sink(raw, sink),
+            Ok((raw.len(), data.len() as u64))
+        );
+        assert_eq!(out, data);
+        assert!(calls > 1);
+
+        // errors of the data and of the sink
+        let sink = |_: &[u8]| Ok(());
+        assert_eq!(
+            inflate_to_sink(&raw[..raw.len() / 2], sink),
+            Err(Error::Underflow)
+        );
+        let sink = |_: &[u8]| Err(Error::Cancelled);
+        assert_eq!(inflate_to_sink(raw, sink), Err(Error::Cancelled));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_sink_writer() {
+        let data: Vec<u8> = (0..100_000u64).map(|i| ((i * i) >> 11) as u8).collect();
+        let zlib = miniz::compress_to_vec(&data, 6);
+        let raw = &zlib[2..zlib.len() - 4];
+
+        let mut writer = std::io::Cursor::new(Vec::new());
+        let sink = |chunk: &[u8]| std::io::Write::write_all(&mut writer, chunk);
+        assert_eq!(inflate_to_sink(raw, sink).unwrap().1, data.len() as u64);
+        assert_eq!(writer.into_inner(), data);
+        let sink = |_: &[u8]| Ok::<(), std::io::Error>(());
+        let err = inflate_to_sink(&raw[..10], sink).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_verbose() {
+        // stored block "abcd", then a fixed block with a literal and a match too far back
+        let mut bits = Bits(vec![0, 4, 0, 0xfb, 0xff, b'a', b'b', b'c', b'd'], 72);
+        bits.put(1, 1); // BFINAL
+        bits.put(1, 2); // fixed Huffman
+        bits.put_literal(b'x');
+        bits.put_code(1, 7); // length code 257: 3 bytes
+        bits.put_code(9, 5); // distance code 9: 25 + 3 extra bits
+        bits.put(5, 3);
+        bits.put_code(0, 7); // end of block
+
+        let mut out = [0u8; 64];
+        let options = InflateOptions::default();
+        let err = inflate_verbose(&mut out, &bits.0, &options).unwrap_err();
+        assert_eq!(
+            err.error,
+            Error::InvalidDistance {
+                distance: 30,
+                available: 5
+            }
+        );
+        assert_eq!((err.block, err.block_bit_pos), (1, 72));
+        assert_eq!(err.bit_pos, 72 + 3 + 8 + 7 + 5 + 3);
+        assert_eq!(err.byte_pos(), 12);
+        assert_eq!(err.out_pos, 5);
+        assert_eq!(&out[..5], b"abcdx");
+        assert!(err.to_string().contains("input byte 12"), "{err}");
+
+        // success and errors in the first block
+        let raw = &miniz::compress_to_vec(b"hello hello hello", 6)[2..];
+        assert_eq!(inflate_verbose(&mut out, raw, &options), Ok(17));
+        let err = inflate_verbose(&mut out, &[0x07], &options).unwrap_err();
+        assert_eq!(err.error, Error::InvalidBlockType);
+        assert_eq!((err.block, err.block_bit_pos, err.out_pos), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_limits() {
+        let data: Vec<u8> = (0..100_000u64).map(|i| ((i * i) >> 7) as u8).collect();
+        let zlib = miniz::compress_to_vec(&data, 6);
+        let mut out = vec![0u8; data.len() + 100];
+
+        let limited = |max_output, max_blocks, max_dynamic_tables| InflateOptions {
+            max_output,
+            max_blocks,
+            max_dynamic_tables,
+            ..Default::default()
+        };
+        let options = limited(data.len(), usize::MAX, usize::MAX);
+        assert_eq!(
+            inflate_zlib_with_options(&mut out, &zlib, &options),
+            Ok(data.len())
+        );
+        let options = limited(data.len() - 1, usize::MAX, usize::MAX);
+        assert_eq!(
+            inflate_zlib_with_options(&mut out, &zlib, &options),
+            Err(Error::LimitExceeded)
+        );
+        let options = limited(usize::MAX, usize::MAX, 0);
+        assert_eq!(
+            inflate_zlib_with_options(&mut out, &zlib, &options),
+            Err(Error::LimitExceeded)
+        );
+
+        // the buffer still bounds the output, exceeding it is no limit violation
+        let options = limited(data.len(), usize::MAX, usize::MAX);
+        assert_eq!(
+            inflate_zlib_with_options(&mut out[..100], &zlib, &options),
+            Err(Error::Overflow)
+        );
+
+        // stored block followed by a fixed block
+        let inp = [0x00, 0x01, 0x00, 0xfe, 0xff, 0x66, 0x4b, 0x04, 0x00];
+        assert_eq!(
+            inflate_with_options(&mut out, &inp, &limited(2, 2, 0)),
+            Ok(2)
+        );
+        assert_eq!(
+            inflate_with_options(&mut out, &inp, &limited(2, 1, 0)),
+            Err(Error::LimitExceeded)
+        );
+        assert_eq!(
+            inflate_with_options(&mut out, &inp, &limited(1, 2, 0)),
+            Err(Error::LimitExceeded)
+        );
+    }
+
+    #[test]
+    fn test_consumed() {
+        use miniz::gzip::{gzip_compress, GzipHeader};
+        use miniz::inflate::{inflate_consumed, inflate_gzip_consumed, inflate_zlib_consumed};
+
+        let data: Vec<u8> = (0..10_000u64).map(|i| ((i * i) >> 5) as u8).collect();
+        let zlib = miniz::compress_to_vec(&data, 6);
+        let gzip = gzip_compress(&data, 6, &GzipHeader::default()).unwrap();
+        let raw = &zlib[2..zlib.len() - 4];
+        let options = InflateOptions {
+            strict_end_of_input: true,
+            ..Default::default()
+        };
+        let mut out = vec![0u8; data.len()];
+
+        // the stream lengths are reported with and without data behind the stream, a stream
+        // missing its last byte is truncated
+        type Inflate = fn(&mut [u8], &[u8], &InflateOptions) -> Result<(usize, usize), Error>;
+        let cases: [(Inflate, &[u8]); 3] = [
+            (inflate_consumed, raw),
+            (inflate_zlib_consumed, &zlib),
+            (inflate_gzip_consumed, &gzip),
+        ];
+        for (inflate, stream) in cases {
+            let mut src = stream.to_vec();
+            assert_eq!(
+                inflate(&mut out, &src, &options),
+                Ok((data.len(), stream.len()))
+            );
+            src.extend_from_slice(b"trailing data");
+            assert_eq!(
+                inflate(&mut out, &src, &options),
+                Ok((data.len(), stream.len()))
+            );
+            assert_eq!(out, data);
+            assert_eq!(
+                inflate(&mut out, &stream[..stream.len() - 1], &options),
+                Err(Error::Underflow)
+            );
+        }
+    }
+
+    #[test]
+    fn test_inflate_to_vec() {
+        let data: Vec<u8> = (0..100_000u64).map(|i| ((i * i) >> 7) as u8).collect();
+        let raw = miniz::compress_to_vec(&data, 6)[2..].to_vec();
+
+        // grows from the default, from a small and from an exact size hint
+        assert_eq!(inflate_to_vec(&raw, &VecLimits::default()).unwrap(), data);
+        for size_hint in [0, 10, data.len()] {
+            let limits = VecLimits {
+                size_hint: Some(size_hint),
+                ..Default::default()
+            };
+            assert_eq!(inflate_to_vec(&raw, &limits).unwrap(), data);
+        }
+
+        // a zero-filled megabyte compresses to about 1 KB
+        let bomb = miniz::compress_to_vec(&vec![0u8; 1 << 20], 9)[2..].to_vec();
+        for max_output in [100, 4096, (1 << 20) - 1] {
+            let limits = VecLimits {
+                size_hint: None,
+                max_output,
+            };
+            assert_eq!(inflate_to_vec(&bomb, &limits), Err(Error::LimitExceeded));
+        }
+        let limits = VecLimits {
+            size_hint: None,
+            max_output: 1 << 20,
+        };
+        assert_eq!(inflate_to_vec(&bomb, &limits).unwrap().len(), 1 << 20);
+        assert_eq!(
+            inflate_to_vec(&[0xff], &limits).err(),
+            Some(Error::InvalidBlockType)
+        );
+    }
+
+    #[test]
+    fn test_zlib_segments() {
+        use miniz::inflate::inflate_zlib_segments;
+
+        let data: Vec<u8> = (0..10_000u64).map(|i| ((i * i) >> 5) as u8).collect();
+        let options = InflateOptions::default();
+        let mut out = vec![0u8; data.len()];
+
+        // stored and Huffman blocks split anywhere, also within the header and trailer
+        for level in [0, 6] {
+            let zlib = miniz::compress_to_vec(&data, level);
+            for size in [1, 3, 7, 1000, zlib.len()] {
+                let mut segments = zlib.chunks(size).flat_map(|chunk| [&[][..], chunk]);
+                assert_eq!(
+                    inflate_zlib_segments(&mut out, &mut segments, &options),
+                    Ok(data.len()),
+                    "level {level}, segments of {size} bytes"
+                );
+                assert_eq!(out, data);
+            }
+
+            let truncated = &zlib[..zlib.len() - 1];
+            assert_eq!(
+                inflate_zlib_segments(&mut out, &mut truncated.chunks(100), &options),
+                Err(Error::Underflow)
+            );
+        }
+
+        let (zlib, _) = far_reference_stream(7);
+        let mut dict = zlib.clone();
+        dict[1] = 0x20 | (31 - ((0x78u16 * 256 + 0x20) % 31) as u8);
+        assert_eq!(
+            inflate_zlib_segments(&mut out, &mut [&dict[..]].into_iter(), &options),
+            Err(Error::InvalidHeader)
+        );
+    }
+
+    #[test]
+    fn test_partial() {
+        use miniz::inflate::{inflate_partial, PartialStatus};
+        use miniz::stream::{Compress, FlushCompress};
+
+        // RFC 7692 section 7.2.3 messages, the trailing 00 00 ff ff stripped
+        let options = InflateOptions::default();
+        let mut out = [0u8; 64];
+        for (message, status) in [
+            (
+                &[0xf2, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00][..],
+                PartialStatus::NeedsMoreInput,
+            ),
+            (
+                &[0xf3, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00],
+                PartialStatus::Done,
+            ),
+            (
+                &[
+                    0x00, 0x05, 0x00, 0xfa, 0xff, 0x48, 0x65, 0x6c, 0x6c, 0x6f, 0x00,
+                ],
+                PartialStatus::NeedsMoreInput,
+            ),
+        ] {
+            assert_eq!(
+                inflate_partial(&mut out, message, &options),
+                Ok((status, 5))
+            );
+            assert_eq!(&out[..5], b"Hello");
+        }
+        assert_eq!(
+            inflate_with_options(
+                &mut out,
+                &[0xf2, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00],
+                &options
+            ),
+            Err(Error::Underflow)
+        );
+
+        // the second message refers to the first one with context takeover
+        let second = [0xf2, 0x00, 0x11, 0x00, 0x00];
+        let options = InflateOptions {
+            dictionary: Some(b"Hello"),
+            ..Default::default()
+        };
+        let partial = inflate_partial(&mut out, &second, &options);
+        assert_eq!(partial, Ok((PartialStatus::NeedsMoreInput, 5)));
+        assert_eq!(&out[..5], b"Hello");
+        let options = InflateOptions::default();
+        assert_eq!(
+            inflate_partial(&mut out, &second, &options),
+            Err(Error::InvalidDistance {
+                distance: 5,
+                available: 1
+            })
+        );
+
+        // sync flushed output of the compressor
+        let data: Vec<u8> = (0..5000u32).map(|i| (i % 7 * i % 13) as u8).collect();
+        let mut compress = Compress::new(6, false);
+        let mut flushed = vec![0u8; 8192];
+        compress.run(&data, &mut flushed, FlushCompress::Sync);
+        flushed.truncate(compress.total_out() as usize - 4);
+        let mut out = vec![0u8; data.len()];
+        let partial = inflate_partial(&mut out, &flushed, &options);
+        assert_eq!(partial, Ok((PartialStatus::NeedsMoreInput, data.len())));
+        assert_eq!(out, data);
+
+        // corrupt data still fails
+        let partial = inflate_partial(&mut out, &[0xff, 0xff], &options);
+        assert_eq!(partial, Err(Error::InvalidBlockType));
+    }
+
+    #[test]
+    fn test_deflate64() {
+        use miniz::inflate::{inflate64, inflate64_with_options};
+
+        // a stored block of 60000 bytes, then a fixed Huffman block
+        let stored: Vec<u8> = (0..60000u32).map(|i| (i % 251) as u8).collect();
+        let mut bits = Bits(Vec::new(), 0);
+        bits.put(0, 1);
+        bits.put(0, 2);
+        bits.0.extend_from_slice(&60000u16.to_le_bytes());
+        bits.0.extend_from_slice(&(!60000u16).to_le_bytes());
+        bits.0.extend_from_slice(&stored);
+        bits.1 = 8 * bits.0.len();
+        bits.put(1, 1); // BFINAL
+        bits.put(1, 2); // fixed Huffman
+        bits.put_code(0xc5, 8); // length code 285: 3 + 16 extra bits
+        bits.put(1000, 16);
+        bits.put_code(30, 5); // distance code 30: 32769 + 14 extra bits
+        bits.put(40000 - 32769, 14);
+        bits.put_code(0xc5, 8);
+        bits.put(0, 16);
+        bits.put_code(31, 5); // distance code 31: 49153 + 14 extra bits
+        bits.put(50000 - 49153, 14);
+        for b in 0..40 {
+            bits.put_literal(b);
+        }
+        bits.put_code(0, 7); // end of block
+
+        let mut expected = stored.clone();
+        expected.extend_from_within(20000..21003);
+        expected.extend_from_within(11003..11006);
+        expected.extend(0..40);
+
+        let mut out = vec![0u8; expected.len()];
+        assert_eq!(inflate64(&mut out, &bits.0), Ok(expected.len()));
+        assert_eq!(out, expected);
+
+        // the same symbols near the end of input, decoded without the fast loop
+        let mut tail = Bits(Vec::new(), 0);
+        tail.put(1, 1);
+        tail.put(1, 2);
+        tail.put_literal(7);
+        tail.put_code(0xc5, 8);
+        tail.put(297, 16);
+        tail.put_code(0, 5);
+        tail.put_code(0, 7);
+        let mut out = vec![0u8; 301];
+        let options = InflateOptions::default();
+        assert_eq!(inflate64_with_options(&mut out, &tail.0, &options), Ok(301));
+        assert_eq!(out, [7; 301]);
+
+        // deflate reads code 285 as a length of 258 without extra bits
+        let mut out = vec![0u8; expected.len()];
+        assert_ne!(inflate(&mut out, &bits.0), Ok(expected.len()));
+        let mut out = vec![0u8; 301];
+        let err = inflate(&mut out, &tail.0).unwrap_err();
+        assert!(matches!(err, Error::InvalidDistance { .. }));
+    }
+
+    #[test]
+    fn test_inflate_fixed() {
+        use miniz::deflate::{deflate_with_strategy, CompressionLevel, Strategy};
+        use miniz::inflate::inflate_fixed;
+
+        // stored, fixed and dynamic Huffman blocks
+        let text = b"Hello Hello Hello, small assets decode on the stack. ".repeat(4);
+        let mut streams = Vec::new();
+        for (level, strategy) in [
+            (CompressionLevel::NoCompression, Strategy::Default),
+            (CompressionLevel::DefaultLevel, Strategy::Fixed),
+            (CompressionLevel::BestCompression, Strategy::Default),
+        ] {
+            let mut packed = Vec::new();
+            deflate_with_strategy(&mut packed, &text, level, strategy);
+            streams.push(packed);
+        }
+
+        for packed in &streams {
+            let before = ALLOCATIONS.with(|count| count.get());
+            let (out, len) = inflate_fixed::<256>(packed).unwrap();
+            assert_eq!(ALLOCATIONS.with(|count| count.get()), before);
+            assert_eq!(&out[..len], &text[..]);
+            assert_eq!(inflate_fixed::<16>(packed).err(), Some(Error::Overflow));
+        }
+    }
+
+    #[test]
+    fn test_many_dynamic_blocks() {
+        use miniz::stream::{Compress, FlushCompress, Inflater};
+
+        // 1000 small dynamic blocks, the tables are rebuilt in place for each of them
+        let mut seed = 0x2337u32;
+        let text: Vec<u8> = (0..100_000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                b"aaabbcd"[(seed >> 16) as usize % 7]
+            })
+            .collect();
+        let mut compress = Compress::new(9, false);
+        let mut packed = vec![0; 2 * text.len()];
+        for (i, piece) in text.chunks(100).enumerate() {
+            let flush = if i == 999 {
+                FlushCompress::Finish
+            } else {
+                FlushCompress::Full
+            };
+            let out = compress.total_out() as usize;
+            compress.run(piece, &mut packed[out..], flush);
+        }
+        packed.truncate(compress.total_out() as usize);
+
+        let mut out = vec![0; text.len()];
+        let before = ALLOCATIONS.with(|count| count.get());
+        assert_eq!(inflate(&mut out, &packed), Ok(text.len()));
+        assert_eq!(ALLOCATIONS.with(|count| count.get()), before);
+        assert_eq!(out, text);
+
+        // block by block through the stream decoder
+        let mut inflater = Inflater::new(false);
+        let mut out = vec![0; text.len()];
+        let (_, consumed, produced) = inflater.decompress(&packed, &mut out).unwrap();
+        assert!(inflater.is_finished());
+        assert_eq!((consumed, produced), (packed.len(), text.len()));
+        assert_eq!(out, text);
+    }
+
+    #[test]
+    fn test_deflate_block_iter() {
+        use miniz::deflate::{deflate, CompressionLevel};
+        use miniz::inflate::{deflate_block_iter, BlockInfo, BlockType};
+        use miniz::stream::{Compress, FlushCompress};
+
+        // a fixed block, an empty stored block from the full flush and a dynamic block
+        let mut seed = 0x2338u32;
+        let text: Vec<u8> = (0..1000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                b"aaabbcd"[(seed >> 16) as usize % 7]
+            })
+            .collect();
+        let mut compress = Compress::new(6, false);
+        let mut packed = vec![0; 4096];
+        compress.run(&text[..20], &mut packed, FlushCompress::Full);
+        let out = compress.total_out() as usize;
+        compress.run(&text[20..], &mut packed[out..], FlushCompress::Finish);
+        packed.truncate(compress.total_out() as usize);
+
+        let mut blocks = deflate_block_iter(&packed);
+        let mut data = Vec::new();
+        let mut infos = Vec::new();
+        while let Some(info) = blocks.next() {
+            infos.push(info.unwrap());
+            data.extend_from_slice(blocks.data());
+        }
+        assert_eq!(data, text);
+        let types: Vec<BlockType> = infos.iter().map(|info| info.block_type).collect();
+        assert_eq!(
+            types,
+            [BlockType::Fixed, BlockType::Stored, BlockType::Dynamic]
+        );
+        assert_eq!(infos[1].size, 0);
+        assert_eq!(infos[2].out_pos, 20);
+        assert_eq!(infos[2].end_bit.div_ceil(8), packed.len());
+        for pair in infos.windows(2) {
+            assert_eq!(pair[0].end_bit, pair[1].start_bit);
+            assert_eq!(pair[0].out_pos + pair[0].size as u64, pair[1].out_pos);
+            assert!(!pair[0].last);
+        }
+        assert!(infos[2].last);
+
+        // stored blocks locate their data in the input
+        let noise: Vec<u8> = (0..100_000u32)
+            .map(|i| (i.wrapping_mul(i) >> 5) as u8)
+            .collect();
+        let mut stored = Vec::new();
+        deflate(&mut stored, &noise, CompressionLevel::NoCompression);
+        let mut out_pos = 0;
+        for info in deflate_block_iter(&stored) {
+            let BlockInfo { end_bit, size, .. } = info.unwrap();
+            assert_eq!(
+                &stored[end_bit / 8 - size..end_bit / 8],
+                &noise[out_pos..][..size]
+            );
+            out_pos += size;
+        }
+        assert_eq!(out_pos, noise.len());
+
+        // blocks longer than the window
+        let zeros = vec![0; 200_000];
+        let mut packed = Vec::new();
+        deflate(&mut packed, &zeros, CompressionLevel::BestCompression);
+        let mut blocks = deflate_block_iter(&packed);
+        let mut data = Vec::new();
+        while let Some(info) = blocks.next() {
+            assert_eq!(info.unwrap().out_pos, data.len() as u64);
+            data.extend_from_slice(blocks.data());
+        }
+        assert_eq!(data, zeros);
+
+        // iteration stops at the first error
+        let truncated = &stored[..1000];
+        let results: Vec<_> = deflate_block_iter(truncated).collect();
+        assert_eq!(results, [Err(Error::Underflow)]);
+    }
+
+    #[test]
+    fn test_inflate_scan_size() {
+        use miniz::deflate::{deflate, deflate_with_strategy, CompressionLevel, Strategy};
+        use miniz::inflate::inflate_scan_size;
+
+        // stored, fixed and dynamic blocks, and streams of several blocks
+        let text = b"Scanning counts the output without writing it. ".repeat(3000);
+        for (level, strategy) in [
+            (CompressionLevel::NoCompression, Strategy::Default),
+            (CompressionLevel::DefaultLevel, Strategy::Fixed),
+            (CompressionLevel::BestCompression, Strategy::Default),
+            (CompressionLevel::BestSpeed, Strategy::HuffmanOnly),
+        ] {
+            let mut packed = Vec::new();
+            deflate_with_strategy(&mut packed, &text, level, strategy);
+            let size = inflate_scan_size(&packed).unwrap();
+            assert_eq!(size, text.len(), "{level:?} {strategy:?}");
+
+            let before = ALLOCATIONS.with(|count| count.get());
+            inflate_scan_size(&packed).unwrap();
+            assert_eq!(ALLOCATIONS.with(|count| count.get()), before);
+
+            // without strict end of input the missing bits may read as zeros, as when decoding
+            let truncated = &packed[..packed.len() - 1];
+            match inflate_scan_size(truncated) {
+                Ok(size) => assert_eq!(inflate(&mut vec![0; size], truncated), Ok(size)),
+                Err(err) => assert_eq!(err, Error::Underflow),
+            }
+        }
+
+        let mut packed = Vec::new();
+        deflate(&mut packed, &[], CompressionLevel::DefaultLevel);
+        assert_eq!(inflate_scan_size(&packed), Ok(0));
+
+        // the same errors as decoding
+        let mut bits = Bits(Vec::new(), 0);
+        bits.put(1, 1);
+        bits.put(1, 2);
+        bits.put_literal(b'a');
+        bits.put_code(1, 7);
+        bits.put_code(1, 5);
+        bits.put_code(0, 7);
+        let mut out = [0u8; 16];
+        assert_eq!(inflate_scan_size(&bits.0), inflate(&mut out, &bits.0));
+        assert_eq!(inflate_scan_size(&[0x07]), Err(Error::InvalidBlockType));
+        assert_eq!(
+            inflate_scan_size(&[0x01, 0x01, 0x00, 0xff, 0xff]),
+            Err(Error::InvalidBlockLength)
+        );
+    }
 }
@@ -0,0 +1,384 @@
+// ----------------------------------------------------------------------------
+mod test {
+    use miniz::prelude::*;
+    use miniz::{
+        decompress_to_vec_gzip_members_with_limit, decompress_to_vec_gzip_with_limit,
+        decompress_to_vec_members_with_limit, decompress_to_vec_with_limit,
+        decompress_to_vec_zlib_with_limit,
+    };
+
+    #[cfg(feature = "zip")]
+    include!("../assets/zip/deflated.rs");
+    #[cfg(feature = "zip")]
+    include!("../assets/zip/textures.rs");
+
+    #[test]
+    fn test_members() {
+        use miniz::bgzf::{bgzf_compress, BGZF_BLOCK_SIZE};
+        use miniz::gzip::{gzip_compress, GzipHeader};
+
+        let data: Vec<u8> = (0..150_000u64).map(|i| ((i * i) >> 9) as u8).collect();
+
+        // bgzf: full blocks, the rest and the empty EOF member
+        let (out, members) = decompress_to_vec_gzip_members(&bgzf_compress(&data, 6)).unwrap();
+        assert_eq!(out, data);
+        let lens: Vec<usize> = members.iter().map(|m| m.output.len()).collect();
+        assert_eq!(
+            lens,
+            [
+                BGZF_BLOCK_SIZE,
+                BGZF_BLOCK_SIZE,
+                data.len() - 2 * BGZF_BLOCK_SIZE,
+                0
+            ]
+        );
+
+        // `cat a.gz b.gz`
+        let a = gzip_compress(b"first ", 6, &GzipHeader::default()).unwrap();
+        let b = gzip_compress(b"second", 6, &GzipHeader::default()).unwrap();
+        let gz = [a.clone(), b].concat();
+        let (out, members) = decompress_to_vec_gzip_members(&gz).unwrap();
+        assert_eq!(out, b"first second");
+        assert_eq!(
+            members,
+            [
+                Member {
+                    input: 0..a.len(),
+                    output: 0..6
+                },
+                Member {
+                    input: a.len()..gz.len(),
+                    output: 6..12
+                }
+            ]
+        );
+        assert!(decompress_to_vec_gzip_members(&[&gz[..], b"garbage"].concat()).is_err());
+        assert!(decompress_to_vec_gzip_members(&[]).is_err());
+
+        // raw deflate streams start at the byte boundary after the final block
+        let zlib = compress_to_vec(&data, 6);
+        let raw = &zlib[2..zlib.len() - 4];
+        let (out, members) =
+            decompress_to_vec_members(&[raw, &[0x4b, 0x04, 0x00]].concat()).unwrap();
+        assert_eq!(out[..data.len()], data[..]);
+        assert_eq!(out[data.len()..], *b"a");
+        assert_eq!(members[1].input, raw.len()..raw.len() + 3);
+    }
+
+    #[test]
+    fn test_compress_uncompress() {
+        use miniz::inflate;
+
+        let data = b"protocol payload, protocol payload".repeat(100);
+        for level in [0, 1, 6, 9] {
+            let packed = compress(&data, level);
+            assert_eq!(uncompress(&packed, data.len()).as_ref(), Ok(&data));
+        }
+
+        // a stream from zlib's compress2 at level 9
+        let zlib = [
+            120, 218, 75, 78, 76, 206, 72, 85, 72, 202, 201, 79, 2, 0, 20, 75, 3, 180,
+        ];
+        assert_eq!(uncompress(&zlib, 10), Ok(b"cache blob".to_vec()));
+
+        // the expected length bounds the output as zlib's destLen
+        assert_eq!(uncompress(&zlib, 64), Ok(b"cache blob".to_vec()));
+        assert_eq!(
+            uncompress(&zlib, 9),
+            Err(Error::Inflate(inflate::Error::Overflow))
+        );
+        let mut corrupt = zlib;
+        corrupt[17] ^= 1;
+        assert_eq!(
+            uncompress(&corrupt, 10),
+            Err(Error::Inflate(inflate::Error::ChecksumMismatch))
+        );
+        assert_eq!(uncompress(&compress(&[], 6), 0), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_zlib_round_trip() {
+        let data: Vec<u8> = (0..200_000).map(|i| (i % 251) as u8).collect();
+        let packed = compress_to_vec(&data, 6);
+        assert_eq!(decompress_to_vec_zlib(&packed), Ok(data));
+
+        let empty = compress_to_vec(&[], 6);
+        assert_eq!(decompress_to_vec_zlib(&empty), Ok(vec![]));
+
+        let mut corrupt = compress_to_vec(b"checksum", 6);
+        *corrupt.last_mut().unwrap() ^= 0x80;
+        assert_eq!(
+            decompress_to_vec_zlib(&corrupt),
+            Err(Error::Inflate(miniz::inflate::Error::ChecksumMismatch))
+        );
+    }
+
+    #[test]
+    fn test_decompress_to_vec() {
+        let out = decompress_to_vec(&[0x2b, 0x1f, 0x05, 0x40, 0x0c, 0x00]).unwrap();
+        assert_eq!(out.len(), 262);
+        assert_eq!(out[0], 0x77);
+
+        // 1 MB of runs from about 1 KB, the first buffer of 4 KB grows several times
+        let data: Vec<u8> = (0..1 << 20).map(|i| (i / 4096) as u8).collect();
+        let zlib = compress_to_vec(&data, 9);
+        assert!(zlib.len() * 4 * 16 < data.len());
+        assert_eq!(decompress_to_vec_zlib(&zlib).unwrap(), data);
+        assert_eq!(decompress_to_vec(&zlib[2..zlib.len() - 4]).unwrap(), data);
+    }
+
+    #[test]
+    fn test_decompress_limit() {
+        let data = vec![0u8; 1 << 20];
+        let zlib = compress_to_vec(&data, 9);
+        let raw = &zlib[2..zlib.len() - 4];
+        let limited = |result: miniz::Result<Vec<u8>>| {
+            matches!(
+                result,
+                Err(Error::Inflate(miniz::inflate::Error::LimitExceeded))
+            )
+        };
+        assert!(limited(decompress_to_vec_with_limit(raw, data.len() - 1)));
+        assert!(limited(decompress_to_vec_zlib_with_limit(&zlib, 5000)));
+        assert_eq!(
+            decompress_to_vec_zlib_with_limit(&zlib, data.len()).unwrap(),
+            data
+        );
+
+        let gz = miniz::gzip::gzip_compress(&data, 9, &Default::default()).unwrap();
+        assert!(limited(decompress_to_vec_gzip_with_limit(&gz, 1 << 19)));
+        assert_eq!(decompress_to_vec_gzip(&gz).unwrap(), data);
+
+        // the limit covers the output of all members together
+        let members = |result: miniz::Result<(Vec<u8>, Vec<Member>)>| result.map(|(out, _)| out);
+        let gz2 = [&gz[..], &gz[..]].concat();
+        let len = 2 * data.len();
+        assert!(limited(members(decompress_to_vec_gzip_members_with_limit(
+            &gz2,
+            len - 1
+        ))));
+        assert_eq!(
+            decompress_to_vec_gzip_members_with_limit(&gz2, len)
+                .unwrap()
+                .1
+                .len(),
+            2
+        );
+        let raw2 = [raw, raw].concat();
+        assert!(limited(members(decompress_to_vec_members_with_limit(
+            &raw2,
+            len - 1
+        ))));
+        assert_eq!(
+            members(decompress_to_vec_members_with_limit(&raw2, len))
+                .unwrap()
+                .len(),
+            len
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn test_read_png_rgba() {
+        let png = include_bytes!("../assets/png/basn2c08.png");
+        let (width, height, rgba) = read_png_rgba(png).unwrap();
+        assert_eq!((width, height), (32, 32));
+        assert_eq!(rgba.len(), 32 * 32 * 4);
+    }
+
+    #[test]
+    #[cfg(feature = "zip")]
+    fn test_unzip_file() {
+        assert_eq!(unzip_file(DEFLATED_ZIP, "first").unwrap(), FIRST);
+        assert_eq!(unzip_file(DEFLATED_ZIP, "second").unwrap(), SECOND);
+        assert!(matches!(
+            unzip_file(DEFLATED_ZIP, "third"),
+            Err(Error::Zip(miniz::zip_read::Error::FileNotFound))
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_decompress_batch() {
+        let inputs: Vec<Vec<u8>> = (0..20)
+            .map(|i| compress_to_vec(&vec![i as u8; i * 1000], 6))
+            .chain([vec![0x78, 0x01, 0x07]])
+            .collect();
+        let srcs: Vec<&[u8]> = inputs.iter().map(|v| v.as_slice()).collect();
+
+        for threads in [0, 1, 3] {
+            let options = BatchOptions {
+                threads,
+                zlib: true,
+            };
+            let results = decompress_batch(&srcs, &options);
+            assert_eq!(results.len(), 21);
+            for (i, result) in results[..20].iter().enumerate() {
+                assert_eq!(result, &Ok(vec![i as u8; i * 1000]));
+            }
+            assert!(results[20].is_err(), "invalid input");
+        }
+
+        assert!(decompress_batch(&[], &BatchOptions::default()).is_empty());
+    }
+
+    #[test]
+    #[cfg(all(feature = "png", feature = "zip"))]
+    fn test_zip_read_png() {
+        let options = miniz::inflate::InflateOptions::default();
+        let files = zip_open(TEXTURES_ZIP).unwrap();
+        let stored = zip_read_png(TEXTURES_ZIP, &files, "textures/stored.png", &options).unwrap();
+        let deflated =
+            zip_read_png(TEXTURES_ZIP, &files, "textures/deflated.png", &options).unwrap();
+        assert_eq!(stored.0.width, 32);
+        assert_eq!(stored.2, deflated.2);
+
+        let missing = zip_read_png(TEXTURES_ZIP, &files, "missing.png", &options);
+        assert_eq!(
+            missing.err(),
+            Some(Error::Zip(miniz::zip_read::Error::FileNotFound))
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "std", feature = "png", feature = "zip"))]
+    fn test_png_decode_batch() {
+        let files = zip_open(TEXTURES_ZIP).unwrap();
+        let names = [
+            "textures/stored.png",
+            "missing.png",
+            "textures/deflated.png",
+        ];
+        let mut bufs = vec![vec![0u8; 32 * 32 * 4]; 3];
+        let mut dsts: Vec<&mut [u8]> = bufs.iter_mut().map(|v| v.as_mut_slice()).collect();
+        let options = BatchOptions {
+            threads: 2,
+            ..Default::default()
+        };
+
+        let results =
+            zip_decode_png_batch(TEXTURES_ZIP, &files, &names, &mut dsts, &options).unwrap();
+        assert_eq!(results[0], Ok((32, 32)));
+        assert_eq!(
+            results[1],
+            Err(Error::Zip(miniz::zip_read::Error::FileNotFound))
+        );
+        assert_eq!(results[2], Ok((32, 32)));
+        assert_eq!(bufs[0], bufs[2]);
+
+        let png = zip_read(TEXTURES_ZIP, &files, names[0]).unwrap();
+        assert_eq!(bufs[0], read_png_rgba(&png).unwrap().2);
+
+        let mut small = vec![0u8; 16];
+        let mut dsts: Vec<&mut [u8]> = vec![&mut small];
+        let results = png_decode_batch(&[&png], &mut dsts, &options).unwrap();
+        assert_eq!(
+            results,
+            vec![Err(Error::Png(miniz::png_read::Error::BufferError))]
+        );
+
+        // every image needs a buffer
+        let mut dsts: Vec<&mut [u8]> = bufs.iter_mut().map(|v| v.as_mut_slice()).collect();
+        let results = png_decode_batch(&[&png, &png], &mut dsts, &options);
+        assert_eq!(
+            results.err(),
+            Some(Error::Png(miniz::png_read::Error::BufferError))
+        );
+        let results = zip_decode_png_batch(TEXTURES_ZIP, &files, &names[..1], &mut [], &options);
+        assert!(results.is_err());
+    }
+
+    #[test]
+    #[cfg(all(feature = "std", feature = "png"))]
+    fn test_png_decode_batch_formats() {
+        // sub-byte greyscale, truecolor and interlaced images through the same workers
+        let pngs: [&[u8]; 4] = [
+            include_bytes!("../assets/png/basn0g01.png"),
+            include_bytes!("../assets/png/basn2c08.png"),
+            include_bytes!("../assets/png/adam7_g04_13x11.png"),
+            include_bytes!("../assets/png/basn0g01.png"),
+        ];
+        let mut bufs = vec![vec![0u8; 32 * 32 * 4]; pngs.len()];
+        let mut dsts: Vec<&mut [u8]> = bufs.iter_mut().map(|v| v.as_mut_slice()).collect();
+        let options = BatchOptions {
+            threads: 1,
+            ..Default::default()
+        };
+        let results = png_decode_batch(&pngs, &mut dsts, &options).unwrap();
+        for ((png, result), buf) in pngs.iter().zip(results).zip(&bufs) {
+            let (width, height, rgba) = read_png_rgba(png).unwrap();
+            assert_eq!(result, Ok((width, height)));
+            assert_eq!(&buf[..rgba.len()], rgba);
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "std", feature = "zip"))]
+    fn test_zip_read_many() {
+        let files = zip_open(TEXTURES_ZIP).unwrap();
+        let names: Vec<&str> = files.iter().map(|f| f.name.as_str()).collect();
+        for threads in [0, 1, 2] {
+            let options = BatchOptions {
+                threads,
+                ..Default::default()
+            };
+            let entries = zip_read_many(TEXTURES_ZIP, &files, &names, &options).unwrap();
+            assert_eq!(entries.len(), names.len());
+            for (name, entry) in names.iter().zip(&entries) {
+                assert_eq!(entry, &zip_read(TEXTURES_ZIP, &files, name).unwrap());
+            }
+        }
+
+        let files = zip_open(DEFLATED_ZIP).unwrap();
+        let options = BatchOptions::default();
+        let entries = zip_read_many(DEFLATED_ZIP, &files, &["second", "first"], &options);
+        assert_eq!(entries.unwrap(), [SECOND.to_vec(), FIRST.to_vec()]);
+        assert_eq!(
+            zip_read_many(DEFLATED_ZIP, &files, &["first", "third"], &options),
+            Err(Error::Zip(miniz::zip_read::Error::FileNotFound))
+        );
+        assert_eq!(
+            zip_read_many(DEFLATED_ZIP, &files, &[], &options),
+            Ok(vec![])
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_deflate_parallel() {
+        use miniz::gzip::{gzip_compress_parallel, gzip_decode, GzipHeader};
+        use miniz::PARALLEL_BLOCK_SIZE;
+
+        let data: Vec<u8> = (0..5 * PARALLEL_BLOCK_SIZE / 2)
+            .map(|i| (i / 7 % 97) as u8)
+            .collect();
+        let single = deflate_parallel(&data, 6, &BatchOptions::default());
+        assert!(single.len() < data.len() / 10);
+        for threads in [1, 2, 4] {
+            let options = BatchOptions {
+                threads,
+                ..Default::default()
+            };
+            assert_eq!(deflate_parallel(&data, 6, &options), single);
+        }
+        assert_eq!(decompress_to_vec(&single).unwrap(), data);
+
+        // the first two blocks end with the sync flush marker
+        let markers = single
+            .windows(4)
+            .filter(|w| w == &[0, 0, 0xff, 0xff])
+            .count();
+        assert_eq!(markers, 2);
+
+        for level in [0, 1, 9] {
+            let raw = deflate_parallel(&data, level, &BatchOptions::default());
+            assert_eq!(decompress_to_vec(&raw).unwrap(), data);
+        }
+        let empty = deflate_parallel(&[], 6, &BatchOptions::default());
+        assert_eq!(decompress_to_vec(&empty).unwrap(), b"");
+
+        let header = GzipHeader::default();
+        let gz = gzip_compress_parallel(&data, 6, &header, &BatchOptions::default()).unwrap();
+        assert_eq!(gzip_decode(&gz).unwrap(), data);
+    }
+}
@@ -0,0 +1,292 @@
+#![cfg(feature = "png")]
+
+// ----------------------------------------------------------------------------
+mod test {
+    use core::cell::RefCell;
+    use miniz::inflate::InflateOptions;
+    use miniz::png_meta::*;
+    use miniz::png_read::{PNGChunkIHDR, PNGColorType};
+    use miniz::png_write::*;
+    use miniz::warning::Warning;
+
+    fn chunk<'a>(chunk_type: &[u8; 4], data: &'a [u8]) -> ExtraChunk<'a> {
+        ExtraChunk {
+            chunk_type: *chunk_type,
+            data,
+            placement: ChunkPlacement::BeforeIdat,
+        }
+    }
+
+    fn png_with(color_type: PNGColorType, chunks: &[ExtraChunk]) -> Vec<u8> {
+        let ihdr = PNGChunkIHDR {
+            width: 1,
+            height: 1,
+            bit_depth: 8,
+            color_type,
+            compression: 0,
+            filter: 0,
+            interlace: 0,
+        };
+        let options = EncodeOptions {
+            extra_chunks: chunks,
+            ..Default::default()
+        };
+        let (plte, data): (&[u32], &[u8]) = match color_type {
+            PNGColorType::IndexedColor => (&[0x123456], &[0]),
+            PNGColorType::Greyscale => (&[], &[1]),
+            _ => (&[], &[1, 2, 3]),
+        };
+        png_write(&ihdr, plte, data, &options).unwrap()
+    }
+
+    #[test]
+    fn test_metadata() {
+        let ztxt = [
+            b"Comment\0\0".as_slice(),
+            &miniz::compress_to_vec(b"zipped", 6),
+        ]
+        .concat();
+        let itxt = [
+            b"Title\0\x01\0de\0Titel\0".as_slice(),
+            &miniz::compress_to_vec("Gr\u{fc}\u{df}e".as_bytes(), 6),
+        ]
+        .concat();
+        let mut after_idat = chunk(b"tEXt", b"Author\0Caf\xe9");
+        after_idat.placement = ChunkPlacement::AfterIdat;
+        let chunks = [
+            chunk(b"gAMA", &[0, 0, 0xb1, 0x8f]),
+            chunk(b"gAMA", &[0, 1, 0x86, 0xa0]),
+            chunk(b"sRGB", &[1]),
+            chunk(b"pHYs", &[0, 0, 0x0b, 0x13, 0, 0, 0x0b, 0x13, 1]),
+            chunk(b"bKGD", &[0, 1, 0, 2, 0, 3]),
+            chunk(b"tIME", &[0x07, 0xe9, 3, 14, 15, 9, 26]),
+            chunk(b"zTXt", &ztxt),
+            chunk(b"iTXt", &itxt),
+            chunk(b"iTXt", b"Note\0\0\0\0\0plain"),
+            after_idat,
+        ];
+        let png = png_with(PNGColorType::TrueColor, &chunks);
+
+        let meta = png_metadata(&png).unwrap();
+        assert_eq!(meta.gamma, Some(45455), "the first gAMA is used");
+        assert_eq!(meta.srgb, Some(1));
+        let physical_size = PhysicalSize {
+            x: 2835,
+            y: 2835,
+            meter: true,
+        };
+        assert_eq!(meta.physical_size, Some(physical_size));
+        assert_eq!(physical_size.dpi(), Some((72, 72)));
+        assert_eq!(meta.background, Some(Background::Rgb(1, 2, 3)));
+        let modified = PngTime {
+            year: 2025,
+            month: 3,
+            day: 14,
+            hour: 15,
+            minute: 9,
+            second: 26,
+        };
+        assert_eq!(meta.modified, Some(modified));
+
+        let texts = [
+            PngText {
+                keyword: "Comment".into(),
+                text: "zipped".into(),
+                compressed: true,
+                ..Default::default()
+            },
+            PngText {
+                keyword: "Title".into(),
+                text: "Gr\u{fc}\u{df}e".into(),
+                language: "de".into(),
+                translated_keyword: "Titel".into(),
+                compressed: true,
+            },
+            PngText {
+                keyword: "Note".into(),
+                text: "plain".into(),
+                ..Default::default()
+            },
+            PngText {
+                keyword: "Author".into(),
+                text: "Caf\u{e9}".into(),
+                ..Default::default()
+            },
+        ];
+        assert_eq!(meta.texts, texts);
+        assert_eq!(meta.text("Comment"), Some("zipped"));
+        assert_eq!(meta.text("Author"), Some("Caf\u{e9}"));
+        assert_eq!(meta.text("Titel"), None);
+
+        let aspect = PhysicalSize {
+            meter: false,
+            ..physical_size
+        };
+        assert_eq!(aspect.dpi(), None);
+
+        let png = png_with(PNGColorType::IndexedColor, &[chunk(b"bKGD", &[0])]);
+        let meta = png_metadata(&png).unwrap();
+        assert_eq!(meta.background, Some(Background::Palette(0)));
+        assert_eq!(meta.texts, []);
+    }
+
+    #[test]
+    fn test_invalid_chunks() {
+        let chunks = [
+            chunk(b"sRGB", &[4]),
+            chunk(b"pHYs", &[0; 8]),
+            chunk(b"bKGD", &[0]),
+            chunk(b"tIME", &[0x07, 0xe9, 13, 1, 0, 0, 0]),
+            chunk(b"tEXt", b"no separator"),
+            chunk(b"zTXt", b"Comment\0\0not zlib"),
+            chunk(b"gAMA", &[0, 1, 0x86, 0xa0]),
+        ];
+        let mut png = png_with(PNGColorType::Greyscale, &chunks);
+
+        // corrupt the CRC of the gAMA chunk
+        let pos = png.windows(4).position(|w| w == b"gAMA").unwrap();
+        png[pos + 8] ^= 1;
+
+        let warnings = RefCell::new(Vec::new());
+        let options = InflateOptions {
+            warnings: Some(&warnings),
+            ..Default::default()
+        };
+        let meta = png_metadata_with_options(&png, &options).unwrap();
+        assert_eq!(meta, PngMetadata::default());
+
+        let chunk = |name: &[u8; 4]| u32::from_be_bytes(*name);
+        let expected = [
+            Warning::PngChunkInvalid {
+                chunk: chunk(b"sRGB"),
+            },
+            Warning::PngChunkInvalid {
+                chunk: chunk(b"pHYs"),
+            },
+            Warning::PngChunkInvalid {
+                chunk: chunk(b"bKGD"),
+            },
+            Warning::PngChunkInvalid {
+                chunk: chunk(b"tIME"),
+            },
+            Warning::PngChunkInvalid {
+                chunk: chunk(b"tEXt"),
+            },
+            Warning::PngChunkInvalid {
+                chunk: chunk(b"zTXt"),
+            },
+            Warning::PngChunkCrc {
+                chunk: chunk(b"gAMA"),
+            },
+        ];
+        assert_eq!(warnings.into_inner(), expected);
+
+        // without CRC checks the gAMA chunk is used
+        let options = InflateOptions {
+            verify_checksums: false,
+            ..Default::default()
+        };
+        let meta = png_metadata_with_options(&png, &options).unwrap();
+        assert_eq!(meta.gamma, Some(100000));
+    }
+
+    #[test]
+    fn test_icc_profile() {
+        let ihdr = PNGChunkIHDR {
+            width: 1,
+            height: 1,
+            bit_depth: 8,
+            color_type: PNGColorType::TrueColor,
+            compression: 0,
+            filter: 0,
+            interlace: 0,
+        };
+        let profile: Vec<u8> = (0..3000).map(|i| (i % 7) as u8).collect();
+        let options = EncodeOptions {
+            color_profile: ColorProfile::Icc {
+                name: "Display P3",
+                profile: &profile,
+            },
+            ..Default::default()
+        };
+        let png = png_write(&ihdr, &[], &[10, 20, 30], &options).unwrap();
+
+        let image = png_decode_rgba_with_metadata(&png).unwrap();
+        assert_eq!((image.width, image.height), (1, 1));
+        assert_eq!(image.pixels, [10, 20, 30, 255]);
+        let icc = image.metadata.icc_profile.unwrap();
+        assert_eq!(icc.name, "Display P3");
+        assert_eq!(icc.profile, profile);
+
+        // a broken profile is skipped
+        let png = png_with(PNGColorType::Greyscale, &[chunk(b"iCCP", b"P3\0\0nope")]);
+        assert_eq!(png_metadata(&png).unwrap().icc_profile, None);
+    }
+
+    #[test]
+    fn test_decompression_limits() {
+        // small chunks expanding beyond the limits
+        let bomb = |len: usize| miniz::compress_to_vec(&vec![0; len], 9);
+        let ztxt = [b"Comment\0\0".as_slice(), &bomb(MAX_TEXT_LEN + 1)].concat();
+        let itxt = [b"Title\0\x01\0\0\0".as_slice(), &bomb(MAX_TEXT_LEN + 1)].concat();
+        let iccp = [b"P3\0\0".as_slice(), &bomb(MAX_PROFILE_LEN + 1)].concat();
+        assert!(ztxt.len() < 2048 && iccp.len() < 32768);
+        let chunks = [
+            chunk(b"zTXt", &ztxt),
+            chunk(b"iTXt", &itxt),
+            chunk(b"iCCP", &iccp),
+        ];
+        let png = png_with(PNGColorType::Greyscale, &chunks);
+
+        let warnings = RefCell::new(Vec::new());
+        let options = InflateOptions {
+            warnings: Some(&warnings),
+            ..Default::default()
+        };
+        let meta = png_metadata_with_options(&png, &options).unwrap();
+        assert_eq!(meta, PngMetadata::default());
+        let too_large = |name: &[u8; 4]| Warning::PngChunkTooLarge {
+            chunk: u32::from_be_bytes(*name),
+        };
+        assert_eq!(
+            warnings.take(),
+            [too_large(b"zTXt"), too_large(b"iTXt"), too_large(b"iCCP")]
+        );
+
+        // the limits can be lowered
+        let ztxt = [b"Comment\0\0".as_slice(), &bomb(1000)].concat();
+        let png = png_with(PNGColorType::Greyscale, &[chunk(b"zTXt", &ztxt)]);
+        assert_eq!(png_metadata(&png).unwrap().texts.len(), 1);
+        let options = InflateOptions {
+            warnings: Some(&warnings),
+            max_output: 999,
+            ..Default::default()
+        };
+        let meta = png_metadata_with_options(&png, &options).unwrap();
+        assert_eq!(meta.texts, []);
+        assert_eq!(warnings.take(), [too_large(b"zTXt")]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_gamma_to_srgb() {
+        // linear data (gamma 1.0) is brightened, alpha is kept
+        let gamma_1 = [0, 1, 0x86, 0xa0];
+        let png = png_with(PNGColorType::Greyscale, &[chunk(b"gAMA", &gamma_1)]);
+        let image = png_decode_rgba_srgb(&png).unwrap();
+        assert_eq!(image.metadata.gamma, Some(100000));
+        assert_eq!(image.pixels, [13, 13, 13, 255]);
+
+        let mut rgba = [0, 64, 128, 128, 255, 255, 255, 0];
+        gamma_to_srgb(&mut rgba, 100000);
+        assert_eq!(rgba, [0, 137, 188, 128, 255, 255, 255, 0]);
+
+        // sRGB data and images with sRGB or iCCP chunks are left alone
+        let mut same = [1, 64, 128, 200];
+        gamma_to_srgb(&mut same, SRGB_GAMMA);
+        assert_eq!(same, [1, 64, 128, 200]);
+        let chunks = [chunk(b"gAMA", &gamma_1), chunk(b"sRGB", &[0])];
+        let png = png_with(PNGColorType::Greyscale, &chunks);
+        assert_eq!(png_decode_rgba_srgb(&png).unwrap().pixels, [1, 1, 1, 255]);
+    }
+}
@@ -1,6 +1,15 @@
+#![cfg(feature = "png")]
+
 // ----------------------------------------------------------------------------
 mod test {
-    use miniz::png_read::png_read;
+    use miniz::buffer::Heap;
+    use miniz::inflate::{InflateOptions, Progress};
+    use miniz::png_read::{
+        decoded_size, png_decode_rgba, png_info, png_read, png_read_into, png_read_region,
+        png_read_rows, png_read_with_options, png_significant_bits, png_transparency, to_planar,
+        to_rgba, to_rgba_with_significant_bits, Error, PNGChunkIHDR, PNGColorType, PngScratch,
+        Transparency,
+    };
 
     #[allow(dead_code)]
     fn write_result(data: Vec<u8>) {
@@ -15,9 +24,12 @@ mod test {
         }
     }
 
+    include!("../assets/png/adam7.rs");
     include!("../assets/png/basn0g01.rs");
     include!("../assets/png/basn2c08.rs");
     include!("../assets/png/f99n0g04.rs");
+    include!("../assets/png/img1.rs");
+    include!("../assets/png/pngtest.rs");
 
     #[test]
     fn test_basn0g01() {
@@ -35,6 +47,50 @@ mod test {
         assert_eq!(data, BASN2C08_IMG, "data");
     }
 
+    #[test]
+    fn test_checksum() {
+        // last byte of the IDAT CRC, the IEND chunk has 12 bytes
+        let mut png = BASN2C08_PNG.to_vec();
+        let pos = png.len() - 13;
+        png[pos] ^= 1;
+        assert_eq!(png_read(&png).err(), Some(Error::ChecksumMismatch));
+
+        let options = InflateOptions {
+            verify_checksums: false,
+            ..Default::default()
+        };
+        let (_, _, data) = png_read_with_options(&png, &mut Heap, &options).unwrap();
+        assert_eq!(data, BASN2C08_IMG);
+
+        // IHDR CRC
+        let mut png = BASN2C08_PNG.to_vec();
+        png[8 + 8 + 13] ^= 1;
+        assert_eq!(png_read(&png).err(), Some(Error::ChecksumMismatch));
+        assert!(png_read_with_options(&png, &mut Heap, &options).is_ok());
+    }
+
+    #[test]
+    fn test_adler32() {
+        // the IDAT stream's Adler-32 is corrupt, the chunk CRCs are intact
+        let mut png = BASN2C08_PNG[..8].to_vec();
+        for c in miniz::png_read::png_chunks(BASN2C08_PNG).unwrap() {
+            let c = c.unwrap();
+            let mut data = c.data.to_vec();
+            if c.name() == "IDAT" {
+                *data.last_mut().unwrap() ^= 1;
+            }
+            chunk(&mut png, c.name().as_bytes(), &data);
+        }
+        assert_eq!(png_read(&png).err(), Some(Error::ChecksumMismatch));
+
+        let options = InflateOptions {
+            verify_checksums: false,
+            ..Default::default()
+        };
+        let (_, _, data) = png_read_with_options(&png, &mut Heap, &options).unwrap();
+        assert_eq!(data, BASN2C08_IMG);
+    }
+
     #[test]
     fn test_f99n0g04() {
         let (png, plte, data) = png_read(F99N0G04_PNG).unwrap();
@@ -42,4 +98,828 @@ mod test {
         assert_eq!(plte.len(), 0, "palette");
         assert_eq!(data, F99N0G04_IMG, "data");
     }
+
+    fn chunk(png: &mut Vec<u8>, ty: &[u8], data: &[u8]) {
+        png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        let start = png.len();
+        png.extend_from_slice(ty);
+        png.extend_from_slice(data);
+        let crc = miniz::checksum::crc32(0, &png[start..]);
+        png.extend_from_slice(&crc.to_be_bytes());
+    }
+
+    // zlib stream with a single stored block
+    fn stored(data: &[u8]) -> Vec<u8> {
+        let len = data.len() as u16;
+        let mut zlib = vec![0x78, 0x01, 0x01];
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(data);
+        zlib.extend_from_slice(&miniz::checksum::adler32(1, data).to_be_bytes());
+        zlib
+    }
+
+    #[test]
+    fn test_average_first_row() {
+        // 4x1 gray, the Average filter on the first row predicts half the left neighbor
+        let mut png = vec![137, 80, 78, 71, 13, 10, 26, 10];
+        chunk(&mut png, b"IHDR", &[0, 0, 0, 4, 0, 0, 0, 1, 8, 0, 0, 0, 0]);
+        chunk(&mut png, b"IDAT", &stored(&[3, 10, 5, 5, 5]));
+        chunk(&mut png, b"IEND", &[]);
+
+        let (_, _, data) = png_read(&png).unwrap();
+        assert_eq!(data, [3, 10, 10, 10, 10]);
+    }
+
+    #[test]
+    fn test_excess_image_data() {
+        // 4x1 gray with the data of a second scanline, plain and interlaced
+        for (interlace, rows) in [(0, &[0, 1, 2, 3, 4][..]), (1, &[0, 1, 0, 2, 0, 3, 4][..])] {
+            let mut png = vec![137, 80, 78, 71, 13, 10, 26, 10];
+            chunk(
+                &mut png,
+                b"IHDR",
+                &[0, 0, 0, 4, 0, 0, 0, 1, 8, 0, 0, 0, interlace],
+            );
+            let mut raw = rows.to_vec();
+            let idat = miniz::compress_to_vec(&raw, 6);
+            chunk(&mut png, b"IDAT", &idat);
+            chunk(&mut png, b"IEND", &[]);
+            assert!(png_read(&png).is_ok());
+
+            let mut png = png[..33].to_vec();
+            raw.extend_from_slice(&[0, 9, 9, 9, 9]);
+            chunk(&mut png, b"IDAT", &miniz::compress_to_vec(&raw, 6));
+            chunk(&mut png, b"IEND", &[]);
+            assert_eq!(png_read(&png).err(), Some(Error::ExcessImageData));
+            let decoded = miniz::png_stream::PngDecoder::new().feed(&png, |_, _| ());
+            assert_eq!(decoded, Err(Error::ExcessImageData), "{interlace}");
+        }
+    }
+
+    #[test]
+    fn test_unfilter_all() {
+        // reference unfiltering of RFC 2083 section 6
+        fn unfilter(data: &mut [u8], bpl: usize, bpp: usize) {
+            let paeth = |a: u8, b: u8, c: u8| {
+                let p = a as i16 + b as i16 - c as i16;
+                let (pa, pb, pc) = (
+                    (p - a as i16).abs(),
+                    (p - b as i16).abs(),
+                    (p - c as i16).abs(),
+                );
+                if pa <= pb && pa <= pc {
+                    a
+                } else if pb <= pc {
+                    b
+                } else {
+                    c
+                }
+            };
+            let mut prev = vec![0u8; bpl - 1];
+            for row in data.chunks_exact_mut(bpl) {
+                let filter = row[0];
+                let line = &mut row[1..];
+                for i in 0..line.len() {
+                    let a = if i >= bpp { line[i - bpp] } else { 0 };
+                    let c = if i >= bpp { prev[i - bpp] } else { 0 };
+                    let b = prev[i];
+                    let pred = match filter {
+                        0 => 0,
+                        1 => a,
+                        2 => b,
+                        3 => ((a as u16 + b as u16) / 2) as u8,
+                        _ => paeth(a, b, c),
+                    };
+                    line[i] = line[i].wrapping_add(pred);
+                }
+                prev.copy_from_slice(line);
+            }
+        }
+
+        // random filtered scanlines of every filter type, also on the first row
+        let mut seed = 0x2545f491u32;
+        let mut random = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed as u8
+        };
+        for (color_type, channels) in [(0u8, 1), (4, 2), (2, 3), (6, 4)] {
+            let width = 37;
+            let bpl = width * channels + 1;
+            let mut raw = Vec::new();
+            for y in 0..10 {
+                raw.push([0, 1, 2, 3, 4][y % 5]);
+                raw.extend((0..bpl - 1).map(|_| random()));
+            }
+            raw.extend_from_within(..bpl);
+            raw[10 * bpl] = 4;
+            let height = raw.len() / bpl;
+
+            let mut ihdr = (width as u32).to_be_bytes().to_vec();
+            ihdr.extend_from_slice(&(height as u32).to_be_bytes());
+            ihdr.extend_from_slice(&[8, color_type, 0, 0, 0]);
+            let mut png = vec![137, 80, 78, 71, 13, 10, 26, 10];
+            chunk(&mut png, b"IHDR", &ihdr);
+            chunk(&mut png, b"IDAT", &miniz::compress_to_vec(&raw, 6));
+            chunk(&mut png, b"IEND", &[]);
+
+            let (_, _, data) = png_read(&png).unwrap();
+            let mut expected = raw.clone();
+            unfilter(&mut expected, bpl, channels);
+            for row in expected.chunks_exact_mut(bpl) {
+                row[0] = 0;
+            }
+            let rows = |data: &[u8]| -> Vec<Vec<u8>> {
+                data.chunks_exact(bpl)
+                    .map(|row| row[1..].to_vec())
+                    .collect()
+            };
+            assert_eq!(rows(&data), rows(&expected), "{channels} channels");
+        }
+    }
+
+    #[test]
+    fn test_to_rgba() {
+        let (png, plte, data) = png_read(BASN0G01_PNG).unwrap();
+        let rgba = to_rgba(&png, &plte, &data).unwrap();
+        assert_eq!(rgba.len(), 32 * 32 * 4, "size");
+        assert_eq!(&rgba[0..4], &[255, 255, 255, 255], "white");
+        assert_eq!(&rgba[31 * 4..32 * 4], &[0, 0, 0, 255], "black");
+
+        let (png, plte, data) = png_read(BASN2C08_PNG).unwrap();
+        let rgba = to_rgba(&png, &plte, &data).unwrap();
+        assert_eq!(&rgba[0..3], &data[1..4], "rgb");
+        assert_eq!(rgba[3], 255, "alpha");
+    }
+
+    #[test]
+    fn test_png_decode_rgba() {
+        use miniz::checksum::crc32;
+
+        // truecolor, greyscale at 1 bit, indexed at 1 bit and truecolor with alpha
+        let (width, height, rgba) = png_decode_rgba(BASN2C08_PNG).unwrap();
+        assert_eq!((width, height), (32, 32));
+        let (png, plte, data) = png_read(BASN2C08_PNG).unwrap();
+        assert_eq!(rgba, to_rgba(&png, &plte, &data).unwrap());
+
+        let (_, _, rgba) = png_decode_rgba(BASN0G01_PNG).unwrap();
+        assert_eq!(&rgba[0..4], &[255, 255, 255, 255]);
+
+        let (_, _, mut rgba) = png_decode_rgba(IMG1_PNG).unwrap();
+        rgba.iter_mut().skip(3).step_by(4).for_each(|a| *a = 255);
+        assert_eq!(crc32(0, &rgba), IMG1_RGBA_CRC);
+
+        // pngtest.png has 5 significant bits in all four channels
+        let (_, _, rgba) = png_decode_rgba(PNGTEST_PNG).unwrap();
+        let (png, plte, data) = png_read(PNGTEST_PNG).unwrap();
+        let mut expected = to_rgba(&png, &plte, &data).unwrap();
+        assert_eq!(crc32(0, &expected), PNGTEST_RGBA_CRC);
+        let scale = |v: u8| ((v as u32 >> 3) * 255 + 15) / 31;
+        expected.iter_mut().for_each(|v| *v = scale(*v) as u8);
+        assert_eq!(rgba, expected);
+
+        // 2 bit grey levels 0, 1, 2, 3 scale to 0, 85, 170, 255
+        let (width, _, rgba) = png_decode_rgba(ADAM7_G02_5X3_REF_PNG).unwrap();
+        assert_eq!(width, 5);
+        let greys: Vec<u8> = rgba.chunks_exact(4).take(4).map(|px| px[0]).collect();
+        assert_eq!(greys, [0, 255, 170, 85]);
+        assert!(rgba
+            .chunks_exact(4)
+            .all(|px| px[1] == px[0] && px[3] == 255));
+
+        assert_eq!(
+            png_decode_rgba(&BASN2C08_PNG[..40]).err(),
+            Some(Error::BufferUnderrun)
+        );
+    }
+
+    #[test]
+    fn test_expand_palette() {
+        use miniz::png_read::expand_palette;
+        use miniz::png_write::*;
+
+        let plte = [0x0000ff, 0x00ff00, 0xff0000];
+        let png = png_write_indexed(3, 1, &plte, &[2, 1, 0], &EncodeOptions::default()).unwrap();
+        let (ihdr, plte, data) = png_read(&png).unwrap();
+        assert_eq!(ihdr.bit_depth, 2);
+        let (color_type, rgb) = expand_palette(&ihdr, &plte, None, &data).unwrap();
+        assert_eq!(color_type, PNGColorType::TrueColor);
+        assert_eq!(rgb, [0, 0, 255, 0, 255, 0, 255, 0, 0]);
+
+        let trns = Transparency::Palette(vec![0, 128]);
+        let (color_type, rgba) = expand_palette(&ihdr, &plte, Some(&trns), &data).unwrap();
+        assert_eq!(color_type, PNGColorType::TrueColorAlpha);
+        assert_eq!(rgba, [0, 0, 255, 255, 0, 255, 0, 128, 255, 0, 0, 0]);
+
+        // index 3 is beyond the palette
+        assert_eq!(
+            expand_palette(&ihdr, &plte, None, &[0, 0b1001_1100]),
+            Err(Error::InvalidPalette)
+        );
+        assert_eq!(
+            expand_palette(&ihdr, &plte, None, &[0]),
+            Err(Error::BufferUnderrun)
+        );
+        let grey = PNGChunkIHDR {
+            color_type: PNGColorType::Greyscale,
+            ..ihdr
+        };
+        assert_eq!(
+            expand_palette(&grey, &plte, None, &data),
+            Err(Error::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_transparency() {
+        use miniz::png_write::*;
+
+        // indexed at 2 bits with alpha for the first two of three palette entries
+        let trns = [ExtraChunk {
+            chunk_type: *b"tRNS",
+            data: &[0, 128],
+            placement: ChunkPlacement::BeforeIdat,
+        }];
+        let options = EncodeOptions {
+            extra_chunks: &trns,
+            ..Default::default()
+        };
+        let plte = [0x0000ff, 0x00ff00, 0xff0000];
+        let png = png_write_indexed(3, 1, &plte, &[2, 1, 0], &options).unwrap();
+        let palette = Transparency::Palette(vec![0, 128]);
+        assert_eq!(png_transparency(&png), Ok(Some(palette)));
+        let (_, _, rgba) = png_decode_rgba(&png).unwrap();
+        assert_eq!(rgba, [0, 0, 255, 255, 0, 255, 0, 128, 255, 0, 0, 0]);
+
+        // greyscale at 4 bits, the key matches the raw sample before scaling
+        let ihdr = PNGChunkIHDR {
+            width: 2,
+            height: 1,
+            bit_depth: 4,
+            color_type: PNGColorType::Greyscale,
+            compression: 0,
+            filter: 0,
+            interlace: 0,
+        };
+        let options = EncodeOptions {
+            color_key: Some(ColorKey::Grey(3)),
+            ..Default::default()
+        };
+        let png = png_write(&ihdr, &[], &[0x3f], &options).unwrap();
+        assert_eq!(png_transparency(&png), Ok(Some(Transparency::Grey(3))));
+        let (_, _, rgba) = png_decode_rgba(&png).unwrap();
+        assert_eq!(rgba, [51, 51, 51, 0, 255, 255, 255, 255]);
+
+        // truecolor
+        let ihdr = PNGChunkIHDR {
+            bit_depth: 8,
+            color_type: PNGColorType::TrueColor,
+            ..ihdr
+        };
+        let options = EncodeOptions {
+            color_key: Some(ColorKey::Rgb(1, 2, 3)),
+            ..Default::default()
+        };
+        let png = png_write(&ihdr, &[], &[1, 2, 3, 1, 2, 4], &options).unwrap();
+        let key = Transparency::Rgb(1, 2, 3);
+        assert_eq!(png_transparency(&png), Ok(Some(key)));
+        let (_, _, rgba) = png_decode_rgba(&png).unwrap();
+        assert_eq!(rgba, [1, 2, 3, 0, 1, 2, 4, 255]);
+
+        // no tRNS chunk
+        assert_eq!(png_transparency(BASN2C08_PNG), Ok(None));
+    }
+
+    #[test]
+    fn test_significant_bits() {
+        use miniz::png_write::*;
+
+        let sbit = |data: &'static [u8]| {
+            [ExtraChunk {
+                chunk_type: *b"sBIT",
+                data,
+                placement: ChunkPlacement::BeforePlte,
+            }]
+        };
+
+        // truecolor with 5 significant bits, the low bits are dropped and the rest rescaled
+        let ihdr = PNGChunkIHDR {
+            width: 2,
+            height: 1,
+            bit_depth: 8,
+            color_type: PNGColorType::TrueColor,
+            compression: 0,
+            filter: 0,
+            interlace: 0,
+        };
+        let chunks = sbit(&[5, 5, 5]);
+        let options = EncodeOptions {
+            extra_chunks: &chunks,
+            ..Default::default()
+        };
+        let png = png_write(&ihdr, &[], &[0x80, 0xff, 0x08, 0x87, 0x00, 0x07], &options).unwrap();
+        assert_eq!(png_significant_bits(&png), Ok(Some([5, 5, 5, 8])));
+        let (_, _, rgba) = png_decode_rgba(&png).unwrap();
+        assert_eq!(rgba, [132, 255, 8, 255, 132, 0, 0, 255]);
+
+        // greyscale at 4 bits with 3 significant bits
+        let ihdr = PNGChunkIHDR {
+            bit_depth: 4,
+            color_type: PNGColorType::Greyscale,
+            ..ihdr
+        };
+        let chunks = sbit(&[3]);
+        let options = EncodeOptions {
+            extra_chunks: &chunks,
+            ..Default::default()
+        };
+        let png = png_write(&ihdr, &[], &[0xf8], &options).unwrap();
+        assert_eq!(png_significant_bits(&png), Ok(Some([3, 3, 3, 4])));
+        let (_, _, rgba) = png_decode_rgba(&png).unwrap();
+        assert_eq!(rgba, [255, 255, 255, 255, 146, 146, 146, 255]);
+
+        // indexed, sBIT applies to the palette entries
+        let chunks = sbit(&[4, 4, 4]);
+        let options = EncodeOptions {
+            extra_chunks: &chunks,
+            ..Default::default()
+        };
+        let png = png_write_indexed(1, 1, &[0xff1012], &[0], &options).unwrap();
+        let (_, _, rgba) = png_decode_rgba(&png).unwrap();
+        assert_eq!(rgba, [17, 17, 255, 255]);
+
+        // without sBIT the samples use the full bit depth, values beyond it are invalid
+        assert_eq!(png_significant_bits(BASN2C08_PNG), Ok(None));
+        let chunks = sbit(&[5]);
+        let options = EncodeOptions {
+            extra_chunks: &chunks,
+            ..Default::default()
+        };
+        let png = png_write(&ihdr, &[], &[0xf8], &options).unwrap();
+        assert_eq!(png_significant_bits(&png), Err(Error::InvalidFormat));
+    }
+
+    #[test]
+    fn test_to_planar() {
+        let (png, plte, data) = png_read(BASN2C08_PNG).unwrap();
+        let rgba = to_rgba(&png, &plte, &data).unwrap();
+        let planar = to_planar(&png, &plte, &data, 40).unwrap();
+        assert_eq!(planar.stride, 40, "stride");
+        for plane in &planar.planes {
+            assert_eq!(plane.len(), 40 * 32, "plane size");
+        }
+
+        // pixel (5, 7)
+        let px = &rgba[(7 * 32 + 5) * 4..(7 * 32 + 6) * 4];
+        for (c, plane) in planar.planes.iter().enumerate() {
+            assert_eq!(plane[7 * 40 + 5], px[c], "channel {c}");
+            assert_eq!(plane[7 * 40 + 35], 0, "padding");
+        }
+
+        let res = to_planar(&png, &plte, &data, 16);
+        assert_eq!(res.err(), Some(Error::BufferError), "stride too small");
+    }
+
+    #[test]
+    fn test_progress() {
+        let produced = std::cell::Cell::new(0);
+        let callback = |_, bytes_out| produced.set(bytes_out);
+        let options = InflateOptions {
+            progress: Some(Progress {
+                interval: 64,
+                callback: &callback,
+            }),
+            ..Default::default()
+        };
+        let (_, _, data) = png_read_with_options(BASN2C08_PNG, &mut Heap, &options).unwrap();
+        assert_eq!(data, BASN2C08_IMG, "data");
+        assert_eq!(produced.get(), 32 * (3 * 32 + 1), "filtered scanline bytes");
+    }
+
+    #[test]
+    fn test_warnings() {
+        use miniz::warning::Warning;
+
+        // insert a tEXt chunk with a broken CRC and a good tIME chunk after IHDR
+        let mut png = BASN2C08_PNG[..33].to_vec();
+        png.extend_from_slice(b"\x00\x00\x00\x01tEXta\x00\x00\x00\x00");
+        png.extend_from_slice(b"\x00\x00\x00\x00tIME");
+        png.extend_from_slice(&miniz::checksum::crc32(0, b"tIME").to_be_bytes());
+        png.extend_from_slice(&BASN2C08_PNG[33..]);
+        png.push(0);
+
+        let warnings = std::cell::RefCell::new(Vec::new());
+        let options = InflateOptions {
+            warnings: Some(&warnings),
+            ..Default::default()
+        };
+        let (_, _, data) = png_read_with_options(&png, &mut Heap, &options).unwrap();
+        assert_eq!(data, BASN2C08_IMG, "data");
+
+        let text = u32::from_be_bytes(*b"tEXt");
+        let time = u32::from_be_bytes(*b"tIME");
+        let warnings = warnings.into_inner();
+        assert_eq!(warnings[0], Warning::PngChunkCrc { chunk: text });
+        assert_eq!(warnings[1], Warning::PngChunkSkipped { chunk: time });
+        assert_eq!(warnings.last(), Some(&Warning::PngDataAfterIend { len: 1 }));
+
+        // the strict profile fails on the ancillary CRC and the data after IEND
+        use miniz::inflate::DecodeProfile;
+        let strict = InflateOptions::profile(DecodeProfile::Strict);
+        let result = png_read_with_options(&png, &mut Heap, &strict);
+        assert_eq!(result.err(), Some(Error::ChecksumMismatch));
+        let mut png = BASN2C08_PNG.to_vec();
+        assert!(png_read_with_options(&png, &mut Heap, &strict).is_ok());
+        png.push(0);
+        let result = png_read_with_options(&png, &mut Heap, &strict);
+        assert_eq!(result.err(), Some(Error::InvalidPng));
+        let lenient = InflateOptions::profile(DecodeProfile::Lenient);
+        assert!(png_read_with_options(&png, &mut Heap, &lenient).is_ok());
+    }
+
+    #[test]
+    fn test_interlaced() {
+        use miniz::checksum::crc32;
+
+        let (png, plte, data) = png_read(PNGTEST_PNG).unwrap();
+        assert_eq!((png.width, png.height, png.interlace), (91, 69, 1));
+        let rgba = to_rgba(&png, &plte, &data).unwrap();
+        assert_eq!(crc32(0, &rgba), PNGTEST_RGBA_CRC, "pngtest");
+
+        let (png, plte, data) = png_read(IMG1_PNG).unwrap();
+        assert_eq!((png.width, png.height, png.bit_depth), (556, 20, 1));
+        let mut rgba = to_rgba(&png, &plte, &data).unwrap();
+        rgba.iter_mut().skip(3).step_by(4).for_each(|a| *a = 255);
+        assert_eq!(crc32(0, &rgba), IMG1_RGBA_CRC, "img1");
+    }
+
+    #[test]
+    fn test_read_into() {
+        let mut scratch = PngScratch::default();
+        let mut out = vec![0; 64 * 1024];
+        for png in [BASN2C08_PNG, PNGTEST_PNG, BASN2C08_PNG, PNGTEST_PNG] {
+            let ihdr = png_info(png).unwrap();
+            let size = decoded_size(&ihdr).unwrap();
+            let (_, plte, data) = png_read(png).unwrap();
+            assert_eq!(png_read_into(png, &mut out, &mut scratch), Ok(size));
+            assert_eq!(&out[..size], &data[..]);
+            assert_eq!(scratch.palette(), &plte[..]);
+        }
+        // 2 scratch buffers for the interlaced passes, only 1 is allocated
+        assert_eq!(scratch.reused(), 1);
+
+        let size = decoded_size(&png_info(BASN2C08_PNG).unwrap()).unwrap();
+        assert_eq!(
+            png_read_into(BASN2C08_PNG, &mut out[..size - 1], &mut scratch),
+            Err(Error::BufferError)
+        );
+    }
+
+    #[test]
+    fn test_read_rows() {
+        for png in [BASN0G01_PNG, BASN2C08_PNG, PNGTEST_PNG] {
+            let (ihdr, plte, data) = png_read(png).unwrap();
+            let bpl = data.len() / ihdr.height;
+            let mut rows = 0;
+            let (header, palette) = png_read_rows(png, |y, row| {
+                assert_eq!(y, rows);
+                assert_eq!(row, &data[y * bpl + 1..(y + 1) * bpl]);
+                rows += 1;
+            })
+            .unwrap();
+            assert_eq!((header, palette), (ihdr, plte));
+            assert_eq!(rows, ihdr.height);
+        }
+
+        // truncated within the single deflate block, the rows before the end are reported
+        let mut rows = 0;
+        let result = png_read_rows(&BASN2C08_PNG[..BASN2C08_PNG.len() - 40], |_, _| rows += 1);
+        assert_eq!(result.err(), Some(Error::MissingIEND));
+        assert!(rows > 0 && rows < 32, "{rows}");
+    }
+
+    #[test]
+    fn test_read_region() {
+        // 1 bit greyscale, truecolor and interlaced images against cropped RGBA
+        for png in [BASN0G01_PNG, BASN2C08_PNG, PNGTEST_PNG, IMG1_PNG] {
+            let (width, height, rgba) = png_decode_rgba(png).unwrap();
+            for (x, y, w, h) in [(0, 0, width, height), (3, 5, 7, 2), (width - 1, 9, 1, 11)] {
+                let (ihdr, plte, data) = png_read_region(png, x, y, w, h).unwrap();
+                assert_eq!((ihdr.width, ihdr.height, ihdr.interlace), (w, h, 0));
+                let cropped: Vec<u8> = rgba
+                    .chunks_exact(width * 4)
+                    .skip(y)
+                    .take(h)
+                    .flat_map(|row| row[x * 4..(x + w) * 4].to_vec())
+                    .collect();
+                let trns = png_transparency(png).unwrap();
+                let sbit = png_significant_bits(png).unwrap();
+                let region =
+                    to_rgba_with_significant_bits(&ihdr, &plte, trns.as_ref(), sbit, &data);
+                assert_eq!(region.unwrap(), cropped, "{x} {y} {w} {h}");
+            }
+            let outside = png_read_region(png, width - 1, 0, 2, 1);
+            assert_eq!(outside.err(), Some(Error::BufferError));
+            let empty = png_read_region(png, 0, 0, 1, 0);
+            assert_eq!(empty.err(), Some(Error::BufferError));
+        }
+
+        // decoding stops after the region, the rest of the file is not needed
+        let ihdr = PNGChunkIHDR {
+            width: 256,
+            height: 512,
+            bit_depth: 8,
+            color_type: PNGColorType::Greyscale,
+            compression: 0,
+            filter: 0,
+            interlace: 0,
+        };
+        let mut x = 0x2324u32;
+        let noise: Vec<u8> = (0..256 * 512)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 17;
+                x ^= x << 5;
+                x as u8
+            })
+            .collect();
+        let options = miniz::png_write::EncodeOptions::default();
+        let png = miniz::png_write::png_write(&ihdr, &[], &noise, &options).unwrap();
+        let (_, _, data) = png_read_region(&png[..png.len() / 2], 16, 8, 4, 2).unwrap();
+        assert_eq!(data[1..5], noise[8 * 256 + 16..8 * 256 + 20]);
+        assert_eq!(data[6..], noise[9 * 256 + 16..9 * 256 + 20]);
+    }
+
+    #[test]
+    #[cfg(feature = "threads")]
+    fn test_read_parallel() {
+        use miniz::png_read::png_read_parallel;
+        use miniz::png_write::{png_write, EncodeOptions, RowFilter};
+
+        for png in [BASN0G01_PNG, BASN2C08_PNG, PNGTEST_PNG, IMG1_PNG] {
+            assert_eq!(png_read_parallel(png), png_read(png));
+        }
+
+        // many batches, every filter type, gradients with some noise
+        let ihdr = PNGChunkIHDR {
+            width: 300,
+            height: 400,
+            bit_depth: 8,
+            color_type: PNGColorType::TrueColorAlpha,
+            compression: 0,
+            filter: 0,
+            interlace: 0,
+        };
+        let mut x = 0x2329u32;
+        let image: Vec<u8> = (0..300 * 400 * 4)
+            .map(|i| {
+                x ^= x << 13;
+                x ^= x >> 17;
+                x ^= x << 5;
+                (i / 7 + i / 1200) as u8 ^ (x as u8 & 3)
+            })
+            .collect();
+        let options = EncodeOptions {
+            filter: RowFilter::Adaptive,
+            ..Default::default()
+        };
+        let png = png_write(&ihdr, &[], &image, &options).unwrap();
+        let decoded = png_read_parallel(&png).unwrap();
+        assert_eq!(decoded, png_read(&png).unwrap());
+
+        // a truncated image data stream fails like with png_read
+        let idat = png.windows(4).position(|w| w == b"IDAT").unwrap() - 4;
+        let len = u32::from_be_bytes(png[idat..idat + 4].try_into().unwrap()) as usize;
+        let mut truncated = png[..33].to_vec();
+        chunk(&mut truncated, b"IDAT", &png[idat + 8..idat + 8 + len / 2]);
+        chunk(&mut truncated, b"IEND", &[]);
+        assert!(png_read_parallel(&truncated).is_err());
+        assert_eq!(png_read_parallel(&truncated), png_read(&truncated));
+    }
+
+    #[test]
+    fn test_interlaced_small() {
+        // passes without columns or rows have no scanlines at all
+        for (interlaced, reference) in [
+            (ADAM7_G02_5X3_PNG, ADAM7_G02_5X3_REF_PNG),
+            (ADAM7_G04_1X1_PNG, ADAM7_G04_1X1_REF_PNG),
+            (ADAM7_G04_13X11_PNG, ADAM7_G04_13X11_REF_PNG),
+        ] {
+            let (png, plte, data) = png_read(interlaced).unwrap();
+            let (ref_png, ref_plte, ref_data) = png_read(reference).unwrap();
+            assert_eq!(png.interlace, 1);
+            assert_eq!(data, ref_data, "{}x{}", png.width, png.height);
+            assert_eq!(
+                to_rgba(&png, &plte, &data).unwrap(),
+                to_rgba(&ref_png, &ref_plte, &ref_data).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_adam7_preview() {
+        use miniz::png_read::{Adam7Pass, Adam7Preview};
+
+        let passes = std::cell::RefCell::new(Vec::new());
+        let callback = |pass: &Adam7Pass| {
+            let bpl = pass.width * 4 + 1;
+            assert_eq!(pass.data.len(), pass.height * bpl, "pass {}", pass.pass);
+            passes
+                .borrow_mut()
+                .push((pass.pass, pass.width, pass.height, pass.image.to_vec()));
+        };
+        let options = InflateOptions {
+            adam7_preview: Some(Adam7Preview {
+                callback: &callback,
+            }),
+            ..Default::default()
+        };
+        let (_, _, data) = png_read_with_options(PNGTEST_PNG, &mut Heap, &options).unwrap();
+
+        let passes = passes.into_inner();
+        let sizes: Vec<_> = passes.iter().map(|p| (p.0, p.1, p.2)).collect();
+        assert_eq!(
+            sizes,
+            [
+                (1, 12, 9),
+                (2, 11, 9),
+                (3, 23, 9),
+                (4, 23, 18),
+                (5, 46, 17),
+                (6, 45, 35),
+                (7, 91, 34)
+            ]
+        );
+
+        // the top left pixel is complete after the first pass, the image after the last
+        assert_eq!(passes[0].3[1..5], data[1..5]);
+        assert_eq!(passes[6].3, data);
+    }
+
+    #[test]
+    fn test_chunk_order() {
+        use miniz::warning::Warning;
+
+        let ihdr = &BASN2C08_PNG[16..29];
+        let idat: Vec<u8> = miniz::png_read::png_chunks(BASN2C08_PNG)
+            .unwrap()
+            .map(Result::unwrap)
+            .filter(|c| c.name() == "IDAT")
+            .flat_map(|c| c.data.to_vec())
+            .collect();
+        let (head, tail) = idat.split_at(idat.len() / 2);
+        let build = |chunks: &[(&[u8; 4], &[u8])]| {
+            let mut png = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+            for (ty, data) in chunks {
+                chunk(&mut png, *ty, data);
+            }
+            chunk(&mut png, b"IEND", &[]);
+            png
+        };
+        let plte: &[u8] = &[0, 0, 0, 255, 255, 255];
+        let gama: &[u8] = &[0, 0, 0xb1, 0x8f];
+        let fine = [
+            build(&[(b"IHDR", ihdr), (b"IDAT", head), (b"IDAT", tail)]),
+            build(&[
+                (b"IHDR", ihdr),
+                (b"gAMA", gama),
+                (b"PLTE", plte),
+                (b"tRNS", &[0, 0, 0, 0, 0, 0]),
+                (b"IDAT", &idat),
+                (b"tEXt", b"a\0b"),
+                (b"tIME", &[7, 234, 10, 16, 12, 0, 0]),
+            ]),
+        ];
+        for png in fine {
+            assert_eq!(png_read(&png).unwrap().2, BASN2C08_IMG);
+        }
+
+        let idat_split = build(&[
+            (b"IHDR", ihdr),
+            (b"IDAT", head),
+            (b"tEXt", b"a\0b"),
+            (b"IDAT", tail),
+        ]);
+        let broken = [
+            (
+                &idat_split,
+                Error::MisplacedChunk(u32::from_be_bytes(*b"IDAT")),
+            ),
+            (
+                &build(&[(b"IHDR", ihdr), (b"IDAT", &idat), (b"PLTE", plte)]),
+                Error::MisplacedChunk(u32::from_be_bytes(*b"PLTE")),
+            ),
+            (
+                &build(&[(b"IHDR", ihdr), (b"IHDR", ihdr), (b"IDAT", &idat)]),
+                Error::DuplicateChunk(u32::from_be_bytes(*b"IHDR")),
+            ),
+            (
+                &build(&[
+                    (b"IHDR", ihdr),
+                    (b"PLTE", plte),
+                    (b"PLTE", plte),
+                    (b"IDAT", &idat),
+                ]),
+                Error::DuplicateChunk(u32::from_be_bytes(*b"PLTE")),
+            ),
+            (
+                &build(&[
+                    (b"IHDR", ihdr),
+                    (b"gAMA", gama),
+                    (b"gAMA", gama),
+                    (b"IDAT", &idat),
+                ]),
+                Error::DuplicateChunk(u32::from_be_bytes(*b"gAMA")),
+            ),
+            (
+                &build(&[
+                    (b"IHDR", ihdr),
+                    (b"PLTE", plte),
+                    (b"gAMA", gama),
+                    (b"IDAT", &idat),
+                ]),
+                Error::MisplacedChunk(u32::from_be_bytes(*b"gAMA")),
+            ),
+            (
+                &build(&[
+                    (b"IHDR", ihdr),
+                    (b"bKGD", &[0; 6]),
+                    (b"PLTE", plte),
+                    (b"IDAT", &idat),
+                ]),
+                Error::MisplacedChunk(u32::from_be_bytes(*b"PLTE")),
+            ),
+            (
+                &build(&[(b"IHDR", ihdr), (b"IDAT", &idat), (b"pHYs", &[0; 9])]),
+                Error::MisplacedChunk(u32::from_be_bytes(*b"pHYs")),
+            ),
+        ];
+        for (png, err) in broken {
+            assert_eq!(png_read(png).err(), Some(err));
+        }
+
+        // lenient decoding warns and goes on
+        let warnings = std::cell::RefCell::new(Vec::new());
+        let options = InflateOptions {
+            lenient_chunk_order: true,
+            warnings: Some(&warnings),
+            ..Default::default()
+        };
+        let (_, _, data) = png_read_with_options(&idat_split, &mut Heap, &options).unwrap();
+        assert_eq!(data, BASN2C08_IMG);
+        let chunk = u32::from_be_bytes(*b"IDAT");
+        assert!(warnings
+            .into_inner()
+            .contains(&Warning::PngChunkMisplaced { chunk }));
+    }
+
+    #[test]
+    fn test_ihdr_validation() {
+        let header = |width: u32, bit_depth: u8, color_type: u8, interlace: u8| {
+            let mut ihdr = width.to_be_bytes().to_vec();
+            ihdr.extend_from_slice(&1u32.to_be_bytes());
+            ihdr.extend_from_slice(&[bit_depth, color_type, 0, 0, interlace]);
+            let mut png = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+            chunk(&mut png, b"IHDR", &ihdr);
+            png_info(&png)
+        };
+
+        // table 11.1 of the specification
+        let allowed: [(u8, &[u8]); 5] = [
+            (0, &[1, 2, 4, 8, 16]),
+            (2, &[8, 16]),
+            (3, &[1, 2, 4, 8]),
+            (4, &[8, 16]),
+            (6, &[8, 16]),
+        ];
+        for (color_type, depths) in allowed {
+            for bit_depth in 0..=32 {
+                let result = header(1, bit_depth, color_type, 0);
+                if depths.contains(&bit_depth) {
+                    assert_eq!(result.unwrap().bit_depth, bit_depth as usize);
+                } else {
+                    assert_eq!(
+                        result,
+                        Err(Error::InvalidFormat),
+                        "{color_type} {bit_depth}"
+                    );
+                }
+            }
+        }
+
+        // the cases of the PngSuite corrupted files xc1n0g08, xc9n2c08, xd0n2c08, xd3n2c08 and
+        // xd9n2c08
+        assert_eq!(header(1, 8, 1, 0), Err(Error::InvalidColorFormat(1)));
+        assert_eq!(header(1, 8, 9, 0), Err(Error::InvalidColorFormat(9)));
+        assert_eq!(header(1, 0, 2, 0), Err(Error::InvalidFormat));
+        assert_eq!(header(1, 3, 2, 0), Err(Error::InvalidFormat));
+        assert_eq!(header(1, 99, 2, 0), Err(Error::InvalidFormat));
+
+        assert_eq!(header(0, 8, 0, 0), Err(Error::InvalidFormat));
+        assert_eq!(header(1 << 31, 8, 0, 0), Err(Error::InvalidFormat));
+        assert_eq!(header(1, 8, 0, 2), Err(Error::InvalidFormat));
+        assert!(header((1 << 31) - 1, 8, 0, 1).is_ok());
+    }
 }
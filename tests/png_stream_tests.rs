@@ -0,0 +1,158 @@
+#![cfg(feature = "png")]
+
+// ----------------------------------------------------------------------------
+mod test {
+    use miniz::checksum::crc32;
+    use miniz::png_read::{to_rgba, Error, PNGChunkIHDR, PNGColorType};
+    use miniz::png_stream::PngDecoder;
+    use miniz::png_write::{png_write, EncodeOptions};
+
+    include!("../assets/png/basn0g01.rs");
+    include!("../assets/png/basn2c08.rs");
+    include!("../assets/png/pngtest.rs");
+
+    // feeds `png` in pieces of `piece` bytes, returns the reported scanlines
+    fn decode(png: &[u8], piece: usize) -> (PngDecoder, Vec<u8>) {
+        let mut decoder = PngDecoder::new();
+        let mut image = Vec::new();
+        let mut next_row = 0;
+        for part in png.chunks(piece) {
+            let consumed = decoder
+                .feed(part, |y, row| {
+                    assert_eq!(y, next_row);
+                    next_row += 1;
+                    image.extend_from_slice(row);
+                })
+                .unwrap();
+            assert_eq!(consumed, part.len());
+        }
+        (decoder, image)
+    }
+
+    // `png_read` output without the filter bytes
+    fn strip(data: &[u8], height: usize) -> Vec<u8> {
+        let bpl = data.len() / height;
+        data.chunks_exact(bpl)
+            .flat_map(|row| row[1..].to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn test_pieces() {
+        for (png, expected) in [
+            (BASN0G01_PNG, &BASN0G01_IMG[..]),
+            (BASN2C08_PNG, &BASN2C08_IMG),
+        ] {
+            for piece in [1, 7, 100, png.len()] {
+                let (decoder, image) = decode(png, piece);
+                assert!(decoder.is_finished(), "piece {piece}");
+                assert_eq!(image, strip(expected, 32), "piece {piece}");
+                assert_eq!(decoder.rows_decoded(), 32);
+            }
+        }
+
+        // interlaced
+        for piece in [3, 100, PNGTEST_PNG.len()] {
+            let (decoder, image) = decode(PNGTEST_PNG, piece);
+            let ihdr = decoder.header().unwrap();
+            let data: Vec<u8> = image
+                .chunks_exact(image.len() / ihdr.height)
+                .flat_map(|row| [&[0][..], row].concat())
+                .collect();
+            let rgba = to_rgba(ihdr, decoder.palette(), &data).unwrap();
+            assert_eq!(crc32(0, &rgba), PNGTEST_RGBA_CRC, "piece {piece}");
+        }
+    }
+
+    #[test]
+    fn test_progressive() {
+        let ihdr = PNGChunkIHDR {
+            width: 256,
+            height: 512,
+            bit_depth: 8,
+            color_type: PNGColorType::Greyscale,
+            compression: 0,
+            filter: 0,
+            interlace: 0,
+        };
+        let mut seed = 1u32;
+        let data: Vec<u8> = (0..256 * 512)
+            .map(|_| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                (seed >> 24) as u8
+            })
+            .collect();
+        let png = png_write(&ihdr, &[], &data, &EncodeOptions::default()).unwrap();
+
+        // rows are reported before the whole file has arrived
+        let mut decoder = PngDecoder::new();
+        let mut image = Vec::new();
+        let half = png.len() / 2;
+        decoder
+            .feed(&png[..half], |_, row| image.extend_from_slice(row))
+            .unwrap();
+        let rows = decoder.rows_decoded();
+        assert!(rows > 0 && rows < ihdr.height, "{rows} rows");
+        assert!(!decoder.is_finished());
+
+        decoder
+            .feed(&png[half..], |_, row| image.extend_from_slice(row))
+            .unwrap();
+        assert!(decoder.is_finished());
+        assert_eq!(image, data);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut png = BASN2C08_PNG.to_vec();
+        png[0] = 0;
+        assert_eq!(
+            PngDecoder::new().feed(&png, |_, _| ()),
+            Err(Error::InvalidSignature)
+        );
+
+        // last byte of the IDAT CRC, the IEND chunk has 12 bytes
+        let mut png = BASN2C08_PNG.to_vec();
+        let pos = png.len() - 13;
+        png[pos] ^= 1;
+        assert_eq!(
+            PngDecoder::new().feed(&png, |_, _| ()),
+            Err(Error::ChecksumMismatch)
+        );
+        let mut decoder = PngDecoder::new();
+        decoder.set_verify_checksums(false);
+        assert_eq!(decoder.feed(&png, |_, _| ()), Ok(png.len()));
+
+        // truncated stream and IEND before the image is complete
+        let mut decoder = PngDecoder::new();
+        decoder
+            .feed(&BASN2C08_PNG[..BASN2C08_PNG.len() - 40], |_, _| ())
+            .unwrap();
+        assert!(!decoder.is_finished());
+        let mut png = BASN2C08_PNG.to_vec();
+        png[8 + 8 + 7] += 1;
+        let mut decoder = PngDecoder::new();
+        decoder.set_verify_checksums(false);
+        assert_eq!(decoder.feed(&png, |_, _| ()), Err(Error::InvalidPng));
+        assert_eq!(decoder.rows_decoded(), 32);
+
+        // a second IHDR, accepted and skipped by lenient decoding
+        let mut png = BASN2C08_PNG[..33].to_vec();
+        png.extend_from_slice(&BASN2C08_PNG[8..]);
+        let ihdr = u32::from_be_bytes(*b"IHDR");
+        assert_eq!(
+            PngDecoder::new().feed(&png, |_, _| ()),
+            Err(Error::DuplicateChunk(ihdr))
+        );
+        let mut decoder = PngDecoder::new();
+        decoder.set_lenient_chunk_order(true);
+        assert_eq!(decoder.feed(&png, |_, _| ()), Ok(png.len()));
+        assert!(decoder.is_finished());
+
+        // data after IEND is not consumed
+        let mut trailing = BASN2C08_PNG.to_vec();
+        trailing.extend_from_slice(b"junk");
+        let consumed = PngDecoder::new().feed(&trailing, |_, _| ()).unwrap();
+        assert_eq!(consumed, BASN2C08_PNG.len());
+    }
+}
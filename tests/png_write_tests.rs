@@ -0,0 +1,440 @@
+#![cfg(feature = "png")]
+
+// ----------------------------------------------------------------------------
+mod test {
+    use miniz::decompress_to_vec_zlib;
+    use miniz::png_read::{png_chunks, png_read, Error, PNGChunkIHDR, PNGColorType};
+    use miniz::png_write::{
+        indexed_bit_depth, pack_indices, png_repair, png_write, png_write_indexed, ChunkPlacement,
+        ColorKey, ColorProfile, EncodeOptions, EncodePreset, ExtraChunk, IdatCompression,
+        RepairOptions, RepairReport, RowFilter,
+    };
+
+    include!("../assets/png/basn2c08.rs");
+
+    // decoded scanlines without their filter bytes
+    fn rows(data: &[u8], row_bytes: usize) -> Vec<u8> {
+        data.chunks_exact(row_bytes + 1)
+            .flat_map(|row| row[1..].to_vec())
+            .collect()
+    }
+
+    fn chunk_names(png: &[u8]) -> Vec<String> {
+        png_chunks(png)
+            .unwrap()
+            .map(|c| c.unwrap().name())
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let (ihdr, plte, data) = png_read(BASN2C08_PNG).unwrap();
+        let png = png_write(&ihdr, &plte, &rows(&data, 96), &EncodeOptions::default()).unwrap();
+        assert_eq!(chunk_names(&png), ["IHDR", "IDAT", "IEND"]);
+        assert_eq!(
+            rows(&png_read(&png).unwrap().2, 96),
+            rows(&BASN2C08_IMG, 96)
+        );
+
+        let indexed = PNGChunkIHDR {
+            width: 3,
+            height: 2,
+            bit_depth: 2,
+            color_type: PNGColorType::IndexedColor,
+            compression: 0,
+            filter: 0,
+            interlace: 0,
+        };
+        let plte = [0xff0000, 0x00ff00, 0x0000ff];
+        let png = png_write(&indexed, &plte, &[0x24, 0x90], &EncodeOptions::default()).unwrap();
+        let (_, decoded_plte, data) = png_read(&png).unwrap();
+        assert_eq!(decoded_plte, plte);
+        assert_eq!(data, [0, 0x24, 0, 0x90]);
+
+        let options = EncodeOptions::default();
+        assert_eq!(
+            png_write(&indexed, &[], &[0x24, 0x90], &options).err(),
+            Some(Error::InvalidPalette)
+        );
+        assert_eq!(
+            png_write(&indexed, &plte, &[0x24], &options).err(),
+            Some(Error::BufferError)
+        );
+    }
+
+    #[test]
+    fn test_color_profile() {
+        let (ihdr, plte, data) = png_read(BASN2C08_PNG).unwrap();
+        let data = rows(&data, 96);
+        let profile: Vec<u8> = (0..3000u32).map(|i| (i % 7) as u8).collect();
+
+        let options = EncodeOptions {
+            color_profile: ColorProfile::Icc {
+                name: "Display P3",
+                profile: &profile,
+            },
+            ..Default::default()
+        };
+        let png = png_write(&ihdr, &plte, &data, &options).unwrap();
+        assert_eq!(chunk_names(&png), ["IHDR", "iCCP", "IDAT", "IEND"]);
+        let iccp = png_chunks(&png).unwrap().nth(1).unwrap().unwrap();
+        assert!(iccp.crc_ok());
+        assert!(iccp.data.starts_with(b"Display P3\0\0"));
+        assert_eq!(
+            decompress_to_vec_zlib(&iccp.data[12..]),
+            Ok(profile.clone())
+        );
+        assert_eq!(
+            rows(&png_read(&png).unwrap().2, 96),
+            rows(&BASN2C08_IMG, 96)
+        );
+
+        let options = EncodeOptions {
+            color_profile: ColorProfile::Srgb(0),
+            ..Default::default()
+        };
+        let png = png_write(&ihdr, &plte, &data, &options).unwrap();
+        assert_eq!(chunk_names(&png), ["IHDR", "sRGB", "IDAT", "IEND"]);
+
+        for name in ["", " leading", "double  space", &"x".repeat(80)] {
+            let options = EncodeOptions {
+                color_profile: ColorProfile::Icc {
+                    name,
+                    profile: &profile,
+                },
+                ..Default::default()
+            };
+            assert_eq!(
+                png_write(&ihdr, &plte, &data, &options).err(),
+                Some(Error::InvalidFormat),
+                "{name:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_color_key() {
+        let (ihdr, plte, data) = png_read(BASN2C08_PNG).unwrap();
+        let data = rows(&data, 96);
+        let options = EncodeOptions {
+            color_profile: ColorProfile::Srgb(0),
+            color_key: Some(ColorKey::Rgb(0xff, 0, 0x12)),
+            ..Default::default()
+        };
+        let png = png_write(&ihdr, &plte, &data, &options).unwrap();
+        assert_eq!(chunk_names(&png), ["IHDR", "sRGB", "tRNS", "IDAT", "IEND"]);
+        let trns = png_chunks(&png).unwrap().nth(2).unwrap().unwrap();
+        assert_eq!(trns.data, [0, 0xff, 0, 0, 0, 0x12]);
+
+        let grey = PNGChunkIHDR {
+            width: 8,
+            height: 1,
+            bit_depth: 1,
+            color_type: PNGColorType::Greyscale,
+            compression: 0,
+            filter: 0,
+            interlace: 0,
+        };
+        let key = |color_key| EncodeOptions {
+            color_key: Some(color_key),
+            ..Default::default()
+        };
+        let png = png_write(&grey, &[], &[0x0f], &key(ColorKey::Grey(1))).unwrap();
+        let trns = png_chunks(&png).unwrap().nth(1).unwrap().unwrap();
+        assert_eq!((trns.name().as_str(), trns.data), ("tRNS", &[0, 1][..]));
+
+        // the sample must fit the bit depth and the key must match the color type
+        for color_key in [ColorKey::Grey(2), ColorKey::Rgb(0, 0, 0)] {
+            assert_eq!(
+                png_write(&grey, &[], &[0x0f], &key(color_key)).err(),
+                Some(Error::InvalidFormat)
+            );
+        }
+        assert_eq!(
+            png_write(&ihdr, &plte, &data, &key(ColorKey::Grey(0))).err(),
+            Some(Error::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_extra_chunks() {
+        let (ihdr, plte, data) = png_read(BASN2C08_PNG).unwrap();
+        let chunk = |chunk_type: &[u8; 4], data, placement| ExtraChunk {
+            chunk_type: *chunk_type,
+            data,
+            placement,
+        };
+        let extra = [
+            chunk(b"edIt", b"layers=3", ChunkPlacement::AfterIdat),
+            chunk(b"gaMe", b"level 1", ChunkPlacement::BeforeIdat),
+            chunk(b"prVt", b"", ChunkPlacement::BeforePlte),
+            chunk(b"gaMe", b"level 2", ChunkPlacement::BeforeIdat),
+        ];
+        let options = EncodeOptions {
+            color_profile: ColorProfile::Srgb(0),
+            extra_chunks: &extra,
+            ..Default::default()
+        };
+        let png = png_write(&ihdr, &plte, &rows(&data, 96), &options).unwrap();
+        assert_eq!(
+            chunk_names(&png),
+            ["IHDR", "sRGB", "prVt", "gaMe", "gaMe", "IDAT", "edIt", "IEND"]
+        );
+        let chunks: Vec<_> = png_chunks(&png).unwrap().map(|c| c.unwrap()).collect();
+        assert_eq!(chunks[4].data, b"level 2");
+        assert_eq!(
+            rows(&png_read(&png).unwrap().2, 96),
+            rows(&BASN2C08_IMG, 96)
+        );
+
+        // critical, reserved and non-letter chunk types are rejected
+        for chunk_type in [b"ABCD", b"abcd", b"ab1D"] {
+            let extra = [chunk(chunk_type, b"", ChunkPlacement::AfterIdat)];
+            let options = EncodeOptions {
+                extra_chunks: &extra,
+                ..Default::default()
+            };
+            assert_eq!(
+                png_write(&ihdr, &plte, &rows(&data, 96), &options).err(),
+                Some(Error::InvalidFormat)
+            );
+        }
+    }
+
+    #[test]
+    fn test_indexed() {
+        assert_eq!(
+            [1, 2, 3, 4, 5, 16, 17, 256].map(indexed_bit_depth),
+            [1, 1, 2, 2, 4, 4, 8, 8]
+        );
+        assert_eq!(pack_indices(&[1, 0, 1], 3, 1).unwrap(), [0xa0]);
+        assert_eq!(pack_indices(&[1, 2, 3, 0, 3], 5, 2).unwrap(), [0x6c, 0xc0]);
+        assert_eq!(pack_indices(&[0xf, 1, 2, 3], 2, 4).unwrap(), [0xf1, 0x23]);
+        assert_eq!(pack_indices(&[2], 1, 1).err(), Some(Error::InvalidPalette));
+        assert_eq!(pack_indices(&[0xff], 1, 8).unwrap(), [0xff]);
+        assert_eq!(pack_indices(&[0; 3], 2, 1).err(), Some(Error::BufferError));
+
+        // 9x3 pixels with 3 colors are stored at 2 bits per pixel, 3 bytes per row
+        let plte = [0x000000, 0xffffff, 0x0000ff];
+        let indices: Vec<u8> = (0..27).map(|i| (i % 3) as u8).collect();
+        let png = png_write_indexed(9, 3, &plte, &indices, &EncodeOptions::default()).unwrap();
+        let (ihdr, decoded_plte, data) = png_read(&png).unwrap();
+        assert_eq!(ihdr.bit_depth, 2);
+        assert_eq!(decoded_plte, plte);
+        assert_eq!(data.len(), 3 * 4);
+        assert_eq!(data[..4], [0, 0x18, 0x61, 0x80]);
+
+        let options = EncodeOptions::default();
+        assert_eq!(
+            png_write_indexed(9, 3, &plte[..2], &indices, &options).err(),
+            Some(Error::InvalidPalette)
+        );
+    }
+
+    #[test]
+    fn test_fast() {
+        let (ihdr, plte, data) = png_read(BASN2C08_PNG).unwrap();
+        let png = png_write(&ihdr, &plte, &rows(&data, 96), &EncodeOptions::fast()).unwrap();
+        assert_eq!(
+            rows(&png_read(&png).unwrap().2, 96),
+            rows(&BASN2C08_IMG, 96)
+        );
+
+        // screenshot-like frame: flat background, a gradient bar and a repeated text-like pattern
+        let (width, height) = (300usize, 40usize);
+        let ihdr = PNGChunkIHDR {
+            width,
+            height,
+            bit_depth: 8,
+            color_type: PNGColorType::TrueColor,
+            compression: 0,
+            filter: 0,
+            interlace: 0,
+        };
+        let mut frame = vec![0xf0u8; width * height * 3];
+        for (y, row) in frame.chunks_exact_mut(width * 3).enumerate() {
+            for (x, px) in row.chunks_exact_mut(3).enumerate() {
+                if y < 8 {
+                    px.copy_from_slice(&[x as u8, 0x40, 0x80]);
+                } else if y % 6 == 0 && x % 7 < 3 {
+                    px.copy_from_slice(&[0, 0, 0]);
+                }
+            }
+        }
+
+        let stored = png_write(&ihdr, &[], &frame, &EncodeOptions::default()).unwrap();
+        let fast = png_write(&ihdr, &[], &frame, &EncodeOptions::fast()).unwrap();
+        assert!(fast.len() * 4 < stored.len());
+        assert_eq!(rows(&png_read(&fast).unwrap().2, width * 3), frame);
+
+        // each setting on its own
+        for (filter, compression) in [
+            (RowFilter::Up, IdatCompression::Stored),
+            (RowFilter::None, IdatCompression::Rle),
+        ] {
+            let options = EncodeOptions {
+                filter,
+                compression,
+                ..Default::default()
+            };
+            let png = png_write(&ihdr, &[], &frame, &options).unwrap();
+            assert_eq!(rows(&png_read(&png).unwrap().2, width * 3), frame);
+        }
+    }
+
+    #[test]
+    fn test_filters() {
+        use miniz::deflate::CompressionLevel;
+
+        // noisy gradient where each filter wins on some rows
+        let image = |row_bytes: usize, height: usize| {
+            (0..row_bytes * height)
+                .map(|i| ((i % row_bytes) * (i / row_bytes + 1) + i * i % 7) as u8)
+                .collect::<Vec<u8>>()
+        };
+
+        let filters = [
+            RowFilter::None,
+            RowFilter::Sub,
+            RowFilter::Up,
+            RowFilter::Adaptive,
+        ];
+        let compressions = [
+            IdatCompression::Stored,
+            IdatCompression::Rle,
+            IdatCompression::Deflate(CompressionLevel::BestSpeed),
+            IdatCompression::Deflate(CompressionLevel::BestCompression),
+        ];
+        let formats = [
+            (PNGColorType::Greyscale, 1, 2),
+            (PNGColorType::Greyscale, 8, 13),
+            (PNGColorType::GreyscaleAplha, 8, 26),
+            (PNGColorType::TrueColor, 8, 39),
+            (PNGColorType::TrueColorAlpha, 8, 52),
+            (PNGColorType::IndexedColor, 4, 7),
+        ];
+        let plte: Vec<u32> = (0..16).map(|i| i * 0x0f0f0f).collect();
+
+        for (color_type, bit_depth, row_bytes) in formats {
+            let ihdr = PNGChunkIHDR {
+                width: 13,
+                height: 7,
+                bit_depth,
+                color_type,
+                compression: 0,
+                filter: 0,
+                interlace: 0,
+            };
+            let data = image(row_bytes, ihdr.height);
+            for filter in filters {
+                for compression in compressions {
+                    let options = EncodeOptions {
+                        filter,
+                        compression,
+                        ..Default::default()
+                    };
+                    let png = png_write(&ihdr, &plte, &data, &options).unwrap();
+                    let decoded = png_read(&png).unwrap().2;
+                    assert_eq!(rows(&decoded, row_bytes), data, "{color_type:?} {filter:?}");
+                }
+            }
+        }
+
+        // adaptive filtering with full deflate beats the fast preset
+        let (ihdr, plte, data) = png_read(BASN2C08_PNG).unwrap();
+        let data = rows(&data, 96);
+        let png = png_write(&ihdr, &plte, &data, &EncodeOptions::small()).unwrap();
+        assert_eq!(rows(&png_read(&png).unwrap().2, 96), data);
+        let fast = png_write(&ihdr, &plte, &data, &EncodeOptions::fast()).unwrap();
+        assert!(png.len() < fast.len());
+    }
+
+    #[test]
+    fn test_presets() {
+        // smooth gradients with a little noise, filtering pays off
+        let ihdr = PNGChunkIHDR {
+            width: 64,
+            height: 48,
+            bit_depth: 8,
+            color_type: PNGColorType::TrueColor,
+            compression: 0,
+            filter: 0,
+            interlace: 0,
+        };
+        let data: Vec<u8> = (0..64 * 48 * 3)
+            .map(|i| ((i % 192) / 3 + (i / 192) * 2 + i * i % 3) as u8)
+            .collect();
+
+        let mut sizes = Vec::new();
+        for preset in [
+            EncodePreset::Fast,
+            EncodePreset::Default,
+            EncodePreset::Best,
+        ] {
+            let options = EncodeOptions::preset(preset);
+            let png = png_write(&ihdr, &[], &data, &options).unwrap();
+            assert_eq!(rows(&png_read(&png).unwrap().2, 64 * 3), data, "{preset:?}");
+            sizes.push(png.len());
+        }
+        assert!(sizes[2] <= sizes[1] && sizes[1] < sizes[0], "{sizes:?}");
+
+        let options = EncodeOptions::preset(EncodePreset::Fast);
+        assert_eq!(options.filter, RowFilter::None);
+        assert_eq!(
+            EncodeOptions::preset(EncodePreset::default()).filter,
+            RowFilter::Adaptive
+        );
+    }
+
+    #[test]
+    fn test_repair() {
+        let options = RepairOptions::default();
+        let (png, report) = png_repair(BASN2C08_PNG, &options).unwrap();
+        assert_eq!(png, BASN2C08_PNG);
+        assert_eq!(report, RepairReport::default());
+
+        // flipped bits in the IHDR CRC and in the gAMA and IDAT length fields
+        let mut damaged = BASN2C08_PNG.to_vec();
+        damaged[29] ^= 0x01;
+        damaged[36] ^= 0x40;
+        damaged[51] ^= 0x10;
+        damaged.extend_from_slice(b"trailing");
+        assert!(png_read(&damaged).is_err());
+
+        let (png, report) = png_repair(&damaged, &options).unwrap();
+        assert_eq!(png, BASN2C08_PNG);
+        let expected = RepairReport {
+            crcs_fixed: 1,
+            lengths_fixed: 2,
+            ..Default::default()
+        };
+        assert_eq!(report, expected);
+
+        let crcs_only = RepairOptions {
+            fix_lengths: false,
+            ..Default::default()
+        };
+        // without length recovery the chunks after gAMA are lost
+        let (png, _) = png_repair(&damaged, &crcs_only).unwrap();
+        assert_eq!(chunk_names(&png), ["IHDR", "gAMA", "IEND"]);
+    }
+
+    #[test]
+    fn test_repair_order() {
+        // IDAT moved in front of IHDR and gAMA, IEND missing
+        let png = BASN2C08_PNG;
+        let mut damaged = png[..8].to_vec();
+        damaged.extend_from_slice(&png[49..133]);
+        damaged.extend_from_slice(&png[8..49]);
+
+        let (repaired, report) = png_repair(&damaged, &RepairOptions::default()).unwrap();
+        assert_eq!(repaired, png);
+        assert!(report.reordered && report.iend_added);
+        assert_eq!(chunk_names(&repaired), ["IHDR", "gAMA", "IDAT", "IEND"]);
+
+        assert_eq!(
+            png_repair(b"not a png", &RepairOptions::default()).err(),
+            Some(Error::InvalidSignature)
+        );
+    }
+}
@@ -0,0 +1,63 @@
+#![cfg(feature = "png")]
+
+// ----------------------------------------------------------------------------
+mod test {
+    use miniz::png_read::Error;
+    use miniz::pnm::{png_to_pnm, rgba_to_pnm, PnmFormat};
+
+    include!("../assets/png/basn0g01.rs");
+    include!("../assets/png/basn2c08.rs");
+
+    #[test]
+    fn test_pgm() {
+        let pgm = png_to_pnm(BASN0G01_PNG, PnmFormat::Pgm).unwrap();
+        let header = b"P5\n32 32\n255\n";
+        assert!(pgm.starts_with(header));
+        let pixels = &pgm[header.len()..];
+        assert_eq!(pixels.len(), 32 * 32);
+
+        // one bit per pixel, MSB first, after the filter byte of each row
+        for (y, row) in BASN0G01_IMG.chunks_exact(5).enumerate() {
+            for x in 0..32 {
+                let bit = (row[1 + x / 8] >> (7 - x % 8)) & 1;
+                assert_eq!(pixels[y * 32 + x], bit * 255, "pixel {x}, {y}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_ppm_pam() {
+        let ppm = png_to_pnm(BASN2C08_PNG, PnmFormat::Ppm).unwrap();
+        let header = b"P6\n32 32\n255\n";
+        assert!(ppm.starts_with(header));
+        let rgb = BASN2C08_IMG
+            .chunks_exact(97)
+            .flat_map(|row| &row[1..])
+            .copied()
+            .collect::<Vec<_>>();
+        assert_eq!(&ppm[header.len()..], rgb);
+
+        let pam = png_to_pnm(BASN2C08_PNG, PnmFormat::Pam).unwrap();
+        let header = b"P7\nWIDTH 32\nHEIGHT 32\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n";
+        assert!(pam.starts_with(header));
+        let rgba = &pam[header.len()..];
+        assert_eq!(rgba.len(), 32 * 32 * 4);
+        assert!(rgba
+            .chunks_exact(4)
+            .zip(rgb.chunks_exact(3))
+            .all(|(a, b)| a[..3] == *b && a[3] == 255));
+    }
+
+    #[test]
+    fn test_rgba_to_pnm() {
+        let rgba = [255, 0, 0, 128, 0, 0, 255, 255];
+        assert_eq!(
+            rgba_to_pnm(2, 1, &rgba, PnmFormat::Pgm).unwrap(),
+            b"P5\n2 1\n255\n\x4c\x1d"
+        );
+        assert_eq!(
+            rgba_to_pnm(2, 2, &rgba, PnmFormat::Ppm),
+            Err(Error::BufferUnderrun)
+        );
+    }
+}
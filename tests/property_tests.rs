@@ -0,0 +1,189 @@
+// Randomized round trips through the encoders and decoders and corrupted inputs for the parsers,
+// reproducible from fixed seeds. The fuzz targets in `fuzz/` explore further with cargo-fuzz.
+
+// ----------------------------------------------------------------------------
+mod test {
+    use miniz::deflate::{deflate_with_strategy, CompressionLevel, Strategy};
+    use miniz::inflate::{inflate_scan_size, inflate_with_options, InflateOptions};
+    use miniz::stream::{Compress, FlushCompress, Inflater, Status};
+    use miniz::{compress_to_vec, decompress_to_vec, decompress_to_vec_zlib};
+
+    const CASES: usize = 100;
+
+    const STRATEGIES: [Strategy; 5] = [
+        Strategy::Default,
+        Strategy::Filtered,
+        Strategy::HuffmanOnly,
+        Strategy::Rle,
+        Strategy::Fixed,
+    ];
+
+    // xorshift64*
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+    }
+
+    // Noise, runs, small alphabets and copies of earlier data up to 40000 bytes back, in
+    // random order. Most inputs are short, some span several blocks.
+    fn input(rng: &mut Rng) -> Vec<u8> {
+        let len = match rng.below(4) {
+            0 => rng.below(64),
+            1 | 2 => rng.below(4096),
+            _ => rng.below(100_000),
+        };
+        let mut data = Vec::with_capacity(len);
+        while data.len() < len {
+            let n = (1 + rng.below(2000)).min(len - data.len());
+            match rng.below(4) {
+                0 => data.extend((0..n).map(|_| rng.next() as u8)),
+                1 => data.resize(data.len() + n, rng.next() as u8),
+                2 => {
+                    let alphabet = 1 + rng.below(8) as u8;
+                    data.extend((0..n).map(|_| b'a' + (rng.next() as u8 % alphabet)));
+                }
+                _ if data.is_empty() => {}
+                _ => {
+                    let start = data.len() - 1 - rng.below(data.len().min(40000));
+                    let end = (start + n).min(data.len());
+                    data.extend_from_within(start..end);
+                }
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn test_deflate_round_trip() {
+        let mut rng = Rng(0x2315);
+        for case in 0..CASES {
+            let src = input(&mut rng);
+            let level = CompressionLevel::Level(rng.below(10) as u8);
+            let strategy = STRATEGIES[rng.below(STRATEGIES.len())];
+
+            let mut packed = Vec::new();
+            deflate_with_strategy(&mut packed, &src, level, strategy);
+            assert_eq!(inflate_scan_size(&packed), Ok(src.len()), "{case}");
+            let unpacked = decompress_to_vec(&packed);
+            assert_eq!(
+                unpacked.as_ref(),
+                Ok(&src),
+                "{case}: {level:?} {strategy:?}"
+            );
+
+            let zlib = compress_to_vec(&src, level.level());
+            assert_eq!(decompress_to_vec_zlib(&zlib).as_ref(), Ok(&src), "{case}");
+        }
+    }
+
+    #[test]
+    fn test_stream_round_trip() {
+        let mut rng = Rng(0x5eed);
+        for case in 0..CASES / 4 {
+            let src = input(&mut rng);
+
+            // compress in random pieces with random sync and full flushes
+            let mut compress = Compress::new(rng.below(10) as u8, true);
+            let mut packed = vec![0; src.len() + src.len() / 2 + 1024];
+            let mut pos = 0;
+            loop {
+                let n = rng.below(src.len() - pos + 1);
+                let flush = if pos + n == src.len() {
+                    FlushCompress::Finish
+                } else {
+                    [
+                        FlushCompress::None,
+                        FlushCompress::Sync,
+                        FlushCompress::Full,
+                    ][rng.below(3)]
+                };
+                let out = compress.total_out() as usize;
+                let status = compress.run(&src[pos..pos + n], &mut packed[out..], flush);
+                pos += n;
+                if status == Status::StreamEnd {
+                    break;
+                }
+            }
+            packed.truncate(compress.total_out() as usize);
+
+            // decode with random input and output piece sizes
+            let mut inflater = Inflater::new(true);
+            let mut out = vec![0; src.len()];
+            let (mut sptr, mut dptr) = (0, 0);
+            while !inflater.is_finished() || dptr < src.len() {
+                let end = (sptr + 1 + rng.below(5000)).min(packed.len());
+                let out_end = (dptr + 1 + rng.below(5000)).min(out.len());
+                let (_, consumed, produced) = inflater
+                    .decompress(&packed[sptr..end], &mut out[dptr..out_end])
+                    .unwrap();
+                sptr += consumed;
+                dptr += produced;
+                assert!(sptr <= packed.len(), "{case}");
+            }
+            assert_eq!(out, src, "{case}");
+        }
+    }
+
+    #[test]
+    fn test_corrupt_streams() {
+        // flipped bits and truncation fail or decode, they never panic
+        let mut rng = Rng(0xbad);
+        for _ in 0..CASES {
+            let src = input(&mut rng);
+            let options = InflateOptions {
+                strict_end_of_input: rng.below(2) == 0,
+                ..Default::default()
+            };
+            let mut packed = Vec::new();
+            let strategy = STRATEGIES[rng.below(STRATEGIES.len())];
+            deflate_with_strategy(&mut packed, &src, CompressionLevel::Level(6), strategy);
+            if packed.is_empty() {
+                continue;
+            }
+            for _ in 0..1 + rng.below(4) {
+                let bit = rng.below(8 * packed.len());
+                packed[bit / 8] ^= 1 << (bit % 8);
+            }
+            packed.truncate(packed.len() - rng.below(packed.len().min(8)));
+            let mut out = vec![0; src.len() + 1024];
+            let _ = inflate_with_options(&mut out, &packed, &options);
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "png", feature = "zip"))]
+    fn test_corrupt_containers() {
+        // the same for PNG and ZIP parsing, a short version of the fuzz targets
+        let seeds: [&[u8]; 4] = [
+            include_bytes!("../assets/png/pngtest.png"),
+            include_bytes!("../assets/png/adam7_g04_13x11.png"),
+            include_bytes!("../assets/zip/zip64.zip"),
+            include_bytes!("../assets/zip/bzip2.zip"),
+        ];
+        let mut rng = Rng(0xf022);
+        for _ in 0..CASES {
+            let mut data = seeds[rng.below(seeds.len())].to_vec();
+            for _ in 0..1 + rng.below(4) {
+                let pos = rng.below(data.len());
+                data[pos] = rng.next() as u8;
+            }
+            let _ = miniz::png_read::png_read(&data);
+            let _ = miniz::png_meta::png_metadata(&data);
+            if let Ok(files) = miniz::zip_read::zip_open_lossy(&data) {
+                for file in files.iter().filter(|file| file.size <= 1 << 20) {
+                    let _ = miniz::zip_read::zip_read(&data, &files, &file.name);
+                }
+            }
+        }
+    }
+}
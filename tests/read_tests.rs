@@ -0,0 +1,79 @@
+#![cfg(feature = "std")]
+
+// ----------------------------------------------------------------------------
+mod test {
+    use miniz::compress_to_vec;
+    use miniz::read::{GzipReader, InflateReader, ZlibReader};
+    use std::io::Read;
+
+    include!("../assets/gz/lines.rs");
+
+    // content of LINES_GZ, compressed with gzip -9 -n
+    fn lines() -> Vec<u8> {
+        (0..15000u32)
+            .flat_map(|i| format!("line {i}: {}\n", i * i % 9973).into_bytes())
+            .collect()
+    }
+
+    // hands out at most 7 bytes per call
+    struct Trickle<'a>(&'a [u8]);
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = buf.len().min(self.0.len()).min(7);
+            buf[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Ok(len)
+        }
+    }
+
+    // reads with a small buffer to cross block boundaries
+    fn read_small(mut reader: impl Read) -> std::io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        let mut buf = [0u8; 1000];
+        loop {
+            match reader.read(&mut buf)? {
+                0 => return Ok(out),
+                len => out.extend_from_slice(&buf[..len]),
+            }
+        }
+    }
+
+    #[test]
+    fn test_gzip_reader() {
+        let mut out = Vec::new();
+        GzipReader::new(LINES_GZ).read_to_end(&mut out).unwrap();
+        assert_eq!(out, lines());
+        let out = read_small(GzipReader::new(Trickle(LINES_GZ))).unwrap();
+        assert_eq!(out, lines());
+
+        // corrupted CRC-32 and truncated trailer
+        let mut gz = LINES_GZ.to_vec();
+        let len = gz.len();
+        gz[len - 8] ^= 1;
+        let err = read_small(GzipReader::new(&gz[..])).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(read_small(GzipReader::new(&LINES_GZ[..len - 3])).is_err());
+    }
+
+    #[test]
+    fn test_zlib_reader() {
+        let data = lines();
+        for level in [0, 1, 9] {
+            let zlib = compress_to_vec(&data, level);
+            let out = read_small(ZlibReader::new(Trickle(&zlib))).unwrap();
+            assert_eq!(out, data);
+
+            let mut raw = Vec::new();
+            let mut reader = InflateReader::new(&zlib[2..zlib.len() - 4]);
+            reader.read_to_end(&mut raw).unwrap();
+            assert_eq!(raw, data);
+        }
+
+        let mut zlib = compress_to_vec(b"hello", 6);
+        let len = zlib.len();
+        zlib[len - 1] ^= 1;
+        let err = read_small(ZlibReader::new(&zlib[..])).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(read_small(ZlibReader::new(&b"\x78"[..])).is_err());
+    }
+}
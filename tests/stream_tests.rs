@@ -0,0 +1,645 @@
+// ----------------------------------------------------------------------------
+mod test {
+    use miniz::inflate::InflateMetrics;
+    use miniz::stream::{
+        BlockBoundary, BlockType, Compress, Decompress, FlushCompress, FlushDecompress, Inflater,
+        RestartPoint, Status,
+    };
+    use miniz::{decompress_to_vec, decompress_to_vec_gzip, decompress_to_vec_zlib};
+
+    include!("../assets/gz/lines.rs");
+
+    // runs `compress` with small output buffers until the stream ends
+    fn compress_all(compress: &mut Compress, chunks: &[&[u8]]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut buf = [0u8; 100];
+        for chunk in chunks {
+            let status = compress.run(chunk, &mut buf, FlushCompress::Sync);
+            assert_eq!(status, Status::Ok);
+            out.extend_from_slice(&buf[..compress.total_out() as usize - out.len()]);
+        }
+        loop {
+            let before = compress.total_out() as usize;
+            let status = compress.run(&[], &mut buf, FlushCompress::Finish);
+            out.extend_from_slice(&buf[..compress.total_out() as usize - before]);
+            if status == Status::StreamEnd {
+                return out;
+            }
+        }
+    }
+
+    #[test]
+    fn test_compress() {
+        let data: Vec<u8> = (0..70000u32).map(|i| (i % 251) as u8).collect();
+        let chunks = [&data[..10], &data[10..66000], &data[66000..]];
+
+        let mut compress = Compress::new(6, true);
+        let zlib = compress_all(&mut compress, &chunks);
+        assert_eq!(compress.total_in(), data.len() as u64);
+        assert_eq!(compress.total_out(), zlib.len() as u64);
+        assert_eq!(decompress_to_vec_zlib(&zlib).unwrap(), data);
+
+        compress.reset();
+        assert_eq!(compress_all(&mut compress, &chunks), zlib, "after reset");
+
+        let mut compress = Compress::new(6, false);
+        let raw = compress_all(&mut compress, &chunks);
+        assert_eq!(decompress_to_vec(&raw).unwrap(), data);
+
+        // nothing left to do
+        let mut buf = [0u8; 16];
+        let status = compress.run(&[], &mut buf, FlushCompress::Finish);
+        assert_eq!(status, Status::StreamEnd);
+    }
+
+    #[test]
+    fn test_compress_flush_modes() {
+        let message = b"{\"type\":\"update\",\"id\":42,\"payload\":\"flush modes\"}";
+
+        // compressed size of each message, the inflater decodes each as soon as it is flushed
+        let sizes = |flush: FlushCompress| {
+            let mut compress = Compress::new(6, false);
+            let mut inflater = Inflater::new(false);
+            let mut sizes = Vec::new();
+            let mut stream = Vec::new();
+            for _ in 0..3 {
+                let mut out = [0u8; 256];
+                assert_eq!(compress.run(message, &mut out, flush), Status::Ok);
+                let out = &out[..compress.total_out() as usize - stream.len()];
+                assert!(out.ends_with(&[0, 0, 0xff, 0xff]), "{flush:?}");
+                sizes.push(out.len());
+                stream.extend_from_slice(out);
+
+                let mut decoded = [0u8; 256];
+                let (_, consumed, produced) = inflater.decompress(out, &mut decoded).unwrap();
+                assert_eq!(consumed, out.len());
+                assert_eq!(&decoded[..produced], message);
+            }
+            let mut out = [0u8; 16];
+            let status = compress.run(&[], &mut out, FlushCompress::Finish);
+            assert_eq!(status, Status::StreamEnd);
+            stream.extend_from_slice(&out[..compress.total_out() as usize - stream.len()]);
+            assert_eq!(decompress_to_vec(&stream).unwrap(), message.repeat(3));
+            sizes
+        };
+
+        // a sync flush keeps the window, later messages refer to the earlier ones
+        let sync = sizes(FlushCompress::Sync);
+        assert!(sync[1] * 2 < sync[0], "{sync:?}");
+        assert_eq!(sync[1], sync[2]);
+
+        // a full flush drops it, every message is compressed on its own
+        let full = sizes(FlushCompress::Full);
+        assert_eq!(full, [sync[0]; 3]);
+    }
+
+    #[test]
+    fn test_compress_levels() {
+        let data = decompress_to_vec_gzip(LINES_GZ).unwrap();
+        let chunks: Vec<&[u8]> = data.chunks(5000).collect();
+
+        let mut sizes = Vec::new();
+        for level in [0, 1, 9] {
+            let mut compress = Compress::new(level, true);
+            let zlib = compress_all(&mut compress, &chunks);
+            assert_eq!(
+                decompress_to_vec_zlib(&zlib).unwrap(),
+                data,
+                "level {level}"
+            );
+            sizes.push(zlib.len());
+        }
+        assert!(sizes[0] > data.len());
+        assert!(sizes[2] < data.len() / 2, "{sizes:?}");
+        assert!(sizes[2] <= sizes[1]);
+
+        // an empty stream, and all input in a single call
+        let mut compress = Compress::new(9, true);
+        let zlib = compress_all(&mut compress, &[]);
+        assert_eq!(decompress_to_vec_zlib(&zlib).unwrap(), b"");
+        compress.reset();
+        let mut out = vec![0u8; data.len()];
+        compress.run(&data, &mut out, FlushCompress::Finish);
+        assert!(compress.total_out() < data.len() as u64 / 2);
+    }
+
+    #[test]
+    fn test_restart_points() {
+        let data: Vec<u8> = (0..5000u32).map(|i| (i % 251) as u8).collect();
+        let mut compress = Compress::new(6, true);
+        compress.set_restart_interval(2000);
+
+        // drain the output after every call
+        let mut out = Vec::new();
+        let mut run = |compress: &mut Compress, input: &[u8], flush| {
+            let mut buf = [0u8; 100];
+            let mut input = input;
+            loop {
+                let before = compress.total_out() as usize;
+                let status = compress.run(input, &mut buf, flush);
+                let len = compress.total_out() as usize - before;
+                out.extend_from_slice(&buf[..len]);
+                input = &[];
+                if status == Status::StreamEnd || len == 0 {
+                    break;
+                }
+            }
+        };
+        run(&mut compress, &data[..2500], FlushCompress::Full);
+        run(&mut compress, &data[2500..], FlushCompress::Finish);
+
+        let points: Vec<u64> = compress
+            .restart_points()
+            .iter()
+            .map(|p| p.total_in)
+            .collect();
+        assert_eq!(points, [2000, 2500, 4500]);
+        assert_eq!(decompress_to_vec_zlib(&out).unwrap(), data);
+
+        // each point starts an independent raw deflate stream, ended by the adler trailer
+        let trailer = out.len() - 4;
+        for &RestartPoint {
+            total_in,
+            total_out,
+        } in compress.restart_points()
+        {
+            let tail = decompress_to_vec(&out[total_out as usize..trailer]).unwrap();
+            assert_eq!(tail, &data[total_in as usize..]);
+        }
+
+        compress.reset();
+        assert!(compress.restart_points().is_empty());
+        compress_all(&mut compress, &[&data]);
+        assert_eq!(compress.restart_points().len(), 2, "interval kept");
+    }
+
+    #[test]
+    fn test_restart_interval_lowered() {
+        let mut compress = Compress::new(6, false);
+        let mut buf = [0u8; 1024];
+        compress.run(&[1; 100], &mut buf, FlushCompress::None);
+        compress.set_restart_interval(50);
+        compress.run(&[], &mut buf, FlushCompress::None);
+        assert_eq!(
+            compress.restart_points(),
+            [RestartPoint {
+                total_in: 100,
+                total_out: compress.total_out(),
+            }]
+        );
+
+        // counting starts over at the flush
+        compress.run(&[2; 120], &mut buf, FlushCompress::None);
+        let points: Vec<u64> = compress
+            .restart_points()
+            .iter()
+            .map(|p| p.total_in)
+            .collect();
+        assert_eq!(points, [100, 150, 200]);
+    }
+
+    #[test]
+    fn test_block_stats() {
+        let data: Vec<u8> = (0..70000u32).map(|i| (i % 251) as u8).collect();
+        let chunks = [&data[..10], &data[10..]];
+        let mut compress = Compress::new(0, true);
+        let zlib = compress_all(&mut compress, &chunks);
+        assert!(compress.block_stats().is_empty(), "disabled by default");
+
+        compress.reset();
+        compress.set_block_stats(true);
+        assert_eq!(compress_all(&mut compress, &chunks), zlib);
+
+        // stored blocks at level 0: each chunk followed by a flush marker, the second one split
+        // at the block limit, and the empty final block
+        let stats = compress.block_stats();
+        let lens: Vec<usize> = stats.iter().map(|s| s.input_len).collect();
+        assert_eq!(lens, [10, 0, 65535, 4455, 0, 0]);
+        assert!(stats.iter().all(|s| s.block_type == BlockType::Stored));
+        assert!(stats.iter().all(|s| s.estimated_bits == s.actual_bits));
+        assert_eq!(stats.iter().map(|s| s.literals).sum::<usize>(), data.len());
+        assert_eq!(stats[0].average_match_len(), 0.0);
+
+        let bits: u64 = stats.iter().map(|s| s.actual_bits).sum();
+        assert_eq!(
+            bits as usize,
+            8 * (zlib.len() - 6),
+            "everything but header and trailer"
+        );
+
+        compress.reset();
+        assert!(compress.block_stats().is_empty());
+        compress_all(&mut compress, &[&data]);
+        assert_eq!(compress.block_stats().len(), 4, "still enabled");
+    }
+
+    #[test]
+    fn test_block_stats_compressed() {
+        let data = decompress_to_vec_gzip(LINES_GZ).unwrap();
+        let mut compress = Compress::new(6, true);
+        compress.set_block_stats(true);
+        let zlib = compress_all(&mut compress, &[&data]);
+
+        let stats = compress.block_stats();
+        assert!(stats
+            .iter()
+            .any(|s| matches!(s.block_type, BlockType::Dynamic | BlockType::Fixed)));
+        assert!(stats.iter().map(|s| s.matches).sum::<usize>() > 0);
+        assert!(stats
+            .iter()
+            .all(|s| s.literals + s.match_bytes == s.input_len));
+        assert!(stats.iter().any(|s| s.average_match_len() > 3.0));
+        assert_eq!(stats.iter().map(|s| s.input_len).sum::<usize>(), data.len());
+
+        // the estimates are exact, the blocks fill the stream up to the padding of the last byte
+        assert!(stats.iter().all(|s| s.estimated_bits == s.actual_bits));
+        let bits: u64 = stats.iter().map(|s| s.actual_bits).sum();
+        assert_eq!(bits.div_ceil(8) as usize, zlib.len() - 6);
+    }
+
+    #[test]
+    fn test_reuse() {
+        // many small messages through the same objects
+        let mut compress = Compress::new(6, true);
+        let mut decompress = Decompress::new(true);
+        let mut buf = [0u8; 256];
+        for i in 0..100usize {
+            let msg: Vec<u8> = (0..i * 7 % 200).map(|j| (i + j) as u8).collect();
+            compress.reset();
+            let zlib = compress_all(&mut compress, &[&msg]);
+            assert_eq!(compress.total_in(), msg.len() as u64);
+
+            decompress.reset(true);
+            let status = decompress.run(&zlib, &mut buf, FlushDecompress::Finish);
+            assert_eq!(status, Ok(Status::StreamEnd), "message {i}");
+            assert_eq!(&buf[..decompress.total_out() as usize], msg);
+        }
+
+        // switching to raw deflate data
+        let raw = miniz::compress_to_vec(b"raw", 6);
+        decompress.reset(false);
+        let status = decompress.run(&raw[2..raw.len() - 4], &mut buf, FlushDecompress::Finish);
+        assert_eq!(status, Ok(Status::StreamEnd));
+        assert_eq!(&buf[..3], b"raw");
+    }
+
+    #[test]
+    fn test_decompress() {
+        let data: Vec<u8> = (0..5000u32).map(|i| (i % 7) as u8).collect();
+        let zlib = miniz::compress_to_vec(&data, 6);
+
+        // output is returned as the input arrives
+        let mut decompress = Decompress::new(true);
+        let mut out = Vec::new();
+        let mut buf = [0u8; 1000];
+        for chunk in zlib.chunks(300) {
+            let before = decompress.total_out() as usize;
+            let status = decompress.run(chunk, &mut buf, FlushDecompress::None);
+            assert_eq!(status, Ok(Status::Ok));
+            out.extend_from_slice(&buf[..decompress.total_out() as usize - before]);
+        }
+        assert!(!out.is_empty());
+        loop {
+            let before = decompress.total_out() as usize;
+            let status = decompress.run(&[], &mut buf, FlushDecompress::Finish);
+            out.extend_from_slice(&buf[..decompress.total_out() as usize - before]);
+            if status == Ok(Status::StreamEnd) {
+                break;
+            }
+            assert_eq!(status, Ok(Status::Ok));
+        }
+        assert_eq!(out, data);
+        assert_eq!(decompress.total_in(), zlib.len() as u64);
+
+        decompress.reset(true);
+        let status = decompress.run(&zlib[..10], &mut buf, FlushDecompress::Finish);
+        assert!(status.is_err(), "truncated stream");
+    }
+
+    #[test]
+    fn test_vectored() {
+        let data: Vec<u8> = (0..3000u32).map(|i| (i % 13) as u8).collect();
+        // wrapped ring buffer: the data is split into two segments
+        let inputs = [&data[..2500], &data[2500..]];
+
+        let mut compress = Compress::new(6, true);
+        let mut zlib = Vec::new();
+        loop {
+            let (mut a, mut b, mut c) = ([0u8; 7], [0u8; 200], [0u8; 93]);
+            let before = compress.total_out() as usize;
+            let input: &[&[u8]] = if before == 0 { &inputs } else { &[] };
+            let status =
+                compress.run_vectored(input, &mut [&mut a, &mut b, &mut c], FlushCompress::Finish);
+            let written = compress.total_out() as usize - before;
+            let gathered = [&a[..], &b[..], &c[..]].concat();
+            zlib.extend_from_slice(&gathered[..written]);
+            if status == Status::StreamEnd {
+                break;
+            }
+        }
+        assert_eq!(compress.total_in(), data.len() as u64);
+        assert_eq!(decompress_to_vec_zlib(&zlib).unwrap(), data);
+
+        let segments: Vec<&[u8]> = zlib.chunks(1000).collect();
+        let mut decompress = Decompress::new(true);
+        let (mut a, mut b) = (vec![0u8; 1000], vec![0u8; 2000]);
+        let status =
+            decompress.run_vectored(&segments, &mut [&mut a, &mut b], FlushDecompress::Finish);
+        assert_eq!(status, Ok(Status::StreamEnd));
+        assert_eq!(decompress.total_in(), zlib.len() as u64);
+        assert_eq!([a, b].concat(), data);
+    }
+
+    #[test]
+    fn test_decompress_clone() {
+        let data: Vec<u8> = (0..2000u32).map(|i| (i % 17) as u8).collect();
+        let zlib = miniz::compress_to_vec(&data, 6);
+        let (head, tail) = zlib.split_at(zlib.len() / 2);
+
+        let mut decompress = Decompress::new(true);
+        let mut buf = vec![0u8; 4000];
+        decompress
+            .run(head, &mut buf, FlushDecompress::None)
+            .unwrap();
+        let mut snapshot = decompress.clone();
+        let produced = snapshot.total_out() as usize;
+
+        // a corrupted continuation fails without affecting the snapshot
+        let mut broken = tail.to_vec();
+        *broken.last_mut().unwrap() ^= 1;
+        assert!(decompress
+            .run(&broken, &mut buf[produced..], FlushDecompress::Finish)
+            .is_err());
+
+        let status = snapshot.run(tail, &mut buf[produced..], FlushDecompress::Finish);
+        assert_eq!(status, Ok(Status::StreamEnd));
+        assert_eq!(snapshot.total_in(), zlib.len() as u64);
+        assert_eq!(&buf[..data.len()], data);
+    }
+
+    #[test]
+    fn test_decompress_blocks() {
+        // stored blocks of 65535 bytes after the 2 byte zlib header
+        let data: Vec<u8> = (0..150_000u32).map(|i| (i % 241) as u8).collect();
+        let zlib = miniz::compress_to_vec(&data, 0);
+
+        let mut decompress = Decompress::new(true);
+        let mut out = vec![0u8; data.len()];
+        // the first block is incomplete
+        let status = decompress.run(&zlib[..1000], &mut out, FlushDecompress::Block);
+        assert_eq!(status, Ok(Status::Ok));
+        assert_eq!(
+            (decompress.total_out(), decompress.block_boundary()),
+            (0, None)
+        );
+
+        let mut boundaries = Vec::new();
+        let mut input = &zlib[1000..];
+        loop {
+            let before = decompress.total_out() as usize;
+            let status = decompress.run(input, &mut out[before..], FlushDecompress::Block);
+            input = &[];
+            boundaries.push(decompress.block_boundary().unwrap());
+            if status == Ok(Status::StreamEnd) {
+                break;
+            }
+        }
+        assert_eq!(out, data);
+        let block = |n: u64, last| BlockBoundary {
+            bit_pos: 16 + n * 8 * (5 + 65535),
+            total_out: n * 65535,
+            last,
+        };
+        assert_eq!(boundaries[0], block(1, false));
+        assert_eq!(boundaries[1], block(2, false));
+        assert_eq!(boundaries[2].total_out, data.len() as u64);
+        assert!(boundaries[2].last);
+
+        // switching back to Finish decodes the remaining blocks
+        let mut decompress = Decompress::new(true);
+        let mut out = vec![0u8; 70_000];
+        decompress
+            .run(&zlib, &mut out, FlushDecompress::Block)
+            .unwrap();
+        assert_eq!(decompress.total_out(), 65535);
+        let status = decompress.run(&[], &mut out, FlushDecompress::Finish);
+        assert_eq!(status, Ok(Status::Ok));
+        assert_eq!(decompress.total_out(), 65535 + 70_000);
+        let status = decompress.run(&[], &mut out, FlushDecompress::Finish);
+        assert_eq!(status, Ok(Status::StreamEnd));
+        assert_eq!(decompress.total_out(), data.len() as u64);
+
+        // the checksum is verified after the final block
+        let mut broken = zlib.clone();
+        *broken.last_mut().unwrap() ^= 1;
+        let mut decompress = Decompress::new(true);
+        let mut out = vec![0u8; data.len()];
+        for _ in 0..2 {
+            let status = decompress.run(&broken, &mut out, FlushDecompress::Block);
+            assert_eq!(status, Ok(Status::Ok));
+            broken.clear();
+        }
+        let status = decompress.run(&[], &mut out, FlushDecompress::Block);
+        assert!(status.is_err());
+    }
+
+    #[test]
+    fn test_decompress_huffman_blocks() {
+        // raw deflate data of gzip -9 -n output, Huffman blocks end at arbitrary bits
+        let expected = decompress_to_vec_gzip(LINES_GZ).unwrap();
+        let raw = &LINES_GZ[10..LINES_GZ.len() - 8];
+
+        let mut decompress = Decompress::new(false);
+        let mut out = vec![0u8; expected.len()];
+        let mut boundaries = Vec::new();
+        let mut input = raw;
+        loop {
+            let before = decompress.total_out() as usize;
+            let status = decompress.run(input, &mut out[before..], FlushDecompress::Block);
+            input = &[];
+            boundaries.push(decompress.block_boundary().unwrap());
+            if status == Ok(Status::StreamEnd) {
+                break;
+            }
+        }
+        assert_eq!(out, expected);
+        assert!(boundaries.len() > 1);
+        assert_eq!(
+            boundaries.last().unwrap().bit_pos.div_ceil(8),
+            raw.len() as u64
+        );
+        assert!(boundaries.iter().any(|b| b.bit_pos % 8 != 0));
+    }
+
+    #[test]
+    fn test_inflater() {
+        let expected = decompress_to_vec_gzip(LINES_GZ).unwrap();
+        let mut zlib = miniz::compress_to_vec(&expected, 6);
+        let stream_len = zlib.len();
+        zlib.extend_from_slice(b"next message");
+
+        // 1000 byte reads, 4 KB of output space per call
+        let mut inflater = Inflater::new(true);
+        let mut out = Vec::new();
+        let mut buf = [0u8; 4096];
+        let mut input = &zlib[..];
+        let mut total_consumed = 0;
+        let mut half_out = 0;
+        loop {
+            let chunk = &input[..input.len().min(1000)];
+            let (status, consumed, produced) = inflater.decompress(chunk, &mut buf).unwrap();
+            input = &input[consumed..];
+            total_consumed += consumed;
+            out.extend_from_slice(&buf[..produced]);
+
+            // output is produced long before the input is complete
+            if total_consumed < stream_len / 2 {
+                half_out = out.len();
+            }
+            if status == Status::StreamEnd {
+                break;
+            }
+        }
+        assert!(inflater.is_finished());
+        assert_eq!(out, expected);
+        assert!(half_out > expected.len() / 4);
+        assert_eq!(total_consumed, stream_len);
+        assert_eq!(inflater.total_in(), stream_len as u64);
+        assert_eq!(input, b"next message");
+        assert_eq!(
+            inflater.decompress(input, &mut buf),
+            Ok((Status::StreamEnd, 0, 0))
+        );
+
+        // raw deflate, small reads
+        let raw = &LINES_GZ[10..LINES_GZ.len() - 8];
+        inflater.reset(false);
+        let mut out = vec![0u8; expected.len()];
+        for chunk in raw.chunks(97) {
+            let produced = inflater.total_out() as usize;
+            let (_, consumed, _) = inflater.decompress(chunk, &mut out[produced..]).unwrap();
+            assert_eq!(consumed, chunk.len());
+        }
+        assert!(inflater.is_finished());
+        assert_eq!(out, expected);
+
+        // corrupt data fails once its block is complete
+        let mut broken = zlib.clone();
+        broken[stream_len - 1] ^= 1;
+        let mut inflater = Inflater::new(true);
+        let mut buf = vec![0u8; expected.len()];
+        assert!(inflater.decompress(&broken, &mut buf).is_err());
+    }
+
+    #[test]
+    fn test_inflater_single_bytes() {
+        // blocks of about 28 KB compressed, decoding them again from their start for every byte
+        // takes minutes
+        let mut seed = 1u32;
+        let data: Vec<u8> = (0..300_000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                b"abcdefgh"[(seed >> 28) as usize & 7]
+            })
+            .collect();
+        let zlib = miniz::compress_to_vec(&data, 6);
+
+        let start = std::time::Instant::now();
+        let mut inflater = Inflater::new(true);
+        let mut out = vec![0u8; data.len()];
+        for byte in zlib.chunks(1) {
+            let produced = inflater.total_out() as usize;
+            inflater.decompress(byte, &mut out[produced..]).unwrap();
+        }
+        assert!(inflater.is_finished());
+        assert_eq!(out, data);
+        assert!(start.elapsed().as_secs() < 10, "{:?}", start.elapsed());
+    }
+
+    #[test]
+    fn test_inflater_metrics() {
+        let expected = decompress_to_vec_gzip(LINES_GZ).unwrap();
+        let raw = &LINES_GZ[10..LINES_GZ.len() - 8];
+
+        // blocks continued after a short read are counted once
+        let mut inflater = Inflater::new(false);
+        let mut out = vec![0u8; expected.len()];
+        for chunk in raw.chunks(97) {
+            let produced = inflater.total_out() as usize;
+            inflater.decompress(chunk, &mut out[produced..]).unwrap();
+        }
+        assert!(inflater.is_finished());
+
+        let metrics = inflater.metrics();
+        if cfg!(feature = "metrics") {
+            assert_eq!(metrics.bytes_in, raw.len() as u64);
+            assert_eq!(metrics.bytes_out, expected.len() as u64);
+            assert!(metrics.fixed_blocks + metrics.dynamic_blocks > 0);
+            assert!(metrics.fast_path_iterations + metrics.slow_path_iterations > 0);
+        } else {
+            assert_eq!(metrics, InflateMetrics::default());
+        }
+
+        inflater.reset(false);
+        assert_eq!(inflater.metrics(), InflateMetrics::default());
+    }
+
+    #[test]
+    fn test_inflater_state() {
+        let expected = decompress_to_vec_gzip(LINES_GZ).unwrap();
+        let mut zlib = miniz::compress_to_vec(&expected, 6);
+        let stream_len = zlib.len();
+        zlib.extend_from_slice(b"next message");
+
+        // save and restore after every call, with input and output waiting in the state
+        let mut inflater = Inflater::new(true);
+        let mut out = Vec::new();
+        let mut buf = [0u8; 3000];
+        let mut input = &zlib[..];
+        loop {
+            let chunk = &input[..input.len().min(1000)];
+            let (status, consumed, produced) = inflater.decompress(chunk, &mut buf).unwrap();
+            input = &input[consumed..];
+            out.extend_from_slice(&buf[..produced]);
+
+            let saved = inflater.save_state();
+            let restored = Inflater::from_state(&saved).unwrap();
+            assert_eq!(restored.save_state(), saved);
+            assert_eq!(restored.total_in(), inflater.total_in());
+            assert_eq!(restored.total_out(), inflater.total_out());
+            inflater = restored;
+            if status == Status::StreamEnd {
+                break;
+            }
+        }
+        assert!(inflater.is_finished());
+        assert_eq!(out, expected);
+        assert_eq!(inflater.total_in(), stream_len as u64);
+        assert_eq!(input, b"next message");
+
+        // raw deflate, resumed from the state of an earlier decoder
+        let raw = &LINES_GZ[10..LINES_GZ.len() - 8];
+        let mut first = Inflater::new(false);
+        let mut out = vec![0u8; expected.len()];
+        let (_, consumed, produced) = first.decompress(&raw[..raw.len() / 2], &mut out).unwrap();
+        let mut second = Inflater::from_state(&first.save_state()).unwrap();
+        second
+            .decompress(&raw[consumed..], &mut out[produced..])
+            .unwrap();
+        assert!(second.is_finished());
+        assert_eq!(out, expected);
+
+        // truncated, corrupted and unknown states
+        let saved = first.save_state();
+        let invalid = miniz::Error::Inflate(miniz::inflate::Error::InvalidState);
+        assert_eq!(Inflater::from_state(&[]).err(), Some(invalid));
+        for len in [4, 5, saved.len() - 1] {
+            assert!(Inflater::from_state(&saved[..len]).is_err(), "{len}");
+        }
+        for pos in [0, 4, 5, 20, saved.len() - 1] {
+            let mut broken = saved.clone();
+            broken[pos] ^= 1;
+            assert!(Inflater::from_state(&broken).is_err(), "{pos}");
+        }
+    }
+}
@@ -0,0 +1,31 @@
+#![cfg(feature = "wasm")]
+
+// ----------------------------------------------------------------------------
+mod test {
+    use miniz::deflate::{deflate, CompressionLevel};
+    use miniz::wasm::inflate_to_vec;
+
+    #[cfg(feature = "png")]
+    const BASN2C08_PNG: &[u8] = include_bytes!("../assets/png/basn2c08.png");
+
+    #[test]
+    fn test_inflate_to_vec() {
+        let src = b"wasm wasm wasm wasm wasm".repeat(100);
+        let mut packed = Vec::new();
+        deflate(&mut packed, &src, CompressionLevel::DefaultLevel);
+        let out = inflate_to_vec(&packed, src.len()).ok().unwrap();
+        assert_eq!(out, src);
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn test_decode_png() {
+        use miniz::wasm::decode_png;
+
+        let png = decode_png(BASN2C08_PNG).ok().unwrap();
+        assert_eq!(png.width(), 32);
+        assert_eq!(png.height(), 32);
+        assert_eq!(png.rgba().len(), 32 * 32 * 4);
+        assert_eq!(png.into_rgba().0.len(), 32 * 32 * 4);
+    }
+}
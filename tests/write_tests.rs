@@ -0,0 +1,90 @@
+#![cfg(feature = "std")]
+
+// ----------------------------------------------------------------------------
+mod test {
+    use miniz::read::{GzipReader, InflateReader, ZlibReader};
+    use miniz::stream::Inflater;
+    use miniz::write::{DeflateWriter, GzipWriter, ZlibWriter};
+    use miniz::{compress_to_vec, decompress_to_vec_gzip, decompress_to_vec_zlib};
+    use std::io::{Read, Write};
+
+    fn lines() -> Vec<u8> {
+        (0..15000u32)
+            .flat_map(|i| format!("line {i}: {}\n", i * i % 9973).into_bytes())
+            .collect()
+    }
+
+    // writes in uneven pieces
+    fn write_pieces(writer: &mut impl Write, data: &[u8]) {
+        for piece in data.chunks(1234) {
+            writer.write_all(piece).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_writers() {
+        let data = lines();
+        for level in [0, 1, 6, 9] {
+            let mut writer = GzipWriter::new(Vec::new(), level);
+            write_pieces(&mut writer, &data);
+            let gz = writer.finish().unwrap();
+            assert_eq!(decompress_to_vec_gzip(&gz).unwrap(), data);
+            let mut out = Vec::new();
+            GzipReader::new(&gz[..]).read_to_end(&mut out).unwrap();
+            assert_eq!(out, data);
+
+            let mut writer = ZlibWriter::new(Vec::new(), level);
+            write_pieces(&mut writer, &data);
+            let zlib = writer.finish().unwrap();
+            assert_eq!(decompress_to_vec_zlib(&zlib).unwrap(), data);
+            // chunking costs little compared to compressing at once
+            let oneshot = compress_to_vec(&data, level);
+            assert!(zlib.len() <= oneshot.len() + oneshot.len() / 100);
+
+            let mut writer = DeflateWriter::new(Vec::new(), level);
+            write_pieces(&mut writer, &data);
+            let raw = writer.finish().unwrap();
+            let mut out = Vec::new();
+            InflateReader::new(&raw[..]).read_to_end(&mut out).unwrap();
+            assert_eq!(out, data);
+        }
+
+        // literals only are larger, but decode the same
+        let mut writer = ZlibWriter::new(Vec::new(), 6);
+        writer.set_strategy(miniz::deflate::Strategy::HuffmanOnly);
+        write_pieces(&mut writer, &data);
+        let zlib = writer.finish().unwrap();
+        assert_eq!(decompress_to_vec_zlib(&zlib).unwrap(), data);
+        assert!(zlib.len() > compress_to_vec(&data, 6).len());
+
+        // empty streams
+        let zlib = ZlibWriter::new(Vec::new(), 6).finish().unwrap();
+        let mut out = Vec::new();
+        ZlibReader::new(&zlib[..]).read_to_end(&mut out).unwrap();
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn test_flush() {
+        let data = lines();
+        let mut writer = DeflateWriter::new(Vec::new(), 6);
+        writer.write_all(&data[..1000]).unwrap();
+        writer.flush().unwrap();
+        writer.flush().unwrap();
+
+        // everything written before the flush decodes without the end of the stream
+        let flushed = writer.get_ref().clone();
+        assert!(flushed.ends_with(&[0, 0, 0xff, 0xff]));
+        let mut inflater = Inflater::new(false);
+        let mut out = vec![0u8; 4096];
+        let (_, consumed, written) = inflater.decompress(&flushed, &mut out).unwrap();
+        assert_eq!(consumed, flushed.len());
+        assert_eq!(&out[..written], &data[..1000]);
+
+        writer.write_all(&data[1000..]).unwrap();
+        let raw = writer.finish().unwrap();
+        let mut out = Vec::new();
+        InflateReader::new(&raw[..]).read_to_end(&mut out).unwrap();
+        assert_eq!(out, data);
+    }
+}
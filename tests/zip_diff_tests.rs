@@ -0,0 +1,56 @@
+#![cfg(feature = "zip")]
+
+// ----------------------------------------------------------------------------
+mod test {
+    use miniz::zip_diff::zip_diff;
+    use miniz::zip_read::{zip_entries, Error};
+
+    include!("../assets/zip/deflated.rs");
+    include!("../assets/zip/folder.rs");
+
+    // offset of the first central directory record of DEFLATED_ZIP ("first")
+    const CD_OFFSET: usize = 94;
+
+    #[test]
+    fn test_zip_diff() {
+        assert!(zip_diff(DEFLATED_ZIP, DEFLATED_ZIP).unwrap().is_empty());
+
+        let diff = zip_diff(DEFLATED_ZIP, FOLDER_ZIP).unwrap();
+        let names = |entries: &[miniz::zip_read::EntryInfo]| {
+            entries.iter().map(|e| e.name.clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(names(&diff.added), ["test", "testdir/", "testdir/test2"]);
+        assert_eq!(names(&diff.removed), ["first", "second"]);
+        assert!(diff.modified.is_empty());
+        assert_eq!(diff.added[0].size, TEST.len());
+
+        assert_eq!(
+            zip_diff(DEFLATED_ZIP, &[0; 16]),
+            Err(Error::NoCentralDirectory)
+        );
+    }
+
+    #[test]
+    fn test_zip_diff_modified() {
+        // changed modification time
+        let mut zip = DEFLATED_ZIP.to_vec();
+        zip[CD_OFFSET + 12] ^= 1;
+        let diff = zip_diff(DEFLATED_ZIP, &zip).unwrap();
+        assert_eq!(diff.modified.len(), 1);
+        assert_eq!(diff.modified[0].name(), "first");
+        assert!(diff.modified[0].metadata_changed());
+        assert!(!diff.modified[0].content_changed());
+
+        // changed CRC
+        let mut zip = DEFLATED_ZIP.to_vec();
+        zip[CD_OFFSET + 16] ^= 1;
+        let diff = zip_diff(DEFLATED_ZIP, &zip).unwrap();
+        assert!(diff.modified[0].content_changed());
+        assert!(!diff.modified[0].metadata_changed());
+
+        let entries = zip_entries(&zip).unwrap();
+        assert_eq!(diff.modified[0].new, entries[0]);
+        assert_eq!(entries[1].size, SECOND.len());
+        assert_eq!(entries[0].size, FIRST.len());
+    }
+}
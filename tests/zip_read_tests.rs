@@ -1,9 +1,15 @@
 // ZIP tests
 // * https://github.com/nih-at/libzip/tree/main/regress
+#![cfg(feature = "zip")]
+#![allow(clippy::needless_borrow)]
 
 // ----------------------------------------------------------------------------
 mod test {
-    use miniz::zip_read::{zip_open, zip_read};
+    use miniz::buffer::Heap;
+    use miniz::inflate::InflateOptions;
+    use miniz::zip_read::{
+        zip_open, zip_open_with_options, zip_read, zip_read_with_options, Error,
+    };
 
     #[allow(dead_code)]
     fn write_result(data: Vec<u8>) {
@@ -18,10 +24,17 @@ mod test {
         }
     }
 
+    include!("../assets/zip/aes.rs");
+    include!("../assets/zip/bzip2.rs");
     include!("../assets/zip/comments.rs");
     include!("../assets/zip/deflated.rs");
+    include!("../assets/zip/encrypted.rs");
     include!("../assets/zip/folder.rs");
+    include!("../assets/zip/nested.rs");
+    include!("../assets/zip/streamed.rs");
+    include!("../assets/zip/textures.rs");
     include!("../assets/zip/utf8.rs");
+    include!("../assets/zip/zip64.rs");
 
     #[test]
     fn test_comments_zip() {
@@ -37,6 +50,31 @@ mod test {
         assert_eq!(&file4, &FILE4);
     }
 
+    #[test]
+    fn test_comments() {
+        use miniz::zip_read::{zip_comment, zip_entries, ZipArchive, ZipReader};
+
+        let comment = b"This is the archive comment for the file.\r\n\r\nLong.\r\n\r\nLonger.\r\n";
+        assert_eq!(zip_comment(COMMENTS_ZIP).unwrap(), comment);
+        assert_eq!(
+            ZipArchive::from_slice(COMMENTS_ZIP).unwrap().comment(),
+            comment
+        );
+        let reader = ZipReader::new(std::io::Cursor::new(COMMENTS_ZIP)).unwrap();
+        assert_eq!(reader.comment(), comment);
+        assert_eq!(zip_comment(DEFLATED_ZIP).unwrap(), b"");
+
+        let comments: Vec<String> = zip_entries(COMMENTS_ZIP)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.comment)
+            .collect();
+        assert_eq!(
+            comments,
+            ["", "First one had no comment.", "Third one.", "Last."]
+        );
+    }
+
     #[test]
     fn test_deflated_zip() {
         let zip = zip_open(&DEFLATED_ZIP).unwrap();
@@ -47,6 +85,83 @@ mod test {
         assert_eq!(&second, &SECOND);
     }
 
+    #[test]
+    fn test_checksum() {
+        let files = zip_open(DEFLATED_ZIP).unwrap();
+        let offset = files.iter().find(|f| f.name == "first").unwrap().offset;
+        let mut zip = DEFLATED_ZIP.to_vec();
+        zip[offset + 14] ^= 1;
+
+        assert_eq!(
+            zip_read(&zip, &files, "first"),
+            Err(Error::ChecksumMismatch)
+        );
+        assert_eq!(zip_read(&zip, &files, "second").unwrap(), SECOND);
+
+        let options = InflateOptions {
+            verify_checksums: false,
+            ..Default::default()
+        };
+        let first = zip_read_with_options(&zip, &files, "first", &mut Heap, &options).unwrap();
+        assert_eq!(first, FIRST);
+        // entries with data descriptors are checked against the central directory
+        let mut files = zip_open(STREAMED_ZIP).unwrap();
+        let text = b"hello streaming world\n".repeat(20);
+        assert_eq!(zip_read(STREAMED_ZIP, &files, "a.txt").unwrap(), text);
+        files[0].crc32 ^= 1;
+        assert_eq!(
+            zip_read(STREAMED_ZIP, &files, "a.txt"),
+            Err(Error::ChecksumMismatch)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_read_to() {
+        use miniz::zip_read::zip_read_to;
+        use miniz::zip_write::{FileOptions, ZipWriter};
+
+        for zip in [DEFLATED_ZIP, STREAMED_ZIP, FOLDER_ZIP] {
+            let files = zip_open(zip).unwrap();
+            for file in &files {
+                let mut out = Vec::new();
+                let len = zip_read_to(zip, &files, &file.name, &mut out).unwrap();
+                assert_eq!(len, out.len() as u64);
+                assert_eq!(out, zip_read(zip, &files, &file.name).unwrap());
+            }
+        }
+
+        // records the largest single write
+        struct Chunks(usize, usize);
+        impl std::io::Write for Chunks {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0 += buf.len();
+                self.1 = self.1.max(buf.len());
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let text: Vec<u8> = (0u64..1 << 20)
+            .map(|i| b"abcdefghij klmnop\n"[(i * i % 19) as usize % 18])
+            .collect();
+        let mut writer = ZipWriter::new();
+        writer
+            .add_file("big.txt", &text, &FileOptions::default())
+            .unwrap();
+        let zip = writer.finish().unwrap();
+        let files = zip_open(&zip).unwrap();
+        let mut chunks = Chunks(0, 0);
+        zip_read_to(&zip, &files, "big.txt", &mut chunks).unwrap();
+        assert_eq!(chunks.0, text.len());
+        assert!(chunks.1 < text.len() / 4, "written in blocks");
+
+        let err = zip_read_to(&zip, &files, "missing", &mut chunks).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
     #[test]
     fn test_folder_zip() {
         let zip = zip_open(&FOLDER_ZIP).unwrap();
@@ -57,10 +172,1061 @@ mod test {
         assert_eq!(&test2, &TEST);
     }
 
+    #[test]
+    fn test_timestamps() {
+        use miniz::zip_read::{zip_entries, DosDateTime};
+        use miniz::zip_stream::ZipStreamReader;
+        use miniz::zip_write::{ExtraField, FileOptions, ZipWriter};
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let dos = DosDateTime::from_dos(0x585d_6daf);
+        assert_eq!(dos.unix_time(), Some(1709214330));
+        assert_eq!(DosDateTime::from_dos(0).unix_time(), None);
+        assert!(DosDateTime::from_dos(0x0021_0000) < dos);
+
+        // 2024-02-29 13:45:30.1234567 UTC
+        let ticks = (1709214330 + 11_644_473_600) * 10_000_000 + 1_234_567u64;
+        let mut ntfs = vec![0u8; 4];
+        ntfs.extend_from_slice(&[1, 0, 24, 0]);
+        for _ in 0..3 {
+            ntfs.extend_from_slice(&ticks.to_le_bytes());
+        }
+        let mut ut = vec![1];
+        ut.extend_from_slice(&1_600_000_000i32.to_le_bytes());
+        let fields = [
+            ExtraField {
+                header_id: 0x5455,
+                data: &ut,
+            },
+            ExtraField {
+                header_id: 0x000a,
+                data: &ntfs,
+            },
+        ];
+
+        let mut writer = ZipWriter::new();
+        let options = FileOptions {
+            dos_time: 0x585d_6daf,
+            ..Default::default()
+        };
+        writer.add_file("dos", b"", &options).unwrap();
+        let options = FileOptions {
+            central_extra: &fields[..1],
+            ..options
+        };
+        writer.add_file("ut", b"", &options).unwrap();
+        let options = FileOptions {
+            local_extra: &fields,
+            central_extra: &fields,
+            ..options
+        };
+        writer.add_file("ntfs", b"", &options).unwrap();
+        let zip = writer.finish().unwrap();
+
+        let entries = zip_entries(&zip).unwrap();
+        let expected = [
+            UNIX_EPOCH + Duration::from_secs(1709214330),
+            UNIX_EPOCH + Duration::from_secs(1_600_000_000),
+            UNIX_EPOCH + Duration::new(1709214330, 123_456_700),
+        ];
+        for (entry, time) in entries.iter().zip(expected) {
+            assert_eq!(entry.modified(), Some(time), "{}", entry.name);
+        }
+        assert_eq!(entries[2].ntfs_mtime, Some(ticks));
+
+        // the local headers carry the same fields
+        let mut reader = ZipStreamReader::new(&zip[..]);
+        let mut last = None;
+        while let Some((entry, _)) = reader.next_entry().unwrap() {
+            last = Some(entry.modified());
+        }
+        assert_eq!(last, Some(Some(expected[2])));
+    }
+
+    #[test]
+    fn test_entry_metadata() {
+        use miniz::zip_read::zip_entries;
+
+        // made on Unix with extended timestamps
+        let entries = zip_entries(FOLDER_ZIP).unwrap();
+        let dir = &entries[1];
+        assert_eq!(dir.name, "testdir/");
+        assert!(dir.is_dir() && !entries[0].is_dir());
+        assert_eq!(dir.unix_mode(), Some(0o40755));
+        assert_eq!(entries[0].unix_mode(), Some(0o100664));
+        let time = dir.dos_datetime();
+        assert_eq!((time.year, time.month, time.day), (2005, 1, 11));
+        assert_eq!((time.hour, time.minute, time.second), (19, 3, 54));
+        assert_eq!(entries[0].mtime, Some(0x3f81_7241));
+
+        // made on MS-DOS without extra fields
+        let entries = zip_entries(DEFLATED_ZIP).unwrap();
+        let time = entries[0].dos_datetime();
+        assert_eq!((time.year, time.month, time.day), (2012, 4, 28));
+        assert_eq!((time.hour, time.minute, time.second), (11, 50, 44));
+        assert_eq!((entries[0].unix_mode(), entries[0].mtime), (None, None));
+        assert_eq!((entries[0].method, entries[0].size), (8, FIRST.len()));
+    }
+
     #[test]
     fn test_utf8_zip() {
         let zip = zip_open(&UTF8_ZIP).unwrap();
         assert_eq!(zip.len(), 1);
         assert_eq!(zip[0].name, UTF8_NAME);
     }
+
+    #[test]
+    fn test_name_encoding() {
+        use miniz::checksum::crc32;
+        use miniz::zip_write::{ExtraField, FileOptions, ZipWriter};
+
+        // the writer sets the UTF-8 flag for non-ASCII names, it is cleared in the central
+        // directory record to get a name in the local code page
+        fn archive(name: &str, central_extra: &[ExtraField]) -> Vec<u8> {
+            let options = FileOptions {
+                central_extra,
+                ..Default::default()
+            };
+            let mut writer = ZipWriter::new();
+            writer.add_file(name, b"x", &options).unwrap();
+            let mut zip = writer.finish().unwrap();
+            let eocd = zip.len() - 22;
+            let cd = u32::from_le_bytes(zip[eocd + 16..eocd + 20].try_into().unwrap()) as usize;
+            zip[cd + 9] &= !0x08;
+            zip
+        }
+
+        let zip = archive("café.txt", &[]);
+        assert_eq!(zip_open(&zip).unwrap()[0].name, "caf├⌐.txt");
+
+        // the Unicode Path extra field holds the CRC-32 of the name it was made for
+        let mut unicode_path = vec![1];
+        unicode_path.extend_from_slice(&crc32(0, "café.txt".as_bytes()).to_le_bytes());
+        unicode_path.extend_from_slice("Café.txt".as_bytes());
+        let field = ExtraField {
+            header_id: 0x7075,
+            data: &unicode_path,
+        };
+        let zip = archive("café.txt", &[field]);
+        assert_eq!(zip_open(&zip).unwrap()[0].name, "Café.txt");
+        let zip = archive("cafe.txt", &[field]);
+        assert_eq!(zip_open(&zip).unwrap()[0].name, "cafe.txt");
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_entry_reader_seek() {
+        use miniz::zip_read::zip_entry_reader;
+        use std::io::{Read, Seek, SeekFrom};
+
+        for (zip, name, expected) in [
+            (COMMENTS_ZIP, "file2", &FILE2[..]),
+            (DEFLATED_ZIP, "second", &SECOND[..]),
+        ] {
+            let files = zip_open(zip).unwrap();
+            let mut reader = zip_entry_reader(zip, &files, name).unwrap();
+            assert_eq!(reader.len(), expected.len() as u64);
+
+            let mut buf = [0u8; 4];
+            reader.seek(SeekFrom::Start(3)).unwrap();
+            reader.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf, &expected[3..7], "forward seek");
+
+            reader.seek(SeekFrom::Current(-6)).unwrap();
+            reader.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf, &expected[1..5], "backward seek");
+
+            reader.seek(SeekFrom::End(-2)).unwrap();
+            let mut tail = Vec::new();
+            reader.read_to_end(&mut tail).unwrap();
+            assert_eq!(&tail, &expected[expected.len() - 2..], "seek from end");
+
+            assert!(reader.seek(SeekFrom::Current(-100)).is_err());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_entry_reader_large() {
+        use miniz::zip_read::zip_entry_reader;
+        use miniz::zip_write::{FileOptions, ZipWriter};
+        use std::io::{Read, Seek, SeekFrom};
+
+        // spans many deflate blocks, decoded step by step instead of all at once
+        let data: Vec<u8> = (0..1u32 << 20).map(|i| (i * 7 / 5 % 251) as u8).collect();
+        let mut writer = ZipWriter::new();
+        writer
+            .add_file("big", &data, &FileOptions::default())
+            .unwrap();
+        let zip = writer.finish().unwrap();
+        assert!(zip.len() < data.len() / 4);
+        let files = zip_open(&zip).unwrap();
+
+        let mut reader = zip_entry_reader(&zip, &files, "big").unwrap();
+        let mut buf = [0u8; 1000];
+        for pos in [700_000, 10, 1_000_000, 500_000] {
+            reader.seek(SeekFrom::Start(pos)).unwrap();
+            reader.read_exact(&mut buf).unwrap();
+            assert_eq!(buf, data[pos as usize..pos as usize + 1000], "at {pos}");
+        }
+
+        reader.seek(SeekFrom::Start(0)).unwrap();
+        let mut all = Vec::new();
+        reader.read_to_end(&mut all).unwrap();
+        assert_eq!(all, data);
+
+        reader.seek(SeekFrom::Start(2 << 20)).unwrap();
+        assert_eq!(reader.read(&mut buf).unwrap(), 0, "behind the end");
+
+        let files = zip_open(ENCRYPTED_ZIP).unwrap();
+        assert!(matches!(
+            zip_entry_reader(ENCRYPTED_ZIP, &files, "secret.txt"),
+            Err(Error::Encrypted)
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_entry_reader_checksum() {
+        use miniz::zip_read::zip_entry_reader;
+        use miniz::zip_write::{FileOptions, ZipWriter};
+        use std::io::{Read, Seek, SeekFrom};
+
+        let data = b"checked at the end of the entry ".repeat(50);
+        for level in [0, 6] {
+            let mut writer = ZipWriter::new();
+            let options = FileOptions {
+                level,
+                ..FileOptions::default()
+            };
+            writer.add_file("data", &data, &options).unwrap();
+            let mut zip = writer.finish().unwrap();
+            let files = zip_open(&zip).unwrap();
+
+            let mut reader = zip_entry_reader(&zip, &files, "data").unwrap();
+            let mut all = Vec::new();
+            reader.read_to_end(&mut all).unwrap();
+            assert_eq!(all, data);
+
+            // the CRC-32 of the local header
+            let offset = files[0].offset;
+            zip[offset + 14] ^= 0xff;
+            let mut reader = zip_entry_reader(&zip, &files, "data").unwrap();
+            let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData, "level {level}");
+
+            // content not read in order from the start is not checked
+            let mut reader = zip_entry_reader(&zip, &files, "data").unwrap();
+            reader.seek(SeekFrom::Start(10)).unwrap();
+            reader.read_to_end(&mut Vec::new()).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_data_descriptor() {
+        let files = zip_open(STREAMED_ZIP).unwrap();
+        let text = b"hello streaming world\n".repeat(20);
+        let offset = files[0].offset;
+        let name_len = u16::from_le_bytes([STREAMED_ZIP[offset + 26], STREAMED_ZIP[offset + 27]]);
+        let extra_len = u16::from_le_bytes([STREAMED_ZIP[offset + 28], STREAMED_ZIP[offset + 29]]);
+        let data_end = offset + 30 + (name_len + extra_len) as usize + files[0].compressed_size;
+        let descriptor = &STREAMED_ZIP[data_end..data_end + 16];
+        assert_eq!(&descriptor[..4], &[0x50, 0x4b, 0x07, 0x08]);
+        assert_eq!(descriptor[4..8], files[0].crc32.to_le_bytes());
+
+        // garbage sizes in the local header are ignored, the central directory is authoritative
+        let mut zip = STREAMED_ZIP.to_vec();
+        zip[offset + 18..offset + 26].fill(0xa5);
+        assert_eq!(zip_read(&zip, &files, "a.txt").unwrap(), text);
+
+        // the data descriptor has to agree with the central directory
+        let mut zip = STREAMED_ZIP.to_vec();
+        zip[data_end + 12] ^= 1;
+        assert_eq!(zip_read(&zip, &files, "a.txt"), Err(Error::InvalidZip));
+        let mut zip = STREAMED_ZIP.to_vec();
+        zip[data_end + 4] ^= 1;
+        assert_eq!(
+            zip_read(&zip, &files, "a.txt"),
+            Err(Error::ChecksumMismatch)
+        );
+
+        // the signature of the data descriptor is optional
+        let mut zip = STREAMED_ZIP[..data_end].to_vec();
+        zip.extend_from_slice(&descriptor[4..]);
+        assert_eq!(zip_read(&zip, &files, "a.txt").unwrap(), text);
+        assert_eq!(
+            zip_read(&zip[..data_end + 8], &files, "a.txt"),
+            Err(Error::BufferError)
+        );
+    }
+
+    #[test]
+    fn test_open_lossy() {
+        use miniz::zip_read::zip_open_lossy;
+
+        // intact archives are read through the central directory
+        assert_eq!(
+            format!("{:?}", zip_open_lossy(DEFLATED_ZIP)),
+            format!("{:?}", zip_open(DEFLATED_ZIP))
+        );
+
+        // the central directory is cut off, the local headers remain
+        for zip in [DEFLATED_ZIP, STREAMED_ZIP, ZIP64_ZIP, BZIP2_ZIP] {
+            let files = zip_open(zip).unwrap();
+            let cd = zip.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+            let partial = &zip[..cd];
+            assert!(zip_open(partial).is_err());
+            let recovered = zip_open_lossy(partial).unwrap();
+            assert_eq!(recovered.len(), files.len());
+            for (file, orig) in recovered.iter().zip(&files) {
+                assert_eq!(
+                    (&file.name, file.offset, file.crc32, file.size),
+                    (&orig.name, orig.offset, orig.crc32, orig.size)
+                );
+                assert_eq!(
+                    zip_read(partial, &recovered, &file.name).unwrap(),
+                    zip_read(zip, &files, &file.name).unwrap()
+                );
+            }
+
+            // an entry whose data is cut off is left out
+            let last = files.last().unwrap().offset;
+            let recovered = zip_open_lossy(&zip[..last + 40]).unwrap();
+            assert_eq!(recovered.len(), files.len() - 1);
+        }
+
+        // data descriptors without signature end with the deflate stream
+        let text = b"hello streaming world\n".repeat(20);
+        let cd = STREAMED_ZIP.windows(4).position(|w| w == b"PK\x01\x02");
+        let mut zip = STREAMED_ZIP[..cd.unwrap()].to_vec();
+        while let Some(pos) = zip.windows(4).position(|w| w == b"PK\x07\x08") {
+            zip.drain(pos..pos + 4);
+        }
+        let recovered = zip_open_lossy(&zip).unwrap();
+        assert_eq!(recovered.len(), 2);
+        assert_eq!(zip_read(&zip, &recovered, "a.txt").unwrap(), text);
+
+        assert_eq!(
+            zip_open_lossy(&[0; 100]).err(),
+            Some(Error::NoCentralDirectory)
+        );
+    }
+
+    #[test]
+    fn test_encrypted() {
+        use miniz::zip_read::zip_read_encrypted;
+
+        // a deflated and a stored entry, both with data descriptors
+        let files = zip_open(ENCRYPTED_ZIP).unwrap();
+        let text = b"zipcrypto protected text\n".repeat(20);
+        assert_eq!(
+            zip_read_encrypted(ENCRYPTED_ZIP, &files, "secret.txt", b"secret").unwrap(),
+            text
+        );
+        assert_eq!(
+            zip_read_encrypted(ENCRYPTED_ZIP, &files, "stored.bin", b"secret").unwrap(),
+            b"stored and encrypted\n"
+        );
+
+        for name in ["secret.txt", "stored.bin"] {
+            assert_eq!(
+                zip_read_encrypted(ENCRYPTED_ZIP, &files, name, b"wrong"),
+                Err(Error::WrongPassword)
+            );
+            assert_eq!(
+                zip_read(ENCRYPTED_ZIP, &files, name),
+                Err(Error::WrongPassword)
+            );
+        }
+
+        let zip = miniz::zip_read::ZipArchive::from_slice(ENCRYPTED_ZIP).unwrap();
+        assert_eq!(
+            zip.by_name_encrypted("secret.txt", b"secret").unwrap(),
+            text
+        );
+
+        // unencrypted entries ignore the password
+        let files = zip_open(DEFLATED_ZIP).unwrap();
+        assert_eq!(
+            zip_read_encrypted(DEFLATED_ZIP, &files, "first", b"secret").unwrap(),
+            FIRST
+        );
+    }
+
+    #[test]
+    fn test_aes() {
+        use miniz::zip_read::zip_read_encrypted;
+
+        // AE-1 with AES-256 and deflate, AE-2 with AES-192 and deflate, AE-2 with AES-128 stored
+        let files = zip_open(AES_ZIP).unwrap();
+        let expected: [(&str, Vec<u8>); 3] = [
+            ("aes256.txt", b"WinZip AES-256 protected text\n".repeat(20)),
+            ("aes192.txt", b"AES-192, AE-2 without CRC-32\n".repeat(5)),
+            ("aes128.bin", b"stored with AES-128\n".to_vec()),
+        ];
+        for (name, text) in &expected {
+            // without the `aes` feature the entries cannot be decrypted
+            let file = zip_read_encrypted(AES_ZIP, &files, name, b"secret");
+            let expected = match cfg!(feature = "aes") {
+                true => Ok(text.clone()),
+                false => Err(Error::Encrypted),
+            };
+            assert_eq!(file, expected, "{name}");
+            assert_eq!(zip_read(AES_ZIP, &files, name), Err(Error::WrongPassword));
+        }
+
+        #[cfg(feature = "aes")]
+        {
+            for (name, _) in &expected {
+                let file = zip_read_encrypted(AES_ZIP, &files, name, b"wrong");
+                assert_eq!(file, Err(Error::WrongPassword), "{name}");
+            }
+
+            // a flipped bit of the encrypted data fails the authentication code
+            let mut zip = AES_ZIP.to_vec();
+            let ofs = files[0].offset + 30 + files[0].name.len() + 11 + 16 + 2;
+            zip[ofs + 5] ^= 1;
+            assert_eq!(
+                zip_read_encrypted(&zip, &files, "aes256.txt", b"secret"),
+                Err(Error::ChecksumMismatch)
+            );
+        }
+    }
+
+    #[test]
+    fn test_malformed() {
+        use miniz::zip_read::zip_entries;
+
+        // truncated and corrupted archives fail with an error instead of panicking
+        for zip in [
+            DEFLATED_ZIP,
+            NESTED_ZIP,
+            STREAMED_ZIP,
+            ZIP64_ZIP,
+            ENCRYPTED_ZIP,
+        ] {
+            let files = zip_open(zip).unwrap();
+            for len in 0..zip.len() {
+                let _ = zip_entries(&zip[..len]);
+                let _ = zip_read(&zip[..len], &files, &files[0].name);
+            }
+            for pos in 0..zip.len() {
+                for flip in [0x01, 0x80, 0xff] {
+                    let mut broken = zip.to_vec();
+                    broken[pos] ^= flip;
+                    if let Ok(files) = zip_open(&broken) {
+                        for file in &files {
+                            let _ = zip_read(&broken, &files, &file.name);
+                        }
+                    }
+                }
+            }
+        }
+
+        // sizes beyond the deflate expansion limit are rejected before allocating
+        let mut files = zip_open(DEFLATED_ZIP).unwrap();
+        files[0].size = usize::MAX;
+        assert_eq!(
+            zip_read(DEFLATED_ZIP, &files, &files[0].name.clone()),
+            Err(Error::InvalidZip)
+        );
+    }
+
+    #[test]
+    fn test_zip64() {
+        // ZIP64 end of central directory record, "big.txt" with all sizes and its offset in
+        // ZIP64 extra fields of the local header and the central directory record
+        let zip = zip_open(ZIP64_ZIP).unwrap();
+        assert_eq!(zip.len(), 2);
+        assert_eq!(zip[1].offset, 51);
+        assert_eq!(
+            zip_read(ZIP64_ZIP, &zip, "small.txt").unwrap(),
+            b"not zip64\n"
+        );
+        let big = zip_read(ZIP64_ZIP, &zip, "big.txt").unwrap();
+        assert_eq!(big, b"zip64 sizes and offset, ".repeat(40));
+
+        let entries = miniz::zip_read::zip_entries(ZIP64_ZIP).unwrap();
+        assert_eq!(entries[1].size, 960);
+
+        // ZIP64 locator pointing at a missing record
+        let mut broken = ZIP64_ZIP.to_vec();
+        let locator = broken.len() - 22 - 20;
+        broken[locator + 8] ^= 0x40;
+        assert_eq!(zip_open(&broken).err(), Some(Error::BufferError));
+    }
+
+    #[test]
+    fn test_validate() {
+        use miniz::zip_read::{zip_entries, zip_validate, Mismatch, ZipArchive};
+        use miniz::zip_write::{FileOptions, ZipWriter};
+
+        // data descriptors, ZIP64, directories, bzip2 and UTF-8 names are consistent
+        for zip in [
+            DEFLATED_ZIP,
+            STREAMED_ZIP,
+            ZIP64_ZIP,
+            FOLDER_ZIP,
+            BZIP2_ZIP,
+            UTF8_ZIP,
+        ] {
+            let reports = zip_validate(zip).unwrap();
+            assert_eq!(reports.len(), zip_entries(zip).unwrap().len());
+            assert!(reports.iter().all(|report| report.is_ok()), "{reports:?}");
+        }
+        let reports = zip_validate(ENCRYPTED_ZIP).unwrap();
+        assert!(reports.iter().all(|r| r.status == Err(Error::Encrypted)));
+
+        let text = b"listed as a.txt, extracted as b.txt ".repeat(20);
+        let mut writer = ZipWriter::new();
+        writer
+            .add_file("a.txt", &text, &FileOptions::default())
+            .unwrap();
+        let stored = FileOptions {
+            level: 0,
+            ..Default::default()
+        };
+        writer.add_file("c.txt", b"stored", &stored).unwrap();
+        let zip = writer.finish().unwrap();
+        let zip_archive = ZipArchive::from_slice(&zip).unwrap();
+        assert!(zip_archive.validate().unwrap().iter().all(|r| r.is_ok()));
+
+        let entries = zip_entries(&zip).unwrap();
+        let (a, c) = (entries[0].offset, entries[1].offset);
+        let cd = zip.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+
+        // another local name, method and size, the data still matches the local CRC-32
+        let mut broken = zip.clone();
+        broken[a + 30] = b'b';
+        broken[c + 8] = 8;
+        broken[c + 22] += 1;
+        let reports = zip_validate(&broken).unwrap();
+        assert_eq!(reports[0].mismatches, [Mismatch::Name("b.txt".into())]);
+        assert_eq!(reports[0].status, Ok(()));
+        assert_eq!(
+            reports[1].mismatches,
+            [Mismatch::Method(8), Mismatch::Size(7)]
+        );
+        assert!(reports[1].status.is_err());
+
+        // a central CRC-32 that only the local header contradicts, and damaged data
+        let mut broken = zip.clone();
+        broken[cd + 16] ^= 1;
+        broken[a + 40] ^= 1;
+        let reports = zip_validate(&broken).unwrap();
+        let crc = u32::from_le_bytes(zip[a + 14..a + 18].try_into().unwrap());
+        assert_eq!(reports[0].mismatches, [Mismatch::Crc32(crc)]);
+        assert!(reports[0].status.is_err());
+        assert!(reports[1].is_ok());
+
+        // a local header that is not there
+        let mut broken = zip.clone();
+        broken[c] = 0;
+        let reports = zip_validate(&broken).unwrap();
+        assert_eq!(reports[1].status, Err(Error::InvalidSignature));
+        assert_eq!(
+            zip_validate(&zip[..20]).err(),
+            Some(Error::NoCentralDirectory)
+        );
+    }
+
+    #[test]
+    fn test_deflate64() {
+        use miniz::bits::BitWriter;
+        use miniz::zip_write::{FileOptions, ZipWriter};
+
+        // "ab" and a match of 103 bytes with the 16 extra bits of the Deflate64 length code 285
+        let mut stream = Vec::new();
+        let mut bw = BitWriter::new(&mut stream);
+        bw.put(0b011, 3); // final fixed Huffman block
+        bw.put_code(0x30 + b'a' as u32, 8);
+        bw.put_code(0x30 + b'b' as u32, 8);
+        bw.put_code(0xc5, 8);
+        bw.put(100, 16);
+        bw.put_code(1, 5); // distance 2
+        bw.put_code(0, 7);
+        bw.flush();
+        let expected = b"ab".repeat(53)[..105].to_vec();
+
+        // a stored entry of the same size turned into a method 9 entry
+        let mut writer = ZipWriter::new();
+        let options = FileOptions {
+            level: 0,
+            ..Default::default()
+        };
+        let placeholder = vec![0; stream.len()];
+        writer.add_file("d64", &placeholder, &options).unwrap();
+        let mut zip = writer.finish().unwrap();
+        let eocd = zip.len() - 22;
+        let cd = u32::from_le_bytes(zip[eocd + 16..eocd + 20].try_into().unwrap()) as usize;
+        let crc = miniz::checksum::crc32(0, &expected).to_le_bytes();
+        let size = (expected.len() as u32).to_le_bytes();
+        for (header, ofs) in [(0, 8), (cd, 10)] {
+            zip[header + ofs..header + ofs + 2].copy_from_slice(&9u16.to_le_bytes());
+            zip[header + ofs + 6..header + ofs + 10].copy_from_slice(&crc);
+            zip[header + ofs + 14..header + ofs + 18].copy_from_slice(&size);
+        }
+        zip[33..33 + stream.len()].copy_from_slice(&stream);
+
+        let files = zip_open(&zip).unwrap();
+        assert_eq!(zip_read(&zip, &files, "d64").unwrap(), expected);
+    }
+
+    #[test]
+    fn test_bzip2() {
+        // written by Python's zipfile with bzip2 level 1, "text.txt" spans two 100k blocks
+        let zip = zip_open(BZIP2_ZIP).unwrap();
+        let runs = zip_read(BZIP2_ZIP, &zip, "runs.bin").unwrap();
+        let expected: Vec<u8> = (0..600).flat_map(|i| vec![i as u8; i % 300 + 1]).collect();
+        assert_eq!(runs, expected);
+        let text = zip_read(BZIP2_ZIP, &zip, "text.txt").unwrap();
+        assert_eq!(text.len(), 165048);
+
+        let mut broken = BZIP2_ZIP.to_vec();
+        let ofs = zip[0].offset + 30 + 8 + 20;
+        broken[ofs] ^= 0x01;
+        assert!(zip_read(&broken, &zip, "runs.bin").is_err());
+    }
+
+    #[test]
+    fn test_multi_disk() {
+        let eocd = DEFLATED_ZIP
+            .windows(4)
+            .rposition(|sig| sig == [0x50, 0x4b, 0x05, 0x06])
+            .unwrap();
+        let cd = u32::from_le_bytes(DEFLATED_ZIP[eocd + 16..eocd + 20].try_into().unwrap());
+
+        // the last disk of a spanned archive, the central directory on another disk
+        // and an entry starting on another disk
+        for ofs in [eocd + 4, eocd + 6, cd as usize + 34] {
+            let mut zip = DEFLATED_ZIP.to_vec();
+            zip[ofs] = 1;
+            assert_eq!(zip_open(&zip).err(), Some(Error::MultiDiskUnsupported));
+        }
+
+        // ZIP64 locator with a total of two disks
+        let mut zip = ZIP64_ZIP.to_vec();
+        let locator = zip.len() - 22 - 20;
+        zip[locator + 16] = 2;
+        assert_eq!(zip_open(&zip).err(), Some(Error::MultiDiskUnsupported));
+    }
+
+    #[test]
+    fn test_warnings() {
+        use miniz::inflate::InflateOptions;
+        use miniz::warning::Warning;
+
+        let warnings = std::cell::RefCell::new(Vec::new());
+        let options = InflateOptions {
+            warnings: Some(&warnings),
+            ..Default::default()
+        };
+        assert_eq!(
+            zip_open_with_options(DEFLATED_ZIP, &options).unwrap().len(),
+            2
+        );
+        assert!(warnings.borrow().is_empty());
+
+        // trailing bytes after the end of central directory record
+        let mut zip = DEFLATED_ZIP.to_vec();
+        zip.extend_from_slice(b"junk");
+        assert_eq!(zip_open_with_options(&zip, &options).unwrap().len(), 2);
+        let offset = DEFLATED_ZIP.len() - 22;
+        assert_eq!(
+            warnings.into_inner(),
+            [Warning::ZipSuspiciousEocd { offset }]
+        );
+    }
+
+    #[test]
+    fn test_prefixed() {
+        use miniz::inflate::DecodeProfile;
+        use miniz::warning::Warning;
+        use miniz::zip_read::{ZipArchive, ZipReader};
+
+        // archives appended to a stub keep offsets relative to their own start
+        let stub = b"MZ\x90\x00 self-extractor stub, not a real executable".repeat(10);
+        for zip in [DEFLATED_ZIP, STREAMED_ZIP, ZIP64_ZIP] {
+            let mut sfx = stub.clone();
+            sfx.extend_from_slice(zip);
+
+            let warnings = std::cell::RefCell::new(Vec::new());
+            let options = InflateOptions {
+                warnings: Some(&warnings),
+                ..Default::default()
+            };
+            let files = zip_open_with_options(&sfx, &options).unwrap();
+            let len = stub.len();
+            assert_eq!(warnings.into_inner(), [Warning::ZipPrefix { len }]);
+            let strict = InflateOptions::profile(DecodeProfile::Strict);
+            let result = zip_open_with_options(&sfx, &strict);
+            assert_eq!(result.err(), Some(Error::InvalidZip));
+            assert!(zip_open_with_options(zip, &strict).is_ok());
+
+            let plain = zip_open(zip).unwrap();
+            assert_eq!(files.len(), plain.len());
+            for (file, orig) in files.iter().zip(&plain) {
+                assert_eq!(file.offset, orig.offset + len);
+                assert_eq!(
+                    zip_read(&sfx, &files, &file.name).unwrap(),
+                    zip_read(zip, &plain, &file.name).unwrap()
+                );
+            }
+
+            let mut reader = ZipReader::new(std::io::Cursor::new(&sfx)).unwrap();
+            for file in &plain {
+                let data = reader.by_name(&file.name).unwrap();
+                assert_eq!(data, zip_read(zip, &plain, &file.name).unwrap());
+            }
+        }
+
+        // rewriting drops the stub
+        let mut sfx = stub.clone();
+        sfx.extend_from_slice(DEFLATED_ZIP);
+        let archive = ZipArchive::from_slice(&sfx).unwrap();
+        let rewritten = archive.append().unwrap().finish().unwrap();
+        let files = zip_open(&rewritten).unwrap();
+        assert_eq!(files[0].offset, 0);
+        assert_eq!(zip_read(&rewritten, &files, "second").unwrap(), SECOND);
+    }
+
+    #[test]
+    fn test_archive() {
+        use miniz::zip_read::{Error, ZipArchive};
+
+        let zip = ZipArchive::from_vec(DEFLATED_ZIP.to_vec()).unwrap();
+        assert_eq!(zip.len(), 2);
+        assert_eq!(zip.files()[1].name, "second");
+        assert_eq!(zip.by_name("first").unwrap(), FIRST);
+        assert_eq!(zip.by_index(1).unwrap(), SECOND);
+        assert_eq!(zip.by_index(2), Err(Error::FileNotFound));
+        assert_eq!(zip.by_name("third"), Err(Error::FileNotFound));
+        assert_eq!(
+            ZipArchive::from_vec(vec![0; 16]).err(),
+            Some(Error::NoCentralDirectory)
+        );
+
+        #[cfg(feature = "png")]
+        {
+            let zip = ZipArchive::from_vec(TEXTURES_ZIP.to_vec()).unwrap();
+            let options = miniz::inflate::InflateOptions::default();
+            let (ihdr, _, _) = zip.read_png("textures/deflated.png", &options).unwrap();
+            assert_eq!(ihdr.width, 32);
+        }
+    }
+
+    #[test]
+    fn test_archive_lookup() {
+        use miniz::zip_read::ZipArchive;
+
+        let zip = ZipArchive::from_slice(FOLDER_ZIP).unwrap();
+        let names: Vec<_> = zip.iter().map(|file| file.name.as_str()).collect();
+        assert_eq!(names, ["test", "testdir/", "testdir/test2"]);
+        assert_eq!((&zip).into_iter().count(), 3);
+        let dirs: Vec<_> = zip.iter().map(|file| file.is_dir()).collect();
+        assert_eq!(dirs, [false, true, false]);
+
+        assert_eq!(zip.file("testdir/test2").unwrap().size, 5);
+        assert!(zip.file("testdir").is_none());
+        let names = |files: Vec<&miniz::zip_read::File>| -> Vec<String> {
+            files.iter().map(|file| file.name.clone()).collect()
+        };
+        assert_eq!(
+            names(zip.entries_with_prefix("testdir/").collect()),
+            ["testdir/", "testdir/test2"]
+        );
+        assert_eq!(names(zip.entries_with_prefix("").collect()).len(), 3);
+        assert!(zip.entries_with_prefix("x").next().is_none());
+
+        // wildcards don't match '/'
+        assert_eq!(names(zip.entries_matching("test*").collect()), ["test"]);
+        assert_eq!(
+            names(zip.entries_matching("*/test?").collect()),
+            ["testdir/test2"]
+        );
+        assert_eq!(names(zip.entries_matching("*").collect()), ["test"]);
+        assert_eq!(names(zip.entries_matching("t*t").collect()), ["test"]);
+        assert_eq!(names(zip.entries_matching("testdir/*").collect()).len(), 2);
+
+        let zip = ZipArchive::from_slice(TEXTURES_ZIP).unwrap();
+        assert_eq!(zip.entries_matching("textures/*.png").count(), 2);
+        assert_eq!(zip.entries_matching("textures/s*.png").count(), 1);
+    }
+
+    #[test]
+    fn test_open_nested() {
+        use miniz::zip_read::{Error, ZipArchive};
+
+        let zip = ZipArchive::from_slice(NESTED_ZIP).unwrap();
+        for name in ["mods/stored.zip", "mods/deflated.zip"] {
+            let inner = zip.open_nested(name).unwrap();
+            assert_eq!(inner.data(), DEFLATED_ZIP, "{name}");
+            assert_eq!(inner.by_name("first").unwrap(), FIRST, "{name}");
+            assert_eq!(inner.by_name("second").unwrap(), SECOND, "{name}");
+        }
+
+        // the stored archive is read in place
+        let inner = zip.open_nested("mods/stored.zip").unwrap();
+        assert!(NESTED_ZIP.as_ptr_range().contains(&inner.data().as_ptr()));
+
+        assert_eq!(
+            zip.open_nested("readme.txt").err(),
+            Some(Error::NoCentralDirectory)
+        );
+        assert_eq!(zip.open_nested("mods").err(), Some(Error::FileNotFound));
+    }
+
+    #[test]
+    fn test_entry_slice() {
+        use miniz::zip_read::{zip_entry_slice, ZipArchive};
+        use std::borrow::Cow;
+
+        let files = zip_open(NESTED_ZIP).unwrap();
+        for file in &files {
+            let slice = zip_entry_slice(NESTED_ZIP, file).unwrap();
+            assert_eq!(slice, zip_read(NESTED_ZIP, &files, &file.name).unwrap());
+            match file.name.as_str() {
+                "mods/stored.zip" => {
+                    assert!(matches!(slice, Cow::Borrowed(_)));
+                    assert!(NESTED_ZIP.as_ptr_range().contains(&slice.as_ptr()));
+                }
+                "mods/deflated.zip" => assert!(matches!(slice, Cow::Owned(_))),
+                _ => {}
+            }
+        }
+
+        let zip = ZipArchive::from_slice(NESTED_ZIP).unwrap();
+        let slice = zip.entry_slice("mods/stored.zip").unwrap();
+        assert_eq!(slice, DEFLATED_ZIP);
+        assert_eq!(zip.entry_slice("mods").err(), Some(Error::FileNotFound));
+
+        // borrowed data is verified as well
+        let file = files.iter().find(|f| f.name == "mods/stored.zip").unwrap();
+        let mut zip = NESTED_ZIP.to_vec();
+        let pos = zip
+            .windows(DEFLATED_ZIP.len())
+            .position(|w| w == DEFLATED_ZIP);
+        zip[pos.unwrap()] ^= 1;
+        assert_eq!(
+            zip_entry_slice(&zip, file).err(),
+            Some(Error::ChecksumMismatch)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_archive_open_path() {
+        use miniz::zip_read::ZipArchive;
+
+        let path = std::env::temp_dir().join("miniz_test_archive_open_path.zip");
+        std::fs::write(&path, DEFLATED_ZIP).unwrap();
+        let zip = ZipArchive::open_path(&path).unwrap();
+        assert_eq!(zip.by_name("second").unwrap(), SECOND);
+
+        std::fs::write(&path, [0u8; 16]).unwrap();
+        let err = ZipArchive::open_path(&path).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_archive_from_reader() {
+        use miniz::zip_read::{zip_read_encrypted, ZipArchive};
+        use miniz::zip_write::{FileOptions, ZipWriter};
+        use std::io::{Cursor, Read, Seek, SeekFrom};
+
+        // counts the bytes read from the archive
+        struct Counting<R> {
+            inner: R,
+            read: usize,
+        }
+        impl<R: Read> Read for Counting<R> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let len = self.inner.read(buf)?;
+                self.read += len;
+                Ok(len)
+            }
+        }
+        impl<R: Seek> Seek for Counting<R> {
+            fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+                self.inner.seek(pos)
+            }
+        }
+
+        for (zip, names) in [
+            (COMMENTS_ZIP, &["file1", "file2", "file3", "file4"][..]),
+            (DEFLATED_ZIP, &["first", "second"]),
+            (STREAMED_ZIP, &["a.txt"]),
+            (ZIP64_ZIP, &["small.txt", "big.txt"]),
+        ] {
+            let files = zip_open(zip).unwrap();
+            let mut reader = ZipArchive::from_reader(Cursor::new(zip)).unwrap();
+            assert_eq!(reader.len(), files.len());
+            for (i, name) in names.iter().enumerate() {
+                let expected = zip_read(zip, &files, name).unwrap();
+                assert_eq!(reader.by_name(name).unwrap(), expected, "{name}");
+                assert_eq!(
+                    reader.by_index(i).unwrap(),
+                    zip_read(zip, &files, &files[i].name).unwrap()
+                );
+            }
+        }
+
+        let files = zip_open(ENCRYPTED_ZIP).unwrap();
+        let mut reader = ZipArchive::from_reader(Cursor::new(ENCRYPTED_ZIP)).unwrap();
+        for name in ["secret.txt", "stored.bin"] {
+            let expected = zip_read_encrypted(ENCRYPTED_ZIP, &files, name, b"secret").unwrap();
+            assert_eq!(reader.by_name_encrypted(name, b"secret").unwrap(), expected);
+        }
+
+        // a small entry behind a large stored one is read without the large one
+        let mut writer = ZipWriter::new();
+        let stored = FileOptions {
+            level: 0,
+            ..FileOptions::default()
+        };
+        let large: Vec<u8> = (0..1 << 20).map(|i| (i * 7 % 251) as u8).collect();
+        writer.add_file("large.bin", &large, &stored).unwrap();
+        writer
+            .add_file("small.txt", b"small", &FileOptions::default())
+            .unwrap();
+        let zip = writer.finish().unwrap();
+        let counting = Counting {
+            inner: Cursor::new(&zip),
+            read: 0,
+        };
+        let mut reader = ZipArchive::from_reader(counting).unwrap();
+        assert_eq!(reader.file("large.bin").unwrap().size, large.len());
+        assert_eq!(reader.by_name("small.txt").unwrap(), b"small");
+        // the tail searched for the end of central directory record and the entry itself
+        assert!(reader.into_inner().read < 0x10000 + 1024);
+
+        let mut reader = ZipArchive::from_reader(Cursor::new(&zip)).unwrap();
+        assert_eq!(reader.by_name("large.bin").unwrap(), large);
+        let err = reader.by_name("missing").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        let err = ZipArchive::from_reader(Cursor::new([0u8; 16])).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_extract_all() {
+        use miniz::zip_read::zip_extract_all;
+        use std::path::{Path, PathBuf};
+
+        let dir = std::env::temp_dir().join("miniz_test_extract_all");
+        let _ = std::fs::remove_dir_all(&dir);
+        let files = zip_open(FOLDER_ZIP).unwrap();
+
+        // flatten folders, rename "test" and skip directory entries
+        let count = zip_extract_all(FOLDER_ZIP, &files, &dir, |name| match name {
+            "test" => Some(PathBuf::from("renamed")),
+            _ if name.ends_with('/') => None,
+            _ => Path::new(name).file_name().map(PathBuf::from),
+        })
+        .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(std::fs::read(dir.join("renamed")).unwrap(), TEST);
+        assert_eq!(std::fs::read(dir.join("test2")).unwrap(), TEST);
+        assert!(!dir.join("testdir").exists());
+
+        // paths leaving the target directory are skipped
+        let count = zip_extract_all(FOLDER_ZIP, &files, &dir, |name| {
+            Some(Path::new("..").join(name))
+        })
+        .unwrap();
+        assert_eq!(count, 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_extract_all_metadata() {
+        use miniz::zip_read::zip_extract_all;
+        use miniz::zip_write::{FileOptions, ZipWriter};
+        use std::path::PathBuf;
+        use std::time::{Duration, UNIX_EPOCH};
+
+        // 2024-02-29 13:45:30
+        let dos_time = ((2024 - 1980) << 25) | (2 << 21) | (29 << 16) | (13 << 11) | (45 << 5) | 15;
+        let options = FileOptions {
+            dos_time,
+            unix_mode: Some(0o4750),
+            ..Default::default()
+        };
+        let mut writer = ZipWriter::new();
+        writer.add_file("bin/tool", b"#!/bin/sh", &options).unwrap();
+        for name in ["../evil", "/etc/evil", "a/../../evil"] {
+            writer.add_file(name, b"evil", &options).unwrap();
+        }
+        let zip = writer.finish().unwrap();
+
+        let dir = std::env::temp_dir().join("miniz_test_extract_all_metadata");
+        let _ = std::fs::remove_dir_all(&dir);
+        let files = zip_open(&zip).unwrap();
+        let count = zip_extract_all(&zip, &files, &dir.join("out"), |name| {
+            Some(PathBuf::from(name))
+        })
+        .unwrap();
+        assert_eq!(count, 1, "paths leaving the target are skipped");
+        assert!(!dir.join("evil").exists());
+
+        let path = dir.join("out/bin/tool");
+        assert_eq!(std::fs::read(&path).unwrap(), b"#!/bin/sh");
+        let metadata = std::fs::metadata(&path).unwrap();
+        let mtime = UNIX_EPOCH + Duration::from_secs(1709214330);
+        assert_eq!(metadata.modified().unwrap(), mtime);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(metadata.permissions().mode() & 0o7777, 0o750);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_extract_all_read_only() {
+        use miniz::zip_read::zip_extract_all;
+        use miniz::zip_write::{FileOptions, ZipWriter};
+        use std::path::PathBuf;
+        use std::time::{Duration, UNIX_EPOCH};
+
+        // 2024-02-29 13:45:30, a read-only directory holding a read-only file
+        let dos_time = ((2024 - 1980) << 25) | (2 << 21) | (29 << 16) | (13 << 11) | (45 << 5) | 15;
+        let options = |unix_mode| FileOptions {
+            dos_time,
+            unix_mode: Some(unix_mode),
+            ..Default::default()
+        };
+        let mut writer = ZipWriter::new();
+        writer.add_directory("ro/", &options(0o555)).unwrap();
+        writer
+            .add_file("ro/data", b"data", &options(0o444))
+            .unwrap();
+        let zip = writer.finish().unwrap();
+
+        let dir = std::env::temp_dir().join("miniz_test_extract_all_read_only");
+        let _ = std::fs::remove_dir_all(&dir);
+        let files = zip_open(&zip).unwrap();
+        let count = zip_extract_all(&zip, &files, &dir, |name| Some(PathBuf::from(name))).unwrap();
+        assert_eq!(count, 1);
+
+        let mtime = UNIX_EPOCH + Duration::from_secs(1709214330);
+        let path = dir.join("ro/data");
+        assert_eq!(std::fs::read(&path).unwrap(), b"data");
+        assert_eq!(std::fs::metadata(&path).unwrap().modified().unwrap(), mtime);
+        #[cfg(unix)]
+        for (path, mode) in [(path, 0o444), (dir.join("ro"), 0o555)] {
+            use std::os::unix::fs::PermissionsExt;
+            let metadata = std::fs::metadata(&path).unwrap();
+            assert_eq!(metadata.modified().unwrap(), mtime, "{path:?}");
+            assert_eq!(metadata.permissions().mode() & 0o777, mode, "{path:?}");
+            // writable again for the cleanup
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
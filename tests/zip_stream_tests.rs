@@ -0,0 +1,79 @@
+#![cfg(all(feature = "std", feature = "zip"))]
+
+// ----------------------------------------------------------------------------
+mod test {
+    use miniz::checksum::crc32;
+    use miniz::zip_read::{zip_entries, zip_open, zip_read};
+    use miniz::zip_stream::ZipStreamReader;
+    use std::io::Read;
+
+    include!("../assets/zip/nested.rs");
+    include!("../assets/zip/streamed.rs");
+
+    // forward-only reader returning at most 7 bytes per call
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = buf.len().min(self.0.len()).min(7);
+            buf[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn test_stream_entries() {
+        for zip in [STREAMED_ZIP, NESTED_ZIP] {
+            let expected = zip_entries(zip).unwrap();
+            let entries: Vec<_> = ZipStreamReader::new(Trickle(zip))
+                .collect::<std::io::Result<_>>()
+                .unwrap();
+
+            assert_eq!(entries.len(), expected.len());
+            for ((entry, data), cd) in entries.iter().zip(&expected) {
+                assert_eq!(entry.name, cd.name);
+                assert_eq!(entry.offset, cd.offset);
+                assert_eq!(
+                    (entry.crc32, entry.compressed_size, entry.size),
+                    (cd.crc32, cd.compressed_size, cd.size)
+                );
+                assert_eq!(crc32(0, data), cd.crc32);
+            }
+        }
+
+        let files = zip_open(NESTED_ZIP).unwrap();
+        let (_, data) = ZipStreamReader::new(NESTED_ZIP).next().unwrap().unwrap();
+        assert_eq!(
+            data,
+            zip_read(NESTED_ZIP, &files, "mods/stored.zip").unwrap()
+        );
+
+        // entries written with data descriptors have no sizes in their local headers
+        assert_eq!(zip_entries(STREAMED_ZIP).unwrap()[0].flags & 8, 8);
+        let (_, data) = ZipStreamReader::new(STREAMED_ZIP).next().unwrap().unwrap();
+        assert_eq!(data, b"hello streaming world\n".repeat(20));
+    }
+
+    #[test]
+    fn test_stream_errors() {
+        // corrupted deflate data of the first entry
+        let mut zip = STREAMED_ZIP.to_vec();
+        zip[40] ^= 0xff;
+        let mut reader = ZipStreamReader::new(&zip[..]);
+        let err = reader.next_entry().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(reader.next().is_some());
+
+        let mut reader = ZipStreamReader::new(&[0u8; 30][..]);
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none(), "iteration ends after an error");
+
+        // truncated inside the second entry
+        let reader = ZipStreamReader::new(&STREAMED_ZIP[..100]);
+        let results: Vec<_> = reader.collect();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+    }
+}
@@ -0,0 +1,422 @@
+#![cfg(feature = "zip")]
+
+// ----------------------------------------------------------------------------
+mod test {
+    use miniz::zip_read::Error;
+    use miniz::zip_read::{zip_open, zip_read};
+    use miniz::zip_write::{
+        zip_merge, zip_recompress, DuplicatePolicy, ExtraField, FileOptions, ZipWriter, DOS_EPOCH,
+    };
+
+    include!("../assets/zip/comments.rs");
+    include!("../assets/zip/deflated.rs");
+    include!("../assets/zip/folder.rs");
+    include!("../assets/zip/recompress.rs");
+
+    fn assert_same_entries(a: &[u8], b: &[u8]) {
+        let files_a = zip_open(a).unwrap();
+        let files_b = zip_open(b).unwrap();
+        assert_eq!(files_a.len(), files_b.len());
+        for (file_a, file_b) in files_a.iter().zip(&files_b) {
+            assert_eq!(file_a.name, file_b.name);
+            assert_eq!(
+                zip_read(a, &files_a, &file_a.name),
+                zip_read(b, &files_b, &file_b.name)
+            );
+        }
+    }
+
+    #[test]
+    fn test_recompress() {
+        // the fixed Huffman coded noise entry shrinks, the other entries are kept
+        let zip = zip_recompress(RECOMPRESS_ZIP, 9).unwrap();
+        assert!(zip.len() < RECOMPRESS_ZIP.len());
+        assert_same_entries(RECOMPRESS_ZIP, &zip);
+
+        let contains = |data: &[u8], s: &[u8]| data.windows(s.len()).any(|w| w == s);
+        assert!(contains(&zip, b"fixed huffman noise"), "file comment");
+        assert!(contains(&zip, b"\xfe\xca\x04\x00test"), "extra field");
+        assert!(zip.ends_with(b"archive comment"));
+
+        // nothing left to gain in a second pass
+        assert_eq!(zip_recompress(&zip, 9).unwrap(), zip);
+    }
+
+    #[test]
+    fn test_recompress_comments() {
+        let zip = zip_recompress(COMMENTS_ZIP, 9).unwrap();
+        assert!(zip.len() <= COMMENTS_ZIP.len());
+        assert_same_entries(COMMENTS_ZIP, &zip);
+        let files = zip_open(&zip).unwrap();
+        let expected: [&[u8]; 4] = [&FILE1, &FILE2, &FILE3, &FILE4];
+        for (name, expected) in ["file1", "file2", "file3", "file4"].iter().zip(expected) {
+            assert_eq!(zip_read(&zip, &files, name).unwrap(), expected);
+        }
+        assert_eq!(
+            zip_recompress(&[0u8; 16], 9),
+            Err(Error::NoCentralDirectory)
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let zip = zip_merge(&[DEFLATED_ZIP, FOLDER_ZIP], DuplicatePolicy::Reject).unwrap();
+        let files = zip_open(&zip).unwrap();
+        let names = files.iter().map(|f| f.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            ["first", "second", "test", "testdir/", "testdir/test2"]
+        );
+        assert_eq!(zip_read(&zip, &files, "second").unwrap(), SECOND);
+        assert_eq!(zip_read(&zip, &files, "testdir/test2").unwrap(), TEST);
+        assert_same_entries(
+            &zip_merge(&[RECOMPRESS_ZIP], DuplicatePolicy::Reject).unwrap(),
+            RECOMPRESS_ZIP,
+        );
+    }
+
+    #[test]
+    fn test_merge_duplicates() {
+        // "first" of the deflated archive renamed to "file1"
+        let mut renamed = DEFLATED_ZIP.to_vec();
+        for i in 0..renamed.len() - 5 {
+            if &renamed[i..i + 5] == b"first" {
+                renamed[i..i + 5].copy_from_slice(b"file1");
+            }
+        }
+
+        let sources: [&[u8]; 2] = [COMMENTS_ZIP, &renamed];
+        assert_eq!(
+            zip_merge(&sources, DuplicatePolicy::Reject),
+            Err(Error::DuplicateEntry)
+        );
+
+        let zip = zip_merge(&sources, DuplicatePolicy::KeepFirst).unwrap();
+        let files = zip_open(&zip).unwrap();
+        assert_eq!(files.len(), 5);
+        assert_eq!(zip_read(&zip, &files, "file1").unwrap(), FILE1);
+
+        let zip = zip_merge(&sources, DuplicatePolicy::KeepLast).unwrap();
+        let files = zip_open(&zip).unwrap();
+        assert_eq!(files.len(), 5);
+        assert_eq!(files[0].name, "file1", "replaced in place");
+        assert_eq!(zip_read(&zip, &files, "file1").unwrap(), FIRST);
+        assert_eq!(zip_read(&zip, &files, "second").unwrap(), SECOND);
+        assert_eq!(zip_read(&zip, &files, "file3").unwrap(), FILE3);
+    }
+
+    #[test]
+    fn test_append() {
+        use miniz::zip_read::ZipArchive;
+        use miniz::zip_write::ZipAppender;
+
+        let archive = ZipArchive::from_slice(COMMENTS_ZIP).unwrap();
+        let mut appender = archive.append().unwrap();
+        assert_eq!(appender.len(), 4);
+        appender
+            .add_file("file2", b"replaced", &FileOptions::default())
+            .unwrap();
+        appender
+            .add_file("new.txt", &b"appended ".repeat(50), &FileOptions::default())
+            .unwrap();
+        appender
+            .add_directory("dir", &FileOptions::default())
+            .unwrap();
+        assert!(appender.remove("file3"));
+        assert!(!appender.remove("file3"));
+        assert_eq!(appender.len(), 5);
+        let zip = appender.finish().unwrap();
+
+        let files = zip_open(&zip).unwrap();
+        let names: Vec<_> = files.iter().map(|file| file.name.as_str()).collect();
+        assert_eq!(names, ["file1", "file2", "file4", "new.txt", "dir/"]);
+        assert_eq!(zip_read(&zip, &files, "file1").unwrap(), FILE1);
+        assert_eq!(zip_read(&zip, &files, "file2").unwrap(), b"replaced");
+        assert_eq!(zip_read(&zip, &files, "file4").unwrap(), FILE4);
+        assert_eq!(
+            zip_read(&zip, &files, "new.txt").unwrap(),
+            b"appended ".repeat(50)
+        );
+        assert!(files[4].is_dir());
+
+        // untouched entries are copied byte for byte, the archive comment is kept
+        let original = zip_open(COMMENTS_ZIP).unwrap();
+        let local = |data: &[u8], file: &miniz::zip_read::File| {
+            let name_len = u16::from_le_bytes([data[file.offset + 26], data[file.offset + 27]]);
+            let extra_len = u16::from_le_bytes([data[file.offset + 28], data[file.offset + 29]]);
+            let end = file.offset + 30 + (name_len + extra_len) as usize + file.compressed_size;
+            data[file.offset..end].to_vec()
+        };
+        assert_eq!(local(&zip, &files[0]), local(COMMENTS_ZIP, &original[0]));
+        assert_eq!(local(&zip, &files[2]), local(COMMENTS_ZIP, &original[3]));
+        let comment = |data: &[u8]| {
+            let eocd = data.windows(4).rposition(|w| w == b"PK\x05\x06").unwrap();
+            data[eocd + 20..].to_vec()
+        };
+        assert_eq!(comment(&zip), comment(COMMENTS_ZIP));
+        assert!(comment(&zip).ends_with(b"Longer.\r\n"));
+
+        // appending to the result again
+        let mut appender = ZipAppender::new(&zip).unwrap();
+        appender
+            .add_file("file1", b"again", &FileOptions::default())
+            .unwrap();
+        let zip = appender.finish().unwrap();
+        let files = zip_open(&zip).unwrap();
+        assert_eq!(files.len(), 5);
+        assert_eq!(zip_read(&zip, &files, "file1").unwrap(), b"again");
+        assert_eq!(zip_read(&zip, &files, "dir/").unwrap(), b"");
+
+        assert_eq!(
+            ZipAppender::new(&[0u8; 16]).err(),
+            Some(Error::NoCentralDirectory)
+        );
+    }
+
+    #[test]
+    fn test_zip_writer() {
+        let mut writer = ZipWriter::new();
+        let options = FileOptions {
+            unix_mode: Some(0o644),
+            ..Default::default()
+        };
+        writer.add_file("a.txt", b"hello", &options).unwrap();
+        writer
+            .add_directory("dir", &FileOptions::default())
+            .unwrap();
+        writer
+            .add_file("dir/b.bin", &SECOND, &FileOptions::default())
+            .unwrap();
+        assert_eq!(writer.len(), 3);
+        let zip = writer.finish().unwrap();
+
+        let entries = miniz::zip_read::zip_entries(&zip).unwrap();
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["a.txt", "dir/", "dir/b.bin"]);
+        assert_eq!(entries[0].external_attributes, 0o100644 << 16);
+        assert_eq!(entries[1].external_attributes, 0x10, "MS-DOS directory");
+        assert_eq!(entries[2].dos_time, DOS_EPOCH);
+
+        let files = zip_open(&zip).unwrap();
+        assert_eq!(zip_read(&zip, &files, "a.txt").unwrap(), b"hello");
+        assert_eq!(zip_read(&zip, &files, "dir/b.bin").unwrap(), SECOND);
+        assert_eq!(ZipWriter::new().finish().unwrap().len(), 22);
+    }
+
+    #[test]
+    fn test_compression() {
+        let text = b"the quick brown fox jumps over the lazy dog. ".repeat(100);
+        let mut state = 0x2545_f491u32;
+        let noise: Vec<u8> = (0..4096)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+
+        let mut writer = ZipWriter::new();
+        for level in [0, 1, 6, 9] {
+            let options = FileOptions {
+                level,
+                ..Default::default()
+            };
+            writer
+                .add_file(&format!("text{level}"), &text, &options)
+                .unwrap();
+        }
+        let options = FileOptions::default();
+        writer.add_file("noise", &noise, &options).unwrap();
+        let zip = writer.finish().unwrap();
+
+        // stored at level 0 and for incompressible data, deflated otherwise
+        let entries = miniz::zip_read::zip_entries(&zip).unwrap();
+        let methods: Vec<u16> = entries.iter().map(|e| e.method).collect();
+        assert_eq!(methods, [0, 8, 8, 8, 0]);
+        assert!(entries[1].compressed_size * 10 < text.len());
+        assert_eq!(entries[4].compressed_size, noise.len());
+
+        let files = zip_open(&zip).unwrap();
+        for entry in &entries[..4] {
+            assert_eq!(zip_read(&zip, &files, &entry.name).unwrap(), text);
+        }
+        assert_eq!(zip_read(&zip, &files, "noise").unwrap(), noise);
+    }
+
+    #[test]
+    fn test_extra_fields() {
+        let build = ExtraField {
+            header_id: 0xcafe,
+            data: b"build 42",
+        };
+        let signing = ExtraField {
+            header_id: 0xd935,
+            data: &[0; 4],
+        };
+        let options = FileOptions {
+            level: 0,
+            local_extra: &[build, signing],
+            central_extra: &[build],
+            ..Default::default()
+        };
+        let mut writer = ZipWriter::new();
+        writer.add_file("a.txt", b"hello", &options).unwrap();
+        let zip = writer.finish().unwrap();
+
+        // local header: extra field length at 28, extra data after the 5 byte name
+        assert_eq!(zip[28..30], 20u16.to_le_bytes());
+        assert_eq!(zip[35..39], [0xfe, 0xca, 8, 0]);
+        assert_eq!(&zip[39..47], b"build 42");
+        assert_eq!(zip[47..51], [0x35, 0xd9, 4, 0]);
+        assert_eq!(&zip[55..60], b"hello");
+
+        // central directory record: extra field length at 30, extra data after the name
+        let cd = &zip[60..];
+        assert_eq!(cd[30..32], 12u16.to_le_bytes());
+        assert_eq!(cd[51..55], [0xfe, 0xca, 8, 0]);
+        assert_eq!(&cd[55..63], b"build 42");
+
+        let files = zip_open(&zip).unwrap();
+        assert_eq!(zip_read(&zip, &files, "a.txt").unwrap(), b"hello");
+
+        let zip64 = ExtraField {
+            header_id: 1,
+            data: &[],
+        };
+        let options = FileOptions {
+            central_extra: &[zip64],
+            ..Default::default()
+        };
+        assert_eq!(
+            ZipWriter::new().add_file("a", b"", &options),
+            Err(Error::InvalidZip)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_add_dir_recursive() {
+        use miniz::zip_write::DirOptions;
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let dir = std::env::temp_dir().join("miniz_test_add_dir_recursive");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("sub/deep")).unwrap();
+        std::fs::create_dir_all(dir.join("target")).unwrap();
+        std::fs::write(dir.join("a.txt"), b"a").unwrap();
+        std::fs::write(dir.join("sub/b.txt"), b"b").unwrap();
+        std::fs::write(dir.join("sub/deep/c.txt"), b"c").unwrap();
+        std::fs::write(dir.join("sub/skip.log"), b"log").unwrap();
+        std::fs::write(dir.join("target/out.txt"), b"out").unwrap();
+
+        // 2024-02-29 12:34:56 UTC
+        let mtime = UNIX_EPOCH + Duration::from_secs(1709210096);
+        let file = std::fs::File::options()
+            .write(true)
+            .open(dir.join("sub/b.txt"))
+            .unwrap();
+        file.set_modified(mtime).unwrap();
+        drop(file);
+
+        let mut writer = ZipWriter::new();
+        let options = DirOptions {
+            include: &["*.txt"],
+            exclude: &["target", "*/deep/c.*"],
+            ..Default::default()
+        };
+        assert_eq!(writer.add_dir_recursive(&dir, &options).unwrap(), 2);
+        let zip = writer.finish().unwrap();
+
+        let entries = miniz::zip_read::zip_entries(&zip).unwrap();
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["a.txt", "sub/", "sub/b.txt", "sub/deep/"]);
+        assert_eq!(entries[2].dos_time, 0x585d645c);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let perms = std::fs::Permissions::from_mode(0o750);
+            std::fs::set_permissions(dir.join("a.txt"), perms).unwrap();
+            let mut writer = ZipWriter::new();
+            writer.add_dir_recursive(&dir, &options).unwrap();
+            let entries = miniz::zip_read::zip_entries(&writer.finish().unwrap()).unwrap();
+            assert_eq!(entries[0].external_attributes >> 16, 0o100750);
+            assert_eq!(entries[1].external_attributes & 0x10, 0x10);
+        }
+
+        let files = zip_open(&zip).unwrap();
+        assert_eq!(zip_read(&zip, &files, "sub/b.txt").unwrap(), b"b");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_add_file_parallel() {
+        let text = b"the quick brown fox jumps over the lazy dog. ".repeat(8000);
+        let batch = miniz::BatchOptions {
+            threads: 3,
+            ..Default::default()
+        };
+        let mut writer = ZipWriter::new();
+        for level in [0, 6] {
+            let options = FileOptions {
+                level,
+                ..Default::default()
+            };
+            writer
+                .add_file_parallel(&format!("text{level}"), &text, &options, &batch)
+                .unwrap();
+        }
+        let zip = writer.finish().unwrap();
+
+        let entries = miniz::zip_read::zip_entries(&zip).unwrap();
+        let methods: Vec<u16> = entries.iter().map(|e| e.method).collect();
+        assert_eq!(methods, [0, 8]);
+        assert!(entries[1].compressed_size * 10 < text.len());
+        let files = zip_open(&zip).unwrap();
+        for entry in &entries {
+            assert_eq!(zip_read(&zip, &files, &entry.name).unwrap(), text);
+        }
+    }
+
+    #[test]
+    fn test_comments() {
+        use miniz::zip_read::{zip_comment, zip_entries, ZipArchive};
+
+        let mut writer = ZipWriter::new();
+        writer.set_comment(b"build 1234, commit abcdef").unwrap();
+        let options = FileOptions {
+            comment: "generated, do not edit",
+            ..Default::default()
+        };
+        writer.add_file("a.txt", b"a", &options).unwrap();
+        writer
+            .add_file("b.txt", b"b", &FileOptions::default())
+            .unwrap();
+        assert_eq!(writer.set_comment(&[0; 65536]), Err(Error::InvalidZip));
+        let zip = writer.finish().unwrap();
+
+        assert_eq!(zip_comment(&zip).unwrap(), b"build 1234, commit abcdef");
+        let entries = zip_entries(&zip).unwrap();
+        assert_eq!(entries[0].comment, "generated, do not edit");
+        assert_eq!(entries[1].comment, "");
+        assert_eq!(
+            zip_read(&zip, &zip_open(&zip).unwrap(), "b.txt").unwrap(),
+            b"b"
+        );
+
+        // the appender keeps or replaces the archive comment
+        let archive = ZipArchive::from_slice(&zip).unwrap();
+        let copy = archive.append().unwrap().finish().unwrap();
+        assert_eq!(zip_comment(&copy).unwrap(), b"build 1234, commit abcdef");
+        let mut appender = archive.append().unwrap();
+        appender.set_comment(b"build 1235");
+        let copy = appender.finish().unwrap();
+        assert_eq!(zip_comment(&copy).unwrap(), b"build 1235");
+        assert_eq!(
+            zip_entries(&copy).unwrap()[0].comment,
+            "generated, do not edit"
+        );
+    }
+}